@@ -32,8 +32,11 @@
 //! ([`AppView::resources`] etc.) return `None` before that phase has been
 //! reached, so the TUI can render partial progress.
 
+use std::fmt::Display;
+
 use lusid_view::{Fragment, Render, View, ViewTree};
 use serde::{Deserialize, Serialize};
+use termtree::Tree as TermTree;
 use thiserror::Error;
 
 /// Per-leaf progress marker, rendered with an emoji prefix:
@@ -58,6 +61,17 @@ impl Render for ViewNode {
     }
 }
 
+/// Same text as [`ViewNode::render`], without building a view.
+impl Display for ViewNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ViewNode::NotStarted => write!(f, "🟩"),
+            ViewNode::Started => write!(f, "⌛"),
+            ViewNode::Complete(view) => write!(f, "✅{view}"),
+        }
+    }
+}
+
 /// Arena entry. Branch children are indices into the containing
 /// [`FlatViewTree`]; leaves carry a [`ViewNode`] progress marker directly
 /// (not a [`View`]), since leaves are the nodes that advance through the
@@ -278,10 +292,16 @@ fn replace_view_tree_nodes(
 /// Protocol message from `lusid-apply` to the TUI. Each phase has a
 /// `*Start` / per-node / `*Complete` triple. The `Operations*` cluster at
 /// the end carries per-operation stdout/stderr streamed as work executes.
+/// [`AppUpdate::Error`] can arrive in any phase, as the last message before
+/// `lusid-apply` exits non-zero. [`AppUpdate::Warning`] can too, and leaves
+/// the [`AppView`] as it was.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum AppUpdate {
+    /// The planned tree, and the plan modules it was planned from.
     ResourceParams {
         resource_params: ViewTree,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        modules: Vec<ModuleVersion>,
     },
 
     ResourcesStart,
@@ -317,8 +337,13 @@ pub enum AppUpdate {
     },
     OperationsComplete,
 
+    /// `simulated` for `lusid-apply --check`: each operation's output is its
+    /// dry run, or a line saying what it would run, and nothing on the
+    /// machine changes.
     OperationsApplyStart {
         operations: Vec<Vec<View>>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        simulated: bool,
     },
     OperationApplyStart {
         index: (usize, usize),
@@ -331,11 +356,267 @@ pub enum AppUpdate {
         index: (usize, usize),
         stderr: String,
     },
+    /// How far along the operation is, for operations that report it, like
+    /// a git clone. Sent alongside its stderr, which it's parsed from.
+    OperationApplyProgress {
+        index: (usize, usize),
+        progress: OperationProgress,
+    },
     OperationApplyComplete {
         index: (usize, usize),
-        error: Option<String>,
+        result: OperationResult,
     },
     OperationsApplyComplete,
+
+    /// The pipeline failed.
+    Error {
+        error: ErrorEnvelope,
+    },
+
+    /// Something's wrong with the plan, but not enough to stop the apply,
+    /// e.g. a deprecated param.
+    Warning {
+        warning: WarningEnvelope,
+    },
+
+    /// An [`AppControl`] read from stdin has taken effect.
+    ControlApplied {
+        control: AppControl,
+    },
+}
+
+/// A plan module used by an apply: the root plan or one it includes, with
+/// the `name` and `version` the module declares. `plan` is relative to the
+/// project root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModuleVersion {
+    pub plan: String,
+    pub name: Option<String>,
+    pub version: Option<String>,
+}
+
+/// A fatal `lusid-apply` error, for machines and humans alike. Sent as
+/// [`AppUpdate::Error`] on stdout, and printed as a single JSON line on stderr
+/// with `--error-format json`.
+///
+/// `code` is stable (see "Error codes" in the README), so wrappers can branch
+/// on it; `message` is the one-line error message; `rendered` is the message
+/// formatted for humans, possibly over several lines (e.g. a plan source
+/// excerpt with a caret under the offending value).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorEnvelope {
+    pub code: String,
+    pub message: String,
+    pub rendered: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<ErrorSpan>,
+}
+
+/// A non-fatal problem found by `lusid-apply`, sent as [`AppUpdate::Warning`].
+/// Fields are as for [`ErrorEnvelope`], with `rendered` headed `warning:`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WarningEnvelope {
+    pub code: String,
+    pub message: String,
+    pub rendered: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<ErrorSpan>,
+}
+
+/// Where in a plan source file an error points. `start` / `end` are char
+/// (not byte) offsets into the file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorSpan {
+    pub path: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Protocol message from the TUI to `lusid-apply`, the other way from
+/// [`AppUpdate`]: newline-delimited JSON on `lusid-apply`'s stdin, read only
+/// when it runs with `--control`.
+///
+/// [`SetLog`](AppControl::SetLog) takes effect as soon as it's read. The
+/// rest are taken up between operations, never interrupting one, and each is
+/// acknowledged with [`AppUpdate::ControlApplied`] once it has been; any
+/// sent before the operations start wait for the first one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AppControl {
+    /// Replace the tracing filter, e.g. `"debug"` or `"lusid_apply=trace,info"`.
+    SetLog {
+        filter: String,
+    },
+
+    /// Hold off on the next operation until [`AppControl::Resume`]. Closing
+    /// stdin while paused cancels the apply, as nothing could resume it.
+    Pause,
+    Resume,
+
+    /// Stop the apply before its next operation. It exits non-zero, with an
+    /// [`AppUpdate::Error`] coded `apply.cancelled`.
+    Cancel,
+
+    /// Run at most `max` downloads at once from the next operation on, or
+    /// any number with `None` (see `--max-parallel-downloads`).
+    SetMaxParallelDownloads {
+        max: Option<usize>,
+    },
+}
+
+/// A machine's evaluated plan as a single self-contained document, printed
+/// by `lusid-apply --render` rather than streamed as [`AppUpdate`]s. Meant to
+/// be committed and diffed between releases, so it holds no run-specific
+/// data: `plan` is relative to the project root, and resources are keyed by
+/// their [`RenderedResource::id`] path rather than by internal node ids.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenderedPlan {
+    pub plan: String,
+    pub resources: Vec<RenderedResource>,
+}
+
+/// One plan item, with its resolved params (as JSON) and the atoms they
+/// expand to (as their one-line `Display` form), secrets redacted.
+///
+/// `id` is the `/`-separated path of plan item ids from the root plan down
+/// to this item, with `#<n>` (its position among its siblings) standing in
+/// for items without an `id`. `requires` lists the ids of the items this one
+/// is ordered after.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenderedResource {
+    pub id: String,
+    pub params: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<String>,
+    pub atoms: Vec<String>,
+}
+
+/// The part of a machine's plan cloud-init can express, printed by
+/// `lusid-apply --render-cloud-init` for `lusid render cloud-init` to turn
+/// into a user-data document. `config` is the cloud-config itself, its keys
+/// as cloud-init names them; `skipped` lists each resource atom (as its
+/// `Display` form) left out of it, or only partly expressed, and why.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenderedCloudInit {
+    pub config: CloudConfig,
+    pub skipped: Vec<CloudInitSkipped>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CloudConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<CloudGroup>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub users: Vec<CloudUser>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub write_files: Vec<CloudWriteFile>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub packages: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub runcmd: Vec<String>,
+}
+
+/// A `groups` entry: a group's name alone, or mapped to the users to add to
+/// it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum CloudGroup {
+    Name(String),
+    Members(std::collections::BTreeMap<String, Vec<String>>),
+}
+
+/// A `users` entry. `Default` is cloud-init's `default`, the image's own
+/// user, which a `users` list drops unless it names it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum CloudUser {
+    Default(String),
+    User {
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        uid: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        primary_group: Option<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        groups: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        gecos: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        homedir: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        shell: Option<String>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        system: bool,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        no_create_home: bool,
+    },
+}
+
+/// A `write_files` entry. `defer` holds it back to cloud-init's final
+/// stage, after `users` has created its owner.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CloudWriteFile {
+    pub path: String,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub permissions: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub defer: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CloudInitSkipped {
+    pub resource: String,
+    pub reason: String,
+}
+
+/// How far along a running operation is, sent with
+/// [`AppUpdate::OperationApplyProgress`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OperationProgress {
+    /// What it's doing, e.g. git's `Receiving objects`.
+    pub stage: String,
+    /// From 0 to 100.
+    pub percent: u8,
+}
+
+/// How an operation ended, sent with [`AppUpdate::OperationApplyComplete`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OperationResult {
+    /// Why the operation failed, or `None` if it succeeded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The failure's stable code, as in [`ErrorEnvelope::code`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    /// Exit code of the command that failed, if the operation ran one and it
+    /// exited unsuccessfully (rather than being killed by a signal).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// The last lines the operation wrote to stdout, at most
+    /// [`OperationResult::STDOUT_TAIL_LINES`], redacted like the stream.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stdout_tail: Vec<String>,
+    /// The last lines the operation wrote to stderr, at most
+    /// [`OperationResult::STDERR_TAIL_LINES`], redacted like the stream.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stderr_tail: Vec<String>,
+    /// How long the operation ran for, from starting it to its output ending.
+    pub duration_ms: u64,
+    /// How many times the operation was retried after failing, under its
+    /// plan item's retry policy.
+    #[serde(default)]
+    pub retries: u32,
+}
+
+impl OperationResult {
+    pub const STDOUT_TAIL_LINES: usize = 20;
+    pub const STDERR_TAIL_LINES: usize = 20;
+
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
 }
 
 /// One operation's live state during the apply phase. `stdout`/`stderr` are
@@ -346,8 +627,11 @@ pub struct OperationView {
     pub label: View,
     pub stdout: String,
     pub stderr: String,
-    pub is_complete: bool,
-    pub error: Option<String>,
+    /// The latest progress it reported, if it reports any.
+    #[serde(default)]
+    pub progress: Option<OperationProgress>,
+    /// How it ended, once it has.
+    pub result: Option<OperationResult>,
 }
 
 impl OperationView {
@@ -356,10 +640,19 @@ impl OperationView {
             label,
             stdout: String::new(),
             stderr: String::new(),
-            is_complete: false,
-            error: None,
+            progress: None,
+            result: None,
         }
     }
+
+    pub fn is_complete(&self) -> bool {
+        self.result.is_some()
+    }
+
+    /// Why it failed, if it has.
+    pub fn error(&self) -> Option<&str> {
+        self.result.as_ref()?.error.as_deref()
+    }
 }
 
 /// TUI state. Each variant carries everything from the prior phases plus the
@@ -409,6 +702,7 @@ pub enum AppView {
         has_changes: Option<bool>,
         operations_tree: FlatViewTree,
         operations_epochs: Vec<Vec<OperationView>>,
+        simulated: bool,
     },
     Done {
         resource_params: FlatViewTree,
@@ -418,6 +712,7 @@ pub enum AppView {
         has_changes: Option<bool>,
         operations_tree: FlatViewTree,
         operations_epochs: Vec<Vec<OperationView>>,
+        simulated: bool,
     },
 }
 
@@ -442,7 +737,12 @@ impl AppView {
         use AppUpdate::*;
         match (self, update) {
             // Phase: Start -> ResourceParams
-            (AppView::Start, ResourceParams { resource_params }) => Ok(AppView::ResourceParams {
+            (
+                AppView::Start,
+                ResourceParams {
+                    resource_params, ..
+                },
+            ) => Ok(AppView::ResourceParams {
                 resource_params: FlatViewTree::from_view_tree_completed(resource_params),
             }),
 
@@ -677,7 +977,10 @@ impl AppView {
                     has_changes,
                     operations_tree,
                 },
-                OperationsApplyStart { operations },
+                OperationsApplyStart {
+                    operations,
+                    simulated,
+                },
             ) => {
                 let epochs = operations
                     .into_iter()
@@ -691,6 +994,7 @@ impl AppView {
                     has_changes,
                     operations_tree,
                     operations_epochs: epochs,
+                    simulated,
                 })
             }
 
@@ -704,6 +1008,7 @@ impl AppView {
                     has_changes,
                     operations_tree,
                     mut operations_epochs,
+                    simulated,
                 },
                 OperationApplyStart { index: (e, o) },
             ) => {
@@ -715,7 +1020,8 @@ impl AppView {
                     .ok_or(AppViewError::OperationIndexOutOfBounds(e, o))?;
                 op.stdout.clear();
                 op.stderr.clear();
-                op.is_complete = false;
+                op.progress = None;
+                op.result = None;
                 Ok(AppView::OperationsApply {
                     resource_params,
                     resources,
@@ -724,6 +1030,7 @@ impl AppView {
                     has_changes,
                     operations_tree,
                     operations_epochs,
+                    simulated,
                 })
             }
             (
@@ -735,6 +1042,7 @@ impl AppView {
                     has_changes,
                     operations_tree,
                     mut operations_epochs,
+                    simulated,
                 },
                 OperationApplyStdout {
                     index: (e, o),
@@ -757,6 +1065,7 @@ impl AppView {
                     has_changes,
                     operations_tree,
                     operations_epochs,
+                    simulated,
                 })
             }
             (
@@ -768,6 +1077,7 @@ impl AppView {
                     has_changes,
                     operations_tree,
                     mut operations_epochs,
+                    simulated,
                 },
                 OperationApplyStderr {
                     index: (e, o),
@@ -794,6 +1104,41 @@ impl AppView {
                     has_changes,
                     operations_tree,
                     operations_epochs,
+                    simulated,
+                })
+            }
+            (
+                AppView::OperationsApply {
+                    resource_params,
+                    resources,
+                    resource_states,
+                    resource_changes,
+                    has_changes,
+                    operations_tree,
+                    mut operations_epochs,
+                    simulated,
+                },
+                OperationApplyProgress {
+                    index: (e, o),
+                    progress,
+                },
+            ) => {
+                let epoch = operations_epochs
+                    .get_mut(e)
+                    .ok_or(AppViewError::OperationIndexOutOfBounds(e, o))?;
+                let op = epoch
+                    .get_mut(o)
+                    .ok_or(AppViewError::OperationIndexOutOfBounds(e, o))?;
+                op.progress = Some(progress);
+                Ok(AppView::OperationsApply {
+                    resource_params,
+                    resources,
+                    resource_states,
+                    resource_changes,
+                    has_changes,
+                    operations_tree,
+                    operations_epochs,
+                    simulated,
                 })
             }
             (
@@ -805,10 +1150,11 @@ impl AppView {
                     has_changes,
                     operations_tree,
                     mut operations_epochs,
+                    simulated,
                 },
                 OperationApplyComplete {
                     index: (e, o),
-                    error,
+                    result,
                 },
             ) => {
                 let epoch = operations_epochs
@@ -817,8 +1163,7 @@ impl AppView {
                 let op = epoch
                     .get_mut(o)
                     .ok_or(AppViewError::OperationIndexOutOfBounds(e, o))?;
-                op.is_complete = true;
-                op.error = error;
+                op.result = Some(result);
                 Ok(AppView::OperationsApply {
                     resource_params,
                     resources,
@@ -827,6 +1172,7 @@ impl AppView {
                     has_changes,
                     operations_tree,
                     operations_epochs,
+                    simulated,
                 })
             }
             (
@@ -838,6 +1184,7 @@ impl AppView {
                     has_changes,
                     operations_tree,
                     operations_epochs,
+                    simulated,
                 },
                 OperationsApplyComplete,
             ) => Ok(AppView::Done {
@@ -848,8 +1195,17 @@ impl AppView {
                 has_changes,
                 operations_tree,
                 operations_epochs,
+                simulated,
             }),
 
+            // A failure doesn't change phase: the view keeps whatever was
+            // reached, and the caller surfaces the error on its own.
+            (state, Error { .. }) => Ok(state),
+
+            (state, Warning { .. }) => Ok(state),
+
+            (state, ControlApplied { .. }) => Ok(state),
+
             (state, update) => Err(AppViewError::InvalidTransition {
                 from: format!("{state:?}"),
                 update: format!("{update:?}"),
@@ -971,6 +1327,16 @@ impl AppView {
             } => Some(operations_epochs),
         }
     }
+
+    /// Whether the operations were only simulated, by `lusid-apply --check`.
+    pub fn simulated(&self) -> bool {
+        match self {
+            AppView::OperationsApply { simulated, .. } | AppView::Done { simulated, .. } => {
+                *simulated
+            }
+            _ => false,
+        }
+    }
 }
 
 /// Lenient conversion to nested ViewTree:
@@ -1006,12 +1372,53 @@ impl From<FlatViewTree> for Option<ViewTree> {
     }
 }
 
-impl std::fmt::Display for FlatViewTree {
+/// Prints as the [`ViewTree`] it converts to, but borrows its nodes rather
+/// than cloning the whole tree to convert it.
+impl Display for FlatViewTree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut seen = vec![false; self.nodes.len()];
+        match self.term_tree(Self::root_index(), &mut seen) {
+            Some(tree) => tree.fmt(f),
+            None => write!(f, "<empty>"),
+        }
+    }
+}
+
+/// A [`FlatViewTree`] node's label, borrowed for printing.
+enum NodeLabel<'a> {
+    Branch(&'a View),
+    Leaf(&'a ViewNode),
+}
+
+impl Display for NodeLabel<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if let Some(tree) = Option::<ViewTree>::from(self.clone()) {
-            tree.fmt(f)
-        } else {
-            write!(f, "<empty>")
+        match self {
+            NodeLabel::Branch(view) => view.fmt(f),
+            NodeLabel::Leaf(node) => node.fmt(f),
+        }
+    }
+}
+
+impl FlatViewTree {
+    /// The subtree at `index` as a [`TermTree`], leniently as in the
+    /// [`ViewTree`] conversion: missing nodes and branches left with no
+    /// children are skipped, and each node is visited at most once.
+    fn term_tree(&self, index: usize, seen: &mut [bool]) -> Option<TermTree<NodeLabel<'_>>> {
+        if std::mem::replace(seen.get_mut(index)?, true) {
+            return None;
+        }
+        match self.nodes[index].as_ref()? {
+            FlatViewTreeNode::Leaf { view } => Some(TermTree::new(NodeLabel::Leaf(view))),
+            FlatViewTreeNode::Branch { view, children } => {
+                let children: Vec<_> = children
+                    .iter()
+                    .filter_map(|child| self.term_tree(*child, seen))
+                    .collect();
+                if children.is_empty() {
+                    return None;
+                }
+                Some(TermTree::new(NodeLabel::Branch(view)).with_leaves(children))
+            }
         }
     }
 }
@@ -0,0 +1,30 @@
+//! Folding a whole apply's [`AppUpdate`](lusid_apply_stdio::AppUpdate)s into
+//! an [`AppView`](lusid_apply_stdio::AppView), and printing its trees.
+
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
+use lusid_bench::{fold, plan, shapes, updates};
+
+const LEAVES: usize = 10_000;
+
+fn app_view(c: &mut Criterion) {
+    let mut group = c.benchmark_group("app_view");
+    group.sample_size(20);
+
+    for (name, shape) in shapes(LEAVES) {
+        let updates = updates(&plan(&shape));
+        let view = fold(updates.clone()).expect("fold");
+        let resources = view.resources().expect("resources");
+
+        group.bench_function(BenchmarkId::new("fold", name), |b| {
+            b.iter_batched(|| updates.clone(), fold, BatchSize::LargeInput)
+        });
+        group.bench_function(BenchmarkId::new("display", name), |b| {
+            b.iter(|| resources.to_string())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, app_view);
+criterion_main!(benches);
@@ -0,0 +1,24 @@
+//! [`compute_epochs`] over wide, deep, and densely linked plans.
+
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
+use lusid_bench::{plan, shapes};
+use lusid_causality::compute_epochs;
+
+const LEAVES: usize = 10_000;
+
+fn epochs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compute_epochs");
+    group.sample_size(20);
+
+    for (name, shape) in shapes(LEAVES) {
+        let plan = plan(&shape);
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter_batched(|| plan.clone(), compute_epochs, BatchSize::LargeInput)
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, epochs);
+criterion_main!(benches);
@@ -0,0 +1,70 @@
+//! [`FlatTree`] conversions and the leaf maps `lusid-apply` runs per phase.
+
+use std::convert::Infallible;
+
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
+use lusid_bench::{Node, Plan, plan, shapes};
+use lusid_causality::CausalityMeta;
+use lusid_tree::{FlatTree, Tree};
+
+const LEAVES: usize = 10_000;
+
+type Flat = FlatTree<Option<Node>, CausalityMeta<String>>;
+
+fn flat_tree(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("runtime");
+    let mut group = c.benchmark_group("flat_tree");
+    group.sample_size(20);
+
+    for (name, shape) in shapes(LEAVES) {
+        let plan = plan(&shape);
+        let flat = Flat::from(plan.clone());
+
+        group.bench_function(BenchmarkId::new("from_tree", name), |b| {
+            b.iter_batched(|| plan.clone(), Flat::from, BatchSize::LargeInput)
+        });
+        group.bench_function(BenchmarkId::new("into_tree", name), |b| {
+            b.iter_batched(|| flat.clone(), Plan::from, BatchSize::LargeInput)
+        });
+        group.bench_function(BenchmarkId::new("map", name), |b| {
+            b.iter_batched(
+                || flat.clone(),
+                |flat| {
+                    runtime.block_on(flat.map(
+                        |node| node.map(|node| node.len()),
+                        |_, _| async { Ok::<_, Infallible>(()) },
+                    ))
+                },
+                BatchSize::LargeInput,
+            )
+        });
+        // Each leaf becomes a branch of two, as a resource expands to atoms.
+        group.bench_function(BenchmarkId::new("map_tree", name), |b| {
+            b.iter_batched(
+                || flat.clone(),
+                |flat| {
+                    runtime.block_on(flat.map_tree(
+                        |node, meta| {
+                            Tree::branch(
+                                meta,
+                                [
+                                    Tree::leaf(CausalityMeta::default(), node.clone()),
+                                    Tree::leaf(CausalityMeta::default(), node),
+                                ],
+                            )
+                        },
+                        |_, _| async { Ok::<_, Infallible>(()) },
+                    ))
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, flat_tree);
+criterion_main!(benches);
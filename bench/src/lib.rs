@@ -0,0 +1,275 @@
+//! Synthetic plans for benchmarking the apply pipeline's data structures:
+//! [`FlatTree`] maps, [`compute_epochs`], and folding [`AppUpdate`]s into an
+//! [`AppView`]. The benchmarks themselves are in `benches/`, run with
+//! `cargo bench -p lusid-bench`.
+//!
+//! A plan is generated from a [`Shape`], deterministically from its `seed`, so
+//! runs before and after a change measure the same tree. Leaves are labels
+//! standing in for resources or operations: the structures under test only
+//! move nodes around, so what's in them matters less than how many there are
+//! and how they're nested and linked.
+
+use lusid_apply_stdio::{AppUpdate, AppView, AppViewError, OperationResult};
+use lusid_causality::{CausalityMeta, CausalityTree, compute_epochs};
+use lusid_tree::{FlatTree, FlatTreeNode, Tree};
+use lusid_view::{Render, ViewTree};
+
+/// A synthetic plan's leaf.
+pub type Node = String;
+
+/// A synthetic plan: leaves wrapped in `Some`, as [`compute_epochs`] takes
+/// them, each with an id `leaf-<n>`.
+pub type Plan = CausalityTree<Option<Node>>;
+
+/// How a synthetic plan is laid out.
+#[derive(Debug, Clone, Copy)]
+pub struct Shape {
+    /// Number of leaves.
+    pub leaves: usize,
+    /// Leaves per branch.
+    pub fanout: usize,
+    /// Whether each branch is nested in the one before, rather than all
+    /// being children of the root.
+    pub nested: bool,
+    /// How many earlier leaves each leaf requires.
+    pub requires: usize,
+    /// Seed for choosing which leaves are required.
+    pub seed: u64,
+}
+
+impl Shape {
+    /// Branches of 100 leaves under the root, with no requires.
+    pub fn wide(leaves: usize) -> Self {
+        Self {
+            leaves,
+            fanout: 100,
+            nested: false,
+            requires: 0,
+            seed: 0,
+        }
+    }
+
+    /// Branches of 20 leaves, each nested in the one before, so the tree is
+    /// `leaves / 20` levels deep.
+    pub fn deep(leaves: usize) -> Self {
+        Self {
+            fanout: 20,
+            nested: true,
+            ..Self::wide(leaves)
+        }
+    }
+
+    /// As [`wide`](Self::wide), with each leaf requiring `requires` earlier
+    /// leaves, picked at random.
+    pub fn dense(leaves: usize, requires: usize) -> Self {
+        Self {
+            requires,
+            ..Self::wide(leaves)
+        }
+    }
+}
+
+/// The shapes each benchmark runs against, by name.
+pub fn shapes(leaves: usize) -> [(&'static str, Shape); 3] {
+    [
+        ("wide", Shape::wide(leaves)),
+        ("deep", Shape::deep(leaves)),
+        ("dense", Shape::dense(leaves, 8)),
+    ]
+}
+
+/// Generate a plan of `shape`.
+pub fn plan(shape: &Shape) -> Plan {
+    let mut rng = SplitMix64(shape.seed);
+    let leaves: Vec<Plan> = (0..shape.leaves)
+        .map(|index| leaf(index, shape.requires, &mut rng))
+        .collect();
+    let mut groups: Vec<Vec<Plan>> = Vec::new();
+    let mut leaves = leaves.into_iter().peekable();
+    while leaves.peek().is_some() {
+        groups.push(leaves.by_ref().take(shape.fanout.max(1)).collect());
+    }
+
+    if shape.nested {
+        let nested = groups.into_iter().rev().fold(None, |inner, mut children| {
+            children.extend(inner);
+            Some(Tree::branch(CausalityMeta::default(), children))
+        });
+        nested.unwrap_or_else(|| Tree::branch(CausalityMeta::default(), []))
+    } else {
+        Tree::branch(
+            CausalityMeta::default(),
+            groups
+                .into_iter()
+                .map(|children| Tree::branch(CausalityMeta::default(), children)),
+        )
+    }
+}
+
+fn leaf(index: usize, requires: usize, rng: &mut SplitMix64) -> Plan {
+    let mut required: Vec<usize> = (0..requires.min(index)).map(|_| rng.below(index)).collect();
+    required.sort_unstable();
+    required.dedup();
+    Tree::leaf(
+        CausalityMeta {
+            id: Some(leaf_id(index)),
+            requires: required.into_iter().map(leaf_id).collect(),
+            required_by: vec![],
+        },
+        Some(leaf_id(index)),
+    )
+}
+
+fn leaf_id(index: usize) -> String {
+    format!("leaf-{index}")
+}
+
+/// `plan` as a [`ViewTree`], as `lusid-apply` renders the planned tree.
+pub fn view_tree(plan: &Plan) -> ViewTree {
+    match plan {
+        Tree::Branch { meta, children } => ViewTree::Branch {
+            view: meta
+                .id
+                .as_ref()
+                .map(|id| id.render())
+                .unwrap_or(".".render()),
+            children: children.iter().map(view_tree).collect(),
+        },
+        Tree::Leaf { node, .. } => ViewTree::Leaf {
+            view: node.render(),
+        },
+    }
+}
+
+/// The updates `lusid-apply` would stream for applying `plan`, from the
+/// planned tree to the last operation: one update per leaf per phase, every
+/// leaf changed, and each operation printing a line.
+///
+/// # Panics
+///
+/// If `plan` has a dependency cycle, which a [`Shape`] never does.
+pub fn updates(plan: &Plan) -> Vec<AppUpdate> {
+    let leaves: Vec<usize> = FlatTree::from(plan.clone())
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, node)| {
+            matches!(node, Some(FlatTreeNode::Leaf { .. })).then_some(index)
+        })
+        .collect();
+    let label = |index: usize, phase: &str| format!("{phase} {index}").render();
+
+    let mut updates = vec![
+        AppUpdate::ResourceParams {
+            resource_params: view_tree(plan),
+            modules: vec![],
+        },
+        AppUpdate::ResourcesStart,
+    ];
+    updates.extend(leaves.iter().map(|&index| AppUpdate::ResourcesNode {
+        index,
+        tree: ViewTree::Leaf {
+            view: label(index, "resource"),
+        },
+    }));
+    updates.push(AppUpdate::ResourcesComplete);
+
+    updates.push(AppUpdate::ResourceStatesStart);
+    for &index in &leaves {
+        updates.push(AppUpdate::ResourceStatesNodeStart { index });
+        updates.push(AppUpdate::ResourceStatesNodeComplete {
+            index,
+            node: label(index, "state"),
+        });
+    }
+    updates.push(AppUpdate::ResourceStatesComplete);
+
+    updates.push(AppUpdate::ResourceChangesStart);
+    updates.extend(leaves.iter().map(|&index| AppUpdate::ResourceChangesNode {
+        index,
+        node: Some(label(index, "change")),
+    }));
+    updates.push(AppUpdate::ResourceChangesComplete { has_changes: true });
+
+    updates.push(AppUpdate::OperationsStart);
+    updates.extend(leaves.iter().map(|&index| AppUpdate::OperationsNode {
+        index,
+        operations: ViewTree::Leaf {
+            view: label(index, "operation"),
+        },
+    }));
+    updates.push(AppUpdate::OperationsComplete);
+
+    let epochs = compute_epochs(plan.clone()).expect("synthetic plans are acyclic");
+    updates.push(AppUpdate::OperationsApplyStart {
+        operations: epochs
+            .iter()
+            .map(|epoch| epoch.iter().map(Render::render).collect())
+            .collect(),
+        simulated: false,
+    });
+    for (epoch_index, epoch) in epochs.iter().enumerate() {
+        for operation_index in 0..epoch.len() {
+            let index = (epoch_index, operation_index);
+            updates.push(AppUpdate::OperationApplyStart { index });
+            updates.push(AppUpdate::OperationApplyStdout {
+                index,
+                stdout: "ok".into(),
+            });
+            updates.push(AppUpdate::OperationApplyComplete {
+                index,
+                result: OperationResult::default(),
+            });
+        }
+    }
+    updates.push(AppUpdate::OperationsApplyComplete);
+    updates
+}
+
+/// Fold `updates` into a fresh [`AppView`], as the TUI does.
+pub fn fold(updates: impl IntoIterator<Item = AppUpdate>) -> Result<AppView, AppViewError> {
+    updates
+        .into_iter()
+        .try_fold(AppView::default(), AppView::update)
+}
+
+/// SplitMix64: small and fast, and good enough to scatter requires edges.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..bound`. `bound` must be nonzero.
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shapes_have_their_leaves_and_schedule() {
+        for (name, shape) in shapes(1_000) {
+            let plan = plan(&shape);
+            let epochs = compute_epochs(plan.clone()).expect(name);
+            assert_eq!(epochs.iter().map(Vec::len).sum::<usize>(), 1_000, "{name}");
+            if shape.requires == 0 {
+                assert_eq!(epochs.len(), 1, "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn updates_fold_to_done() {
+        let plan = plan(&Shape::dense(500, 4));
+        let view = fold(updates(&plan)).expect("fold");
+        assert!(matches!(view, AppView::Done { .. }));
+    }
+}
@@ -22,6 +22,18 @@ pub enum EpochError<NodeId> {
     CycleDetected { remaining: usize },
 }
 
+impl<NodeId> EpochError<NodeId> {
+    /// Stable, machine-readable code for this failure.
+    pub fn code(&self) -> &'static str {
+        match self {
+            EpochError::DuplicateId(_) => "causality.duplicate-id",
+            EpochError::UnknownRequiresRef(_) => "causality.unknown-requires",
+            EpochError::UnknownRequiredByRef(_) => "causality.unknown-required-by",
+            EpochError::CycleDetected { .. } => "causality.cycle",
+        }
+    }
+}
+
 /// Flatten a causality tree into topologically-sorted dependency layers (epochs).
 ///
 /// Uses Kahn's algorithm. Each returned epoch is a `Vec<Node>` whose members have no
@@ -33,6 +45,11 @@ pub enum EpochError<NodeId> {
 /// - Leaves wrapped in `None` are carried through the dependency graph (for their id
 ///   references) but dropped from the output — useful as pure "marker" nodes.
 /// - Returns an empty epoch-free output if the input tree has no leaves.
+/// - Deterministic: the same tree always yields the same epochs, and nodes
+///   within an epoch are in tree (pre-order) position, regardless of the
+///   order edges released them in. Node ids aren't used as the sort key
+///   because many leaves have none, and sub-item ids are freshly scoped on
+///   every plan.
 ///
 /// # Errors
 ///
@@ -210,7 +227,8 @@ where
     let mut indegree_mut = indegree;
 
     while !queue.is_empty() {
-        let current_wave: Vec<usize> = queue.drain(..).collect();
+        let mut current_wave: Vec<usize> = queue.drain(..).collect();
+        current_wave.sort_unstable();
         seen += current_wave.len();
 
         let mut specs: Vec<Node> = Vec::new();
@@ -246,3 +264,78 @@ where
 // Note(cc): branch-level `requires` inflates the edge count — a branch with k leaves
 // whose `requires: [X]` resolves to m leaves produces k * m edges. Fine in practice for
 // plan-sized inputs, but worth knowing before scaling this to huge trees.
+
+#[cfg(test)]
+mod tests {
+    use crate::Shuffler;
+
+    use super::*;
+
+    fn leaf(id: &str, requires: &[&str]) -> CausalityTree<Option<String>> {
+        CausalityTree::leaf(
+            CausalityMeta {
+                id: Some(id.to_string()),
+                requires: requires.iter().map(ToString::to_string).collect(),
+                required_by: vec![],
+            },
+            Some(id.to_string()),
+        )
+    }
+
+    #[test]
+    fn epochs_are_in_tree_order() {
+        // "q1" releases "r" before "q2" releases "p", but "p" comes first in
+        // the tree.
+        let tree = CausalityTree::branch(
+            CausalityMeta::default(),
+            [
+                leaf("p", &["q2"]),
+                leaf("r", &["q1"]),
+                leaf("q1", &[]),
+                leaf("q2", &[]),
+            ],
+        );
+
+        let epochs = compute_epochs(tree).unwrap();
+
+        assert_eq!(epochs, vec![vec!["q1", "q2"], vec!["p", "r"]]);
+    }
+
+    #[test]
+    fn epochs_are_independent_of_sibling_order() {
+        let tree = CausalityTree::branch(
+            CausalityMeta::default(),
+            [
+                leaf("a", &[]),
+                CausalityTree::branch(
+                    CausalityMeta {
+                        id: Some("group".to_string()),
+                        requires: vec!["a".to_string()],
+                        required_by: vec!["z".to_string()],
+                    },
+                    [leaf("b", &[]), leaf("c", &["b"]), leaf("d", &[])],
+                ),
+                leaf("e", &["c"]),
+                leaf("z", &[]),
+                leaf("f", &[]),
+            ],
+        );
+
+        let sorted = |epochs: Vec<Vec<String>>| -> Vec<Vec<String>> {
+            epochs
+                .into_iter()
+                .map(|mut epoch| {
+                    epoch.sort();
+                    epoch
+                })
+                .collect()
+        };
+        let expected = sorted(compute_epochs(tree.clone()).unwrap());
+
+        for seed in 0..64 {
+            let shuffled = Shuffler::new(seed).shuffle_tree(tree.clone());
+            let epochs = sorted(compute_epochs(shuffled).unwrap());
+            assert_eq!(epochs, expected, "seed {seed}");
+        }
+    }
+}
@@ -0,0 +1,672 @@
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Display, Write},
+    hash::Hash,
+};
+
+use thiserror::Error;
+
+use crate::{CausalityMeta, EpochError, compute_epochs, tree::CausalityTree};
+
+/// Render a causality tree as indented text, annotating every node with its
+/// `id` / `requires` / `required_by` edges and every leaf with the epoch
+/// [`compute_epochs`] resolved it into.
+///
+/// Meant for humans answering "why did this run when it did?": the epoch
+/// buckets alone show the final order, but not which edges produced it.
+/// `label` renders a leaf node; marker leaves (`None`) are shown as
+/// `(marker)` and have no epoch since they're dropped from the output.
+///
+/// Epoch numbers match the indices of the `Vec` returned by
+/// [`compute_epochs`] for the same tree.
+///
+/// # Errors
+///
+/// Same as [`compute_epochs`].
+pub fn explain_ordering<Node, NodeId, LabelFn>(
+    tree: &CausalityTree<Option<Node>, NodeId>,
+    label: LabelFn,
+) -> Result<String, EpochError<NodeId>>
+where
+    NodeId: Debug + Display + Clone + Eq + Hash,
+    LabelFn: Fn(&Node) -> String,
+{
+    // Schedule a shadow tree whose leaves are their own pre-order ordinal, so
+    // the epoch of each leaf can be looked up while walking the original.
+    let mut next_ordinal = 0usize;
+    let shadow = shadow_tree(tree, &mut next_ordinal);
+    let epochs = compute_epochs(shadow)?;
+
+    let mut epoch_by_ordinal: HashMap<usize, usize> = HashMap::new();
+    for (epoch_index, ordinals) in epochs.into_iter().enumerate() {
+        for ordinal in ordinals {
+            epoch_by_ordinal.insert(ordinal, epoch_index);
+        }
+    }
+
+    let mut out = String::new();
+    let mut next_ordinal = 0usize;
+    write_node(
+        &mut out,
+        tree,
+        "",
+        None,
+        &label,
+        &epoch_by_ordinal,
+        &mut next_ordinal,
+    );
+    Ok(out)
+}
+
+fn shadow_tree<Node, NodeId>(
+    tree: &CausalityTree<Option<Node>, NodeId>,
+    next_ordinal: &mut usize,
+) -> CausalityTree<Option<usize>, NodeId>
+where
+    NodeId: Clone,
+{
+    match tree {
+        CausalityTree::Branch { meta, children } => CausalityTree::Branch {
+            meta: meta.clone(),
+            children: children
+                .iter()
+                .map(|child| shadow_tree(child, next_ordinal))
+                .collect(),
+        },
+        CausalityTree::Leaf { meta, node } => {
+            let ordinal = *next_ordinal;
+            *next_ordinal += 1;
+            CausalityTree::Leaf {
+                meta: meta.clone(),
+                node: node.as_ref().map(|_| ordinal),
+            }
+        }
+    }
+}
+
+fn write_node<Node, NodeId, LabelFn>(
+    out: &mut String,
+    tree: &CausalityTree<Option<Node>, NodeId>,
+    prefix: &str,
+    is_last: Option<bool>,
+    label: &LabelFn,
+    epoch_by_ordinal: &HashMap<usize, usize>,
+    next_ordinal: &mut usize,
+) where
+    NodeId: Display,
+    LabelFn: Fn(&Node) -> String,
+{
+    let (connector, child_prefix) = match is_last {
+        None => ("", prefix.to_string()),
+        Some(true) => ("└── ", format!("{prefix}    ")),
+        Some(false) => ("├── ", format!("{prefix}│   ")),
+    };
+
+    match tree {
+        CausalityTree::Branch { meta, children } => {
+            let _ = writeln!(out, "{prefix}{connector}branch{}", format_meta(meta));
+            let count = children.len();
+            for (index, child) in children.iter().enumerate() {
+                write_node(
+                    out,
+                    child,
+                    &child_prefix,
+                    Some(index + 1 == count),
+                    label,
+                    epoch_by_ordinal,
+                    next_ordinal,
+                );
+            }
+        }
+        CausalityTree::Leaf { meta, node } => {
+            let ordinal = *next_ordinal;
+            *next_ordinal += 1;
+            let (text, epoch) = match node {
+                Some(node) => {
+                    let epoch = match epoch_by_ordinal.get(&ordinal) {
+                        Some(epoch) => format!(" => epoch {epoch}"),
+                        None => " => unscheduled".to_string(),
+                    };
+                    (label(node), epoch)
+                }
+                None => ("(marker)".to_string(), String::new()),
+            };
+            let _ = writeln!(out, "{prefix}{connector}{text}{}{epoch}", format_meta(meta));
+        }
+    }
+}
+
+fn format_meta<NodeId: Display>(meta: &CausalityMeta<NodeId>) -> String {
+    let mut out = String::new();
+    if let Some(id) = &meta.id {
+        let _ = write!(out, " [id: {id}]");
+    }
+    if !meta.requires.is_empty() {
+        let _ = write!(out, " [requires: {}]", join(&meta.requires));
+    }
+    if !meta.required_by.is_empty() {
+        let _ = write!(out, " [required_by: {}]", join(&meta.required_by));
+    }
+    out
+}
+
+fn join<NodeId: Display>(ids: &[NodeId]) -> String {
+    ids.iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Which side of a dependency edge a [`NodeConstraint`] came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstraintKind {
+    /// The explained node (or one of its ancestors) `requires` another id.
+    Requires,
+    /// Another node declared the explained node (or one of its ancestors) in
+    /// its `required_by`.
+    RequiredBy,
+}
+
+/// A leaf whose epoch would change if a constraint were removed.
+#[derive(Debug, Clone)]
+pub struct EpochMove<Node> {
+    pub node: Node,
+    pub from: usize,
+    pub to: usize,
+}
+
+/// One dependency edge that bears on the explained node's placement.
+///
+/// - `declared_on`: id of the node whose metadata declares the edge, if any.
+/// - `other`: the id on the far end — the required id for
+///   [`ConstraintKind::Requires`], the referenced id (the explained node or
+///   one of its ancestors) for [`ConstraintKind::RequiredBy`].
+/// - `inherited`: the edge reaches the explained node through parent
+///   structure (declared on, or referencing, an ancestor branch) rather than
+///   the node itself.
+/// - `binding`: the edge's predecessors finish in the epoch directly before
+///   the explained node, so it is (one of) the reasons the node isn't earlier.
+/// - `moves_if_removed`: every leaf whose epoch would change without it.
+#[derive(Debug, Clone)]
+pub struct NodeConstraint<Node, NodeId> {
+    pub kind: ConstraintKind,
+    pub declared_on: Option<NodeId>,
+    pub other: NodeId,
+    pub inherited: bool,
+    pub binding: bool,
+    pub moves_if_removed: Vec<EpochMove<Node>>,
+}
+
+/// Why a node landed in the epoch(s) it did. See [`explain_node`].
+///
+/// `epochs` has one entry for a leaf and the sorted, deduplicated epochs of
+/// every descendant leaf for a branch. It is empty for a marker leaf.
+#[derive(Debug, Clone)]
+pub struct NodeExplanation<Node, NodeId> {
+    pub id: NodeId,
+    pub epochs: Vec<usize>,
+    pub constraints: Vec<NodeConstraint<Node, NodeId>>,
+}
+
+#[derive(Debug, Error)]
+pub enum ExplainError<NodeId> {
+    #[error("Unknown id: {0}")]
+    UnknownId(NodeId),
+
+    #[error(transparent)]
+    Epoch(#[from] EpochError<NodeId>),
+}
+
+/// Explain the epoch placement of the node declared with `id`: which
+/// `requires` / `required_by` edges (its own or inherited through ancestor
+/// branches) constrain it, which of those are binding, and which leaves would
+/// move to a different epoch if each edge were removed.
+///
+/// Epoch numbers match the indices returned by [`compute_epochs`].
+///
+/// # Errors
+///
+/// - [`ExplainError::UnknownId`] if no node declares `id`.
+/// - [`ExplainError::Epoch`] if the tree can't be scheduled at all.
+pub fn explain_node<Node, NodeId>(
+    tree: &CausalityTree<Option<Node>, NodeId>,
+    id: &NodeId,
+) -> Result<NodeExplanation<Node, NodeId>, ExplainError<NodeId>>
+where
+    Node: Debug + Clone,
+    NodeId: Debug + Clone + Eq + Hash,
+{
+    let mut visits: Vec<Visit<'_, Node, NodeId>> = Vec::new();
+    collect_visits(tree, None, &mut visits);
+
+    // Leaf ordinals under every node (including itself, for leaves).
+    let mut leaves_under: Vec<Vec<usize>> = vec![Vec::new(); visits.len()];
+    let mut leaf_nodes: Vec<Option<&Node>> = Vec::new();
+    let mut leaf_visits: Vec<usize> = Vec::new();
+    for (index, visit) in visits.iter().enumerate() {
+        if let Some(node) = visit.leaf {
+            let ordinal = leaf_nodes.len();
+            leaf_nodes.push(node);
+            leaf_visits.push(index);
+            let mut current = Some(index);
+            while let Some(i) = current {
+                leaves_under[i].push(ordinal);
+                current = visits[i].parent;
+            }
+        }
+    }
+
+    let mut id_to_visit: HashMap<&NodeId, usize> = HashMap::new();
+    for (index, visit) in visits.iter().enumerate() {
+        if let Some(id) = &visit.meta.id {
+            id_to_visit.insert(id, index);
+        }
+    }
+
+    let target = *id_to_visit
+        .get(id)
+        .ok_or_else(|| ExplainError::UnknownId(id.clone()))?;
+    let ancestors = ancestors_of(&visits, target);
+    let target_leaves = &leaves_under[target];
+
+    // Waves count every leaf (markers included); epochs only count waves
+    // with at least one real node, matching what `compute_epochs` returns.
+    let waves_of = |skip: Option<EdgeRef>| -> Result<Vec<usize>, EpochError<NodeId>> {
+        let shadow = shadow_tree_without(tree, &mut 0, &mut 0, skip);
+        let mut wave_of = vec![0; leaf_nodes.len()];
+        for (wave_index, wave) in compute_epochs(shadow)?.into_iter().enumerate() {
+            for ordinal in wave {
+                wave_of[ordinal] = wave_index;
+            }
+        }
+        Ok(wave_of)
+    };
+    let epochs_of = |wave_of: &[usize]| -> Vec<Option<usize>> {
+        let mut real_waves: Vec<usize> = wave_of
+            .iter()
+            .zip(leaf_nodes.iter())
+            .filter_map(|(&wave, node)| node.is_some().then_some(wave))
+            .collect();
+        real_waves.sort_unstable();
+        real_waves.dedup();
+        wave_of
+            .iter()
+            .zip(leaf_nodes.iter())
+            .map(|(wave, node)| {
+                node.as_ref()?;
+                real_waves.binary_search(wave).ok()
+            })
+            .collect()
+    };
+
+    let baseline_waves = waves_of(None)?;
+    let baseline = epochs_of(&baseline_waves);
+
+    let mut epochs: Vec<usize> = target_leaves
+        .iter()
+        .filter_map(|&ordinal| baseline[ordinal])
+        .collect();
+    epochs.sort_unstable();
+    epochs.dedup();
+
+    // An edge is binding if its predecessors' latest wave is directly before
+    // some affected leaf's wave.
+    let is_binding = |predecessors: &[usize], affected: &[usize]| {
+        let Some(latest) = predecessors.iter().map(|&o| baseline_waves[o]).max() else {
+            return false;
+        };
+        affected
+            .iter()
+            .any(|&ordinal| baseline_waves[ordinal] == latest + 1)
+    };
+
+    let mut constraints = Vec::new();
+    let mut push_constraint = |edge: EdgeRef,
+                               other: &NodeId,
+                               inherited: bool,
+                               binding: bool|
+     -> Result<(), EpochError<NodeId>> {
+        let moved = epochs_of(&waves_of(Some(edge))?);
+        let moves_if_removed = baseline
+            .iter()
+            .zip(moved.iter())
+            .enumerate()
+            .filter_map(|(ordinal, (before, after))| match (before, after) {
+                (Some(from), Some(to)) if from != to => Some(EpochMove {
+                    node: leaf_nodes[ordinal].cloned()?,
+                    from: *from,
+                    to: *to,
+                }),
+                _ => None,
+            })
+            .collect();
+        constraints.push(NodeConstraint {
+            kind: edge.kind,
+            declared_on: visits[edge.visit].meta.id.clone(),
+            other: other.clone(),
+            inherited,
+            binding,
+            moves_if_removed,
+        });
+        Ok(())
+    };
+
+    // `requires` declared on the node, its ancestors, or (for a branch) its
+    // descendants.
+    for (index, visit) in visits.iter().enumerate() {
+        let on_path = ancestors.contains(&index) || index == target;
+        let below = is_descendant(&visits, index, target);
+        if !on_path && !below {
+            continue;
+        }
+        for (position, required) in visit.meta.requires.iter().enumerate() {
+            let Some(&required_visit) = id_to_visit.get(required) else {
+                continue;
+            };
+            let affected: Vec<usize> = leaves_under[index]
+                .iter()
+                .filter(|ordinal| target_leaves.contains(ordinal))
+                .copied()
+                .collect();
+            let binding = is_binding(&leaves_under[required_visit], &affected);
+            let edge = EdgeRef {
+                visit: index,
+                kind: ConstraintKind::Requires,
+                position,
+            };
+            push_constraint(edge, required, ancestors.contains(&index), binding)?;
+        }
+    }
+
+    // `required_by` anywhere in the tree that points at the node or one of
+    // its ancestors (or descendants, for a branch).
+    for (index, visit) in visits.iter().enumerate() {
+        for (position, required_by) in visit.meta.required_by.iter().enumerate() {
+            let Some(&referenced) = id_to_visit.get(required_by) else {
+                continue;
+            };
+            let affected: Vec<usize> = leaves_under[referenced]
+                .iter()
+                .filter(|ordinal| target_leaves.contains(ordinal))
+                .copied()
+                .collect();
+            if affected.is_empty() {
+                continue;
+            }
+            let binding = is_binding(&leaves_under[index], &affected);
+            let edge = EdgeRef {
+                visit: index,
+                kind: ConstraintKind::RequiredBy,
+                position,
+            };
+            push_constraint(edge, required_by, ancestors.contains(&referenced), binding)?;
+        }
+    }
+
+    Ok(NodeExplanation {
+        id: id.clone(),
+        epochs,
+        constraints,
+    })
+}
+
+impl<Node, NodeId> Display for NodeExplanation<Node, NodeId>
+where
+    Node: Display,
+    NodeId: Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.epochs.as_slice() {
+            [] => writeln!(f, "{} is not scheduled (marker)", self.id)?,
+            [epoch] => writeln!(f, "{} is in epoch {epoch}", self.id)?,
+            epochs => writeln!(
+                f,
+                "{} spans epochs {}",
+                self.id,
+                epochs
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )?,
+        }
+
+        if self.constraints.is_empty() {
+            return writeln!(f, "  no constraints: it runs as early as possible");
+        }
+
+        for constraint in &self.constraints {
+            let declared_on = match &constraint.declared_on {
+                Some(id) => id.to_string(),
+                None => "an anonymous node".to_string(),
+            };
+            match constraint.kind {
+                ConstraintKind::Requires => {
+                    write!(f, "  requires {}", constraint.other)?;
+                    if constraint.inherited {
+                        write!(f, " (inherited from {declared_on})")?;
+                    }
+                }
+                ConstraintKind::RequiredBy => {
+                    write!(f, "  required by {declared_on}")?;
+                    if constraint.inherited {
+                        write!(f, " (via ancestor {})", constraint.other)?;
+                    }
+                }
+            }
+            if constraint.binding {
+                write!(f, " [binding]")?;
+            }
+            writeln!(f)?;
+
+            if constraint.moves_if_removed.is_empty() {
+                writeln!(f, "    removing it would move nothing")?;
+            } else {
+                writeln!(f, "    removing it would move:")?;
+                for EpochMove { node, from, to } in &constraint.moves_if_removed {
+                    writeln!(f, "      {node}: epoch {from} -> {to}")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct Visit<'a, Node, NodeId> {
+    meta: &'a CausalityMeta<NodeId>,
+    parent: Option<usize>,
+    /// `Some` for leaves, carrying the (possibly marker) node.
+    leaf: Option<Option<&'a Node>>,
+}
+
+/// Identifies a single `requires` / `required_by` entry by the pre-order
+/// index of the node that declares it.
+#[derive(Debug, Clone, Copy)]
+struct EdgeRef {
+    visit: usize,
+    kind: ConstraintKind,
+    position: usize,
+}
+
+fn collect_visits<'a, Node, NodeId>(
+    tree: &'a CausalityTree<Option<Node>, NodeId>,
+    parent: Option<usize>,
+    visits: &mut Vec<Visit<'a, Node, NodeId>>,
+) {
+    let index = visits.len();
+    match tree {
+        CausalityTree::Branch { meta, children } => {
+            visits.push(Visit {
+                meta,
+                parent,
+                leaf: None,
+            });
+            for child in children {
+                collect_visits(child, Some(index), visits);
+            }
+        }
+        CausalityTree::Leaf { meta, node } => visits.push(Visit {
+            meta,
+            parent,
+            leaf: Some(node.as_ref()),
+        }),
+    }
+}
+
+fn ancestors_of<Node, NodeId>(visits: &[Visit<'_, Node, NodeId>], index: usize) -> Vec<usize> {
+    let mut out = Vec::new();
+    let mut current = visits[index].parent;
+    while let Some(i) = current {
+        out.push(i);
+        current = visits[i].parent;
+    }
+    out
+}
+
+fn is_descendant<Node, NodeId>(
+    visits: &[Visit<'_, Node, NodeId>],
+    index: usize,
+    ancestor: usize,
+) -> bool {
+    ancestors_of(visits, index).contains(&ancestor)
+}
+
+/// Like [`shadow_tree`], but every leaf is scheduled (markers included) and
+/// the `skip` edge, if any, is left out.
+fn shadow_tree_without<Node, NodeId>(
+    tree: &CausalityTree<Option<Node>, NodeId>,
+    next_visit: &mut usize,
+    next_ordinal: &mut usize,
+    skip: Option<EdgeRef>,
+) -> CausalityTree<Option<usize>, NodeId>
+where
+    NodeId: Clone,
+{
+    let visit = *next_visit;
+    *next_visit += 1;
+
+    let mut meta = match tree {
+        CausalityTree::Branch { meta, .. } | CausalityTree::Leaf { meta, .. } => meta.clone(),
+    };
+    if let Some(edge) = skip.filter(|edge| edge.visit == visit) {
+        match edge.kind {
+            ConstraintKind::Requires => meta.requires.remove(edge.position),
+            ConstraintKind::RequiredBy => meta.required_by.remove(edge.position),
+        };
+    }
+
+    match tree {
+        CausalityTree::Branch { children, .. } => CausalityTree::Branch {
+            meta,
+            children: children
+                .iter()
+                .map(|child| shadow_tree_without(child, next_visit, next_ordinal, skip))
+                .collect(),
+        },
+        CausalityTree::Leaf { .. } => {
+            let ordinal = *next_ordinal;
+            *next_ordinal += 1;
+            CausalityTree::Leaf {
+                meta,
+                node: Some(ordinal),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn annotates_edges_and_epochs() {
+        let tree: CausalityTree<Option<&str>> = CausalityTree::branch(
+            CausalityMeta::default(),
+            [
+                CausalityTree::leaf(
+                    CausalityMeta {
+                        id: Some("b".to_string()),
+                        requires: vec!["a".to_string()],
+                        required_by: vec![],
+                    },
+                    Some("second"),
+                ),
+                CausalityTree::leaf(CausalityMeta::id("a".to_string()), Some("first")),
+                CausalityTree::leaf(CausalityMeta::default(), None),
+            ],
+        );
+
+        let text = explain_ordering(&tree, |node| node.to_string()).unwrap();
+
+        assert_eq!(
+            text,
+            "branch\n\
+             ├── second [id: b] [requires: a] => epoch 1\n\
+             ├── first [id: a] => epoch 0\n\
+             └── (marker)\n"
+        );
+    }
+
+    #[test]
+    fn explains_binding_and_inherited_constraints() {
+        // "c" inherits `requires: [a]` from its parent branch and declares its
+        // own `requires: [b]`; "b" is later than "a", so only "b" binds.
+        let tree: CausalityTree<Option<&str>> = CausalityTree::branch(
+            CausalityMeta::default(),
+            [
+                CausalityTree::leaf(CausalityMeta::id("a".to_string()), Some("a")),
+                CausalityTree::leaf(
+                    CausalityMeta {
+                        id: Some("b".to_string()),
+                        requires: vec!["a".to_string()],
+                        required_by: vec![],
+                    },
+                    Some("b"),
+                ),
+                CausalityTree::branch(
+                    CausalityMeta::requires(vec!["a".to_string()]),
+                    [CausalityTree::leaf(
+                        CausalityMeta {
+                            id: Some("c".to_string()),
+                            requires: vec!["b".to_string()],
+                            required_by: vec![],
+                        },
+                        Some("c"),
+                    )],
+                ),
+            ],
+        );
+
+        let explanation = explain_node(&tree, &"c".to_string()).unwrap();
+        assert_eq!(explanation.epochs, vec![2]);
+        assert_eq!(explanation.constraints.len(), 2);
+
+        let inherited = &explanation.constraints[0];
+        assert_eq!(inherited.kind, ConstraintKind::Requires);
+        assert_eq!(inherited.other, "a");
+        assert!(inherited.inherited);
+        assert!(!inherited.binding);
+        assert!(inherited.moves_if_removed.is_empty());
+
+        let own = &explanation.constraints[1];
+        assert_eq!(own.other, "b");
+        assert!(!own.inherited);
+        assert!(own.binding);
+        assert_eq!(own.moves_if_removed.len(), 1);
+        assert_eq!(own.moves_if_removed[0].node, "c");
+        assert_eq!(own.moves_if_removed[0].from, 2);
+        assert_eq!(own.moves_if_removed[0].to, 1);
+    }
+
+    #[test]
+    fn explain_unknown_id() {
+        let tree: CausalityTree<Option<&str>> =
+            CausalityTree::leaf(CausalityMeta::id("a".to_string()), Some("a"));
+
+        assert!(matches!(
+            explain_node(&tree, &"b".to_string()),
+            Err(ExplainError::UnknownId(id)) if id == "b"
+        ));
+    }
+}
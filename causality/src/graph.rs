@@ -0,0 +1,265 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    hash::Hash,
+};
+
+use crate::{CausalityMeta, EpochError, tree::CausalityTree};
+
+/// The leaf-to-leaf edges of a [`CausalityTree`], for asking how its leaves
+/// are ordered without scheduling it — e.g. whether a new edge would close a
+/// cycle before adding it.
+///
+/// Ids, `requires` and `required_by` resolve as in [`compute_epochs`]: a
+/// branch's constraints apply to each of its leaves, and a branch id refers
+/// to all of them.
+///
+/// [`compute_epochs`]: crate::compute_epochs
+#[derive(Debug, Clone)]
+pub struct CausalityGraph<NodeId> {
+    id_to_leaves: HashMap<NodeId, Vec<usize>>,
+    /// For each leaf (by pre-order index), the leaves that run after it.
+    outgoing: Vec<Vec<usize>>,
+}
+
+impl<NodeId> CausalityGraph<NodeId>
+where
+    NodeId: Debug + Clone + Eq + Hash,
+{
+    /// # Errors
+    ///
+    /// As [`compute_epochs`](crate::compute_epochs), except that a cycle
+    /// isn't an error here.
+    pub fn new<Node>(tree: &CausalityTree<Node, NodeId>) -> Result<Self, EpochError<NodeId>> {
+        let mut leaves: Vec<(Vec<NodeId>, Vec<NodeId>)> = Vec::new();
+        let mut id_to_leaves: HashMap<NodeId, Vec<usize>> = HashMap::new();
+        let mut seen_ids: HashSet<NodeId> = HashSet::new();
+        collect(
+            tree,
+            &mut Vec::new(),
+            &mut Vec::new(),
+            &mut Vec::new(),
+            &mut seen_ids,
+            &mut id_to_leaves,
+            &mut leaves,
+        )?;
+
+        let mut outgoing: Vec<Vec<usize>> = vec![Vec::new(); leaves.len()];
+        for (i, (requires, required_by)) in leaves.iter().enumerate() {
+            for id in requires {
+                let Some(targets) = id_to_leaves.get(id) else {
+                    return Err(EpochError::UnknownRequiresRef(id.clone()));
+                };
+                for &j in targets {
+                    outgoing[j].push(i);
+                }
+            }
+            for id in required_by {
+                let Some(targets) = id_to_leaves.get(id) else {
+                    return Err(EpochError::UnknownRequiredByRef(id.clone()));
+                };
+                outgoing[i].extend(targets);
+            }
+        }
+
+        Ok(Self {
+            id_to_leaves,
+            outgoing,
+        })
+    }
+
+    /// Whether any leaf of `before` runs, directly or transitively, before any
+    /// leaf of `after`. Unknown ids aren't ordered against anything.
+    pub fn runs_before(&self, before: &NodeId, after: &NodeId) -> bool {
+        let (Some(from), Some(to)) = (self.id_to_leaves.get(before), self.id_to_leaves.get(after))
+        else {
+            return false;
+        };
+        let targets: HashSet<usize> = to.iter().copied().collect();
+
+        let mut visited = vec![false; self.outgoing.len()];
+        let mut stack = from.clone();
+        while let Some(leaf) = stack.pop() {
+            for &next in &self.outgoing[leaf] {
+                if targets.contains(&next) {
+                    return true;
+                }
+                if !visited[next] {
+                    visited[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+        false
+    }
+
+    /// Order every leaf of `after` after every leaf of `before`, as `after`
+    /// requiring `before` would.
+    pub fn add_requires(&mut self, after: &NodeId, before: &NodeId) {
+        let (Some(from), Some(to)) = (self.id_to_leaves.get(before), self.id_to_leaves.get(after))
+        else {
+            return;
+        };
+        for &i in from {
+            self.outgoing[i].extend(to);
+        }
+    }
+}
+
+// Note(cc): this walk mirrors the one in `compute_epochs`, by reference
+// rather than consuming the tree. Keep the two resolving ids alike.
+fn collect<Node, NodeId>(
+    tree: &CausalityTree<Node, NodeId>,
+    ancestor_requires: &mut Vec<NodeId>,
+    ancestor_required_by: &mut Vec<NodeId>,
+    active_branch_ids: &mut Vec<NodeId>,
+    seen_ids: &mut HashSet<NodeId>,
+    id_to_leaves: &mut HashMap<NodeId, Vec<usize>>,
+    leaves: &mut Vec<(Vec<NodeId>, Vec<NodeId>)>,
+) -> Result<(), EpochError<NodeId>>
+where
+    NodeId: Clone + Eq + Hash,
+{
+    match tree {
+        CausalityTree::Branch { children, meta } => {
+            let CausalityMeta {
+                id,
+                requires,
+                required_by,
+            } = meta;
+
+            let requires_len = ancestor_requires.len();
+            ancestor_requires.extend(requires.iter().cloned());
+            let required_by_len = ancestor_required_by.len();
+            ancestor_required_by.extend(required_by.iter().cloned());
+
+            if let Some(branch_id) = id {
+                if !seen_ids.insert(branch_id.clone()) {
+                    return Err(EpochError::DuplicateId(branch_id.clone()));
+                }
+                id_to_leaves.entry(branch_id.clone()).or_default();
+                active_branch_ids.push(branch_id.clone());
+            }
+
+            for child in children {
+                collect(
+                    child,
+                    ancestor_requires,
+                    ancestor_required_by,
+                    active_branch_ids,
+                    seen_ids,
+                    id_to_leaves,
+                    leaves,
+                )?;
+            }
+
+            ancestor_requires.truncate(requires_len);
+            ancestor_required_by.truncate(required_by_len);
+            if id.is_some() {
+                active_branch_ids.pop();
+            }
+            Ok(())
+        }
+        CausalityTree::Leaf { meta, .. } => {
+            let CausalityMeta {
+                id,
+                requires,
+                required_by,
+            } = meta;
+
+            let index = leaves.len();
+            leaves.push((
+                ancestor_requires.iter().chain(requires).cloned().collect(),
+                ancestor_required_by
+                    .iter()
+                    .chain(required_by)
+                    .cloned()
+                    .collect(),
+            ));
+
+            for branch_id in active_branch_ids.iter() {
+                if let Some(v) = id_to_leaves.get_mut(branch_id) {
+                    v.push(index);
+                }
+            }
+
+            if let Some(leaf_id) = id {
+                if !seen_ids.insert(leaf_id.clone()) {
+                    return Err(EpochError::DuplicateId(leaf_id.clone()));
+                }
+                id_to_leaves.insert(leaf_id.clone(), vec![index]);
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf(id: &str, requires: &[&str]) -> CausalityTree<()> {
+        CausalityTree::leaf(
+            CausalityMeta {
+                id: Some(id.to_string()),
+                requires: requires.iter().map(ToString::to_string).collect(),
+                required_by: vec![],
+            },
+            (),
+        )
+    }
+
+    fn runs_before(graph: &CausalityGraph<String>, before: &str, after: &str) -> bool {
+        graph.runs_before(&before.to_string(), &after.to_string())
+    }
+
+    #[test]
+    fn follows_edges_transitively_and_through_branches() {
+        let tree = CausalityTree::branch(
+            CausalityMeta::default(),
+            [
+                leaf("a", &[]),
+                CausalityTree::branch(
+                    CausalityMeta {
+                        id: Some("group".to_string()),
+                        requires: vec!["a".to_string()],
+                        required_by: vec![],
+                    },
+                    [leaf("b", &[]), leaf("c", &[])],
+                ),
+                leaf("d", &["c"]),
+                leaf("e", &[]),
+            ],
+        );
+        let graph = CausalityGraph::new(&tree).unwrap();
+
+        assert!(runs_before(&graph, "a", "d"));
+        assert!(runs_before(&graph, "a", "group"));
+        assert!(!runs_before(&graph, "d", "a"));
+        assert!(!runs_before(&graph, "a", "e"));
+        assert!(!runs_before(&graph, "a", "missing"));
+    }
+
+    #[test]
+    fn added_edges_are_followed() {
+        let tree = CausalityTree::branch(
+            CausalityMeta::default(),
+            [leaf("a", &[]), leaf("b", &[]), leaf("c", &["b"])],
+        );
+        let mut graph = CausalityGraph::new(&tree).unwrap();
+        assert!(!runs_before(&graph, "a", "c"));
+
+        graph.add_requires(&"b".to_string(), &"a".to_string());
+        assert!(runs_before(&graph, "a", "c"));
+        assert!(!runs_before(&graph, "c", "a"));
+    }
+
+    #[test]
+    fn rejects_unknown_references() {
+        let tree = CausalityTree::branch(CausalityMeta::default(), [leaf("a", &["z"])]);
+        assert!(matches!(
+            CausalityGraph::new(&tree),
+            Err(EpochError::UnknownRequiresRef(id)) if id == "z"
+        ));
+    }
+}
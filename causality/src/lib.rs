@@ -8,9 +8,25 @@
 //! [`compute_epochs`] flattens the tree into topologically-sorted layers ("epochs")
 //! using Kahn's algorithm. Each epoch is a set of nodes with no remaining
 //! dependencies, so they can be executed in parallel.
+//!
+//! [`explain_ordering`] renders the same tree as text with its edges and the
+//! resolved epoch of every leaf, for debugging why something ran when it did;
+//! [`explain_node`] narrows that to the edges that placed a single node.
+//!
+//! [`CausalityGraph`] answers whether one node runs before another without
+//! scheduling the tree, so an edge can be checked for cycles before it's
+//! added.
 
 mod epoch;
+mod explain;
+mod graph;
+#[cfg(any(test, feature = "testing"))]
+mod shuffle;
 mod tree;
 
 pub use crate::epoch::*;
+pub use crate::explain::*;
+pub use crate::graph::*;
+#[cfg(any(test, feature = "testing"))]
+pub use crate::shuffle::*;
 pub use crate::tree::*;
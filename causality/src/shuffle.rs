@@ -0,0 +1,56 @@
+//! Seedable shuffling for tests.
+//!
+//! Scheduling must not depend on the order siblings happen to be inserted
+//! in (plan item order, `Vec`/`IndexMap` iteration order, …) beyond the
+//! documented tie-break. [`Shuffler`] reorders siblings reproducibly from a
+//! seed so tests can assert that invariant across many orderings, and print
+//! the failing seed when it doesn't hold.
+//!
+//! Only compiled for this crate's tests or with the `testing` feature, for
+//! downstream crates' dev-dependencies.
+
+use lusid_tree::Tree;
+
+/// Deterministic Fisher–Yates shuffler seeded from a `u64` (SplitMix64).
+#[derive(Debug, Clone)]
+pub struct Shuffler {
+    state: u64,
+}
+
+impl Shuffler {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Shuffle `items` in place.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = (self.next_u64() % (i as u64 + 1)) as usize;
+            items.swap(i, j);
+        }
+    }
+
+    /// Shuffle the children of every branch in `tree`, recursively. Metadata
+    /// stays attached to its node.
+    pub fn shuffle_tree<Node, Meta>(&mut self, tree: Tree<Node, Meta>) -> Tree<Node, Meta> {
+        match tree {
+            Tree::Branch { meta, children } => {
+                let mut children: Vec<_> = children
+                    .into_iter()
+                    .map(|child| self.shuffle_tree(child))
+                    .collect();
+                self.shuffle(&mut children);
+                Tree::Branch { meta, children }
+            }
+            leaf @ Tree::Leaf { .. } => leaf,
+        }
+    }
+}
@@ -5,7 +5,9 @@
 //! - Boolean `stdout` / `stderr` knobs that toggle between piped (captured) and
 //!   inherited (streamed directly to the parent's stdio).
 //! - A [`Command::sudo`] helper that rewraps the command under `sudo -n`, preserving
-//!   explicitly-set env vars and the working directory.
+//!   explicitly-set env vars and the working directory, and [`Command::sudo_as`] to
+//!   run as a given user and/or group instead of root. [`set_sudo`] turns the
+//!   former off where lusid already runs as root.
 //! - Uniform `CommandError` variants for the common failure modes.
 //! - [`Command::handle`] for commands where success and failure both produce the
 //!   same value type (e.g. apt's `dpkg-query` check classifying a package as
@@ -30,11 +32,22 @@ use std::path::Path;
 use std::pin::Pin;
 use std::process::{ExitStatus, Stdio};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::io::AsyncReadExt;
-use tokio::process::{Child, ChildStderr, ChildStdout, Command as BaseCommand};
+use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command as BaseCommand};
 
 use thiserror::Error;
 
+static SUDO: AtomicBool = AtomicBool::new(true);
+
+/// Whether [`Command::sudo`] wraps commands in `sudo`, for the whole process.
+/// Off for targets where lusid already runs as root and may have no `sudo`
+/// at all, like a container. [`Command::sudo_as`] with a user or group still
+/// needs `sudo` either way.
+pub fn set_sudo(enabled: bool) {
+    SUDO.store(enabled, Ordering::Relaxed);
+}
+
 #[derive(Error, Debug)]
 pub enum CommandError {
     #[error("failed to spawn command: {command}")]
@@ -54,6 +67,16 @@ pub enum CommandError {
     #[error("command failed: {command}\n{stderr}")]
     Failure { command: String, stderr: String },
 
+    #[error("command failed: {command}")]
+    Exit {
+        command: String,
+        #[source]
+        exit: CommandExit,
+    },
+
+    #[error("unable to capture stdin")]
+    NoStdin,
+
     #[error("unable to capture stdout")]
     NoStdout,
 
@@ -67,6 +90,24 @@ pub enum CommandError {
     ReadStderr(#[source] tokio::io::Error),
 }
 
+/// How a command exited, when it exited unsuccessfully: the source of
+/// [`CommandError::Exit`]. Errors that wrap a [`CommandError`] transparently
+/// still have this in their source chain, which is how [`exit_code`] finds it.
+#[derive(Error, Debug)]
+#[error("{status}")]
+pub struct CommandExit {
+    pub status: ExitStatus,
+}
+
+/// The exit code of the command that failed `error`, if one exited
+/// unsuccessfully anywhere in its source chain. `None` for a command killed
+/// by a signal.
+pub fn exit_code(error: &(dyn std::error::Error + 'static)) -> Option<i32> {
+    std::iter::successors(Some(error), |error| error.source())
+        .find_map(|error| error.downcast_ref::<CommandExit>())
+        .and_then(|exit| exit.status.code())
+}
+
 #[derive(Debug)]
 pub struct Command {
     cmd: BaseCommand,
@@ -167,11 +208,28 @@ impl Command {
     /// directory. The `-n` flag makes sudo fail fast rather than block for a password
     /// prompt — lusid operations must be non-interactive.
     pub fn sudo(self) -> Self {
+        self.sudo_as(None, None)
+    }
+
+    /// Like [`sudo`](Self::sudo), but run as `user` and/or `group` (`sudo -u`
+    /// / `sudo -g`) rather than root. Used to run commands as the user that
+    /// should own what they create, e.g. a git clone in that user's home.
+    pub fn sudo_as(self, user: Option<&str>, group: Option<&str>) -> Self {
+        if user.is_none() && group.is_none() && !SUDO.load(Ordering::Relaxed) {
+            return self;
+        }
+
         let mut privileged_cmd = Command::new("sudo");
 
         let cmd = self.cmd.as_std();
 
         privileged_cmd.arg("-n"); // non-interactive
+        if let Some(user) = user {
+            privileged_cmd.arg("-u").arg(user);
+        }
+        if let Some(group) = group {
+            privileged_cmd.arg("-g").arg(group);
+        }
 
         for env in cmd.get_envs() {
             if let (key, Some(value)) = env {
@@ -223,6 +281,29 @@ pub struct CommandOutput {
     pub status: Pin<Box<dyn Future<Output = Result<ExitStatus, CommandError>> + Send + 'static>>,
 }
 
+/// `output` with its `status` failing unless `command` exits successfully.
+fn checked(command: String, output: CommandOutput) -> CommandOutput {
+    let CommandOutput {
+        stdout,
+        stderr,
+        status,
+    } = output;
+    CommandOutput {
+        stdout,
+        stderr,
+        status: Box::pin(async move {
+            let status = status.await?;
+            if !status.success() {
+                return Err(CommandError::Exit {
+                    command,
+                    exit: CommandExit { status },
+                });
+            }
+            Ok(status)
+        }),
+    }
+}
+
 /// Buffered result of a command run to completion. Produced by [`Command::outcome`].
 pub struct CommandOutcome {
     pub status: ExitStatus,
@@ -241,8 +322,36 @@ impl Command {
         // > the stdout/stderr handles to be pipes, even if they have been previously configured.
         // > If this is not desired then the `spawn` method should be used in combination with the
         // > `wait_with_output` method on child.
+        let child = self.spawn()?;
+        self.output_of(child)
+    }
+
+    /// Like [`Self::output`], but `status` fails with [`CommandError::Exit`]
+    /// unless the command exits successfully. Operations run their commands
+    /// this way, so a failed `apt-get install` fails the apply.
+    pub async fn output_checked(&mut self) -> Result<CommandOutput, CommandError> {
+        let output = self.output().await?;
+        Ok(checked(self.to_string(), output))
+    }
+
+    /// Like [`Self::output`], but also hand back the command's stdin, for
+    /// commands fed input while they run (e.g. `lusid-apply --control`). The
+    /// command sees EOF once it's dropped.
+    pub async fn output_with_stdin(&mut self) -> Result<(ChildStdin, CommandOutput), CommandError> {
         let mut child = self.spawn()?;
+        let stdin = child.stdin.take().ok_or(CommandError::NoStdin)?;
+        Ok((stdin, self.output_of(child)?))
+    }
+
+    /// [`Self::output_with_stdin`], checked like [`Self::output_checked`].
+    pub async fn output_with_stdin_checked(
+        &mut self,
+    ) -> Result<(ChildStdin, CommandOutput), CommandError> {
+        let (stdin, output) = self.output_with_stdin().await?;
+        Ok((stdin, checked(self.to_string(), output)))
+    }
 
+    fn output_of(&self, mut child: Child) -> Result<CommandOutput, CommandError> {
         let stdout = child.stdout.take().ok_or(CommandError::NoStdout)?;
         let stderr = child.stderr.take().ok_or(CommandError::NoStderr)?;
 
@@ -385,6 +494,26 @@ impl Command {
         cmd.arg(command);
         cmd
     }
+
+    /// Render as a single shell command line, quoting words where needed, with
+    /// explicitly-set env vars as leading `KEY=VALUE` assignments. Unlike the
+    /// [`Display`] form, the result can be pasted into a script (used by
+    /// `lusid plan export-script`). The working directory isn't represented.
+    pub fn to_shell(&self) -> String {
+        let cmd = self.cmd.as_std();
+        let envs = cmd.get_envs().filter_map(|(key, value)| {
+            let value = value?;
+            Some(format!(
+                "{}={}",
+                key.to_string_lossy(),
+                shell_words::quote(&value.to_string_lossy())
+            ))
+        });
+        let words = std::iter::once(cmd.get_program())
+            .chain(cmd.get_args())
+            .map(|word| shell_words::quote(&word.to_string_lossy()).into_owned());
+        envs.chain(words).collect::<Vec<_>>().join(" ")
+    }
 }
 
 #[cfg(test)]
@@ -408,4 +537,37 @@ mod tests {
             "lusid -a -b"
         )
     }
+
+    #[test]
+    fn test_sudo_as() {
+        let mut cmd = Command::new("git");
+        cmd.arg("status");
+        assert_eq!(
+            cmd.sudo_as(Some("me"), Some("staff")).to_string(),
+            "sudo -n -u me -g staff git status"
+        )
+    }
+
+    #[test]
+    fn test_to_shell_quotes_words() {
+        let mut cmd = Command::new("sh");
+        cmd.env("LANG", "C").arg("-c").arg("echo 'hi' && true");
+        assert_eq!(cmd.to_shell(), r#"LANG=C sh -c 'echo '\''hi'\'' && true'"#)
+    }
+
+    #[tokio::test]
+    async fn test_output_checked_fails_on_exit_status() {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg("exit 3")
+            .output_checked()
+            .await
+            .unwrap();
+        let error = output.status.await.unwrap_err();
+        assert_eq!(error.to_string(), "command failed: sh -c exit 3");
+        assert_eq!(exit_code(&error), Some(3));
+
+        let output = Command::new("true").output_checked().await.unwrap();
+        assert!(output.status.await.unwrap().success());
+    }
 }
@@ -2,14 +2,17 @@
 //!
 //! A [`Context`] bundles things every stage needs: the plan root directory (used to
 //! resolve `HostPath` params relative to the source file), platform-specific data/cache
-//! paths ([`Paths`]), and a reusable HTTP client. Construct once per run and hand it
-//! down; prefer adding fields here over threading new arguments everywhere.
+//! paths ([`Paths`]), a reusable HTTP client and the host's artifact cache. Construct
+//! once per run and hand it down; prefer adding fields here over threading new
+//! arguments everywhere.
 
 mod paths;
 
 use std::path::{Path, PathBuf};
 
-use lusid_http::{HttpClient, HttpError};
+use lusid_http::{ArtifactCache, HttpClient, HttpError};
+
+pub use lusid_http::DownloadLimits;
 use lusid_secrets::Secrets;
 use thiserror::Error;
 
@@ -31,6 +34,7 @@ pub struct Context {
     root: PathBuf,
     paths: Paths,
     http: HttpClient,
+    artifacts: ArtifactCache,
     secrets: Secrets,
 }
 
@@ -38,10 +42,12 @@ impl Context {
     pub fn create(root: &Path) -> Result<Self, ContextError> {
         let paths = Paths::create()?;
         let http = HttpClient::new()?;
+        let artifacts = artifact_cache(&paths, &http);
         Ok(Self {
             root: root.to_path_buf(),
             paths,
             http,
+            artifacts,
             secrets: Secrets::empty(),
         })
     }
@@ -58,6 +64,21 @@ impl Context {
         &mut self.http
     }
 
+    pub fn artifact_cache(&self) -> &ArtifactCache {
+        &self.artifacts
+    }
+
+    pub fn download_limits(&self) -> DownloadLimits {
+        self.http.limits()
+    }
+
+    /// Throttle every download made through this context, including the
+    /// artifact cache's.
+    pub fn set_download_limits(&mut self, limits: DownloadLimits) {
+        self.http = self.http.clone().with_limits(limits);
+        self.artifacts = artifact_cache(&self.paths, &self.http);
+    }
+
     pub fn secrets(&self) -> &Secrets {
         &self.secrets
     }
@@ -66,3 +87,7 @@ impl Context {
         self.secrets = secrets;
     }
 }
+
+fn artifact_cache(paths: &Paths, http: &HttpClient) -> ArtifactCache {
+    ArtifactCache::new(paths.cache_dir().join("artifacts"), http.clone())
+}
@@ -8,27 +8,58 @@
 //! - [`write_file_atomic`] / [`copy_file_atomic`]: write to a sibling temp file, copy
 //!   destination metadata (or source metadata, respectively), then rename. This means
 //!   readers never observe a half-written file.
+//! - [`file_equals`] / [`files_equal`]: compare contents a chunk at a time, so a
+//!   multi-hundred-MB file is never read into memory whole.
 //! - [`change_owner`] / [`change_owner_by_id`]: uid/gid changes, Unix-only.
-//! - [`copy_dir`]: shells out to `cp --recursive` (see the note on the function for
-//!   portability caveats).
+//! - [`copy_dir`]: shells out to `cp --recursive` on Unix (see the note on the
+//!   function for portability caveats).
+//!
+//! Reading, writing, copying, renaming and removing files and directories work on
+//! every platform. Modes, owners and symlinks are Unix-only: elsewhere their
+//! functions fail with [`FsError::Unsupported`], and [`CAPABILITIES`] says up front
+//! which are available, so plan evaluation, check mode and file contents still work
+//! on Windows.
 //
 // TODO(cc): like `lusid-cmd`, this crate relies on tokio features (`fs`, `io-util`,
 // `process`) that are enabled transitively via the workspace rather than declared in
 // its own `Cargo.toml`. `cargo check -p lusid-fs` in isolation fails. Declare the
 // needed tokio features locally.
 
+#[cfg(unix)]
 use nix::unistd::{Group, User};
+#[cfg(unix)]
 use std::os::unix::fs::{MetadataExt, PermissionsExt};
 use std::path::{Path, PathBuf};
+#[cfg(unix)]
 use std::process::Stdio;
 use std::time::SystemTime;
 
 use filetime::FileTime;
 use thiserror::Error;
 use tokio::fs::{self};
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+#[cfg(unix)]
 use tokio::process::Command;
 
+/// What the filesystem supports on this platform, beyond reading, writing,
+/// copying, renaming and removing, which work everywhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Permission bits: [`get_mode`], [`change_mode`] and [`set_umask`].
+    pub modes: bool,
+    /// Owners: [`get_owner_user`], [`get_owner_group`] and [`change_owner`].
+    pub owners: bool,
+    /// [`create_symlink_atomic`].
+    pub symlinks: bool,
+}
+
+/// The [`Capabilities`] of the platform this was built for.
+pub const CAPABILITIES: Capabilities = Capabilities {
+    modes: cfg!(unix),
+    owners: cfg!(unix),
+    symlinks: cfg!(unix),
+};
+
 #[derive(Error, Debug)]
 pub enum FsError {
     #[error("Cannot create directory '{path}': {source}")]
@@ -109,6 +140,7 @@ pub enum FsError {
         source: std::io::Error,
     },
 
+    #[cfg(unix)]
     #[error("Failed to get user from name: {user}")]
     UserFromName {
         user: String,
@@ -116,6 +148,7 @@ pub enum FsError {
         source: nix::Error,
     },
 
+    #[cfg(unix)]
     #[error("Failed to get user from uid: {uid}")]
     UserFromUid {
         uid: u32,
@@ -126,6 +159,7 @@ pub enum FsError {
     #[error("User not found: {user}")]
     UserNotFound { user: String },
 
+    #[cfg(unix)]
     #[error("Failed to get group from name: {group}")]
     GroupFromName {
         group: String,
@@ -133,6 +167,7 @@ pub enum FsError {
         source: nix::Error,
     },
 
+    #[cfg(unix)]
     #[error("Failed to get group from gid: {gid}")]
     GroupFromGid {
         gid: u32,
@@ -224,6 +259,12 @@ pub enum FsError {
         #[source]
         source: std::io::Error,
     },
+
+    #[error("Cannot {operation} '{path}': not supported on this platform")]
+    Unsupported {
+        operation: &'static str,
+        path: PathBuf,
+    },
 }
 
 pub async fn create_dir<P: AsRef<Path>>(path: P) -> Result<(), FsError> {
@@ -238,10 +279,9 @@ pub async fn create_dir<P: AsRef<Path>>(path: P) -> Result<(), FsError> {
 
 /// Recursively copy a directory tree.
 ///
-/// Note(cc): shells out to `cp --recursive`, which is GNU coreutils — BSD `cp` on
-/// macOS uses `-R` instead, and Windows has no `cp` at all. If we ever care about
-/// non-Linux targets, swap to a Rust-native walker (e.g. the `walkdir` + manual copy
-/// pattern, or the `fs_extra` crate).
+/// Note(cc): on Unix, shells out to `cp --recursive`, which is GNU coreutils — BSD
+/// `cp` on macOS uses `-R` instead. Elsewhere it walks the tree itself, copying
+/// contents but not permissions; if that walker proves itself, use it on Unix too.
 ///
 /// Note(cc): callers must ensure `to` does not exist. GNU `cp -r src dst` when
 /// `dst` already exists creates `dst/<basename of src>` (a nested copy) instead
@@ -252,6 +292,7 @@ pub async fn create_dir<P: AsRef<Path>>(path: P) -> Result<(), FsError> {
 /// the happy path. A future caller that bypasses that probe will need to
 /// rmdir `to` first or this helper should grow a "remove destination first"
 /// option.
+#[cfg(unix)]
 pub async fn copy_dir<F: AsRef<Path>, T: AsRef<Path>>(from: F, to: T) -> Result<(), FsError> {
     let from_path = from.as_ref();
     let to_path = to.as_ref();
@@ -287,6 +328,38 @@ pub async fn copy_dir<F: AsRef<Path>, T: AsRef<Path>>(from: F, to: T) -> Result<
     }
 }
 
+#[cfg(not(unix))]
+pub async fn copy_dir<F: AsRef<Path>, T: AsRef<Path>>(from: F, to: T) -> Result<(), FsError> {
+    let mut pending = vec![(from.as_ref().to_path_buf(), to.as_ref().to_path_buf())];
+    while let Some((from, to)) = pending.pop() {
+        create_dir(&to).await?;
+        for entry in read_dir(&from).await? {
+            let Some(name) = entry.file_name() else {
+                continue;
+            };
+            let target = to.join(name);
+            let metadata = fs::metadata(&entry)
+                .await
+                .map_err(|source| FsError::Metadata {
+                    path: entry.clone(),
+                    source,
+                })?;
+            if metadata.is_dir() {
+                pending.push((entry, target));
+            } else {
+                fs::copy(&entry, &target)
+                    .await
+                    .map_err(|source| FsError::CopyFile {
+                        from: entry.clone(),
+                        to: target.clone(),
+                        source,
+                    })?;
+            }
+        }
+    }
+    Ok(())
+}
+
 pub async fn read_dir<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>, FsError> {
     let p = path.as_ref();
     let mut dir = fs::read_dir(p).await.map_err(|source| FsError::ReadDir {
@@ -335,6 +408,7 @@ pub async fn setup_directory_access<P: AsRef<Path>>(path: P) -> Result<(), FsErr
     Ok(())
 }
 
+#[cfg(unix)]
 pub async fn get_mode<P: AsRef<Path>>(path: P) -> Result<u32, FsError> {
     let p = path.as_ref();
     let metadata = fs::metadata(p).await.map_err(|source| FsError::Metadata {
@@ -344,6 +418,26 @@ pub async fn get_mode<P: AsRef<Path>>(path: P) -> Result<u32, FsError> {
     Ok(metadata.permissions().mode())
 }
 
+#[cfg(not(unix))]
+pub async fn get_mode<P: AsRef<Path>>(path: P) -> Result<u32, FsError> {
+    Err(unsupported("read the mode of", path))
+}
+
+/// Set the process umask to `mask`, returning the previous one. It's
+/// process-wide, so it holds for everything created afterwards: files
+/// written here and those written by child processes, which inherit it.
+#[cfg(unix)]
+pub fn set_umask(mask: u32) -> u32 {
+    nix::sys::stat::umask(nix::sys::stat::Mode::from_bits_truncate(mask)).bits()
+}
+
+/// There's no umask to set without Unix modes, so this does nothing.
+#[cfg(not(unix))]
+pub fn set_umask(_mask: u32) -> u32 {
+    0
+}
+
+#[cfg(unix)]
 pub async fn change_mode<P: AsRef<Path>>(path: P, mode: u32) -> Result<(), FsError> {
     let p = path.as_ref();
     let mut permissions = fs::metadata(p)
@@ -364,6 +458,11 @@ pub async fn change_mode<P: AsRef<Path>>(path: P, mode: u32) -> Result<(), FsErr
     Ok(())
 }
 
+#[cfg(not(unix))]
+pub async fn change_mode<P: AsRef<Path>>(path: P, _mode: u32) -> Result<(), FsError> {
+    Err(unsupported("change the mode of", path))
+}
+
 #[cfg(unix)]
 pub async fn change_owner_by_id<P: AsRef<Path>>(
     path: P,
@@ -424,24 +523,67 @@ pub async fn change_owner<P: AsRef<Path>>(
     change_owner_by_id(path, uid, gid).await
 }
 
-pub async fn get_owner_user<P: AsRef<Path>>(path: P) -> Result<Option<User>, FsError> {
+#[cfg(not(unix))]
+pub async fn change_owner_by_id<P: AsRef<Path>>(
+    path: P,
+    _uid: Option<u32>,
+    _gid: Option<u32>,
+) -> Result<(), FsError> {
+    Err(unsupported("change the owner of", path))
+}
+
+#[cfg(not(unix))]
+pub async fn change_owner<P: AsRef<Path>>(
+    path: P,
+    _user: Option<&str>,
+    _group: Option<&str>,
+) -> Result<(), FsError> {
+    Err(unsupported("change the owner of", path))
+}
+
+/// The name of the user owning `path`, or `None` if its uid has no user.
+#[cfg(unix)]
+pub async fn get_owner_user<P: AsRef<Path>>(path: P) -> Result<Option<String>, FsError> {
     let p = path.as_ref();
     let metadata = fs::metadata(p).await.map_err(|source| FsError::Metadata {
         path: p.to_path_buf(),
         source,
     })?;
     let uid = metadata.uid();
-    User::from_uid(uid.into()).map_err(|source| FsError::UserFromUid { uid, source })
+    let user = User::from_uid(uid.into()).map_err(|source| FsError::UserFromUid { uid, source })?;
+    Ok(user.map(|user| user.name))
+}
+
+#[cfg(not(unix))]
+pub async fn get_owner_user<P: AsRef<Path>>(path: P) -> Result<Option<String>, FsError> {
+    Err(unsupported("read the owner of", path))
 }
 
-pub async fn get_owner_group<P: AsRef<Path>>(path: P) -> Result<Option<Group>, FsError> {
+/// The name of the group owning `path`, or `None` if its gid has no group.
+#[cfg(unix)]
+pub async fn get_owner_group<P: AsRef<Path>>(path: P) -> Result<Option<String>, FsError> {
     let p = path.as_ref();
     let metadata = fs::metadata(p).await.map_err(|source| FsError::Metadata {
         path: p.to_path_buf(),
         source,
     })?;
     let gid = metadata.gid();
-    Group::from_gid(gid.into()).map_err(|source| FsError::GroupFromGid { gid, source })
+    let group =
+        Group::from_gid(gid.into()).map_err(|source| FsError::GroupFromGid { gid, source })?;
+    Ok(group.map(|group| group.name))
+}
+
+#[cfg(not(unix))]
+pub async fn get_owner_group<P: AsRef<Path>>(path: P) -> Result<Option<String>, FsError> {
+    Err(unsupported("read the group of", path))
+}
+
+#[cfg(not(unix))]
+fn unsupported(operation: &'static str, path: impl AsRef<Path>) -> FsError {
+    FsError::Unsupported {
+        operation,
+        path: path.as_ref().to_path_buf(),
+    }
 }
 
 pub async fn create_file<P: AsRef<Path>>(path: P) -> Result<tokio::fs::File, FsError> {
@@ -538,6 +680,7 @@ pub async fn copy_metadata<Src: AsRef<Path>, Dest: AsRef<Path>>(
         })?;
 
     // Copy ownership
+    #[cfg(unix)]
     change_owner_by_id(dest, Some(src_metadata.uid()), Some(src_metadata.gid())).await?;
 
     // Copy file times
@@ -571,6 +714,86 @@ pub async fn read_file_to_bytes<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, FsEr
     })
 }
 
+/// Whether the file at `path` contains exactly `expected`. Reads the file a
+/// chunk at a time, stopping at the first difference.
+pub async fn file_equals<P: AsRef<Path>>(path: P, expected: &[u8]) -> Result<bool, FsError> {
+    let p = path.as_ref();
+    if file_size(p).await? != expected.len() as u64 {
+        return Ok(false);
+    }
+    let mut file = open_file(p).await?;
+    let mut buffer = vec![0; COMPARE_CHUNK_BYTES];
+    let mut offset = 0;
+    loop {
+        let read = read_chunk(&mut file, &mut buffer, p).await?;
+        if read == 0 {
+            return Ok(offset == expected.len());
+        }
+        if expected.get(offset..offset + read) != Some(&buffer[..read]) {
+            return Ok(false);
+        }
+        offset += read;
+    }
+}
+
+/// Whether the files at `a` and `b` have the same contents. Reads both a
+/// chunk at a time, stopping at the first difference.
+pub async fn files_equal<A: AsRef<Path>, B: AsRef<Path>>(a: A, b: B) -> Result<bool, FsError> {
+    let (a, b) = (a.as_ref(), b.as_ref());
+    if file_size(a).await? != file_size(b).await? {
+        return Ok(false);
+    }
+    let (mut a_file, mut b_file) = (open_file(a).await?, open_file(b).await?);
+    let mut a_buffer = vec![0; COMPARE_CHUNK_BYTES];
+    let mut b_buffer = vec![0; COMPARE_CHUNK_BYTES];
+    loop {
+        let a_read = read_chunk(&mut a_file, &mut a_buffer, a).await?;
+        let b_read = read_chunk(&mut b_file, &mut b_buffer, b).await?;
+        if a_buffer[..a_read] != b_buffer[..b_read] {
+            return Ok(false);
+        }
+        if a_read == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+const COMPARE_CHUNK_BYTES: usize = 64 * 1024;
+
+async fn file_size(path: &Path) -> Result<u64, FsError> {
+    fs::metadata(path)
+        .await
+        .map(|metadata| metadata.len())
+        .map_err(|source| FsError::Metadata {
+            path: path.to_path_buf(),
+            source,
+        })
+}
+
+/// Fill `buffer` from `file`, short only at end of file, so two files read
+/// side by side stay aligned.
+async fn read_chunk(
+    file: &mut tokio::fs::File,
+    buffer: &mut [u8],
+    path: &Path,
+) -> Result<usize, FsError> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        let read = file
+            .read(&mut buffer[filled..])
+            .await
+            .map_err(|source| FsError::ReadFile {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        if read == 0 {
+            break;
+        }
+        filled += read;
+    }
+    Ok(filled)
+}
+
 /// Atomically copy `from` → `to`, preserving the source's permissions/owner/times.
 ///
 /// Unlike [`write_file_atomic`], this copies metadata from the *source* (since the
@@ -667,6 +890,7 @@ pub async fn remove_file<P: AsRef<Path>>(path: P) -> Result<(), FsError> {
 /// silently produces a dangling symlink. If dangling-link confusion ever
 /// surfaces in practice, add an `lstat(from)` here and surface a dedicated
 /// `FsError::SymlinkSourceMissing`.
+#[cfg(unix)]
 pub async fn create_symlink_atomic<F: AsRef<Path>, T: AsRef<Path>>(
     from: F,
     to: T,
@@ -689,6 +913,16 @@ pub async fn create_symlink_atomic<F: AsRef<Path>, T: AsRef<Path>>(
     Ok(())
 }
 
+// Note(cc): Windows has symlinks, but creating one needs Developer Mode or an
+// elevated process, and a different call for files and directories.
+#[cfg(not(unix))]
+pub async fn create_symlink_atomic<F: AsRef<Path>, T: AsRef<Path>>(
+    _from: F,
+    to: T,
+) -> Result<(), FsError> {
+    Err(unsupported("create a symlink at", to))
+}
+
 /// Atomically probe `path`: returns the symlink target if `path` is a
 /// symlink, [`SymlinkTarget::NotASymlink`] if it exists but is something else
 /// (regular file, directory, …), or [`SymlinkTarget::Missing`] if it doesn't
@@ -698,8 +932,9 @@ pub async fn create_symlink_atomic<F: AsRef<Path>, T: AsRef<Path>>(
 /// widens the window for a stale answer — never produces a confusing
 /// "Cannot read symlink" error for something that lstat'd as a symlink and
 /// then got replaced before the readlink. We translate `EINVAL` from
-/// `readlink` (path is no longer a symlink) into `NotASymlink` for that
-/// reason.
+/// `readlink` (path is no longer a symlink, which std reports as
+/// [`InvalidInput`](std::io::ErrorKind::InvalidInput)) into `NotASymlink`
+/// for that reason.
 pub async fn probe_symlink<P: AsRef<Path>>(path: P) -> Result<SymlinkTarget, FsError> {
     let p = path.as_ref();
     let metadata = match fs::symlink_metadata(p).await {
@@ -719,7 +954,9 @@ pub async fn probe_symlink<P: AsRef<Path>>(path: P) -> Result<SymlinkTarget, FsE
     }
     match fs::read_link(p).await {
         Ok(target) => Ok(SymlinkTarget::Symlink(target)),
-        Err(err) if err.raw_os_error() == Some(nix::libc::EINVAL) => Ok(SymlinkTarget::NotASymlink),
+        Err(err) if err.kind() == std::io::ErrorKind::InvalidInput => {
+            Ok(SymlinkTarget::NotASymlink)
+        }
         Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(SymlinkTarget::Missing),
         Err(source) => Err(FsError::ReadSymlink {
             path: p.to_path_buf(),
@@ -754,6 +991,28 @@ mod tests {
     use super::*;
     use tempfile::tempdir;
 
+    #[tokio::test]
+    async fn files_are_compared_by_contents() {
+        let dir = tempdir().unwrap();
+        let contents = vec![7; 2 * COMPARE_CHUNK_BYTES + 1];
+        let mut changed = contents.clone();
+        *changed.last_mut().unwrap() = 8;
+        let (a, b, c) = (
+            dir.path().join("a"),
+            dir.path().join("b"),
+            dir.path().join("c"),
+        );
+        write_file(&a, &contents).await.unwrap();
+        write_file(&b, &contents).await.unwrap();
+        write_file(&c, &changed).await.unwrap();
+
+        assert!(files_equal(&a, &b).await.unwrap());
+        assert!(!files_equal(&a, &c).await.unwrap());
+        assert!(!file_equals(&c, &contents).await.unwrap());
+        assert!(!file_equals(&a, &contents[1..]).await.unwrap());
+    }
+
+    #[cfg(unix)]
     #[tokio::test]
     async fn create_symlink_atomic_creates_when_destination_is_missing() {
         let dir = tempdir().unwrap();
@@ -769,6 +1028,7 @@ mod tests {
         );
     }
 
+    #[cfg(unix)]
     #[tokio::test]
     async fn create_symlink_atomic_replaces_an_existing_regular_file() {
         let dir = tempdir().unwrap();
@@ -785,6 +1045,7 @@ mod tests {
         );
     }
 
+    #[cfg(unix)]
     #[tokio::test]
     async fn create_symlink_atomic_replaces_a_stale_symlink() {
         let dir = tempdir().unwrap();
@@ -807,6 +1068,7 @@ mod tests {
         );
     }
 
+    #[cfg(unix)]
     #[tokio::test]
     async fn create_symlink_atomic_fails_when_destination_is_a_non_empty_dir() {
         // Non-empty directory at `to` makes `rename(2)` fail with
@@ -825,6 +1087,7 @@ mod tests {
         assert!(matches!(err, FsError::RenameFile { .. }));
     }
 
+    #[cfg(unix)]
     #[tokio::test]
     async fn create_symlink_atomic_fails_when_destination_is_an_empty_dir() {
         // Linux's `rename(2)` rejects renaming a non-directory over a
@@ -840,6 +1103,7 @@ mod tests {
         assert!(matches!(err, FsError::RenameFile { .. }));
     }
 
+    #[cfg(unix)]
     #[tokio::test]
     async fn probe_symlink_classifies_files_links_and_missing() {
         let dir = tempdir().unwrap();
@@ -863,6 +1127,7 @@ mod tests {
         );
     }
 
+    #[cfg(unix)]
     #[tokio::test]
     async fn probe_symlink_handles_dangling_symlink() {
         // `lstat` returns the symlink itself, regardless of whether the
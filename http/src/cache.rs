@@ -0,0 +1,183 @@
+//! Host-local cache of downloaded artifacts.
+//!
+//! Every run on a host shares one [`ArtifactCache`] under the XDG cache dir,
+//! so when several machines (or repeated runs) need the same artifact it's
+//! downloaded once and copied from the cache after that.
+//!
+//! Entries are keyed by URL plus the expected [`Checksum`]. An entry with a
+//! checksum is reused as-is, since its content is pinned; one without is
+//! downloaded again on every fetch, as the URL may serve something new, and
+//! only the latest copy is kept. Apt repository and gpg keys are fetched
+//! here, pinned by the plan's SHA-256 if it has one, and so are VM images,
+//! pinned by the digest their distro's SUMS file lists.
+//!
+//! Note(cc): `@core/git` clones go through git itself, so aren't cached
+//! here; a shared mirror would be the git equivalent.
+//!
+//! Every `lusid-apply` on a host shares the cache, as do the resources of one
+//! epoch, which are applied concurrently. A fetch holds the entry's
+//! [`StoreLock`] while it downloads, so a second fetch of the same entry
+//! waits for the first, then uses what it cached.
+
+use std::path::{Path, PathBuf};
+
+use lusid_fs::{self as fs};
+use lusid_store::StoreLock;
+use sha2::{Digest, Sha256, Sha512};
+use tokio::io::AsyncReadExt;
+use tracing::debug;
+
+use crate::{HttpClient, HttpError, with_added_extension};
+
+/// The content an artifact must have, as a hex digest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum<'a> {
+    Sha256(&'a str),
+    Sha512(&'a str),
+}
+
+impl Checksum<'_> {
+    fn algorithm(&self) -> &'static str {
+        match self {
+            Checksum::Sha256(_) => "sha256",
+            Checksum::Sha512(_) => "sha512",
+        }
+    }
+
+    fn hex(&self) -> &str {
+        match self {
+            Checksum::Sha256(hex) | Checksum::Sha512(hex) => hex,
+        }
+    }
+
+    async fn of_file(&self, path: &Path) -> Result<String, HttpError> {
+        match self {
+            Checksum::Sha256(_) => digest_file::<Sha256>(path).await,
+            Checksum::Sha512(_) => digest_file::<Sha512>(path).await,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ArtifactCache {
+    dir: PathBuf,
+    http: HttpClient,
+}
+
+impl ArtifactCache {
+    pub fn new(dir: PathBuf, http: HttpClient) -> Self {
+        Self { dir, http }
+    }
+
+    /// Where the artifact at `url` with content `checksum` lives in the cache.
+    pub fn path(&self, url: &str, checksum: Option<Checksum<'_>>) -> PathBuf {
+        let mut hasher = Sha256::new();
+        hasher.update(url.as_bytes());
+        if let Some(checksum) = checksum {
+            hasher.update([0]);
+            hasher.update(checksum.algorithm().as_bytes());
+            hasher.update([0]);
+            hasher.update(checksum.hex().to_ascii_lowercase().as_bytes());
+        }
+        self.dir.join(hex(&hasher.finalize()))
+    }
+
+    /// Fetch the artifact at `url` into the cache, returning its path there.
+    ///
+    /// With a `checksum`, a cached copy is used without downloading, and a
+    /// download that doesn't match is discarded with
+    /// [`HttpError::ChecksumMismatch`].
+    pub async fn fetch(
+        &self,
+        url: &str,
+        checksum: Option<Checksum<'_>>,
+    ) -> Result<PathBuf, HttpError> {
+        let path = self.path(url, checksum);
+        if checksum.is_some() && fs::path_exists(&path).await? {
+            debug!(url, path = %path.display(), "artifact cache hit");
+            return Ok(path);
+        }
+
+        let _lock = StoreLock::acquire(with_added_extension(&path, "lock")).await?;
+        // Whoever held the lock before may have just cached it.
+        if checksum.is_some() && fs::path_exists(&path).await? {
+            debug!(url, path = %path.display(), "artifact cache hit");
+            return Ok(path);
+        }
+        let download_path = with_added_extension(&path, "download");
+        if fs::path_exists(&download_path).await? {
+            fs::remove_file(&download_path).await?;
+        }
+        self.http.download_file(url, &download_path).await?;
+
+        if let Some(expected) = checksum {
+            let actual = expected.of_file(&download_path).await?;
+            if !actual.eq_ignore_ascii_case(expected.hex()) {
+                fs::remove_file(&download_path).await?;
+                return Err(HttpError::ChecksumMismatch {
+                    url: url.to_owned(),
+                    algorithm: expected.algorithm(),
+                    expected: expected.hex().to_owned(),
+                    actual,
+                });
+            }
+        }
+
+        fs::rename_file(&download_path, &path).await?;
+        debug!(url, path = %path.display(), "artifact cached");
+        Ok(path)
+    }
+}
+
+async fn digest_file<D: Digest>(path: &Path) -> Result<String, HttpError> {
+    let mut file = fs::open_file(path).await?;
+    let mut hasher = D::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .await
+            .map_err(|source| HttpError::Read {
+                path: path.to_owned(),
+                source,
+            })?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hex(&hasher.finalize()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache() -> ArtifactCache {
+        ArtifactCache::new(PathBuf::from("/cache"), HttpClient::new().unwrap())
+    }
+
+    #[test]
+    fn entries_are_keyed_by_url_and_checksum() {
+        let cache = cache();
+        let url = "https://example.com/key.asc";
+        let unpinned = cache.path(url, None);
+        let pinned = cache.path(url, Some(Checksum::Sha256("ABCD")));
+
+        assert!(unpinned.starts_with("/cache"));
+        assert_ne!(unpinned, pinned);
+        assert_eq!(pinned, cache.path(url, Some(Checksum::Sha256("abcd"))));
+        assert_ne!(pinned, cache.path(url, Some(Checksum::Sha512("abcd"))));
+        assert_ne!(
+            pinned,
+            cache.path(
+                "https://example.com/other.asc",
+                Some(Checksum::Sha256("abcd"))
+            )
+        );
+    }
+}
@@ -3,16 +3,33 @@
 //! Wraps `reqwest` with sensible defaults (gzip/brotli, read timeout) and exposes
 //! a streaming `download_file` that writes through a `.tmp` sidecar and renames on
 //! success — so partial downloads never appear as completed files.
+//!
+//! [`ArtifactCache`] keeps downloads on the host, to share between runs (see
+//! [`cache`]), and [`DownloadLimits`] throttle them (see [`throttle`]).
+//!
+//! [`HttpClient::post_json`] is the one request that sends rather than
+//! fetches, for `lusid`'s webhook hooks.
+
+mod cache;
+mod throttle;
+
+pub use crate::cache::{ArtifactCache, Checksum};
+pub use crate::throttle::DownloadLimits;
 
 use std::ffi::OsString;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use lusid_fs::{self as fs, FsError};
+use lusid_store::StoreError;
 use reqwest::Client;
 use thiserror::Error;
 use tokio::io::AsyncWriteExt;
+use tokio::sync::Semaphore;
 use tokio_stream::StreamExt;
 
+use crate::throttle::RateLimiter;
+
 const REQUEST_TIMEOUT_SEC: u64 = 10;
 
 #[derive(Error, Debug)]
@@ -32,13 +49,33 @@ pub enum HttpError {
         source: std::io::Error,
     },
 
+    #[error("File read error for '{path}': {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Checksum mismatch for '{url}': expected {algorithm} {expected}, got {actual}")]
+    ChecksumMismatch {
+        url: String,
+        algorithm: &'static str,
+        expected: String,
+        actual: String,
+    },
+
     #[error(transparent)]
     Fs(#[from] FsError),
+
+    #[error(transparent)]
+    Store(#[from] StoreError),
 }
 
 #[derive(Debug, Clone)]
 pub struct HttpClient {
     client: Client,
+    limits: DownloadLimits,
+    slots: Option<Arc<Semaphore>>,
+    rate: Option<Arc<RateLimiter>>,
 }
 
 impl HttpClient {
@@ -49,7 +86,31 @@ impl HttpClient {
             .brotli(true)
             .build()
             .map_err(HttpError::BuildClient)?;
-        Ok(HttpClient { client })
+        Ok(HttpClient {
+            client,
+            limits: DownloadLimits::default(),
+            slots: None,
+            rate: None,
+        })
+    }
+
+    /// Throttle downloads to `limits`, shared by every clone of the returned
+    /// client.
+    pub fn with_limits(self, limits: DownloadLimits) -> Self {
+        Self {
+            client: self.client,
+            limits,
+            slots: limits
+                .max_parallel
+                .map(|max| Arc::new(Semaphore::new(max.max(1)))),
+            rate: limits
+                .max_kib_per_sec
+                .map(|max| Arc::new(RateLimiter::new(max))),
+        }
+    }
+
+    pub fn limits(&self) -> DownloadLimits {
+        self.limits
     }
 
     #[allow(dead_code)]
@@ -73,7 +134,9 @@ impl HttpClient {
     /// If `file_path` already exists, this is a no-op — the URL is trusted to be
     /// content-stable. The download is staged to a `.tmp` sidecar and atomically
     /// renamed on success, so interrupted runs don't leave a half-written file
-    /// masquerading as complete.
+    /// masquerading as complete. Waits its turn if
+    /// [`max_parallel`](DownloadLimits::max_parallel) downloads are already
+    /// running.
     ///
     /// Note(cc): no retry, resume, or content verification (checksum, etag) yet.
     /// If the URL changes under us, we'll silently use the stale local copy.
@@ -92,6 +155,12 @@ impl HttpClient {
             fs::remove_file(&temp_file).await?;
         }
 
+        // Never closed, so acquiring can't fail.
+        let _slot = match &self.slots {
+            Some(slots) => slots.acquire().await.ok(),
+            None => None,
+        };
+
         let resp = self
             .client
             .get(url)
@@ -104,6 +173,9 @@ impl HttpClient {
 
         while let Some(chunk) = stream.next().await {
             let bytes = chunk.map_err(HttpError::Stream)?;
+            if let Some(rate) = &self.rate {
+                rate.consume(bytes.len()).await;
+            }
             file.write_all(&bytes)
                 .await
                 .map_err(|source| HttpError::Write {
@@ -132,6 +204,20 @@ impl HttpClient {
             .await
             .map_err(HttpError::Request)
     }
+
+    /// POST a JSON document to `url`, failing on an error status. Errors
+    /// leave out the URL, as a webhook's carries its token.
+    pub async fn post_json(&self, url: &str, body: String) -> Result<(), HttpError> {
+        self.client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|error| HttpError::Request(error.without_url()))?;
+        Ok(())
+    }
 }
 
 // Produce "<orig_ext>.<added>" if an extension exists, otherwise "added".
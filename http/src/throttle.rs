@@ -0,0 +1,80 @@
+//! Download throttling, so an apply across a fleet doesn't saturate a shared
+//! uplink.
+//!
+//! [`DownloadLimits`] caps how many downloads an [`HttpClient`](crate::HttpClient)
+//! runs at once and how fast they may go in total. The rate is enforced by
+//! scheduling: each chunk read reserves its share of the budget, and the
+//! reader sleeps until that share would have been spent at the limit.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// Limits on an [`HttpClient`](crate::HttpClient)'s downloads. `None` is
+/// unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DownloadLimits {
+    /// Most downloads to run at once.
+    pub max_parallel: Option<usize>,
+
+    /// Most KiB per second, shared by all downloads at once.
+    pub max_kib_per_sec: Option<u64>,
+}
+
+/// Shared budget of bytes per second.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    bytes_per_sec: u64,
+    next: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(kib_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec: kib_per_sec.max(1) * 1024,
+            next: Mutex::new(None),
+        }
+    }
+
+    /// Wait until `bytes` more have been read within the rate.
+    pub(crate) async fn consume(&self, bytes: usize) {
+        let until = self.reserve(bytes, Instant::now());
+        tokio::time::sleep_until(until).await;
+    }
+
+    /// Reserve `bytes` of the budget, returning when they're paid for. Time
+    /// the budget went unused is forgotten, so a pause doesn't build up a
+    /// burst.
+    fn reserve(&self, bytes: usize, now: Instant) -> Instant {
+        let mut next = self.next.lock().unwrap_or_else(|error| error.into_inner());
+        let start = next.map_or(now, |next| next.max(now));
+        let until = start + Duration::from_secs_f64(bytes as f64 / self.bytes_per_sec as f64);
+        *next = Some(until);
+        until
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reservations_queue_behind_each_other() {
+        let limiter = RateLimiter::new(1);
+        let now = Instant::now();
+
+        assert_eq!(limiter.reserve(1024, now), now + Duration::from_secs(1));
+        assert_eq!(limiter.reserve(512, now), now + Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn unused_budget_is_not_saved_up() {
+        let limiter = RateLimiter::new(1);
+        let now = Instant::now();
+        limiter.reserve(1024, now);
+
+        let later = now + Duration::from_secs(10);
+        assert_eq!(limiter.reserve(1024, later), later + Duration::from_secs(1));
+    }
+}
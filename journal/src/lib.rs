@@ -0,0 +1,443 @@
+//! The operation journal: every operation an apply ran, kept on the machine
+//! it ran on, so a failed apply can be audited after the fact and, later,
+//! resumed.
+//!
+//! Each apply that gets as far as running operations writes one journal,
+//! `<data_dir>/journal/<journal_id>.jsonl` (see [`Paths`]), where the id is
+//! the apply's start time in unix milliseconds. It holds one
+//! [`JournalRecord`] per line: an [`ApplyStart`](JournalRecord::ApplyStart),
+//! an [`Operation`](JournalRecord::Operation) as each operation ends, and an
+//! [`ApplyComplete`](JournalRecord::ApplyComplete). Each line is flushed as
+//! it's written, so a journal without an `ApplyComplete` is an apply that
+//! was killed partway through.
+//!
+//! [`JournalWriter`] writes one and [`list_journals`] / [`read_journal`]
+//! read them back. Only the newest [`KEEP_JOURNALS`] are kept.
+//!
+//! Note(cc): nothing resumes an apply from its journal yet.
+//! [`Journal::applied`] is what a resume would skip, but operations aren't
+//! idempotent in general, and the plan may have changed since, so a resume
+//! should re-plan and only trust the journal for what to report.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use lusid_apply_stdio::OperationResult;
+use lusid_ctx::Paths;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+
+/// How many journals to keep; older ones are deleted as new ones start.
+pub const KEEP_JOURNALS: usize = 100;
+
+const JOURNAL_EXTENSION: &str = "jsonl";
+
+#[derive(Error, Debug)]
+pub enum JournalError {
+    #[error("failed to read journals in {path}")]
+    ReadDir {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("failed to read journal {path}")]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("failed to parse journal {path}, line {line}")]
+    Parse {
+        path: PathBuf,
+        line: usize,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error("failed to create journal {path}")]
+    Create {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("journal {journal_id} not found")]
+    NotFound { journal_id: String },
+}
+
+impl JournalError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            JournalError::ReadDir { .. } | JournalError::Read { .. } => "journal.read",
+            JournalError::Parse { .. } => "journal.parse",
+            JournalError::Create { .. } => "journal.create",
+            JournalError::NotFound { .. } => "journal.not-found",
+        }
+    }
+}
+
+/// One line of a journal. Times are unix milliseconds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JournalRecord {
+    /// The apply is about to run `operations`, rendered, by epoch.
+    ApplyStart {
+        time: u64,
+        plan: String,
+        operations: Vec<Vec<String>>,
+    },
+    /// An operation ended, after any retries, at `index` (epoch, operation)
+    /// among the epoch's merged operations.
+    Operation {
+        index: (usize, usize),
+        operation: String,
+        started: u64,
+        finished: u64,
+        result: OperationResult,
+    },
+    /// The apply ended, with its error if it failed.
+    ApplyComplete {
+        time: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+}
+
+/// Writes one apply's journal. Writes are best-effort, as for run logs — a
+/// full disk shouldn't stop an apply halfway through an epoch — so the first
+/// failure is kept and returned by [`JournalWriter::finish`], and later
+/// writes are skipped.
+#[derive(Debug)]
+pub struct JournalWriter {
+    id: String,
+    file: File,
+    error: Option<io::Error>,
+}
+
+impl JournalWriter {
+    /// Start a new journal, pruning old ones beyond [`KEEP_JOURNALS`].
+    pub async fn create(paths: &Paths) -> Result<Self, JournalError> {
+        let dir = journal_dir(paths);
+        fs::create_dir_all(&dir)
+            .await
+            .map_err(|source| JournalError::Create {
+                path: dir.clone(),
+                source,
+            })?;
+
+        let existing = journal_ids(&dir).await?;
+        let base = now_millis();
+
+        // `create_new` fails if the id is taken, e.g. by an apply started in
+        // the same millisecond.
+        let mut suffix = 0;
+        let (id, file) = loop {
+            let id = match suffix {
+                0 => base.to_string(),
+                n => format!("{base}-{n}"),
+            };
+            let path = dir.join(format!("{id}.{JOURNAL_EXTENSION}"));
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+                .await
+            {
+                Ok(file) => break (id, file),
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => suffix += 1,
+                Err(source) => return Err(JournalError::Create { path, source }),
+            }
+        };
+
+        let prune = existing.len().saturating_sub(KEEP_JOURNALS - 1);
+        for old in existing.into_iter().take(prune) {
+            // Best-effort: a journal that can't be pruned now will be next time.
+            let _ = fs::remove_file(dir.join(format!("{old}.{JOURNAL_EXTENSION}"))).await;
+        }
+
+        Ok(Self {
+            id,
+            file,
+            error: None,
+        })
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Append `record` and flush it.
+    pub async fn record(&mut self, record: &JournalRecord) {
+        if self.error.is_some() {
+            return;
+        }
+        let mut line = serde_json::to_string(record).expect("journal records serialize");
+        line.push('\n');
+        let written = async {
+            self.file.write_all(line.as_bytes()).await?;
+            self.file.flush().await
+        };
+        self.error = written.await.err();
+    }
+
+    pub async fn finish(self) -> io::Result<()> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        self.file.sync_all().await
+    }
+}
+
+/// A journal read back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Journal {
+    pub id: String,
+    pub records: Vec<JournalRecord>,
+}
+
+/// How a journaled apply ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JournalStatus {
+    Succeeded,
+    Failed {
+        error: String,
+    },
+    /// No `ApplyComplete`: the apply was killed, or is still running.
+    Unfinished,
+}
+
+impl Journal {
+    pub fn status(&self) -> JournalStatus {
+        let complete = self.records.iter().rev().find_map(|record| match record {
+            JournalRecord::ApplyComplete { error, .. } => Some(error),
+            _ => None,
+        });
+        match complete {
+            Some(None) => JournalStatus::Succeeded,
+            Some(Some(error)) => JournalStatus::Failed {
+                error: error.clone(),
+            },
+            None => JournalStatus::Unfinished,
+        }
+    }
+
+    /// The operations that ran, with how each ended.
+    pub fn operations(&self) -> impl Iterator<Item = (&str, &OperationResult)> {
+        self.records.iter().filter_map(|record| match record {
+            JournalRecord::Operation {
+                operation, result, ..
+            } => Some((operation.as_str(), result)),
+            _ => None,
+        })
+    }
+
+    /// The operations that succeeded, in the order they ran.
+    pub fn applied(&self) -> impl Iterator<Item = &str> {
+        self.operations()
+            .filter(|(_, result)| result.succeeded())
+            .map(|(operation, _)| operation)
+    }
+
+    /// The operations that failed, with their errors.
+    pub fn failed(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.operations()
+            .filter_map(|(operation, result)| Some((operation, result.error.as_deref()?)))
+    }
+}
+
+/// Where an apply's journals are kept.
+pub fn journal_dir(paths: &Paths) -> PathBuf {
+    paths.data_dir().join("journal")
+}
+
+/// Every kept journal's id, oldest first.
+pub async fn list_journals(paths: &Paths) -> Result<Vec<String>, JournalError> {
+    journal_ids(&journal_dir(paths)).await
+}
+
+/// Read the journal `journal_id`. A last line cut off partway, by an apply
+/// killed as it wrote it, is left out.
+pub async fn read_journal(paths: &Paths, journal_id: &str) -> Result<Journal, JournalError> {
+    let well_formed = journal_id.chars().all(|c| c.is_ascii_digit() || c == '-');
+    let path = journal_dir(paths).join(format!("{journal_id}.{JOURNAL_EXTENSION}"));
+    if !well_formed || !fs::try_exists(&path).await.unwrap_or(false) {
+        return Err(JournalError::NotFound {
+            journal_id: journal_id.to_owned(),
+        });
+    }
+
+    let contents = fs::read_to_string(&path)
+        .await
+        .map_err(|source| JournalError::Read {
+            path: path.clone(),
+            source,
+        })?;
+    let ends_whole = contents.ends_with('\n');
+    let lines: Vec<&str> = contents.lines().collect();
+    let mut records = Vec::with_capacity(lines.len());
+    for (number, line) in lines.iter().enumerate() {
+        let last = number + 1 == lines.len();
+        match serde_json::from_str(line) {
+            Ok(record) => records.push(record),
+            Err(_) if last && !ends_whole => break,
+            Err(source) => {
+                return Err(JournalError::Parse {
+                    path,
+                    line: number + 1,
+                    source,
+                });
+            }
+        }
+    }
+
+    Ok(Journal {
+        id: journal_id.to_owned(),
+        records,
+    })
+}
+
+/// The current time in unix milliseconds, as journal records keep it.
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| {
+            u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX)
+        })
+}
+
+/// The ids of the journals in `dir`, oldest first.
+async fn journal_ids(dir: &Path) -> Result<Vec<String>, JournalError> {
+    let mut entries = match fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(source) => {
+            return Err(JournalError::ReadDir {
+                path: dir.to_owned(),
+                source,
+            });
+        }
+    };
+
+    let mut ids = Vec::new();
+    loop {
+        let entry = entries
+            .next_entry()
+            .await
+            .map_err(|source| JournalError::ReadDir {
+                path: dir.to_owned(),
+                source,
+            })?;
+        let Some(entry) = entry else {
+            break;
+        };
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext == JOURNAL_EXTENSION) {
+            let stem = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned());
+            ids.extend(stem);
+        }
+    }
+    ids.sort_by_key(|id| journal_order(id));
+    Ok(ids)
+}
+
+/// Sort key for a journal id: its time, then its collision suffix.
+fn journal_order(id: &str) -> (u64, u64) {
+    let (time, suffix) = id.split_once('-').unwrap_or((id, "0"));
+    (time.parse().unwrap_or(0), suffix.parse().unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(dir: &Path) -> Paths {
+        Paths::new(dir.join("data"), dir.join("cache"), dir.join("runtime"))
+    }
+
+    fn operation(operation: &str, error: Option<&str>) -> JournalRecord {
+        JournalRecord::Operation {
+            index: (0, 0),
+            operation: operation.to_owned(),
+            started: 1,
+            finished: 2,
+            result: OperationResult {
+                error: error.map(str::to_owned),
+                ..OperationResult::default()
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_back_what_was_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = paths(dir.path());
+
+        let mut writer = JournalWriter::create(&paths).await.unwrap();
+        let id = writer.id().to_owned();
+        let records = vec![
+            JournalRecord::ApplyStart {
+                time: 1,
+                plan: "web.lusid".into(),
+                operations: vec![vec!["a".into(), "b".into()]],
+            },
+            operation("a", None),
+            operation("b", Some("exit status 100")),
+            JournalRecord::ApplyComplete {
+                time: 3,
+                error: Some("b failed".into()),
+            },
+        ];
+        for record in &records {
+            writer.record(record).await;
+        }
+        writer.finish().await.unwrap();
+
+        assert_eq!(list_journals(&paths).await.unwrap(), vec![id.clone()]);
+        let journal = read_journal(&paths, &id).await.unwrap();
+        assert_eq!(journal.records, records);
+        assert_eq!(
+            journal.status(),
+            JournalStatus::Failed {
+                error: "b failed".into()
+            }
+        );
+        assert_eq!(journal.applied().collect::<Vec<_>>(), vec!["a"]);
+        assert_eq!(
+            journal.failed().collect::<Vec<_>>(),
+            vec![("b", "exit status 100")]
+        );
+    }
+
+    #[tokio::test]
+    async fn skips_a_cut_off_last_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = paths(dir.path());
+
+        let mut writer = JournalWriter::create(&paths).await.unwrap();
+        let id = writer.id().to_owned();
+        writer.record(&operation("a", None)).await;
+        writer.finish().await.unwrap();
+        let path = journal_dir(&paths).join(format!("{id}.{JOURNAL_EXTENSION}"));
+        let mut contents = std::fs::read_to_string(&path).unwrap();
+        contents.push_str("{\"type\":\"operat");
+        std::fs::write(&path, contents).unwrap();
+
+        let journal = read_journal(&paths, &id).await.unwrap();
+        assert_eq!(journal.records, vec![operation("a", None)]);
+        assert_eq!(journal.status(), JournalStatus::Unfinished);
+    }
+
+    #[test]
+    fn orders_ids_by_time_then_suffix() {
+        let mut ids = vec!["200", "100-1", "100", "100-10", "100-2"];
+        ids.sort_by_key(|id| journal_order(id));
+        assert_eq!(ids, vec!["100", "100-1", "100-2", "100-10", "200"]);
+    }
+}
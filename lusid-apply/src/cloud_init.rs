@@ -0,0 +1,357 @@
+//! A plan as cloud-init user-data, for first-boot provisioning of a machine
+//! lusid then manages: the resource atoms cloud-init has a module for become
+//! a [`CloudConfig`], and everything else is listed as skipped.
+//!
+//! - `@core/group` → `groups`, `@core/user` → `users` (after `default`, so
+//!   the image's own user is kept).
+//! - `@core/file` contents, from the plan or read from this host, with their
+//!   mode and owner → `write_files`.
+//! - `@core/apt` and `@core/pacman` packages → `packages`.
+//! - `@core/command` installs → `runcmd`.
+//!
+//! Only what's present is expressed: cloud-init runs once, on a fresh
+//! machine, so removals have nothing to remove. Secrets are never written
+//! into user-data, which cloud metadata services hand to anything on the
+//! machine that asks.
+//
+// Note(cc): cloud-init runs its modules in its own order (groups and users,
+// then files, then packages, then commands), not the plan's. A command that
+// needs a file written after a package is installed, say, still works, but
+// the plan's `requires` aren't consulted.
+
+use std::collections::BTreeMap;
+
+use lusid_apply_stdio::{
+    CloudConfig, CloudGroup, CloudInitSkipped, CloudUser, CloudWriteFile, RenderedCloudInit,
+};
+use lusid_plan::Declared;
+use lusid_resource::resources::command::{CommandResource, CommandStatus};
+use lusid_resource::resources::dnf::DnfResource;
+use lusid_resource::resources::file::FileResource;
+use lusid_resource::resources::group::GroupResource;
+use lusid_resource::resources::user::UserResource;
+use lusid_resource::{Resource, ResourceParams};
+use lusid_secrets::Redactor;
+
+use crate::{ApplyError, RenderOptions, collect_leaves, plan_for_render};
+
+/// Evaluate a plan into a [`RenderedCloudInit`]. As for [`render`](crate::render),
+/// nothing is probed or applied.
+pub async fn render_cloud_init(options: RenderOptions) -> Result<RenderedCloudInit, ApplyError> {
+    let (_, resource_params, redactor) = plan_for_render(options).await?;
+    let mut resources = Vec::new();
+    collect_leaves(resource_params, &mut |params: Declared<ResourceParams>| {
+        for tree in params.node.resources() {
+            collect_leaves(tree, &mut |resource: Resource| resources.push(resource));
+        }
+    });
+    Ok(cloud_config(resources, &redactor).await)
+}
+
+async fn cloud_config(resources: Vec<Resource>, redactor: &Redactor) -> RenderedCloudInit {
+    let mut config = CloudConfig::default();
+    let mut skipped = Vec::new();
+    // Mode and owner atoms follow their file's contents atom, and are
+    // attached to its `write_files` entry by path.
+    let mut files: BTreeMap<String, usize> = BTreeMap::new();
+
+    for resource in resources {
+        let mut skip = |reason: &str| {
+            skipped.push(CloudInitSkipped {
+                resource: redactor.redact(&resource.to_string()),
+                reason: reason.to_owned(),
+            })
+        };
+        match &resource {
+            Resource::Apt(apt) => config.packages.push(apt.package.clone()),
+            Resource::Pacman(pacman) => config.packages.push(pacman.package.clone()),
+            Resource::Apk(apk) => config.packages.push(apk.package.clone()),
+            Resource::Dnf(DnfResource::Present { package }) => {
+                config.packages.push(package.clone())
+            }
+            Resource::Group(GroupResource::Present {
+                name,
+                gid,
+                system: _,
+                append_users,
+            }) => {
+                config.groups.push(match append_users {
+                    Some(users) => {
+                        CloudGroup::Members(BTreeMap::from([(name.clone(), users.clone())]))
+                    }
+                    None => CloudGroup::Name(name.clone()),
+                });
+                if gid.is_some() {
+                    skip("cloud-init can't set a group's gid");
+                }
+            }
+            Resource::User(UserResource::Present {
+                name,
+                uid,
+                group,
+                append_groups,
+                comment,
+                home,
+                shell,
+                system,
+                create_home,
+            }) => {
+                if config.users.is_empty() {
+                    config.users.push(CloudUser::Default("default".to_owned()));
+                }
+                config.users.push(CloudUser::User {
+                    name: name.clone(),
+                    uid: *uid,
+                    primary_group: group.clone(),
+                    groups: append_groups.clone().unwrap_or_default(),
+                    gecos: comment.clone(),
+                    homedir: home.as_ref().map(ToString::to_string),
+                    shell: shell.clone(),
+                    system: *system,
+                    no_create_home: !create_home,
+                });
+            }
+            Resource::File(FileResource::Contents { contents, path, .. }) => {
+                files.insert(path.to_string(), config.write_files.len());
+                config
+                    .write_files
+                    .push(write_file(path.to_string(), redactor.redact(contents)));
+            }
+            Resource::File(FileResource::Sourced { source, path, .. }) => {
+                match lusid_fs::read_file_to_string(source.as_path()).await {
+                    Ok(contents) => {
+                        files.insert(path.to_string(), config.write_files.len());
+                        config
+                            .write_files
+                            .push(write_file(path.to_string(), redactor.redact(&contents)));
+                    }
+                    Err(error) => skip(&format!("couldn't read its source as text: {error}")),
+                }
+            }
+            // `write_files` creates a file's missing parent directories.
+            Resource::File(FileResource::Parent { .. }) => {}
+            Resource::File(FileResource::Present { .. }) => skip("no contents to write it with"),
+            Resource::File(FileResource::Secret { .. }) => {
+                skip("secrets aren't written into user-data")
+            }
+            Resource::File(FileResource::Mode { path, mode }) => {
+                match files.get(&path.to_string()) {
+                    Some(&index) => {
+                        config.write_files[index].permissions = Some(format!("0{mode}"))
+                    }
+                    None => skip("no contents to write it with"),
+                }
+            }
+            Resource::File(FileResource::User { path, user }) => {
+                match files.get(&path.to_string()) {
+                    Some(&index) => {
+                        let file = &mut config.write_files[index];
+                        let group = file
+                            .owner
+                            .as_deref()
+                            .and_then(|owner| owner.split_once(':'));
+                        file.owner = Some(match group {
+                            Some((_, group)) => format!("{user}:{group}"),
+                            None => user.to_string(),
+                        });
+                        file.defer = true;
+                    }
+                    None => skip("no contents to write it with"),
+                }
+            }
+            Resource::File(FileResource::Group { path, group }) => {
+                match files.get(&path.to_string()) {
+                    Some(&index) => {
+                        let file = &mut config.write_files[index];
+                        // cloud-init's owner defaults to `root:root`.
+                        let user = file
+                            .owner
+                            .as_deref()
+                            .map(|owner| owner.split(':').next().unwrap_or(owner))
+                            .unwrap_or("root");
+                        file.owner = Some(format!("{user}:{group}"));
+                        file.defer = true;
+                    }
+                    None => skip("no contents to write it with"),
+                }
+            }
+            // Note(cc): `creates`, `unless` and `only_if` guards aren't carried
+            // into `runcmd`, which runs once on first boot regardless.
+            Resource::Command(CommandResource {
+                status: CommandStatus::Install,
+                install: Some(install),
+                ..
+            }) => config.runcmd.push(redactor.redact(install)),
+            Resource::User(UserResource::Absent { .. })
+            | Resource::Group(GroupResource::Absent { .. })
+            | Resource::File(FileResource::Absent { .. })
+            | Resource::Dnf(DnfResource::Absent { .. })
+            | Resource::Command(CommandResource {
+                status: CommandStatus::Uninstall,
+                ..
+            }) => skip("nothing to remove on first boot"),
+            _ => skip("cloud-init has no module for it"),
+        }
+    }
+
+    RenderedCloudInit { config, skipped }
+}
+
+fn write_file(path: String, content: String) -> CloudWriteFile {
+    CloudWriteFile {
+        path,
+        content,
+        permissions: None,
+        owner: None,
+        defer: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lusid_operation::operations::file::{FileGroup, FileMode, FilePath, FileUser};
+
+    use super::*;
+
+    fn contents(path: &str) -> Resource {
+        Resource::File(FileResource::Contents {
+            contents: "hi\n".into(),
+            path: FilePath::new(path),
+            restarts: None,
+        })
+    }
+
+    fn user(name: &str) -> Resource {
+        Resource::User(UserResource::Present {
+            name: name.into(),
+            uid: None,
+            group: None,
+            append_groups: Some(vec!["docker".into()]),
+            comment: None,
+            home: None,
+            shell: Some("/bin/bash".into()),
+            system: false,
+            create_home: true,
+        })
+    }
+
+    async fn render(resources: Vec<Resource>) -> RenderedCloudInit {
+        cloud_config(resources, &Redactor::empty()).await
+    }
+
+    #[tokio::test]
+    async fn files_take_their_mode_and_owner() {
+        let rendered = render(vec![
+            contents("/etc/motd"),
+            Resource::File(FileResource::Mode {
+                path: FilePath::new("/etc/motd"),
+                mode: FileMode::new(0o640),
+            }),
+            Resource::File(FileResource::User {
+                path: FilePath::new("/etc/motd"),
+                user: FileUser::new("alice"),
+            }),
+            Resource::File(FileResource::Group {
+                path: FilePath::new("/etc/motd"),
+                group: FileGroup::new("staff"),
+            }),
+            contents("/etc/issue"),
+            Resource::File(FileResource::Group {
+                path: FilePath::new("/etc/issue"),
+                group: FileGroup::new("adm"),
+            }),
+        ])
+        .await;
+        assert_eq!(
+            rendered.config.write_files,
+            vec![
+                CloudWriteFile {
+                    path: "/etc/motd".into(),
+                    content: "hi\n".into(),
+                    permissions: Some("0640".into()),
+                    owner: Some("alice:staff".into()),
+                    defer: true,
+                },
+                CloudWriteFile {
+                    path: "/etc/issue".into(),
+                    content: "hi\n".into(),
+                    permissions: None,
+                    owner: Some("root:adm".into()),
+                    defer: true,
+                },
+            ]
+        );
+        assert!(rendered.skipped.is_empty());
+    }
+
+    #[tokio::test]
+    async fn users_follow_the_default_user() {
+        let rendered = render(vec![Resource::Apt(
+            lusid_resource::resources::apt::AptResource {
+                package: "nginx".into(),
+            },
+        )])
+        .await;
+        assert!(rendered.config.users.is_empty());
+        assert_eq!(rendered.config.packages, vec!["nginx"]);
+
+        let rendered = render(vec![user("alice"), user("bob")]).await;
+        let names: Vec<_> = rendered
+            .config
+            .users
+            .iter()
+            .map(|user| match user {
+                CloudUser::Default(name) | CloudUser::User { name, .. } => name.as_str(),
+            })
+            .collect();
+        assert_eq!(names, vec!["default", "alice", "bob"]);
+        let CloudUser::User { groups, shell, .. } = &rendered.config.users[1] else {
+            panic!("expected a user");
+        };
+        assert_eq!(groups, &vec!["docker".to_owned()]);
+        assert_eq!(shell.as_deref(), Some("/bin/bash"));
+    }
+
+    #[tokio::test]
+    async fn skips_what_cloud_init_cant_express() {
+        let absent = Resource::File(FileResource::Absent {
+            path: FilePath::new("/etc/motd"),
+            restarts: None,
+        });
+        let mode = Resource::File(FileResource::Mode {
+            path: FilePath::new("/etc/issue"),
+            mode: FileMode::new(0o644),
+        });
+        let group = Resource::Group(GroupResource::Present {
+            name: "docker".into(),
+            gid: Some(999),
+            system: false,
+            append_users: Some(vec!["alice".into()]),
+        });
+        let rendered = render(vec![absent.clone(), mode.clone(), group.clone()]).await;
+        assert_eq!(
+            rendered.config.groups,
+            vec![CloudGroup::Members(BTreeMap::from([(
+                "docker".to_owned(),
+                vec!["alice".to_owned()]
+            )]))]
+        );
+        assert_eq!(
+            rendered.skipped,
+            vec![
+                CloudInitSkipped {
+                    resource: absent.to_string(),
+                    reason: "nothing to remove on first boot".into(),
+                },
+                CloudInitSkipped {
+                    resource: mode.to_string(),
+                    reason: "no contents to write it with".into(),
+                },
+                CloudInitSkipped {
+                    resource: group.to_string(),
+                    reason: "cloud-init can't set a group's gid".into(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,275 @@
+//! Applies embedded in another Rust program, like a custom UI or an
+//! orchestrator, without going through `lusid-apply`'s stdout protocol.
+//!
+//! [`start_apply`] returns the apply to drive, the [`AppUpdate`]s it makes
+//! as a typed [`Stream`], and an [`ApplyHandle`] to confirm or cancel it.
+//! The updates are the same ones `lusid-apply` prints, in the same order,
+//! ending with [`AppUpdate::Error`] if the apply fails.
+//!
+//! Cancelling never interrupts a running probe or operation: the apply
+//! stops before its next operation, or while waiting to retry one, with
+//! [`ApplyError::Cancelled`].
+
+use std::future::{Future, pending};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{self, Poll};
+use std::time::Duration;
+
+use futures_util::Stream;
+use lusid_apply_stdio::{AppControl, AppUpdate};
+use lusid_ctx::{Context, DownloadLimits};
+use tokio::sync::{mpsc, watch};
+
+use crate::{ApplyError, ApplyOptions, Emitter, run_apply};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Decision {
+    Pending,
+    Confirmed,
+    Cancelled,
+}
+
+/// Start an apply of `options`. Nothing happens until the returned future is
+/// polled: await it, or spawn it, while reading the updates.
+///
+/// With `require_confirmation`, the apply stops once it has emitted
+/// [`AppUpdate::OperationsComplete`], before it changes anything, and waits
+/// for [`ApplyHandle::confirm`]. Dropping every handle before then cancels
+/// it. An apply with no changes, or in `check` mode, never gets that far.
+pub fn start_apply(
+    options: ApplyOptions,
+    require_confirmation: bool,
+) -> (
+    impl Future<Output = Result<(), ApplyError>>,
+    ApplyUpdates,
+    ApplyHandle,
+) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let (decision_sender, decision) = watch::channel(Decision::Pending);
+    let mut gate = Gate {
+        decision: Some(decision),
+        require_confirmation,
+        controls: None,
+        paused: false,
+    };
+    let apply = async move { run_apply(options, &Emitter::Channel(sender), &mut gate).await };
+    (
+        apply,
+        ApplyUpdates { receiver },
+        ApplyHandle {
+            decision: Arc::new(decision_sender),
+        },
+    )
+}
+
+/// The [`AppUpdate`]s of an apply from [`start_apply`], ending when it
+/// returns. Unbounded, so an apply never waits on its reader.
+#[derive(Debug)]
+pub struct ApplyUpdates {
+    receiver: mpsc::UnboundedReceiver<AppUpdate>,
+}
+
+impl Stream for ApplyUpdates {
+    type Item = AppUpdate;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+/// Confirms or cancels an apply from [`start_apply`]. Clones control the
+/// same apply; the first decision sticks.
+#[derive(Debug, Clone)]
+pub struct ApplyHandle {
+    decision: Arc<watch::Sender<Decision>>,
+}
+
+impl ApplyHandle {
+    /// Let the apply go ahead with its operations.
+    pub fn confirm(&self) {
+        self.decide(Decision::Confirmed);
+    }
+
+    /// Stop the apply before its next operation.
+    pub fn cancel(&self) {
+        self.decide(Decision::Cancelled);
+    }
+
+    fn decide(&self, decision: Decision) {
+        self.decision.send_if_modified(|current| {
+            if *current != Decision::Pending {
+                return false;
+            }
+            *current = decision;
+            true
+        });
+    }
+}
+
+/// What an apply checks with before changing anything. `decision` is `None`
+/// for `lusid-apply` itself, which never waits for a confirmation; it's
+/// controlled, if at all, by the [`AppControl`]s on `controls`.
+pub(crate) struct Gate {
+    decision: Option<watch::Receiver<Decision>>,
+    require_confirmation: bool,
+    controls: Option<mpsc::UnboundedReceiver<AppControl>>,
+    paused: bool,
+}
+
+impl Gate {
+    pub(crate) fn open() -> Self {
+        Self {
+            decision: None,
+            require_confirmation: false,
+            controls: None,
+            paused: false,
+        }
+    }
+
+    pub(crate) fn controlled(controls: mpsc::UnboundedReceiver<AppControl>) -> Self {
+        Self {
+            controls: Some(controls),
+            ..Self::open()
+        }
+    }
+
+    /// Wait for a confirmation, if one is required.
+    pub(crate) async fn confirmed(&mut self) -> Result<(), ApplyError> {
+        let Some(decision) = &mut self.decision else {
+            return Ok(());
+        };
+        if !self.require_confirmation {
+            return self.not_cancelled();
+        }
+        // An error means every handle is gone, and with them any confirmation.
+        match decision
+            .wait_for(|decision| *decision != Decision::Pending)
+            .await
+            .map(|decision| *decision)
+        {
+            Ok(Decision::Confirmed) => Ok(()),
+            _ => Err(ApplyError::Cancelled),
+        }
+    }
+
+    pub(crate) fn not_cancelled(&self) -> Result<(), ApplyError> {
+        match &self.decision {
+            Some(decision) if *decision.borrow() == Decision::Cancelled => {
+                Err(ApplyError::Cancelled)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Between two operations: take up the controls sent since the last one,
+    /// acknowledging each, and wait here for as long as they leave the apply
+    /// paused.
+    pub(crate) async fn between_operations(
+        &mut self,
+        ctx: &mut Context,
+        emitter: &Emitter,
+    ) -> Result<(), ApplyError> {
+        self.not_cancelled()?;
+        let Self {
+            controls: Some(controls),
+            paused,
+            ..
+        } = self
+        else {
+            return Ok(());
+        };
+        loop {
+            let control = if *paused {
+                // Once the sender is gone, nothing could resume the apply.
+                match controls.recv().await {
+                    Some(control) => control,
+                    None => return Err(ApplyError::Cancelled),
+                }
+            } else {
+                match controls.try_recv() {
+                    Ok(control) => control,
+                    Err(_) => return Ok(()),
+                }
+            };
+            take_control(control, paused, ctx, emitter).await?;
+        }
+    }
+
+    /// Wait `delay` before retrying an operation, taking up controls as they
+    /// come, so a cancel needn't wait the delay out. Then carry on as
+    /// [`Gate::between_operations`].
+    pub(crate) async fn backoff(
+        &mut self,
+        delay: Duration,
+        ctx: &mut Context,
+        emitter: &Emitter,
+    ) -> Result<(), ApplyError> {
+        let sleep = tokio::time::sleep(delay);
+        tokio::pin!(sleep);
+        loop {
+            let control = tokio::select! {
+                () = &mut sleep => break,
+                () = cancelled(&mut self.decision) => return Err(ApplyError::Cancelled),
+                control = next_control(&mut self.controls) => control,
+            };
+            take_control(control, &mut self.paused, ctx, emitter).await?;
+        }
+        self.between_operations(ctx, emitter).await
+    }
+}
+
+/// Act on `control`, acknowledging it, or fail if it cancels the apply.
+async fn take_control(
+    control: AppControl,
+    paused: &mut bool,
+    ctx: &mut Context,
+    emitter: &Emitter,
+) -> Result<(), ApplyError> {
+    match &control {
+        AppControl::Pause => *paused = true,
+        AppControl::Resume => *paused = false,
+        AppControl::Cancel => {}
+        AppControl::SetMaxParallelDownloads { max } => {
+            ctx.set_download_limits(DownloadLimits {
+                max_parallel: *max,
+                ..ctx.download_limits()
+            });
+        }
+        // Applied as soon as it's read, by whoever reads the controls.
+        AppControl::SetLog { .. } => return Ok(()),
+    }
+    let cancelled = control == AppControl::Cancel;
+    emitter.emit(AppUpdate::ControlApplied { control }).await?;
+    if cancelled {
+        return Err(ApplyError::Cancelled);
+    }
+    Ok(())
+}
+
+/// Resolves once the apply is cancelled through its [`ApplyHandle`].
+async fn cancelled(decision: &mut Option<watch::Receiver<Decision>>) {
+    let Some(decision) = decision else {
+        return pending().await;
+    };
+    // An error means every handle is gone, and no cancel can come.
+    let cancelled = decision
+        .wait_for(|decision| *decision == Decision::Cancelled)
+        .await
+        .is_ok();
+    if !cancelled {
+        pending().await
+    }
+}
+
+/// The next control sent, if it ever is. Once the sender is gone, that's
+/// left for [`Gate::between_operations`] to see.
+async fn next_control(controls: &mut Option<mpsc::UnboundedReceiver<AppControl>>) -> AppControl {
+    let Some(controls) = controls else {
+        return pending().await;
+    };
+    match controls.recv().await {
+        Some(control) => control,
+        None => pending().await,
+    }
+}
@@ -3,61 +3,118 @@
 //! applies them — all while streaming [`AppUpdate`]s as newline-delimited
 //! JSON on stdout for the `lusid` TUI to render.
 //!
-//! The public surface is [`apply`] + [`ApplyOptions`]; `main.rs` is a thin
-//! clap wrapper.
+//! The public surface is [`apply`] + [`ApplyOptions`] (and
+//! [`apply_with_controls`] to pause, resume or cancel it from stdin,
+//! [`start_apply`]
+//! to embed an apply in another program, [`explain`] +
+//! [`ExplainOptions`] for ordering diagnostics, [`export_script`] +
+//! [`ExportScriptOptions`] for a shell-script rendering of the operations,
+//! [`render`] + [`RenderOptions`] for a reviewable document of the resources,
+//! [`render_cloud_init`] for the part of it cloud-init can express);
+//! `main.rs` is a thin clap wrapper.
 //!
 //! ## Pipeline (one phase per [`AppUpdate`] group)
 //!
 //! 1. [`plan`](lusid_plan::plan) — evaluate the plan, validate params,
-//!    produce a [`PlanTree<ResourceParams>`](lusid_plan::PlanTree).
+//!    produce a [`PlanTree<ResourceParams>`](lusid_plan::PlanTree). Each
+//!    leaf is [`Declared`] with its plan line, which the resources and
+//!    changes it expands to carry on, so the TUI shows where each is from.
 //! 2. `ResourceParams → Resources` via `ResourceParams::resources` — each
 //!    plan node can expand into multiple resources with intra-scope ordering
 //!    (file mode/user/group, etc.), handled by
-//!    [`map_plan_subitems`](lusid_plan::map_plan_subitems).
+//!    [`map_plan_subitems`](lusid_plan::map_plan_subitems). Stops here if two
+//!    resources disagree on something they both manage (see [`Claims`]), or
+//!    a resource would remove or overwrite something in `protections`.
 //! 3. `Resource → ResourceState` via async state probes. This is the only
 //!    I/O-bound phase prior to apply; emits per-leaf `NodeStart`/`NodeComplete`
 //!    so the TUI can show a spinner while each probe runs.
 //! 4. `(Resource, State) → ResourceChange` — pure; `None` means "no-op, prune".
+//!    Stops here if a change is destructive and `allow_destructive` isn't set,
+//!    unless it's a `check`.
 //! 5. `ResourceChange → Operations` tree — each change expands to one or
 //!    more ordered operations. Short-circuits if step 4 produced no changes.
 //! 6. [`compute_epochs`] — Kahn's topological layering over the causality
 //!    metadata in the operations tree; operations within an epoch are
 //!    independent, operations across epochs have a required-before edge.
+//!    Before the first operation runs, waits for an embedder's confirmation
+//!    if [`start_apply`] was asked to, then up to `lock_timeout` for any
+//!    other writer to the machine (see [`writers`]), then holds the apply
+//!    lock until done, with the umask set to [`APPLY_UMASK`].
 //! 7. [`Operation::merge`] + [`Operation::apply`] — per-epoch, merge like
 //!    operations (e.g. multiple `apt install`s into one), then apply
 //!    sequentially. Stdout + stderr are streamed line-by-line back into
-//!    `AppUpdate` events.
+//!    `AppUpdate` events, with progress parsed out of stderr (see
+//!    [`Operation::progress`]), and journaled on the machine as each ends
+//!    (see [`lusid_journal`]). An operation that fails is retried as its plan
+//!    item's [`RetryPolicy`] says, and only merged with operations sharing
+//!    that policy. Between operations, any [`AppControl`]s sent to
+//!    [`apply_with_controls`] are taken up. A `check` runs each operation's
+//!    [`Operation::simulate`] dry run instead, without the confirmation, the
+//!    lock or the umask, since nothing on the machine changes.
 //!
 //! Human-facing output belongs on stderr (via `tracing`); stdout is reserved
 //! for the machine-readable protocol.
 
-use std::path::PathBuf;
+pub mod cloud_init;
+pub mod embed;
+pub mod writers;
+
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
 use std::sync::LazyLock;
+use std::time::{Duration, Instant};
 
-use lusid_apply_stdio::AppUpdate;
-use lusid_causality::{CausalityTree, EpochError, compute_epochs};
-use lusid_ctx::{Context, ContextError};
-use lusid_operation::{Operation, OperationApplyError};
+use lusid_apply_stdio::{
+    AppControl, AppUpdate, ErrorEnvelope, ErrorSpan, ModuleVersion, OperationProgress,
+    OperationResult, RenderedPlan, RenderedResource, WarningEnvelope,
+};
+use lusid_causality::{
+    CausalityTree, EpochError, ExplainError, compute_epochs, explain_node, explain_ordering,
+};
+use lusid_cmd::CommandError;
+use lusid_ctx::{Context, ContextError, DownloadLimits};
+use lusid_journal::{JournalError, JournalRecord, JournalWriter, now_millis};
+use lusid_operation::{Operation, OperationApplyError, Progress, ProgressParser, Severity};
 use lusid_params::ParamsContext;
 use lusid_plan::{
-    self, PlanError, PlanId, PlanNodeId, PlanTree, map_plan_subitems, plan, render_plan_tree,
+    self, Declared, PlanError, PlanId, PlanMeta, PlanNodeId, PlanTree, RetryPolicy,
+    map_declared_subitems, map_plan_subitems, plan, plan_with_modules, render_plan_tree,
+};
+use lusid_resource::conflict::{Claims, ConflictError};
+use lusid_resource::protect::{ProtectedError, Protections};
+use lusid_resource::{
+    HostPathValidationError, Resource, ResourceChange, ResourceParams, ResourceState,
+    ResourceStateError,
 };
-use lusid_resource::{HostPathValidationError, Resource, ResourceState, ResourceStateError};
 use lusid_secrets::{LoadError, Redactor, Secrets};
 use lusid_store::Store;
 use lusid_system::{GetSystemError, System};
-use lusid_tree::FlatTree;
-use lusid_view::Render;
-use rimu::SourceId;
-use rimu_interop::{ToRimuError, to_rimu};
+use lusid_tree::{FlatTree, Tree};
+use lusid_view::{Fragment, Render, View};
+use rimu::{SourceId, Span, Spanned, Value};
+use rimu_interop::{ToRimuError, render_diagnostic, render_warning, to_rimu};
 use thiserror::Error;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::sync::Mutex;
-use tracing::{debug, error, info};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
+use tokio::sync::{Mutex, mpsc};
+use tracing::{debug, error, info, warn};
+
+use crate::embed::Gate;
+use crate::writers::{LockError, wait_for_writers};
+
+pub use crate::cloud_init::render_cloud_init;
+pub use crate::embed::{ApplyHandle, ApplyUpdates, start_apply};
+
+/// The umask operations run under, and so the mode of anything an apply
+/// creates without an explicit one: `0644` files and `0755` directories,
+/// whatever the umask of the shell or service that started `lusid-apply`.
+pub const APPLY_UMASK: u32 = 0o022;
 
 /// Inputs for [`apply`]. `root_path` is the lusid working-dir root passed to
 /// [`Context::create`]; `plan_id` selects a plan; `params_json` is an
 /// optional JSON object (validated against the plan's params schema).
+/// `machine_json` is an optional JSON object of facts about the machine,
+/// which the plan's param defaults can read (see [`lusid_params`]).
 ///
 /// Secrets: if `identity_path` is `Some`, `lusid-apply` loads that identity,
 /// reads `lusid-secrets.toml` from `secrets_dir` (defaulting to
@@ -72,13 +129,44 @@ use tracing::{debug, error, info};
 /// has already re-encrypted ciphertexts per-target, so whatever landed in
 /// `secrets_dir` is exactly the subset this guest is supposed to see.
 /// Requires `identity_path` to be set.
+///
+/// `explain_ordering` logs the operations tree annotated with its causality
+/// edges and each operation's resolved epoch (see [`explain_ordering`]) to
+/// stderr before applying, for debugging unexpected ordering.
+///
+/// `check` probes the machine's state and reports the changes an apply would
+/// make, then simulates their operations instead of applying them: each runs
+/// its dry run (see [`Operation::simulate`]), or reports what it would run,
+/// in an update stream tagged as simulated.
+///
+/// Unless `allow_destructive` is set, an apply whose changes include a
+/// destructive operation (see [`Severity`]) stops after the changes are
+/// emitted, with [`ApplyError::Destructive`] listing them.
+///
+/// `protections` are the paths and users the apply refuses to remove or
+/// overwrite, checked before any state is probed (see [`Protections`]).
+/// `allow_destructive` doesn't lift them.
+///
+/// `lock_timeout` is how long to wait for other writers to the machine, like
+/// apt holding the dpkg lock or another `lusid-apply`, before failing with
+/// [`LockError::Busy`]. Zero fails at once.
+///
+/// `download_limits` throttle the apply's downloads, including apt's (see
+/// [`DownloadLimits`]).
 pub struct ApplyOptions {
     pub root_path: PathBuf,
     pub plan_id: PlanId,
     pub params_json: Option<String>,
+    pub machine_json: Option<String>,
     pub identity_path: Option<PathBuf>,
     pub secrets_dir: Option<PathBuf>,
     pub guest_mode: bool,
+    pub explain_ordering: bool,
+    pub check: bool,
+    pub allow_destructive: bool,
+    pub protections: Protections,
+    pub lock_timeout: Duration,
+    pub download_limits: DownloadLimits,
 }
 
 #[derive(Error, Debug)]
@@ -92,6 +180,9 @@ pub enum ApplyError {
     #[error("failed to parse JSON parameters: {0}")]
     JsonParameters(#[source] serde_json::Error),
 
+    #[error("failed to parse JSON machine facts: {0}")]
+    JsonMachine(#[source] serde_json::Error),
+
     #[error("failed to parse parameters into rimu value: {0}")]
     RimuParameters(#[from] ToRimuError),
 
@@ -107,6 +198,12 @@ pub enum ApplyError {
     #[error("failed to flush stdout: {0}")]
     FlushStdout(#[source] tokio::io::Error),
 
+    #[error("the apply's update stream was dropped")]
+    UpdatesClosed,
+
+    #[error("the apply was cancelled")]
+    Cancelled,
+
     #[error(transparent)]
     Plan(#[from] PlanError),
 
@@ -119,30 +216,216 @@ pub enum ApplyError {
     #[error(transparent)]
     OperationApply(#[from] OperationApplyError),
 
+    #[error("failed to simulate operation: {0}")]
+    Simulate(#[source] CommandError),
+
     #[error(transparent)]
     Secrets(#[from] LoadError),
 
     #[error("host-path validation failed: {0}")]
     HostPathValidation(#[from] HostPathValidationError),
+
+    #[error(transparent)]
+    Protected(#[from] ProtectedError),
+
+    #[error(transparent)]
+    Conflict(#[from] ConflictError),
+
+    #[error(transparent)]
+    Lock(#[from] LockError),
+
+    #[error(transparent)]
+    Journal(#[from] JournalError),
+
+    #[error(transparent)]
+    Explain(#[from] ExplainError<PlanNodeId>),
+
+    #[error(
+        "{} destructive change(s), pass --allow-destructive to apply them: {}",
+        changes.len(),
+        changes.join("; ")
+    )]
+    Destructive { changes: Vec<String> },
+
+    #[error("no plan node has id \"{0}\"")]
+    UnknownNodeId(String),
+
+    #[error("plan node id \"{node_id}\" is ambiguous, use one of: {}", candidates.join("; "))]
+    AmbiguousNodeId {
+        node_id: String,
+        candidates: Vec<String>,
+    },
+}
+
+impl ApplyError {
+    /// The plan source span this error points at, if it came from the plan.
+    pub fn span(&self) -> Option<&Span> {
+        match self {
+            ApplyError::Plan(error) => error.span(),
+            ApplyError::HostPathValidation(error) => error.span(),
+            _ => None,
+        }
+    }
+
+    /// Stable, machine-readable code for this failure. Wrapped errors report
+    /// their own, more specific code (e.g. `"plan.load.parse"`).
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApplyError::Context(_) => "apply.context",
+            ApplyError::GetSystem(_) => "apply.system",
+            ApplyError::JsonParameters(_)
+            | ApplyError::JsonMachine(_)
+            | ApplyError::RimuParameters(_) => "apply.params-input",
+            ApplyError::JsonOutput(_)
+            | ApplyError::WriteStdout(_)
+            | ApplyError::FlushStdout(_)
+            | ApplyError::UpdatesClosed => "apply.output",
+            ApplyError::Cancelled => "apply.cancelled",
+            ApplyError::ReadOperationStdio(_) => "apply.operation-stdio",
+            ApplyError::Plan(error) => error.code(),
+            ApplyError::Epoch(error) => error.code(),
+            ApplyError::ResourceState(error) => error.code(),
+            ApplyError::OperationApply(error) => error.code(),
+            ApplyError::Simulate(_) => "apply.simulate",
+            ApplyError::Secrets(error) => error.code(),
+            ApplyError::HostPathValidation(error) => error.code(),
+            ApplyError::Protected(error) => error.code(),
+            ApplyError::Conflict(error) => error.code(),
+            ApplyError::Lock(error) => error.code(),
+            ApplyError::Journal(error) => error.code(),
+            ApplyError::Explain(ExplainError::Epoch(error)) => error.code(),
+            ApplyError::Explain(ExplainError::UnknownId(_)) | ApplyError::UnknownNodeId(_) => {
+                "explain.unknown-node"
+            }
+            ApplyError::AmbiguousNodeId { .. } => "explain.ambiguous-node",
+            ApplyError::Destructive { .. } => "apply.destructive",
+        }
+    }
+
+    /// Exit code of the command an operation ran, if that's what failed.
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            ApplyError::OperationApply(error) => error.exit_code(),
+            ApplyError::Simulate(error) => lusid_cmd::exit_code(error),
+            _ => None,
+        }
+    }
+
+    /// Human-facing rendering: the message, followed by the plan source
+    /// excerpt with a caret when [`span`](Self::span) points into a file.
+    pub fn render(&self) -> String {
+        render_diagnostic(self, self.span())
+    }
+
+    /// Code, message, rendering and span, bundled for [`AppUpdate::Error`] and
+    /// `--error-format json`.
+    pub fn envelope(&self) -> ErrorEnvelope {
+        ErrorEnvelope {
+            code: self.code().to_string(),
+            message: self.to_string(),
+            rendered: self.render(),
+            span: self.span().and_then(error_span),
+        }
+    }
+}
+
+fn error_span(span: &Span) -> Option<ErrorSpan> {
+    let path = span.source().as_str();
+    (!path.is_empty()).then(|| ErrorSpan {
+        path: path.to_string(),
+        start: span.start(),
+        end: span.end(),
+    })
+}
+
+/// Log the warnings planning raised (e.g. deprecated params), returning them
+/// for [`AppUpdate::Warning`].
+fn plan_warnings(params_ctx: &ParamsContext) -> Vec<WarningEnvelope> {
+    params_ctx
+        .take_warnings()
+        .into_iter()
+        .map(|warning| {
+            warn!(code = warning.code(), "{warning}");
+            WarningEnvelope {
+                code: warning.code().to_string(),
+                message: warning.to_string(),
+                rendered: render_warning(&warning, Some(warning.span())),
+                span: error_span(warning.span()),
+            }
+        })
+        .collect()
 }
 
 /// Run the full apply pipeline, streaming [`AppUpdate`]s to stdout as it
 /// goes. Returns `Ok(())` on success (including the "no changes" early
-/// return after phase 4) or the first fatal error. On operation failure,
-/// an `OperationApplyComplete { error: Some(..) }` is emitted before the
-/// error propagates so the TUI can show which operation failed.
+/// return after phase 4) or the first fatal error. Each operation ends with
+/// an `OperationApplyComplete` carrying its [`OperationResult`], so on
+/// failure the TUI can show which operation failed and why before the error
+/// propagates. Any fatal
+/// error is also emitted as [`AppUpdate::Error`] (see
+/// [`ApplyError::envelope`]) before it is returned.
 pub async fn apply(options: ApplyOptions) -> Result<(), ApplyError> {
+    run_apply(options, &Emitter::Stdout, &mut Gate::open()).await
+}
+
+/// [`apply`], taking up the [`AppControl`]s on `controls` between operations:
+/// pausing, resuming or cancelling it, or changing its download limits. Each
+/// is acknowledged with [`AppUpdate::ControlApplied`] once it has taken
+/// effect.
+pub async fn apply_with_controls(
+    options: ApplyOptions,
+    controls: mpsc::UnboundedReceiver<AppControl>,
+) -> Result<(), ApplyError> {
+    run_apply(options, &Emitter::Stdout, &mut Gate::controlled(controls)).await
+}
+
+/// [`apply`], with its updates sent to `emitter` and its operations behind
+/// `gate`.
+pub(crate) async fn run_apply(
+    options: ApplyOptions,
+    emitter: &Emitter,
+    gate: &mut Gate,
+) -> Result<(), ApplyError> {
+    let result = apply_pipeline(options, emitter, gate).await;
+    if let Err(err) = &result {
+        // Best-effort: if the output itself is what failed, there's no one to
+        // tell.
+        if let Err(emit_err) = emitter
+            .emit(AppUpdate::Error {
+                error: err.envelope(),
+            })
+            .await
+        {
+            debug!("failed to emit error update: {emit_err}");
+        }
+    }
+    result
+}
+
+async fn apply_pipeline(
+    options: ApplyOptions,
+    emitter: &Emitter,
+    gate: &mut Gate,
+) -> Result<(), ApplyError> {
     info!("starting");
     let ApplyOptions {
         root_path,
         plan_id,
         params_json,
+        machine_json,
         identity_path,
         secrets_dir,
         guest_mode,
+        explain_ordering: should_explain_ordering,
+        check,
+        allow_destructive,
+        protections,
+        lock_timeout,
+        download_limits,
     } = options;
 
     let mut ctx = Context::create(&root_path)?;
+    ctx.set_download_limits(download_limits);
     let mut store = Store::new(ctx.paths().cache_dir());
     let system = System::get().await?;
 
@@ -158,34 +441,39 @@ pub async fn apply(options: ApplyOptions) -> Result<(), ApplyError> {
     ctx.set_secrets(secrets);
 
     info!(plan = %plan_id, "using plan");
+    let journal_plan = plan_id.to_string();
 
-    let param_values = match params_json {
-        None => {
-            info!("no parameters provided");
-            None
-        }
-        Some(json) => {
-            let value: serde_json::Value =
-                serde_json::from_str(&json).map_err(ApplyError::JsonParameters)?;
-            let value = to_rimu(value, SourceId::empty())?;
-            Some(value)
-        }
-    };
+    let param_values = parse_params_json(params_json)?;
 
     // Fallback root path for resolving relative `host-path` strings that
     // arrive without a real source span — i.e. CLI-supplied `--params`.
     // Anchoring on the project root means a `--params '{"src": "./foo"}'`
     // invocation resolves "./foo" relative to the directory the user thinks of
     // as their project root, not the CWD lusid-apply happens to run from.
-    let params_ctx = ParamsContext::new(root_path.clone());
+    let params_ctx = params_context(&root_path, machine_json)?;
 
     // Parse/evaluate to tree of resource params.
-    let resource_params = plan(plan_id, param_values, &params_ctx, &mut store, &system).await?;
+    let (resource_params, modules) =
+        plan_with_modules(plan_id, param_values, &params_ctx, &mut store, &system).await?;
     debug!("Resource params: {resource_params:?}");
-    emit(AppUpdate::ResourceParams {
-        resource_params: render_plan_tree(resource_params.clone()),
-    })
-    .await?;
+    let canonical_root = root_path.canonicalize().unwrap_or(root_path.clone());
+    let modules = modules
+        .into_iter()
+        .map(|module| ModuleVersion {
+            plan: relative_plan_path(module.plan_id, &canonical_root),
+            name: module.name,
+            version: module.version,
+        })
+        .collect();
+    emitter
+        .emit(AppUpdate::ResourceParams {
+            resource_params: render_plan_tree(&resource_params),
+            modules,
+        })
+        .await?;
+    for warning in plan_warnings(&params_ctx) {
+        emitter.emit(AppUpdate::Warning { warning }).await?;
+    }
     let resource_params = FlatTree::from(resource_params);
 
     // Validate `host-path` sources up front so a typo doesn't surface as a
@@ -196,16 +484,18 @@ pub async fn apply(options: ApplyOptions) -> Result<(), ApplyError> {
     // serial walk would multiply round-trips by the leaf count.
     let validations = resource_params
         .leaves()
-        .map(|params| params.validate_host_paths());
+        .map(|params| params.node.validate_host_paths());
     futures_util::future::try_join_all(validations).await?;
 
     // Get tree of atomic resources.
-    emit(AppUpdate::ResourcesStart).await?;
+    emitter.emit(AppUpdate::ResourcesStart).await?;
     let resources = resource_params
         .map_tree(
-            |node, meta| PlanTree::branch(meta, map_plan_subitems(node, |node| node.resources())),
+            |node, meta| {
+                PlanTree::branch(meta, map_declared_subitems(node, |node| node.resources()))
+            },
             |index, tree| {
-                emit(AppUpdate::ResourcesNode {
+                emitter.emit(AppUpdate::ResourcesNode {
                     index,
                     tree: render_plan_tree(tree),
                 })
@@ -213,22 +503,20 @@ pub async fn apply(options: ApplyOptions) -> Result<(), ApplyError> {
         )
         .await?;
     debug!("Resources: {:?}", CausalityTree::from(resources.clone()));
-    emit(AppUpdate::ResourcesComplete).await?;
+    emitter.emit(AppUpdate::ResourcesComplete).await?;
+
+    check_resources(resources.leaves(), &protections)?;
 
     // Get tree of (resource, resource state)
-    emit(AppUpdate::ResourceStatesStart).await?;
+    emitter.emit(AppUpdate::ResourceStatesStart).await?;
+    // Probed in one batch, so resources of a type with a bulk probe (apt,
+    // pacman) cost one command between them.
     let resource_states = resources
-        .map_result_async(
-            |resource| {
-                let mut ctx = ctx.clone();
-                async move {
-                    let state = resource.state(&mut ctx).await?;
-                    Ok::<(Resource, ResourceState), ApplyError>((resource, state))
-                }
-            },
-            |index| emit(AppUpdate::ResourceStatesNodeStart { index }),
+        .map_batch_result_async(
+            |resources| observe_states(&mut ctx, resources),
+            |index| emitter.emit(AppUpdate::ResourceStatesNodeStart { index }),
             |index, (_resource, resource_state)| {
-                emit(AppUpdate::ResourceStatesNodeComplete {
+                emitter.emit(AppUpdate::ResourceStatesNodeComplete {
                     index,
                     node: resource_state.render(),
                 })
@@ -239,17 +527,17 @@ pub async fn apply(options: ApplyOptions) -> Result<(), ApplyError> {
         "Resource states: {:?}",
         CausalityTree::from(resource_states.clone()).map(|(_resource, state)| state)
     );
-    emit(AppUpdate::ResourceStatesComplete).await?;
+    emitter.emit(AppUpdate::ResourceStatesComplete).await?;
 
     // Get tree of resource changes
-    emit(AppUpdate::ResourceChangesStart).await?;
+    emitter.emit(AppUpdate::ResourceChangesStart).await?;
     let resource_changes = resource_states
         .map(
-            |(resource, state)| resource.change(&state),
+            |(resource, state)| resource_change(resource, &state),
             |index, node| {
-                emit(AppUpdate::ResourceChangesNode {
+                emitter.emit(AppUpdate::ResourceChangesNode {
                     index,
-                    node: node.map(|n| n.render()),
+                    node: node.as_ref().map(render_change),
                 })
             },
         )
@@ -261,27 +549,51 @@ pub async fn apply(options: ApplyOptions) -> Result<(), ApplyError> {
 
     let has_changes = resource_changes.leaves().any(|node| node.is_some());
 
-    emit(AppUpdate::ResourceChangesComplete { has_changes }).await?;
+    emitter
+        .emit(AppUpdate::ResourceChangesComplete { has_changes })
+        .await?;
 
     if !has_changes {
         info!("No changes to apply!");
         return Ok(());
     };
 
+    if !check && !allow_destructive {
+        let destructive: Vec<String> = resource_changes
+            .leaves()
+            .flatten()
+            .filter(|change| change.node.severity() == Severity::Destructive)
+            .map(ToString::to_string)
+            .collect();
+        if !destructive.is_empty() {
+            return Err(ApplyError::Destructive {
+                changes: destructive,
+            });
+        }
+    }
+
     // Get CausalityTree<Operations>
-    emit(AppUpdate::OperationsStart).await?;
+    emitter.emit(AppUpdate::OperationsStart).await?;
     let operations = resource_changes
         .map_tree(
             |node, meta| match node {
                 Some(node) => {
-                    let children = map_plan_subitems(node, |node| node.operations())
-                        .map(|tree| tree.map(Some));
+                    let retry = node.retry;
+                    let children =
+                        map_plan_subitems(node.node, |node| node.operations()).map(move |tree| {
+                            tree.map(|operation| {
+                                Some(PlannedOperation {
+                                    operation,
+                                    retry: retry.clone(),
+                                })
+                            })
+                        });
                     PlanTree::branch(meta, children)
                 }
                 None => PlanTree::leaf(meta, None),
             },
             |index, tree| {
-                emit(AppUpdate::OperationsNode {
+                emitter.emit(AppUpdate::OperationsNode {
                     index,
                     operations: render_plan_tree(tree),
                 })
@@ -292,17 +604,88 @@ pub async fn apply(options: ApplyOptions) -> Result<(), ApplyError> {
         "Operations tree: {:?}",
         CausalityTree::from(operations.clone())
     );
-    emit(AppUpdate::OperationsComplete).await?;
+    emitter.emit(AppUpdate::OperationsComplete).await?;
 
-    let operation_epochs = compute_epochs(CausalityTree::from(operations))?;
+    let operations = CausalityTree::from(operations);
+    if should_explain_ordering {
+        let explanation = explain_ordering(&operations, ToString::to_string)?;
+        info!("Operation ordering:\n{explanation}");
+    }
+
+    let operation_epochs = compute_epochs(operations)?;
     debug!("Operation epochs: {operation_epochs:?}");
-    emit(AppUpdate::OperationsApplyStart {
-        operations: operation_epochs
-            .iter()
-            .map(|epoch| epoch.iter().map(Render::render).collect())
-            .collect(),
-    })
-    .await?;
+
+    if check {
+        info!("Check mode, simulating operations");
+        return simulate_epochs(operation_epochs, &mut ctx, emitter, gate, &redactor).await;
+    }
+
+    // Nothing has changed on the machine yet: the last point an embedder
+    // can look over the operations and back out.
+    gate.confirmed().await?;
+
+    // Held until the apply returns.
+    let _lock = wait_for_writers(&ctx.paths().data_dir().join("apply.lock"), lock_timeout).await?;
+    lusid_fs::set_umask(APPLY_UMASK);
+
+    let mut journal = JournalWriter::create(ctx.paths()).await?;
+    let journal_id = journal.id().to_owned();
+    journal
+        .record(&JournalRecord::ApplyStart {
+            time: now_millis(),
+            plan: journal_plan,
+            operations: operation_epochs
+                .iter()
+                .map(|epoch| epoch.iter().map(ToString::to_string).collect())
+                .collect(),
+        })
+        .await;
+    let applied = apply_epochs(
+        operation_epochs,
+        &mut ctx,
+        emitter,
+        gate,
+        &redactor,
+        &mut journal,
+    )
+    .await;
+    journal
+        .record(&JournalRecord::ApplyComplete {
+            time: now_millis(),
+            error: applied.as_ref().err().map(ToString::to_string),
+        })
+        .await;
+    if let Err(error) = journal.finish().await {
+        warn!(
+            journal = %journal_id,
+            "failed to write operation journal: {error}"
+        );
+    }
+    applied?;
+
+    info!("Apply completed");
+    Ok(())
+}
+
+/// Apply each epoch's merged operations, streaming their output and
+/// journaling each as it ends.
+async fn apply_epochs(
+    operation_epochs: Vec<Vec<PlannedOperation>>,
+    ctx: &mut Context,
+    emitter: &Emitter,
+    gate: &mut Gate,
+    redactor: &Redactor,
+    journal: &mut JournalWriter,
+) -> Result<(), ApplyError> {
+    emitter
+        .emit(AppUpdate::OperationsApplyStart {
+            operations: operation_epochs
+                .iter()
+                .map(|epoch| epoch.iter().map(Render::render).collect())
+                .collect(),
+            simulated: false,
+        })
+        .await?;
 
     let epochs_count = operation_epochs.len();
     for (epoch_index, operations) in operation_epochs.into_iter().enumerate() {
@@ -313,75 +696,869 @@ pub async fn apply(options: ApplyOptions) -> Result<(), ApplyError> {
         );
         debug!("Operations: {operations:?}");
 
-        let operations = Operation::merge(operations);
+        let operations = merge_operations(operations);
         debug!("Merged operations: {operations:?}");
 
-        for (operation_index, operation) in operations.iter().enumerate() {
+        for (operation_index, planned) in operations.iter().enumerate() {
             let index = (epoch_index, operation_index);
+            let PlannedOperation { operation, retry } = planned;
+            gate.between_operations(ctx, emitter).await?;
+
+            let started_at = now_millis();
+            let mut retries = 0;
+            loop {
+                let started = Instant::now();
+                let attempt = match operation.apply(ctx).await {
+                    Ok((output, stdout, stderr)) => {
+                        let output = async {
+                            output.await?;
+                            Ok(())
+                        };
+                        Ok(stream_operation(
+                            emitter,
+                            redactor,
+                            index,
+                            started,
+                            output,
+                            stdout,
+                            stderr,
+                            operation.progress(),
+                        )
+                        .await)
+                    }
+                    Err(error) => Err(ApplyError::from(error)),
+                };
+                let error = match &attempt {
+                    Ok((_, Ok(()))) => None,
+                    Ok((_, Err(error))) | Err(error) => Some(error),
+                };
+                if let Some(delay) = error.and_then(|error| retry_delay(retry, retries, error)) {
+                    warn!(
+                        operation = %operation,
+                        retry = retries + 1,
+                        "operation failed, retrying in {delay:?}"
+                    );
+                    gate.backoff(delay, ctx, emitter).await?;
+                    retries += 1;
+                    continue;
+                }
 
-            let (output, stdout, stderr) = operation.apply(&mut ctx).await?;
+                // A command that couldn't start has no result to report.
+                let (mut result, outcome) = attempt?;
+                result.retries = retries;
+                journal
+                    .record(&JournalRecord::Operation {
+                        index,
+                        operation: operation.to_string(),
+                        started: started_at,
+                        finished: now_millis(),
+                        result: result.clone(),
+                    })
+                    .await;
+                emitter
+                    .emit(AppUpdate::OperationApplyComplete { index, result })
+                    .await?;
+                outcome?;
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
 
-            let output_task = async {
-                output.await?;
+/// Simulate each epoch's merged operations for a `check`, streaming their
+/// dry runs as an apply streams its operations. An operation without a dry
+/// run reports the command it would run, or itself when it isn't one.
+async fn simulate_epochs(
+    operation_epochs: Vec<Vec<PlannedOperation>>,
+    ctx: &mut Context,
+    emitter: &Emitter,
+    gate: &mut Gate,
+    redactor: &Redactor,
+) -> Result<(), ApplyError> {
+    emitter
+        .emit(AppUpdate::OperationsApplyStart {
+            operations: operation_epochs
+                .iter()
+                .map(|epoch| epoch.iter().map(Render::render).collect())
+                .collect(),
+            simulated: true,
+        })
+        .await?;
+
+    for (epoch_index, operations) in operation_epochs.into_iter().enumerate() {
+        let operations = merge_operations(operations);
+        for (operation_index, PlannedOperation { operation, .. }) in operations.iter().enumerate() {
+            let index = (epoch_index, operation_index);
+            gate.between_operations(ctx, emitter).await?;
 
-                Ok::<(), ApplyError>(())
+            let started = Instant::now();
+            let Some(mut command) = operation.simulate() else {
+                let would = match operation.script() {
+                    Some(script) => format!("would run: {script}"),
+                    None => format!("would apply: {operation}"),
+                };
+                let output = async { Ok(()) };
+                let lines = std::io::Cursor::new(format!("{would}\n").into_bytes());
+                let (result, outcome) = stream_operation(
+                    emitter,
+                    redactor,
+                    index,
+                    started,
+                    output,
+                    lines,
+                    tokio::io::empty(),
+                    None,
+                )
+                .await;
+                emitter
+                    .emit(AppUpdate::OperationApplyComplete { index, result })
+                    .await?;
+                outcome?;
+                continue;
+            };
+            let output = command
+                .output_checked()
+                .await
+                .map_err(ApplyError::Simulate)?;
+            let status = async {
+                output.status.await.map_err(ApplyError::Simulate)?;
+                Ok(())
             };
+            let (result, outcome) = stream_operation(
+                emitter,
+                redactor,
+                index,
+                started,
+                status,
+                output.stdout,
+                output.stderr,
+                None,
+            )
+            .await;
+            emitter
+                .emit(AppUpdate::OperationApplyComplete { index, result })
+                .await?;
+            outcome?;
+        }
+    }
 
-            let stdout_task = {
-                let mut lines = BufReader::new(stdout).lines();
-                let redactor = redactor.clone();
-                async move {
-                    while let Some(line) = lines
-                        .next_line()
-                        .await
-                        .map_err(ApplyError::ReadOperationStdio)?
-                    {
-                        emit(AppUpdate::OperationApplyStdout {
-                            index,
-                            stdout: redactor.redact(&line),
-                        })
-                        .await?;
-                    }
-                    Ok::<(), ApplyError>(())
+    info!("Check completed");
+    Ok(())
+}
+
+/// Stream a running operation's stdout and stderr as updates, line by line
+/// and redacted, until `output` resolves. Returns its result, for the caller
+/// to emit, and its failure, if it failed.
+///
+/// With a `progress` parser, stderr lines it parses are also sent as
+/// progress updates. A progress meter redraws its line with `\r`, so only
+/// then is a lone `\r` read as a line break; a redrawn line it parses is
+/// sent only as progress, so stderr keeps just the meter's last line.
+#[allow(clippy::too_many_arguments)]
+async fn stream_operation(
+    emitter: &Emitter,
+    redactor: &Redactor,
+    index: (usize, usize),
+    started: Instant,
+    output: impl Future<Output = Result<(), ApplyError>>,
+    stdout: impl AsyncRead + Unpin,
+    stderr: impl AsyncRead + Unpin,
+    progress: Option<ProgressParser>,
+) -> (OperationResult, Result<(), ApplyError>) {
+    let stdout_task = {
+        let mut lines = BufReader::new(stdout).lines();
+        async move {
+            let mut tail = VecDeque::with_capacity(OperationResult::STDOUT_TAIL_LINES);
+            while let Some(line) = lines
+                .next_line()
+                .await
+                .map_err(ApplyError::ReadOperationStdio)?
+            {
+                let line = redactor.redact(&line);
+                if tail.len() == OperationResult::STDOUT_TAIL_LINES {
+                    tail.pop_front();
                 }
-            };
+                tail.push_back(line.clone());
+                emitter
+                    .emit(AppUpdate::OperationApplyStdout {
+                        index,
+                        stdout: line,
+                    })
+                    .await?;
+            }
+            Ok::<_, ApplyError>(tail)
+        }
+    };
 
-            let stderr_task = {
-                let mut lines = BufReader::new(stderr).lines();
-                let redactor = redactor.clone();
-                async move {
-                    while let Some(line) = lines
-                        .next_line()
-                        .await
-                        .map_err(ApplyError::ReadOperationStdio)?
-                    {
-                        emit(AppUpdate::OperationApplyStderr {
+    let stderr_task = {
+        let mut stderr = BufReader::new(stderr);
+        async move {
+            let mut tail = VecDeque::with_capacity(OperationResult::STDERR_TAIL_LINES);
+            let mut buf = Vec::new();
+            while let Some(redrawn) = read_stderr_line(&mut stderr, &mut buf, progress.is_some())
+                .await
+                .map_err(ApplyError::ReadOperationStdio)?
+            {
+                let line = redactor.redact(&String::from_utf8_lossy(&buf));
+                if let Some(Progress { stage, percent }) = progress.and_then(|parse| parse(&line)) {
+                    emitter
+                        .emit(AppUpdate::OperationApplyProgress {
                             index,
-                            stderr: redactor.redact(&line),
+                            progress: OperationProgress { stage, percent },
                         })
                         .await?;
+                    if redrawn {
+                        continue;
                     }
-                    Ok::<(), ApplyError>(())
                 }
-            };
+                if tail.len() == OperationResult::STDERR_TAIL_LINES {
+                    tail.pop_front();
+                }
+                tail.push_back(line.clone());
+                emitter
+                    .emit(AppUpdate::OperationApplyStderr {
+                        index,
+                        stderr: line,
+                    })
+                    .await?;
+            }
+            Ok::<_, ApplyError>(tail)
+        }
+    };
 
-            if let Err(error) = tokio::try_join!(output_task, stdout_task, stderr_task) {
-                emit(AppUpdate::OperationApplyComplete {
-                    index,
-                    error: Some(error.to_string()),
-                })
-                .await?;
-                return Err(error);
-            } else {
-                emit(AppUpdate::OperationApplyComplete { index, error: None }).await?;
+    // Joined, not tried: a failed command's last lines on stderr are
+    // usually why it failed, so they're read to the end regardless.
+    let (output_result, stdout_result, stderr_result) =
+        tokio::join!(output, stdout_task, stderr_task);
+    let (stdout_tail, stdout_result) = match stdout_result {
+        Ok(tail) => (Vec::from(tail), Ok(())),
+        Err(error) => (Vec::new(), Err(error)),
+    };
+    let (stderr_tail, stderr_result) = match stderr_result {
+        Ok(tail) => (Vec::from(tail), Ok(())),
+        Err(error) => (Vec::new(), Err(error)),
+    };
+    let outcome = output_result.and(stdout_result).and(stderr_result);
+    let mut result = OperationResult {
+        stdout_tail,
+        stderr_tail,
+        duration_ms: u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+        ..OperationResult::default()
+    };
+    if let Err(error) = &outcome {
+        result.error = Some(error.to_string());
+        result.code = Some(error.code().to_string());
+        result.exit_code = error.exit_code();
+    }
+    (result, outcome)
+}
+
+/// Read the next line of an operation's stderr into `buf`, ended by `\n` or
+/// `\r\n`, or with `redraws` by a lone `\r` too. Returns whether it was a
+/// lone `\r`, as a progress meter redraws its line, or `None` at the end.
+async fn read_stderr_line(
+    reader: &mut (impl AsyncBufRead + Unpin),
+    buf: &mut Vec<u8>,
+    redraws: bool,
+) -> std::io::Result<Option<bool>> {
+    buf.clear();
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            return Ok((!buf.is_empty()).then_some(false));
+        }
+        let end = available
+            .iter()
+            .position(|&b| b == b'\n' || (redraws && b == b'\r'));
+        let Some(end) = end else {
+            let read = available.len();
+            buf.extend_from_slice(available);
+            reader.consume(read);
+            continue;
+        };
+        let carriage_return = available[end] == b'\r';
+        buf.extend_from_slice(&available[..end]);
+        reader.consume(end + 1);
+        if !carriage_return {
+            if buf.last() == Some(&b'\r') {
+                buf.pop();
             }
+            return Ok(Some(false));
+        }
+        let newline = reader.fill_buf().await?.first() == Some(&b'\n');
+        if newline {
+            reader.consume(1);
         }
+        return Ok(Some(!newline));
     }
+}
 
-    info!("Apply completed");
+/// An operation, with the retry policy of the plan item it's from.
+#[derive(Debug, Clone)]
+struct PlannedOperation {
+    operation: Operation,
+    retry: Option<RetryPolicy>,
+}
+
+impl Display for PlannedOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.operation.fmt(f)
+    }
+}
+
+impl Render for PlannedOperation {
+    fn render(&self) -> View {
+        self.operation.render()
+    }
+}
+
+/// [`Operation::merge`] an epoch's operations, each with those sharing its
+/// retry policy, so a retry never reruns another item's operation or skips
+/// its own policy. Policies keep the order they first appear in.
+fn merge_operations(operations: Vec<PlannedOperation>) -> Vec<PlannedOperation> {
+    let mut groups: Vec<(Option<RetryPolicy>, Vec<Operation>)> = Vec::new();
+    for PlannedOperation { operation, retry } in operations {
+        match groups.iter_mut().find(|(policy, _)| *policy == retry) {
+            Some((_, group)) => group.push(operation),
+            None => groups.push((retry, vec![operation])),
+        }
+    }
+    groups
+        .into_iter()
+        .flat_map(|(retry, operations)| {
+            Operation::merge(operations)
+                .into_iter()
+                .map(move |operation| PlannedOperation {
+                    operation,
+                    retry: retry.clone(),
+                })
+        })
+        .collect()
+}
+
+/// How long to wait before retrying an operation that failed with `error`
+/// after `retries` retries, if its `retry` policy says to retry it.
+fn retry_delay(retry: &Option<RetryPolicy>, retries: u32, error: &ApplyError) -> Option<Duration> {
+    let retry = retry.as_ref()?;
+    retry
+        .retries(retries, error.exit_code())
+        .then(|| retry.delay_before(retries))
+}
+
+/// Inputs for [`explain`]. `root_path`, `plan_id` and `params_json` are as
+/// in [`ApplyOptions`]; `node_id` selects the plan node to explain, either by
+/// its bare item id or by its full rendered [`PlanNodeId`].
+pub struct ExplainOptions {
+    pub root_path: PathBuf,
+    pub plan_id: PlanId,
+    pub params_json: Option<String>,
+    pub machine_json: Option<String>,
+    pub node_id: String,
+}
+
+/// Evaluate a plan and explain the epoch placement of one of its nodes (see
+/// [`explain_node`]), returning the explanation as human-readable text.
+///
+/// Runs only the planning phase: no resource states are probed, nothing is
+/// applied, and no [`AppUpdate`]s are emitted. Epochs are therefore those of
+/// the planned resource params, which is the granularity plan authors write
+/// `requires` / `required_by` at.
+pub async fn explain(options: ExplainOptions) -> Result<String, ApplyError> {
+    let ExplainOptions {
+        root_path,
+        plan_id,
+        params_json,
+        machine_json,
+        node_id,
+    } = options;
+
+    let ctx = Context::create(&root_path)?;
+    let mut store = Store::new(ctx.paths().cache_dir());
+    let system = System::get().await?;
+    let param_values = parse_params_json(params_json)?;
+    let params_ctx = params_context(&root_path, machine_json)?;
+
+    let resource_params = plan(plan_id, param_values, &params_ctx, &mut store, &system).await?;
+    plan_warnings(&params_ctx);
+
+    let mut candidates: Vec<PlanNodeId> = Vec::new();
+    collect_matching_ids(&resource_params, &node_id, &mut candidates);
+    let id = match candidates.as_slice() {
+        [] => return Err(ApplyError::UnknownNodeId(node_id)),
+        [id] => id.clone(),
+        _ => {
+            return Err(ApplyError::AmbiguousNodeId {
+                node_id,
+                candidates: candidates.iter().map(ToString::to_string).collect(),
+            });
+        }
+    };
+
+    let explanation = explain_node(&resource_params.map(Some), &id)?;
+    Ok(explanation.to_string())
+}
+
+/// Inputs for [`export_script`]. Fields are as in [`ApplyOptions`]; secrets
+/// are loaded the same way so secret-backed resources can be probed.
+pub struct ExportScriptOptions {
+    pub root_path: PathBuf,
+    pub plan_id: PlanId,
+    pub params_json: Option<String>,
+    pub machine_json: Option<String>,
+    pub identity_path: Option<PathBuf>,
+    pub secrets_dir: Option<PathBuf>,
+    pub guest_mode: bool,
+    pub protections: Protections,
+}
+
+/// Run the pipeline up to the merged, per-epoch operations (phases 1–7 minus
+/// the apply) and render them as a commented `sh` script, for targets where
+/// lusid itself can't run. No [`AppUpdate`]s are emitted.
+///
+/// Operations without a shell equivalent (see [`Operation::script`]) are kept
+/// in the script as `# UNSUPPORTED:` comments, so a reader can see what must
+/// be done by other means.
+///
+/// Resources are checked against `protections` and each other's claims as
+/// in [`apply`], so a script never does what the apply would refuse to.
+///
+/// Note(cc): resource states are probed on the host running this, so the
+/// script is only right for targets in the same state as this host.
+pub async fn export_script(options: ExportScriptOptions) -> Result<String, ApplyError> {
+    let ExportScriptOptions {
+        root_path,
+        plan_id,
+        params_json,
+        machine_json,
+        identity_path,
+        secrets_dir,
+        guest_mode,
+        protections,
+    } = options;
+
+    let mut ctx = Context::create(&root_path)?;
+    let mut store = Store::new(ctx.paths().cache_dir());
+    let system = System::get().await?;
+    let secrets_dir = secrets_dir.unwrap_or_else(|| root_path.join("secrets"));
+    let secrets = Secrets::load(&secrets_dir, identity_path.as_deref(), guest_mode).await?;
+    ctx.set_secrets(secrets);
+    let param_values = parse_params_json(params_json)?;
+    let params_ctx = params_context(&root_path, machine_json)?;
+
+    let resource_params = plan(
+        plan_id.clone(),
+        param_values,
+        &params_ctx,
+        &mut store,
+        &system,
+    )
+    .await?;
+    plan_warnings(&params_ctx);
+    let resource_params = FlatTree::from(resource_params);
+    let validations = resource_params
+        .leaves()
+        .map(|params| params.node.validate_host_paths());
+    futures_util::future::try_join_all(validations).await?;
+
+    let resources = resource_params
+        .map_tree(
+            |node, meta| {
+                PlanTree::branch(meta, map_declared_subitems(node, |node| node.resources()))
+            },
+            |_, _| async { Ok::<_, ApplyError>(()) },
+        )
+        .await?;
+    check_resources(resources.leaves(), &protections)?;
+    let resource_states = resources
+        .map_batch_result_async(
+            |resources| observe_states(&mut ctx, resources),
+            |_| async { Ok(()) },
+            |_, _| async { Ok(()) },
+        )
+        .await?;
+    let resource_changes = resource_states
+        .map(
+            |(resource, state)| resource_change(resource, &state),
+            |_, _| async { Ok::<_, ApplyError>(()) },
+        )
+        .await?;
+    let operations = resource_changes
+        .map_tree(
+            |node, meta| match node {
+                Some(node) => {
+                    let children = map_plan_subitems(node.node, |node| node.operations())
+                        .map(|tree| tree.map(Some));
+                    PlanTree::branch(meta, children)
+                }
+                None => PlanTree::leaf(meta, None),
+            },
+            |_, _| async { Ok::<_, ApplyError>(()) },
+        )
+        .await?;
+    let operation_epochs = compute_epochs(CausalityTree::from(operations))?;
+
+    Ok(render_script(&plan_id, operation_epochs))
+}
+
+/// Refuse resources claiming what another resource already claims (see
+/// [`Claims`]), or removing or overwriting something in `protections`.
+fn check_resources<'a>(
+    resources: impl Iterator<Item = &'a Declared<Resource>>,
+    protections: &Protections,
+) -> Result<(), ApplyError> {
+    let mut claims = Claims::new();
+    for resource in resources {
+        claims.claim(&resource.node, resource.to_string())?;
+        protections.check(&resource.node)?;
+    }
     Ok(())
 }
 
+/// The change to bring `resource` from `state` to what it should be, if any,
+/// declared where the resource is.
+fn resource_change(
+    resource: Declared<Resource>,
+    state: &ResourceState,
+) -> Option<Declared<ResourceChange>> {
+    let Declared {
+        node,
+        location,
+        retry,
+    } = resource;
+    node.change(state)
+        .map(|change| Declared::new(change, location).with_retry(retry))
+}
+
+/// A change as the TUI shows it, led by its [`Severity`] unless that's
+/// [`Severity::Safe`], so changes that stop or delete things stand out, and
+/// followed by where it's declared.
+fn render_change(change: &Declared<ResourceChange>) -> View {
+    match change.node.severity() {
+        Severity::Safe => change.render(),
+        severity => View::Fragment(Fragment::new(vec![
+            severity.render(),
+            View::Span(": ".into()),
+            change.render(),
+        ])),
+    }
+}
+
+/// Pair each resource with its observed state.
+async fn observe_states(
+    ctx: &mut Context,
+    resources: Vec<Declared<Resource>>,
+) -> Result<Vec<(Declared<Resource>, ResourceState)>, ApplyError> {
+    let nodes: Vec<&Resource> = resources.iter().map(|resource| &resource.node).collect();
+    let states = Resource::states(ctx, &nodes).await?;
+    Ok(resources.into_iter().zip(states).collect())
+}
+
+fn render_script(plan_id: &PlanId, epochs: Vec<Vec<Operation>>) -> String {
+    let mut script = String::new();
+    script.push_str("#!/bin/sh\n");
+    script.push_str(&format!(
+        "# Exported by `lusid plan export-script` from {plan_id}.\n"
+    ));
+    script.push_str(
+        "# Operations within an epoch are independent; each epoch needs the ones before it.\n",
+    );
+    script.push_str("set -eu\n");
+    if epochs.is_empty() {
+        script.push_str("\n# No changes to apply.\n");
+    }
+    for (epoch_index, operations) in epochs.into_iter().enumerate() {
+        script.push_str(&format!("\n# Epoch {epoch_index}\n"));
+        for operation in Operation::merge(operations) {
+            match operation.script() {
+                Some(line) => {
+                    script.push_str(&format!("# {operation}\n{line}\n"));
+                }
+                None => {
+                    script.push_str(&format!("# UNSUPPORTED: {operation}\n"));
+                }
+            }
+        }
+    }
+    script
+}
+
+/// Inputs for [`render`]. Fields are as in [`ApplyOptions`]; secrets are
+/// loaded only so their plaintexts can be redacted from the output.
+pub struct RenderOptions {
+    pub root_path: PathBuf,
+    pub plan_id: PlanId,
+    pub params_json: Option<String>,
+    pub machine_json: Option<String>,
+    pub identity_path: Option<PathBuf>,
+    pub secrets_dir: Option<PathBuf>,
+    pub guest_mode: bool,
+}
+
+/// Evaluate a plan into a [`RenderedPlan`]: every plan item's resolved params
+/// and the resource atoms they expand to, for reviewing and diffing.
+///
+/// Runs only the planning and resource-expansion phases: nothing is probed
+/// or applied, and no [`AppUpdate`]s are emitted. Any decrypted secret
+/// plaintext that shows up in the output (e.g. passed in via `--params`) is
+/// replaced by the [`Redactor`].
+pub async fn render(options: RenderOptions) -> Result<RenderedPlan, ApplyError> {
+    let (plan, resource_params, redactor) = plan_for_render(options).await?;
+    Ok(render_plan_document(plan, resource_params, &redactor))
+}
+
+/// The planning half of [`render`] and [`render_cloud_init`]: the plan's path
+/// relative to the project root, its evaluated tree, and a redactor for its
+/// secrets.
+async fn plan_for_render(
+    options: RenderOptions,
+) -> Result<(String, PlanTree<Declared<ResourceParams>>, Redactor), ApplyError> {
+    let RenderOptions {
+        root_path,
+        plan_id,
+        params_json,
+        machine_json,
+        identity_path,
+        secrets_dir,
+        guest_mode,
+    } = options;
+
+    let ctx = Context::create(&root_path)?;
+    let mut store = Store::new(ctx.paths().cache_dir());
+    let system = System::get().await?;
+    let secrets_dir = secrets_dir.unwrap_or_else(|| root_path.join("secrets"));
+    let secrets = Secrets::load(&secrets_dir, identity_path.as_deref(), guest_mode).await?;
+    let redactor = secrets.redactor();
+    let param_values = parse_params_json(params_json)?;
+    let params_ctx = params_context(&root_path, machine_json)?;
+
+    let resource_params = plan(
+        plan_id.clone(),
+        param_values,
+        &params_ctx,
+        &mut store,
+        &system,
+    )
+    .await?;
+    plan_warnings(&params_ctx);
+
+    let root_path = root_path.canonicalize().unwrap_or(root_path);
+    let plan = relative_plan_path(plan_id, &root_path);
+
+    Ok((plan, resource_params, redactor))
+}
+
+/// A plan's path relative to the (canonical) project root, for output that
+/// shouldn't depend on where the project is checked out.
+fn relative_plan_path(plan_id: PlanId, root_path: &Path) -> String {
+    match plan_id.as_path() {
+        Some(path) => path
+            .strip_prefix(root_path)
+            .unwrap_or(path.as_path())
+            .display()
+            .to_string(),
+        None => String::new(),
+    }
+}
+
+/// A plan node flattened out of its tree, keyed by its rendered id path.
+struct RenderItem {
+    path: String,
+    ancestors: Vec<String>,
+    meta: PlanMeta,
+    params: Option<ResourceParams>,
+}
+
+fn render_plan_document(
+    plan: String,
+    tree: PlanTree<Declared<ResourceParams>>,
+    redactor: &Redactor,
+) -> RenderedPlan {
+    // The root is the anonymous branch `plan` wraps the top-level items in.
+    let top_level = match tree {
+        PlanTree::Branch { children, .. } => children,
+        leaf => vec![leaf],
+    };
+    let mut items = Vec::new();
+    for (index, child) in top_level.into_iter().enumerate() {
+        flatten_render_items(child, index, &[], &mut items);
+    }
+
+    let paths: HashMap<&PlanNodeId, &str> = items
+        .iter()
+        .filter_map(|item| Some((item.meta.id.as_ref()?, item.path.as_str())))
+        .collect();
+
+    // `requires` as declared, plus the reverse of every `required_by`.
+    let mut ordering: HashMap<&str, BTreeSet<String>> = HashMap::new();
+    for item in &items {
+        for id in &item.meta.requires {
+            if let Some(target) = paths.get(id) {
+                ordering
+                    .entry(item.path.as_str())
+                    .or_default()
+                    .insert(target.to_string());
+            }
+        }
+        for id in &item.meta.required_by {
+            if let Some(target) = paths.get(id) {
+                ordering
+                    .entry(*target)
+                    .or_default()
+                    .insert(item.path.clone());
+            }
+        }
+    }
+
+    let resources = items
+        .iter()
+        .filter_map(|item| {
+            let params = item.params.as_ref()?;
+            // A sub-plan's ordering applies to every item inside it.
+            let requires = item
+                .ancestors
+                .iter()
+                .map(String::as_str)
+                .chain([item.path.as_str()])
+                .filter_map(|path| ordering.get(path))
+                .flatten()
+                .cloned()
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect();
+            let mut atoms = Vec::new();
+            for tree in params.clone().resources() {
+                collect_leaves(tree, &mut |atom: Resource| {
+                    atoms.push(redactor.redact(&atom.to_string()))
+                });
+            }
+            Some(RenderedResource {
+                id: item.path.clone(),
+                params: redact_json(
+                    serde_json::to_value(params).expect("resource params serialize to JSON"),
+                    redactor,
+                ),
+                requires,
+                atoms,
+            })
+        })
+        .collect();
+
+    RenderedPlan { plan, resources }
+}
+
+fn flatten_render_items(
+    tree: PlanTree<Declared<ResourceParams>>,
+    index: usize,
+    ancestors: &[String],
+    out: &mut Vec<RenderItem>,
+) {
+    let (meta, children, params) = match tree {
+        PlanTree::Branch { meta, children } => (meta, children, None),
+        // A rendered plan is diffed between releases, so it leaves out plan
+        // lines, which shift with every edit above them.
+        PlanTree::Leaf { meta, node } => (meta, Vec::new(), Some(node.node)),
+    };
+    let segment = match &meta.id {
+        Some(PlanNodeId::PlanItem { item_id, .. }) => item_id.clone(),
+        _ => format!("#{index}"),
+    };
+    let path = match ancestors.last() {
+        Some(parent) => format!("{parent}/{segment}"),
+        None => segment,
+    };
+
+    let mut child_ancestors = ancestors.to_vec();
+    child_ancestors.push(path.clone());
+    out.push(RenderItem {
+        path,
+        ancestors: ancestors.to_vec(),
+        meta,
+        params,
+    });
+    for (index, child) in children.into_iter().enumerate() {
+        flatten_render_items(child, index, &child_ancestors, out);
+    }
+}
+
+/// `value` with every string in it, keys included, redacted.
+fn redact_json(value: serde_json::Value, redactor: &Redactor) -> serde_json::Value {
+    match value {
+        serde_json::Value::String(string) => serde_json::Value::String(redactor.redact(&string)),
+        serde_json::Value::Array(items) => items
+            .into_iter()
+            .map(|item| redact_json(item, redactor))
+            .collect(),
+        serde_json::Value::Object(map) => map
+            .into_iter()
+            .map(|(key, value)| (redactor.redact(&key), redact_json(value, redactor)))
+            .collect(),
+        other => other,
+    }
+}
+
+fn collect_leaves<Node, Meta>(tree: Tree<Node, Meta>, visit: &mut impl FnMut(Node)) {
+    match tree {
+        Tree::Branch { children, .. } => {
+            for child in children {
+                collect_leaves(child, visit);
+            }
+        }
+        Tree::Leaf { node, .. } => visit(node),
+    }
+}
+
+fn collect_matching_ids<Node>(tree: &PlanTree<Node>, node_id: &str, out: &mut Vec<PlanNodeId>) {
+    let (meta, children) = match tree {
+        PlanTree::Branch { meta, children } => (meta, children.as_slice()),
+        PlanTree::Leaf { meta, .. } => (meta, [].as_slice()),
+    };
+    if let Some(id) = &meta.id {
+        let matches = match id {
+            PlanNodeId::PlanItem { item_id, .. } => item_id == node_id,
+            _ => false,
+        };
+        if matches || id.to_string() == node_id {
+            out.push(id.clone());
+        }
+    }
+    for child in children {
+        collect_matching_ids(child, node_id, out);
+    }
+}
+
+/// Parse the `--params` JSON object into a Rimu value. CLI-supplied params
+/// have no source, so they get an empty [`SourceId`].
+fn parse_params_json(params_json: Option<String>) -> Result<Option<Spanned<Value>>, ApplyError> {
+    match params_json {
+        None => {
+            info!("no parameters provided");
+            Ok(None)
+        }
+        Some(json) => {
+            let value: serde_json::Value =
+                serde_json::from_str(&json).map_err(ApplyError::JsonParameters)?;
+            let value = to_rimu(value, SourceId::empty())?;
+            Ok(Some(value))
+        }
+    }
+}
+
+/// The [`ParamsContext`] to plan with, carrying the machine facts for param
+/// defaults when there are any.
+fn params_context(
+    root_path: &Path,
+    machine_json: Option<String>,
+) -> Result<ParamsContext, ApplyError> {
+    let params_ctx = ParamsContext::new(root_path);
+    let Some(json) = machine_json else {
+        return Ok(params_ctx);
+    };
+    let value: serde_json::Value = serde_json::from_str(&json).map_err(ApplyError::JsonMachine)?;
+    Ok(params_ctx.with_machine(to_rimu(value, SourceId::empty())?))
+}
+
 /// Serializes access to stdout across the apply. Operation stdout/stderr are
 /// drained concurrently via `tokio::try_join!`, so without a mutex two
 /// `emit()` calls can interleave — one task's JSON can land between another's
@@ -390,24 +1567,138 @@ pub async fn apply(options: ApplyOptions) -> Result<(), ApplyError> {
 /// AppUpdates with large trees exceed that easily.
 static EMIT_LOCK: LazyLock<Mutex<()>> = LazyLock::new(|| Mutex::new(()));
 
-/// Serialize `update` to a single JSON line on stdout and flush.
-///
-/// The flush is load-bearing: the TUI reads line-by-line with
-/// `AsyncBufRead::lines()`, so buffering would make progress updates
-/// invisible to the reader even though the work completed long before.
-async fn emit(update: AppUpdate) -> Result<(), ApplyError> {
-    let mut line = serde_json::to_vec(&update).map_err(ApplyError::JsonOutput)?;
-    line.push(b'\n');
+/// Where an apply's [`AppUpdate`]s go: stdout for `lusid-apply`, or an
+/// embedder's [`ApplyUpdates`] stream.
+#[derive(Debug, Clone)]
+pub(crate) enum Emitter {
+    Stdout,
+    Channel(mpsc::UnboundedSender<AppUpdate>),
+}
 
-    let _guard = EMIT_LOCK.lock().await;
-    let mut stdout = tokio::io::stdout();
+impl Emitter {
+    /// Send `update` on. On stdout, it's serialized to a single JSON line
+    /// and flushed.
+    ///
+    /// The flush is load-bearing: the TUI reads line-by-line with
+    /// `AsyncBufRead::lines()`, so buffering would make progress updates
+    /// invisible to the reader even though the work completed long before.
+    async fn emit(&self, update: AppUpdate) -> Result<(), ApplyError> {
+        match self {
+            Emitter::Stdout => {
+                let mut line = serde_json::to_vec(&update).map_err(ApplyError::JsonOutput)?;
+                line.push(b'\n');
 
-    stdout
-        .write_all(&line)
-        .await
-        .map_err(ApplyError::WriteStdout)?;
+                let _guard = EMIT_LOCK.lock().await;
+                let mut stdout = tokio::io::stdout();
 
-    stdout.flush().await.map_err(ApplyError::FlushStdout)?;
+                stdout
+                    .write_all(&line)
+                    .await
+                    .map_err(ApplyError::WriteStdout)?;
 
-    Ok(())
+                stdout.flush().await.map_err(ApplyError::FlushStdout)?;
+
+                Ok(())
+            }
+            Emitter::Channel(sender) => sender.send(update).map_err(|_| ApplyError::UpdatesClosed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lusid_operation::operations::service::ServiceOperation;
+
+    use super::*;
+
+    fn restart(unit: &str, retry: Option<RetryPolicy>) -> PlannedOperation {
+        PlannedOperation {
+            operation: Operation::Service(ServiceOperation::Restart { unit: unit.into() }),
+            retry,
+        }
+    }
+
+    fn retry(count: u32) -> Option<RetryPolicy> {
+        Some(RetryPolicy {
+            count,
+            delay: RetryPolicy::DEFAULT_DELAY,
+            on_exit_codes: vec![],
+        })
+    }
+
+    fn merged(operations: Vec<PlannedOperation>) -> Vec<(String, Option<RetryPolicy>)> {
+        merge_operations(operations)
+            .into_iter()
+            .map(|PlannedOperation { operation, retry }| (operation.to_string(), retry))
+            .collect()
+    }
+
+    #[test]
+    fn merge_collapses_operations_sharing_a_policy() {
+        assert_eq!(
+            merged(vec![
+                restart("nginx.service", retry(3)),
+                restart("nginx.service", retry(3)),
+                restart("caddy.service", None),
+                restart("caddy.service", None),
+            ]),
+            vec![
+                ("Service::Restart(nginx.service)".to_owned(), retry(3)),
+                ("Service::Restart(caddy.service)".to_owned(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_keeps_operations_with_different_policies_apart() {
+        assert_eq!(
+            merged(vec![
+                restart("nginx.service", retry(3)),
+                restart("nginx.service", None),
+                restart("nginx.service", retry(1)),
+                restart("nginx.service", retry(3)),
+            ]),
+            vec![
+                ("Service::Restart(nginx.service)".to_owned(), retry(3)),
+                ("Service::Restart(nginx.service)".to_owned(), None),
+                ("Service::Restart(nginx.service)".to_owned(), retry(1)),
+            ]
+        );
+    }
+
+    async fn read_lines(input: &[u8], redraws: bool) -> Vec<(String, bool)> {
+        let mut reader = input;
+        let mut buf = Vec::new();
+        let mut lines = Vec::new();
+        while let Some(redrawn) = read_stderr_line(&mut reader, &mut buf, redraws)
+            .await
+            .unwrap()
+        {
+            lines.push((String::from_utf8_lossy(&buf).into_owned(), redrawn));
+        }
+        lines
+    }
+
+    #[tokio::test]
+    async fn splits_stderr_on_redraws_only_with_progress() {
+        let input = b"Receiving objects:  50%\rReceiving objects: 100%, done.\r\nwarning: x\n";
+        assert_eq!(
+            read_lines(input, true).await,
+            vec![
+                ("Receiving objects:  50%".to_owned(), true),
+                ("Receiving objects: 100%, done.".to_owned(), false),
+                ("warning: x".to_owned(), false),
+            ]
+        );
+        assert_eq!(
+            read_lines(input, false).await,
+            vec![
+                (
+                    "Receiving objects:  50%\rReceiving objects: 100%, done.".to_owned(),
+                    false
+                ),
+                ("warning: x".to_owned(), false),
+            ]
+        );
+    }
 }
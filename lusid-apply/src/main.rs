@@ -1,14 +1,39 @@
 //! `lusid-apply` CLI entry point. Tracing goes to stderr so stdout stays
 //! clean for the [`AppUpdate`](lusid_apply_stdio::AppUpdate) JSON stream.
-//! Exits non-zero on any pipeline error (the error is also logged).
+//! Exits non-zero on any pipeline error. The error is printed to stderr as a
+//! plain diagnostic (see [`ApplyError::render`]) rather than through tracing,
+//! so source excerpts keep their column alignment; or, with
+//! `--error-format json`, as one [`ErrorEnvelope`](lusid_apply_stdio::ErrorEnvelope)
+//! JSON line for wrappers to branch on its `code`.
+//!
+//! `--explain <NODE_ID>`, `--export-script`, `--render` and
+//! `--render-cloud-init` are the exceptions to the stdout protocol: they skip
+//! the apply entirely and print a plain-text explanation, a shell script, or
+//! a [`RenderedPlan`](lusid_apply_stdio::RenderedPlan) or
+//! [`RenderedCloudInit`](lusid_apply_stdio::RenderedCloudInit) JSON document
+//! instead.
+//!
+//! With `--control`, stdin is read as newline-delimited
+//! [`AppControl`] JSON, so the TUI can change the log filter mid-apply, and
+//! pause, resume or cancel it between operations.
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use lusid_apply_stdio::AppControl;
+use lusid_ctx::DownloadLimits;
+use lusid_operation::operations::file::FilePath;
 use lusid_plan::PlanId;
+use lusid_resource::protect::Protections;
+use std::io::BufRead;
 use std::path::PathBuf;
-use tracing::{debug, error};
-use tracing_subscriber::{EnvFilter, fmt};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+use tracing_subscriber::{EnvFilter, Registry, fmt, prelude::*, reload};
 
-use lusid_apply::{ApplyOptions, apply};
+use lusid_apply::{
+    ApplyError, ApplyOptions, ExplainOptions, ExportScriptOptions, RenderOptions, apply,
+    apply_with_controls, explain, export_script, render, render_cloud_init,
+};
 
 #[derive(Parser, Debug)]
 #[command(name = "lusid-apply", about = "Apply a Lusid plan.", version)]
@@ -25,6 +50,11 @@ struct Cli {
     #[arg(long = "params")]
     params_json: Option<String>,
 
+    /// Facts about the machine being applied to, as a JSON string (top-level
+    /// object), for the plan's param defaults.
+    #[arg(long = "machine")]
+    machine_json: Option<String>,
+
     /// Path to the age/SSH identity file used to decrypt project secrets.
     /// Omit to run without secrets (plans referencing `@core/secret` will
     /// fail at apply time).
@@ -43,44 +73,301 @@ struct Cli {
     #[arg(long = "guest-mode")]
     guest_mode: bool,
 
+    /// Run operations as this process's user rather than under `sudo`, for
+    /// targets where lusid-apply already runs as root and may have no `sudo`,
+    /// like a container.
+    #[arg(long = "no-sudo")]
+    no_sudo: bool,
+
+    /// Log the operations tree annotated with requires / required_by edges
+    /// and each operation's resolved epoch, to explain the apply ordering.
+    #[arg(long = "explain-ordering")]
+    explain_ordering: bool,
+
+    /// Instead of applying, explain why the plan node with this id lands in
+    /// its epoch, printing the explanation as text on stdout.
+    #[arg(long = "explain", value_name = "NODE_ID")]
+    explain_node_id: Option<String>,
+
+    /// Instead of applying, print the operations that would run as a shell
+    /// script on stdout.
+    #[arg(long = "export-script", conflicts_with = "explain_node_id")]
+    export_script: bool,
+
+    /// Instead of applying, print the evaluated plan's resources as a JSON
+    /// document on stdout.
+    #[arg(long = "render", conflicts_with_all = ["explain_node_id", "export_script"])]
+    render: bool,
+
+    /// Instead of applying, print the part of the evaluated plan cloud-init
+    /// can express as a JSON document on stdout.
+    #[arg(long = "render-cloud-init", conflicts_with_all = ["explain_node_id", "export_script", "render"])]
+    render_cloud_init: bool,
+
+    /// Report the changes an apply would make, then simulate their
+    /// operations without applying them: package installs run their package
+    /// manager's dry run, other operations report what they would run.
+    #[arg(long = "check", conflicts_with_all = ["explain_node_id", "export_script", "render", "render_cloud_init"])]
+    check: bool,
+
+    /// Apply changes that delete things re-applying can't bring back, like
+    /// removing a directory or a user. Without it, such an apply stops after
+    /// reporting its changes.
+    #[arg(long = "allow-destructive", conflicts_with_all = ["explain_node_id", "export_script", "render", "render_cloud_init"])]
+    allow_destructive: bool,
+
+    /// Also refuse to remove or overwrite this path, or anything above it.
+    /// Repeatable.
+    #[arg(long = "protect-path", value_name = "PATH", value_parser = |path: &str| FilePath::parse(path))]
+    protect_paths: Vec<FilePath>,
+
+    /// Also refuse to remove this package. Repeatable.
+    #[arg(long = "protect-package", value_name = "PACKAGE")]
+    protect_packages: Vec<String>,
+
+    /// Also refuse to remove this user. Repeatable.
+    #[arg(long = "protect-user", value_name = "USER")]
+    protect_users: Vec<String>,
+
+    /// Start from no protections, rather than the built-in paths, packages
+    /// and users (`/etc`, `openssh-server`, `root`, ...).
+    #[arg(long = "no-default-protections")]
+    no_default_protections: bool,
+
+    /// Wait up to this many seconds for other writers to the machine, like
+    /// apt holding the dpkg lock or another lusid-apply, before failing.
+    #[arg(long = "wait-for-locks", value_name = "SECS", default_value_t = 0)]
+    wait_for_locks: u64,
+
+    /// Run at most this many downloads at once.
+    #[arg(long = "max-parallel-downloads", value_name = "N")]
+    max_parallel_downloads: Option<usize>,
+
+    /// Download at most this many KiB per second, across all downloads.
+    #[arg(long = "max-download-rate", value_name = "KIB_PER_SEC")]
+    max_download_rate: Option<u64>,
+
+    /// How to print a fatal error on stderr.
+    #[arg(long = "error-format", value_enum, default_value = "human")]
+    error_format: ErrorFormat,
+
     /// Log level (e.g., trace, debug, info, warn, error). Default: info.
     #[arg(long = "log", default_value = "info")]
     log: String,
+
+    /// Read `AppControl` JSON lines from stdin, e.g. to change `--log` or
+    /// pause the apply while running.
+    #[arg(long = "control")]
+    control: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ErrorFormat {
+    /// The message, plus a plan source excerpt when there is one.
+    Human,
+    /// A single JSON error envelope line.
+    Json,
 }
 
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
-    install_tracing(&cli.log);
+    let log_filter = install_tracing(&cli.log);
+    let controls = cli.control.then(|| read_control(log_filter));
     debug!(cli = ?cli, "parsed cli");
+    if cli.no_sudo {
+        lusid_cmd::set_sudo(false);
+    }
 
     let plan_path = cli
         .plan_path
         .canonicalize()
         .unwrap_or(cli.plan_path.clone());
     let plan_id = PlanId::Path(plan_path.clone());
+
+    if let Some(node_id) = cli.explain_node_id {
+        let options = ExplainOptions {
+            root_path: cli.root_path,
+            plan_id,
+            params_json: cli.params_json,
+            machine_json: cli.machine_json,
+            node_id,
+        };
+        match explain(options).await {
+            Ok(explanation) => print!("{explanation}"),
+            Err(err) => {
+                report(&err, cli.error_format);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let mut protections = if cli.no_default_protections {
+        Protections::none()
+    } else {
+        Protections::default()
+    };
+    protections.paths.extend(cli.protect_paths);
+    protections.packages.extend(cli.protect_packages);
+    protections.users.extend(cli.protect_users);
+
+    if cli.export_script {
+        let options = ExportScriptOptions {
+            root_path: cli.root_path,
+            plan_id,
+            params_json: cli.params_json,
+            machine_json: cli.machine_json,
+            identity_path: cli.identity_path,
+            secrets_dir: cli.secrets_dir,
+            guest_mode: cli.guest_mode,
+            protections,
+        };
+        match export_script(options).await {
+            Ok(script) => print!("{script}"),
+            Err(err) => {
+                report(&err, cli.error_format);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if cli.render {
+        let options = RenderOptions {
+            root_path: cli.root_path,
+            plan_id,
+            params_json: cli.params_json,
+            machine_json: cli.machine_json,
+            identity_path: cli.identity_path,
+            secrets_dir: cli.secrets_dir,
+            guest_mode: cli.guest_mode,
+        };
+        let rendered = render(options).await.and_then(|rendered| {
+            serde_json::to_string_pretty(&rendered).map_err(ApplyError::JsonOutput)
+        });
+        match rendered {
+            Ok(json) => println!("{json}"),
+            Err(err) => {
+                report(&err, cli.error_format);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if cli.render_cloud_init {
+        let options = RenderOptions {
+            root_path: cli.root_path,
+            plan_id,
+            params_json: cli.params_json,
+            machine_json: cli.machine_json,
+            identity_path: cli.identity_path,
+            secrets_dir: cli.secrets_dir,
+            guest_mode: cli.guest_mode,
+        };
+        let rendered = render_cloud_init(options).await.and_then(|rendered| {
+            serde_json::to_string_pretty(&rendered).map_err(ApplyError::JsonOutput)
+        });
+        match rendered {
+            Ok(json) => println!("{json}"),
+            Err(err) => {
+                report(&err, cli.error_format);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     let options = ApplyOptions {
         root_path: cli.root_path,
         plan_id,
         params_json: cli.params_json,
+        machine_json: cli.machine_json,
         identity_path: cli.identity_path,
         secrets_dir: cli.secrets_dir,
         guest_mode: cli.guest_mode,
+        explain_ordering: cli.explain_ordering,
+        check: cli.check,
+        allow_destructive: cli.allow_destructive,
+        protections,
+        lock_timeout: Duration::from_secs(cli.wait_for_locks),
+        download_limits: DownloadLimits {
+            max_parallel: cli.max_parallel_downloads,
+            max_kib_per_sec: cli.max_download_rate,
+        },
     };
 
-    if let Err(err) = apply(options).await {
-        error!("{err}");
+    let result = match controls {
+        Some(controls) => apply_with_controls(options, controls).await,
+        None => apply(options).await,
+    };
+    if let Err(err) = result {
+        report(&err, cli.error_format);
         std::process::exit(1);
     }
 }
 
-fn install_tracing(level: &str) {
+fn report(err: &ApplyError, format: ErrorFormat) {
+    match format {
+        ErrorFormat::Human => eprintln!("{}", err.render()),
+        ErrorFormat::Json => match serde_json::to_string(&err.envelope()) {
+            Ok(json) => eprintln!("{json}"),
+            // Still say something rather than exit silently.
+            Err(_) => eprintln!("{}", err.render()),
+        },
+    }
+}
+
+fn install_tracing(level: &str) -> reload::Handle<EnvFilter, Registry> {
     let filter = EnvFilter::try_new(level).unwrap_or_else(|_| EnvFilter::new("info"));
-    fmt()
-        .with_env_filter(filter)
-        .with_target(true)
-        .with_level(true)
-        .with_ansi(true)
-        .with_writer(std::io::stderr)
+    let (filter, handle) = reload::Layer::new(filter);
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(
+            fmt::layer()
+                .with_target(true)
+                .with_level(true)
+                .with_ansi(true)
+                .with_writer(std::io::stderr),
+        )
         .init();
+    handle
+}
+
+// Reads control messages from stdin until it closes, applying log filter
+// changes at once and passing the rest on to the apply. A plain OS thread
+// rather than a tokio task: a blocking stdin read would otherwise hold up
+// runtime shutdown after the apply is done.
+fn read_control(
+    log_filter: reload::Handle<EnvFilter, Registry>,
+) -> mpsc::UnboundedReceiver<AppControl> {
+    let (controls, receiver) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<AppControl>(&line) {
+                Ok(AppControl::SetLog { filter }) => match EnvFilter::try_new(&filter) {
+                    Ok(new_filter) => {
+                        if log_filter.reload(new_filter).is_err() {
+                            break;
+                        }
+                        info!(filter, "log filter changed");
+                    }
+                    Err(error) => warn!(filter, %error, "invalid log filter"),
+                },
+                Ok(control) => {
+                    // The apply is done, or never started.
+                    if controls.send(control).is_err() {
+                        break;
+                    }
+                }
+                Err(error) => warn!(%error, "invalid control message"),
+            }
+        }
+    });
+    receiver
 }
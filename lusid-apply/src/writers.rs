@@ -0,0 +1,256 @@
+//! Other writers to the machine an apply would step on: a package manager
+//! holding its lock, or another `lusid-apply` mid-apply.
+//!
+//! Checked once the changes are known and before the first operation runs,
+//! so a busy machine fails with [`LockError::Busy`] naming who's busy,
+//! rather than with whatever `apt-get` prints when it can't get the dpkg
+//! lock halfway through an epoch. [`wait_for_writers`] polls until they're
+//! gone or the timeout runs out, and returns the apply's own lock, which
+//! keeps a second `lusid-apply` out until it's dropped.
+//!
+//! Note(cc): the lusid lock lives in the apply user's data dir, so it only
+//! keeps out applies run as the same user. Two users applying to one machine
+//! at once still meet at the package managers' locks.
+
+use std::fmt::Display;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{self, Read, Seek, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+use tracing::warn;
+
+/// The locks dpkg takes: `lock-frontend` for apt and other frontends, `lock`
+/// for dpkg itself. Both are `fcntl` locks on files that always exist.
+const DPKG_LOCKS: &[&str] = &["/var/lib/dpkg/lock-frontend", "/var/lib/dpkg/lock"];
+
+/// pacman's lock: the file exists while pacman runs.
+const PACMAN_LOCK: &str = "/var/lib/pacman/db.lck";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Something else writing to the machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Writer {
+    /// A process holding a dpkg lock: apt, unattended-upgrades, dpkg, ...
+    Dpkg {
+        pid: Option<u32>,
+        command: Option<String>,
+    },
+    Pacman,
+    Lusid {
+        pid: Option<u32>,
+    },
+}
+
+impl Display for Writer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Writer::Dpkg { pid, command } => {
+                write!(f, "dpkg lock held")?;
+                if let Some(pid) = pid {
+                    write!(f, " by pid {pid}")?;
+                }
+                if let Some(command) = command {
+                    write!(f, " ({command})")?;
+                }
+                Ok(())
+            }
+            Writer::Pacman => write!(
+                f,
+                "{PACMAN_LOCK} exists (remove it if no pacman is running)"
+            ),
+            Writer::Lusid { pid: Some(pid) } => write!(f, "lusid-apply running as pid {pid}"),
+            Writer::Lusid { pid: None } => write!(f, "another lusid-apply running"),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum LockError {
+    #[error(
+        "machine is busy after waiting {}s: {}",
+        waited.as_secs(),
+        join(writers)
+    )]
+    Busy {
+        writers: Vec<Writer>,
+        waited: Duration,
+    },
+
+    #[error("failed to lock {path}: {source}")]
+    Lock {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("failed to read /proc/locks: {0}")]
+    ProcLocks(#[source] io::Error),
+}
+
+impl LockError {
+    /// Stable, machine-readable code for this failure.
+    pub fn code(&self) -> &'static str {
+        match self {
+            LockError::Busy { .. } => "apply.busy",
+            LockError::Lock { .. } | LockError::ProcLocks(_) => "apply.lock",
+        }
+    }
+}
+
+/// The apply's own lock, held until dropped.
+#[derive(Debug)]
+pub struct ApplyLock {
+    _file: File,
+}
+
+/// Take the lusid lock at `lock_path` and wait until no package manager
+/// holds its lock, for up to `timeout`, warning once if there's any waiting
+/// to do.
+pub async fn wait_for_writers(lock_path: &Path, timeout: Duration) -> Result<ApplyLock, LockError> {
+    let lock_err = |source| LockError::Lock {
+        path: lock_path.to_path_buf(),
+        source,
+    };
+    if let Some(parent) = lock_path.parent() {
+        std::fs::create_dir_all(parent).map_err(lock_err)?;
+    }
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(lock_path)
+        .map_err(lock_err)?;
+
+    let start = Instant::now();
+    let mut locked = false;
+    let mut waiting = false;
+    loop {
+        let mut writers = Vec::new();
+        if !locked {
+            match file.try_lock() {
+                Ok(()) => {
+                    locked = true;
+                    write_pid(&mut file).map_err(lock_err)?;
+                }
+                Err(TryLockError::WouldBlock) => writers.push(Writer::Lusid {
+                    pid: read_pid(&mut file),
+                }),
+                Err(TryLockError::Error(source)) => return Err(lock_err(source)),
+            }
+        }
+        writers.extend(package_manager_writers().await?);
+
+        if writers.is_empty() {
+            return Ok(ApplyLock { _file: file });
+        }
+        let waited = start.elapsed();
+        if waited >= timeout {
+            return Err(LockError::Busy { writers, waited });
+        }
+        if !waiting {
+            waiting = true;
+            warn!(
+                "waiting up to {}s for other writers: {}",
+                timeout.as_secs(),
+                join(&writers)
+            );
+        }
+        tokio::time::sleep(POLL_INTERVAL.min(timeout - waited)).await;
+    }
+}
+
+fn join(writers: &[Writer]) -> String {
+    writers
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+fn write_pid(file: &mut File) -> io::Result<()> {
+    file.set_len(0)?;
+    file.rewind()?;
+    write!(file, "{}", std::process::id())?;
+    file.flush()
+}
+
+// Best-effort: the holder may not have written its pid yet.
+fn read_pid(file: &mut File) -> Option<u32> {
+    let mut contents = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut contents).ok()?;
+    contents.trim().parse().ok()
+}
+
+async fn package_manager_writers() -> Result<Vec<Writer>, LockError> {
+    let mut writers = Vec::new();
+
+    let dpkg_locks: Vec<(u64, u64)> = DPKG_LOCKS
+        .iter()
+        .filter_map(|path| std::fs::metadata(path).ok())
+        .map(|metadata| (metadata.dev(), metadata.ino()))
+        .collect();
+    if !dpkg_locks.is_empty() {
+        let proc_locks = match tokio::fs::read_to_string("/proc/locks").await {
+            Ok(proc_locks) => proc_locks,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(error) => return Err(LockError::ProcLocks(error)),
+        };
+        if let Some(pid) = proc_locks_holder(&proc_locks, &dpkg_locks) {
+            writers.push(Writer::Dpkg {
+                pid,
+                command: pid.and_then(process_name),
+            });
+        }
+    }
+
+    if tokio::fs::try_exists(PACMAN_LOCK).await.unwrap_or(false) {
+        writers.push(Writer::Pacman);
+    }
+
+    Ok(writers)
+}
+
+/// Whether any of `files`, as `(st_dev, st_ino)`, is locked in `proc_locks`,
+/// the contents of `/proc/locks`: `None` if not, else the holder's pid, when
+/// the kernel reports one. Waiters (lines marked `->`) don't count.
+///
+/// Reading `/proc/locks` rather than trying the lock ourselves means this
+/// works without the root the dpkg lock files need to be opened for writing.
+fn proc_locks_holder(proc_locks: &str, files: &[(u64, u64)]) -> Option<Option<u32>> {
+    proc_locks.lines().find_map(|line| {
+        // e.g. `12: POSIX  ADVISORY  WRITE 4242 fd:01:1048602 0 EOF`
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.get(1) == Some(&"->") {
+            return None;
+        }
+        let pid = fields.get(4)?;
+        let mut id = fields.get(5)?.split(':');
+        let major = u64::from_str_radix(id.next()?, 16).ok()?;
+        let minor = u64::from_str_radix(id.next()?, 16).ok()?;
+        let inode: u64 = id.next()?.parse().ok()?;
+        files
+            .iter()
+            .any(|&(dev, ino)| ino == inode && dev_major(dev) == major && dev_minor(dev) == minor)
+            .then(|| pid.parse().ok())
+    })
+}
+
+// Linux's `st_dev` encoding, as glibc's `major(3)` and `minor(3)` decode it.
+fn dev_major(dev: u64) -> u64 {
+    ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff)
+}
+
+fn dev_minor(dev: u64) -> u64 {
+    (dev & 0xff) | ((dev >> 12) & !0xff)
+}
+
+fn process_name(pid: u32) -> Option<String> {
+    let comm = std::fs::read_to_string(format!("/proc/{pid}/comm")).ok()?;
+    Some(comm.trim().to_owned())
+}
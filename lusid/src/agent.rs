@@ -0,0 +1,495 @@
+//! `lusid agent`: keep the local machine on its plan unattended, as a
+//! lightweight pull-mode agent.
+//!
+//! Every `--interval` the agent runs a cycle: `lusid-apply --check` against
+//! the machine matching `$(hostname)`, and with `--apply`, an apply if the
+//! check found drift. A successful apply is recorded as a
+//! [generation](crate::generations), as `local apply` records one. Each
+//! cycle is summed up as a [`CycleReport`], kept in
+//! `<data_dir>/agent/<machine_id>/` (see [`Paths`]):
+//!
+//! - `state.json` — the [`AgentState`]: how many cycles have run, the last
+//!   one, and when the machine was last in sync or applied. It's read back
+//!   when the agent starts, so the counts survive restarts.
+//! - `reports/<started>.json` — every cycle's report, named by when it
+//!   started as a [run id](crate::logs::run_id) is; only the newest
+//!   [`KEEP_REPORTS`] are kept.
+//!
+//! With `--listen`, the agent serves `GET /healthz`, `200` while it's
+//! running, and `GET /readyz`, `200` once a cycle has finished and the last
+//! one didn't fail, else `503`, both with the state as JSON (see
+//! [`serve_health`]). It also serves `GET /metrics`, the state as
+//! Prometheus metrics (see [`metrics`](crate::metrics)).
+//!
+//! A cycle that fails is recorded and the agent carries on: the next one
+//! may well succeed, and `/readyz` says it didn't meanwhile.
+//
+// Note(cc): `lusid.toml` is read once, when the agent starts; the plan is
+// read afresh by every cycle's `lusid-apply`. Pulling the project itself,
+// say with `git pull` in a timer of its own, is left to the host.
+
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use lusid_ctx::{Paths, PathsError};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::fs;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, warn};
+
+use crate::logs::{KEEP_RUNS, run_id};
+use crate::metrics::{self, StageDurations};
+use crate::report::ApplyReport;
+
+/// How many cycle reports to keep, as many as there are run logs.
+pub const KEEP_REPORTS: usize = KEEP_RUNS;
+
+#[derive(Error, Debug)]
+pub enum AgentError {
+    #[error(transparent)]
+    Paths(#[from] PathsError),
+
+    #[error("failed to listen on {addr}")]
+    Listen {
+        addr: SocketAddr,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("failed to write agent file {path}")]
+    Write {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("failed to serialize agent state")]
+    Serialize(#[source] serde_json::Error),
+
+    #[error("{pass} failed: {error}")]
+    PassFailed { pass: &'static str, error: String },
+}
+
+/// `lusid agent`'s flags.
+#[derive(Debug, Clone)]
+pub struct AgentOptions {
+    pub interval: Duration,
+    /// Apply when a check finds changes, rather than only report them.
+    pub apply: bool,
+    pub allow_destructive: bool,
+    /// Where to serve the health endpoint, if anywhere.
+    pub listen: Option<SocketAddr>,
+}
+
+/// Parse an interval like `30m`: a whole number with a unit of `s`, `m`,
+/// `h` or `d`.
+pub fn parse_interval(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("{value:?} has no unit, e.g. `30m`"))?;
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("{value:?} doesn't start with a number"))?;
+    let secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("unknown unit {unit:?}, expected s, m, h or d")),
+    };
+    if number == 0 {
+        return Err("the interval must be more than zero".to_owned());
+    }
+    Ok(Duration::from_secs(number * secs))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CycleStatus {
+    /// The check found nothing to change.
+    InSync,
+    /// The check found changes, and the agent doesn't apply them.
+    Drifted,
+    /// The check found changes, and an apply made them.
+    Applied,
+    /// The check or the apply failed; see the cycle's `error`.
+    Failed,
+}
+
+impl CycleStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CycleStatus::InSync => "in_sync",
+            CycleStatus::Drifted => "drifted",
+            CycleStatus::Applied => "applied",
+            CycleStatus::Failed => "failed",
+        }
+    }
+}
+
+/// One cycle: a check, and the apply that followed it, if any. Times are
+/// unix seconds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CycleReport {
+    pub started_at: u64,
+    pub finished_at: u64,
+    pub status: CycleStatus,
+    /// The check's run, for `lusid logs`.
+    pub check_run_id: Option<String>,
+    pub check: Option<ApplyReport>,
+    /// How long each stage of the check took.
+    #[serde(default)]
+    pub check_stages: StageDurations,
+    pub apply_run_id: Option<String>,
+    pub apply: Option<ApplyReport>,
+    #[serde(default)]
+    pub apply_stages: StageDurations,
+    /// The generation a successful apply was recorded as.
+    pub generation: Option<u32>,
+    pub error: Option<String>,
+}
+
+impl CycleReport {
+    pub fn new(started_at: u64) -> Self {
+        Self {
+            started_at,
+            finished_at: started_at,
+            status: CycleStatus::Failed,
+            check_run_id: None,
+            check: None,
+            check_stages: StageDurations::new(),
+            apply_run_id: None,
+            apply: None,
+            apply_stages: StageDurations::new(),
+            generation: None,
+            error: None,
+        }
+    }
+
+    /// Finish as failed, with `error`.
+    pub fn failed(mut self, error: String) -> Self {
+        self.status = CycleStatus::Failed;
+        self.error = Some(error);
+        self.finish()
+    }
+
+    /// Finish with `status`.
+    pub fn finished(mut self, status: CycleStatus) -> Self {
+        self.status = status;
+        self.finish()
+    }
+
+    fn finish(mut self) -> Self {
+        self.finished_at = now_secs();
+        self
+    }
+}
+
+/// What the agent has done so far, kept in `state.json`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentState {
+    pub machine: String,
+    pub cycles: u64,
+    /// Failed cycles since the last one that didn't fail.
+    pub consecutive_failures: u64,
+    /// When a cycle last found or left the machine in sync.
+    pub last_in_sync_at: Option<u64>,
+    /// When an apply last succeeded.
+    pub last_applied_at: Option<u64>,
+    /// When the last apply, successful or not, finished.
+    #[serde(default)]
+    pub last_apply_at: Option<u64>,
+    #[serde(default)]
+    pub last_apply_succeeded: Option<bool>,
+    pub last_cycle: Option<CycleReport>,
+}
+
+impl AgentState {
+    pub fn new(machine: &str) -> Self {
+        Self {
+            machine: machine.to_owned(),
+            ..Self::default()
+        }
+    }
+
+    pub fn record(&mut self, cycle: CycleReport) {
+        self.cycles += 1;
+        match cycle.status {
+            CycleStatus::Failed => self.consecutive_failures += 1,
+            CycleStatus::InSync | CycleStatus::Drifted | CycleStatus::Applied => {
+                self.consecutive_failures = 0
+            }
+        }
+        if matches!(cycle.status, CycleStatus::InSync | CycleStatus::Applied) {
+            self.last_in_sync_at = Some(cycle.finished_at);
+        }
+        if cycle.status == CycleStatus::Applied {
+            self.last_applied_at = Some(cycle.finished_at);
+        }
+        if cycle.apply_run_id.is_some() {
+            self.last_apply_at = Some(cycle.finished_at);
+            self.last_apply_succeeded = Some(cycle.status == CycleStatus::Applied);
+        }
+        self.last_cycle = Some(cycle);
+    }
+
+    /// A cycle has finished, and the last one didn't fail.
+    pub fn ready(&self) -> bool {
+        self.last_cycle
+            .as_ref()
+            .is_some_and(|cycle| cycle.status != CycleStatus::Failed)
+    }
+}
+
+/// The agent's files for one machine.
+#[derive(Debug, Clone)]
+pub struct AgentDir {
+    dir: PathBuf,
+}
+
+impl AgentDir {
+    pub fn open(machine_id: &str) -> Result<Self, AgentError> {
+        let paths = Paths::create()?;
+        Ok(Self {
+            dir: paths.data_dir().join("agent").join(machine_id),
+        })
+    }
+
+    /// The state a previous agent left, or a fresh one if there's none or
+    /// it can't be read.
+    pub async fn load_state(&self, machine_id: &str) -> AgentState {
+        let path = self.state_path();
+        let json = match fs::read_to_string(&path).await {
+            Ok(json) => json,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return AgentState::new(machine_id);
+            }
+            Err(error) => {
+                warn!(path = %path.display(), %error, "failed to read agent state, starting afresh");
+                return AgentState::new(machine_id);
+            }
+        };
+        match serde_json::from_str::<AgentState>(&json) {
+            Ok(state) if state.machine == machine_id => state,
+            Ok(_) => AgentState::new(machine_id),
+            Err(error) => {
+                warn!(path = %path.display(), %error, "failed to parse agent state, starting afresh");
+                AgentState::new(machine_id)
+            }
+        }
+    }
+
+    pub async fn save_state(&self, state: &AgentState) -> Result<(), AgentError> {
+        let json = serde_json::to_string_pretty(state).map_err(AgentError::Serialize)?;
+        write_atomic(&self.state_path(), json).await
+    }
+
+    /// Write `cycle` to `reports/<started>.json`, pruning old reports beyond
+    /// [`KEEP_REPORTS`].
+    pub async fn write_report(&self, cycle: &CycleReport) -> Result<(), AgentError> {
+        let reports_dir = self.dir.join("reports");
+        let json = serde_json::to_string_pretty(cycle).map_err(AgentError::Serialize)?;
+        let name = format!("{}.json", run_id(cycle.started_at));
+        write_atomic(&reports_dir.join(name), json).await?;
+
+        // Best-effort: a report that can't be pruned now will be next time.
+        let mut names = Vec::new();
+        if let Ok(mut entries) = fs::read_dir(&reports_dir).await {
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                names.push(entry.file_name());
+            }
+        }
+        names.sort();
+        let excess = names.len().saturating_sub(KEEP_REPORTS);
+        for name in &names[..excess] {
+            let _ = fs::remove_file(reports_dir.join(name)).await;
+        }
+        Ok(())
+    }
+
+    fn state_path(&self) -> PathBuf {
+        self.dir.join("state.json")
+    }
+}
+
+// Write through a `.tmp` sidecar, so a reader never sees half a file.
+async fn write_atomic(path: &Path, contents: String) -> Result<(), AgentError> {
+    let write_error = |source| AgentError::Write {
+        path: path.to_owned(),
+        source,
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await.map_err(write_error)?;
+    }
+    let temp = path.with_extension("json.tmp");
+    fs::write(&temp, contents).await.map_err(write_error)?;
+    fs::rename(&temp, path).await.map_err(write_error)
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Bind the health and metrics endpoints' listener, so a bad `--listen` fails the agent
+/// up front.
+pub async fn listen(addr: SocketAddr) -> Result<TcpListener, AgentError> {
+    TcpListener::bind(addr)
+        .await
+        .map_err(|source| AgentError::Listen { addr, source })
+}
+
+/// Answer `/healthz`, `/readyz` and `/metrics` on `listener`, from `state`,
+/// until the agent stops.
+pub async fn serve_health(listener: TcpListener, state: Arc<Mutex<AgentState>>) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(error) => {
+                warn!(%error, "failed to accept health check connection");
+                continue;
+            }
+        };
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(error) = answer(stream, &state).await {
+                debug!(%error, "failed to answer health check");
+            }
+        });
+    }
+}
+
+/// How long a client has to send its request, so a stalled one doesn't hold
+/// its connection, and task, open.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How much of a request is read, request line and headers together.
+const MAX_REQUEST_LEN: u64 = 8 * 1024;
+
+async fn answer(mut stream: TcpStream, state: &Mutex<AgentState>) -> io::Result<()> {
+    let request_line = tokio::time::timeout(REQUEST_TIMEOUT, read_request(&mut stream))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "timed out reading request"))??;
+
+    let state = state.lock().expect("agent state lock poisoned").clone();
+    let (status, content_type, body) = route(&request_line, &state);
+    let response = format!(
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: {content_type}\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n\
+         {body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Read a request's line, then past its headers.
+async fn read_request(stream: &mut TcpStream) -> io::Result<String> {
+    // Past the cap, reads end as if the client had stopped sending, so a
+    // long line can't grow without bound.
+    let mut stream = BufReader::new(stream.take(MAX_REQUEST_LEN));
+    let mut request_line = String::new();
+    stream.read_line(&mut request_line).await?;
+    // The headers don't matter, but a client may wait to finish sending
+    // them before it reads the response.
+    let mut header = String::new();
+    while stream.read_line(&mut header).await? > 2 {
+        header.clear();
+    }
+    Ok(request_line)
+}
+
+const JSON: &str = "application/json";
+// Prometheus' text exposition format.
+const METRICS: &str = "text/plain; version=0.0.4";
+
+// The status line, content type and body for a request line, e.g.
+// `GET /readyz HTTP/1.1`.
+fn route(request_line: &str, state: &AgentState) -> (&'static str, &'static str, String) {
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next(), parts.next());
+    let state_json = || serde_json::to_string(state).unwrap_or_else(|_| "{}".to_owned());
+    match (method, path) {
+        (Some("GET"), Some("/healthz")) => ("200 OK", JSON, state_json()),
+        (Some("GET"), Some("/readyz")) if state.ready() => ("200 OK", JSON, state_json()),
+        (Some("GET"), Some("/readyz")) => ("503 Service Unavailable", JSON, state_json()),
+        (Some("GET"), Some("/metrics")) => ("200 OK", METRICS, metrics::render(state)),
+        (Some("GET"), _) => ("404 Not Found", JSON, r#"{"error":"not found"}"#.to_owned()),
+        _ => (
+            "405 Method Not Allowed",
+            JSON,
+            r#"{"error":"method not allowed"}"#.to_owned(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_intervals() {
+        assert_eq!(parse_interval("30m"), Ok(Duration::from_secs(30 * 60)));
+        assert_eq!(parse_interval("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_interval("1d"), Ok(Duration::from_secs(24 * 60 * 60)));
+        assert!(parse_interval("30").is_err());
+        assert!(parse_interval("m").is_err());
+        assert!(parse_interval("0h").is_err());
+        assert!(parse_interval("2w").is_err());
+    }
+
+    #[test]
+    fn ready_after_a_cycle_that_didnt_fail() {
+        let mut state = AgentState::new("web-1");
+        assert_eq!(route("GET /healthz HTTP/1.1\r\n", &state).0, "200 OK");
+        assert_eq!(
+            route("GET /readyz HTTP/1.1\r\n", &state).0,
+            "503 Service Unavailable"
+        );
+
+        state.record(CycleReport::new(1).finished(CycleStatus::Applied));
+        assert_eq!(route("GET /readyz HTTP/1.1\r\n", &state).0, "200 OK");
+        assert!(state.last_applied_at.is_some());
+
+        state.record(CycleReport::new(2).failed("lusid-apply failed".into()));
+        state.record(CycleReport::new(3).failed("lusid-apply failed".into()));
+        assert_eq!(state.consecutive_failures, 2);
+        assert_eq!(
+            route("GET /readyz HTTP/1.1\r\n", &state).0,
+            "503 Service Unavailable"
+        );
+        assert_eq!(route("GET /nope HTTP/1.1\r\n", &state).0, "404 Not Found");
+    }
+
+    #[tokio::test]
+    async fn stops_reading_a_request_at_the_cap() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        // A header line that never ends, from a client that never closes.
+        let client = tokio::spawn(async move {
+            let mut client = TcpStream::connect(addr).await.unwrap();
+            let header = format!("GET /healthz HTTP/1.1\r\nX-Long: {}", "a".repeat(64 * 1024));
+            let _ = client.write_all(header.as_bytes()).await;
+            std::future::pending::<()>().await;
+        });
+
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let request_line = tokio::time::timeout(Duration::from_secs(1), read_request(&mut stream))
+            .await
+            .expect("read past the cap")
+            .unwrap();
+        assert_eq!(request_line, "GET /healthz HTTP/1.1\r\n");
+        client.abort();
+    }
+}
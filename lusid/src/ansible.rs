@@ -0,0 +1,728 @@
+//! `lusid import ansible`: an experimental converter from an Ansible playbook
+//! (or bare task list) to a lusid plan skeleton, to ease migrating the most
+//! common cases.
+//!
+//! Supported modules, with or without the `ansible.builtin.` prefix: `apt`,
+//! `file`, `copy`, `template`, `user`, `service` / `systemd`, `git`. Each task
+//! becomes one plan item. Anything that doesn't translate — other modules,
+//! task keywords like `when` or `notify`, Jinja expressions — is kept as a
+//! `TODO(import)` comment next to (or in place of) the item, so nothing is
+//! dropped silently. The output is a starting point to review, not a plan to
+//! apply as-is.
+//!
+//! Only a play's `pre_tasks`, `tasks` and `post_tasks` (and `block`s within
+//! them) are read: roles, handlers and includes aren't followed.
+
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AnsibleImportError {
+    #[error("failed to read {path}")]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("failed to parse {path} as YAML")]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: serde_yaml_ng::Error,
+    },
+
+    #[error("expected a list of plays or tasks at the top of {path}")]
+    NotAList { path: PathBuf },
+}
+
+/// Task-level keywords, as opposed to the module key. These are either
+/// dropped with a note or (for `name` and `args`) used in the conversion.
+const TASK_KEYWORDS: &[&str] = &[
+    "name",
+    "args",
+    "when",
+    "become",
+    "become_user",
+    "become_method",
+    "notify",
+    "listen",
+    "register",
+    "tags",
+    "loop",
+    "loop_control",
+    "with_items",
+    "with_dict",
+    "vars",
+    "environment",
+    "ignore_errors",
+    "changed_when",
+    "failed_when",
+    "delegate_to",
+    "run_once",
+    "no_log",
+    "check_mode",
+    "until",
+    "retries",
+    "delay",
+];
+
+/// Read and convert the playbook at `path`.
+pub async fn import_ansible(path: &Path) -> Result<String, AnsibleImportError> {
+    let source =
+        tokio::fs::read_to_string(path)
+            .await
+            .map_err(|source| AnsibleImportError::Read {
+                path: path.to_path_buf(),
+                source,
+            })?;
+    let playbook: Value =
+        serde_yaml_ng::from_str(&source).map_err(|source| AnsibleImportError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    let Value::Array(entries) = playbook else {
+        return Err(AnsibleImportError::NotAList {
+            path: path.to_path_buf(),
+        });
+    };
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "imported".into());
+    Ok(convert(&name, &path.display().to_string(), &entries))
+}
+
+/// One converted task: a plan item, or a note that it couldn't be converted.
+#[derive(Debug, Clone, PartialEq)]
+enum Converted {
+    Item {
+        comment: Option<String>,
+        todos: Vec<String>,
+        module: &'static str,
+        id: String,
+        params: Vec<(&'static str, Param)>,
+    },
+    Skipped {
+        comment: Option<String>,
+        reason: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Param {
+    String(String),
+    Number(u64),
+    Mode(u32),
+    Bool(bool),
+    List(Vec<String>),
+}
+
+fn convert(name: &str, source: &str, entries: &[Value]) -> String {
+    let mut ids = Ids::default();
+    let converted: Vec<Converted> = tasks(entries)
+        .into_iter()
+        .map(|task| convert_task(task, &mut ids))
+        .collect();
+    render(name, source, &converted)
+}
+
+/// Flatten plays (`pre_tasks`, `tasks`, `post_tasks`) and `block`s into one
+/// task list, in order. A top-level list without plays is a task list.
+fn tasks(entries: &[Value]) -> Vec<&Map<String, Value>> {
+    let mut out = Vec::new();
+    for entry in entries {
+        let Value::Object(entry) = entry else {
+            continue;
+        };
+        let is_play = ["hosts", "tasks", "pre_tasks", "post_tasks", "roles"]
+            .iter()
+            .any(|key| entry.contains_key(*key));
+        if !is_play {
+            flatten_task(entry, &mut out);
+            continue;
+        }
+        for key in ["pre_tasks", "tasks", "post_tasks"] {
+            if let Some(Value::Array(tasks)) = entry.get(key) {
+                for task in tasks {
+                    if let Value::Object(task) = task {
+                        flatten_task(task, &mut out);
+                    }
+                }
+            }
+        }
+    }
+    out
+}
+
+fn flatten_task<'a>(task: &'a Map<String, Value>, out: &mut Vec<&'a Map<String, Value>>) {
+    match task.get("block") {
+        Some(Value::Array(block)) => {
+            for task in block {
+                if let Value::Object(task) = task {
+                    flatten_task(task, out);
+                }
+            }
+        }
+        _ => out.push(task),
+    }
+}
+
+fn convert_task(task: &Map<String, Value>, ids: &mut Ids) -> Converted {
+    let comment = task.get("name").and_then(Value::as_str).map(str::to_string);
+    let Some((module_key, module_args)) = task
+        .iter()
+        .find(|(key, _)| !TASK_KEYWORDS.contains(&key.as_str()))
+    else {
+        return Converted::Skipped {
+            comment,
+            reason: "no module found in task".into(),
+        };
+    };
+    let module_name = module_key
+        .strip_prefix("ansible.builtin.")
+        .or_else(|| module_key.strip_prefix("ansible.legacy."))
+        .unwrap_or(module_key);
+
+    let mut args = Args::new(module_args, task.get("args"));
+    let mut todos = Vec::new();
+    let converted = match module_name {
+        "apt" => convert_apt(&mut args, &mut todos),
+        "file" => convert_file(&mut args, &mut todos),
+        "copy" => convert_copy(&mut args, &mut todos),
+        "template" => convert_template(&mut args, &mut todos),
+        "user" => convert_user(&mut args, &mut todos),
+        "service" | "systemd" | "systemd_service" => convert_service(&mut args, &mut todos),
+        "git" => convert_git(&mut args, &mut todos),
+        _ => Err(format!("`{module_key}` has no lusid equivalent")),
+    };
+    let (module, params) = match converted {
+        Ok(converted) => converted,
+        Err(reason) => return Converted::Skipped { comment, reason },
+    };
+
+    for key in args.rest() {
+        todos.push(format!("`{module_name}` argument `{key}` isn't converted"));
+    }
+    for (key, param) in &params {
+        let templated = match param {
+            Param::String(value) => value.contains("{{"),
+            Param::List(values) => values.iter().any(|value| value.contains("{{")),
+            _ => false,
+        };
+        if templated {
+            todos.push(format!("`{key}` is a Jinja expression; rewrite it in Rimu"));
+        }
+    }
+    for key in task.keys() {
+        match key.as_str() {
+            "name" | "args" => {}
+            "notify" => todos.push(
+                "`notify` isn't converted; a file's `restarts` restarts a unit when it changes"
+                    .into(),
+            ),
+            key if TASK_KEYWORDS.contains(&key) => {
+                todos.push(format!("task keyword `{key}` isn't converted"))
+            }
+            _ => {}
+        }
+    }
+
+    let id = ids.next(comment.as_deref().unwrap_or(module_name));
+    Converted::Item {
+        comment,
+        todos,
+        module,
+        id,
+        params,
+    }
+}
+
+type ModuleParams = (&'static str, Vec<(&'static str, Param)>);
+
+fn convert_apt(args: &mut Args, todos: &mut Vec<String>) -> Result<ModuleParams, String> {
+    let state = args.string("state").unwrap_or_else(|| "present".into());
+    match state.as_str() {
+        "present" | "installed" => {}
+        "latest" => {
+            todos.push("`state: latest` is converted as present; lusid doesn't upgrade".into())
+        }
+        other => return Err(format!("apt `state: {other}` isn't supported by @core/apt")),
+    }
+    let Some(mut packages) = args.list(&["name", "pkg", "package"]) else {
+        return Err("apt task without packages".into());
+    };
+    let param = if packages.len() == 1 {
+        ("package", Param::String(packages.remove(0)))
+    } else {
+        ("packages", Param::List(packages))
+    };
+    Ok(("@core/apt", vec![param]))
+}
+
+fn convert_file(args: &mut Args, todos: &mut Vec<String>) -> Result<ModuleParams, String> {
+    let path = args
+        .string_of(&["path", "dest", "name"])
+        .ok_or("file task without `path`")?;
+    let state = args.string("state").unwrap_or_else(|| "file".into());
+    let (module, state) = match state.as_str() {
+        "directory" => ("@core/directory", "present"),
+        "file" | "touch" => ("@core/file", "present"),
+        "absent" => {
+            todos.push("if `path` is a directory, use @core/directory instead".into());
+            ("@core/file", "absent")
+        }
+        "link" => {
+            let source = args.string("src").ok_or("file link without `src`")?;
+            todos.push(
+                "`source` is read on the machine running lusid, Ansible's `src` on the target"
+                    .into(),
+            );
+            return Ok((
+                "@core/file",
+                vec![
+                    ("state", Param::String("linked".into())),
+                    ("source", Param::String(source)),
+                    ("path", Param::String(path)),
+                ],
+            ));
+        }
+        other => return Err(format!("file `state: {other}` has no lusid equivalent")),
+    };
+    let mut params = vec![
+        ("state", Param::String(state.into())),
+        ("path", Param::String(path)),
+    ];
+    if state == "present" {
+        params.extend(args.ownership(todos));
+    }
+    Ok((module, params))
+}
+
+fn convert_copy(args: &mut Args, todos: &mut Vec<String>) -> Result<ModuleParams, String> {
+    let path = args.string("dest").ok_or("copy task without `dest`")?;
+    let mut params = if let Some(contents) = args.string("content") {
+        vec![
+            ("state", Param::String("contents".into())),
+            ("contents", Param::String(contents)),
+            ("path", Param::String(path)),
+        ]
+    } else {
+        let source = args
+            .string("src")
+            .ok_or("copy task without `src` or `content`")?;
+        todos.push("`source` is relative to this plan, not to a role's `files/`".into());
+        let module = if source.ends_with('/') {
+            "@core/directory"
+        } else {
+            "@core/file"
+        };
+        let mut params = vec![
+            ("state", Param::String("sourced".into())),
+            ("source", Param::String(source)),
+            ("path", Param::String(path)),
+        ];
+        params.extend(args.ownership(todos));
+        return Ok((module, params));
+    };
+    params.extend(args.ownership(todos));
+    Ok(("@core/file", params))
+}
+
+fn convert_template(args: &mut Args, todos: &mut Vec<String>) -> Result<ModuleParams, String> {
+    let source = args.string("src").ok_or("template task without `src`")?;
+    let path = args.string("dest").ok_or("template task without `dest`")?;
+    todos.push(
+        "the template is copied as-is; render it in Rimu and use `state: \"contents\"`".into(),
+    );
+    let mut params = vec![
+        ("state", Param::String("sourced".into())),
+        ("source", Param::String(source)),
+        ("path", Param::String(path)),
+    ];
+    params.extend(args.ownership(todos));
+    Ok(("@core/file", params))
+}
+
+fn convert_user(args: &mut Args, todos: &mut Vec<String>) -> Result<ModuleParams, String> {
+    let name = args.string("name").ok_or("user task without `name`")?;
+    let state = args.string("state").unwrap_or_else(|| "present".into());
+    let mut params = vec![
+        ("state", Param::String(state.clone())),
+        ("name", Param::String(name)),
+    ];
+    match state.as_str() {
+        "present" => {}
+        "absent" => {
+            if let Some(remove) = args.bool("remove") {
+                params.push(("remove_home", Param::Bool(remove)));
+            }
+            return Ok(("@core/user", params));
+        }
+        other => return Err(format!("user `state: {other}` has no lusid equivalent")),
+    }
+    if let Some(uid) = args.number("uid") {
+        params.push(("uid", Param::Number(uid)));
+    }
+    if let Some(group) = args.string("group") {
+        params.push(("group", Param::String(group)));
+    }
+    if let Some(groups) = args.list(&["groups"]) {
+        if args.bool("append") != Some(true) {
+            todos.push("lusid only adds `append_groups`; it never removes other groups".into());
+        }
+        params.push(("append_groups", Param::List(groups)));
+    }
+    for key in ["comment", "home", "shell"] {
+        if let Some(value) = args.string(key) {
+            params.push((key, Param::String(value)));
+        }
+    }
+    for key in ["system", "create_home"] {
+        if let Some(value) = args.bool(key) {
+            params.push((key, Param::Bool(value)));
+        }
+    }
+    Ok(("@core/user", params))
+}
+
+fn convert_service(args: &mut Args, todos: &mut Vec<String>) -> Result<ModuleParams, String> {
+    let name = args.string("name").ok_or("service task without `name`")?;
+    let mut params = vec![("name", Param::String(name))];
+    if let Some(enabled) = args.bool("enabled") {
+        params.push(("enabled", Param::Bool(enabled)));
+    }
+    if let Some(state) = args.string("state") {
+        let active = match state.as_str() {
+            "started" => true,
+            "stopped" => false,
+            "restarted" | "reloaded" => {
+                todos.push(format!(
+                    "`state: {state}` is converted as started; use `restarts` on the files that change"
+                ));
+                true
+            }
+            other => return Err(format!("service `state: {other}` has no lusid equivalent")),
+        };
+        params.push(("active", Param::Bool(active)));
+    }
+    Ok(("@core/systemd", params))
+}
+
+fn convert_git(args: &mut Args, _todos: &mut Vec<String>) -> Result<ModuleParams, String> {
+    let repo = args.string("repo").ok_or("git task without `repo`")?;
+    let path = args.string("dest").ok_or("git task without `dest`")?;
+    let mut params = vec![("repo", Param::String(repo)), ("path", Param::String(path))];
+    if let Some(version) = args.string("version") {
+        params.push(("version", Param::String(version)));
+    }
+    for key in ["update", "force"] {
+        if let Some(value) = args.bool(key) {
+            params.push((key, Param::Bool(value)));
+        }
+    }
+    if let Some(depth) = args.number("depth") {
+        params.push(("depth", Param::Number(depth)));
+    }
+    Ok(("@core/git", params))
+}
+
+/// A module's arguments, from its value (a mapping or free-form `k=v` string)
+/// merged with the task's `args`. Converters take what they use; whatever is
+/// left is reported as not converted.
+struct Args {
+    map: Map<String, Value>,
+}
+
+impl Args {
+    fn new(module_args: &Value, task_args: Option<&Value>) -> Self {
+        let mut map = match module_args {
+            Value::Object(map) => map.clone(),
+            // Note(cc): free-form args are split on whitespace, so quoted
+            // values with spaces come through mangled.
+            Value::String(free_form) => free_form
+                .split_whitespace()
+                .filter_map(|word| word.split_once('='))
+                .map(|(key, value)| (key.to_string(), Value::String(value.to_string())))
+                .collect(),
+            _ => Map::new(),
+        };
+        if let Some(Value::Object(task_args)) = task_args {
+            map.extend(task_args.clone());
+        }
+        Self { map }
+    }
+
+    fn take(&mut self, key: &str) -> Option<Value> {
+        self.map.remove(key)
+    }
+
+    fn string(&mut self, key: &str) -> Option<String> {
+        match self.take(key)? {
+            Value::String(value) => Some(value),
+            Value::Number(value) => Some(value.to_string()),
+            Value::Bool(value) => Some(value.to_string()),
+            _ => None,
+        }
+    }
+
+    /// The first of `keys` that's set (Ansible's argument aliases).
+    fn string_of(&mut self, keys: &[&str]) -> Option<String> {
+        keys.iter().find_map(|key| self.string(key))
+    }
+
+    fn bool(&mut self, key: &str) -> Option<bool> {
+        match self.take(key)? {
+            Value::Bool(value) => Some(value),
+            Value::String(value) => match value.to_lowercase().as_str() {
+                "yes" | "true" | "on" | "1" => Some(true),
+                "no" | "false" | "off" | "0" => Some(false),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn number(&mut self, key: &str) -> Option<u64> {
+        match self.take(key)? {
+            Value::Number(value) => value.as_u64(),
+            Value::String(value) => value.parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// A list, or a comma-separated string, from the first of `keys` set.
+    fn list(&mut self, keys: &[&str]) -> Option<Vec<String>> {
+        keys.iter().find_map(|key| match self.take(key)? {
+            Value::Array(values) => Some(
+                values
+                    .iter()
+                    .filter_map(|value| value.as_str().map(str::to_string))
+                    .collect(),
+            ),
+            Value::String(value) => Some(
+                value
+                    .split(',')
+                    .map(|value| value.trim().to_string())
+                    .filter(|value| !value.is_empty())
+                    .collect(),
+            ),
+            _ => None,
+        })
+    }
+
+    /// `mode`, `owner` and `group`, as lusid's `mode`, `user` and `group`.
+    fn ownership(&mut self, todos: &mut Vec<String>) -> Vec<(&'static str, Param)> {
+        let mut params = Vec::new();
+        if let Some(mode) = self.take("mode") {
+            match parse_mode(&mode) {
+                Some(mode) => params.push(("mode", Param::Mode(mode))),
+                None => todos.push(format!("`mode: {mode}` isn't an octal mode")),
+            }
+        }
+        if let Some(owner) = self.string("owner") {
+            params.push(("user", Param::String(owner)));
+        }
+        if let Some(group) = self.string("group") {
+            params.push(("group", Param::String(group)));
+        }
+        params
+    }
+
+    fn rest(self) -> impl Iterator<Item = String> {
+        self.map.into_iter().map(|(key, _)| key)
+    }
+}
+
+/// An octal mode, quoted (`"0644"`) or not (`0644`, which YAML may hand us as
+/// the integer 644). Symbolic modes (`u+rwx`) aren't converted.
+fn parse_mode(mode: &Value) -> Option<u32> {
+    let digits = match mode {
+        Value::String(mode) => mode.strip_prefix("0o").unwrap_or(mode).to_string(),
+        Value::Number(mode) => mode.as_u64()?.to_string(),
+        _ => return None,
+    };
+    u32::from_str_radix(&digits, 8).ok()
+}
+
+/// Unique, slug-shaped item ids derived from task names, or from what
+/// `lusid scan` captured.
+#[derive(Default)]
+pub(crate) struct Ids {
+    used: Vec<String>,
+}
+
+impl Ids {
+    pub(crate) fn next(&mut self, name: &str) -> String {
+        let mut slug = String::new();
+        for c in name.chars() {
+            if c.is_ascii_alphanumeric() {
+                slug.push(c.to_ascii_lowercase());
+            } else if !slug.is_empty() && !slug.ends_with('-') {
+                slug.push('-');
+            }
+        }
+        let slug = slug.trim_end_matches('-').to_string();
+        let slug = if slug.is_empty() { "task".into() } else { slug };
+        let mut id = slug.clone();
+        let mut n = 2;
+        while self.used.contains(&id) {
+            id = format!("{slug}-{n}");
+            n += 1;
+        }
+        self.used.push(id.clone());
+        id
+    }
+}
+
+fn render(name: &str, source: &str, converted: &[Converted]) -> String {
+    let mut out = String::new();
+    out.push_str(&format!("name: {}\n", quote(name)));
+    out.push_str("version: \"0.1.0\"\n\n");
+    out.push_str(&format!(
+        "# Imported from {source} by `lusid import ansible` (experimental).\n"
+    ));
+    out.push_str("# Review every item, and each TODO(import), before applying.\n\n");
+    out.push_str("params: {}\n\n");
+
+    let has_items = converted
+        .iter()
+        .any(|converted| matches!(converted, Converted::Item { .. }));
+    if !has_items {
+        for converted in converted {
+            render_comments(&mut out, "", converted);
+        }
+        out.push_str("setup: (params, system) => []\n");
+        return out;
+    }
+
+    out.push_str("setup: (params, system) =>\n");
+    for (index, converted) in converted.iter().enumerate() {
+        if index > 0 {
+            out.push('\n');
+        }
+        render_comments(&mut out, "  ", converted);
+        let Converted::Item {
+            module, id, params, ..
+        } = converted
+        else {
+            continue;
+        };
+        out.push_str(&format!("  - module: {}\n", quote(module)));
+        out.push_str(&format!("    id: {}\n", quote(id)));
+        out.push_str("    params:\n");
+        for (key, param) in params {
+            out.push_str(&format!("      {key}: {}\n", render_param(param)));
+        }
+    }
+    out
+}
+
+fn render_comments(out: &mut String, indent: &str, converted: &Converted) {
+    let (comment, todos) = match converted {
+        Converted::Item { comment, todos, .. } => (comment, todos.clone()),
+        Converted::Skipped { comment, reason } => (comment, vec![format!("skipped: {reason}")]),
+    };
+    if let Some(comment) = comment {
+        out.push_str(&format!("{indent}# {comment}\n"));
+    }
+    for todo in todos {
+        out.push_str(&format!("{indent}# TODO(import): {todo}\n"));
+    }
+}
+
+pub(crate) fn render_param(param: &Param) -> String {
+    match param {
+        Param::String(value) => quote(value),
+        Param::Number(value) => value.to_string(),
+        Param::Mode(mode) => format!("{mode} # 0o{mode:o}"),
+        Param::Bool(value) => value.to_string(),
+        Param::List(values) => format!(
+            "[{}]",
+            values
+                .iter()
+                .map(|value| quote(value))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+pub(crate) fn quote(value: &str) -> String {
+    Value::String(value.to_string()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn import(yaml: &str) -> String {
+        let entries: Vec<Value> = serde_yaml_ng::from_str(yaml).unwrap();
+        convert("site", "site.yml", &entries)
+    }
+
+    #[test]
+    fn converts_common_modules() {
+        let plan = import(
+            r#"
+- hosts: web
+  become: true
+  tasks:
+    - name: Install nginx
+      ansible.builtin.apt:
+        name: [nginx, curl]
+        state: present
+    - name: Configure nginx
+      template:
+        src: nginx.conf.j2
+        dest: /etc/nginx/nginx.conf
+        mode: "0644"
+      notify: restart nginx
+    - name: Start nginx
+      service: name=nginx state=started enabled=yes
+"#,
+        );
+        assert!(plan.contains("  - module: \"@core/apt\"\n    id: \"install-nginx\"\n"));
+        assert!(plan.contains("      packages: [\"nginx\", \"curl\"]\n"));
+        assert!(plan.contains("      mode: 420 # 0o644\n"));
+        assert!(plan.contains("# TODO(import): `notify` isn't converted"));
+        assert!(plan.contains(
+            "  - module: \"@core/systemd\"\n    id: \"start-nginx\"\n    params:\n      name: \"nginx\"\n      enabled: true\n      active: true\n"
+        ));
+    }
+
+    #[test]
+    fn keeps_unsupported_tasks_as_todos() {
+        let plan = import(
+            r#"
+- name: Run migrations
+  shell: ./manage.py migrate
+  when: migrate
+- name: Clone app
+  git:
+    repo: https://example.com/app.git
+    dest: "{{ app_dir }}"
+    depth: 1
+    accept_hostkey: true
+"#,
+        );
+        assert!(plan.contains(
+            "  # Run migrations\n  # TODO(import): skipped: `shell` has no lusid equivalent\n"
+        ));
+        assert!(plan.contains("      depth: 1\n"));
+        assert!(plan.contains("# TODO(import): `git` argument `accept_hostkey` isn't converted"));
+        assert!(plan.contains("# TODO(import): `path` is a Jinja expression"));
+    }
+
+    #[test]
+    fn dedupes_ids_and_parses_modes() {
+        let mut ids = Ids::default();
+        assert_eq!(ids.next("Create dir"), "create-dir");
+        assert_eq!(ids.next("Create dir!"), "create-dir-2");
+        assert_eq!(parse_mode(&Value::from("0755")), Some(0o755));
+        assert_eq!(parse_mode(&Value::from(644)), Some(0o644));
+        assert_eq!(parse_mode(&Value::from("u+rwx")), None);
+    }
+}
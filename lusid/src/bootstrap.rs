@@ -0,0 +1,65 @@
+//! `lusid remote bootstrap`: bring a bare host, like a fresh cloud VM, under
+//! management in one command.
+//!
+//! lusid asks little of a target: `lusid-apply` is one static binary, so
+//! there's no Python or agent to install first. What's left is access:
+//!
+//! 1. Log in as the host's first user (`--login-user`, e.g. `root`, or a
+//!    cloud image's `ubuntu`) with the machine's configured key.
+//! 2. If that isn't the configured SSH user, create the configured user,
+//!    authorize the key for it and give it passwordless sudo, with
+//!    [`setup_user_script`].
+//! 3. Log in again as the configured user, create the
+//!    [staging directory](crate::staging) and upload the `lusid-apply` for
+//!    the machine's arch.
+//! 4. Run the `doctor` checks, so anything else missing shows up now.
+//!
+//! Every step is safe to repeat, so a bootstrap that failed halfway can be
+//! run again.
+
+/// The shell script that sets up `user` on a host: create it if it doesn't
+/// exist, authorize `public_key` for it, and give it passwordless sudo. Run
+/// as root, or as a user with passwordless sudo.
+// Note(cc): sudo is granted in `/etc/sudoers.d/lusid`, which a host's
+// `/etc/sudoers` has to include, as Debian, Ubuntu and Fedora's do. Hosts
+// without sudo at all, like a minimal Alpine, fail the `sudo` doctor check
+// after.
+pub fn setup_user_script(user: &str, public_key: &str) -> String {
+    format!(
+        "set -eu\n\
+         if [ \"$(id -u)\" -eq 0 ]; then root=; else root='sudo -n'; fi\n\
+         user={user}\n\
+         key={key}\n\
+         if ! id -u \"$user\" >/dev/null 2>&1; then\n\
+         if command -v useradd >/dev/null 2>&1; then\n\
+         $root useradd --create-home --shell /bin/sh \"$user\"\n\
+         else\n\
+         $root adduser -D -s /bin/sh \"$user\"\n\
+         fi\n\
+         fi\n\
+         home=$(getent passwd \"$user\" | cut -d: -f6)\n\
+         $root mkdir -p \"$home/.ssh\"\n\
+         $root touch \"$home/.ssh/authorized_keys\"\n\
+         $root grep -qxF \"$key\" \"$home/.ssh/authorized_keys\" || \
+         printf '%s\\n' \"$key\" | $root tee -a \"$home/.ssh/authorized_keys\" >/dev/null\n\
+         $root chown -R \"$user:$(id -gn \"$user\")\" \"$home/.ssh\"\n\
+         $root chmod 700 \"$home/.ssh\"\n\
+         $root chmod 600 \"$home/.ssh/authorized_keys\"\n\
+         printf '%s ALL=(ALL) NOPASSWD:ALL\\n' \"$user\" | $root tee /etc/sudoers.d/lusid >/dev/null\n\
+         $root chmod 440 /etc/sudoers.d/lusid\n",
+        user = shell_words::quote(user),
+        key = shell_words::quote(public_key.trim()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_user_and_key() {
+        let script = setup_user_script("deploy", "ssh-ed25519 AAAA it's me\n");
+        assert!(script.contains("\nuser=deploy\n"));
+        assert!(script.contains("\nkey='ssh-ed25519 AAAA it'\\''s me'\n"));
+    }
+}
@@ -1,12 +1,15 @@
 //! `lusid.toml` deserialization. Splits into an on-disk `ConfigToml`
-//! (deserialized straight from TOML) and an in-memory [`Config`] where plan
-//! paths have been resolved to absolute, CLI/env overrides have been
-//! applied, and defaults filled in.
+//! (deserialized straight from TOML, once it's passed the schema in
+//! [`config_schema`](crate::config_schema)) and an in-memory [`Config`] where
+//! plan and VM disk paths have been resolved to absolute, CLI/env overrides
+//! have been applied, and defaults filled in.
 
 use comfy_table::Table;
+use lusid_ctx::DownloadLimits;
 use lusid_machine::Machine;
-use lusid_system::Hostname;
+use lusid_system::{Arch, Hostname};
 use serde::Deserialize;
+use serde_json::json;
 use std::collections::BTreeMap;
 use std::io;
 use std::path::{Path, PathBuf};
@@ -15,6 +18,13 @@ use tokio::fs::read_to_string;
 use toml::Value;
 
 use crate::Cli;
+use crate::config_schema::{ConfigDiagnostic, check_config};
+use crate::hooks::{Hook, HookAction, HookEvent};
+use crate::keys::{Action, KeyBindings, KeyMap, KeyMapError};
+use crate::notify::Notifier;
+use crate::staging::{DEFAULT_STAGING_DIR, validate_staging_dir};
+use crate::stderr_log::LogLevel;
+use crate::tui::TuiOptions;
 
 #[derive(Error, Debug)]
 pub enum ConfigError {
@@ -44,11 +54,33 @@ pub enum ConfigError {
         source: toml::de::Error,
     },
 
+    #[error("invalid config {path}:{}", list_diagnostics(.diagnostics))]
+    Invalid {
+        path: PathBuf,
+        diagnostics: Vec<ConfigDiagnostic>,
+    },
+
     #[error("failed to resolve plan path: {base_path} + {plan_path}")]
     ResolvingPlanPath {
         base_path: PathBuf,
         plan_path: PathBuf,
     },
+
+    #[error("invalid staging_dir {staging_dir:?} for machine {machine_id}: {reason}")]
+    InvalidStagingDir {
+        machine_id: String,
+        staging_dir: String,
+        reason: &'static str,
+    },
+
+    #[error("invalid protected path {path:?}: must be absolute")]
+    InvalidProtectedPath { path: String },
+
+    #[error("invalid hook #{number}: {reason}")]
+    InvalidHook { number: usize, reason: &'static str },
+
+    #[error(transparent)]
+    Keys(#[from] KeyMapError),
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -58,6 +90,54 @@ struct ConfigToml {
     pub log: Option<String>,
     pub lusid_apply_linux_x86_64_path: Option<String>,
     pub lusid_apply_linux_aarch64_path: Option<String>,
+    pub staging_dir: Option<String>,
+    #[serde(default)]
+    pub keys: BTreeMap<Action, KeyBindings>,
+    pub notify: Option<Vec<Notifier>>,
+    #[serde(default)]
+    pub downloads: DownloadsToml,
+    #[serde(default)]
+    pub protect: ProtectToml,
+    pub wait_for_locks: Option<u64>,
+    #[serde(default)]
+    pub hooks: Vec<HookToml>,
+}
+
+/// `[downloads]`: limits for every download an apply makes, see
+/// [`DownloadLimits`].
+#[derive(Debug, Clone, Default, Deserialize)]
+struct DownloadsToml {
+    pub max_parallel: Option<usize>,
+    pub max_kib_per_sec: Option<u64>,
+}
+
+/// `[protect]`: what `lusid-apply` refuses to remove or overwrite, on top of
+/// its built-in denylist unless `defaults = false`, see
+/// [`Protections`](lusid_resource::protect::Protections).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct ProtectToml {
+    pub defaults: Option<bool>,
+    pub paths: Vec<String>,
+    pub packages: Vec<String>,
+    pub users: Vec<String>,
+}
+
+/// `[[hooks]]`: one [`Hook`], with exactly one of `webhook` or `command`.
+#[derive(Debug, Clone, Deserialize)]
+struct HookToml {
+    pub events: Option<Vec<HookEvent>>,
+    pub webhook: Option<String>,
+    pub command: Option<Vec<String>>,
+}
+
+/// Resolved `[protect]`, handed to `lusid-apply` as `--protect-*` flags.
+#[derive(Debug, Clone)]
+pub struct ProtectConfig {
+    pub defaults: bool,
+    pub paths: Vec<String>,
+    pub packages: Vec<String>,
+    pub users: Vec<String>,
 }
 
 /// Resolved configuration. `path` is the original config file location
@@ -70,6 +150,14 @@ pub struct Config {
     pub log: String,
     pub lusid_apply_linux_x86_64_path: String,
     pub lusid_apply_linux_aarch64_path: String,
+    pub keys: KeyMap,
+    pub notify: Vec<Notifier>,
+    pub downloads: DownloadLimits,
+    pub protect: ProtectConfig,
+    /// Seconds `lusid-apply` waits for other writers to the machine, like apt
+    /// holding the dpkg lock, before failing. `None` leaves its default.
+    pub wait_for_locks: Option<u64>,
+    pub hooks: Vec<Hook>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -78,16 +166,105 @@ struct MachineConfigToml {
     pub machine: Machine,
     pub plan: PathBuf,
     pub params: Option<Value>,
+    #[serde(default)]
+    pub groups: Vec<String>,
+    pub vars: Option<Value>,
+    pub staging_dir: Option<String>,
+    pub lusid_apply_path: Option<String>,
+    pub log: Option<String>,
+    #[serde(default)]
+    pub check: bool,
+    pub jobs: Option<usize>,
+    pub ssh: Option<RemoteToml>,
+}
+
+/// `[machines.<id>.ssh]`: how to reach a remote machine.
+#[derive(Debug, Clone, Deserialize)]
+struct RemoteToml {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub user: String,
+    pub key: PathBuf,
+    pub host_key: String,
 }
 
 /// Per-machine entry. `plan` is already resolved to an absolute path (see
 /// [`Config::resolve_plan_path`]); `params` is a raw TOML value that will
-/// be converted to JSON and handed to `lusid-apply --params`.
+/// be converted to JSON and handed to `lusid-apply --params`. `groups` and
+/// `vars` are free-form, for the plan's param defaults to read (see
+/// [`MachineConfig::facts`]).
+/// `staging_dir` is the machine's own, else the top-level one, else
+/// [`DEFAULT_STAGING_DIR`]; validated, but not yet resolved against the
+/// target (see [`StagingDir`](crate::staging::StagingDir)).
+/// `apply` is how `lusid-apply` runs on it.
+/// `remote` is how to reach it over SSH, if it's a remote machine.
 #[derive(Debug, Clone)]
 pub struct MachineConfig {
     pub machine: Machine,
     pub plan: PathBuf,
     pub params: Option<Value>,
+    pub groups: Vec<String>,
+    pub vars: Option<Value>,
+    pub staging_dir: String,
+    pub apply: ApplyConfig,
+    pub remote: Option<RemoteConfig>,
+}
+
+/// Resolved `lusid-apply` settings for one machine, each the machine's own
+/// if it sets one, else the top-level one.
+///
+/// `bin` is the prebuilt binary to run on it: its `lusid_apply_path`, else
+/// the one for its arch. `log` is the log filter, though `--log` on the
+/// command line wins over both. `check` makes every apply of it only a
+/// check: changes are planned and shown, never applied, except by `dev
+/// apply --ci` and `dev verify`, which apply to test the plan. `downloads`
+/// has its `jobs` as `max_parallel`, as downloads are all `lusid-apply`
+/// runs in parallel.
+#[derive(Debug, Clone)]
+pub struct ApplyConfig {
+    pub bin: String,
+    pub log: String,
+    pub check: bool,
+    pub downloads: DownloadLimits,
+}
+
+/// The top-level settings a machine's own [`ApplyConfig`] falls back to.
+struct ApplyDefaults<'a> {
+    cli_log: Option<&'a str>,
+    log: &'a str,
+    lusid_apply_linux_x86_64_path: &'a str,
+    lusid_apply_linux_aarch64_path: &'a str,
+    downloads: DownloadLimits,
+}
+
+/// Resolved `[machines.<id>.ssh]`. `host` defaults to the machine's
+/// hostname, `port` to 22. `key` is the private key to log in with, with a
+/// leading `~/` expanded and a relative path resolved against the config's
+/// directory; `host_key` is the server's public host key, which the
+/// connection is pinned to.
+#[derive(Debug, Clone)]
+pub struct RemoteConfig {
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    pub key: PathBuf,
+    pub host_key: String,
+}
+
+impl MachineConfig {
+    /// What the plan's param defaults see as `machine`, handed to
+    /// `lusid-apply --machine`: the machine's id, hostname, arch and os, plus
+    /// its `groups` and `vars`.
+    pub fn facts(&self, machine_id: &str) -> serde_json::Value {
+        json!({
+            "id": machine_id,
+            "hostname": self.machine.hostname,
+            "arch": self.machine.arch,
+            "os": self.machine.os,
+            "groups": self.groups,
+            "vars": self.vars,
+        })
+    }
 }
 
 impl Config {
@@ -98,12 +275,23 @@ impl Config {
             log,
             lusid_apply_linux_x86_64_path,
             lusid_apply_linux_aarch64_path,
+            staging_dir,
+            keys,
+            notify,
+            downloads,
+            protect,
+            wait_for_locks,
+            hooks,
         } = config;
 
-        let machines = Self::resolve_machines(machines, path)?;
-
         let log = cli.log.clone().or(log).unwrap_or("error".into());
 
+        if let Some(path) = protect.paths.iter().find(|path| !path.starts_with('/')) {
+            return Err(ConfigError::InvalidProtectedPath { path: path.clone() });
+        }
+
+        let hooks = Self::resolve_hooks(hooks)?;
+
         let lusid_apply_linux_x86_64_path = cli
             .lusid_apply_linux_x86_64_path
             .clone()
@@ -114,6 +302,23 @@ impl Config {
             .clone()
             .or(lusid_apply_linux_aarch64_path.clone())
             .unwrap_or("lusid-apply-linux-aarch64".into());
+        let downloads = DownloadLimits {
+            max_parallel: downloads.max_parallel,
+            max_kib_per_sec: downloads.max_kib_per_sec,
+        };
+
+        let machines = Self::resolve_machines(
+            machines,
+            path,
+            staging_dir.as_deref(),
+            &ApplyDefaults {
+                cli_log: cli.log.as_deref(),
+                log: &log,
+                lusid_apply_linux_x86_64_path: &lusid_apply_linux_x86_64_path,
+                lusid_apply_linux_aarch64_path: &lusid_apply_linux_aarch64_path,
+                downloads,
+            },
+        )?;
 
         Ok(Config {
             path: path.to_owned(),
@@ -121,9 +326,28 @@ impl Config {
             log,
             lusid_apply_linux_x86_64_path,
             lusid_apply_linux_aarch64_path,
+            keys: KeyMap::new(&keys)?,
+            notify: notify.unwrap_or_else(Notifier::defaults),
+            downloads,
+            protect: ProtectConfig {
+                defaults: protect.defaults.unwrap_or(true),
+                paths: protect.paths,
+                packages: protect.packages,
+                users: protect.users,
+            },
+            wait_for_locks,
+            hooks,
         })
     }
 
+    pub fn tui_options(&self) -> TuiOptions {
+        TuiOptions {
+            keys: self.keys.clone(),
+            notify: self.notify.clone(),
+            log_level: LogLevel::from_filter(&self.log),
+        }
+    }
+
     pub fn get_machine(&self, machine_id: &str) -> Result<MachineConfig, ConfigError> {
         self.machines
             .get(machine_id)
@@ -135,14 +359,15 @@ impl Config {
 
     /// Look up the machine whose hostname matches the host we're running on.
     /// Used by `local apply` — the user doesn't specify which machine to
-    /// apply, we infer it. Errors if no configured machine matches.
-    pub fn local_machine(&self) -> Result<MachineConfig, ConfigError> {
+    /// apply, we infer it. Errors if no configured machine matches. Returns
+    /// the machine's id alongside its config.
+    pub fn local_machine(&self) -> Result<(String, MachineConfig), ConfigError> {
         let hostname = Hostname::get().map_err(ConfigError::GetHostname)?;
         self.machines
-            .values()
-            .find(|cfg| cfg.machine.hostname == hostname)
+            .iter()
+            .find(|(_, cfg)| cfg.machine.hostname == hostname)
+            .map(|(id, cfg)| (id.clone(), cfg.clone()))
             .ok_or(ConfigError::LocalMachineNotFound { hostname })
-            .cloned()
     }
 
     pub fn print_machines(&self) {
@@ -158,6 +383,11 @@ impl Config {
                 machine,
                 plan,
                 params: _,
+                groups: _,
+                vars: _,
+                staging_dir: _,
+                apply: _,
+                remote: _,
             } = config;
             let Machine {
                 hostname,
@@ -177,6 +407,17 @@ impl Config {
         println!("{table}")
     }
 
+    /// The settings to run `lusid-apply` with on this host to plan for
+    /// `machine`, which may be another host: its own, but with the binary
+    /// for this host.
+    // Note(cc): this host is taken to be x86_64, as it always has been here.
+    pub fn planning_apply(&self, machine: &MachineConfig) -> ApplyConfig {
+        ApplyConfig {
+            bin: self.lusid_apply_linux_x86_64_path.clone(),
+            ..machine.apply.clone()
+        }
+    }
+
     pub fn root(&self) -> &Path {
         self.path.parent().unwrap()
     }
@@ -193,37 +434,146 @@ impl Config {
                 path: path.to_owned(),
                 source,
             })?;
-        let config = toml::from_str(&string).map_err(|source| ConfigError::Parse {
+        let table: toml::Table = toml::from_str(&string).map_err(|source| ConfigError::Parse {
             path: path.to_owned(),
             source,
         })?;
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        let diagnostics = check_config(&table, base_dir);
+        if !diagnostics.is_empty() {
+            return Err(ConfigError::Invalid {
+                path: path.to_owned(),
+                diagnostics,
+            });
+        }
+        let config = Value::Table(table)
+            .try_into()
+            .map_err(|source| ConfigError::Parse {
+                path: path.to_owned(),
+                source,
+            })?;
         Ok(config)
     }
 
+    fn resolve_hooks(hooks: Vec<HookToml>) -> Result<Vec<Hook>, ConfigError> {
+        hooks
+            .into_iter()
+            .enumerate()
+            .map(|(index, hook)| {
+                let invalid = |reason| ConfigError::InvalidHook {
+                    number: index + 1,
+                    reason,
+                };
+                let action = match (hook.webhook, hook.command) {
+                    (Some(url), None) => HookAction::Webhook(url),
+                    (None, Some(command)) if command.is_empty() => {
+                        return Err(invalid("command is empty"));
+                    }
+                    (None, Some(command)) => HookAction::Command(command),
+                    (None, None) => return Err(invalid("needs a webhook or a command")),
+                    (Some(_), Some(_)) => {
+                        return Err(invalid("has both a webhook and a command"));
+                    }
+                };
+                Ok(Hook {
+                    events: hook.events.unwrap_or_else(|| HookEvent::ALL.to_vec()),
+                    action,
+                })
+            })
+            .collect()
+    }
+
     fn resolve_machines(
         machines: BTreeMap<String, MachineConfigToml>,
         plan_path: &Path,
+        default_staging_dir: Option<&str>,
+        defaults: &ApplyDefaults<'_>,
     ) -> Result<BTreeMap<String, MachineConfig>, ConfigError> {
         machines
             .into_iter()
             .map(|(name, config)| {
                 let MachineConfigToml {
-                    machine,
+                    mut machine,
                     plan,
                     params,
+                    groups,
+                    vars,
+                    staging_dir,
+                    lusid_apply_path,
+                    log,
+                    check,
+                    jobs,
+                    ssh,
                 } = config;
+                let staging_dir = staging_dir
+                    .or(default_staging_dir.map(str::to_string))
+                    .unwrap_or(DEFAULT_STAGING_DIR.into());
+                if let Err(reason) = validate_staging_dir(&staging_dir) {
+                    return Err(ConfigError::InvalidStagingDir {
+                        machine_id: name,
+                        staging_dir,
+                        reason,
+                    });
+                }
+                if let (Some(vm), Some(base_dir)) = (&mut machine.vm, plan_path.parent()) {
+                    vm.disks = std::mem::take(&mut vm.disks)
+                        .into_iter()
+                        .map(|disk| disk.resolve(base_dir))
+                        .collect();
+                }
+                let apply = ApplyConfig {
+                    bin: lusid_apply_path.unwrap_or_else(|| {
+                        match machine.arch {
+                            Arch::X86_64 => defaults.lusid_apply_linux_x86_64_path,
+                            Arch::Aarch64 => defaults.lusid_apply_linux_aarch64_path,
+                        }
+                        .to_owned()
+                    }),
+                    log: defaults
+                        .cli_log
+                        .map(str::to_owned)
+                        .or(log)
+                        .unwrap_or_else(|| defaults.log.to_owned()),
+                    check,
+                    downloads: DownloadLimits {
+                        max_parallel: jobs.or(defaults.downloads.max_parallel),
+                        ..defaults.downloads
+                    },
+                };
+                let remote = ssh.map(|ssh| RemoteConfig {
+                    host: ssh.host.unwrap_or_else(|| machine.hostname.to_string()),
+                    port: ssh.port.unwrap_or(22),
+                    user: ssh.user,
+                    key: Self::resolve_key_path(plan_path, ssh.key),
+                    host_key: ssh.host_key,
+                });
                 Ok((
                     name,
                     MachineConfig {
                         machine,
                         plan: Self::resolve_plan_path(plan_path, &plan)?,
                         params,
+                        groups,
+                        vars,
+                        staging_dir,
+                        apply,
+                        remote,
                     },
                 ))
             })
             .collect::<Result<_, _>>()
     }
 
+    fn resolve_key_path(base_path: &Path, key: PathBuf) -> PathBuf {
+        if let (Ok(rest), Some(home)) = (key.strip_prefix("~"), std::env::var_os("HOME")) {
+            return PathBuf::from(home).join(rest);
+        }
+        match base_path.parent() {
+            Some(parent) if key.is_relative() => parent.join(key),
+            _ => key,
+        }
+    }
+
     fn resolve_plan_path(base_path: &Path, plan_path: &Path) -> Result<PathBuf, ConfigError> {
         if plan_path.is_absolute() {
             Ok(plan_path.to_path_buf())
@@ -238,3 +588,11 @@ impl Config {
         }
     }
 }
+
+/// Every diagnostic, one per line below the error's own.
+fn list_diagnostics(diagnostics: &[ConfigDiagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(|diagnostic| format!("\n  {diagnostic}"))
+        .collect()
+}
@@ -0,0 +1,481 @@
+//! The shape of `lusid.toml`, checked before it's deserialized (see
+//! [`Config::load`](crate::config::Config::load)), so a mistake is reported
+//! by where it is and what was expected rather than as serde's first error.
+//!
+//! [`check_config`] walks the parsed TOML against [`CONFIG`] and collects
+//! every problem it finds: unknown keys (with the known key they're closest
+//! to, for typos), missing keys, values of the wrong type, integers out of
+//! range, and plan files that don't exist. Whatever passes is then
+//! deserialized as before, which still checks the values this schema leaves
+//! to their own types, like `arch` or `memory_size`.
+
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+use toml::{Table, Value};
+
+use crate::keys::Action;
+
+/// What a value in `lusid.toml` may be.
+#[derive(Debug, Clone, Copy)]
+pub enum Schema {
+    /// Anything, left to deserialization to check.
+    Any,
+    String,
+    Bool,
+    /// An integer from `min` to `max`, inclusive.
+    Integer {
+        min: i64,
+        max: i64,
+    },
+    /// A path to a file, relative to the config's directory, which must
+    /// exist.
+    ExistingPath,
+    List(&'static Schema),
+    /// One value, or a list of them.
+    OneOrList(&'static Schema),
+    /// A table of these fields.
+    Table(&'static [Field]),
+    /// A table of any keys, or only `keys` if given, each with a `values`.
+    Map {
+        keys: Option<&'static [&'static str]>,
+        values: &'static Schema,
+    },
+}
+
+impl Schema {
+    fn describe(&self) -> &'static str {
+        match self {
+            Schema::Any => "any value",
+            Schema::String => "a string",
+            Schema::Bool => "a boolean",
+            Schema::Integer { .. } => "an integer",
+            Schema::ExistingPath => "a path",
+            Schema::List(_) => "a list",
+            Schema::OneOrList(_) => "a value or a list",
+            Schema::Table(_) | Schema::Map { .. } => "a table",
+        }
+    }
+}
+
+/// One key of a [`Schema::Table`].
+#[derive(Debug, Clone, Copy)]
+pub struct Field {
+    pub key: &'static str,
+    pub schema: Schema,
+    pub required: bool,
+}
+
+const fn optional(key: &'static str, schema: Schema) -> Field {
+    Field {
+        key,
+        schema,
+        required: false,
+    }
+}
+
+const fn required(key: &'static str, schema: Schema) -> Field {
+    Field {
+        key,
+        schema,
+        required: true,
+    }
+}
+
+const STRINGS: Schema = Schema::List(&Schema::String);
+
+const PORT: Schema = Schema::Integer {
+    min: 1,
+    max: u16::MAX as i64,
+};
+
+const U16: Schema = Schema::Integer {
+    min: 0,
+    max: u16::MAX as i64,
+};
+
+const POSITIVE: Schema = Schema::Integer {
+    min: 1,
+    max: i64::MAX,
+};
+
+const NON_NEGATIVE: Schema = Schema::Integer {
+    min: 0,
+    max: i64::MAX,
+};
+
+const VM: Schema = Schema::Table(&[
+    optional("memory_size", Schema::Any),
+    optional("cpu_count", Schema::Any),
+    optional("disk_size", Schema::Any),
+    optional("graphics", Schema::Bool),
+    optional(
+        "disks",
+        Schema::List(&Schema::Table(&[
+            required("path", Schema::String),
+            optional("format", Schema::String),
+            optional("readonly", Schema::Bool),
+        ])),
+    ),
+    optional(
+        "usb",
+        Schema::List(&Schema::Table(&[
+            required("vendor_id", U16),
+            required("product_id", U16),
+        ])),
+    ),
+    optional("bridge", Schema::String),
+    optional("qemu_args", STRINGS),
+]);
+
+const SSH: Schema = Schema::Table(&[
+    optional("host", Schema::String),
+    optional("port", PORT),
+    required("user", Schema::String),
+    required("key", Schema::String),
+    required("host_key", Schema::String),
+]);
+
+const MACHINE: Schema = Schema::Table(&[
+    required("hostname", Schema::String),
+    required("arch", Schema::Any),
+    required("os", Schema::Any),
+    optional("vm", VM),
+    required("plan", Schema::ExistingPath),
+    optional("params", Schema::Any),
+    optional("groups", STRINGS),
+    optional("vars", Schema::Any),
+    optional("staging_dir", Schema::String),
+    optional("lusid_apply_path", Schema::String),
+    optional("log", Schema::String),
+    optional("check", Schema::Bool),
+    optional("jobs", POSITIVE),
+    optional("ssh", SSH),
+]);
+
+/// The whole of `lusid.toml`.
+pub const CONFIG: Schema = Schema::Table(&[
+    optional(
+        "machines",
+        Schema::Map {
+            keys: None,
+            values: &MACHINE,
+        },
+    ),
+    optional("log", Schema::String),
+    optional("lusid_apply_linux_x86_64_path", Schema::String),
+    optional("lusid_apply_linux_aarch64_path", Schema::String),
+    optional("staging_dir", Schema::String),
+    optional(
+        "keys",
+        Schema::Map {
+            keys: Some(Action::NAMES.as_slice()),
+            values: &Schema::OneOrList(&Schema::String),
+        },
+    ),
+    optional("notify", STRINGS),
+    optional(
+        "downloads",
+        Schema::Table(&[
+            optional("max_parallel", POSITIVE),
+            optional("max_kib_per_sec", POSITIVE),
+        ]),
+    ),
+    optional(
+        "protect",
+        Schema::Table(&[
+            optional("defaults", Schema::Bool),
+            optional("paths", STRINGS),
+            optional("packages", STRINGS),
+            optional("users", STRINGS),
+        ]),
+    ),
+    optional("wait_for_locks", NON_NEGATIVE),
+    optional(
+        "hooks",
+        Schema::List(&Schema::Table(&[
+            optional("events", STRINGS),
+            optional("webhook", Schema::String),
+            optional("command", STRINGS),
+        ])),
+    ),
+]);
+
+/// One problem found in `lusid.toml`, at `key`, a dotted path like
+/// `machines.web.ssh.port` (list items are `hooks[0]`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigDiagnostic {
+    pub key: String,
+    pub problem: ConfigProblem,
+}
+
+impl Display for ConfigDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.problem)
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConfigProblem {
+    #[error("unknown key{}", DidYouMean(.suggestion))]
+    UnknownKey { suggestion: Option<&'static str> },
+
+    #[error("missing required key")]
+    Missing,
+
+    #[error("expected {expected}, got {got}")]
+    WrongType {
+        expected: &'static str,
+        got: &'static str,
+    },
+
+    #[error("{value} is out of range, expected {min} to {max}")]
+    OutOfRange { value: i64, min: i64, max: i64 },
+
+    #[error("{} doesn't exist", .path.display())]
+    PathNotFound { path: PathBuf },
+}
+
+struct DidYouMean<'a>(&'a Option<&'static str>);
+
+impl Display for DidYouMean<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(suggestion) => write!(f, ", did you mean `{suggestion}`?"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Check `config` against [`CONFIG`], with relative paths resolved against
+/// `base_dir`, returning every problem found.
+pub fn check_config(config: &Table, base_dir: &Path) -> Vec<ConfigDiagnostic> {
+    let mut checker = Checker {
+        base_dir,
+        diagnostics: Vec::new(),
+    };
+    let Schema::Table(fields) = CONFIG else {
+        unreachable!("the config schema is a table");
+    };
+    checker.check_table(config, fields, "");
+    checker.diagnostics
+}
+
+struct Checker<'a> {
+    base_dir: &'a Path,
+    diagnostics: Vec<ConfigDiagnostic>,
+}
+
+impl Checker<'_> {
+    fn report(&mut self, key: &str, problem: ConfigProblem) {
+        self.diagnostics.push(ConfigDiagnostic {
+            key: key.to_owned(),
+            problem,
+        });
+    }
+
+    fn check(&mut self, value: &Value, schema: &Schema, key: &str) {
+        match (schema, value) {
+            (Schema::Any, _)
+            | (Schema::String, Value::String(_))
+            | (Schema::Bool, Value::Boolean(_)) => {}
+            (Schema::Integer { min, max }, Value::Integer(value)) => {
+                if value < min || value > max {
+                    self.report(
+                        key,
+                        ConfigProblem::OutOfRange {
+                            value: *value,
+                            min: *min,
+                            max: *max,
+                        },
+                    );
+                }
+            }
+            (Schema::ExistingPath, Value::String(path)) => {
+                let path = self.base_dir.join(path);
+                if !path.exists() {
+                    self.report(key, ConfigProblem::PathNotFound { path });
+                }
+            }
+            (Schema::List(item) | Schema::OneOrList(item), Value::Array(values)) => {
+                for (index, value) in values.iter().enumerate() {
+                    self.check(value, item, &format!("{key}[{index}]"));
+                }
+            }
+            (Schema::OneOrList(item), value) => self.check(value, item, key),
+            (Schema::Table(fields), Value::Table(table)) => self.check_table(table, fields, key),
+            (Schema::Map { keys, values }, Value::Table(table)) => {
+                for (name, value) in table {
+                    let nested = nested_key(key, name);
+                    let unknown = keys.filter(|keys| !keys.contains(&name.as_str()));
+                    if let Some(keys) = unknown {
+                        let suggestion = closest(name, keys.iter().copied());
+                        self.report(&nested, ConfigProblem::UnknownKey { suggestion });
+                        continue;
+                    }
+                    self.check(value, values, &nested);
+                }
+            }
+            (schema, value) => self.report(
+                key,
+                ConfigProblem::WrongType {
+                    expected: schema.describe(),
+                    got: value.type_str(),
+                },
+            ),
+        }
+    }
+
+    fn check_table(&mut self, table: &Table, fields: &[Field], key: &str) {
+        for field in fields {
+            match table.get(field.key) {
+                Some(value) => self.check(value, &field.schema, &nested_key(key, field.key)),
+                None if field.required => {
+                    self.report(&nested_key(key, field.key), ConfigProblem::Missing);
+                }
+                None => {}
+            }
+        }
+        for name in table.keys() {
+            if !fields.iter().any(|field| field.key == name) {
+                let suggestion = closest(name, fields.iter().map(|field| field.key));
+                self.report(
+                    &nested_key(key, name),
+                    ConfigProblem::UnknownKey { suggestion },
+                );
+            }
+        }
+    }
+}
+
+fn nested_key(parent: &str, key: &str) -> String {
+    match parent {
+        "" => key.to_owned(),
+        parent => format!("{parent}.{key}"),
+    }
+}
+
+/// The candidate closest to `name`, if any is close enough to be a typo of
+/// it: within a third of its length in edits (at least one), or the same
+/// but for `-` and `_`.
+fn closest(name: &str, candidates: impl Iterator<Item = &'static str>) -> Option<&'static str> {
+    let normalized = name.replace('-', "_");
+    let threshold = (name.chars().count() / 3).max(1);
+    candidates
+        .map(|candidate| {
+            let distance = if candidate.replace('-', "_") == normalized {
+                0
+            } else {
+                edit_distance(name, candidate)
+            };
+            (distance, candidate)
+        })
+        .filter(|&(distance, _)| distance <= threshold)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Edits (insertions, deletions, substitutions and swaps of adjacent
+/// chars) to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // `rows[i][j]` is the distance between the first `i` chars of `a` and
+    // the first `j` of `b`.
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let substitution = rows[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = substitution.min(rows[i - 1][j] + 1).min(rows[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = distance;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Plans are resolved against this crate's directory, where
+    // `Cargo.toml` stands in for a plan file.
+    fn check(toml: &str) -> Vec<String> {
+        let config: Table = toml::from_str(toml).unwrap();
+        check_config(&config, Path::new(env!("CARGO_MANIFEST_DIR")))
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect()
+    }
+
+    const MACHINE: &str = r#"
+        [machines.web]
+        hostname = "web"
+        arch = "x86-64"
+        os = { type = "linux", linux = "debian", debian = 13 }
+        plan = "./Cargo.toml"
+    "#;
+
+    #[test]
+    fn accepts_a_valid_config() {
+        let config = format!("{MACHINE}\n[keys]\ndown = [\"n\", \"down\"]\n");
+        assert_eq!(check(&config), Vec::<String>::new());
+    }
+
+    #[test]
+    fn suggests_keys_for_typos() {
+        let config =
+            format!("staging-dir = \"/tmp\"\n{MACHINE}\nhostnme = \"web\"\n[keys]\ndwon = \"n\"\n");
+        assert_eq!(
+            check(&config),
+            vec![
+                "machines.web.hostnme: unknown key, did you mean `hostname`?",
+                "keys.dwon: unknown key, did you mean `down`?",
+                "staging-dir: unknown key, did you mean `staging_dir`?",
+            ]
+        );
+        assert_eq!(check("wibble = 1"), vec!["wibble: unknown key".to_owned()]);
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let config = r#"
+            [machines.web]
+            hostname = "web"
+            arch = "x86-64"
+            os = { type = "linux", linux = "debian", debian = 13 }
+            plan = "./missing.lusid"
+            groups = "web"
+            ssh = { port = 70000, key = "~/.ssh/id", host_key = "ssh-ed25519 AAAA" }
+        "#;
+        let diagnostics = check(config);
+        assert_eq!(diagnostics.len(), 4, "{diagnostics:?}");
+        assert!(diagnostics[0].starts_with("machines.web.plan: "));
+        assert!(diagnostics[0].ends_with("missing.lusid doesn't exist"));
+        assert_eq!(
+            diagnostics[1..],
+            [
+                "machines.web.groups: expected a list, got string",
+                "machines.web.ssh.port: 70000 is out of range, expected 1 to 65535",
+                "machines.web.ssh.user: missing required key",
+            ]
+        );
+    }
+
+    #[test]
+    fn measures_edit_distance() {
+        assert_eq!(edit_distance("plan", "plan"), 0);
+        assert_eq!(edit_distance("plna", "plan"), 1);
+        assert_eq!(edit_distance("hostnam", "hostname"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+}
@@ -0,0 +1,121 @@
+//! Container targets: apply a machine's plan inside a running docker or
+//! podman container, for testing a plan against a fresh image or building
+//! an image from one.
+//!
+//! Like `dev apply`, nothing runs the plan from this host: `lusid-apply` and
+//! the plan are copied into a [staging directory](crate::staging) in the
+//! container with `<engine> cp`, and `lusid-apply` is run there with
+//! `<engine> exec` as root, so its operations skip `sudo` (`--no-sudo`),
+//! which most images don't have. The staging directory is removed after
+//! the apply, so a container committed to an image carries no lusid files.
+
+use std::ffi::OsStr;
+use std::path::Path;
+
+use clap::ValueEnum;
+use lusid_cmd::{Command, CommandError};
+use which::which;
+
+use crate::staging::StagingDir;
+
+/// Where the staging directory goes in a container. Absolute, as there's no
+/// login user's home to resolve, and under `/var/tmp` so it's on the
+/// container's own filesystem rather than a tmpfs `/tmp`.
+pub const CONTAINER_STAGING_DIR: &str = "/var/tmp/lusid";
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContainerEngine {
+    Docker,
+    Podman,
+}
+
+impl ContainerEngine {
+    /// The engine on this host's `PATH`, preferring docker.
+    pub fn detect() -> Result<Self, which::Error> {
+        match which("docker") {
+            Ok(_) => Ok(ContainerEngine::Docker),
+            Err(_) => which("podman").map(|_| ContainerEngine::Podman),
+        }
+    }
+
+    fn program(&self) -> &'static str {
+        match self {
+            ContainerEngine::Docker => "docker",
+            ContainerEngine::Podman => "podman",
+        }
+    }
+}
+
+/// A running container, by name or id.
+#[derive(Debug, Clone)]
+pub struct Container {
+    pub engine: ContainerEngine,
+    pub name: String,
+}
+
+impl Container {
+    // Note(cc): there's no executor trait for targets to implement: each
+    // target is a way of running `lusid-apply`, and `run_local_apply` takes
+    // any `Command`, so one exec'd into the container is enough.
+    /// A command run in the container as root. Stdin stays open (`-i`), so
+    /// the TUI can send `lusid-apply` control messages.
+    pub fn exec<I, S>(&self, program: &str, args: I) -> Command
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let mut command = Command::new(self.engine.program());
+        command
+            .args(["exec", "-i", "--user", "0"])
+            .arg(&self.name)
+            .arg(program)
+            .args(args);
+        command
+    }
+
+    /// Copy the local file `local` to `remote` in the container, creating
+    /// its parent directories.
+    pub async fn copy_file(&self, local: &Path, remote: &str) -> Result<(), CommandError> {
+        if let Some(parent) = Path::new(remote).parent() {
+            self.exec("mkdir", [Path::new("-p"), parent]).run().await?;
+        }
+        self.copy(local, remote).await
+    }
+
+    /// Copy the contents of the local directory `local` into `remote` in the
+    /// container, creating it.
+    pub async fn copy_dir(&self, local: &Path, remote: &str) -> Result<(), CommandError> {
+        self.exec("mkdir", ["-p", remote]).run().await?;
+        // `<dir>/.` copies what's in the directory rather than the directory
+        // itself, whether or not `remote` exists already.
+        self.copy(&local.join("."), remote).await
+    }
+
+    async fn copy(&self, local: &Path, remote: &str) -> Result<(), CommandError> {
+        Command::new(self.engine.program())
+            .arg("cp")
+            .arg(local)
+            .arg(format!("{}:{remote}", self.name))
+            .run()
+            .await?;
+        Ok(())
+    }
+
+    /// Remove the staging directory from the container. A no-op if it
+    /// doesn't exist.
+    pub async fn clean(&self, staging: &StagingDir) -> Result<(), CommandError> {
+        self.exec("rm", ["-rf", "--", staging.root()]).run().await?;
+        Ok(())
+    }
+
+    /// Save the container's filesystem as the image `image`.
+    pub async fn commit(&self, image: &str) -> Result<(), CommandError> {
+        Command::new(self.engine.program())
+            .arg("commit")
+            .arg(&self.name)
+            .arg(image)
+            .run()
+            .await?;
+        Ok(())
+    }
+}
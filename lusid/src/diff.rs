@@ -0,0 +1,201 @@
+//! `plan diff`: compare two [`RenderedPlan`]s resource by resource.
+//!
+//! Resources are matched by their [`RenderedResource::id`] path. A resource
+//! is modified when its params, atoms or ordering differ; for atoms only the
+//! ones that came or went are shown, since most edits touch one of many.
+//!
+//! Note(cc): items without an `id` are keyed by position (`#<n>`), so
+//! inserting one shifts its unnamed siblings and they show up as modified.
+//! Giving shared-module items ids makes their diffs much quieter.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+use lusid_apply_stdio::{RenderedPlan, RenderedResource};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanDiff {
+    pub added: Vec<RenderedResource>,
+    pub removed: Vec<RenderedResource>,
+    pub modified: Vec<(RenderedResource, RenderedResource)>,
+}
+
+impl PlanDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+pub fn diff_plans(old: RenderedPlan, new: RenderedPlan) -> PlanDiff {
+    let mut old: BTreeMap<String, RenderedResource> = old
+        .resources
+        .into_iter()
+        .map(|resource| (resource.id.clone(), resource))
+        .collect();
+
+    let mut added = Vec::new();
+    let mut modified = Vec::new();
+    for resource in new.resources {
+        match old.remove(&resource.id) {
+            None => added.push(resource),
+            Some(previous) if previous != resource => modified.push((previous, resource)),
+            Some(_) => {}
+        }
+    }
+    let removed = old.into_values().collect();
+
+    PlanDiff {
+        added,
+        removed,
+        modified,
+    }
+}
+
+impl Display for PlanDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences.");
+        }
+
+        for resource in &self.added {
+            writeln!(f, "+ {}", resource.id)?;
+            write_resource(f, "+", resource)?;
+        }
+        for resource in &self.removed {
+            writeln!(f, "- {}", resource.id)?;
+            write_resource(f, "-", resource)?;
+        }
+        for (old, new) in &self.modified {
+            writeln!(f, "~ {}", new.id)?;
+            if old.params != new.params {
+                writeln!(f, "    - {}", old.params)?;
+                writeln!(f, "    + {}", new.params)?;
+            }
+            if old.requires != new.requires {
+                writeln!(f, "    - requires: {}", old.requires.join(", "))?;
+                writeln!(f, "    + requires: {}", new.requires.join(", "))?;
+            }
+            for atom in old.atoms.iter().filter(|atom| !new.atoms.contains(atom)) {
+                writeln!(f, "    - {atom}")?;
+            }
+            for atom in new.atoms.iter().filter(|atom| !old.atoms.contains(atom)) {
+                writeln!(f, "    + {atom}")?;
+            }
+        }
+
+        writeln!(
+            f,
+            "\n{} added, {} removed, {} modified",
+            self.added.len(),
+            self.removed.len(),
+            self.modified.len()
+        )
+    }
+}
+
+fn write_resource(
+    f: &mut fmt::Formatter<'_>,
+    sign: &str,
+    resource: &RenderedResource,
+) -> fmt::Result {
+    writeln!(f, "    {sign} {}", resource.params)?;
+    if !resource.requires.is_empty() {
+        writeln!(f, "    {sign} requires: {}", resource.requires.join(", "))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn resource(id: &str, params: serde_json::Value, atoms: &[&str]) -> RenderedResource {
+        RenderedResource {
+            id: id.into(),
+            params,
+            requires: Vec::new(),
+            atoms: atoms.iter().map(|atom| atom.to_string()).collect(),
+        }
+    }
+
+    fn plan(resources: Vec<RenderedResource>) -> RenderedPlan {
+        RenderedPlan {
+            plan: "server.lusid".into(),
+            resources,
+        }
+    }
+
+    #[test]
+    fn matches_resources_by_id() {
+        let old = plan(vec![
+            resource(
+                "curl",
+                json!({"apt": {"package": {"package": "curl"}}}),
+                &["Apt(curl)"],
+            ),
+            resource(
+                "old",
+                json!({"apt": {"package": {"package": "old"}}}),
+                &["Apt(old)"],
+            ),
+            resource(
+                "web/#0",
+                json!({"apt": {"package": {"package": "nginx"}}}),
+                &["Apt(nginx)"],
+            ),
+        ]);
+        let new = plan(vec![
+            resource(
+                "curl",
+                json!({"apt": {"package": {"package": "curl"}}}),
+                &["Apt(curl)"],
+            ),
+            resource(
+                "web/#0",
+                json!({"apt": {"packages": {"packages": ["nginx", "nginx-extras"]}}}),
+                &["Apt(nginx)", "Apt(nginx-extras)"],
+            ),
+            resource(
+                "new",
+                json!({"apt": {"package": {"package": "new"}}}),
+                &["Apt(new)"],
+            ),
+        ]);
+
+        let diff = diff_plans(old, new);
+
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].id, "new");
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.removed[0].id, "old");
+        assert_eq!(diff.modified.len(), 1);
+        assert_eq!(
+            diff.to_string(),
+            r#"+ new
+    + {"apt":{"package":{"package":"new"}}}
+- old
+    - {"apt":{"package":{"package":"old"}}}
+~ web/#0
+    - {"apt":{"package":{"package":"nginx"}}}
+    + {"apt":{"packages":{"packages":["nginx","nginx-extras"]}}}
+    + Apt(nginx-extras)
+
+1 added, 1 removed, 1 modified
+"#
+        );
+    }
+
+    #[test]
+    fn identical_plans_have_no_differences() {
+        let resources = vec![resource(
+            "curl",
+            json!({"apt": {"package": {"package": "curl"}}}),
+            &["Apt(curl)"],
+        )];
+        let diff = diff_plans(plan(resources.clone()), plan(resources));
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "No differences.\n");
+    }
+}
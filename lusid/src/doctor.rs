@@ -0,0 +1,345 @@
+//! `lusid doctor`: preflight checks against an apply target, so a target that
+//! can't be applied to shows up as a checklist up front rather than as a
+//! failure halfway through an apply.
+//!
+//! Every check runs over the same SSH connection `apply` would use, with their
+//! probes batched into one round trip (see [`Ssh::output_batch`]); a failing
+//! check never stops the others, and an unreachable target reports the rest as
+//! skipped.
+
+use comfy_table::Table;
+use lusid_machine::Machine;
+use lusid_ssh::{Ssh, SshOutput};
+use lusid_system::{Arch, Linux, Os};
+use tokio::io::AsyncReadExt;
+use which::which;
+
+/// Free space the staging directory needs: the uploaded `lusid-apply` binary
+/// and plan directory, with headroom for files operations stage there.
+const MIN_STAGING_FREE_KIB: u64 = 512 * 1024;
+
+/// Checks that need a connection, in report order (after `ssh` itself).
+const REMOTE_CHECKS: [&str; 4] = ["sudo", "staging disk", "package manager", "arch"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    Skip,
+}
+
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl Check {
+    pub fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Pass,
+            detail: detail.into(),
+        }
+    }
+
+    pub fn fail(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Fail,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// What the target is expected to be: the machine from `lusid.toml`, the
+/// `lusid-apply` binary that would be uploaded to it (a path, or a name on
+/// `$PATH`), and the configured directory uploads are staged in.
+pub struct DoctorTarget<'a> {
+    pub machine: &'a Machine,
+    pub apply_bin: &'a str,
+    pub staging_dir: &'a str,
+}
+
+/// Run every check that needs a connection.
+pub async fn run_checks(ssh: &mut Ssh, target: &DoctorTarget<'_>) -> Vec<Check> {
+    let package_manager = package_manager(&target.machine.os);
+    let commands = [
+        SUDO_COMMAND.to_string(),
+        staging_space_command(target.staging_dir),
+        format!("command -v {package_manager}"),
+        ARCH_COMMAND.to_string(),
+    ];
+    let outputs = match ssh.output_batch(&commands).await {
+        Ok(outputs) => outputs,
+        Err(error) => {
+            return REMOTE_CHECKS
+                .iter()
+                .map(|&name| Check::fail(name, error.to_string()))
+                .collect();
+        }
+    };
+    let [sudo, staging_space, package_manager_path, arch] =
+        <[SshOutput; 4]>::try_from(outputs).expect("a batch has one output per command");
+
+    vec![
+        check_sudo(&sudo),
+        check_staging_space(&staging_space, target.staging_dir),
+        check_package_manager(&package_manager_path, package_manager, &target.machine.os),
+        check_arch(&arch, target.machine.arch, target.apply_bin).await,
+    ]
+}
+
+/// The report for a target we couldn't connect to.
+pub fn unreachable(error: &dyn std::fmt::Display) -> Vec<Check> {
+    let mut checks = vec![Check::fail("ssh", format!("failed to connect: {error}"))];
+    checks.extend(REMOTE_CHECKS.iter().map(|&name| Check {
+        name,
+        status: CheckStatus::Skip,
+        detail: "target unreachable".to_string(),
+    }));
+    checks
+}
+
+pub fn print_checks(checks: &[Check]) {
+    let mut table = Table::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL)
+        .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .set_header(vec!["check", "status", "detail"]);
+
+    for check in checks {
+        let status = match check.status {
+            CheckStatus::Pass => "ok",
+            CheckStatus::Fail => "FAIL",
+            CheckStatus::Skip => "skipped",
+        };
+        table.add_row(vec![check.name, status, &check.detail]);
+    }
+
+    println!("{table}")
+}
+
+// lusid-apply runs privileged operations non-interactively, so a sudo that
+// prompts for a password is as good as none.
+const SUDO_COMMAND: &str = "sudo -n true";
+
+const ARCH_COMMAND: &str = "uname -m";
+
+fn check_sudo(output: &SshOutput) -> Check {
+    if output.success() {
+        Check::pass("sudo", "passwordless sudo works")
+    } else {
+        Check::fail(
+            "sudo",
+            format!("`{SUDO_COMMAND}` failed: {}", describe_failure(output)),
+        )
+    }
+}
+
+fn staging_space_command(staging_dir: &str) -> String {
+    // The staging dir may not exist yet (and `~/` is only known on the
+    // target), so measure its closest existing ancestor.
+    let dir = match staging_dir.strip_prefix("~/") {
+        Some(rest) => format!("\"$HOME\"/'{rest}'"),
+        None => format!("'{staging_dir}'"),
+    };
+    format!("d={dir}; while [ ! -e \"$d\" ]; do d=$(dirname \"$d\"); done; df -Pk -- \"$d\"")
+}
+
+fn check_staging_space(output: &SshOutput, staging_dir: &str) -> Check {
+    let name = "staging disk";
+    if !output.success() {
+        return Check::fail(
+            name,
+            format!("`df` on {staging_dir} failed: {}", describe_failure(output)),
+        );
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    match parse_df_available_kib(&stdout) {
+        Some(available) if available >= MIN_STAGING_FREE_KIB => Check::pass(
+            name,
+            format!("{} MiB free in {staging_dir}", available / 1024),
+        ),
+        Some(available) => Check::fail(
+            name,
+            format!(
+                "{} MiB free in {staging_dir}, need at least {} MiB",
+                available / 1024,
+                MIN_STAGING_FREE_KIB / 1024
+            ),
+        ),
+        None => Check::fail(name, format!("unexpected `df` output: {}", stdout.trim())),
+    }
+}
+
+fn package_manager(os: &Os) -> &'static str {
+    match os {
+        Os::Linux(Linux::Ubuntu { .. } | Linux::Debian { .. }) => "apt-get",
+        Os::Linux(Linux::Arch) => "pacman",
+        Os::Linux(Linux::Fedora { .. } | Linux::Rhel { .. }) => "dnf",
+        Os::Linux(Linux::Alpine { .. }) => "apk",
+        Os::MacOs(_) => "brew",
+    }
+}
+
+fn check_package_manager(output: &SshOutput, package_manager: &str, os: &Os) -> Check {
+    let name = "package manager";
+    if output.success() {
+        Check::pass(
+            name,
+            format!(
+                "{package_manager} at {}",
+                String::from_utf8_lossy(&output.stdout).trim()
+            ),
+        )
+    } else {
+        Check::fail(
+            name,
+            format!("{package_manager} not found, but the machine's os is {os}"),
+        )
+    }
+}
+
+async fn check_arch(output: &SshOutput, machine_arch: Arch, apply_bin: &str) -> Check {
+    let name = "arch";
+
+    if !output.success() {
+        return Check::fail(
+            name,
+            format!("`{ARCH_COMMAND}` failed: {}", describe_failure(output)),
+        );
+    }
+    let uname = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let Some(target_arch) = parse_uname_arch(&uname) else {
+        return Check::fail(name, format!("unsupported target arch {uname:?}"));
+    };
+    if target_arch != machine_arch {
+        return Check::fail(
+            name,
+            format!("target is {target_arch}, but the machine's arch is {machine_arch}"),
+        );
+    }
+
+    let binary_arch = match read_binary_arch(apply_bin).await {
+        Ok(arch) => arch,
+        Err(detail) => return Check::fail(name, detail),
+    };
+    if binary_arch != target_arch {
+        return Check::fail(
+            name,
+            format!("{apply_bin} is built for {binary_arch}, but the target is {target_arch}"),
+        );
+    }
+
+    Check::pass(name, format!("{target_arch}, matching {apply_bin}"))
+}
+
+async fn read_binary_arch(apply_bin: &str) -> Result<Arch, String> {
+    let path = which(apply_bin).map_err(|error| format!("{apply_bin}: {error}"))?;
+    let mut header = [0u8; 20];
+    let mut file = tokio::fs::File::open(&path)
+        .await
+        .map_err(|error| format!("failed to open {}: {error}", path.display()))?;
+    file.read_exact(&mut header)
+        .await
+        .map_err(|error| format!("failed to read {}: {error}", path.display()))?;
+    elf_arch(&header)
+        .ok_or_else(|| format!("{} is not an x86-64 or aarch64 ELF binary", path.display()))
+}
+
+fn describe_failure(output: &SshOutput) -> String {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr = stderr.trim();
+    match (output.exit_code, stderr.is_empty()) {
+        (Some(code), true) => format!("exit code {code}"),
+        (Some(code), false) => format!("exit code {code}: {stderr}"),
+        (None, true) => "no exit code".to_string(),
+        (None, false) => stderr.to_string(),
+    }
+}
+
+/// The "Available" column of POSIX `df -Pk` output, in KiB.
+fn parse_df_available_kib(output: &str) -> Option<u64> {
+    // Header, then one line per filesystem: name, size, used, available,
+    // capacity, mount point. The name can't contain whitespace in -P output.
+    let line = output.lines().nth(1)?;
+    line.split_whitespace().nth(3)?.parse().ok()
+}
+
+fn parse_uname_arch(uname: &str) -> Option<Arch> {
+    match uname {
+        "x86_64" => Some(Arch::X86_64),
+        "aarch64" | "arm64" => Some(Arch::Aarch64),
+        _ => None,
+    }
+}
+
+/// Target arch of a 64-bit ELF binary, from the first 20 bytes of its header.
+fn elf_arch(header: &[u8; 20]) -> Option<Arch> {
+    const EM_X86_64: u16 = 62;
+    const EM_AARCH64: u16 = 183;
+
+    if &header[..4] != b"\x7fELF" {
+        return None;
+    }
+    let machine = [header[18], header[19]];
+    let machine = match header[5] {
+        1 => u16::from_le_bytes(machine),
+        2 => u16::from_be_bytes(machine),
+        _ => return None,
+    };
+    match machine {
+        EM_X86_64 => Some(Arch::X86_64),
+        EM_AARCH64 => Some(Arch::Aarch64),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn df_available() {
+        let output = "Filesystem     1024-blocks    Used Available Capacity Mounted on\n\
+                      /dev/vda1         10218772 2345678   7344444      25% /\n";
+        assert_eq!(parse_df_available_kib(output), Some(7344444));
+        assert_eq!(parse_df_available_kib("df: /nope: No such file\n"), None);
+    }
+
+    fn output(exit_code: u32, stdout: &str) -> SshOutput {
+        SshOutput {
+            exit_code: Some(exit_code),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn checks_read_batched_outputs() {
+        assert_eq!(check_sudo(&output(0, "")).status, CheckStatus::Pass);
+        assert_eq!(check_sudo(&output(1, "")).status, CheckStatus::Fail);
+
+        let os = Os::Linux(Linux::Arch);
+        let check = check_package_manager(&output(0, "/usr/bin/pacman\n"), "pacman", &os);
+        assert_eq!(check.status, CheckStatus::Pass);
+        assert_eq!(check.detail, "pacman at /usr/bin/pacman");
+    }
+
+    #[test]
+    fn elf_machine() {
+        let mut header = [0u8; 20];
+        header[..4].copy_from_slice(b"\x7fELF");
+        header[5] = 1;
+        header[18] = 62;
+        assert_eq!(elf_arch(&header), Some(Arch::X86_64));
+        header[18] = 183;
+        assert_eq!(elf_arch(&header), Some(Arch::Aarch64));
+        header[0] = b'#';
+        assert_eq!(elf_arch(&header), None);
+    }
+}
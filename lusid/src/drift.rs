@@ -0,0 +1,411 @@
+//! `lusid remote drift`: check a group of machines for drift from their
+//! plans, in parallel, and report it as one document.
+//!
+//! Each machine gets `lusid-apply --check` over SSH, as `verify` runs it: it
+//! observes every resource's state and plans the changes an apply would
+//! make, then stops. A resource with a planned change has drifted. The
+//! [`DriftReport`] lists the drifted resources across the group and, per
+//! machine, the change each needs: the rows and columns of a machines by
+//! resources matrix, for a dashboard to draw.
+//!
+//! A machine that can't be checked, because it's unreachable or its plan
+//! fails, is reported as failed alongside the rest, rather than stopping
+//! them.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use clap::ValueEnum;
+use comfy_table::Table;
+use lusid_apply_stdio::{AppUpdate, AppView, FlatViewTree, FlatViewTreeNode, ViewNode};
+use serde::Serialize;
+
+use crate::config::{Config, MachineConfig};
+use crate::report::ApplyReport;
+
+/// The group every machine is in.
+pub const ALL_GROUP: &str = "all";
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DriftOutput {
+    /// A table, one row per machine.
+    Text,
+    /// A [`DriftReport`] as JSON.
+    Json,
+}
+
+/// The remote machines in `group`, by id: those with an `ssh` section, as
+/// only they can be checked. Every such machine for [`ALL_GROUP`].
+pub fn group_machines(config: &Config, group: &str) -> Vec<(String, MachineConfig)> {
+    config
+        .machines
+        .iter()
+        .filter(|(_, machine)| machine.remote.is_some())
+        .filter(|(_, machine)| group == ALL_GROUP || machine.groups.iter().any(|g| g == group))
+        .map(|(id, machine)| (id.clone(), machine.clone()))
+        .collect()
+}
+
+/// Follows a check's updates, to pair each planned change with the resource
+/// it's for. The changes come by their index in the resources tree, which
+/// only the folded [`AppView`] has.
+#[derive(Debug, Default)]
+pub struct DriftRecorder {
+    view: AppView,
+    error: Option<String>,
+}
+
+impl DriftRecorder {
+    pub fn record(&mut self, update: &AppUpdate) {
+        if self.error.is_some() {
+            return;
+        }
+        match std::mem::take(&mut self.view).update(update.clone()) {
+            Ok(view) => self.view = view,
+            Err(error) => self.error = Some(error.to_string()),
+        }
+    }
+
+    /// Each drifted resource, keyed by [`resource_key`], with the change an
+    /// apply would make to it.
+    fn drift(&self) -> Result<BTreeMap<String, String>, String> {
+        if let Some(error) = &self.error {
+            return Err(format!("couldn't follow lusid-apply's updates: {error}"));
+        }
+        let (Some(resources), Some(changes)) =
+            (self.view.resources(), self.view.resource_changes())
+        else {
+            return Ok(BTreeMap::new());
+        };
+        let parents = parents(resources);
+        let mut drift = BTreeMap::new();
+        for (index, node) in changes.nodes().enumerate() {
+            let Some(FlatViewTreeNode::Leaf {
+                view: ViewNode::Complete(change),
+            }) = node
+            else {
+                continue;
+            };
+            let resource = match resources.get(index) {
+                Ok(FlatViewTreeNode::Leaf {
+                    view: ViewNode::Complete(resource),
+                }) => resource.to_string(),
+                _ => format!("resource {index}"),
+            };
+            drift.insert(
+                resource_key(resources, &parents, index, resource),
+                change.to_string(),
+            );
+        }
+        Ok(drift)
+    }
+}
+
+/// Each node's parent in `tree`, by index.
+fn parents(tree: &FlatViewTree) -> HashMap<usize, usize> {
+    let mut parents = HashMap::new();
+    for (index, node) in tree.nodes().enumerate() {
+        if let Some(FlatViewTreeNode::Branch { children, .. }) = node {
+            parents.extend(children.iter().map(|child| (*child, index)));
+        }
+    }
+    parents
+}
+
+/// `resource`, at `index`, after the ids of the plan nodes it's under,
+/// outermost first: `PlanItem(plan = Path(web.lusid), item = motd) >
+/// File(/etc/motd)`. A resource's own text isn't unique, as a module used
+/// twice declares the same ones twice.
+fn resource_key(
+    tree: &FlatViewTree,
+    parents: &HashMap<usize, usize>,
+    index: usize,
+    resource: String,
+) -> String {
+    let mut key = vec![resource];
+    let mut index = index;
+    while let Some(&parent) = parents.get(&index) {
+        // Plan nodes without an id are `.`.
+        if let Ok(FlatViewTreeNode::Branch { view, .. }) = tree.get(parent) {
+            let id = view.to_string();
+            if id != "." {
+                key.push(id);
+            }
+        }
+        index = parent;
+    }
+    key.reverse();
+    key.join(" > ")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftStatus {
+    /// Nothing to change.
+    InSync,
+    Drifted,
+    /// The check didn't finish; see the machine's `error`.
+    Failed,
+}
+
+/// One machine's row of the matrix.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MachineDrift {
+    pub machine: String,
+    pub status: DriftStatus,
+    /// Each drifted resource, after the ids of the plan nodes it's under,
+    /// with the change an apply would make to it.
+    pub drift: BTreeMap<String, String>,
+    /// Why the check failed, if it did.
+    pub error: Option<String>,
+}
+
+impl MachineDrift {
+    /// A finished check, from its report and the updates it recorded.
+    pub fn checked(machine: String, report: &ApplyReport, recorder: &DriftRecorder) -> Self {
+        if let Some(error) = &report.error {
+            return Self::failed(machine, error.clone());
+        }
+        match recorder.drift() {
+            Err(error) => Self::failed(machine, error),
+            Ok(drift) => Self {
+                machine,
+                status: if drift.is_empty() {
+                    DriftStatus::InSync
+                } else {
+                    DriftStatus::Drifted
+                },
+                drift,
+                error: None,
+            },
+        }
+    }
+
+    /// A check that couldn't run, like on a machine that's unreachable.
+    pub fn failed(machine: String, error: String) -> Self {
+        Self {
+            machine,
+            status: DriftStatus::Failed,
+            drift: BTreeMap::new(),
+            error: Some(error),
+        }
+    }
+}
+
+/// The result of `remote drift`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DriftReport {
+    pub group: String,
+    /// Every resource drifted on at least one machine, sorted: the matrix's
+    /// columns.
+    pub resources: Vec<String>,
+    /// One per machine checked, by id: the matrix's rows.
+    pub machines: Vec<MachineDrift>,
+}
+
+impl DriftReport {
+    pub fn new(group: String, mut machines: Vec<MachineDrift>) -> Self {
+        machines.sort_by(|a, b| a.machine.cmp(&b.machine));
+        let resources: BTreeSet<&String> = machines
+            .iter()
+            .flat_map(|machine| machine.drift.keys())
+            .collect();
+        Self {
+            group,
+            resources: resources.into_iter().cloned().collect(),
+            machines,
+        }
+    }
+
+    pub fn failed(&self) -> usize {
+        self.machines
+            .iter()
+            .filter(|machine| machine.status == DriftStatus::Failed)
+            .count()
+    }
+
+    pub fn print_table(&self) {
+        let mut table = Table::new();
+        table
+            .load_preset(comfy_table::presets::UTF8_FULL)
+            .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+            .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+            .set_header(vec!["machine", "status", "drift"]);
+
+        for machine in &self.machines {
+            let status = match machine.status {
+                DriftStatus::InSync => "in sync",
+                DriftStatus::Drifted => "DRIFTED",
+                DriftStatus::Failed => "FAILED",
+            };
+            let detail = match &machine.error {
+                Some(error) => error.clone(),
+                None => machine
+                    .drift
+                    .iter()
+                    .map(|(resource, change)| format!("{resource}: {change}"))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            };
+            table.add_row(vec![machine.machine.as_str(), status, &detail]);
+        }
+
+        println!("{table}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lusid_view::{Render, ViewTree};
+
+    use super::*;
+
+    fn leaf(label: &str) -> ViewTree {
+        ViewTree::Leaf {
+            view: label.render(),
+        }
+    }
+
+    // A check of a plan with two resources, of which only the second
+    // drifted.
+    fn check_updates() -> Vec<AppUpdate> {
+        vec![
+            AppUpdate::ResourceParams {
+                resource_params: ViewTree::Branch {
+                    view: ".".render(),
+                    children: vec![leaf("@core/apt"), leaf("@core/file")],
+                },
+                modules: vec![],
+            },
+            AppUpdate::ResourcesStart,
+            AppUpdate::ResourcesNode {
+                index: 1,
+                tree: leaf("Apt(nginx)"),
+            },
+            AppUpdate::ResourcesNode {
+                index: 2,
+                tree: leaf("File(/etc/motd)"),
+            },
+            AppUpdate::ResourcesComplete,
+            AppUpdate::ResourceStatesStart,
+            AppUpdate::ResourceStatesNodeComplete {
+                index: 1,
+                node: "installed".render(),
+            },
+            AppUpdate::ResourceStatesNodeComplete {
+                index: 2,
+                node: "differs".render(),
+            },
+            AppUpdate::ResourceStatesComplete,
+            AppUpdate::ResourceChangesStart,
+            AppUpdate::ResourceChangesNode {
+                index: 1,
+                node: None,
+            },
+            AppUpdate::ResourceChangesNode {
+                index: 2,
+                node: Some("write /etc/motd".render()),
+            },
+            AppUpdate::ResourceChangesComplete { has_changes: true },
+        ]
+    }
+
+    fn check(machine: &str, updates: &[AppUpdate]) -> MachineDrift {
+        let mut report = ApplyReport::default();
+        let mut recorder = DriftRecorder::default();
+        for update in updates {
+            report.record(update);
+            recorder.record(update);
+        }
+        MachineDrift::checked(machine.into(), &report, &recorder)
+    }
+
+    #[test]
+    fn pairs_changes_with_their_resources() {
+        let drift = check("web-1", &check_updates());
+        assert_eq!(drift.status, DriftStatus::Drifted);
+        assert_eq!(
+            drift.drift,
+            BTreeMap::from([("File(/etc/motd)".into(), "write /etc/motd".into())])
+        );
+    }
+
+    #[test]
+    fn keys_resources_by_their_plan_nodes() {
+        // The same resource, from one module used by two items. Each item's
+        // resources are a branch, its leaves appended after the plan's.
+        let resources = |index, id: &str| AppUpdate::ResourcesNode {
+            index,
+            tree: ViewTree::Branch {
+                view: id.render(),
+                children: vec![leaf("File(/etc/motd)")],
+            },
+        };
+        let state = |index| AppUpdate::ResourceStatesNodeComplete {
+            index,
+            node: "differs".render(),
+        };
+        let change = |index| AppUpdate::ResourceChangesNode {
+            index,
+            node: Some("write /etc/motd".render()),
+        };
+        let mut updates = check_updates();
+        updates.splice(
+            1..,
+            [
+                AppUpdate::ResourcesStart,
+                resources(1, "PlanItem(motd-a)"),
+                resources(2, "PlanItem(motd-b)"),
+                AppUpdate::ResourcesComplete,
+                AppUpdate::ResourceStatesStart,
+                state(3),
+                state(4),
+                AppUpdate::ResourceStatesComplete,
+                AppUpdate::ResourceChangesStart,
+                change(3),
+                change(4),
+                AppUpdate::ResourceChangesComplete { has_changes: true },
+            ],
+        );
+
+        let drift = check("web-1", &updates);
+        assert_eq!(
+            drift.drift.keys().collect::<Vec<_>>(),
+            [
+                "PlanItem(motd-a) > File(/etc/motd)",
+                "PlanItem(motd-b) > File(/etc/motd)",
+            ]
+        );
+    }
+
+    #[test]
+    fn report_collects_columns_across_machines() {
+        let mut in_sync = check_updates();
+        in_sync[11] = AppUpdate::ResourceChangesNode {
+            index: 2,
+            node: None,
+        };
+        let report = DriftReport::new(
+            "web".into(),
+            vec![
+                check("web-2", &in_sync),
+                MachineDrift::failed("web-3".into(), "failed to connect".into()),
+                check("web-1", &check_updates()),
+            ],
+        );
+        assert_eq!(report.resources, vec!["File(/etc/motd)"]);
+        let rows: Vec<(&str, DriftStatus)> = report
+            .machines
+            .iter()
+            .map(|machine| (machine.machine.as_str(), machine.status))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("web-1", DriftStatus::Drifted),
+                ("web-2", DriftStatus::InSync),
+                ("web-3", DriftStatus::Failed),
+            ]
+        );
+        assert_eq!(report.failed(), 1);
+    }
+}
@@ -0,0 +1,529 @@
+//! Generations: a numbered record of each successful `local apply`.
+//!
+//! Borrowed from Nix: every apply that runs to completion is recorded as
+//! generation `N + 1` for that machine, capturing what was applied — the
+//! project's git revision, the plan path, and the params (plus a hash of
+//! them, for comparing at a glance). `lusid rollback --to N` re-applies a
+//! recorded generation by checking its revision out into a scratch worktree
+//! and running `lusid-apply` against that, with the recorded params.
+//!
+//! Records live in `<data_dir>/generations/<machine_id>/<N>.json` (see
+//! [`Paths`]), one file per generation, so nothing ever rewrites an older
+//! record.
+//!
+//! Note(cc): lusid has no lockfile or state file yet, so a generation can't
+//! snapshot either — rolling back re-runs the *plan* of generation `N`, which
+//! re-fetches anything the plan doesn't pin. Once a state file exists, copy
+//! it in alongside the record.
+
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use comfy_table::Table;
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::{Paths, PathsError};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tokio::fs;
+
+#[derive(Error, Debug)]
+pub enum GenerationsError {
+    #[error(transparent)]
+    Paths(#[from] PathsError),
+
+    #[error("failed to read generations in {path}")]
+    ReadDir {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("failed to read generation {path}")]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("failed to parse generation {path}")]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error("failed to write generation {path}")]
+    Write {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("failed to serialize generation")]
+    Serialize(#[source] serde_json::Error),
+
+    #[error("generation {number} not found for machine {machine_id}")]
+    NotFound { machine_id: String, number: u32 },
+
+    #[error("generation {number} can't be re-applied: {reason}")]
+    NotReproducible { number: u32, reason: &'static str },
+
+    #[error("failed to prepare worktree {path}")]
+    Worktree {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error(transparent)]
+    Git(#[from] CommandError),
+}
+
+/// The project's git state when a generation was applied. `dirty` means the
+/// working tree had uncommitted changes, so `commit` alone doesn't describe
+/// what was applied.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Revision {
+    pub commit: String,
+    pub dirty: bool,
+}
+
+/// One recorded apply. `plan` is relative to the project root, so it still
+/// resolves inside a checkout of `revision`. `revision` is `None` when the
+/// project isn't a git repository.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Generation {
+    pub number: u32,
+    pub applied_at: u64,
+    pub plan: PathBuf,
+    pub revision: Option<Revision>,
+    pub params: Option<serde_json::Value>,
+    pub params_hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollback_of: Option<u32>,
+}
+
+/// What's needed to record a new generation; numbering and timestamp are
+/// filled in by [`Generations::record`].
+#[derive(Debug, Clone)]
+pub struct NewGeneration {
+    pub plan: PathBuf,
+    pub revision: Option<Revision>,
+    pub params: Option<serde_json::Value>,
+    pub rollback_of: Option<u32>,
+}
+
+/// The generation records of a single machine.
+#[derive(Debug, Clone)]
+pub struct Generations {
+    machine_id: String,
+    dir: PathBuf,
+}
+
+impl Generations {
+    pub fn open(machine_id: &str) -> Result<Self, GenerationsError> {
+        let paths = Paths::create()?;
+        let dir = paths.data_dir().join("generations").join(machine_id);
+        Ok(Self {
+            machine_id: machine_id.to_owned(),
+            dir,
+        })
+    }
+
+    /// All recorded generations, oldest first. A machine that has never been
+    /// applied has none.
+    pub async fn list(&self) -> Result<Vec<Generation>, GenerationsError> {
+        let mut entries = match fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(source) => {
+                return Err(GenerationsError::ReadDir {
+                    path: self.dir.clone(),
+                    source,
+                });
+            }
+        };
+
+        let mut generations = Vec::new();
+        while let Some(entry) =
+            entries
+                .next_entry()
+                .await
+                .map_err(|source| GenerationsError::ReadDir {
+                    path: self.dir.clone(),
+                    source,
+                })?
+        {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                generations.push(read_generation(&path).await?);
+            }
+        }
+        generations.sort_by_key(|generation| generation.number);
+        Ok(generations)
+    }
+
+    pub async fn get(&self, number: u32) -> Result<Generation, GenerationsError> {
+        let path = self.path(number);
+        if !fs::try_exists(&path).await.unwrap_or(false) {
+            return Err(GenerationsError::NotFound {
+                machine_id: self.machine_id.clone(),
+                number,
+            });
+        }
+        read_generation(&path).await
+    }
+
+    /// Record `new` as the next generation and return it.
+    pub async fn record(&self, new: NewGeneration) -> Result<Generation, GenerationsError> {
+        let existing = self.list().await?;
+        let NewGeneration {
+            plan,
+            revision,
+            params,
+            rollback_of,
+        } = new;
+        let generation = Generation {
+            number: next_number(&existing),
+            applied_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or(0),
+            plan,
+            revision,
+            params_hash: params_hash(params.as_ref()),
+            params,
+            rollback_of,
+        };
+
+        fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|source| GenerationsError::Write {
+                path: self.dir.clone(),
+                source,
+            })?;
+        let path = self.path(generation.number);
+        let json =
+            serde_json::to_string_pretty(&generation).map_err(GenerationsError::Serialize)?;
+        fs::write(&path, json)
+            .await
+            .map_err(|source| GenerationsError::Write { path, source })?;
+
+        Ok(generation)
+    }
+
+    fn path(&self, number: u32) -> PathBuf {
+        self.dir.join(format!("{number}.json"))
+    }
+}
+
+async fn read_generation(path: &Path) -> Result<Generation, GenerationsError> {
+    let json = fs::read_to_string(path)
+        .await
+        .map_err(|source| GenerationsError::Read {
+            path: path.to_owned(),
+            source,
+        })?;
+    serde_json::from_str(&json).map_err(|source| GenerationsError::Parse {
+        path: path.to_owned(),
+        source,
+    })
+}
+
+fn next_number(existing: &[Generation]) -> u32 {
+    existing
+        .iter()
+        .map(|generation| generation.number)
+        .max()
+        .map_or(1, |number| number + 1)
+}
+
+/// SHA-256 of the params' JSON, with object keys sorted so equal params
+/// always hash equal.
+pub fn params_hash(params: Option<&serde_json::Value>) -> String {
+    let mut json = String::new();
+    if let Some(params) = params {
+        write_canonical(&mut json, params);
+    }
+    let mut hash = String::with_capacity(64);
+    for byte in Sha256::digest(json.as_bytes()) {
+        let _ = write!(hash, "{byte:02x}");
+    }
+    hash
+}
+
+fn write_canonical(out: &mut String, value: &serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            out.push('{');
+            for (index, (key, value)) in entries.into_iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                out.push_str(&serde_json::Value::from(key.as_str()).to_string());
+                out.push(':');
+                write_canonical(out, value);
+            }
+            out.push('}');
+        }
+        serde_json::Value::Array(items) => {
+            out.push('[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_canonical(out, item);
+            }
+            out.push(']');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+/// The current git revision of the repository containing `root`, or `None`
+/// if `root` isn't in a git repository.
+pub async fn current_revision(root: &Path) -> Option<Revision> {
+    let commit = git(root).args(["rev-parse", "HEAD"]).run().await.ok()?;
+    let status = git(root).args(["status", "--porcelain"]).run().await.ok()?;
+    Some(Revision {
+        commit: String::from_utf8_lossy(&commit).trim().to_owned(),
+        dirty: !status.is_empty(),
+    })
+}
+
+/// A detached scratch checkout of an earlier revision (a generation's, or
+/// any commit for `plan diff`), at `<cache_dir>/worktrees/<name>`. Call
+/// [`Worktree::remove`] when done.
+pub struct Worktree {
+    repo_root: PathBuf,
+    path: PathBuf,
+    project_root: PathBuf,
+}
+
+impl Worktree {
+    /// Check out `generation`'s revision, refusing generations that weren't
+    /// applied from a clean commit.
+    pub async fn checkout(
+        root: &Path,
+        machine_id: &str,
+        generation: &Generation,
+    ) -> Result<Self, GenerationsError> {
+        let revision = match &generation.revision {
+            None => {
+                return Err(GenerationsError::NotReproducible {
+                    number: generation.number,
+                    reason: "the project wasn't a git repository when it was applied",
+                });
+            }
+            Some(Revision { dirty: true, .. }) => {
+                return Err(GenerationsError::NotReproducible {
+                    number: generation.number,
+                    reason: "it was applied from uncommitted changes",
+                });
+            }
+            Some(revision) => revision,
+        };
+        let name = format!("generation-{machine_id}-{}", generation.number);
+        Self::checkout_commit(root, &name, &revision.commit).await
+    }
+
+    /// Check out `commit` (already resolved, see [`resolve_commit`]) under
+    /// the scratch name `name`.
+    pub async fn checkout_commit(
+        root: &Path,
+        name: &str,
+        commit: &str,
+    ) -> Result<Self, GenerationsError> {
+        let repo_root = git(root)
+            .args(["rev-parse", "--show-toplevel"])
+            .run()
+            .await?;
+        let repo_root = PathBuf::from(String::from_utf8_lossy(&repo_root).trim());
+        let prefix = git(root).args(["rev-parse", "--show-prefix"]).run().await?;
+        let prefix = PathBuf::from(String::from_utf8_lossy(&prefix).trim());
+
+        let paths = Paths::create()?;
+        let path = paths.cache_dir().join("worktrees").join(name);
+        if fs::try_exists(&path).await.unwrap_or(false) {
+            // Left over from an interrupted run.
+            let _ = git(&repo_root)
+                .args(["worktree", "remove", "--force"])
+                .arg(&path)
+                .run()
+                .await;
+            fs::remove_dir_all(&path)
+                .await
+                .or_else(|error| match error.kind() {
+                    std::io::ErrorKind::NotFound => Ok(()),
+                    _ => Err(error),
+                })
+                .map_err(|source| GenerationsError::Worktree {
+                    path: path.clone(),
+                    source,
+                })?;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .map_err(|source| GenerationsError::Worktree {
+                    path: path.clone(),
+                    source,
+                })?;
+        }
+
+        git(&repo_root)
+            .args(["worktree", "add", "--detach"])
+            .arg(&path)
+            .arg(commit)
+            .run()
+            .await?;
+
+        Ok(Self {
+            repo_root,
+            project_root: path.join(prefix),
+            path,
+        })
+    }
+
+    /// The project root inside the checkout; resolve the generation's `plan`
+    /// against this.
+    pub fn project_root(&self) -> &Path {
+        &self.project_root
+    }
+
+    pub async fn remove(self) -> Result<(), GenerationsError> {
+        git(&self.repo_root)
+            .args(["worktree", "remove", "--force"])
+            .arg(&self.path)
+            .run()
+            .await?;
+        Ok(())
+    }
+}
+
+/// Resolve a git ref (branch, tag, `HEAD~2`, …) in the repository
+/// containing `root` to a full commit hash.
+pub async fn resolve_commit(root: &Path, reference: &str) -> Result<String, GenerationsError> {
+    let commit = git(root)
+        .args(["rev-parse", "--verify"])
+        .arg(format!("{reference}^{{commit}}"))
+        .run()
+        .await?;
+    Ok(String::from_utf8_lossy(&commit).trim().to_owned())
+}
+
+fn git(dir: &Path) -> Command {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(dir);
+    command
+}
+
+pub fn print_generations(generations: &[Generation]) {
+    let mut table = Table::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL)
+        .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .set_header(vec!["#", "applied (UTC)", "revision", "params", "plan", ""]);
+
+    for generation in generations {
+        let Generation {
+            number,
+            applied_at,
+            plan,
+            revision,
+            params: _,
+            params_hash,
+            rollback_of,
+        } = generation;
+        let revision = match revision {
+            None => "-".to_owned(),
+            Some(Revision { commit, dirty }) => {
+                let short = &commit[..commit.len().min(12)];
+                if *dirty {
+                    format!("{short} (dirty)")
+                } else {
+                    short.to_owned()
+                }
+            }
+        };
+        let note = rollback_of
+            .map(|number| format!("rollback to {number}"))
+            .unwrap_or_default();
+        table.add_row(vec![
+            number.to_string(),
+            format_utc(*applied_at),
+            revision,
+            params_hash[..params_hash.len().min(12)].to_owned(),
+            plan.display().to_string(),
+            note,
+        ]);
+    }
+
+    println!("{table}");
+}
+
+/// `YYYY-MM-DD HH:MM:SS` for a unix timestamp, without pulling in a date
+/// crate. Days-to-civil conversion from Howard Hinnant's `civil_from_days`.
+pub fn format_utc(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    let (hour, minute, second) = (rem / 3600, (rem % 3600) / 60, rem % 60);
+
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02} {hour:02}:{minute:02}:{second:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn generation(number: u32) -> Generation {
+        Generation {
+            number,
+            applied_at: 0,
+            plan: PathBuf::from("plan.lusid"),
+            revision: None,
+            params: None,
+            params_hash: params_hash(None),
+            rollback_of: None,
+        }
+    }
+
+    #[test]
+    fn numbers_follow_the_highest_generation() {
+        assert_eq!(next_number(&[]), 1);
+        assert_eq!(next_number(&[generation(1), generation(4)]), 5);
+    }
+
+    #[test]
+    fn params_hash_ignores_key_order() {
+        let a = json!({ "user": "me", "shell": "zsh" });
+        let b = json!({ "shell": "zsh", "user": "me" });
+        assert_eq!(params_hash(Some(&a)), params_hash(Some(&b)));
+        assert_ne!(params_hash(Some(&a)), params_hash(None));
+    }
+
+    #[test]
+    fn formats_unix_timestamps_as_utc() {
+        assert_eq!(format_utc(0), "1970-01-01 00:00:00");
+        assert_eq!(format_utc(951_782_400), "2000-02-29 00:00:00");
+        assert_eq!(format_utc(1_790_000_000), "2026-09-21 14:13:20");
+    }
+}
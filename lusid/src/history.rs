@@ -0,0 +1,479 @@
+//! `lusid history`: the [run logs](crate::logs) in the TUI. Every kept run is
+//! listed newest first, one row per machine it applied, with its outcome and
+//! a side pane summarizing its [`ApplyReport`]. From there a run can be
+//! replayed in the apply [`tui`], or its report diffed against another's.
+//!
+//! A replay feeds the run's recorded update stream and stderr into the same
+//! TUI an apply uses, all at once: it opens on the run as it ended rather
+//! than playing it back in real time, and it never notifies.
+
+use std::collections::HashSet;
+
+use crossterm::event::{Event, KeyEvent};
+use lusid_apply_stdio::AppUpdate;
+use ratatui::{
+    Frame,
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+};
+use thiserror::Error;
+
+use crate::keys::{Action, KeyMap};
+use crate::logs::{LogsError, list_runs, read_log, unstamp_update};
+use crate::report::ApplyReport;
+use crate::tui::{TerminalSession, TuiError, TuiOptions, is_plain_key, read_events, tui};
+
+#[derive(Error, Debug)]
+pub enum HistoryError {
+    #[error(transparent)]
+    Logs(#[from] LogsError),
+
+    #[error(transparent)]
+    Tui(#[from] TuiError),
+}
+
+/// One machine's apply in a past run.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub run_id: String,
+    pub machine_id: String,
+    pub report: ApplyReport,
+}
+
+/// Browse the kept runs until the user quits.
+pub async fn history(options: TuiOptions) -> Result<(), HistoryError> {
+    let entries = load_history().await?;
+    if entries.is_empty() {
+        eprintln!("No applies logged yet.");
+        return Ok(());
+    }
+
+    let mut browser = Browser::new(entries, options.keys.clone());
+    while let Some(index) = browser.run().await? {
+        let entry = &browser.entries[index];
+        replay(&entry.run_id, &entry.machine_id, options.clone()).await?;
+    }
+    Ok(())
+}
+
+/// Every machine's apply in every kept run, newest run first.
+pub async fn load_history() -> Result<Vec<HistoryEntry>, LogsError> {
+    let mut entries = Vec::new();
+    for run in list_runs().await?.into_iter().rev() {
+        for machine_id in run.machines {
+            let updates = read_updates(&run.id, &machine_id).await?;
+            entries.push(HistoryEntry {
+                report: fold_report(&updates),
+                run_id: run.id.clone(),
+                machine_id,
+            });
+        }
+    }
+    Ok(entries)
+}
+
+/// A machine's recorded `AppUpdate` lines in a run. A record that doesn't
+/// parse, like one cut short by a crash mid-write, is skipped.
+async fn read_updates(run_id: &str, machine_id: &str) -> Result<Vec<String>, LogsError> {
+    let log = read_log(run_id, Some(machine_id), true).await?;
+    Ok(log
+        .lines()
+        .filter_map(|record| unstamp_update(record).ok())
+        .collect())
+}
+
+fn fold_report(updates: &[String]) -> ApplyReport {
+    let mut report = ApplyReport::default();
+    for line in updates {
+        if let Ok(update) = serde_json::from_str::<AppUpdate>(line) {
+            report.record(&update);
+        }
+    }
+    report
+}
+
+async fn replay(run_id: &str, machine_id: &str, options: TuiOptions) -> Result<(), HistoryError> {
+    let stdout = read_updates(run_id, machine_id).await?.join("\n");
+    let stderr = read_log(run_id, Some(machine_id), false).await?;
+    let options = TuiOptions {
+        notify: Vec::new(),
+        ..options
+    };
+    // The apply is long over: there's nothing to wait for or to control.
+    let wait = Box::pin(async { Ok::<_, TuiError>(()) });
+    tui(
+        stdout.as_bytes(),
+        stderr.as_bytes(),
+        tokio::io::sink(),
+        wait,
+        None,
+        options,
+    )
+    .await?;
+    Ok(())
+}
+
+/// What changed from one run's report to another's.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReportDiff {
+    /// Changes the new run planned that the old one didn't.
+    pub changes_added: Vec<String>,
+    /// Changes the old run planned that the new one didn't.
+    pub changes_removed: Vec<String>,
+    pub failures_added: Vec<String>,
+    pub failures_removed: Vec<String>,
+    /// The old and new runs' pipeline errors, if they differ.
+    pub error: Option<(Option<String>, Option<String>)>,
+}
+
+impl ReportDiff {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+pub fn diff_reports(old: &ApplyReport, new: &ApplyReport) -> ReportDiff {
+    ReportDiff {
+        changes_added: missing(&new.changes, &old.changes),
+        changes_removed: missing(&old.changes, &new.changes),
+        failures_added: missing(&new.failed_operations, &old.failed_operations),
+        failures_removed: missing(&old.failed_operations, &new.failed_operations),
+        error: (old.error != new.error).then(|| (old.error.clone(), new.error.clone())),
+    }
+}
+
+/// The items of `items` not in `other`, in order.
+fn missing(items: &[String], other: &[String]) -> Vec<String> {
+    let other: HashSet<&String> = other.iter().collect();
+    items
+        .iter()
+        .filter(|item| !other.contains(item))
+        .cloned()
+        .collect()
+}
+
+struct Browser {
+    entries: Vec<HistoryEntry>,
+    keys: KeyMap,
+    list: ListState,
+    marked: Option<usize>,
+    /// The older and newer entries whose reports the side pane compares,
+    /// until the selection moves.
+    diff: Option<(usize, usize)>,
+}
+
+impl Browser {
+    fn new(entries: Vec<HistoryEntry>, keys: KeyMap) -> Self {
+        Self {
+            entries,
+            keys,
+            list: ListState::default().with_selected(Some(0)),
+            marked: None,
+            diff: None,
+        }
+    }
+
+    fn selected(&self) -> usize {
+        self.list.selected().unwrap_or(0)
+    }
+
+    /// Show the list until the user quits, or picks an entry to replay.
+    async fn run(&mut self) -> Result<Option<usize>, TuiError> {
+        let mut terminal = TerminalSession::init();
+        let mut events = read_events();
+
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            let Some(event) = events.recv().await else {
+                return Ok(None);
+            };
+            let Event::Key(KeyEvent {
+                code, modifiers, ..
+            }) = event
+            else {
+                continue;
+            };
+            if !is_plain_key(code, modifiers) {
+                continue;
+            }
+            let Some(action) = self.keys.action(code) else {
+                continue;
+            };
+
+            match action {
+                Action::Quit => return Ok(None),
+                Action::Toggle => return Ok(Some(self.selected())),
+                action => self.handle_action(action),
+            }
+        }
+    }
+
+    fn handle_action(&mut self, action: Action) {
+        let selected = self.selected();
+        let last = self.entries.len() - 1;
+        match action {
+            Action::Up => self.select(selected.saturating_sub(1)),
+            Action::Down => self.select((selected + 1).min(last)),
+            Action::Top => self.select(0),
+            Action::Bottom => self.select(last),
+            Action::Mark => {
+                self.marked = (self.marked != Some(selected)).then_some(selected);
+            }
+            Action::Diff => {
+                // Entries are newest first, so the older run is further down.
+                self.diff = self
+                    .marked
+                    .filter(|&marked| marked != selected)
+                    .map(|marked| (marked.max(selected), marked.min(selected)));
+            }
+            _ => {}
+        }
+    }
+
+    fn select(&mut self, index: usize) {
+        self.list.select(Some(index));
+        self.diff = None;
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let outer = Block::bordered().title_top("lusid history");
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(5), Constraint::Length(1)].as_ref())
+            .split(outer.inner(frame.area()));
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(45), Constraint::Percentage(55)].as_ref())
+            .split(layout[0]);
+
+        frame.render_widget(outer, frame.area());
+        self.draw_runs(frame, panes[0]);
+        self.draw_details(frame, panes[1]);
+        self.draw_help(frame, layout[1]);
+    }
+
+    fn draw_runs(&mut self, frame: &mut Frame, area: Rect) {
+        let items = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                let marker = if self.marked == Some(index) {
+                    "* "
+                } else {
+                    "  "
+                };
+                let (status, color) = if entry.report.succeeded() {
+                    ("ok", Color::Green)
+                } else {
+                    ("failed", Color::Red)
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(marker, Style::default().fg(Color::Yellow)),
+                    Span::raw(format!("{}  {}  ", entry.run_id, entry.machine_id)),
+                    Span::styled(status, Style::default().fg(color)),
+                    Span::styled(
+                        format!("  {} change(s)", entry.report.changes.len()),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ]))
+            })
+            .collect::<Vec<_>>();
+
+        let widget = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title("runs"))
+            .highlight_style(
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            );
+
+        frame.render_stateful_widget(widget, area, &mut self.list);
+    }
+
+    fn draw_details(&self, frame: &mut Frame, area: Rect) {
+        let (title, lines) = match self.diff {
+            Some((old, new)) => {
+                let (old, new) = (&self.entries[old], &self.entries[new]);
+                (
+                    format!("{} → {}", old.run_id, new.run_id),
+                    diff_lines(&diff_reports(&old.report, &new.report)),
+                )
+            }
+            None => {
+                let entry = &self.entries[self.selected()];
+                (
+                    format!("{} {}", entry.run_id, entry.machine_id),
+                    report_lines(&entry.report),
+                )
+            }
+        };
+
+        let widget = Paragraph::new(Text::from(lines))
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .wrap(Wrap { trim: false });
+        frame.render_widget(widget, area);
+    }
+
+    fn draw_help(&self, frame: &mut Frame, area: Rect) {
+        let key = |action| self.keys.hint(action);
+        let hints = format!(
+            "{}/{} move  {} replay  {} mark  {} diff with marked  {} quit",
+            key(Action::Up),
+            key(Action::Down),
+            key(Action::Toggle),
+            key(Action::Mark),
+            key(Action::Diff),
+            key(Action::Quit),
+        );
+
+        let widget = Paragraph::new(Line::from(Span::styled(
+            hints,
+            Style::default().fg(Color::DarkGray),
+        )))
+        .alignment(Alignment::Left);
+        frame.render_widget(widget, area);
+    }
+}
+
+fn heading(text: String) -> Line<'static> {
+    Line::from(Span::styled(
+        text,
+        Style::default().add_modifier(Modifier::BOLD),
+    ))
+}
+
+fn item(prefix: &'static str, text: &str, color: Color) -> Line<'static> {
+    Line::from(Span::styled(
+        format!("{prefix}{text}"),
+        Style::default().fg(color),
+    ))
+}
+
+fn report_lines(report: &ApplyReport) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+
+    if let Some(error) = &report.error {
+        lines.push(item("error: ", error, Color::Red));
+    }
+
+    if !report.modules.is_empty() {
+        lines.push(heading("modules".to_owned()));
+        for module in &report.modules {
+            let name = module.name.as_deref().unwrap_or(&module.plan);
+            let module = match &module.version {
+                Some(version) => format!("{name} {version}"),
+                None => name.to_owned(),
+            };
+            lines.push(item("  ", &module, Color::Reset));
+        }
+    }
+
+    lines.push(heading(format!("{} change(s)", report.changes.len())));
+    for change in &report.changes {
+        lines.push(item("  ", change, Color::Reset));
+    }
+
+    lines.push(heading(format!(
+        "{} operation(s), {} failed",
+        report.operations,
+        report.failed_operations.len()
+    )));
+    for failure in &report.failed_operations {
+        lines.push(item("  ", failure, Color::Red));
+    }
+
+    if !report.warnings.is_empty() {
+        lines.push(heading(format!("{} warning(s)", report.warnings.len())));
+        for warning in &report.warnings {
+            lines.push(item("  ", warning, Color::Yellow));
+        }
+    }
+
+    lines
+}
+
+fn diff_lines(diff: &ReportDiff) -> Vec<Line<'static>> {
+    if diff.is_empty() {
+        return vec![Line::from("no differences")];
+    }
+
+    let mut lines = Vec::new();
+
+    if let Some((old, new)) = &diff.error {
+        let old = old.as_deref().unwrap_or("none");
+        let new = new.as_deref().unwrap_or("none");
+        lines.push(heading("error".to_owned()));
+        lines.push(item("- ", old, Color::Red));
+        lines.push(item("+ ", new, Color::Green));
+    }
+
+    if !diff.changes_added.is_empty() || !diff.changes_removed.is_empty() {
+        lines.push(heading("changes".to_owned()));
+        for change in &diff.changes_removed {
+            lines.push(item("- ", change, Color::Red));
+        }
+        for change in &diff.changes_added {
+            lines.push(item("+ ", change, Color::Green));
+        }
+    }
+
+    if !diff.failures_added.is_empty() || !diff.failures_removed.is_empty() {
+        lines.push(heading("failed operations".to_owned()));
+        for failure in &diff.failures_removed {
+            lines.push(item("- ", failure, Color::Red));
+        }
+        for failure in &diff.failures_added {
+            lines.push(item("+ ", failure, Color::Green));
+        }
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply_report(changes: &[&str], failed: &[&str], error: Option<&str>) -> ApplyReport {
+        let mut report = ApplyReport::default();
+        report.changes = changes.iter().map(|change| change.to_string()).collect();
+        report.failed_operations = failed.iter().map(|failure| failure.to_string()).collect();
+        report.error = error.map(str::to_owned);
+        report
+    }
+
+    #[test]
+    fn diffs_changes_failures_and_errors() {
+        let old = apply_report(
+            &["Apt::Install(git)", "File::Write(/etc/motd)"],
+            &["Apt::Install(git): exit 100"],
+            None,
+        );
+        let new = apply_report(
+            &["File::Write(/etc/motd)", "Service::Restart(nginx)"],
+            &[],
+            Some("plan failed"),
+        );
+
+        assert_eq!(
+            diff_reports(&old, &new),
+            ReportDiff {
+                changes_added: vec!["Service::Restart(nginx)".to_owned()],
+                changes_removed: vec!["Apt::Install(git)".to_owned()],
+                failures_added: vec![],
+                failures_removed: vec!["Apt::Install(git): exit 100".to_owned()],
+                error: Some((None, Some("plan failed".to_owned()))),
+            }
+        );
+    }
+
+    #[test]
+    fn identical_reports_have_no_diff() {
+        let old = apply_report(&["Apt::Install(git)"], &[], None);
+        assert!(diff_reports(&old, &old).is_empty());
+    }
+}
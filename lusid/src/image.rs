@@ -0,0 +1,182 @@
+//! Image targets: apply a machine's plan to the root filesystem of a mounted
+//! image, like an SD card image for a Raspberry Pi, without booting it.
+//!
+//! As for [containers](crate::container), `lusid-apply` and the plan are
+//! copied into a [staging directory](crate::staging) in the image, and
+//! `lusid-apply` is run there as root, with `--no-sudo`. It runs in a
+//! `systemd-nspawn` container of the image where nspawn is installed, which
+//! mounts `/proc`, `/sys` and `/dev` and sets up DNS itself, or else under
+//! `chroot`, with those mounted from the host for the length of the apply.
+//! The staging directory is removed afterwards.
+//!
+//! Writing to the image's root, mounting and entering it all need root, so
+//! each step runs with `sudo`.
+//
+// Note(cc): an image for another architecture, like an arm64 Raspberry Pi
+// image on an x86_64 host, needs qemu-user-static registered with binfmt_misc
+// on the host to run anything inside it. The machine's `arch` picks the
+// `lusid-apply` binary, so it's the image's architecture, not the host's.
+//
+// Note(cc): nothing boots in the image, so there's no systemd to talk to:
+// `@core/systemd` with `active = true` fails, where `enabled` alone works.
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use lusid_cmd::{Command, CommandError};
+use which::which;
+
+use crate::staging::StagingDir;
+
+/// Where the staging directory goes in an image, as for
+/// [`CONTAINER_STAGING_DIR`](crate::container::CONTAINER_STAGING_DIR).
+pub const IMAGE_STAGING_DIR: &str = "/var/tmp/lusid";
+
+/// The host filesystems a chroot gets, with how each is mounted.
+const CHROOT_MOUNTS: &[(&str, &[&str])] = &[
+    ("proc", &["-t", "proc", "proc"]),
+    ("sys", &["-t", "sysfs", "sysfs"]),
+    ("dev", &["--rbind", "/dev"]),
+];
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageRunner {
+    Nspawn,
+    Chroot,
+}
+
+impl ImageRunner {
+    /// `systemd-nspawn` if it's on this host's `PATH`, else `chroot`.
+    pub fn detect() -> Self {
+        match which("systemd-nspawn") {
+            Ok(_) => ImageRunner::Nspawn,
+            Err(_) => ImageRunner::Chroot,
+        }
+    }
+}
+
+/// The root filesystem of a mounted image.
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub runner: ImageRunner,
+    pub root: PathBuf,
+}
+
+impl Image {
+    /// A command run in the image as root. Stdin, stdout and stderr are the
+    /// command's own (`--pipe`), so the TUI can send `lusid-apply` control
+    /// messages and read its updates.
+    pub fn exec<I, S>(&self, program: &str, args: I) -> Command
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let mut command = match self.runner {
+            ImageRunner::Nspawn => {
+                let mut command = Command::new("systemd-nspawn");
+                command
+                    .args(["--quiet", "--pipe", "--directory"])
+                    .arg(&self.root)
+                    .arg("--");
+                command
+            }
+            ImageRunner::Chroot => {
+                let mut command = Command::new("chroot");
+                command.arg(&self.root);
+                command
+            }
+        };
+        command.arg(program).args(args);
+        command.sudo()
+    }
+
+    /// Mount what the runner doesn't provide itself: the host's `/proc`,
+    /// `/sys` and `/dev` for a chroot. Nothing for nspawn. If one fails,
+    /// those already mounted are unmounted again.
+    pub async fn mount(&self) -> Result<(), CommandError> {
+        if self.runner != ImageRunner::Chroot {
+            return Ok(());
+        }
+        for (index, (target, source)) in CHROOT_MOUNTS.iter().enumerate() {
+            let mut mount = Command::new("mount");
+            mount.args(*source).arg(self.path(target));
+            if let Err(error) = mount.sudo().run().await {
+                // The mount's error says more than any unmount's would.
+                let _ = self.unmount_each(&CHROOT_MOUNTS[..index]).await;
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    /// Undo [`Image::mount`], so the image can be unmounted from the host.
+    pub async fn unmount(&self) -> Result<(), CommandError> {
+        if self.runner != ImageRunner::Chroot {
+            return Ok(());
+        }
+        self.unmount_each(CHROOT_MOUNTS).await
+    }
+
+    /// Unmount each of `mounts`, last first. One failing doesn't stop the
+    /// rest; the first error is returned.
+    async fn unmount_each(&self, mounts: &[(&str, &[&str])]) -> Result<(), CommandError> {
+        let mut result = Ok(());
+        for (target, _) in mounts.iter().rev() {
+            let mut umount = Command::new("umount");
+            umount.arg("--recursive").arg(self.path(target));
+            let unmounted = umount.sudo().run().await;
+            if result.is_ok() {
+                result = unmounted.map(drop);
+            }
+        }
+        result
+    }
+
+    /// Copy the local file `local` to `remote` in the image, creating its
+    /// parent directories.
+    pub async fn copy_file(&self, local: &Path, remote: &str) -> Result<(), CommandError> {
+        let remote = self.path(remote);
+        if let Some(parent) = remote.parent() {
+            mkdir(parent).await?;
+        }
+        copy(local, &remote).await
+    }
+
+    /// Copy the contents of the local directory `local` into `remote` in the
+    /// image, creating it.
+    pub async fn copy_dir(&self, local: &Path, remote: &str) -> Result<(), CommandError> {
+        let remote = self.path(remote);
+        mkdir(&remote).await?;
+        // As for containers, `<dir>/.` copies what's in the directory.
+        copy(&local.join("."), &remote).await
+    }
+
+    /// Remove the staging directory from the image. A no-op if it doesn't
+    /// exist.
+    pub async fn clean(&self, staging: &StagingDir) -> Result<(), CommandError> {
+        let mut rm = Command::new("rm");
+        rm.args(["-rf", "--"]).arg(self.path(staging.root()));
+        rm.sudo().run().await?;
+        Ok(())
+    }
+
+    /// `path` in the image, as a path on this host.
+    fn path(&self, path: &str) -> PathBuf {
+        self.root.join(path.trim_start_matches('/'))
+    }
+}
+
+async fn mkdir(path: &Path) -> Result<(), CommandError> {
+    let mut mkdir = Command::new("mkdir");
+    mkdir.arg("-p").arg(path);
+    mkdir.sudo().run().await?;
+    Ok(())
+}
+
+async fn copy(local: &Path, remote: &Path) -> Result<(), CommandError> {
+    let mut cp = Command::new("cp");
+    cp.args(["-R", "--"]).arg(local).arg(remote);
+    cp.sudo().run().await?;
+    Ok(())
+}
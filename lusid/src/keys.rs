@@ -0,0 +1,350 @@
+//! TUI key bindings, remappable from the `[keys]` section of `lusid.toml`.
+//!
+//! Each [`Action`] is bound to one or more keys. An action listed in
+//! `[keys]` has its default keys replaced, not extended; actions not listed
+//! keep theirs. A key bound by the user is taken away from whichever action
+//! had it by default, so remapping `j` doesn't also leave it moving down.
+//!
+//! ```toml
+//! [keys]
+//! down = ["n", "down"]
+//! up = ["e", "up"]
+//! toggle-stderr = "s"
+//! ```
+//!
+//! Keys are written as a single character (`"q"`, `"G"`) or a named key:
+//! `up`, `down`, `left`, `right`, `enter`, `esc`, `space`, `tab`, `backtab`,
+//! `pageup`, `pagedown`, `home`, `end`, `backspace`, `delete`, `f1`–`f12`.
+//!
+//! Note(cc): the TUI has no search yet, so there's no action to bind for it.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crossterm::event::KeyCode;
+use serde::Deserialize;
+use thiserror::Error;
+
+/// Something the user can do in the TUI. Which actions apply depends on the
+/// page: stage and tree actions on the main page, paging and jumps on the
+/// stderr page; `up`/`down` move the selection or scroll, respectively.
+/// `filter-level` cycles the stderr page between all lines, warnings and
+/// errors, and errors only; `log-more` / `log-less` change `lusid-apply`'s
+/// log level while it runs, and `pause` / `cancel` pause (or resume) and
+/// cancel it before its next operation, on either page. On the history page
+/// (`lusid history`), `toggle` replays the selected run, `mark` marks it and
+/// `diff` compares the marked run's report with the selected one's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    Quit,
+    ToggleStderr,
+    ToggleFollow,
+    PrevStage,
+    NextStage,
+    Up,
+    Down,
+    Toggle,
+    PageUp,
+    PageDown,
+    Top,
+    Bottom,
+    FilterLevel,
+    LogMore,
+    LogLess,
+    Pause,
+    Cancel,
+    Mark,
+    Diff,
+}
+
+impl Action {
+    const ALL: [Action; 19] = [
+        Action::Quit,
+        Action::ToggleStderr,
+        Action::ToggleFollow,
+        Action::PrevStage,
+        Action::NextStage,
+        Action::Up,
+        Action::Down,
+        Action::Toggle,
+        Action::PageUp,
+        Action::PageDown,
+        Action::Top,
+        Action::Bottom,
+        Action::FilterLevel,
+        Action::LogMore,
+        Action::LogLess,
+        Action::Pause,
+        Action::Cancel,
+        Action::Mark,
+        Action::Diff,
+    ];
+
+    /// What each of [`Action::ALL`] is called in `[keys]`.
+    pub const NAMES: [&'static str; 19] = [
+        "quit",
+        "toggle-stderr",
+        "toggle-follow",
+        "prev-stage",
+        "next-stage",
+        "up",
+        "down",
+        "toggle",
+        "page-up",
+        "page-down",
+        "top",
+        "bottom",
+        "filter-level",
+        "log-more",
+        "log-less",
+        "pause",
+        "cancel",
+        "mark",
+        "diff",
+    ];
+
+    fn default_keys(self) -> &'static [KeyCode] {
+        use KeyCode::*;
+        match self {
+            Action::Quit => &[Char('q'), Esc],
+            Action::ToggleStderr => &[Char('e')],
+            Action::ToggleFollow => &[Char('f')],
+            Action::PrevStage => &[Left, BackTab],
+            Action::NextStage => &[Right, Tab],
+            Action::Up => &[Up, Char('k')],
+            Action::Down => &[Down, Char('j')],
+            Action::Toggle => &[Enter, Char(' ')],
+            Action::PageUp => &[PageUp],
+            Action::PageDown => &[PageDown],
+            Action::Top => &[Char('g'), Home],
+            Action::Bottom => &[Char('G'), End],
+            Action::FilterLevel => &[Char('l')],
+            Action::LogMore => &[Char('v')],
+            Action::LogLess => &[Char('V')],
+            Action::Pause => &[Char('p')],
+            Action::Cancel => &[Char('x')],
+            Action::Mark => &[Char('m')],
+            Action::Diff => &[Char('d')],
+        }
+    }
+}
+
+/// One key or a list of keys, as written in `[keys]`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum KeyBindings {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyBindings {
+    fn names(&self) -> &[String] {
+        match self {
+            KeyBindings::One(name) => std::slice::from_ref(name),
+            KeyBindings::Many(names) => names,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum KeyMapError {
+    #[error("unknown key {key:?} in [keys]")]
+    UnknownKey { key: String },
+
+    #[error("key {key:?} is bound to both {first:?} and {second:?} in [keys]")]
+    Conflict {
+        key: String,
+        first: Action,
+        second: Action,
+    },
+}
+
+/// Resolved bindings: which action each key triggers, and the keys of each
+/// action in order (the first is the one shown in the help line).
+#[derive(Debug, Clone)]
+pub struct KeyMap {
+    actions: HashMap<KeyCode, Action>,
+    keys: BTreeMap<Action, Vec<KeyCode>>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self::new(&BTreeMap::new()).expect("default key bindings are valid")
+    }
+}
+
+impl KeyMap {
+    pub fn new(overrides: &BTreeMap<Action, KeyBindings>) -> Result<Self, KeyMapError> {
+        let mut actions = HashMap::new();
+        let mut keys = BTreeMap::new();
+
+        for (&action, bindings) in overrides {
+            let mut codes = Vec::new();
+            for name in bindings.names() {
+                let code = parse_key(name)?;
+                match actions.insert(code, action) {
+                    Some(first) if first != action => {
+                        return Err(KeyMapError::Conflict {
+                            key: name.clone(),
+                            first,
+                            second: action,
+                        });
+                    }
+                    _ => {}
+                }
+                codes.push(code);
+            }
+            keys.insert(action, codes);
+        }
+
+        for action in Action::ALL {
+            if keys.contains_key(&action) {
+                continue;
+            }
+            let codes: Vec<KeyCode> = action
+                .default_keys()
+                .iter()
+                .copied()
+                .filter(|code| !actions.contains_key(code))
+                .collect();
+            for &code in &codes {
+                actions.insert(code, action);
+            }
+            keys.insert(action, codes);
+        }
+
+        Ok(Self { actions, keys })
+    }
+
+    pub fn action(&self, code: KeyCode) -> Option<Action> {
+        self.actions.get(&code).copied()
+    }
+
+    /// Display name of the action's first key, for the help line. `-` when
+    /// the action has been left without keys.
+    pub fn hint(&self, action: Action) -> String {
+        self.keys
+            .get(&action)
+            .and_then(|codes| codes.first())
+            .map(|&code| key_name(code))
+            .unwrap_or_else(|| "-".to_owned())
+    }
+}
+
+fn parse_key(name: &str) -> Result<KeyCode, KeyMapError> {
+    let mut chars = name.chars();
+    if let (Some(char), None) = (chars.next(), chars.next()) {
+        return Ok(KeyCode::Char(char));
+    }
+
+    let code = match name.to_ascii_lowercase().as_str() {
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "enter" | "return" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "space" => KeyCode::Char(' '),
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "pageup" | "pgup" => KeyCode::PageUp,
+        "pagedown" | "pgdn" => KeyCode::PageDown,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "backspace" => KeyCode::Backspace,
+        "delete" | "del" => KeyCode::Delete,
+        other => match other.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+            Some(n @ 1..=12) => KeyCode::F(n),
+            _ => {
+                return Err(KeyMapError::UnknownKey {
+                    key: name.to_owned(),
+                });
+            }
+        },
+    };
+    Ok(code)
+}
+
+fn key_name(code: KeyCode) -> String {
+    match code {
+        KeyCode::Char(' ') => "Space".to_owned(),
+        KeyCode::Char(char) => char.to_string(),
+        KeyCode::PageUp => "PgUp".to_owned(),
+        KeyCode::PageDown => "PgDn".to_owned(),
+        KeyCode::F(n) => format!("F{n}"),
+        other => format!("{other:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overrides(toml: &str) -> BTreeMap<Action, KeyBindings> {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn defaults_match_the_builtin_bindings() {
+        let keys = KeyMap::default();
+        assert_eq!(keys.action(KeyCode::Char('q')), Some(Action::Quit));
+        assert_eq!(keys.action(KeyCode::Tab), Some(Action::NextStage));
+        assert_eq!(keys.action(KeyCode::Char('G')), Some(Action::Bottom));
+        assert_eq!(keys.hint(Action::PrevStage), "Left");
+        assert_eq!(keys.hint(Action::Toggle), "Enter");
+        assert_eq!(keys.action(KeyCode::Char('p')), Some(Action::Pause));
+        assert_eq!(keys.action(KeyCode::Char('d')), Some(Action::Diff));
+    }
+
+    #[test]
+    fn names_match_actions() {
+        for (action, name) in Action::ALL.into_iter().zip(Action::NAMES) {
+            let parsed: Action = toml::Value::String(name.to_owned()).try_into().unwrap();
+            assert_eq!(parsed, action);
+        }
+    }
+
+    #[test]
+    fn overrides_replace_defaults_and_steal_keys() {
+        let keys = KeyMap::new(&overrides(
+            r#"
+            down = ["n", "down"]
+            up = "j"
+            "#,
+        ))
+        .unwrap();
+        assert_eq!(keys.action(KeyCode::Char('n')), Some(Action::Down));
+        assert_eq!(keys.action(KeyCode::Char('j')), Some(Action::Up));
+        // `k` was only bound by `up`'s defaults, which were replaced.
+        assert_eq!(keys.action(KeyCode::Char('k')), None);
+        assert_eq!(keys.hint(Action::Up), "j");
+    }
+
+    #[test]
+    fn overrides_take_keys_from_other_defaults() {
+        let keys = KeyMap::new(&overrides(r#"toggle-stderr = "q""#)).unwrap();
+        assert_eq!(keys.action(KeyCode::Char('q')), Some(Action::ToggleStderr));
+        assert_eq!(keys.action(KeyCode::Esc), Some(Action::Quit));
+        assert_eq!(keys.hint(Action::Quit), "Esc");
+    }
+
+    #[test]
+    fn rejects_unknown_and_conflicting_keys() {
+        assert!(matches!(
+            KeyMap::new(&overrides(r#"quit = "ctrl-c""#)),
+            Err(KeyMapError::UnknownKey { .. })
+        ));
+        assert!(matches!(
+            KeyMap::new(&overrides("quit = \"x\"\ntop = \"x\"")),
+            Err(KeyMapError::Conflict { .. })
+        ));
+    }
+
+    #[test]
+    fn parses_named_keys() {
+        assert_eq!(parse_key("PageDown").unwrap(), KeyCode::PageDown);
+        assert_eq!(parse_key("space").unwrap(), KeyCode::Char(' '));
+        assert_eq!(parse_key("f5").unwrap(), KeyCode::F(5));
+        assert!(parse_key("f13").is_err());
+    }
+}
@@ -10,30 +10,149 @@
 //! ## Subcommands
 //!
 //! - `machines list` — table of all machines in `lusid.toml`.
-//! - `local apply` — apply the machine matching `$(hostname)` to this host.
+//! - `doctor --machine [--dev]` — preflight checks (SSH, sudo, staging disk
+//!   space, package manager, arch) against a remote machine or its dev VM
+//!   before applying to it.
+//! - `verify --machine [--dev]` — apply to a remote machine or its dev VM,
+//!   then run `lusid-apply --check` and fail if anything is still left to
+//!   change.
+//! - `plan explain` — explain why a plan node lands in its epoch, by running
+//!   `lusid-apply --explain` on this host (planning only, nothing applied).
+//! - `plan diff --machine [OLD] [NEW]` — compare the machine's rendered
+//!   resources at two git refs, defaulting to its last applied generation
+//!   and the working tree (see [`diff`]).
+//! - `plan export-script` — print the operations an apply would run as a shell
+//!   script, via `lusid-apply --export-script` on this host.
+//! - `render --machine` — print the machine's evaluated resources as a JSON
+//!   document for review, via `lusid-apply --render` on this host.
+//! - `render cloud-init --machine` — print the part of the machine's plan
+//!   cloud-init can express (users, packages, files, commands) as user-data.
+//! - `local apply` — apply the machine matching `$(hostname)` to this host,
+//!   recording it as a new [generation](generations). With `--raw`, skip
+//!   the TUI and print the timestamped update stream as JSON lines. With
+//!   `--allow-destructive`, apply changes `lusid-apply` otherwise stops at,
+//!   like removing a directory (also on `dev apply`).
+//! - `generations list` / `rollback --to N` — list the local machine's
+//!   generations, or re-apply an earlier one's plan and params.
+//! - `agent --interval 30m` — check the local machine on a schedule, and
+//!   with `--apply` apply it when it's drifted, keeping a state file and
+//!   reports and optionally serving a health endpoint (see [`agent`]).
+//! - `logs [RUN_ID] --machine` — list past applies, or print one machine's
+//!   stderr (or update stream) from one (see [`logs`]).
+//! - `history` — browse past applies in the TUI: each run's summary, a
+//!   replay of it, or a diff of two runs' reports (see [`history`]).
+//! - `remote bootstrap --machine` — set up a bare host for lusid over SSH:
+//!   its SSH user, passwordless sudo, the staging directory and
+//!   `lusid-apply` (see [`bootstrap`]).
+//! - `remote drift --group` — run `lusid-apply --check` on every machine in
+//!   a group at once, and print which resources drifted on each, as a table
+//!   or a JSON matrix for dashboards (see [`drift`]).
+//! - `remote clean --machine` — remove the staging directory from the
+//!   machine.
 //! - `remote apply`/`ssh` — **unimplemented**, `todo!()` today.
 //! - `dev apply`/`ssh` — spin up a local QEMU VM (via [`lusid-vm`]), SFTP
-//!   the plan + `lusid-apply` binary into it, and run apply over SSH (or
-//!   open an interactive shell).
+//!   the plan + `lusid-apply` binary into its [staging directory](staging),
+//!   and run apply over SSH (or open an interactive shell).
+//! - `dev apply --ci` — the same in a throwaway VM, applied twice to check the
+//!   plan converges, with a JSON report instead of the TUI (see [`report`]).
+//! - `dev clean` — remove the staging directory from the dev VM.
+//! - `container apply --machine --container` — copy the plan and
+//!   `lusid-apply` into a running docker or podman container and apply it
+//!   there, optionally committing the result as an image (see [`container`]).
+//! - `image apply --machine --root` — apply to the root filesystem of a
+//!   mounted image, under `systemd-nspawn` or `chroot` (see [`image`]).
+//! - `import ansible` — convert an Ansible playbook into a plan skeleton
+//!   (experimental, see [`ansible`]).
+//! - `scan --machine --kinds --paths` — print a starter plan capturing a
+//!   remote machine's current packages, users, groups, files and services
+//!   (see [`scan`]).
+//! - `resource list` — table of the `@core/*` resources and the platforms
+//!   each works on.
+//! - `resource docs ID` / `resource schema` — document a resource's params,
+//!   or all of them as a JSON Schema for editors.
+//!
+//! Applies and `remote drift` fire the [`hooks`] configured in `lusid.toml`.
 
+mod agent;
+mod ansible;
+mod bootstrap;
 mod config;
+mod config_schema;
+mod container;
+mod diff;
+mod doctor;
+mod drift;
+mod generations;
+mod history;
+mod hooks;
+mod image;
+mod keys;
+mod logs;
+mod metrics;
+mod notify;
+mod report;
+mod scan;
+mod staging;
+mod stderr_log;
 mod tui;
 
-use std::{env, net::Ipv4Addr, path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    env,
+    net::{Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
 
 use clap::{Parser, Subcommand};
-use lusid_apply_stdio::AppViewError;
-use lusid_cmd::{Command, CommandError};
-use lusid_ctx::Context;
+use comfy_table::Table;
+use lusid_apply_stdio::{
+    AppUpdate, AppViewError, CloudInitSkipped, RenderedCloudInit, RenderedPlan,
+};
+use lusid_cmd::{Command, CommandError, CommandOutput};
+use lusid_ctx::{Context, DownloadLimits};
+use lusid_resource::docs::{json_schema, resource_doc, resource_docs};
 use lusid_secrets::cli::{CliEnv as SecretsCliEnv, CliError as SecretsCliError, SecretsCommand};
 use lusid_secrets::{ReencryptForMachineError, reencrypt_for_machine};
-use lusid_ssh::{Ssh, SshConnectOptions, SshError, SshKeypairError, SshVolume};
+use lusid_ssh::{
+    Ssh, SshConnectOptions, SshError, SshKeypair, SshKeypairError, SshVolume, parse_public_key,
+};
 use lusid_vm::{Vm, VmError, VmOptions};
 use thiserror::Error;
-use tracing::error;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio::time::MissedTickBehavior;
+use tracing::{error, info};
 use which::which;
 
-use crate::config::{Config, ConfigError, MachineConfig};
+use crate::agent::{
+    AgentDir, AgentError, AgentOptions, CycleReport, CycleStatus, now_secs, parse_interval,
+    serve_health,
+};
+use crate::ansible::{AnsibleImportError, import_ansible};
+use crate::config::{ApplyConfig, Config, ConfigError, MachineConfig, ProtectConfig, RemoteConfig};
+use crate::container::{CONTAINER_STAGING_DIR, Container, ContainerEngine};
+use crate::diff::diff_plans;
+use crate::doctor::{Check, CheckStatus, DoctorTarget, print_checks, run_checks, unreachable};
+use crate::drift::{DriftOutput, DriftRecorder, DriftReport, MachineDrift, group_machines};
+use crate::generations::{
+    Generation, Generations, GenerationsError, NewGeneration, Worktree, current_revision,
+    print_generations, resolve_commit,
+};
+use crate::history::{HistoryError, history};
+use crate::hooks::HookPayload;
+use crate::image::{IMAGE_STAGING_DIR, Image, ImageRunner};
+use crate::logs::{
+    LogsError, MachineLog, Run, list_runs, now_millis, print_runs, read_log, stamp_update,
+};
+use crate::metrics::StageTimer;
+use crate::report::{ApplyReport, CiReport};
+use crate::scan::{ScanError, ScanKind, scan};
+use crate::staging::{StagingDir, StagingError};
 use crate::tui::{TuiError, tui};
 
 /// Parsed CLI. `lusid_apply_linux_*_path` point at prebuilt apply binaries
@@ -81,11 +200,86 @@ pub enum Cmd {
         #[command(subcommand)]
         command: MachinesCmd,
     },
+    #[doc = " Check a machine is ready to be applied to"]
+    Doctor {
+        #[doc = " Machine identifier"]
+        #[arg(long = "machine")]
+        machine_id: String,
+
+        #[doc = " Check the machine's dev VM rather than the machine itself"]
+        #[arg(long = "dev")]
+        dev: bool,
+    },
+    #[doc = " Apply a machine's plan, then check it has nothing left to change"]
+    Verify {
+        #[doc = " Machine identifier"]
+        #[arg(long = "machine")]
+        machine_id: String,
+
+        #[doc = " Verify in the machine's dev VM rather than the machine itself"]
+        #[arg(long = "dev")]
+        dev: bool,
+    },
+    #[doc = " Inspect a machine's plan"]
+    Plan {
+        #[command(subcommand)]
+        command: PlanCmd,
+    },
+    #[doc = " Print a machine's evaluated resources as JSON, secrets redacted"]
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    Render {
+        #[command(subcommand)]
+        command: Option<RenderCmd>,
+        #[doc = " Machine identifier"]
+        #[arg(long = "machine", required = true)]
+        machine_id: Option<String>,
+    },
     #[doc = " Manage local machine"]
     Local {
         #[command(subcommand)]
         command: LocalCmd,
     },
+    #[doc = " Inspect the recorded applies of the local machine"]
+    Generations {
+        #[command(subcommand)]
+        command: GenerationsCmd,
+    },
+    #[doc = " Re-apply an earlier generation to the local machine"]
+    Rollback {
+        #[doc = " Generation number (see `lusid generations list`)"]
+        #[arg(long = "to")]
+        to: u32,
+    },
+    #[doc = " Keep the local machine on its plan: check it every interval, and optionally apply"]
+    Agent {
+        #[doc = " How long between checks, e.g. `30m`, `1h` or `90s`"]
+        #[arg(long = "interval", default_value = "30m", value_parser = parse_interval)]
+        interval: Duration,
+        #[doc = " Apply when a check finds changes, rather than only report them"]
+        #[arg(long = "apply")]
+        apply: bool,
+        #[doc = " Apply destructive changes, like removing a directory or deleting a user"]
+        #[arg(long = "allow-destructive", requires = "apply")]
+        allow_destructive: bool,
+        #[doc = " Serve /healthz, /readyz and /metrics on this address, e.g. `127.0.0.1:9464`"]
+        #[arg(long = "listen")]
+        listen: Option<SocketAddr>,
+    },
+    #[doc = " Show the logs of past applies"]
+    Logs {
+        #[doc = " Run id; omit to list runs"]
+        run_id: Option<String>,
+
+        #[doc = " Machine to show; needed when the run applied several"]
+        #[arg(long = "machine")]
+        machine_id: Option<String>,
+
+        #[doc = " Show the update stream as JSON lines instead of stderr"]
+        #[arg(long = "updates")]
+        updates: bool,
+    },
+    #[doc = " Browse past applies: their summaries, replays and diffs"]
+    History,
     #[doc = " Manage remote machines"]
     Remote {
         #[command(subcommand)]
@@ -96,11 +290,43 @@ pub enum Cmd {
         #[command(subcommand)]
         command: DevCmd,
     },
+    #[doc = " Apply plans inside docker or podman containers"]
+    Container {
+        #[command(subcommand)]
+        command: ContainerCmd,
+    },
+    #[doc = " Apply plans to mounted images, without booting them"]
+    Image {
+        #[command(subcommand)]
+        command: ImageCmd,
+    },
     #[doc = " Manage age-encrypted project secrets"]
     Secrets {
         #[command(subcommand)]
         command: SecretsCommand,
     },
+    #[doc = " Convert other tools' configuration into lusid plans (experimental)"]
+    Import {
+        #[command(subcommand)]
+        command: ImportCmd,
+    },
+    #[doc = " Print a starter plan capturing a remote machine's current state"]
+    Scan {
+        #[doc = " Machine identifier"]
+        #[arg(long = "machine")]
+        machine_id: String,
+        #[doc = " What to capture, comma-separated"]
+        #[arg(long = "kinds", value_enum, value_delimiter = ',', required = true)]
+        kinds: Vec<ScanKind>,
+        #[doc = " Files and directories to capture with the `file` kind, comma-separated"]
+        #[arg(long = "paths", value_delimiter = ',')]
+        paths: Vec<String>,
+    },
+    #[doc = " Document the core resources and their params"]
+    Resource {
+        #[command(subcommand)]
+        command: ResourceCmd,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -109,13 +335,98 @@ pub enum MachinesCmd {
     List,
 }
 
+#[derive(Subcommand, Debug)]
+pub enum RenderCmd {
+    #[doc = " Print the part of a machine's plan cloud-init can express, as user-data"]
+    CloudInit {
+        #[doc = " Machine identifier"]
+        #[arg(long = "machine")]
+        machine_id: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PlanCmd {
+    #[doc = " Explain which constraints place a plan node in its epoch"]
+    Explain {
+        #[doc = " Machine identifier"]
+        #[arg(long = "machine")]
+        machine_id: String,
+
+        #[doc = " Plan item id (or full plan node id) to explain"]
+        node_id: String,
+    },
+    #[doc = " Print the operations an apply would run as a shell script"]
+    ExportScript {
+        #[doc = " Machine identifier"]
+        #[arg(long = "machine")]
+        machine_id: String,
+    },
+    #[doc = " Compare the resources of a machine's plan at two git revisions"]
+    Diff {
+        #[doc = " Machine identifier"]
+        #[arg(long = "machine")]
+        machine_id: String,
+
+        #[doc = " Old git ref (default: the machine's last applied generation)"]
+        old: Option<String>,
+
+        #[doc = " New git ref (default: the working tree)"]
+        new: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ImportCmd {
+    #[doc = " Print a plan skeleton converted from an Ansible playbook or task list"]
+    Ansible {
+        #[doc = " Path to the playbook (.yml)"]
+        playbook: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ResourceCmd {
+    #[doc = " List the core resources, with their platforms"]
+    List,
+    #[doc = " Print a resource's params"]
+    Docs {
+        #[doc = " Resource id, e.g. `file` or `@core/file`"]
+        id: String,
+    },
+    #[doc = " Print a JSON Schema for plan items using core modules"]
+    Schema,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum LocalCmd {
-    Apply,
+    Apply {
+        #[doc = " Skip the TUI: print lusid-apply's updates as timestamped JSON lines"]
+        #[arg(long = "raw")]
+        raw: bool,
+        #[doc = " Apply destructive changes, like removing a directory or deleting a user"]
+        #[arg(long = "allow-destructive")]
+        allow_destructive: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum GenerationsCmd {
+    #[doc = " List generations, oldest first"]
+    List,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum RemoteCmd {
+    #[doc = " Set up a bare host for lusid: its SSH user, sudo, and lusid-apply"]
+    Bootstrap {
+        #[doc = " Machine identifier"]
+        #[arg(long = "machine")]
+        machine_id: String,
+        #[doc = " User to log in as first, to create the configured SSH user (default: the configured user)"]
+        #[arg(long = "login-user")]
+        login_user: Option<String>,
+    },
     Apply {
         #[doc = " Machine identifier"]
         #[arg(long = "machine")]
@@ -125,6 +436,22 @@ pub enum RemoteCmd {
         #[arg(long = "machine")]
         machine_id: String,
     },
+    #[doc = " Remove lusid's staging directory from the machine"]
+    Clean {
+        #[arg(long = "machine")]
+        machine_id: String,
+    },
+    #[doc = " Check a group of machines for drift from their plans, in parallel"]
+    Drift {
+        #[doc = " Group to check, or `all` for every machine with an SSH section"]
+        #[arg(long = "group")]
+        group: String,
+        #[arg(long = "output", value_enum, default_value = "text")]
+        output: DriftOutput,
+        #[doc = " Check at most this many machines at once"]
+        #[arg(long = "jobs", default_value_t = 8)]
+        jobs: usize,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -133,11 +460,86 @@ pub enum DevCmd {
         #[doc = " Machine identifier"]
         #[arg(long = "machine")]
         machine_id: String,
+        #[doc = " Headless plan test: apply in a fresh VM, apply again to check nothing changes, print a JSON report, and delete the VM"]
+        #[arg(long = "ci")]
+        ci: bool,
+        #[doc = " Apply destructive changes, like removing a directory or deleting a user; always on with --ci"]
+        #[arg(long = "allow-destructive")]
+        allow_destructive: bool,
     },
     Ssh {
         #[arg(long = "machine")]
         machine_id: String,
     },
+    #[doc = " Remove lusid's staging directory from the machine's dev VM"]
+    Clean {
+        #[arg(long = "machine")]
+        machine_id: String,
+    },
+    #[doc = " Copy a local file or directory into the machine's dev VM"]
+    Push {
+        #[arg(long = "machine")]
+        machine_id: String,
+        local: PathBuf,
+        #[doc = " Path in the VM; relative (or `~/`) paths are under the SSH user's home, and a trailing `/` copies into that directory"]
+        remote: String,
+    },
+    #[doc = " Copy a file or directory out of the machine's dev VM"]
+    Pull {
+        #[arg(long = "machine")]
+        machine_id: String,
+        #[doc = " Path in the VM; relative (or `~/`) paths are under the SSH user's home"]
+        remote: String,
+        #[doc = " Local destination; an existing directory receives the copy inside it"]
+        local: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ContainerCmd {
+    #[doc = " Apply a machine's plan inside a running container"]
+    Apply {
+        #[doc = " Machine identifier"]
+        #[arg(long = "machine")]
+        machine_id: String,
+        #[doc = " Name or id of the running container"]
+        #[arg(long = "container")]
+        container: String,
+        #[doc = " Container engine (default: docker if it's installed, else podman)"]
+        #[arg(long = "engine", value_enum)]
+        engine: Option<ContainerEngine>,
+        #[doc = " After a successful apply, commit the container as this image"]
+        #[arg(long = "commit", value_name = "IMAGE")]
+        commit: Option<String>,
+        #[doc = " Skip the TUI: print lusid-apply's updates as timestamped JSON lines"]
+        #[arg(long = "raw")]
+        raw: bool,
+        #[doc = " Apply destructive changes, like removing a directory or deleting a user"]
+        #[arg(long = "allow-destructive")]
+        allow_destructive: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ImageCmd {
+    #[doc = " Apply a machine's plan to the root filesystem of a mounted image"]
+    Apply {
+        #[doc = " Machine identifier"]
+        #[arg(long = "machine")]
+        machine_id: String,
+        #[doc = " Where the image's root filesystem is mounted"]
+        #[arg(long = "root")]
+        root: PathBuf,
+        #[doc = " How to run in the image (default: nspawn if it's installed, else chroot)"]
+        #[arg(long = "runner", value_enum)]
+        runner: Option<ImageRunner>,
+        #[doc = " Skip the TUI: print lusid-apply's updates as timestamped JSON lines"]
+        #[arg(long = "raw")]
+        raw: bool,
+        #[doc = " Apply destructive changes, like removing a directory or deleting a user"]
+        #[arg(long = "allow-destructive")]
+        allow_destructive: bool,
+    },
 }
 
 #[derive(Error, Debug)]
@@ -160,6 +562,9 @@ pub enum AppError {
     #[error(transparent)]
     View(#[from] AppViewError),
 
+    #[error("failed to write cloud-init user-data: {0}")]
+    CloudInitYaml(#[from] serde_yaml_ng::Error),
+
     #[error("failed to convert params toml to json: {0}")]
     ParamsTomlToJson(#[from] serde_json::Error),
 
@@ -172,6 +577,12 @@ pub enum AppError {
     #[error("failed to forward stderr from lusid-apply")]
     ForwardApplyStderr(#[source] tokio::io::Error),
 
+    #[error("failed to write to stdout")]
+    WriteStdout(#[source] tokio::io::Error),
+
+    #[error("lusid-apply failed")]
+    ApplyFailed,
+
     #[error(transparent)]
     Which(#[from] which::Error),
 
@@ -187,8 +598,60 @@ pub enum AppError {
     #[error("failed to re-encrypt secrets for target: {0}")]
     ReencryptSecrets(#[from] ReencryptForMachineError),
 
-    #[error("failed to serialize VM SSH keypair: {0}")]
+    #[error("SSH key error: {0}")]
     SshKeypair(#[from] SshKeypairError),
+
+    #[error(transparent)]
+    Staging(#[from] StagingError),
+
+    #[error(transparent)]
+    AnsibleImport(#[from] AnsibleImportError),
+
+    #[error(transparent)]
+    Scan(#[from] ScanError),
+
+    #[error(transparent)]
+    Generations(#[from] GenerationsError),
+
+    #[error(transparent)]
+    Logs(#[from] LogsError),
+
+    #[error(transparent)]
+    History(#[from] HistoryError),
+
+    #[error(transparent)]
+    Agent(#[from] AgentError),
+
+    #[error("no generations recorded for machine {machine_id}, so there's nothing to diff against")]
+    NoGenerations { machine_id: String },
+
+    #[error("machine {machine_id} has no [machines.{machine_id}.ssh] section")]
+    NotRemote { machine_id: String },
+
+    #[error("failed to set up user {user} as {login_user}: {stderr}")]
+    SetupUser {
+        user: String,
+        login_user: String,
+        stderr: String,
+    },
+
+    #[error("no machines with an [machines.<id>.ssh] section in group {group}")]
+    NoRemoteMachines { group: String },
+
+    #[error("{failed} machine(s) couldn't be checked for drift (see the report on stdout)")]
+    DriftCheckFailed { failed: usize },
+
+    #[error("{failed} doctor check(s) failed")]
+    DoctorFailed { failed: usize },
+
+    #[error("machine {machine_id} didn't converge (see the report on stdout)")]
+    NotConverged { machine_id: String },
+
+    #[error("{count} resource(s) of machine {machine_id} still change after an apply")]
+    NotIdempotent { machine_id: String, count: usize },
+
+    #[error("no core resource {id} (see `lusid resource list`)")]
+    UnknownResource { id: String },
 }
 
 /// Resolve the config path (CLI flag → `LUSID_CONFIG` env → CWD → `.`) and
@@ -212,20 +675,179 @@ pub async fn run(cli: Cli, config: Config) -> Result<(), AppError> {
         Cmd::Machines { command } => match command {
             MachinesCmd::List => cmd_machines_list(config).await,
         },
+        Cmd::Doctor { machine_id, dev } => {
+            if dev {
+                cmd_dev_doctor(config, machine_id).await
+            } else {
+                cmd_remote_doctor(config, machine_id).await
+            }
+        }
+        Cmd::Verify { machine_id, dev } => {
+            if dev {
+                cmd_dev_verify(config, machine_id, secrets_dir, identity_path).await
+            } else {
+                cmd_remote_verify(config, machine_id).await
+            }
+        }
+        Cmd::Plan { command } => match command {
+            PlanCmd::Explain {
+                machine_id,
+                node_id,
+            } => cmd_plan_explain(config, machine_id, node_id).await,
+            PlanCmd::ExportScript { machine_id } => {
+                cmd_plan_export_script(config, machine_id, secrets_dir, identity_path).await
+            }
+            PlanCmd::Diff {
+                machine_id,
+                old,
+                new,
+            } => cmd_plan_diff(config, machine_id, old, new, secrets_dir, identity_path).await,
+        },
+        Cmd::Render {
+            command,
+            machine_id,
+        } => match command {
+            Some(RenderCmd::CloudInit { machine_id }) => {
+                cmd_render_cloud_init(config, machine_id, secrets_dir, identity_path).await
+            }
+            None => {
+                let machine_id = machine_id.expect("clap requires --machine without a subcommand");
+                cmd_render(config, machine_id, secrets_dir, identity_path).await
+            }
+        },
         Cmd::Local { command } => match command {
-            LocalCmd::Apply => cmd_local_apply(config, secrets_dir, identity_path).await,
+            LocalCmd::Apply {
+                raw,
+                allow_destructive,
+            } => cmd_local_apply(config, secrets_dir, identity_path, raw, allow_destructive).await,
         },
+        Cmd::Generations { command } => match command {
+            GenerationsCmd::List => cmd_generations_list(config).await,
+        },
+        Cmd::Rollback { to } => cmd_rollback(config, to, secrets_dir, identity_path).await,
+        Cmd::Agent {
+            interval,
+            apply,
+            allow_destructive,
+            listen,
+        } => {
+            let options = AgentOptions {
+                interval,
+                apply,
+                allow_destructive,
+                listen,
+            };
+            cmd_agent(config, secrets_dir, identity_path, options).await
+        }
+        Cmd::Logs {
+            run_id,
+            machine_id,
+            updates,
+        } => cmd_logs(run_id, machine_id, updates).await,
+        Cmd::History => cmd_history(config).await,
         Cmd::Remote { command } => match command {
+            RemoteCmd::Bootstrap {
+                machine_id,
+                login_user,
+            } => cmd_remote_bootstrap(config, machine_id, login_user).await,
             RemoteCmd::Apply { machine_id } => cmd_remote_apply(config, machine_id).await,
             RemoteCmd::Ssh { machine_id } => cmd_remote_ssh(config, machine_id).await,
+            RemoteCmd::Clean { machine_id } => cmd_remote_clean(config, machine_id).await,
+            RemoteCmd::Drift {
+                group,
+                output,
+                jobs,
+            } => cmd_remote_drift(config, group, output, jobs).await,
         },
         Cmd::Dev { command } => match command {
-            DevCmd::Apply { machine_id } => {
-                cmd_dev_apply(config, machine_id, secrets_dir, identity_path).await
+            DevCmd::Apply {
+                machine_id,
+                ci,
+                allow_destructive,
+            } => {
+                if ci {
+                    cmd_dev_apply_ci(config, machine_id, secrets_dir, identity_path).await
+                } else {
+                    cmd_dev_apply(
+                        config,
+                        machine_id,
+                        secrets_dir,
+                        identity_path,
+                        allow_destructive,
+                    )
+                    .await
+                }
             }
             DevCmd::Ssh { machine_id } => cmd_dev_ssh(config, machine_id).await,
+            DevCmd::Clean { machine_id } => cmd_dev_clean(config, machine_id).await,
+            DevCmd::Push {
+                machine_id,
+                local,
+                remote,
+            } => cmd_dev_push(config, machine_id, local, remote).await,
+            DevCmd::Pull {
+                machine_id,
+                remote,
+                local,
+            } => cmd_dev_pull(config, machine_id, remote, local).await,
+        },
+        Cmd::Container { command } => match command {
+            ContainerCmd::Apply {
+                machine_id,
+                container,
+                engine,
+                commit,
+                raw,
+                allow_destructive,
+            } => {
+                let engine = match engine {
+                    Some(engine) => engine,
+                    None => ContainerEngine::detect()?,
+                };
+                let container = Container {
+                    engine,
+                    name: container,
+                };
+                cmd_container_apply(
+                    config,
+                    machine_id,
+                    container,
+                    commit,
+                    raw,
+                    allow_destructive,
+                )
+                .await
+            }
+        },
+        Cmd::Image { command } => match command {
+            ImageCmd::Apply {
+                machine_id,
+                root,
+                runner,
+                raw,
+                allow_destructive,
+            } => {
+                let image = Image {
+                    runner: runner.unwrap_or_else(ImageRunner::detect),
+                    root,
+                };
+                cmd_image_apply(config, machine_id, image, raw, allow_destructive).await
+            }
         },
         Cmd::Secrets { command } => cmd_secrets(command, secrets_dir, identity_path).await,
+        Cmd::Import { command } => match command {
+            ImportCmd::Ansible { playbook } => cmd_import_ansible(playbook).await,
+        },
+        Cmd::Scan {
+            machine_id,
+            kinds,
+            paths,
+        } => cmd_scan(config, machine_id, kinds, paths).await,
+        Cmd::Resource { command } => match command {
+            ResourceCmd::List => cmd_resource_list(),
+            ResourceCmd::Docs { id } => cmd_resource_docs(id),
+            ResourceCmd::Schema => cmd_resource_schema(),
+        },
     }
 }
 
@@ -242,38 +864,64 @@ async fn cmd_machines_list(config: Config) -> Result<(), AppError> {
     Ok(())
 }
 
-async fn cmd_secrets(
-    command: SecretsCommand,
-    secrets_dir: PathBuf,
-    identity_path: Option<PathBuf>,
+// Runs `lusid-apply --explain` locally against the machine's plan and
+// params. Planning doesn't touch the target, so this works for any machine
+// in `lusid.toml`, not just the local one.
+//
+// Note(cc): the plan's `setup` sees *this* host's `System`, so plans that
+// branch on system facts may explain differently than they'd apply remotely.
+async fn cmd_plan_explain(
+    config: Config,
+    machine_id: String,
+    node_id: String,
 ) -> Result<(), AppError> {
-    let env = SecretsCliEnv {
-        secrets_dir,
-        identity_path,
-    };
-    lusid_secrets::cli::run(command, env).await?;
+    let machine_config = config.get_machine(&machine_id)?;
+    let facts = machine_config.facts(&machine_id);
+    let apply = config.planning_apply(&machine_config);
+    let MachineConfig { plan, params, .. } = machine_config;
+
+    let mut command = Command::new(&apply.bin);
+    command
+        .args(["--root", &config.root().to_string_lossy()])
+        .args(["--plan", &plan.to_string_lossy()])
+        .args(["--log", &apply.log])
+        .args(["--machine", &facts.to_string()])
+        .args(["--explain", &node_id]);
+
+    if let Some(params) = params {
+        let params_json = serde_json::to_string(&params)?;
+        command.args(["--params", &params_json]);
+    }
+
+    let stdout = command.run().await?;
+    print!("{}", String::from_utf8_lossy(&stdout));
+
     Ok(())
 }
 
-// Spawns `lusid-apply` as a subprocess and pipes its stdout + stderr into
-// the TUI.
-async fn cmd_local_apply(
+// Runs `lusid-apply --export-script` locally. Unlike `explain`, this probes
+// resource states, so the changes (and the script) are relative to *this*
+// host's state — export from a host that matches the target.
+async fn cmd_plan_export_script(
     config: Config,
+    machine_id: String,
     secrets_dir: PathBuf,
     identity_path: Option<PathBuf>,
 ) -> Result<(), AppError> {
-    let Config {
-        ref lusid_apply_linux_x86_64_path,
-        ..
-    } = config;
-    let MachineConfig { plan, params, .. } = config.local_machine()?;
+    let machine_config = config.get_machine(&machine_id)?;
+    let facts = machine_config.facts(&machine_id);
+    let apply = config.planning_apply(&machine_config);
+    let MachineConfig { plan, params, .. } = machine_config;
 
-    let mut command = Command::new(lusid_apply_linux_x86_64_path);
+    let mut command = Command::new(&apply.bin);
     command
         .args(["--root", &config.root().to_string_lossy()])
         .args(["--plan", &plan.to_string_lossy()])
-        .args(["--log", &config.log])
-        .args(["--secrets-dir", &secrets_dir.to_string_lossy()]);
+        .args(["--log", &apply.log])
+        .args(["--secrets-dir", &secrets_dir.to_string_lossy()])
+        .args(["--machine", &facts.to_string()])
+        .args(protect_args(&config.protect))
+        .arg("--export-script");
 
     if let Some(identity_path) = identity_path.as_deref() {
         command.args(["--identity", &identity_path.to_string_lossy()]);
@@ -284,102 +932,1557 @@ async fn cmd_local_apply(
         command.args(["--params", &params_json]);
     }
 
-    let output = command.output().await?;
-
-    let wait = Box::pin(async move {
-        output.status.await?;
-        Ok::<_, CommandError>(())
-    });
-    tui(output.stdout, output.stderr, wait).await?;
+    let stdout = command.run().await?;
+    print!("{}", String::from_utf8_lossy(&stdout));
 
     Ok(())
 }
 
-// TODO(cc): implement remote apply/ssh. Expected shape: resolve the machine
-// from config, connect to its hostname over SSH (using either agent auth or
-// a configured key), upload the plan + lusid-apply binary, run apply, and
-// pipe through the TUI — essentially `cmd_dev_*` without the VM bring-up.
-//
-// Secrets strategy: mirror `cmd_dev_apply`'s per-target re-encryption, with
-// two substitutions:
-//   - Recipient key is the target machine's entry in `lusid-secrets.toml`'s
-//     `[machines]` table — looked up by `machine_id` — rather than an
-//     ephemeral VM auth key. `lusid_secrets` will need a small helper to
-//     expose that lookup; the CLI doesn't have `Recipients` access directly.
-//   - Guest identity is the target's existing
-//     `/etc/ssh/ssh_host_ed25519_key` on the machine itself — nothing is
-//     SFTP'd for the identity; just pass `--identity=/etc/ssh/ssh_host_ed25519_key`
-//     (plus `--guest-mode --secrets-dir=...`). Requires the guest
-//     `lusid-apply` to run as root, which it typically does already.
-async fn cmd_remote_apply(_config: Config, _machine_id: String) -> Result<(), AppError> {
-    todo!()
-}
+// Runs `lusid-apply --render` locally. Like `explain`, this is planning
+// only, so it works for any machine in `lusid.toml`. Secrets are loaded
+// only to redact their plaintexts from the output.
+async fn cmd_render(
+    config: Config,
+    machine_id: String,
+    secrets_dir: PathBuf,
+    identity_path: Option<PathBuf>,
+) -> Result<(), AppError> {
+    let machine_config = config.get_machine(&machine_id)?;
+    let facts = machine_config.facts(&machine_id);
+    let apply = config.planning_apply(&machine_config);
+    let MachineConfig { plan, params, .. } = machine_config;
+    let params = params.map(serde_json::to_value).transpose()?;
+
+    let stdout = render_command(
+        &apply,
+        config.root(),
+        &plan,
+        params.as_ref(),
+        &facts,
+        &secrets_dir,
+        identity_path.as_deref(),
+    )?
+    .run()
+    .await?;
+    print!("{}", String::from_utf8_lossy(&stdout));
 
-async fn cmd_remote_ssh(_config: Config, _machine_id: String) -> Result<(), AppError> {
-    todo!()
+    Ok(())
 }
 
-// `dev apply`: boot a local QEMU VM matching the machine spec, upload the
-// plan directory and a prebuilt `lusid-apply` binary over SFTP, then run
-// apply remotely and stream its stdout/stderr through the TUI just like
-// local apply. The VM's SSH keypair lives inside its instance dir (see
-// `lusid_vm`).
-//
-// Secrets are forwarded via per-target re-encryption: when `identity_path`
-// is set, the host decrypts every `*.age` with the operator identity,
-// re-encrypts each plaintext to the VM's SSH keypair alone, ships the
-// ciphertexts to `<dev_dir>/secrets/`, and points the guest's
-// `lusid-apply` at `<dev_dir>/identity` (the same VM keypair in OpenSSH
-// PEM form) via `--identity --guest-mode`. The operator identity never
-// leaves the host.
-async fn cmd_dev_apply(
+// `render cloud-init`: `lusid-apply --render-cloud-init` on this host, as for
+// `render`, printed as a `#cloud-config` document. What the plan has that
+// cloud-init can't express is listed in comments at the top, so it's seen
+// by whoever reads the user-data, and left for lusid's first apply.
+async fn cmd_render_cloud_init(
     config: Config,
     machine_id: String,
     secrets_dir: PathBuf,
     identity_path: Option<PathBuf>,
 ) -> Result<(), AppError> {
-    let MachineConfig {
-        plan,
-        machine,
-        params,
-    } = config.get_machine(&machine_id)?;
+    let machine_config = config.get_machine(&machine_id)?;
+    let facts = machine_config.facts(&machine_id);
+    let apply = config.planning_apply(&machine_config);
+    let MachineConfig { plan, params, .. } = machine_config;
+    let params = params.map(serde_json::to_value).transpose()?;
+
+    let mut command = local_apply_command(
+        &apply,
+        config.root(),
+        &plan,
+        params.as_ref(),
+        &facts,
+        &secrets_dir,
+        identity_path.as_deref(),
+    )?;
+    command.arg("--render-cloud-init");
+    let stdout = command.run().await?;
+    let RenderedCloudInit {
+        config: cloud_config,
+        skipped,
+    } = serde_json::from_slice(&stdout).map_err(AppError::ParseApplyStdoutJson)?;
+
+    let mut user_data = String::from("#cloud-config\n");
+    if !skipped.is_empty() {
+        user_data.push_str("#\n# Left for lusid, as cloud-init can't express them:\n");
+        for CloudInitSkipped { resource, reason } in skipped {
+            user_data.push_str(&format!("#   {resource}: {reason}\n"));
+        }
+    }
+    user_data.push_str(&serde_yaml_ng::to_string(&cloud_config)?);
+    print!("{user_data}");
 
-    let root = config.root();
-    let mut ctx = Context::create(root).unwrap();
+    Ok(())
+}
 
-    let instance_id = &machine_id;
-    let ports = vec![];
-    let options = VmOptions {
-        instance_id,
-        machine: &machine,
-        ports,
-    };
-    let vm = Vm::run(&mut ctx, options).await?;
+fn render_command(
+    apply: &ApplyConfig,
+    root: &Path,
+    plan: &Path,
+    params: Option<&serde_json::Value>,
+    facts: &serde_json::Value,
+    secrets_dir: &Path,
+    identity_path: Option<&Path>,
+) -> Result<Command, AppError> {
+    let mut command =
+        local_apply_command(apply, root, plan, params, facts, secrets_dir, identity_path)?;
+    command.arg("--render");
+    Ok(command)
+}
 
-    let vm_keypair = vm.ssh_keypair().await?;
+// `lusid-apply` on this host, run with `apply`, for `plan` with `params`, on
+// the machine described by `facts` (see `MachineConfig::facts`).
+fn local_apply_command(
+    apply: &ApplyConfig,
+    root: &Path,
+    plan: &Path,
+    params: Option<&serde_json::Value>,
+    facts: &serde_json::Value,
+    secrets_dir: &Path,
+    identity_path: Option<&Path>,
+) -> Result<Command, AppError> {
+    let mut command = Command::new(&apply.bin);
+    command
+        .args(["--root", &root.to_string_lossy()])
+        .args(["--plan", &plan.to_string_lossy()])
+        .args(["--log", &apply.log])
+        .args(["--secrets-dir", &secrets_dir.to_string_lossy()])
+        .args(["--machine", &facts.to_string()])
+        .args(download_limit_args(apply.downloads));
 
-    let mut ssh = Ssh::connect(SshConnectOptions {
-        private_key: vm_keypair.private_key.clone(),
-        addrs: (Ipv4Addr::LOCALHOST, vm.ssh_port),
-        username: vm.user.clone(),
-        config: Arc::new(Default::default()),
-        timeout: Duration::from_secs(10),
-    })
-    .await?;
+    if let Some(identity_path) = identity_path {
+        command.args(["--identity", &identity_path.to_string_lossy()]);
+    }
 
-    let dev_dir = format!("/home/{}", vm.user);
-    let plan_dir = plan.parent().unwrap();
-    let plan_filename = plan.file_name().unwrap().to_string_lossy();
-    let apply_bin = which(&config.lusid_apply_linux_x86_64_path)?;
+    if let Some(params) = params {
+        let params_json = serde_json::to_string(params)?;
+        command.args(["--params", &params_json]);
+    }
 
-    let mut volumes = vec![
+    Ok(command)
+}
+
+/// One side of a `plan diff`.
+enum DiffSide {
+    WorkingTree,
+    Commit { reference: String, commit: String },
+    Generation(Generation),
+}
+
+impl DiffSide {
+    fn describe(&self) -> String {
+        match self {
+            DiffSide::WorkingTree => "working tree".to_owned(),
+            DiffSide::Commit { reference, commit } => {
+                format!("{reference} ({})", &commit[..commit.len().min(12)])
+            }
+            DiffSide::Generation(generation) => format!("generation {}", generation.number),
+        }
+    }
+}
+
+// Renders the machine's plan at both sides (see `cmd_render`) and compares
+// them. Git refs are checked out into scratch worktrees (see
+// `generations::Worktree`); the working tree side uses the project as-is.
+//
+// Note(cc): a git ref side is evaluated with the machine's *current* plan
+// path and params from `lusid.toml`, so the diff isolates changes to the
+// plans themselves. Only a generation side uses its own recorded params.
+async fn cmd_plan_diff(
+    config: Config,
+    machine_id: String,
+    old: Option<String>,
+    new: Option<String>,
+    secrets_dir: PathBuf,
+    identity_path: Option<PathBuf>,
+) -> Result<(), AppError> {
+    let root = config.root();
+    let old = match old {
+        Some(reference) => DiffSide::Commit {
+            commit: resolve_commit(root, &reference).await?,
+            reference,
+        },
+        None => {
+            let mut generations = Generations::open(&machine_id)?.list().await?;
+            let generation = generations.pop().ok_or_else(|| AppError::NoGenerations {
+                machine_id: machine_id.clone(),
+            })?;
+            DiffSide::Generation(generation)
+        }
+    };
+    let new = match new {
+        Some(reference) => DiffSide::Commit {
+            commit: resolve_commit(root, &reference).await?,
+            reference,
+        },
+        None => DiffSide::WorkingTree,
+    };
+
+    let old_plan = render_diff_side(
+        &config,
+        &machine_id,
+        &old,
+        &secrets_dir,
+        identity_path.as_deref(),
+    )
+    .await?;
+    let new_plan = render_diff_side(
+        &config,
+        &machine_id,
+        &new,
+        &secrets_dir,
+        identity_path.as_deref(),
+    )
+    .await?;
+
+    println!("--- {}", old.describe());
+    println!("+++ {}", new.describe());
+    println!();
+    print!("{}", diff_plans(old_plan, new_plan));
+
+    Ok(())
+}
+
+async fn render_diff_side(
+    config: &Config,
+    machine_id: &str,
+    side: &DiffSide,
+    secrets_dir: &Path,
+    identity_path: Option<&Path>,
+) -> Result<RenderedPlan, AppError> {
+    let machine_config = config.get_machine(machine_id)?;
+    let facts = machine_config.facts(machine_id);
+    let apply = config.planning_apply(&machine_config);
+    let MachineConfig { plan, params, .. } = machine_config;
+    let params = params.map(serde_json::to_value).transpose()?;
+    let plan = plan.strip_prefix(config.root()).unwrap_or(&plan).to_owned();
+
+    let (worktree, root, plan, params) = match side {
+        DiffSide::WorkingTree => (None, config.root().to_owned(), plan, params),
+        DiffSide::Commit { commit, .. } => {
+            let name = format!("diff-{machine_id}-{commit}");
+            let worktree = Worktree::checkout_commit(config.root(), &name, commit).await?;
+            let root = worktree.project_root().to_owned();
+            (Some(worktree), root, plan, params)
+        }
+        DiffSide::Generation(generation) => {
+            let worktree = Worktree::checkout(config.root(), machine_id, generation).await?;
+            let root = worktree.project_root().to_owned();
+            (
+                Some(worktree),
+                root,
+                generation.plan.clone(),
+                generation.params.clone(),
+            )
+        }
+    };
+
+    let stdout = render_command(
+        &apply,
+        &root,
+        &root.join(plan),
+        params.as_ref(),
+        &facts,
+        secrets_dir,
+        identity_path,
+    )?
+    .run()
+    .await;
+    if let Some(worktree) = worktree {
+        worktree.remove().await?;
+    }
+
+    serde_json::from_slice(&stdout?).map_err(AppError::ParseApplyStdoutJson)
+}
+
+async fn cmd_import_ansible(playbook: PathBuf) -> Result<(), AppError> {
+    print!("{}", import_ansible(&playbook).await?);
+    Ok(())
+}
+
+// `scan`: see `scan`. It only reads, over the same connection as an apply.
+async fn cmd_scan(
+    config: Config,
+    machine_id: String,
+    kinds: Vec<ScanKind>,
+    paths: Vec<String>,
+) -> Result<(), AppError> {
+    let MachineConfig { remote, .. } = config.get_machine(&machine_id)?;
+    let remote = remote.ok_or_else(|| AppError::NotRemote {
+        machine_id: machine_id.clone(),
+    })?;
+    let keypair = SshKeypair::load_private(&remote.key).await?;
+    let mut ssh = connect_remote(&remote, &keypair, &remote.user).await?;
+    let plan = scan(&mut ssh, &machine_id, &kinds, &paths).await;
+    ssh.disconnect().await?;
+    print!("{}", plan?);
+    Ok(())
+}
+
+fn cmd_resource_list() -> Result<(), AppError> {
+    let mut table = Table::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL)
+        .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .set_header(vec!["module", "platforms", "description"]);
+    for doc in resource_docs() {
+        table.add_row(vec![
+            format!("@core/{}", doc.id),
+            doc.platforms.join(", "),
+            doc.description.to_owned(),
+        ]);
+    }
+    println!("{table}");
+    Ok(())
+}
+
+fn cmd_resource_docs(id: String) -> Result<(), AppError> {
+    let doc = resource_doc(&id).ok_or(AppError::UnknownResource { id })?;
+    print!("{doc}");
+    Ok(())
+}
+
+fn cmd_resource_schema() -> Result<(), AppError> {
+    println!("{}", serde_json::to_string_pretty(&json_schema())?);
+    Ok(())
+}
+
+async fn cmd_secrets(
+    command: SecretsCommand,
+    secrets_dir: PathBuf,
+    identity_path: Option<PathBuf>,
+) -> Result<(), AppError> {
+    let env = SecretsCliEnv {
+        secrets_dir,
+        identity_path,
+    };
+    lusid_secrets::cli::run(command, env).await?;
+    Ok(())
+}
+
+// Applies the local machine, then records it as a new generation (see
+// `generations`) if `lusid-apply` ran to a successful exit.
+async fn cmd_local_apply(
+    config: Config,
+    secrets_dir: PathBuf,
+    identity_path: Option<PathBuf>,
+    raw: bool,
+    allow_destructive: bool,
+) -> Result<(), AppError> {
+    let (machine_id, machine_config) = config.local_machine()?;
+    let facts = machine_config.facts(&machine_id);
+    let MachineConfig {
+        plan,
+        params,
+        apply,
+        ..
+    } = machine_config;
+    let root = config.root();
+    let params = params.map(serde_json::to_value).transpose()?;
+
+    let mut command = local_apply_command(
+        &apply,
+        root,
+        &plan,
+        params.as_ref(),
+        &facts,
+        &secrets_dir,
+        identity_path.as_deref(),
+    )?;
+    if allow_destructive {
+        command.arg("--allow-destructive");
+    }
+    if apply.check {
+        command.arg("--check");
+    }
+    let succeeded = run_local_apply(&config, &machine_id, command, raw).await?;
+
+    // The TUI has already shown a failure; whatever reads raw output wants
+    // the exit status instead.
+    if raw && !succeeded {
+        return Err(AppError::ApplyFailed);
+    }
+
+    // A check changed nothing, so there's no new generation.
+    if succeeded && !apply.check {
+        record_generation(&machine_id, root, &plan, params).await?;
+    }
+
+    Ok(())
+}
+
+// Record a successful apply of `plan` as the local machine's next
+// generation.
+async fn record_generation(
+    machine_id: &str,
+    root: &Path,
+    plan: &Path,
+    params: Option<serde_json::Value>,
+) -> Result<Generation, AppError> {
+    let generation = Generations::open(machine_id)?
+        .record(NewGeneration {
+            plan: plan.strip_prefix(root).unwrap_or(plan).to_owned(),
+            revision: current_revision(root).await,
+            params,
+            rollback_of: None,
+        })
+        .await?;
+    info!(
+        machine_id,
+        generation = generation.number,
+        "recorded generation"
+    );
+    Ok(generation)
+}
+
+// Spawns `lusid-apply` (see `local_apply_command`) as a subprocess and pipes
+// its stdout + stderr into the TUI, or with `raw` straight through (see
+// `run_raw_apply`), logging both as a new run and firing its hooks. Returns
+// whether the apply exited successfully — `false` also covers quitting the
+// TUI before it finished.
+async fn run_local_apply(
+    config: &Config,
+    machine_id: &str,
+    mut command: Command,
+    raw: bool,
+) -> Result<bool, AppError> {
+    add_config_args(config, &mut command);
+    let run = Run::create().await?;
+    let mut log = run.machine(machine_id).await?;
+    hooks::fire(&config.hooks, &HookPayload::start(run.id(), machine_id)).await;
+    let result = if raw {
+        run_raw_apply(command, &mut log).await
+    } else {
+        run_tui_apply(config, command, &mut log).await
+    };
+    let payload = HookPayload::finished(
+        run.id(),
+        machine_id,
+        matches!(result, Ok(true)),
+        log.report().clone(),
+        result.as_ref().err().map(ToString::to_string),
+    );
+    finish_log(&run, log).await;
+    hooks::fire(&config.hooks, &payload).await;
+    result
+}
+
+async fn run_tui_apply(
+    config: &Config,
+    mut command: Command,
+    log: &mut MachineLog,
+) -> Result<bool, AppError> {
+    command.arg("--control");
+    let (control, output) = command.output_with_stdin().await?;
+
+    let succeeded = Arc::new(AtomicBool::new(false));
+    let wait = Box::pin({
+        let succeeded = succeeded.clone();
+        async move {
+            let status = output.status.await?;
+            succeeded.store(status.success(), Ordering::SeqCst);
+            Ok::<_, CommandError>(())
+        }
+    });
+    tui(
+        output.stdout,
+        output.stderr,
+        control,
+        wait,
+        Some(log),
+        config.tui_options(),
+    )
+    .await?;
+
+    Ok(succeeded.load(Ordering::SeqCst))
+}
+
+// `local apply --raw`: re-emit each `AppUpdate` line from `lusid-apply` on
+// stdout as `{"time":<unix ms>,"update":<update>}` (see `stamp_update`),
+// stamped as it arrives, and forward its stderr as-is. Lines are checked to
+// be JSON but otherwise passed through untouched, so the output keeps up
+// with the protocol.
+async fn run_raw_apply(mut command: Command, log: &mut MachineLog) -> Result<bool, AppError> {
+    let CommandOutput {
+        stdout,
+        stderr,
+        status,
+    } = command.output().await?;
+
+    let mut stdout_lines = BufReader::new(stdout).lines();
+    let mut stderr_lines = BufReader::new(stderr).lines();
+    let mut stdout_done = false;
+    let mut stderr_done = false;
+    let mut out = tokio::io::stdout();
+    let mut err = tokio::io::stderr();
+
+    while !(stdout_done && stderr_done) {
+        tokio::select! {
+            line = stdout_lines.next_line(), if !stdout_done => {
+                match line.map_err(AppError::ReadApplyStdout)? {
+                    None => stdout_done = true,
+                    Some(line) if line.trim().is_empty() => {}
+                    Some(line) => {
+                        serde_json::from_str::<serde::de::IgnoredAny>(&line)
+                            .map_err(AppError::ParseApplyStdoutJson)?;
+                        log.update(&line).await;
+                        let record = stamp_update(now_millis(), &line);
+                        out.write_all(record.as_bytes())
+                            .await
+                            .map_err(AppError::WriteStdout)?;
+                        out.flush().await.map_err(AppError::WriteStdout)?;
+                    }
+                }
+            }
+
+            line = stderr_lines.next_line(), if !stderr_done => {
+                match line.map_err(AppError::ForwardApplyStderr)? {
+                    None => stderr_done = true,
+                    Some(line) => {
+                        log.stderr(&line).await;
+                        err.write_all(format!("{line}\n").as_bytes())
+                            .await
+                            .map_err(AppError::ForwardApplyStderr)?;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(status.await?.success())
+}
+
+// Flush a machine's run log and say where to find it. A log that couldn't
+// be written is worth a warning, not failing an apply that otherwise worked.
+async fn finish_log(run: &Run, log: MachineLog) {
+    match log.finish().await {
+        Ok(()) => eprintln!("Logs: lusid logs {}", run.id()),
+        Err(error) => error!(run_id = run.id(), %error, "failed to write run log"),
+    }
+}
+
+// `agent`: check the local machine every interval, and with `--apply` apply
+// it when the check finds changes, keeping its state and reports and
+// optionally serving health and metrics endpoints (see `agent`). Runs until
+// interrupted.
+async fn cmd_agent(
+    config: Config,
+    secrets_dir: PathBuf,
+    identity_path: Option<PathBuf>,
+    options: AgentOptions,
+) -> Result<(), AppError> {
+    let (machine_id, _) = config.local_machine()?;
+    let dir = AgentDir::open(&machine_id)?;
+    let state = Arc::new(Mutex::new(dir.load_state(&machine_id).await));
+    if let Some(addr) = options.listen {
+        let listener = agent::listen(addr).await?;
+        info!(%addr, "serving /healthz, /readyz and /metrics");
+        tokio::spawn(serve_health(listener, state.clone()));
+    }
+
+    let mut ticks = tokio::time::interval(options.interval);
+    // A cycle that runs past the interval pushes the next one back, rather
+    // than the missed ones running back to back.
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = ticks.tick() => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+
+        let cycle = agent_cycle(
+            &config,
+            &machine_id,
+            &secrets_dir,
+            identity_path.as_deref(),
+            &options,
+        )
+        .await;
+        match &cycle.error {
+            Some(error) => error!(machine_id, %error, "agent cycle failed"),
+            None => info!(machine_id, status = ?cycle.status, "agent cycle finished"),
+        }
+        // Best-effort, like run logs: the next cycle tries again.
+        if let Err(error) = dir.write_report(&cycle).await {
+            error!(%error, "failed to write agent report");
+        }
+        let snapshot = {
+            let mut state = state.lock().expect("agent state lock poisoned");
+            state.record(cycle);
+            state.clone()
+        };
+        if let Err(error) = dir.save_state(&snapshot).await {
+            error!(%error, "failed to write agent state");
+        }
+    }
+}
+
+// One `agent` cycle. Errors end up in the report rather than being
+// returned, so the agent carries on.
+async fn agent_cycle(
+    config: &Config,
+    machine_id: &str,
+    secrets_dir: &Path,
+    identity_path: Option<&Path>,
+    options: &AgentOptions,
+) -> CycleReport {
+    let mut cycle = CycleReport::new(now_secs());
+    let result = agent_passes(
+        &mut cycle,
+        config,
+        machine_id,
+        secrets_dir,
+        identity_path,
+        options,
+    )
+    .await;
+    match result {
+        Ok(status) => cycle.finished(status),
+        Err(error) => cycle.failed(error.to_string()),
+    }
+}
+
+// Check the local machine, then apply it if the check found changes and
+// `options.apply` is set, filling in `cycle` as each pass finishes.
+async fn agent_passes(
+    cycle: &mut CycleReport,
+    config: &Config,
+    machine_id: &str,
+    secrets_dir: &Path,
+    identity_path: Option<&Path>,
+    options: &AgentOptions,
+) -> Result<CycleStatus, AppError> {
+    let machine_config = config.get_machine(machine_id)?;
+    let facts = machine_config.facts(machine_id);
+    let MachineConfig {
+        plan,
+        params,
+        apply,
+        ..
+    } = machine_config;
+    let root = config.root();
+    let params = params.map(serde_json::to_value).transpose()?;
+    let command = || {
+        local_apply_command(
+            &apply,
+            root,
+            &plan,
+            params.as_ref(),
+            &facts,
+            secrets_dir,
+            identity_path,
+        )
+    };
+
+    let mut check = command()?;
+    check.arg("--check");
+    let run = Run::create().await?;
+    cycle.check_run_id = Some(run.id().to_owned());
+    let mut stages = StageTimer::new(Instant::now());
+    let report = run_local_headless(config, &run, machine_id, check, "check", |update| {
+        stages.record(update, Instant::now())
+    })
+    .await?;
+    cycle.check_stages = stages.durations();
+    let failure = report.failure().map(str::to_owned);
+    let changed = !report.changes.is_empty();
+    cycle.check = Some(report);
+    if let Some(error) = failure {
+        return Err(AgentError::PassFailed {
+            pass: "check",
+            error,
+        }
+        .into());
+    }
+    if !changed {
+        return Ok(CycleStatus::InSync);
+    }
+    if !options.apply || apply.check {
+        return Ok(CycleStatus::Drifted);
+    }
+
+    let mut apply = command()?;
+    if options.allow_destructive {
+        apply.arg("--allow-destructive");
+    }
+    let run = Run::create().await?;
+    cycle.apply_run_id = Some(run.id().to_owned());
+    hooks::fire(&config.hooks, &HookPayload::start(run.id(), machine_id)).await;
+    let mut stages = StageTimer::new(Instant::now());
+    let result = run_local_headless(config, &run, machine_id, apply, "apply", |update| {
+        stages.record(update, Instant::now())
+    })
+    .await;
+    cycle.apply_stages = stages.durations();
+    let payload = match &result {
+        Ok(report) => HookPayload::finished(
+            run.id(),
+            machine_id,
+            report.succeeded(),
+            report.clone(),
+            None,
+        ),
+        Err(error) => HookPayload::finished(
+            run.id(),
+            machine_id,
+            false,
+            ApplyReport::default(),
+            Some(error.to_string()),
+        ),
+    };
+    hooks::fire(&config.hooks, &payload).await;
+    let report = result?;
+    let failure = report.failure().map(str::to_owned);
+    cycle.apply = Some(report);
+    if let Some(error) = failure {
+        return Err(AgentError::PassFailed {
+            pass: "apply",
+            error,
+        }
+        .into());
+    }
+
+    let generation = record_generation(machine_id, root, &plan, params).await?;
+    cycle.generation = Some(generation.number);
+    Ok(CycleStatus::Applied)
+}
+
+// `lusid-apply` on this host without the TUI, logged to `run`: progress and
+// stderr lines go to stderr, and its updates are folded into a report and
+// handed to `observe`.
+async fn run_local_headless(
+    config: &Config,
+    run: &Run,
+    machine_id: &str,
+    mut command: Command,
+    label: &str,
+    observe: impl FnMut(&AppUpdate),
+) -> Result<ApplyReport, AppError> {
+    add_config_args(config, &mut command);
+    let mut log = run.machine(machine_id).await?;
+    let result = async {
+        let CommandOutput {
+            stdout,
+            stderr,
+            status,
+        } = command.output().await?;
+        let mut report = fold_apply_output(
+            BufReader::new(stdout),
+            BufReader::new(stderr),
+            &mut log,
+            label,
+            observe,
+        )
+        .await?;
+        let status = status.await?;
+        if !status.success() && report.error.is_none() {
+            report.error = Some(format!("lusid-apply exited with {status}"));
+        }
+        Ok::<_, AppError>(report)
+    }
+    .await;
+    finish_log(run, log).await;
+    result
+}
+
+async fn cmd_logs(
+    run_id: Option<String>,
+    machine_id: Option<String>,
+    updates: bool,
+) -> Result<(), AppError> {
+    match run_id {
+        None => print_runs(&list_runs().await?),
+        Some(run_id) => print!(
+            "{}",
+            read_log(&run_id, machine_id.as_deref(), updates).await?
+        ),
+    }
+    Ok(())
+}
+
+async fn cmd_history(config: Config) -> Result<(), AppError> {
+    history(config.tui_options()).await?;
+    Ok(())
+}
+
+async fn cmd_generations_list(config: Config) -> Result<(), AppError> {
+    let (machine_id, _) = config.local_machine()?;
+    let generations = Generations::open(&machine_id)?.list().await?;
+    print_generations(&generations);
+    Ok(())
+}
+
+// Re-applies generation `number` of the local machine from a scratch git
+// worktree at its recorded revision, with its recorded params, and records
+// the result as a new generation.
+//
+// Note(cc): secrets come from the *current* secrets dir, not the old
+// revision's — the operator identity may have been rotated since, and old
+// ciphertexts wouldn't decrypt.
+async fn cmd_rollback(
+    config: Config,
+    number: u32,
+    secrets_dir: PathBuf,
+    identity_path: Option<PathBuf>,
+) -> Result<(), AppError> {
+    let (machine_id, machine_config) = config.local_machine()?;
+    let facts = machine_config.facts(&machine_id);
+    let generations = Generations::open(&machine_id)?;
+    let generation = generations.get(number).await?;
+
+    let worktree = Worktree::checkout(config.root(), &machine_id, &generation).await?;
+    let root = worktree.project_root().to_owned();
+    let plan = root.join(&generation.plan);
+
+    let result = async {
+        let mut command = local_apply_command(
+            &machine_config.apply,
+            &root,
+            &plan,
+            generation.params.as_ref(),
+            &facts,
+            &secrets_dir,
+            identity_path.as_deref(),
+        )?;
+        if machine_config.apply.check {
+            command.arg("--check");
+        }
+        run_local_apply(&config, &machine_id, command, false).await
+    }
+    .await;
+    worktree.remove().await?;
+
+    if result? && !machine_config.apply.check {
+        let recorded = generations
+            .record(NewGeneration {
+                plan: generation.plan,
+                revision: generation.revision,
+                params: generation.params,
+                rollback_of: Some(number),
+            })
+            .await?;
+        info!(
+            machine_id,
+            generation = recorded.number,
+            rollback_of = number,
+            "recorded generation"
+        );
+    }
+
+    Ok(())
+}
+
+// TODO(cc): implement remote apply/ssh. Expected shape: resolve the machine
+// from config, connect to it with `connect_remote`, upload the plan +
+// lusid-apply binary with `prepare_remote_apply` (as `remote drift` and
+// `verify` do), run apply, and pipe through the TUI — essentially
+// `cmd_dev_*` without the VM bring-up.
+//
+// Secrets strategy: mirror `cmd_dev_apply`'s per-target re-encryption, with
+// two substitutions:
+//   - Recipient key is the target machine's entry in `lusid-secrets.toml`'s
+//     `[machines]` table — looked up by `machine_id` — rather than an
+//     ephemeral VM auth key. `lusid_secrets` will need a small helper to
+//     expose that lookup; the CLI doesn't have `Recipients` access directly.
+//   - Guest identity is the target's existing
+//     `/etc/ssh/ssh_host_ed25519_key` on the machine itself — nothing is
+//     SFTP'd for the identity; just pass `--identity=/etc/ssh/ssh_host_ed25519_key`
+//     (plus `--guest-mode --secrets-dir=...`). Requires the guest
+//     `lusid-apply` to run as root, which it typically does already.
+//
+// Connections: keep one `Ssh` per target for the whole command; uploads and
+// the apply itself each open a channel on it. Resource state probes run
+// inside `lusid-apply` on the target, so they never cross the link; anything
+// probed from this host (like `doctor`'s checks) should go through
+// `Ssh::output_batch`, one round trip for all of them.
+async fn cmd_remote_apply(_config: Config, _machine_id: String) -> Result<(), AppError> {
+    todo!()
+}
+
+async fn cmd_remote_ssh(_config: Config, _machine_id: String) -> Result<(), AppError> {
+    todo!()
+}
+
+// `remote clean`: remove the machine's staging directory, as `dev clean`
+// does in its dev VM.
+async fn cmd_remote_clean(config: Config, machine_id: String) -> Result<(), AppError> {
+    let MachineConfig {
+        staging_dir,
+        remote,
+        ..
+    } = config.get_machine(&machine_id)?;
+    let remote = remote.ok_or_else(|| AppError::NotRemote {
+        machine_id: machine_id.clone(),
+    })?;
+    let keypair = SshKeypair::load_private(&remote.key).await?;
+    let mut ssh = connect_remote(&remote, &keypair, &remote.user).await?;
+
+    let staging = StagingDir::resolve(&mut ssh, &staging_dir).await?;
+    staging.clean(&mut ssh).await?;
+    info!(
+        machine_id,
+        staging_dir = staging.root(),
+        "removed staging directory"
+    );
+
+    ssh.disconnect().await?;
+
+    Ok(())
+}
+
+// `doctor --machine`: run the preflight checks over the machine's configured
+// SSH connection, as `doctor --dev` does in its dev VM.
+async fn cmd_remote_doctor(config: Config, machine_id: String) -> Result<(), AppError> {
+    let MachineConfig {
+        machine,
+        staging_dir,
+        apply,
+        remote,
+        ..
+    } = config.get_machine(&machine_id)?;
+    let remote = remote.ok_or_else(|| AppError::NotRemote {
+        machine_id: machine_id.clone(),
+    })?;
+    let keypair = SshKeypair::load_private(&remote.key).await?;
+
+    let target = DoctorTarget {
+        machine: &machine,
+        apply_bin: &apply.bin,
+        staging_dir: &staging_dir,
+    };
+
+    let checks = match connect_remote(&remote, &keypair, &remote.user).await {
+        Ok(mut ssh) => {
+            let mut checks = vec![Check::pass(
+                "ssh",
+                format!(
+                    "connected as {}@{}:{}",
+                    remote.user, remote.host, remote.port
+                ),
+            )];
+            checks.extend(run_checks(&mut ssh, &target).await);
+            ssh.disconnect().await?;
+            checks
+        }
+        Err(error) => unreachable(&error),
+    };
+
+    print_checks(&checks);
+
+    let failed = checks
+        .iter()
+        .filter(|check| check.status == CheckStatus::Fail)
+        .count();
+    if failed > 0 {
+        return Err(AppError::DoctorFailed { failed });
+    }
+    Ok(())
+}
+
+// `verify --machine`: apply the machine's plan over its SSH connection, then
+// check nothing is left to change, as `verify --dev` does in its dev VM.
+// Secrets aren't forwarded (see `prepare_remote_apply`).
+async fn cmd_remote_verify(config: Config, machine_id: String) -> Result<(), AppError> {
+    let machine_config = config.get_machine(&machine_id)?;
+    let remote = machine_config
+        .remote
+        .as_ref()
+        .ok_or_else(|| AppError::NotRemote {
+            machine_id: machine_id.clone(),
+        })?;
+    let keypair = SshKeypair::load_private(&remote.key).await?;
+    let mut ssh = connect_remote(remote, &keypair, &remote.user).await?;
+    let command = prepare_remote_apply(&config, &machine_id, &machine_config, &mut ssh).await?;
+
+    verify_apply(ssh, &command, machine_id).await
+}
+
+// `remote bootstrap`: see `bootstrap` for the steps. The configured user is
+// only set up when logging in as someone else, since a user can't grant
+// itself sudo.
+async fn cmd_remote_bootstrap(
+    config: Config,
+    machine_id: String,
+    login_user: Option<String>,
+) -> Result<(), AppError> {
+    let MachineConfig {
+        machine,
+        staging_dir,
+        apply,
+        remote,
+        ..
+    } = config.get_machine(&machine_id)?;
+    let remote = remote.ok_or_else(|| AppError::NotRemote {
+        machine_id: machine_id.clone(),
+    })?;
+    let keypair = SshKeypair::load_private(&remote.key).await?;
+
+    if let Some(login_user) = login_user.filter(|login_user| *login_user != remote.user) {
+        let mut ssh = connect_remote(&remote, &keypair, &login_user).await?;
+        let script = bootstrap::setup_user_script(&remote.user, &keypair.public_openssh()?);
+        let output = ssh.output(&script).await?;
+        ssh.disconnect().await?;
+        if !output.success() {
+            return Err(AppError::SetupUser {
+                user: remote.user,
+                login_user,
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+            });
+        }
+        info!(machine_id, user = remote.user, "set up user");
+    }
+
+    let mut ssh = connect_remote(&remote, &keypair, &remote.user).await?;
+    let staging = StagingDir::resolve(&mut ssh, &staging_dir).await?;
+    ssh.sync(SshVolume::FilePath {
+        local: which(&apply.bin)?,
+        remote: staging.apply_bin(),
+    })
+    .await?;
+    info!(
+        machine_id,
+        path = staging.apply_bin(),
+        "uploaded lusid-apply"
+    );
+
+    let target = DoctorTarget {
+        machine: &machine,
+        apply_bin: &apply.bin,
+        staging_dir: &staging_dir,
+    };
+    let mut checks = vec![Check::pass(
+        "ssh",
+        format!(
+            "connected as {}@{}:{}",
+            remote.user, remote.host, remote.port
+        ),
+    )];
+    checks.extend(run_checks(&mut ssh, &target).await);
+    ssh.disconnect().await?;
+
+    print_checks(&checks);
+
+    let failed = checks
+        .iter()
+        .filter(|check| check.status == CheckStatus::Fail)
+        .count();
+    if failed > 0 {
+        return Err(AppError::DoctorFailed { failed });
+    }
+    Ok(())
+}
+
+// Connect to a remote machine as `user`, pinned to its configured host key.
+// Generous on time, as a freshly created cloud VM may still be booting.
+async fn connect_remote(
+    remote: &RemoteConfig,
+    keypair: &SshKeypair,
+    user: &str,
+) -> Result<Ssh, AppError> {
+    let ssh = Ssh::connect(SshConnectOptions {
+        private_key: keypair.private_key.clone(),
+        host_key: parse_public_key(&remote.host_key)?,
+        addrs: (remote.host.clone(), remote.port),
+        username: user.to_owned(),
+        config: Arc::new(Default::default()),
+        timeout: Duration::from_secs(60),
+    })
+    .await?;
+    Ok(ssh)
+}
+
+// `remote drift`: see `drift`. Each machine is checked in a task of its own,
+// over its own connection, at most `jobs` at once; their logs share one run.
+async fn cmd_remote_drift(
+    config: Config,
+    group: String,
+    output: DriftOutput,
+    jobs: usize,
+) -> Result<(), AppError> {
+    let machines = group_machines(&config, &group);
+    if machines.is_empty() {
+        return Err(AppError::NoRemoteMachines { group });
+    }
+
+    let config = Arc::new(config);
+    let run = Arc::new(Run::create().await?);
+    let permits = Arc::new(Semaphore::new(jobs.max(1)));
+    let mut checks = JoinSet::new();
+    for (machine_id, machine_config) in machines {
+        let config = config.clone();
+        let run = run.clone();
+        let permits = permits.clone();
+        checks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            check_remote_drift(&config, &run, &machine_id, &machine_config)
+                .await
+                .unwrap_or_else(|error| MachineDrift::failed(machine_id, error.to_string()))
+        });
+    }
+    let mut drifts = Vec::new();
+    while let Some(drift) = checks.join_next().await {
+        match drift {
+            Ok(drift) => drifts.push(drift),
+            Err(error) => std::panic::resume_unwind(error.into_panic()),
+        }
+    }
+    eprintln!("Logs: lusid logs {}", run.id());
+
+    let report = DriftReport::new(group, drifts);
+    match output {
+        DriftOutput::Text => report.print_table(),
+        DriftOutput::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+    }
+    if let Some(payload) = HookPayload::drift(run.id(), &report) {
+        hooks::fire(&config.hooks, &payload).await;
+    }
+    match report.failed() {
+        0 => Ok(()),
+        failed => Err(AppError::DriftCheckFailed { failed }),
+    }
+}
+
+// One machine's `remote drift` check: upload as for an apply, then run
+// `lusid-apply --check`, logging to `run`.
+async fn check_remote_drift(
+    config: &Config,
+    run: &Run,
+    machine_id: &str,
+    machine_config: &MachineConfig,
+) -> Result<MachineDrift, AppError> {
+    let remote = machine_config
+        .remote
+        .as_ref()
+        .ok_or_else(|| AppError::NotRemote {
+            machine_id: machine_id.to_owned(),
+        })?;
+    let keypair = SshKeypair::load_private(&remote.key).await?;
+    let mut ssh = connect_remote(remote, &keypair, &remote.user).await?;
+    let command = prepare_remote_apply(config, machine_id, machine_config, &mut ssh).await?;
+
+    let mut log = run.machine(machine_id).await?;
+    let mut recorder = DriftRecorder::default();
+    let report = headless_apply(
+        &mut ssh,
+        &format!("{command} --check"),
+        &mut log,
+        machine_id,
+        |update| recorder.record(update),
+    )
+    .await;
+    if let Err(error) = log.finish().await {
+        error!(run_id = run.id(), %error, "failed to write run log");
+    }
+    ssh.disconnect().await?;
+
+    Ok(MachineDrift::checked(
+        machine_id.to_owned(),
+        &report?,
+        &recorder,
+    ))
+}
+
+// `dev apply`: boot a local QEMU VM matching the machine spec, upload the
+// plan directory and a prebuilt `lusid-apply` binary over SFTP into the
+// machine's staging directory (see `staging`), then run
+// apply remotely and stream its stdout/stderr through the TUI just like
+// local apply. The VM's SSH keypair lives inside its instance dir (see
+// `lusid_vm`).
+//
+// Secrets are forwarded via per-target re-encryption: when `identity_path`
+// is set, the host decrypts every `*.age` with the operator identity,
+// re-encrypts each plaintext to the VM's SSH keypair alone, ships the
+// ciphertexts to `<staging>/secrets/`, and points the guest's
+// `lusid-apply` at `<staging>/identity` (the same VM keypair in OpenSSH
+// PEM form) via `--identity --guest-mode`. The operator identity never
+// leaves the host.
+async fn cmd_dev_apply(
+    config: Config,
+    machine_id: String,
+    secrets_dir: PathBuf,
+    identity_path: Option<PathBuf>,
+    allow_destructive: bool,
+) -> Result<(), AppError> {
+    let machine_config = config.get_machine(&machine_id)?;
+
+    let mut ctx = create_context(&config);
+
+    let options = VmOptions {
+        instance_id: &machine_id,
+        machine: &machine_config.machine,
+        ports: vec![],
+    };
+    let vm = Vm::run(&mut ctx, options).await?;
+
+    let mut ssh = connect_vm(&vm).await?;
+    let command = prepare_dev_apply(
+        &config,
+        &machine_id,
+        &machine_config,
+        &vm,
+        &mut ssh,
+        &secrets_dir,
+        identity_path.as_deref(),
+    )
+    .await?;
+    let mut command = format!("{command} --control");
+    if allow_destructive {
+        command.push_str(" --allow-destructive");
+    }
+    if machine_config.apply.check {
+        command.push_str(" --check");
+    }
+
+    let run = Run::create().await?;
+    let mut log = run.machine(&machine_id).await?;
+
+    let mut handle = ssh.command(&command).await?;
+    let control = Box::pin(handle.channel.stdin());
+    let wait = Box::pin(async move {
+        handle.channel.wait().await?;
+        Ok::<_, SshError>(())
+    });
+
+    let result = tui(
+        &mut handle.stdout,
+        &mut handle.stderr,
+        control,
+        wait,
+        Some(&mut log),
+        config.tui_options(),
+    )
+    .await;
+    finish_log(&run, log).await;
+    result?;
+
+    ssh.disconnect().await?;
+
+    Ok(())
+}
+
+// `dev apply --ci`: apply in a throwaway VM (`<machine>-ci`, so the machine's
+// own dev VM is left alone), then apply again and require that nothing
+// changes. Progress goes to stderr as plain lines, the `CiReport` to stdout
+// as JSON, and the VM is deleted whatever happened.
+//
+// Note(cc): a CI run killed before teardown leaves its VM behind, and the
+// next one reuses it rather than starting fresh. Delete
+// `<data_dir>/vm/instances/<machine>-ci` to reset it.
+async fn cmd_dev_apply_ci(
+    config: Config,
+    machine_id: String,
+    secrets_dir: PathBuf,
+    identity_path: Option<PathBuf>,
+) -> Result<(), AppError> {
+    let mut machine_config = config.get_machine(&machine_id)?;
+    // CI runners have no display for a QEMU window.
+    machine_config.machine.vm.get_or_insert_default().graphics = Some(false);
+
+    let mut ctx = create_context(&config);
+
+    let instance_id = format!("{machine_id}-ci");
+    let options = VmOptions {
+        instance_id: &instance_id,
+        machine: &machine_config.machine,
+        ports: vec![],
+    };
+    let vm = Vm::run(&mut ctx, options).await?;
+
+    let result = async {
+        let mut ssh = connect_vm(&vm).await?;
+        let command = prepare_dev_apply(
+            &config,
+            &machine_id,
+            &machine_config,
+            &vm,
+            &mut ssh,
+            &secrets_dir,
+            identity_path.as_deref(),
+        )
+        .await?;
+        // The VM is thrown away after, so there's nothing to protect.
+        let command = format!("{command} --allow-destructive");
+
+        let apply = run_headless_apply(&mut ssh, &command, &machine_id, "apply").await?;
+        let reapply = if apply.succeeded() {
+            Some(run_headless_apply(&mut ssh, &command, &machine_id, "reapply").await?)
+        } else {
+            None
+        };
+
+        ssh.disconnect().await?;
+        Ok::<_, AppError>(CiReport::new(machine_id.clone(), apply, reapply))
+    }
+    .await;
+
+    if let Err(error) = vm.stop().await {
+        error!(%error, "failed to stop CI VM");
+    }
+    if let Err(error) = vm.remove().await {
+        error!(%error, "failed to remove CI VM");
+    }
+
+    let report = result?;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+
+    if !report.converged {
+        return Err(AppError::NotConverged { machine_id });
+    }
+    Ok(())
+}
+
+// One headless apply for `dev apply --ci` or `verify`, with its own run log.
+// Progress lines are prefixed with `label` so consecutive applies can be told
+// apart.
+async fn run_headless_apply(
+    ssh: &mut Ssh,
+    command: &str,
+    machine_id: &str,
+    label: &str,
+) -> Result<ApplyReport, AppError> {
+    let run = Run::create().await?;
+    let mut log = run.machine(machine_id).await?;
+    let result = headless_apply(ssh, command, &mut log, label, |_| {}).await;
+    finish_log(&run, log).await;
+    result
+}
+
+// Run `command` over `ssh` and fold its updates into a report, logging them
+// to `log` and handing each to `observe` too.
+async fn headless_apply(
+    ssh: &mut Ssh,
+    command: &str,
+    log: &mut MachineLog,
+    label: &str,
+    observe: impl FnMut(&AppUpdate),
+) -> Result<ApplyReport, AppError> {
+    let mut handle = ssh.command(command).await?;
+    let mut report =
+        fold_apply_output(&mut handle.stdout, &mut handle.stderr, log, label, observe).await?;
+
+    let exit_code = handle.channel.wait().await?;
+    if exit_code != Some(0) && report.error.is_none() {
+        report.error = Some(match exit_code {
+            Some(code) => format!("lusid-apply exited with code {code}"),
+            None => "lusid-apply exited without an exit code".to_owned(),
+        });
+    }
+    Ok(report)
+}
+
+// Fold `lusid-apply`'s stdout updates into a report until both streams end,
+// logging them to `log`, handing each to `observe`, and printing progress
+// and stderr lines on stderr.
+async fn fold_apply_output(
+    stdout: impl AsyncBufRead + Unpin,
+    stderr: impl AsyncBufRead + Unpin,
+    log: &mut MachineLog,
+    label: &str,
+    mut observe: impl FnMut(&AppUpdate),
+) -> Result<ApplyReport, AppError> {
+    let mut report = ApplyReport::default();
+
+    let mut stdout_lines = stdout.lines();
+    let mut stderr_lines = stderr.lines();
+    let mut stdout_done = false;
+    let mut stderr_done = false;
+
+    while !(stdout_done && stderr_done) {
+        tokio::select! {
+            line = stdout_lines.next_line(), if !stdout_done => {
+                match line.map_err(AppError::ReadApplyStdout)? {
+                    None => stdout_done = true,
+                    Some(line) if line.trim().is_empty() => {}
+                    Some(line) => {
+                        let update: AppUpdate = serde_json::from_str(&line)
+                            .map_err(AppError::ParseApplyStdoutJson)?;
+                        log.update(&line).await;
+                        observe(&update);
+                        if let Some(progress) = report.record(&update) {
+                            eprintln!("{label}: {progress}");
+                        }
+                    }
+                }
+            }
+
+            line = stderr_lines.next_line(), if !stderr_done => {
+                match line.map_err(AppError::ForwardApplyStderr)? {
+                    None => stderr_done = true,
+                    Some(line) => {
+                        log.stderr(&line).await;
+                        eprintln!("{line}");
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+// `verify --dev`: apply the machine's plan in its dev VM, then run
+// `lusid-apply --check` against the result. Any change the check still plans
+// is a resource that doesn't converge, like a command without a guard; each
+// is printed on stdout and the command fails.
+async fn cmd_dev_verify(
+    config: Config,
+    machine_id: String,
+    secrets_dir: PathBuf,
+    identity_path: Option<PathBuf>,
+) -> Result<(), AppError> {
+    let machine_config = config.get_machine(&machine_id)?;
+
+    let mut ctx = create_context(&config);
+
+    let options = VmOptions {
+        instance_id: &machine_id,
+        machine: &machine_config.machine,
+        ports: vec![],
+    };
+    let vm = Vm::run(&mut ctx, options).await?;
+
+    let mut ssh = connect_vm(&vm).await?;
+    let command = prepare_dev_apply(
+        &config,
+        &machine_id,
+        &machine_config,
+        &vm,
+        &mut ssh,
+        &secrets_dir,
+        identity_path.as_deref(),
+    )
+    .await?;
+
+    verify_apply(ssh, &command, machine_id).await
+}
+
+// Run `command`, then the same with `--check`, and fail if the check still
+// plans any change, printing each on stdout.
+async fn verify_apply(mut ssh: Ssh, command: &str, machine_id: String) -> Result<(), AppError> {
+    let apply = run_headless_apply(&mut ssh, command, &machine_id, "apply").await?;
+    if !apply.succeeded() {
+        ssh.disconnect().await?;
+        return Err(AppError::ApplyFailed);
+    }
+    let check_command = format!("{command} --check");
+    let check = run_headless_apply(&mut ssh, &check_command, &machine_id, "check").await?;
+    ssh.disconnect().await?;
+
+    if !check.succeeded() {
+        return Err(AppError::ApplyFailed);
+    }
+    if !check.changes.is_empty() {
+        for change in &check.changes {
+            println!("not idempotent: {change}");
+        }
+        return Err(AppError::NotIdempotent {
+            machine_id,
+            count: check.changes.len(),
+        });
+    }
+
+    println!("{machine_id}: applied, and nothing is left to change");
+    Ok(())
+}
+
+async fn connect_vm(vm: &Vm) -> Result<Ssh, AppError> {
+    let ssh = Ssh::connect(SshConnectOptions {
+        private_key: vm.ssh_keypair().await?.private_key,
+        host_key: vm.ssh_host_key()?,
+        addrs: (Ipv4Addr::LOCALHOST, vm.ssh_port),
+        username: vm.user.clone(),
+        config: Arc::new(Default::default()),
+        timeout: Duration::from_secs(10),
+    })
+    .await?;
+    Ok(ssh)
+}
+
+// The `lusid-apply` flags `lusid.toml` sets for every apply on this host.
+fn add_config_args(config: &Config, command: &mut Command) {
+    command.args(protect_args(&config.protect));
+    command.args(wait_for_locks_args(config.wait_for_locks));
+}
+
+// `lusid-apply` args for a machine's download limits (see `ApplyConfig`).
+fn download_limit_args(limits: DownloadLimits) -> Vec<String> {
+    let mut args = Vec::new();
+    if let Some(max_parallel) = limits.max_parallel {
+        args.extend(["--max-parallel-downloads".into(), max_parallel.to_string()]);
+    }
+    if let Some(max_kib_per_sec) = limits.max_kib_per_sec {
+        args.extend(["--max-download-rate".into(), max_kib_per_sec.to_string()]);
+    }
+    args
+}
+
+// `lusid-apply` args for the `[protect]` section in `lusid.toml`.
+fn protect_args(protect: &ProtectConfig) -> Vec<String> {
+    let mut args = Vec::new();
+    if !protect.defaults {
+        args.push("--no-default-protections".into());
+    }
+    let flags = [
+        ("--protect-path", &protect.paths),
+        ("--protect-package", &protect.packages),
+        ("--protect-user", &protect.users),
+    ];
+    for (flag, values) in flags {
+        for value in values {
+            args.extend([flag.into(), value.clone()]);
+        }
+    }
+    args
+}
+
+// `lusid-apply` args for `wait_for_locks` in `lusid.toml`.
+fn wait_for_locks_args(wait_for_locks: Option<u64>) -> Vec<String> {
+    match wait_for_locks {
+        Some(secs) => vec!["--wait-for-locks".into(), secs.to_string()],
+        None => vec![],
+    }
+}
+
+// A context for this host, its downloads (e.g. of VM images) throttled to
+// the `[downloads]` limits in `lusid.toml`.
+fn create_context(config: &Config) -> Context {
+    let mut ctx = Context::create(config.root()).unwrap();
+    ctx.set_download_limits(config.downloads);
+    ctx
+}
+
+// Upload `lusid-apply`, the plan and any forwarded secrets into the VM's
+// staging directory, and build the `lusid-apply` command line to run there.
+async fn prepare_dev_apply(
+    config: &Config,
+    machine_id: &str,
+    machine_config: &MachineConfig,
+    vm: &Vm,
+    ssh: &mut Ssh,
+    secrets_dir: &Path,
+    identity_path: Option<&Path>,
+) -> Result<String, AppError> {
+    let MachineConfig {
+        plan,
+        staging_dir,
+        apply,
+        ..
+    } = machine_config;
+
+    let vm_keypair = vm.ssh_keypair().await?;
+
+    let staging = StagingDir::resolve(ssh, staging_dir).await?;
+    let plan_dir = plan.parent().unwrap();
+    let apply_bin = which(&apply.bin)?;
+
+    let mut volumes = vec![
         SshVolume::FilePath {
             local: apply_bin,
-            remote: format!("{dev_dir}/lusid-apply"),
+            remote: staging.apply_bin(),
         },
         SshVolume::DirPath {
             local: plan_dir.to_path_buf(),
-            remote: format!("{dev_dir}/plan"),
+            remote: staging.plan_dir(),
         },
     ];
 
@@ -387,22 +2490,22 @@ async fn cmd_dev_apply(
     // `identity_path`: no identity → no secrets shipped, and the guest
     // will run without a secrets context (plans referencing
     // `@core/secret` will error loudly).
-    let guest_identity_path = format!("{dev_dir}/identity");
-    let guest_secrets_dir = format!("{dev_dir}/secrets");
-    let forward_secrets = if let Some(identity_path) = identity_path.as_deref() {
+    let guest_identity_path = staging.identity_path();
+    let guest_secrets_dir = staging.secrets_dir();
+    let forward_secrets = if let Some(identity_path) = identity_path {
         // The VM's auth keypair doubles as the age recipient/identity: it
         // already lives on both sides (instance dir on host, authorized_keys
         // on guest via cloud-init), is ephemeral per-VM, and re-using it
-        // avoids a second keygen + a cloud-init host-key injection path.
+        // avoids minting and shipping a second keypair.
         let machine_pubkey = vm_keypair.public_openssh()?;
         let reencrypted =
-            reencrypt_for_machine(identity_path, &secrets_dir, &machine_pubkey).await?;
+            reencrypt_for_machine(identity_path, secrets_dir, &machine_pubkey).await?;
 
         let private_pem = vm_keypair.private_openssh()?;
         volumes.push(SshVolume::FileBytes {
             local: private_pem.into_bytes(),
             permissions: Some(0o600),
-            remote: guest_identity_path.clone(),
+            remote: guest_identity_path,
         });
         for secret in reencrypted {
             volumes.push(SshVolume::FileBytes {
@@ -416,34 +2519,254 @@ async fn cmd_dev_apply(
         false
     };
 
-    let log = &config.log;
+    let command = apply_command(
+        config,
+        machine_id,
+        machine_config,
+        &staging,
+        forward_secrets,
+    )?;
+
+    for volume in volumes {
+        ssh.sync(volume).await?;
+    }
+
+    Ok(command)
+}
+
+// Upload `lusid-apply` and the plan into a remote machine's staging
+// directory, and build the `lusid-apply` command line to run there.
+//
+// Note(cc): secrets aren't forwarded. A dev VM's throwaway keypair doubles as
+// its age identity, but a remote machine's only key lusid knows is the login
+// key, which isn't shipped to the machine. Plans using `@core/secret` fail.
+async fn prepare_remote_apply(
+    config: &Config,
+    machine_id: &str,
+    machine_config: &MachineConfig,
+    ssh: &mut Ssh,
+) -> Result<String, AppError> {
+    let MachineConfig {
+        plan,
+        staging_dir,
+        apply,
+        ..
+    } = machine_config;
+
+    let staging = StagingDir::resolve(ssh, staging_dir).await?;
+    let plan_dir = plan.parent().unwrap();
+    ssh.sync(SshVolume::FilePath {
+        local: which(&apply.bin)?,
+        remote: staging.apply_bin(),
+    })
+    .await?;
+    ssh.sync(SshVolume::DirPath {
+        local: plan_dir.to_path_buf(),
+        remote: staging.plan_dir(),
+    })
+    .await?;
+
+    apply_command(config, machine_id, machine_config, &staging, false)
+}
+
+// The `lusid-apply` command line to run from `staging`: the machine's plan,
+// params and facts, the `lusid.toml` apply settings, and with
+// `forward_secrets` the identity and secrets uploaded into `staging`.
+fn apply_command(
+    config: &Config,
+    machine_id: &str,
+    machine_config: &MachineConfig,
+    staging: &StagingDir,
+    forward_secrets: bool,
+) -> Result<String, AppError> {
+    let MachineConfig {
+        plan,
+        params,
+        apply,
+        ..
+    } = machine_config;
+    let plan_filename = plan.file_name().unwrap().to_string_lossy();
+
+    let log = &apply.log;
     let mut command = format!(
-        "{dev_dir}/lusid-apply --root {} --plan {dev_dir}/plan/{plan_filename} --log {log}",
-        root.display()
+        "{} --root {} --plan {}/{plan_filename} --log {log}",
+        staging.apply_bin(),
+        config.root().display(),
+        staging.plan_dir(),
     );
     if forward_secrets {
         command.push_str(&format!(
-            " --guest-mode --identity {guest_identity_path} --secrets-dir {guest_secrets_dir}"
+            " --guest-mode --identity {} --secrets-dir {}",
+            staging.identity_path(),
+            staging.secrets_dir(),
         ));
     }
     if let Some(params) = params {
         let params_json = serde_json::to_string(&params)?;
-        command.push_str(&format!(" --params '{params_json}'"));
+        command.push_str(&format!(" --params {}", shell_words::quote(&params_json)));
+    }
+    // Quoted, as the facts hold the machine's vars, which can hold quotes.
+    let facts = machine_config.facts(machine_id).to_string();
+    command.push_str(&format!(" --machine {}", shell_words::quote(&facts)));
+    for arg in download_limit_args(apply.downloads)
+        .into_iter()
+        .chain(wait_for_locks_args(config.wait_for_locks))
+    {
+        command.push_str(&format!(" {arg}"));
     }
+    // Quoted, as a protected path can hold spaces or quotes.
+    for arg in protect_args(&config.protect) {
+        command.push_str(&format!(" {}", shell_words::quote(&arg)));
+    }
+    Ok(command)
+}
 
-    for volume in volumes {
-        ssh.sync(volume).await?;
+// `container apply`: copy `lusid-apply` and the plan into the container's
+// staging directory (see `container`), apply as root there through the TUI
+// or `--raw`, like a local apply, then remove the staging directory and, if
+// the apply succeeded, commit the container as `commit`.
+//
+// Note(cc): secrets aren't forwarded. A container has no key of its own to
+// re-encrypt them to, as a dev VM has, and a key shipped in would be baked
+// into any image committed from it. Plans using `@core/secret` fail here.
+//
+// Note(cc): operations that run as another user (`sudo_as`, e.g. a git
+// clone with `user`) still need `sudo` in the image.
+async fn cmd_container_apply(
+    config: Config,
+    machine_id: String,
+    container: Container,
+    commit: Option<String>,
+    raw: bool,
+    allow_destructive: bool,
+) -> Result<(), AppError> {
+    let machine_config = config.get_machine(&machine_id)?;
+    let facts = machine_config.facts(&machine_id);
+    let MachineConfig {
+        plan,
+        params,
+        apply,
+        ..
+    } = machine_config;
+
+    let staging = StagingDir::new(CONTAINER_STAGING_DIR);
+    let plan_dir = plan.parent().unwrap();
+    let plan_filename = plan.file_name().unwrap().to_string_lossy();
+    let apply_bin = which(&apply.bin)?;
+    container
+        .copy_file(&apply_bin, &staging.apply_bin())
+        .await?;
+    container.copy_dir(plan_dir, &staging.plan_dir()).await?;
+
+    let mut command = container.exec(
+        &staging.apply_bin(),
+        [
+            "--root".to_owned(),
+            staging.plan_dir(),
+            "--plan".to_owned(),
+            format!("{}/{plan_filename}", staging.plan_dir()),
+            "--log".to_owned(),
+            apply.log.clone(),
+            "--machine".to_owned(),
+            facts.to_string(),
+            "--no-sudo".to_owned(),
+        ],
+    );
+    if let Some(params) = params {
+        let params_json = serde_json::to_string(&params)?;
+        command.args(["--params", &params_json]);
+    }
+    command.args(download_limit_args(apply.downloads));
+    if allow_destructive {
+        command.arg("--allow-destructive");
+    }
+    if apply.check {
+        command.arg("--check");
     }
 
-    let mut handle = ssh.command(&command).await?;
-    let wait = Box::pin(async move {
-        handle.channel.wait().await?;
-        Ok::<_, SshError>(())
-    });
+    let result = run_local_apply(&config, &machine_id, command, raw).await;
+    container.clean(&staging).await?;
+    let succeeded = result?;
+
+    if raw && !succeeded {
+        return Err(AppError::ApplyFailed);
+    }
 
-    tui(&mut handle.stdout, &mut handle.stderr, wait).await?;
+    if let Some(image) = commit.filter(|_| succeeded) {
+        container.commit(&image).await?;
+        info!(machine_id, image, "committed container");
+    }
 
-    ssh.disconnect().await?;
+    Ok(())
+}
+
+async fn cmd_image_apply(
+    config: Config,
+    machine_id: String,
+    image: Image,
+    raw: bool,
+    allow_destructive: bool,
+) -> Result<(), AppError> {
+    let machine_config = config.get_machine(&machine_id)?;
+    let facts = machine_config.facts(&machine_id);
+    let MachineConfig {
+        plan,
+        params,
+        apply,
+        ..
+    } = machine_config;
+
+    let staging = StagingDir::new(IMAGE_STAGING_DIR);
+    let plan_dir = plan.parent().unwrap();
+    let plan_filename = plan.file_name().unwrap().to_string_lossy();
+    let apply_bin = which(&apply.bin)?;
+
+    let mut command = image.exec(
+        &staging.apply_bin(),
+        [
+            "--root".to_owned(),
+            staging.plan_dir(),
+            "--plan".to_owned(),
+            format!("{}/{plan_filename}", staging.plan_dir()),
+            "--log".to_owned(),
+            apply.log.clone(),
+            "--machine".to_owned(),
+            facts.to_string(),
+            "--no-sudo".to_owned(),
+        ],
+    );
+    if let Some(params) = params {
+        let params_json = serde_json::to_string(&params)?;
+        command.args(["--params", &params_json]);
+    }
+    command.args(download_limit_args(apply.downloads));
+    if allow_destructive {
+        command.arg("--allow-destructive");
+    }
+    if apply.check {
+        command.arg("--check");
+    }
+
+    // Whatever fails along the way, what was mounted is unmounted and the
+    // staging directory removed, so neither is left behind in the image.
+    let result = async {
+        image.copy_file(&apply_bin, &staging.apply_bin()).await?;
+        image.copy_dir(plan_dir, &staging.plan_dir()).await?;
+        image.mount().await?;
+        let result = run_local_apply(&config, &machine_id, command, raw).await;
+        let unmounted = image.unmount().await;
+        let succeeded = result?;
+        unmounted?;
+        Ok::<_, AppError>(succeeded)
+    }
+    .await;
+    let cleaned = image.clean(&staging).await;
+    let succeeded = result?;
+    cleaned?;
+
+    if raw && !succeeded {
+        return Err(AppError::ApplyFailed);
+    }
 
     Ok(())
 }
@@ -452,14 +2775,179 @@ async fn cmd_dev_apply(
 // exists) and attach the local TTY to a remote interactive shell via
 // `Ssh::terminal`. No TUI, no apply — just a shell inside the guest.
 async fn cmd_dev_ssh(config: Config, machine_id: String) -> Result<(), AppError> {
+    let MachineConfig { machine, .. } = config.get_machine(&machine_id)?;
+
+    let mut ctx = create_context(&config);
+
+    let instance_id = &machine_id;
+    let ports = vec![];
+    let options = VmOptions {
+        instance_id,
+        machine: &machine,
+        ports,
+    };
+    let vm = Vm::run(&mut ctx, options).await?;
+
+    let mut ssh = Ssh::connect(SshConnectOptions {
+        private_key: vm.ssh_keypair().await?.private_key,
+        host_key: vm.ssh_host_key()?,
+        addrs: (Ipv4Addr::LOCALHOST, vm.ssh_port),
+        username: vm.user,
+        config: Arc::new(Default::default()),
+        timeout: Duration::from_secs(10),
+    })
+    .await?;
+
+    let _exit_code = ssh.terminal().await?;
+
+    ssh.disconnect().await?;
+
+    Ok(())
+}
+
+// `dev push` / `dev pull`: boot the VM (idempotent, as for `dev ssh`) and
+// copy over SFTP, for quick debugging without a plan.
+async fn cmd_dev_push(
+    config: Config,
+    machine_id: String,
+    local: PathBuf,
+    mut remote: String,
+) -> Result<(), AppError> {
+    let mut ssh = connect_dev_vm(&config, &machine_id).await?;
+
+    if let Some(name) = local.file_name().filter(|_| remote.ends_with('/')) {
+        remote.push_str(&name.to_string_lossy());
+    }
+    let remote = home_relative(&remote).to_owned();
+    let volume = if local.is_dir() {
+        SshVolume::DirPath { local, remote }
+    } else {
+        SshVolume::FilePath { local, remote }
+    };
+    ssh.sync(volume).await?;
+
+    ssh.disconnect().await?;
+
+    Ok(())
+}
+
+async fn cmd_dev_pull(
+    config: Config,
+    machine_id: String,
+    remote: String,
+    local: PathBuf,
+) -> Result<(), AppError> {
+    let mut ssh = connect_dev_vm(&config, &machine_id).await?;
+
+    let remote = home_relative(&remote);
+    let local = match Path::new(remote.trim_end_matches('/')).file_name() {
+        Some(name) if local.is_dir() => local.join(name),
+        _ => local,
+    };
+    ssh.fetch(remote, &local).await?;
+
+    ssh.disconnect().await?;
+
+    Ok(())
+}
+
+async fn connect_dev_vm(config: &Config, machine_id: &str) -> Result<Ssh, AppError> {
+    let MachineConfig { machine, .. } = config.get_machine(machine_id)?;
+
+    let mut ctx = create_context(config);
+    let options = VmOptions {
+        instance_id: machine_id,
+        machine: &machine,
+        ports: vec![],
+    };
+    let vm = Vm::run(&mut ctx, options).await?;
+
+    connect_vm(&vm).await
+}
+
+/// SFTP paths are relative to the SSH user's home already, so `~/` is just
+/// dropped.
+fn home_relative(remote: &str) -> &str {
+    let relative = match remote {
+        "~" => "",
+        _ => remote.strip_prefix("~/").unwrap_or(remote),
+    };
+    if relative.is_empty() { "." } else { relative }
+}
+
+// `doctor --dev`: boot the machine's dev VM exactly as `dev apply` would and
+// run the preflight checks over SSH, printing them as a table. Fails if any
+// check failed, so scripts can gate an apply on it.
+async fn cmd_dev_doctor(config: Config, machine_id: String) -> Result<(), AppError> {
+    let MachineConfig {
+        machine,
+        staging_dir,
+        apply,
+        ..
+    } = config.get_machine(&machine_id)?;
+
+    let mut ctx = create_context(&config);
+
+    let instance_id = &machine_id;
+    let ports = vec![];
+    let options = VmOptions {
+        instance_id,
+        machine: &machine,
+        ports,
+    };
+    let vm = Vm::run(&mut ctx, options).await?;
+
+    let target = DoctorTarget {
+        machine: &machine,
+        apply_bin: &apply.bin,
+        staging_dir: &staging_dir,
+    };
+
+    let connect = Ssh::connect(SshConnectOptions {
+        private_key: vm.ssh_keypair().await?.private_key,
+        host_key: vm.ssh_host_key()?,
+        addrs: (Ipv4Addr::LOCALHOST, vm.ssh_port),
+        username: vm.user.clone(),
+        config: Arc::new(Default::default()),
+        timeout: Duration::from_secs(10),
+    })
+    .await;
+    let checks = match connect {
+        Ok(mut ssh) => {
+            let mut checks = vec![Check::pass(
+                "ssh",
+                format!("connected as {}@localhost:{}", vm.user, vm.ssh_port),
+            )];
+            checks.extend(run_checks(&mut ssh, &target).await);
+            ssh.disconnect().await?;
+            checks
+        }
+        Err(error) => unreachable(&error),
+    };
+
+    print_checks(&checks);
+
+    let failed = checks
+        .iter()
+        .filter(|check| check.status == CheckStatus::Fail)
+        .count();
+    if failed > 0 {
+        return Err(AppError::DoctorFailed { failed });
+    }
+    Ok(())
+}
+
+// `dev clean`: boot the VM (reusing the instance if it exists) and remove
+// the machine's staging directory, including any forwarded identity and
+// secrets left over from `dev apply`.
+async fn cmd_dev_clean(config: Config, machine_id: String) -> Result<(), AppError> {
     let MachineConfig {
-        plan: _,
         machine,
-        params: _,
+        staging_dir,
+        ..
     } = config.get_machine(&machine_id)?;
 
-    let root = config.path.parent().unwrap();
-    let mut ctx = Context::create(root).unwrap();
+    let mut ctx = create_context(&config);
 
     let instance_id = &machine_id;
     let ports = vec![];
@@ -472,6 +2960,7 @@ async fn cmd_dev_ssh(config: Config, machine_id: String) -> Result<(), AppError>
 
     let mut ssh = Ssh::connect(SshConnectOptions {
         private_key: vm.ssh_keypair().await?.private_key,
+        host_key: vm.ssh_host_key()?,
         addrs: (Ipv4Addr::LOCALHOST, vm.ssh_port),
         username: vm.user,
         config: Arc::new(Default::default()),
@@ -479,7 +2968,13 @@ async fn cmd_dev_ssh(config: Config, machine_id: String) -> Result<(), AppError>
     })
     .await?;
 
-    let _exit_code = ssh.terminal().await?;
+    let staging = StagingDir::resolve(&mut ssh, &staging_dir).await?;
+    staging.clean(&mut ssh).await?;
+    info!(
+        machine_id,
+        staging_dir = staging.root(),
+        "removed staging directory"
+    );
 
     ssh.disconnect().await?;
 
@@ -0,0 +1,392 @@
+//! Run logs: what each apply streamed, kept per machine so a run can still be
+//! diagnosed once the TUI is gone.
+//!
+//! Every apply is a run, with an id from its start time (`20261016-120431`).
+//! A run's logs live in `<data_dir>/runs/<run_id>/` (see [`Paths`]), two files
+//! per machine it applied to:
+//!
+//! - `<machine_id>.jsonl` — the `AppUpdate` stream, one
+//!   `{"time":<unix ms>,"update":<update>}` line per update (the same shape
+//!   `local apply --raw` prints, see [`stamp_update`])
+//! - `<machine_id>.stderr` — `lusid-apply`'s stderr as-is
+//!
+//! `lusid logs` lists runs, and `lusid logs <run_id> --machine <id>` prints
+//! one machine's stderr (or with `--updates`, its update stream). `lusid
+//! history` browses them in the TUI (see [`history`](crate::history)). Only
+//! the newest [`KEEP_RUNS`] runs are kept.
+//!
+//! Note(cc): most runs apply a single machine, but `remote drift --group`
+//! checks several at once under one [`Run`], each with its own
+//! [`MachineLog`], so their interleaved streams stay separable. `remote
+//! apply`, still `todo!()`, should do the same when it fans out.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use comfy_table::Table;
+use lusid_apply_stdio::AppUpdate;
+use lusid_ctx::{Paths, PathsError};
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::{
+    fs::{self, File},
+    io::AsyncWriteExt,
+};
+
+use crate::generations::format_utc;
+use crate::report::ApplyReport;
+
+/// How many runs to keep; older ones are deleted as new ones start.
+pub const KEEP_RUNS: usize = 100;
+
+const UPDATES_EXTENSION: &str = "jsonl";
+const STDERR_EXTENSION: &str = "stderr";
+
+#[derive(Error, Debug)]
+pub enum LogsError {
+    #[error(transparent)]
+    Paths(#[from] PathsError),
+
+    #[error("failed to read run logs in {path}")]
+    ReadDir {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("failed to read run log {path}")]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("failed to write run log {path}")]
+    Write {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("run {run_id} not found (see `lusid logs`)")]
+    RunNotFound { run_id: String },
+
+    #[error("run {run_id} has no logs for machine {machine_id}")]
+    MachineNotInRun { run_id: String, machine_id: String },
+
+    #[error("run {run_id} applied several machines, pick one with --machine: {machines}")]
+    AmbiguousMachine { run_id: String, machines: String },
+}
+
+/// One apply run's log directory.
+#[derive(Debug, Clone)]
+pub struct Run {
+    id: String,
+    dir: PathBuf,
+}
+
+impl Run {
+    /// Start a new run, pruning old runs beyond [`KEEP_RUNS`].
+    pub async fn create() -> Result<Self, LogsError> {
+        let runs_dir = runs_dir()?;
+        fs::create_dir_all(&runs_dir)
+            .await
+            .map_err(|source| LogsError::Write {
+                path: runs_dir.clone(),
+                source,
+            })?;
+
+        let existing = run_ids(&runs_dir).await?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let base = run_id(now);
+
+        // `create_dir` fails if the id is taken, e.g. by a concurrent run.
+        let mut suffix = 0;
+        let (id, dir) = loop {
+            let id = match suffix {
+                0 => base.clone(),
+                n => format!("{base}-{n}"),
+            };
+            let dir = runs_dir.join(&id);
+            match fs::create_dir(&dir).await {
+                Ok(()) => break (id, dir),
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => suffix += 1,
+                Err(source) => return Err(LogsError::Write { path: dir, source }),
+            }
+        };
+
+        for old in runs_to_prune(existing, KEEP_RUNS - 1) {
+            // Best-effort: a run that can't be pruned now will be next time.
+            let _ = fs::remove_dir_all(runs_dir.join(old)).await;
+        }
+
+        Ok(Self { id, dir })
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Open `machine_id`'s log files in this run.
+    pub async fn machine(&self, machine_id: &str) -> Result<MachineLog, LogsError> {
+        let updates = create(&self.dir.join(format!("{machine_id}.{UPDATES_EXTENSION}"))).await?;
+        let stderr = create(&self.dir.join(format!("{machine_id}.{STDERR_EXTENSION}"))).await?;
+        Ok(MachineLog {
+            updates,
+            stderr,
+            error: None,
+            report: ApplyReport::default(),
+        })
+    }
+}
+
+async fn create(path: &Path) -> Result<File, LogsError> {
+    File::create(path).await.map_err(|source| LogsError::Write {
+        path: path.to_owned(),
+        source,
+    })
+}
+
+/// One machine's log files in a [`Run`]. Writes are best-effort — a full
+/// disk shouldn't stop an apply — so the first failure is kept and returned
+/// by [`MachineLog::finish`], and later writes are skipped.
+///
+/// The updates are also folded into an [`ApplyReport`], the run's summary
+/// for [hooks](crate::hooks).
+#[derive(Debug)]
+pub struct MachineLog {
+    updates: File,
+    stderr: File,
+    error: Option<io::Error>,
+    report: ApplyReport,
+}
+
+impl MachineLog {
+    /// Append a raw `AppUpdate` line from `lusid-apply`'s stdout.
+    pub async fn update(&mut self, line: &str) {
+        if let Ok(update) = serde_json::from_str::<AppUpdate>(line) {
+            self.report.record(&update);
+        }
+        let record = stamp_update(now_millis(), line);
+        if self.error.is_none() {
+            self.error = self.updates.write_all(record.as_bytes()).await.err();
+        }
+    }
+
+    /// Append a line from `lusid-apply`'s stderr.
+    pub async fn stderr(&mut self, line: &str) {
+        if self.error.is_none() {
+            let line = format!("{line}\n");
+            self.error = self.stderr.write_all(line.as_bytes()).await.err();
+        }
+    }
+
+    pub fn report(&self) -> &ApplyReport {
+        &self.report
+    }
+
+    pub async fn finish(mut self) -> io::Result<()> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        self.updates.flush().await?;
+        self.stderr.flush().await
+    }
+}
+
+/// Wrap a JSON `AppUpdate` line as `{"time":<unix ms>,"update":<line>}\n`.
+/// The line is spliced in rather than re-serialized, so it must already be a
+/// single JSON value.
+pub fn stamp_update(time: u128, line: &str) -> String {
+    format!("{{\"time\":{time},\"update\":{line}}}\n")
+}
+
+/// The `AppUpdate` line a [`stamp_update`] record wraps.
+pub fn unstamp_update(record: &str) -> Result<String, serde_json::Error> {
+    #[derive(Deserialize)]
+    struct Stamped {
+        update: serde_json::Value,
+    }
+    let Stamped { update } = serde_json::from_str(record)?;
+    serde_json::to_string(&update)
+}
+
+pub fn now_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis())
+}
+
+/// A past run: its id and the machines it has logs for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunSummary {
+    pub id: String,
+    pub machines: Vec<String>,
+}
+
+/// All kept runs, oldest first.
+pub async fn list_runs() -> Result<Vec<RunSummary>, LogsError> {
+    let runs_dir = runs_dir()?;
+    let mut runs = Vec::new();
+    for id in run_ids(&runs_dir).await? {
+        let machines = run_machines(&runs_dir.join(&id)).await?;
+        runs.push(RunSummary { id, machines });
+    }
+    Ok(runs)
+}
+
+/// The contents of one machine's stderr log, or with `updates` its update
+/// stream. `machine_id` may be left out when the run applied one machine.
+pub async fn read_log(
+    run_id: &str,
+    machine_id: Option<&str>,
+    updates: bool,
+) -> Result<String, LogsError> {
+    let dir = runs_dir()?.join(run_id);
+    let well_formed = run_id.chars().all(|c| c.is_ascii_digit() || c == '-');
+    if !well_formed || !fs::try_exists(&dir).await.unwrap_or(false) {
+        return Err(LogsError::RunNotFound {
+            run_id: run_id.to_owned(),
+        });
+    }
+
+    let machines = run_machines(&dir).await?;
+    let machine_id = match (machine_id, machines.as_slice()) {
+        (Some(machine_id), _) if machines.iter().any(|machine| machine == machine_id) => {
+            machine_id.to_owned()
+        }
+        (Some(machine_id), _) => {
+            return Err(LogsError::MachineNotInRun {
+                run_id: run_id.to_owned(),
+                machine_id: machine_id.to_owned(),
+            });
+        }
+        (None, [machine_id]) => machine_id.clone(),
+        (None, _) => {
+            return Err(LogsError::AmbiguousMachine {
+                run_id: run_id.to_owned(),
+                machines: machines.join(", "),
+            });
+        }
+    };
+
+    let extension = if updates {
+        UPDATES_EXTENSION
+    } else {
+        STDERR_EXTENSION
+    };
+    let path = dir.join(format!("{machine_id}.{extension}"));
+    fs::read_to_string(&path)
+        .await
+        .map_err(|source| LogsError::Read { path, source })
+}
+
+pub fn print_runs(runs: &[RunSummary]) {
+    let mut table = Table::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL)
+        .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .set_header(vec!["run", "machines"]);
+
+    for RunSummary { id, machines } in runs {
+        table.add_row(vec![id.clone(), machines.join(", ")]);
+    }
+
+    println!("{table}");
+}
+
+fn runs_dir() -> Result<PathBuf, LogsError> {
+    Ok(Paths::create()?.data_dir().join("runs"))
+}
+
+/// `YYYYMMDD-HHMMSS` (UTC), so ids sort by start time.
+pub fn run_id(secs: u64) -> String {
+    format_utc(secs).replace(['-', ':'], "").replace(' ', "-")
+}
+
+/// The ids to delete so that at most `keep` runs remain, oldest first.
+fn runs_to_prune(mut ids: Vec<String>, keep: usize) -> Vec<String> {
+    ids.sort();
+    let excess = ids.len().saturating_sub(keep);
+    ids.truncate(excess);
+    ids
+}
+
+async fn run_ids(runs_dir: &Path) -> Result<Vec<String>, LogsError> {
+    let mut ids = dir_entries(runs_dir).await?;
+    ids.sort();
+    Ok(ids)
+}
+
+async fn run_machines(run_dir: &Path) -> Result<Vec<String>, LogsError> {
+    let mut machines: Vec<String> = dir_entries(run_dir)
+        .await?
+        .into_iter()
+        .filter_map(|name| {
+            name.strip_suffix(&format!(".{UPDATES_EXTENSION}"))
+                .map(str::to_owned)
+        })
+        .collect();
+    machines.sort();
+    Ok(machines)
+}
+
+async fn dir_entries(dir: &Path) -> Result<Vec<String>, LogsError> {
+    let read_dir_error = |source| LogsError::ReadDir {
+        path: dir.to_owned(),
+        source,
+    };
+    let mut entries = match fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(source) => return Err(read_dir_error(source)),
+    };
+    let mut names = Vec::new();
+    while let Some(entry) = entries.next_entry().await.map_err(read_dir_error)? {
+        names.push(entry.file_name().to_string_lossy().into_owned());
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_ids_sort_by_start_time() {
+        assert_eq!(run_id(0), "19700101-000000");
+        assert_eq!(run_id(1_792_152_271), "20261016-120431");
+    }
+
+    #[test]
+    fn prunes_oldest_runs() {
+        let ids = vec![
+            "20261016-120431".to_owned(),
+            "20261014-090000".to_owned(),
+            "20261015-110431".to_owned(),
+        ];
+        assert_eq!(runs_to_prune(ids.clone(), 2), vec!["20261014-090000"]);
+        assert!(runs_to_prune(ids, 5).is_empty());
+    }
+
+    #[test]
+    fn stamps_updates() {
+        assert_eq!(
+            stamp_update(1_000, r#""ResourcesStart""#),
+            "{\"time\":1000,\"update\":\"ResourcesStart\"}\n"
+        );
+    }
+
+    #[test]
+    fn unstamps_updates() {
+        let line = r#"{"Warning":{"warning":{"message":"deprecated"}}}"#;
+        assert_eq!(unstamp_update(&stamp_update(1_000, line)).unwrap(), line);
+        assert!(unstamp_update(r#"{"time":1000}"#).is_err());
+    }
+}
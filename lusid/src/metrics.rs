@@ -0,0 +1,358 @@
+//! Prometheus metrics for `lusid agent`, served at `GET /metrics` on its
+//! `--listen` address (see [`agent`](crate::agent)).
+//!
+//! Everything comes from the [`AgentState`], so the metrics describe the
+//! agent's last cycle and last apply as of when they finished. All are
+//! gauges but `lusid_agent_cycles_total`:
+//!
+//! - `lusid_agent_info{machine}` — always `1`, naming the machine.
+//! - `lusid_agent_cycles_total`, `lusid_agent_consecutive_failures`.
+//! - `lusid_agent_last_cycle_timestamp_seconds`,
+//!   `lusid_agent_last_cycle_duration_seconds` and
+//!   `lusid_agent_last_cycle_status{status}`, `1` for the last cycle's
+//!   status and `0` for the others.
+//! - `lusid_agent_drifted_resources` — changes the last check found.
+//! - `lusid_agent_resources_changed` and `lusid_agent_operations_failed` —
+//!   of the last cycle's apply, `0` if it didn't apply.
+//! - `lusid_agent_last_apply_timestamp_seconds` and
+//!   `lusid_agent_last_apply_success` — of the last apply, whichever cycle
+//!   it was in; `lusid_agent_last_successful_apply_timestamp_seconds`.
+//! - `lusid_agent_stage_duration_seconds{pass,stage}` — how long each
+//!   [`Stage`] of the last cycle's check and apply took.
+//!
+//! A metric with nothing to report yet, like the last apply's before any,
+//! is left out rather than reported as `0`.
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Write as _};
+use std::time::Instant;
+
+use lusid_apply_stdio::AppUpdate;
+use serde::{Deserialize, Serialize};
+
+use crate::agent::{AgentState, CycleStatus};
+
+/// A stage of the `lusid-apply` pipeline, as its updates mark it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// Evaluating the plan, up to its resource params.
+    Plan,
+    Resources,
+    ResourceStates,
+    ResourceChanges,
+    Operations,
+    /// Applying the operations.
+    Apply,
+}
+
+impl Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Stage::Plan => "plan",
+            Stage::Resources => "resources",
+            Stage::ResourceStates => "resource_states",
+            Stage::ResourceChanges => "resource_changes",
+            Stage::Operations => "operations",
+            Stage::Apply => "apply",
+        })
+    }
+}
+
+/// Each finished stage's duration, in milliseconds.
+pub type StageDurations = BTreeMap<Stage, u64>;
+
+/// Times the stages of one `lusid-apply` run from its updates. Stages it
+/// never finishes, as when the run fails, are left out.
+#[derive(Debug, Clone)]
+pub struct StageTimer {
+    current: Option<(Stage, Instant)>,
+    durations: StageDurations,
+}
+
+impl StageTimer {
+    /// Start timing, with [`Stage::Plan`], at `now`: when `lusid-apply` is
+    /// started.
+    pub fn new(now: Instant) -> Self {
+        Self {
+            current: Some((Stage::Plan, now)),
+            durations: StageDurations::new(),
+        }
+    }
+
+    pub fn record(&mut self, update: &AppUpdate, now: Instant) {
+        let (started, finished) = match update {
+            AppUpdate::ResourceParams { .. } => (None, Some(Stage::Plan)),
+            AppUpdate::ResourcesStart => (Some(Stage::Resources), None),
+            AppUpdate::ResourcesComplete => (None, Some(Stage::Resources)),
+            AppUpdate::ResourceStatesStart => (Some(Stage::ResourceStates), None),
+            AppUpdate::ResourceStatesComplete => (None, Some(Stage::ResourceStates)),
+            AppUpdate::ResourceChangesStart => (Some(Stage::ResourceChanges), None),
+            AppUpdate::ResourceChangesComplete { .. } => (None, Some(Stage::ResourceChanges)),
+            AppUpdate::OperationsStart => (Some(Stage::Operations), None),
+            AppUpdate::OperationsComplete => (None, Some(Stage::Operations)),
+            AppUpdate::OperationsApplyStart { .. } => (Some(Stage::Apply), None),
+            AppUpdate::OperationsApplyComplete => (None, Some(Stage::Apply)),
+            _ => return,
+        };
+        if let Some(stage) = started {
+            self.current = Some((stage, now));
+        }
+        let Some(stage) = finished else {
+            return;
+        };
+        if let Some((current, start)) = self.current.take_if(|(current, _)| *current == stage) {
+            let elapsed = now.saturating_duration_since(start).as_millis();
+            self.durations
+                .insert(current, u64::try_from(elapsed).unwrap_or(u64::MAX));
+        }
+    }
+
+    pub fn durations(self) -> StageDurations {
+        self.durations
+    }
+}
+
+/// The agent's metrics, in Prometheus' text format.
+pub fn render(state: &AgentState) -> String {
+    let mut out = Metrics::default();
+    let machine = [("machine", state.machine.as_str())];
+
+    out.gauge("lusid_agent_info", "The machine the agent keeps.");
+    out.sample("lusid_agent_info", &machine, 1);
+
+    out.counter("lusid_agent_cycles_total", "Cycles run.");
+    out.sample("lusid_agent_cycles_total", &[], state.cycles);
+
+    out.gauge(
+        "lusid_agent_consecutive_failures",
+        "Failed cycles since the last one that didn't fail.",
+    );
+    out.sample(
+        "lusid_agent_consecutive_failures",
+        &[],
+        state.consecutive_failures,
+    );
+
+    if let Some(time) = state.last_apply_at {
+        out.gauge(
+            "lusid_agent_last_apply_timestamp_seconds",
+            "When the last apply finished.",
+        );
+        out.sample("lusid_agent_last_apply_timestamp_seconds", &[], time);
+    }
+    if let Some(succeeded) = state.last_apply_succeeded {
+        out.gauge(
+            "lusid_agent_last_apply_success",
+            "Whether the last apply succeeded.",
+        );
+        out.sample("lusid_agent_last_apply_success", &[], u8::from(succeeded));
+    }
+    if let Some(time) = state.last_applied_at {
+        out.gauge(
+            "lusid_agent_last_successful_apply_timestamp_seconds",
+            "When an apply last succeeded.",
+        );
+        out.sample(
+            "lusid_agent_last_successful_apply_timestamp_seconds",
+            &[],
+            time,
+        );
+    }
+
+    let Some(cycle) = &state.last_cycle else {
+        return out.0;
+    };
+
+    out.gauge(
+        "lusid_agent_last_cycle_timestamp_seconds",
+        "When the last cycle finished.",
+    );
+    out.sample(
+        "lusid_agent_last_cycle_timestamp_seconds",
+        &[],
+        cycle.finished_at,
+    );
+    out.gauge(
+        "lusid_agent_last_cycle_duration_seconds",
+        "How long the last cycle took.",
+    );
+    out.sample(
+        "lusid_agent_last_cycle_duration_seconds",
+        &[],
+        cycle.finished_at.saturating_sub(cycle.started_at),
+    );
+
+    out.gauge(
+        "lusid_agent_last_cycle_status",
+        "The last cycle's status: 1 for the one it had.",
+    );
+    for status in [
+        CycleStatus::InSync,
+        CycleStatus::Drifted,
+        CycleStatus::Applied,
+        CycleStatus::Failed,
+    ] {
+        out.sample(
+            "lusid_agent_last_cycle_status",
+            &[("status", status.as_str())],
+            u8::from(cycle.status == status),
+        );
+    }
+
+    if let Some(check) = &cycle.check {
+        out.gauge(
+            "lusid_agent_drifted_resources",
+            "Resources the last check found to change.",
+        );
+        out.sample("lusid_agent_drifted_resources", &[], check.changes.len());
+    }
+
+    let (changed, failed) = cycle.apply.as_ref().map_or((0, 0), |apply| {
+        (apply.changes.len(), apply.failed_operations.len())
+    });
+    out.gauge(
+        "lusid_agent_resources_changed",
+        "Resources the last cycle's apply changed.",
+    );
+    out.sample("lusid_agent_resources_changed", &[], changed);
+    out.gauge(
+        "lusid_agent_operations_failed",
+        "Operations that failed in the last cycle's apply.",
+    );
+    out.sample("lusid_agent_operations_failed", &[], failed);
+
+    if !(cycle.check_stages.is_empty() && cycle.apply_stages.is_empty()) {
+        out.gauge(
+            "lusid_agent_stage_duration_seconds",
+            "How long each stage of the last cycle's check and apply took.",
+        );
+        for (pass, stages) in [
+            ("check", &cycle.check_stages),
+            ("apply", &cycle.apply_stages),
+        ] {
+            for (stage, millis) in stages {
+                let stage = stage.to_string();
+                out.sample(
+                    "lusid_agent_stage_duration_seconds",
+                    &[("pass", pass), ("stage", &stage)],
+                    Seconds(*millis),
+                );
+            }
+        }
+    }
+
+    out.0
+}
+
+#[derive(Debug, Default)]
+struct Metrics(String);
+
+impl Metrics {
+    fn gauge(&mut self, name: &str, help: &str) {
+        self.header(name, help, "gauge");
+    }
+
+    fn counter(&mut self, name: &str, help: &str) {
+        self.header(name, help, "counter");
+    }
+
+    fn header(&mut self, name: &str, help: &str, kind: &str) {
+        let _ = writeln!(self.0, "# HELP {name} {help}");
+        let _ = writeln!(self.0, "# TYPE {name} {kind}");
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl Display) {
+        let _ = write!(self.0, "{name}");
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(label, value)| format!("{label}=\"{}\"", escape_label(value)))
+                .collect();
+            let _ = write!(self.0, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.0, " {value}");
+    }
+}
+
+/// Milliseconds, shown as seconds.
+struct Seconds(u64);
+
+impl Display for Seconds {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{:03}", self.0 / 1000, self.0 % 1000)
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::agent::CycleReport;
+    use crate::report::ApplyReport;
+
+    use super::*;
+
+    #[test]
+    fn times_finished_stages() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut timer = StageTimer::new(start);
+        timer.record(
+            &AppUpdate::ResourceParams {
+                resource_params: lusid_view::ViewTree::Branch {
+                    view: lusid_view::Render::render(&"."),
+                    children: vec![],
+                },
+                modules: vec![],
+            },
+            at(1200),
+        );
+        timer.record(&AppUpdate::ResourceStatesStart, at(1300));
+        timer.record(&AppUpdate::ResourceStatesComplete, at(4300));
+        // Started, but the run failed before it finished.
+        timer.record(&AppUpdate::ResourceChangesStart, at(4400));
+        assert_eq!(
+            timer.durations(),
+            StageDurations::from([(Stage::Plan, 1200), (Stage::ResourceStates, 3000)])
+        );
+    }
+
+    #[test]
+    fn renders_the_last_cycle() {
+        let mut state = AgentState::new("web-\"1\"");
+        let mut cycle = CycleReport::new(100);
+        let mut check = ApplyReport::default();
+        check.changes = vec!["write /etc/motd".into()];
+        cycle.check = Some(check);
+        cycle.check_stages = StageDurations::from([(Stage::ResourceStates, 1500)]);
+        cycle.apply_run_id = Some("20261016-120431".into());
+        state.record(cycle.failed("apply failed: exit 1".into()));
+
+        let metrics = render(&state);
+        for line in [
+            "lusid_agent_info{machine=\"web-\\\"1\\\"\"} 1",
+            "# TYPE lusid_agent_cycles_total counter",
+            "lusid_agent_cycles_total 1",
+            "lusid_agent_last_apply_success 0",
+            "lusid_agent_last_cycle_status{status=\"failed\"} 1",
+            "lusid_agent_last_cycle_status{status=\"in_sync\"} 0",
+            "lusid_agent_drifted_resources 1",
+            "lusid_agent_resources_changed 0",
+            "lusid_agent_stage_duration_seconds{pass=\"check\",stage=\"resource_states\"} 1.500",
+        ] {
+            assert!(
+                metrics.lines().any(|metric| metric == line),
+                "missing {line:?} in:\n{metrics}"
+            );
+        }
+        assert!(!metrics.contains("lusid_agent_last_successful_apply_timestamp_seconds"));
+    }
+}
@@ -0,0 +1,83 @@
+//! Completion notifications for the TUI, so a long apply running in a
+//! background terminal doesn't finish silently.
+//!
+//! The TUI only notifies when its terminal doesn't have focus (see
+//! [`tui`](crate::tui)); how is set by `notify` in `lusid.toml`, a list of
+//! [`Notifier`]s that defaults to `["bell", "osc9"]`. `notify = []` turns
+//! notifications off.
+//!
+//! Note(cc): OSC 9 is understood by iTerm2, Windows Terminal, kitty, WezTerm
+//! and foot among others; terminals that don't just ignore it. Focus
+//! reporting is similarly terminal-dependent — where it's unsupported the TUI
+//! never sees focus lost, so it never notifies.
+
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Notifier {
+    /// Ring the terminal bell.
+    Bell,
+    /// Send an OSC 9 escape sequence, which many terminals turn into a
+    /// desktop notification.
+    Osc9,
+    /// Run `notify-send`.
+    Desktop,
+}
+
+impl Notifier {
+    pub fn defaults() -> Vec<Notifier> {
+        vec![Notifier::Bell, Notifier::Osc9]
+    }
+}
+
+/// Notify that the apply finished, successfully or not. Best-effort: the
+/// TUI owns the terminal, so failures are dropped rather than printed.
+pub fn notify(notifiers: &[Notifier], succeeded: bool) {
+    let message = if succeeded {
+        "lusid: apply complete"
+    } else {
+        "lusid: apply failed"
+    };
+
+    let mut stdout = io::stdout();
+    for notifier in notifiers {
+        match notifier {
+            Notifier::Bell => {
+                let _ = stdout.write_all(b"\x07");
+            }
+            Notifier::Osc9 => {
+                let _ = stdout.write_all(osc9(message).as_bytes());
+            }
+            Notifier::Desktop => {
+                // Fire and forget; its output would land on the TUI.
+                let _ = Command::new("notify-send")
+                    .args(["lusid", message])
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn();
+            }
+        }
+    }
+    let _ = stdout.flush();
+}
+
+fn osc9(message: &str) -> String {
+    // Control characters would end the sequence early.
+    let message: String = message.chars().filter(|c| !c.is_control()).collect();
+    format!("\x1b]9;{message}\x07")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn osc9_wraps_the_message() {
+        assert_eq!(osc9("done\x07!"), "\x1b]9;done!\x07");
+    }
+}
@@ -0,0 +1,239 @@
+//! Headless apply reports: what an apply changed and whether it worked,
+//! collected from `lusid-apply`'s update stream instead of drawn in the TUI.
+//!
+//! `dev apply --ci` applies twice and prints a [`CiReport`] as JSON on
+//! stdout: the first apply converges the machine, and the second must find
+//! nothing left to change. Anything it still changes is a resource that
+//! doesn't converge — usually a command without a guard.
+//!
+//! While an apply runs, [`ApplyReport::record`] also returns a plain progress
+//! line for the interesting updates, so CI logs show where a run got to
+//! without a terminal to draw on.
+
+use lusid_apply_stdio::{AppUpdate, ModuleVersion, OperationResult};
+use serde::{Deserialize, Serialize};
+
+/// One apply, as seen through its updates.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApplyReport {
+    /// The plan modules applied, with their declared versions.
+    pub modules: Vec<ModuleVersion>,
+    /// The planned changes, one rendered change per changed resource.
+    pub changes: Vec<String>,
+    /// How many operations the apply ran.
+    pub operations: usize,
+    /// The operations were only simulated, by a check.
+    #[serde(default)]
+    pub simulated: bool,
+    /// Operations that failed, as `<operation>: <error>`.
+    pub failed_operations: Vec<String>,
+    /// Warnings about the plan, e.g. deprecated params.
+    pub warnings: Vec<String>,
+    /// The pipeline error, if the apply failed outright.
+    pub error: Option<String>,
+    #[serde(skip)]
+    operation_views: Vec<Vec<String>>,
+}
+
+impl ApplyReport {
+    /// Fold in one update, returning a progress line worth printing, if any.
+    pub fn record(&mut self, update: &AppUpdate) -> Option<String> {
+        match update {
+            AppUpdate::ResourceParams { modules, .. } => {
+                self.modules = modules.clone();
+                None
+            }
+            AppUpdate::ResourceChangesNode {
+                node: Some(change), ..
+            } => {
+                self.changes.push(change.to_string());
+                None
+            }
+            AppUpdate::ResourceChangesComplete { .. } => {
+                Some(format!("{} resource(s) to change", self.changes.len()))
+            }
+            AppUpdate::OperationsApplyStart {
+                operations,
+                simulated,
+            } => {
+                self.operation_views = operations
+                    .iter()
+                    .map(|epoch| epoch.iter().map(ToString::to_string).collect())
+                    .collect();
+                self.operations = operations.iter().map(Vec::len).sum();
+                self.simulated = *simulated;
+                let verb = if *simulated { "simulating" } else { "applying" };
+                Some(format!("{verb} {} operation(s)", self.operations))
+            }
+            AppUpdate::OperationApplyComplete {
+                index: (epoch, operation),
+                result:
+                    OperationResult {
+                        error: Some(error), ..
+                    },
+            } => {
+                let name = self
+                    .operation_views
+                    .get(*epoch)
+                    .and_then(|epoch| epoch.get(*operation))
+                    .map_or("operation", String::as_str);
+                let failure = format!("{name}: {error}");
+                self.failed_operations.push(failure.clone());
+                Some(format!("failed: {failure}"))
+            }
+            AppUpdate::OperationsApplyComplete => Some(format!(
+                "applied, {} operation(s) failed",
+                self.failed_operations.len()
+            )),
+            AppUpdate::Warning { warning } => {
+                self.warnings.push(warning.message.clone());
+                Some(format!("warning: {}", warning.message))
+            }
+            AppUpdate::Error { error } => {
+                self.error = Some(error.message.clone());
+                Some(format!("error: {}", error.message))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none() && self.failed_operations.is_empty()
+    }
+
+    /// Why the apply failed: its pipeline error, else its first failed
+    /// operation.
+    pub fn failure(&self) -> Option<&str> {
+        self.error
+            .as_deref()
+            .or(self.failed_operations.first().map(String::as_str))
+    }
+}
+
+/// The result of `dev apply --ci`: an apply, then a re-apply that should
+/// have had nothing to do.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CiReport {
+    pub machine: String,
+    /// Both applies succeeded and the re-apply changed nothing.
+    pub converged: bool,
+    pub apply: ApplyReport,
+    /// `None` if the first apply failed, so there was nothing to re-apply.
+    pub reapply: Option<ApplyReport>,
+}
+
+impl CiReport {
+    pub fn new(machine: String, apply: ApplyReport, reapply: Option<ApplyReport>) -> Self {
+        let converged = apply.succeeded()
+            && reapply
+                .as_ref()
+                .is_some_and(|reapply| reapply.succeeded() && reapply.changes.is_empty());
+        Self {
+            machine,
+            converged,
+            apply,
+            reapply,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lusid_apply_stdio::WarningEnvelope;
+    use lusid_view::{View, ViewTree};
+
+    use super::*;
+
+    fn report(updates: Vec<AppUpdate>) -> (ApplyReport, Vec<String>) {
+        let mut report = ApplyReport::default();
+        let lines = updates
+            .iter()
+            .filter_map(|update| report.record(update))
+            .collect();
+        (report, lines)
+    }
+
+    #[test]
+    fn records_changes_and_failed_operations() {
+        let (report, lines) = report(vec![
+            AppUpdate::ResourceParams {
+                resource_params: ViewTree::Branch {
+                    view: View::Span(".".into()),
+                    children: vec![],
+                },
+                modules: vec![ModuleVersion {
+                    plan: "web.lusid".into(),
+                    name: Some("web".into()),
+                    version: Some("1.2.0".into()),
+                }],
+            },
+            AppUpdate::Warning {
+                warning: WarningEnvelope {
+                    code: "params.deprecated".into(),
+                    message: "Parameter \"pkg\" is deprecated".into(),
+                    rendered: "warning: Parameter \"pkg\" is deprecated".into(),
+                    span: None,
+                },
+            },
+            AppUpdate::ResourceChangesNode {
+                index: 1,
+                node: Some(View::Span("write /etc/motd".into())),
+            },
+            AppUpdate::ResourceChangesNode {
+                index: 2,
+                node: None,
+            },
+            AppUpdate::ResourceChangesComplete { has_changes: true },
+            AppUpdate::OperationsApplyStart {
+                operations: vec![vec![View::Span("apt install nginx".into())]],
+                simulated: false,
+            },
+            AppUpdate::OperationApplyComplete {
+                index: (0, 0),
+                result: OperationResult {
+                    error: Some("exit code 100".into()),
+                    exit_code: Some(100),
+                    ..OperationResult::default()
+                },
+            },
+            AppUpdate::OperationsApplyComplete,
+        ]);
+
+        assert_eq!(report.modules[0].version.as_deref(), Some("1.2.0"));
+        assert_eq!(report.changes, vec!["write /etc/motd"]);
+        assert_eq!(report.operations, 1);
+        assert_eq!(
+            report.failed_operations,
+            vec!["apt install nginx: exit code 100"]
+        );
+        assert!(!report.succeeded());
+        assert_eq!(
+            lines,
+            vec![
+                "warning: Parameter \"pkg\" is deprecated",
+                "1 resource(s) to change",
+                "applying 1 operation(s)",
+                "failed: apt install nginx: exit code 100",
+                "applied, 1 operation(s) failed",
+            ]
+        );
+    }
+
+    #[test]
+    fn converges_only_when_the_reapply_changes_nothing() {
+        let clean = ApplyReport::default();
+        let changed = ApplyReport {
+            changes: vec!["run `date > /tmp/now`".into()],
+            ..ApplyReport::default()
+        };
+
+        let report = CiReport::new("web".into(), changed.clone(), Some(clean.clone()));
+        assert!(report.converged);
+
+        let report = CiReport::new("web".into(), changed.clone(), Some(changed));
+        assert!(!report.converged);
+
+        let report = CiReport::new("web".into(), clean, None);
+        assert!(!report.converged);
+    }
+}
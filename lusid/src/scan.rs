@@ -0,0 +1,640 @@
+//! `lusid scan`: a starter plan from a machine as it is, to help bring a
+//! hand-configured server under lusid.
+//!
+//! Each selected [`ScanKind`] is probed over SSH, all in one batch (see
+//! [`Ssh::output_batch`]), with a second batch reading the files found under
+//! `--paths`. Nothing on the machine is changed. Only what looks set up by
+//! hand is captured: manually installed packages rather than their
+//! dependencies, and users and groups in the regular id range rather than
+//! system accounts. Anything that doesn't translate, like a file too large
+//! to inline or a symlink, is kept as a `TODO(scan)` comment.
+//!
+//! Like `lusid import ansible`, the output is a starting point to review, not
+//! a plan to apply as-is: it captures everything as found, including what
+//! the distro installed the same way on every machine.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+use clap::ValueEnum;
+use lusid_ssh::{Ssh, SshError, SshOutput};
+use thiserror::Error;
+
+use crate::ansible::{Ids, Param, quote, render_param};
+
+/// Files larger than this are left as a TODO rather than inlined.
+const MAX_CONTENTS_BYTES: u64 = 64 * 1024;
+
+/// The ids `useradd` and `groupadd` give regular accounts by default (see
+/// `login.defs`), short of `nobody`'s.
+const REGULAR_IDS: Range<u32> = 1000..65534;
+
+/// What to capture, in the order it's written to the plan.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ScanKind {
+    /// Manually installed apt packages.
+    Apt,
+    /// Explicitly installed pacman packages.
+    Pacman,
+    /// Packages installed on request with dnf.
+    Dnf,
+    /// Packages in apk's world, the ones added on request.
+    Apk,
+    /// Regular groups, with their supplementary members.
+    Group,
+    /// Regular users.
+    User,
+    /// Files and directories under `--paths`.
+    File,
+    /// Enabled systemd services.
+    Systemd,
+}
+
+#[derive(Error, Debug)]
+pub enum ScanError {
+    #[error(transparent)]
+    Ssh(#[from] SshError),
+
+    #[error("scan path {path:?} must be absolute")]
+    RelativePath { path: String },
+
+    #[error("`{command}` failed on the machine: {stderr}")]
+    Probe { command: String, stderr: String },
+}
+
+/// One captured plan item, or a note about something that couldn't be.
+#[derive(Debug, Clone, PartialEq)]
+enum Scanned {
+    Item {
+        todos: Vec<String>,
+        module: &'static str,
+        id: String,
+        params: Vec<(&'static str, Param)>,
+    },
+    Skipped {
+        reason: String,
+    },
+}
+
+/// Probe `kinds` on the machine at the other end of `ssh`, and print them as
+/// a plan named `name`. `paths` are where the `file` kind looks.
+pub async fn scan(
+    ssh: &mut Ssh,
+    name: &str,
+    kinds: &[ScanKind],
+    paths: &[String],
+) -> Result<String, ScanError> {
+    let mut kinds = kinds.to_vec();
+    kinds.sort();
+    kinds.dedup();
+    if let Some(path) = paths.iter().find(|path| !path.starts_with('/')) {
+        return Err(ScanError::RelativePath { path: path.clone() });
+    }
+
+    let commands: Vec<String> = kinds.iter().flat_map(|kind| probes(*kind, paths)).collect();
+    let outputs = ssh.output_batch(&commands).await?;
+    let mut outputs = commands.into_iter().zip(outputs);
+
+    let mut ids = Ids::default();
+    let mut scanned = Vec::new();
+    for kind in &kinds {
+        match kind {
+            ScanKind::Apt => {
+                let packages = lines(&stdout(&mut outputs)?);
+                scanned.extend(packages_item(
+                    "@core/apt",
+                    ids.next("apt packages"),
+                    packages,
+                ));
+            }
+            ScanKind::Pacman => {
+                let packages = lines(&stdout(&mut outputs)?);
+                scanned.extend(packages_item(
+                    "@core/pacman",
+                    ids.next("pacman packages"),
+                    packages,
+                ));
+            }
+            ScanKind::Dnf => {
+                let packages = lines(&stdout(&mut outputs)?);
+                scanned.extend(packages_item(
+                    "@core/dnf",
+                    ids.next("dnf packages"),
+                    packages,
+                ));
+            }
+            ScanKind::Apk => {
+                let packages = apk_world(&stdout(&mut outputs)?);
+                scanned.extend(packages_item(
+                    "@core/apk",
+                    ids.next("apk packages"),
+                    packages,
+                ));
+            }
+            ScanKind::Group => {
+                let groups = parse_groups(&stdout(&mut outputs)?);
+                scanned.extend(group_items(&groups, &mut ids));
+            }
+            ScanKind::User => {
+                let users = parse_users(&stdout(&mut outputs)?);
+                let groups = parse_groups(&stdout(&mut outputs)?);
+                scanned.extend(user_items(&users, &groups, &mut ids));
+            }
+            ScanKind::File => {
+                let mut entries = Vec::new();
+                for _ in paths {
+                    entries.extend(parse_find(&stdout(&mut outputs)?));
+                }
+                scanned.extend(file_items(ssh, entries, &mut ids).await?);
+            }
+            ScanKind::Systemd => {
+                let enabled = lines(&stdout(&mut outputs)?);
+                let active = lines(&stdout(&mut outputs)?);
+                scanned.extend(systemd_items(&enabled, &active, &mut ids));
+            }
+        }
+    }
+
+    Ok(render(name, &kinds, &scanned))
+}
+
+/// The commands `kind` is probed with, in the order [`scan`] reads them.
+fn probes(kind: ScanKind, paths: &[String]) -> Vec<String> {
+    match kind {
+        ScanKind::Apt => vec!["apt-mark showmanual".into()],
+        ScanKind::Pacman => vec!["pacman -Qqe".into()],
+        // A quoted newline, as dnf 5 needs one; dnf 4 adds its own too, and
+        // the blank lines that leaves are dropped.
+        ScanKind::Dnf => vec!["dnf repoquery --userinstalled --queryformat '%{name}\n'".into()],
+        ScanKind::Apk => vec!["cat /etc/apk/world".into()],
+        ScanKind::Group => vec!["getent group".into()],
+        ScanKind::User => vec!["getent passwd".into(), "getent group".into()],
+        ScanKind::File => paths
+            .iter()
+            .map(|path| {
+                format!(
+                    "sudo -n find {} -xdev \\( -type f -o -type d -o -type l \\) -printf '%y\\t%m\\t%u\\t%g\\t%s\\t%p\\t%l\\n'",
+                    shell_words::quote(path)
+                )
+            })
+            .collect(),
+        // The first column of each, without the table's header and footer.
+        ScanKind::Systemd => vec![
+            "systemctl list-unit-files --type=service --state=enabled --no-legend | cut -d' ' -f1"
+                .into(),
+            "systemctl list-units --type=service --state=active --no-legend --plain | cut -d' ' -f1"
+                .into(),
+        ],
+    }
+}
+
+/// The next probe's stdout, or its failure.
+fn stdout(outputs: &mut impl Iterator<Item = (String, SshOutput)>) -> Result<String, ScanError> {
+    let (command, output) = outputs.next().expect("a batch has one output per command");
+    let stdout = stdout_bytes(command, output)?;
+    Ok(String::from_utf8_lossy(&stdout).into_owned())
+}
+
+fn stdout_bytes(command: String, output: SshOutput) -> Result<Vec<u8>, ScanError> {
+    if !output.success() {
+        return Err(ScanError::Probe {
+            command,
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        });
+    }
+    Ok(output.stdout)
+}
+
+fn lines(stdout: &str) -> Vec<String> {
+    let mut lines: Vec<String> = stdout
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect();
+    lines.sort();
+    lines.dedup();
+    lines
+}
+
+/// The package names in `/etc/apk/world`, without the version constraints
+/// (`nginx>=1.26`) or repository tags (`curl@edge`) they were added with.
+/// Conflicts (`!busybox-extras`) aren't packages to install, so are dropped.
+fn apk_world(stdout: &str) -> Vec<String> {
+    let names: Vec<&str> = stdout
+        .split_whitespace()
+        .filter(|entry| !entry.starts_with('!'))
+        .map(|entry| {
+            entry
+                .find(['@', '=', '<', '>', '~'])
+                .map_or(entry, |end| &entry[..end])
+        })
+        .collect();
+    lines(&names.join("\n"))
+}
+
+fn packages_item(module: &'static str, id: String, packages: Vec<String>) -> Option<Scanned> {
+    if packages.is_empty() {
+        return None;
+    }
+    Some(Scanned::Item {
+        todos: vec!["drop the packages the distro installs on every machine".into()],
+        module,
+        id,
+        params: vec![("packages", Param::List(packages))],
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct GroupEntry {
+    name: String,
+    gid: u32,
+    members: Vec<String>,
+}
+
+/// `getent group` lines: `name:password:gid:member,member`.
+fn parse_groups(stdout: &str) -> Vec<GroupEntry> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?.to_string();
+            let gid = fields.nth(1)?.parse().ok()?;
+            let members = fields
+                .next()?
+                .split(',')
+                .filter(|member| !member.is_empty())
+                .map(String::from)
+                .collect();
+            Some(GroupEntry { name, gid, members })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct UserEntry {
+    name: String,
+    uid: u32,
+    gid: u32,
+    comment: String,
+    home: String,
+    shell: String,
+}
+
+/// `getent passwd` lines: `name:password:uid:gid:gecos:home:shell`.
+fn parse_users(stdout: &str) -> Vec<UserEntry> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            let [name, _, uid, gid, gecos, home, shell] = fields.as_slice() else {
+                return None;
+            };
+            Some(UserEntry {
+                name: name.to_string(),
+                uid: uid.parse().ok()?,
+                gid: gid.parse().ok()?,
+                // `Full Name,,,`: the rest of the GECOS fields, usually empty.
+                comment: gecos.trim_end_matches(',').to_string(),
+                home: home.to_string(),
+                shell: shell.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn group_items(groups: &[GroupEntry], ids: &mut Ids) -> Vec<Scanned> {
+    groups
+        .iter()
+        .filter(|group| REGULAR_IDS.contains(&group.gid))
+        .map(|group| {
+            let mut params = vec![
+                ("state", Param::String("present".into())),
+                ("name", Param::String(group.name.clone())),
+                ("gid", Param::Number(group.gid.into())),
+            ];
+            if !group.members.is_empty() {
+                params.push(("append_users", Param::List(group.members.clone())));
+            }
+            Scanned::Item {
+                todos: Vec::new(),
+                module: "@core/group",
+                id: ids.next(&format!("group {}", group.name)),
+                params,
+            }
+        })
+        .collect()
+}
+
+fn user_items(users: &[UserEntry], groups: &[GroupEntry], ids: &mut Ids) -> Vec<Scanned> {
+    let group_names: BTreeMap<u32, &str> = groups
+        .iter()
+        .map(|group| (group.gid, group.name.as_str()))
+        .collect();
+    users
+        .iter()
+        .filter(|user| REGULAR_IDS.contains(&user.uid))
+        .map(|user| {
+            let mut todos = Vec::new();
+            let mut params = vec![
+                ("state", Param::String("present".into())),
+                ("name", Param::String(user.name.clone())),
+                ("uid", Param::Number(user.uid.into())),
+            ];
+            match group_names.get(&user.gid) {
+                Some(group) => params.push(("group", Param::String(group.to_string()))),
+                None => todos.push(format!("primary gid {} has no group", user.gid)),
+            }
+            let append_groups: Vec<String> = groups
+                .iter()
+                .filter(|group| group.members.contains(&user.name))
+                .map(|group| group.name.clone())
+                .collect();
+            if !append_groups.is_empty() {
+                params.push(("append_groups", Param::List(append_groups)));
+            }
+            if !user.comment.is_empty() {
+                params.push(("comment", Param::String(user.comment.clone())));
+            }
+            params.push(("home", Param::String(user.home.clone())));
+            params.push(("shell", Param::String(user.shell.clone())));
+            Scanned::Item {
+                todos,
+                module: "@core/user",
+                id: ids.next(&format!("user {}", user.name)),
+                params,
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum FindKind {
+    File,
+    Directory,
+    Symlink { target: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct FindEntry {
+    kind: FindKind,
+    mode: u32,
+    user: String,
+    group: String,
+    size: u64,
+    path: String,
+}
+
+/// The probe's `find -printf` lines: type, octal mode, user, group, size,
+/// path and symlink target, tab-separated.
+fn parse_find(stdout: &str) -> Vec<FindEntry> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [kind, mode, user, group, size, path, target] = fields.as_slice() else {
+                return None;
+            };
+            let kind = match *kind {
+                "f" => FindKind::File,
+                "d" => FindKind::Directory,
+                "l" => FindKind::Symlink {
+                    target: target.to_string(),
+                },
+                _ => return None,
+            };
+            Some(FindEntry {
+                kind,
+                mode: u32::from_str_radix(mode, 8).ok()?,
+                user: user.to_string(),
+                group: group.to_string(),
+                size: size.parse().ok()?,
+                path: path.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Read the files small enough to inline, then capture every entry. A path
+/// under more than one of `--paths` is captured once.
+async fn file_items(
+    ssh: &mut Ssh,
+    entries: Vec<FindEntry>,
+    ids: &mut Ids,
+) -> Result<Vec<Scanned>, ScanError> {
+    let mut seen = BTreeSet::new();
+    let entries: Vec<FindEntry> = entries
+        .into_iter()
+        .filter(|entry| seen.insert(entry.path.clone()))
+        .collect();
+
+    let reads: Vec<&FindEntry> = entries
+        .iter()
+        .filter(|entry| entry.kind == FindKind::File && entry.size <= MAX_CONTENTS_BYTES)
+        .collect();
+    let commands: Vec<String> = reads
+        .iter()
+        .map(|entry| format!("sudo -n cat -- {}", shell_words::quote(&entry.path)))
+        .collect();
+    let outputs = ssh.output_batch(&commands).await?;
+    let mut contents = BTreeMap::new();
+    for ((command, output), entry) in commands.into_iter().zip(outputs).zip(reads) {
+        // Anything but UTF-8 can't be inlined as a plan string.
+        if let Ok(stdout) = String::from_utf8(stdout_bytes(command, output)?) {
+            contents.insert(entry.path.clone(), stdout);
+        }
+    }
+    Ok(entries
+        .iter()
+        .map(|entry| file_item(entry, contents.get(&entry.path).map(String::as_str), ids))
+        .collect())
+}
+
+/// `contents` are the file's, if it was read.
+fn file_item(entry: &FindEntry, contents: Option<&str>, ids: &mut Ids) -> Scanned {
+    let mut todos = Vec::new();
+    let (module, state) = match &entry.kind {
+        FindKind::Directory => ("@core/directory", "present"),
+        FindKind::File => (
+            "@core/file",
+            if contents.is_some() {
+                "contents"
+            } else {
+                "present"
+            },
+        ),
+        FindKind::Symlink { target } => {
+            return Scanned::Skipped {
+                reason: format!(
+                    "{} is a symlink to {target}; @core/file links to a path on the machine running lusid",
+                    entry.path
+                ),
+            };
+        }
+    };
+    let mut params = vec![
+        ("state", Param::String(state.into())),
+        ("path", Param::String(entry.path.clone())),
+    ];
+    match contents {
+        Some(contents) => params.push(("contents", Param::String(contents.into()))),
+        None if entry.kind == FindKind::File => {
+            let why = if entry.size > MAX_CONTENTS_BYTES {
+                format!("{} bytes, too many to inline", entry.size)
+            } else {
+                "not UTF-8, so can't be inlined".to_string()
+            };
+            todos.push(format!(
+                "{why}; copy it next to the plan and use `state: \"sourced\"`"
+            ));
+        }
+        None => {}
+    }
+    params.push(("mode", Param::Mode(entry.mode)));
+    params.push(("user", Param::String(entry.user.clone())));
+    params.push(("group", Param::String(entry.group.clone())));
+    Scanned::Item {
+        todos,
+        module,
+        id: ids.next(&entry.path),
+        params,
+    }
+}
+
+fn systemd_items(enabled: &[String], active: &[String], ids: &mut Ids) -> Vec<Scanned> {
+    enabled
+        .iter()
+        // Templates, like `getty@.service`, are enabled per instance.
+        .filter(|unit| !unit.contains("@."))
+        .map(|unit| Scanned::Item {
+            todos: Vec::new(),
+            module: "@core/systemd",
+            id: ids.next(unit.trim_end_matches(".service")),
+            params: vec![
+                ("name", Param::String(unit.clone())),
+                ("enabled", Param::Bool(true)),
+                ("active", Param::Bool(active.contains(unit))),
+            ],
+        })
+        .collect()
+}
+
+fn render(name: &str, kinds: &[ScanKind], scanned: &[Scanned]) -> String {
+    let kinds: Vec<String> = kinds
+        .iter()
+        .filter_map(|kind| kind.to_possible_value())
+        .map(|kind| kind.get_name().to_string())
+        .collect();
+    let mut out = String::new();
+    out.push_str(&format!("name: {}\n", quote(name)));
+    out.push_str("version: \"0.1.0\"\n\n");
+    out.push_str(&format!(
+        "# Scanned from {name} by `lusid scan` ({}).\n",
+        kinds.join(", ")
+    ));
+    out.push_str("# Review every item, and each TODO(scan), before applying.\n\n");
+    out.push_str("params: {}\n\n");
+
+    if scanned.is_empty() {
+        out.push_str("setup: (params, system) => []\n");
+        return out;
+    }
+    out.push_str("setup: (params, system) =>\n");
+    for (index, scanned) in scanned.iter().enumerate() {
+        if index > 0 {
+            out.push('\n');
+        }
+        match scanned {
+            Scanned::Item {
+                todos,
+                module,
+                id,
+                params,
+            } => {
+                for todo in todos {
+                    out.push_str(&format!("  # TODO(scan): {todo}\n"));
+                }
+                out.push_str(&format!("  - module: {}\n", quote(module)));
+                out.push_str(&format!("    id: {}\n", quote(id)));
+                out.push_str("    params:\n");
+                for (key, param) in params {
+                    out.push_str(&format!("      {key}: {}\n", render_param(param)));
+                }
+            }
+            Scanned::Skipped { reason } => {
+                out.push_str(&format!("  # TODO(scan): skipped: {reason}\n"));
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captures_regular_users_and_groups() {
+        let groups =
+            parse_groups("root:x:0:\nsudo:x:27:alice\nalice:x:1000:\ndev:x:1001:alice,bob\n");
+        let users = parse_users(
+            "root:x:0:0:root:/root:/bin/bash\nalice:x:1000:1000:Alice,,,:/home/alice:/bin/bash\n",
+        );
+        let mut ids = Ids::default();
+        let mut scanned = group_items(&groups, &mut ids);
+        scanned.extend(user_items(&users, &groups, &mut ids));
+        let plan = render("web", &[ScanKind::Group, ScanKind::User], &scanned);
+
+        assert!(plan.contains("# Scanned from web by `lusid scan` (group, user).\n"));
+        assert!(plan.contains(
+            "  - module: \"@core/group\"\n    id: \"group-dev\"\n    params:\n      state: \"present\"\n      name: \"dev\"\n      gid: 1001\n      append_users: [\"alice\", \"bob\"]\n"
+        ));
+        assert!(plan.contains("      append_groups: [\"sudo\", \"dev\"]\n"));
+        assert!(plan.contains("      comment: \"Alice\"\n"));
+        assert!(!plan.contains("\"root\""));
+    }
+
+    #[test]
+    fn captures_files_and_directories() {
+        let entries = parse_find(
+            "d\t755\troot\troot\t4096\t/etc/nginx\t\nf\t644\troot\troot\t12\t/etc/nginx/nginx.conf\t\nf\t600\troot\troot\t999999\t/etc/nginx/big.bin\t\nl\t777\troot\troot\t7\t/etc/nginx/current\t/srv/v1\n",
+        );
+        assert_eq!(entries.len(), 4);
+
+        let mut ids = Ids::default();
+        let directory = file_item(&entries[0], None, &mut ids);
+        let conf = file_item(&entries[1], Some("events {}\n"), &mut ids);
+        let big = file_item(&entries[2], None, &mut ids);
+        let link = file_item(&entries[3], None, &mut ids);
+        let plan = render("web", &[ScanKind::File], &[directory, conf, big, link]);
+
+        assert!(plan.contains("  - module: \"@core/directory\"\n    id: \"etc-nginx\"\n"));
+        assert!(plan.contains(
+            "      state: \"contents\"\n      path: \"/etc/nginx/nginx.conf\"\n      contents: \"events {}\\n\"\n      mode: 420 # 0o644\n"
+        ));
+        assert!(plan.contains("  # TODO(scan): 999999 bytes, too many to inline;"));
+        assert!(
+            plan.contains("  # TODO(scan): skipped: /etc/nginx/current is a symlink to /srv/v1;")
+        );
+    }
+
+    #[test]
+    fn reads_package_names_from_the_apk_world() {
+        assert_eq!(
+            apk_world("alpine-base curl@edge nginx>=1.26 !busybox-extras\n"),
+            ["alpine-base", "curl", "nginx"]
+        );
+    }
+
+    #[test]
+    fn skips_templated_services() {
+        let enabled = lines("ssh.service\ngetty@.service\nnginx.service\n");
+        let active = lines("ssh.service\n");
+        let scanned = systemd_items(&enabled, &active, &mut Ids::default());
+        assert_eq!(scanned.len(), 2);
+        let plan = render("web", &[ScanKind::Systemd], &scanned);
+        assert!(plan.contains(
+            "    id: \"nginx\"\n    params:\n      name: \"nginx.service\"\n      enabled: true\n      active: false\n"
+        ));
+    }
+}
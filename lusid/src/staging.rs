@@ -0,0 +1,139 @@
+//! The staging directory on apply targets: everything lusid uploads to a
+//! target lives under one directory, so it's easy to find, and
+//! `lusid <dev|remote> clean` can remove it in one go.
+//!
+//! Layout:
+//!
+//! - `bin/lusid-apply` — the uploaded apply binary.
+//! - `plan/` — the plan's directory.
+//! - `identity`, `secrets/` — the guest identity and the secrets
+//!   re-encrypted to it (only when secrets are forwarded).
+//! - `backups/`, `checkpoints/` — reserved for operations that keep state
+//!   between applies.
+//!
+//! Configured with `staging_dir` in `lusid.toml`, at the top level or per
+//! machine. A leading `~/` is the SSH user's home on the target, since apply
+//! runs as that user (escalating with `sudo` per operation).
+
+use lusid_ssh::{Ssh, SshError};
+use thiserror::Error;
+
+pub const DEFAULT_STAGING_DIR: &str = "~/.cache/lusid";
+
+#[derive(Error, Debug)]
+pub enum StagingError {
+    #[error("failed to resolve $HOME on target: {0}")]
+    ResolveHome(String),
+
+    #[error("failed to remove staging directory {path}: {detail}")]
+    Clean { path: String, detail: String },
+
+    #[error(transparent)]
+    Ssh(#[from] SshError),
+}
+
+/// Why a configured `staging_dir` was rejected, if it was. Staging dirs end
+/// up in `rm -rf` and in single-quoted shell words, so be strict: absolute or
+/// `~/`-relative, below the root, no `..`, no quotes.
+pub fn validate_staging_dir(staging_dir: &str) -> Result<(), &'static str> {
+    let relative = if let Some(rest) = staging_dir.strip_prefix("~/") {
+        rest
+    } else if let Some(rest) = staging_dir.strip_prefix('/') {
+        rest
+    } else {
+        return Err("must be absolute or start with ~/");
+    };
+    if relative.trim_matches('/').is_empty() {
+        return Err("must be a directory below / or ~");
+    }
+    if relative.split('/').any(|component| component == "..") {
+        return Err("must not contain ..");
+    }
+    if staging_dir.contains('\'') {
+        return Err("must not contain a single quote");
+    }
+    Ok(())
+}
+
+/// A staging directory resolved to an absolute path on the target.
+#[derive(Debug, Clone)]
+pub struct StagingDir {
+    root: String,
+}
+
+impl StagingDir {
+    /// Resolve a (validated) configured staging dir against the target,
+    /// expanding a leading `~/`.
+    pub async fn resolve(ssh: &mut Ssh, staging_dir: &str) -> Result<Self, StagingError> {
+        let Some(rest) = staging_dir.strip_prefix("~/") else {
+            return Ok(Self::new(staging_dir));
+        };
+        let output = ssh.output("printf '%s' \"$HOME\"").await?;
+        let home = String::from_utf8_lossy(&output.stdout).into_owned();
+        if !output.success() || !home.starts_with('/') {
+            return Err(StagingError::ResolveHome(format!(
+                "got {home:?} (exit code {:?})",
+                output.exit_code
+            )));
+        }
+        Ok(Self::new(&format!("{}/{rest}", home.trim_end_matches('/'))))
+    }
+
+    /// A staging directory at the absolute path `root`, for targets with no
+    /// home to resolve against.
+    pub fn new(root: &str) -> Self {
+        Self {
+            root: root.trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    pub fn apply_bin(&self) -> String {
+        format!("{}/bin/lusid-apply", self.root)
+    }
+
+    pub fn plan_dir(&self) -> String {
+        format!("{}/plan", self.root)
+    }
+
+    pub fn identity_path(&self) -> String {
+        format!("{}/identity", self.root)
+    }
+
+    pub fn secrets_dir(&self) -> String {
+        format!("{}/secrets", self.root)
+    }
+
+    /// Remove the staging directory and everything in it. A no-op if it
+    /// doesn't exist.
+    pub async fn clean(&self, ssh: &mut Ssh) -> Result<(), StagingError> {
+        let output = ssh.output(&format!("rm -rf -- '{}'", self.root)).await?;
+        if !output.success() {
+            return Err(StagingError::Clean {
+                path: self.root.clone(),
+                detail: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_staging_dirs() {
+        assert_eq!(validate_staging_dir("~/.cache/lusid"), Ok(()));
+        assert_eq!(validate_staging_dir("/var/lib/lusid/"), Ok(()));
+        assert!(validate_staging_dir("/").is_err());
+        assert!(validate_staging_dir("~/").is_err());
+        assert!(validate_staging_dir("~").is_err());
+        assert!(validate_staging_dir("relative/dir").is_err());
+        assert!(validate_staging_dir("/var/lib/../..").is_err());
+        assert!(validate_staging_dir("/tmp/it's").is_err());
+    }
+}
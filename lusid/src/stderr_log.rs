@@ -0,0 +1,196 @@
+//! Parsing `lusid-apply`'s stderr for the TUI's stderr page.
+//!
+//! `lusid-apply` logs with `tracing_subscriber`'s default formatter, so a
+//! log line looks like
+//!
+//! ```text
+//! 2026-10-16T12:04:31.123456Z  WARN lusid_apply: message
+//! ```
+//!
+//! with ANSI colors around the timestamp, level and target. [`StderrLine`]
+//! strips the colors and picks out the level, so the TUI can color lines
+//! itself and hide the noisy ones with a [`LevelFilter`].
+//!
+//! Lines without a level (a multi-line message's continuation, the rendered
+//! apply error, a panic) are kept as-is and never filtered out: they're rare,
+//! and usually the ones worth reading.
+//!
+//! [`LogLevel`] also steps `lusid-apply`'s own filter up and down while it
+//! runs, with the `log-more` / `log-less` actions.
+
+use std::fmt::{self, Display};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    fn parse(word: &str) -> Option<Self> {
+        match word {
+            "TRACE" => Some(LogLevel::Trace),
+            "DEBUG" => Some(LogLevel::Debug),
+            "INFO" => Some(LogLevel::Info),
+            "WARN" => Some(LogLevel::Warn),
+            "ERROR" => Some(LogLevel::Error),
+            _ => None,
+        }
+    }
+
+    /// `--log` as a bare level, e.g. `"debug"`. `None` for anything more
+    /// involved, like per-target directives.
+    pub fn from_filter(filter: &str) -> Option<Self> {
+        Self::parse(&filter.trim().to_ascii_uppercase())
+    }
+
+    pub fn as_filter(self) -> &'static str {
+        match self {
+            LogLevel::Trace => "trace",
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+        }
+    }
+
+    pub fn more_verbose(self) -> Self {
+        match self {
+            LogLevel::Error => LogLevel::Warn,
+            LogLevel::Warn => LogLevel::Info,
+            LogLevel::Info => LogLevel::Debug,
+            LogLevel::Debug | LogLevel::Trace => LogLevel::Trace,
+        }
+    }
+
+    pub fn less_verbose(self) -> Self {
+        match self {
+            LogLevel::Trace => LogLevel::Debug,
+            LogLevel::Debug => LogLevel::Info,
+            LogLevel::Info => LogLevel::Warn,
+            LogLevel::Warn | LogLevel::Error => LogLevel::Error,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StderrLine {
+    pub text: String,
+    pub level: Option<LogLevel>,
+}
+
+impl StderrLine {
+    pub fn parse(raw: &str) -> Self {
+        let text = strip_ansi(raw);
+        // The level is the first word, or the second after a timestamp.
+        let level = text.split_whitespace().take(2).find_map(LogLevel::parse);
+        Self { text, level }
+    }
+}
+
+/// Which stderr lines the stderr page shows, cycled with the
+/// `filter-level` action.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LevelFilter {
+    #[default]
+    All,
+    Warn,
+    Error,
+}
+
+impl LevelFilter {
+    pub fn next(self) -> Self {
+        match self {
+            LevelFilter::All => LevelFilter::Warn,
+            LevelFilter::Warn => LevelFilter::Error,
+            LevelFilter::Error => LevelFilter::All,
+        }
+    }
+
+    pub fn allows(self, line: &StderrLine) -> bool {
+        let min = match self {
+            LevelFilter::All => return true,
+            LevelFilter::Warn => LogLevel::Warn,
+            LevelFilter::Error => LogLevel::Error,
+        };
+        line.level.is_none_or(|level| level >= min)
+    }
+}
+
+impl Display for LevelFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LevelFilter::All => "all",
+            LevelFilter::Warn => "warn+error",
+            LevelFilter::Error => "error",
+        })
+    }
+}
+
+/// Drop ANSI escape sequences: CSI sequences (`ESC [ ... final`) whole, and
+/// any other escape along with the character after it.
+fn strip_ansi(raw: &str) -> String {
+    let mut text = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(char) = chars.next() {
+        if char != '\x1b' {
+            text.push(char);
+            continue;
+        }
+        if chars.next() == Some('[') {
+            for char in chars.by_ref() {
+                if ('\x40'..='\x7e').contains(&char) {
+                    break;
+                }
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_colored_tracing_lines() {
+        let line = StderrLine::parse(
+            "\x1b[2m2026-10-16T12:04:31.123456Z\x1b[0m \x1b[33m WARN\x1b[0m \
+             \x1b[2mlusid_apply\x1b[0m\x1b[2m:\x1b[0m disk almost full",
+        );
+        assert_eq!(line.level, Some(LogLevel::Warn));
+        assert_eq!(
+            line.text,
+            "2026-10-16T12:04:31.123456Z  WARN lusid_apply: disk almost full"
+        );
+
+        let line = StderrLine::parse("DEBUG lusid_apply: no timestamp");
+        assert_eq!(line.level, Some(LogLevel::Debug));
+    }
+
+    #[test]
+    fn lines_without_a_level_pass_every_filter() {
+        let plain = StderrLine::parse("error: unknown module `@core/nope`");
+        let info = StderrLine::parse("2026-10-16T12:04:31Z  INFO lusid_apply: hi");
+        let error = StderrLine::parse("2026-10-16T12:04:31Z ERROR lusid_apply: oh no");
+        assert_eq!(plain.level, None);
+
+        assert!(LevelFilter::Warn.allows(&plain));
+        assert!(!LevelFilter::Warn.allows(&info));
+        assert!(LevelFilter::Warn.allows(&error));
+        assert!(LevelFilter::Error.allows(&plain));
+        assert!(LevelFilter::All.allows(&info));
+    }
+
+    #[test]
+    fn steps_log_levels() {
+        assert_eq!(LogLevel::from_filter("Debug"), Some(LogLevel::Debug));
+        assert_eq!(LogLevel::from_filter("lusid_apply=debug,info"), None);
+        assert_eq!(LogLevel::Info.more_verbose().as_filter(), "debug");
+        assert_eq!(LogLevel::Trace.more_verbose(), LogLevel::Trace);
+        assert_eq!(LogLevel::Error.less_verbose(), LogLevel::Error);
+    }
+}
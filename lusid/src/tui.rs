@@ -8,11 +8,26 @@
 //!   [`FlatViewTree`] (tree navigation with collapse/expand/selection)
 //! - an "operations apply" pane during execution that flat-lists each
 //!   operation and shows its streaming stdout/stderr
-//! - a separate stderr page accumulating the full apply stderr buffer
+//! - a separate stderr page accumulating the full apply stderr buffer,
+//!   colored by log level and filterable down to warnings and errors (see
+//!   [`stderr_log`](crate::stderr_log)), with warning/error counts badged
+//!   on the pipeline box
 //!
-//! Input: crossterm events are read on a dedicated OS thread (blocking read)
+//! The TUI also talks back: [`AppControl`] messages written to `control`
+//! (`lusid-apply --control`'s stdin) change the apply's log level while it
+//! runs, and pause, resume or cancel it between operations.
+//!
+//! Drawing is driven by a dirty flag: each stdout update, stderr line, input
+//! event or exit marks the view dirty, and a dirty view is redrawn on the
+//! next tick of a 30 fps clock, so a flood of operation output doesn't redraw
+//! the terminal once per line.
+//!
+//! Input: crossterm events are read on a dedicated OS thread (polling read)
 //! and forwarded into a tokio mpsc channel so the main select loop stays
-//! responsive. Terminal raw-mode is acquired via `ratatui::init` and
+//! responsive. Keys are looked up in a [`KeyMap`] (see [`keys`](crate::keys))
+//! and handled as [`Action`]s, so bindings can be remapped in `lusid.toml`.
+//! Focus reporting is enabled too, so that an apply finishing while the
+//! terminal is in the background can [notify](crate::notify) the user. Terminal raw-mode is acquired via `ratatui::init` and
 //! restored in the [`TerminalSession`]'s `Drop` so panics don't leave the
 //! terminal in a bad state.
 
@@ -21,15 +36,20 @@
 use std::collections::HashSet;
 use std::future::Future;
 use std::io;
+use std::ops::Range;
 use std::pin::Pin;
+use std::time::Duration;
 
-use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::event::{
+    DisableFocusChange, EnableFocusChange, Event, KeyCode, KeyEvent, KeyModifiers,
+};
 use lusid_apply_stdio::{
-    AppUpdate, AppView, AppViewError, FlatViewTree, FlatViewTreeError, FlatViewTreeNode,
-    OperationView, ViewNode,
+    AppControl, AppUpdate, AppView, AppViewError, FlatViewTree, FlatViewTreeError,
+    FlatViewTreeNode, OperationView, ViewNode,
 };
 use lusid_cmd::CommandError;
 use lusid_ssh::SshError;
+use lusid_view::{Color as ViewColor, TextStyle, View};
 use ratatui::{
     CompletedFrame, DefaultTerminal, Frame,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
@@ -40,10 +60,23 @@ use ratatui::{
 use serde_json::Error as SerdeJsonError;
 use thiserror::Error;
 use tokio::{
-    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
     sync::mpsc::{UnboundedReceiver, unbounded_channel},
+    time::{MissedTickBehavior, interval},
 };
 
+use crate::keys::{Action, KeyMap};
+use crate::logs::MachineLog;
+use crate::notify::{Notifier, notify};
+use crate::stderr_log::{LevelFilter, LogLevel, StderrLine};
+
+/// The shortest time between redraws, capping the TUI at 30 frames a second.
+const FRAME_INTERVAL: Duration = Duration::from_millis(1000 / 30);
+
+/// How long the event thread waits for input before checking whether the
+/// TUI is still listening.
+const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 #[derive(Error, Debug)]
 pub enum TuiError {
     #[error(transparent)]
@@ -74,26 +107,47 @@ pub enum TuiError {
     TaskJoin(#[from] tokio::task::JoinError),
 }
 
+/// Per-user TUI settings, from `lusid.toml` (see [`Config::tui_options`](crate::config::Config::tui_options)).
+#[derive(Debug, Clone)]
+pub struct TuiOptions {
+    pub keys: KeyMap,
+    pub notify: Vec<Notifier>,
+    /// The apply's starting `--log`, if it's a bare level; `log-more` /
+    /// `log-less` step from `info` otherwise.
+    pub log_level: Option<LogLevel>,
+}
+
 /// Drive the TUI. Reads `stdout` line-by-line as JSON `AppUpdate`s and
 /// `stderr` line-by-line as raw text, while racing a `wait` future that
-/// resolves when the apply process exits. Returns when the user quits or
+/// resolves when the apply process exits. Writes `AppControl`s to `control`
+/// as JSON lines, and copies both streams into `log` as they're read. Returns when the user quits or
 /// the wait future resolves; surfaces the apply's exit error if any.
 ///
 /// Generic over the IO and wait types so the same function works for a
-/// subprocess (`lusid-cmd`) and an SSH command handle (`lusid-ssh`).
-pub async fn tui<Stdout, Stderr, Wait, WaitError>(
+/// subprocess (`lusid-cmd`), an SSH command handle (`lusid-ssh`), and a
+/// [replay](crate::history) of a logged run, which has no `log` to copy to.
+pub async fn tui<Stdout, Stderr, Control, Wait, WaitError>(
     stdout: Stdout,
     stderr: Stderr,
+    mut control: Control,
     wait: Pin<Box<Wait>>,
+    mut log: Option<&mut MachineLog>,
+    options: TuiOptions,
 ) -> Result<(), TuiError>
 where
     Stdout: AsyncRead + Unpin,
     Stderr: AsyncRead + Unpin,
+    Control: AsyncWrite + Unpin,
     Wait: Future<Output = Result<(), WaitError>>,
     WaitError: Into<TuiError>,
 {
+    let TuiOptions {
+        keys,
+        notify: notifiers,
+        log_level,
+    } = options;
     let mut terminal = TerminalSession::init();
-    let mut app = TuiApp::new();
+    let mut app = TuiApp::new(keys, log_level);
 
     let mut stdout_lines = BufReader::new(stdout).lines();
     let mut stderr_lines = BufReader::new(stderr).lines();
@@ -104,24 +158,40 @@ where
 
     let mut outcome: Option<Result<(), TuiError>> = None;
     let mut should_quit = false;
+    let mut notified = false;
+
+    // Redraw only once something changed, and at most once a frame: a burst
+    // of operation output then costs one draw, not one per line. After idling,
+    // the first change draws straight away.
+    let mut dirty = true;
+    let mut frames = interval(FRAME_INTERVAL);
+    frames.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
     tokio::pin!(wait);
 
     loop {
-        terminal.draw(|frame| draw_ui(frame, &mut app, outcome.as_ref()))?;
-
         tokio::select! {
+            _ = frames.tick(), if dirty => {
+                terminal.draw(|frame| draw_ui(frame, &mut app, outcome.as_ref()))?;
+                dirty = false;
+            }
+
             result = &mut wait, if outcome.is_none() => {
                 app.child_exited = true;
                 outcome = Some(result.map_err(Into::into));
+                dirty = true;
             }
 
             line = stdout_lines.next_line(), if !stdout_done => {
                 match line {
                     Ok(Some(line)) => {
                         if !line.trim().is_empty() {
+                            if let Some(log) = log.as_deref_mut() {
+                                log.update(&line).await;
+                            }
                             let update: AppUpdate = serde_json::from_str(&line)?;
                             app.apply_update(update)?;
+                            dirty = true;
                         }
                     }
                     Ok(None) => stdout_done = true,
@@ -133,7 +203,11 @@ where
                 match line {
                     Ok(Some(line)) => {
                         if !line.trim().is_empty() {
-                            app.push_stderr(line)
+                            if let Some(log) = log.as_deref_mut() {
+                                log.stderr(&line).await;
+                            }
+                            app.push_stderr(line);
+                            dirty = true;
                         }
                     }
                     Ok(None) => stderr_done = true,
@@ -143,6 +217,26 @@ where
 
             Some(event) = events.recv() => {
                 should_quit = app.handle_event(event)?;
+                dirty = true;
+            }
+        }
+
+        // Best-effort: once the apply has exited there's nobody to tell.
+        if let Some(message) = app.pending_control.take() {
+            if !app.child_exited {
+                let mut line = serde_json::to_string(&message)?;
+                line.push('\n');
+                let _ = control.write_all(line.as_bytes()).await;
+                let _ = control.flush().await;
+            }
+        }
+
+        // Wait for stdout to drain too, so a final `AppUpdate::Error` counts.
+        if !notified && outcome.is_some() && stdout_done {
+            notified = true;
+            if !app.focused {
+                let succeeded = matches!(outcome, Some(Ok(()))) && app.apply_error.is_none();
+                notify(&notifiers, succeeded);
             }
         }
 
@@ -157,13 +251,15 @@ where
     }
 }
 
-struct TerminalSession {
+pub(crate) struct TerminalSession {
     terminal: DefaultTerminal,
 }
 
 impl TerminalSession {
-    fn init() -> Self {
+    pub fn init() -> Self {
         let terminal = ratatui::init();
+        // Best-effort: without focus reporting we just never notify.
+        let _ = crossterm::execute!(io::stdout(), EnableFocusChange);
         Self { terminal }
     }
 
@@ -177,18 +273,24 @@ impl TerminalSession {
 
 impl Drop for TerminalSession {
     fn drop(&mut self) {
+        let _ = crossterm::execute!(io::stdout(), DisableFocusChange);
         ratatui::restore();
     }
 }
 
-fn read_events() -> UnboundedReceiver<Event> {
+/// Forward terminal events from a dedicated thread. The thread polls rather
+/// than blocks, so it ends soon after the receiver is dropped instead of
+/// taking the next key press from whatever reads the terminal after.
+pub(crate) fn read_events() -> UnboundedReceiver<Event> {
     let (event_tx, event_rx) = unbounded_channel();
 
     std::thread::spawn(move || {
-        loop {
-            if let Ok(event) = crossterm::event::read() {
-                if event_tx.send(event).is_err() {
-                    break;
+        while !event_tx.is_closed() {
+            if let Ok(true) = crossterm::event::poll(EVENT_POLL_INTERVAL) {
+                if let Ok(event) = crossterm::event::read() {
+                    if event_tx.send(event).is_err() {
+                        break;
+                    }
                 }
             }
         }
@@ -197,6 +299,14 @@ fn read_events() -> UnboundedReceiver<Event> {
     event_rx
 }
 
+/// Whether a key press is a plain one, to look up in the [`KeyMap`]. Shift
+/// is already part of a typed character (`G`), so it doesn't make a
+/// character key a different binding.
+pub(crate) fn is_plain_key(code: KeyCode, modifiers: KeyModifiers) -> bool {
+    modifiers == KeyModifiers::NONE
+        || (modifiers == KeyModifiers::SHIFT && matches!(code, KeyCode::Char(_)))
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum UiPage {
     Main,
@@ -270,11 +380,18 @@ impl PipelineStage {
     }
 }
 
+/// Navigation state for one stage's tree.
+///
+/// `rows` caches the expanded tree flattened to [`TreeRow`]s, without labels,
+/// so a frame only walks the tree after it or `collapsed` changed, and only
+/// labels the rows on screen. Anything that changes either must call
+/// [`invalidate_rows`](Self::invalidate_rows).
 #[derive(Debug, Default, Clone)]
 struct TreeState {
     collapsed: HashSet<usize>,
     selected_node: Option<usize>,
     list_offset: usize,
+    rows: Option<Vec<TreeRow>>,
 }
 
 impl TreeState {
@@ -284,6 +401,26 @@ impl TreeState {
         } else {
             self.collapsed.insert(node_index);
         }
+        self.invalidate_rows();
+    }
+
+    fn invalidate_rows(&mut self) {
+        self.rows = None;
+    }
+
+    /// The visible rows of `tree`, rebuilt if invalidated.
+    fn rows(&mut self, tree: &FlatViewTree) -> &[TreeRow] {
+        if self.rows.is_none() {
+            self.rows = Some(build_visible_rows(tree, self));
+        }
+        self.rows.as_deref().unwrap_or_default()
+    }
+
+    fn selected_row(&mut self, tree: &FlatViewTree) -> Option<usize> {
+        let selected_node = self.selected_node?;
+        self.rows(tree)
+            .iter()
+            .position(|row| row.index == selected_node)
     }
 
     fn is_expanded(&self, node_index: usize) -> bool {
@@ -361,10 +498,11 @@ impl OperationsApplyState {
 /// `follow_pipeline` auto-advances the visible stage as new phases arrive;
 /// disabled by the user when they navigate manually.
 ///
-/// `stderr_buffer` accumulates *all* stderr (not line-limited) so the user
+/// `stderr_lines` accumulates *all* stderr (not line-limited) so the user
 /// can scroll back through the full apply log on the dedicated page.
 #[derive(Debug, Clone)]
 struct TuiApp {
+    keys: KeyMap,
     app_view: AppView,
     stage: PipelineStage,
     follow_pipeline: bool,
@@ -380,19 +518,42 @@ struct TuiApp {
 
     child_exited: bool,
 
-    // Collect *all* stderr output.
-    stderr_buffer: String,
-    stderr_lines_count: usize,
+    // Whether the terminal has focus, per crossterm focus events. Assumed
+    // until told otherwise.
+    focused: bool,
+
+    // Rendered diagnostic from `AppUpdate::Error`, shown in the pipeline box.
+    apply_error: Option<String>,
+
+    // The apply's log level: `None` until changed from a non-bare `--log`.
+    // `log_changed` shows it on the pipeline box once the user has touched
+    // it, and `pending_control` carries the change to the main loop.
+    log_level: Option<LogLevel>,
+    log_changed: bool,
+    pending_control: Option<AppControl>,
+
+    // `pause_requested` is what the pause key last asked for, so pressing it
+    // again asks for the opposite; `paused` is what the apply last
+    // acknowledged, shown on the pipeline box.
+    pause_requested: bool,
+    paused: bool,
+
+    // Collect *all* stderr output, plus counts for the pipeline badge.
+    stderr_lines: Vec<StderrLine>,
+    stderr_warnings: usize,
+    stderr_errors: usize,
 
     // stderr page UI state.
+    stderr_filter: LevelFilter,
     stderr_scroll: u16,
     stderr_follow: bool,
     stderr_view_height: u16,
 }
 
 impl TuiApp {
-    fn new() -> Self {
+    fn new(keys: KeyMap, log_level: Option<LogLevel>) -> Self {
         Self {
+            keys,
             app_view: AppView::default(),
             stage: PipelineStage::ResourceParams,
             follow_pipeline: true,
@@ -408,9 +569,22 @@ impl TuiApp {
 
             child_exited: false,
 
-            stderr_buffer: String::new(),
-            stderr_lines_count: 0,
+            focused: true,
+
+            apply_error: None,
+
+            log_level,
+            log_changed: false,
+            pending_control: None,
+
+            pause_requested: false,
+            paused: false,
+
+            stderr_lines: Vec::new(),
+            stderr_warnings: 0,
+            stderr_errors: 0,
 
+            stderr_filter: LevelFilter::default(),
             stderr_scroll: 0,
             stderr_follow: true,
             stderr_view_height: 0,
@@ -418,10 +592,33 @@ impl TuiApp {
     }
 
     fn apply_update(&mut self, update: AppUpdate) -> Result<(), TuiError> {
+        if let AppUpdate::Error { error } = update {
+            self.apply_error = Some(error.rendered);
+            return Ok(());
+        }
+        if let AppUpdate::ControlApplied { control } = &update {
+            match control {
+                AppControl::Pause => self.paused = true,
+                AppControl::Resume => self.paused = false,
+                _ => {}
+            }
+        }
+
         let current = std::mem::take(&mut self.app_view);
 
         self.app_view = current.update(update)?;
 
+        // Any stage's tree may have changed.
+        for state in [
+            &mut self.params_state,
+            &mut self.resources_state,
+            &mut self.states_state,
+            &mut self.changes_state,
+            &mut self.operations_state,
+        ] {
+            state.invalidate_rows();
+        }
+
         if self.follow_pipeline && self.page == UiPage::Main {
             let next = PipelineStage::from_app_view(&self.app_view);
             if next.is_available(&self.app_view) {
@@ -437,14 +634,22 @@ impl TuiApp {
     }
 
     fn handle_event(&mut self, event: Event) -> Result<bool, TuiError> {
+        match event {
+            Event::FocusGained => self.focused = true,
+            Event::FocusLost => self.focused = false,
+            _ => {}
+        }
+
         if let Event::Key(KeyEvent {
             code, modifiers, ..
         }) = event
         {
-            if modifiers == KeyModifiers::NONE {
-                match self.page {
-                    UiPage::Main => return Ok(self.handle_event_main(code)),
-                    UiPage::Stderr => return Ok(self.handle_event_stderr(code)),
+            if is_plain_key(code, modifiers) {
+                if let Some(action) = self.keys.action(code) {
+                    match self.page {
+                        UiPage::Main => return Ok(self.handle_action_main(action)),
+                        UiPage::Stderr => return Ok(self.handle_action_stderr(action)),
+                    }
                 }
             }
         }
@@ -452,18 +657,18 @@ impl TuiApp {
         Ok(false)
     }
 
-    fn handle_event_main(&mut self, code: KeyCode) -> bool {
-        match code {
-            KeyCode::Char('q') | KeyCode::Esc => return true,
+    fn handle_action_main(&mut self, action: Action) -> bool {
+        match action {
+            Action::Quit => return true,
 
-            KeyCode::Char('e') => {
+            Action::ToggleStderr => {
                 self.page = UiPage::Stderr;
                 self.stderr_follow = true;
                 self.stderr_scroll = u16::MAX; // clamp-to-bottom in draw
                 return false;
             }
 
-            KeyCode::Char('f') => {
+            Action::ToggleFollow => {
                 self.follow_pipeline = !self.follow_pipeline;
                 if self.follow_pipeline {
                     let next = PipelineStage::from_app_view(&self.app_view);
@@ -473,80 +678,108 @@ impl TuiApp {
                 }
             }
 
-            KeyCode::Left => {
+            Action::PrevStage => {
                 self.follow_pipeline = false;
                 self.navigate_stage_relative(-1);
             }
 
-            KeyCode::Right => {
+            Action::NextStage => {
                 self.follow_pipeline = false;
                 self.navigate_stage_relative(1);
             }
 
-            // Optional: keep Tab behavior as another way to move between stages.
-            KeyCode::Tab => {
-                self.follow_pipeline = false;
-                self.navigate_stage_relative(1);
-            }
+            Action::Down => self.move_down(),
+            Action::Up => self.move_up(),
 
-            // Optional: keep Shift-Tab behavior as another way to move between
-            // stages.
-            KeyCode::BackTab => {
-                self.follow_pipeline = false;
-                self.navigate_stage_relative(-1);
-            }
+            Action::Toggle => self.toggle_selected(),
 
-            KeyCode::Down | KeyCode::Char('j') => self.move_down(),
-            KeyCode::Up | KeyCode::Char('k') => self.move_up(),
+            Action::LogMore => self.change_log_level(LogLevel::more_verbose),
+            Action::LogLess => self.change_log_level(LogLevel::less_verbose),
 
-            KeyCode::Enter | KeyCode::Char(' ') => self.toggle_selected(),
+            Action::Pause => self.toggle_pause(),
+            Action::Cancel => self.pending_control = Some(AppControl::Cancel),
 
-            _ => {}
+            Action::PageUp
+            | Action::PageDown
+            | Action::Top
+            | Action::Bottom
+            | Action::FilterLevel => {}
         }
 
         false
     }
 
-    fn handle_event_stderr(&mut self, code: KeyCode) -> bool {
-        match code {
-            KeyCode::Char('q') | KeyCode::Esc => return true,
+    fn handle_action_stderr(&mut self, action: Action) -> bool {
+        match action {
+            Action::Quit => return true,
 
             // Toggle back to main view.
-            KeyCode::Char('e') => {
+            Action::ToggleStderr => {
                 self.page = UiPage::Main;
                 return false;
             }
 
             // Scrolling controls.
-            KeyCode::Up | KeyCode::Char('k') => self.stderr_scroll_up(1),
-            KeyCode::Down | KeyCode::Char('j') => self.stderr_scroll_down(1),
+            Action::Up => self.stderr_scroll_up(1),
+            Action::Down => self.stderr_scroll_down(1),
 
-            KeyCode::PageUp => {
+            Action::PageUp => {
                 let step = self.stderr_view_height.max(1);
                 self.stderr_scroll_up(step);
             }
 
-            KeyCode::PageDown => {
+            Action::PageDown => {
                 let step = self.stderr_view_height.max(1);
                 self.stderr_scroll_down(step);
             }
 
-            KeyCode::Home | KeyCode::Char('g') => {
+            Action::Top => {
                 self.stderr_follow = false;
                 self.stderr_scroll = 0;
             }
 
-            KeyCode::End | KeyCode::Char('G') => {
+            Action::Bottom => {
                 self.stderr_follow = true;
                 self.stderr_scroll = u16::MAX; // clamp-to-bottom in draw
             }
 
-            _ => {}
+            // Line positions shift under a new filter, so jump to the end.
+            Action::FilterLevel => {
+                self.stderr_filter = self.stderr_filter.next();
+                self.stderr_follow = true;
+                self.stderr_scroll = u16::MAX; // clamp-to-bottom in draw
+            }
+
+            Action::LogMore => self.change_log_level(LogLevel::more_verbose),
+            Action::LogLess => self.change_log_level(LogLevel::less_verbose),
+
+            Action::Pause => self.toggle_pause(),
+            Action::Cancel => self.pending_control = Some(AppControl::Cancel),
+
+            Action::ToggleFollow | Action::PrevStage | Action::NextStage | Action::Toggle => {}
         }
 
         false
     }
 
+    fn change_log_level(&mut self, step: fn(LogLevel) -> LogLevel) {
+        let level = step(self.log_level.unwrap_or(LogLevel::Info));
+        self.log_level = Some(level);
+        self.log_changed = true;
+        self.pending_control = Some(AppControl::SetLog {
+            filter: level.as_filter().to_owned(),
+        });
+    }
+
+    fn toggle_pause(&mut self) {
+        self.pause_requested = !self.pause_requested;
+        self.pending_control = Some(if self.pause_requested {
+            AppControl::Pause
+        } else {
+            AppControl::Resume
+        });
+    }
+
     fn navigate_stage_relative(&mut self, direction: i32) {
         if direction == 0 {
             return;
@@ -608,13 +841,10 @@ impl TuiApp {
 
     fn toggle_selected(&mut self) {
         if let Some((tree, state)) = self.tree_for_stage_mut() {
-            let rows = build_visible_rows(tree, state);
-            if rows.is_empty() {
+            let selected_row = state.selected_row(tree).unwrap_or(0);
+            let Some(row) = state.rows(tree).get(selected_row).copied() else {
                 return;
-            }
-
-            let selected_row = selected_row_index(&rows, state).unwrap_or(0);
-            let row = &rows[selected_row];
+            };
 
             if row.is_branch {
                 state.toggle(row.index);
@@ -649,11 +879,13 @@ impl TuiApp {
     }
 
     fn push_stderr(&mut self, line: String) {
-        if !self.stderr_buffer.is_empty() {
-            self.stderr_buffer.push('\n');
+        let line = StderrLine::parse(&line);
+        match line.level {
+            Some(LogLevel::Warn) => self.stderr_warnings += 1,
+            Some(LogLevel::Error) => self.stderr_errors += 1,
+            _ => {}
         }
-        self.stderr_buffer.push_str(&line);
-        self.stderr_lines_count = self.stderr_lines_count.saturating_add(1);
+        self.stderr_lines.push(line);
 
         // If the user is following stderr, keep "pinned to bottom". We
         // don’t know the view height here, so we set an oversize scroll and
@@ -680,7 +912,7 @@ fn draw_ui(frame: &mut ratatui::Frame, app: &mut TuiApp, outcome: Option<&Result
         .direction(Direction::Vertical)
         .constraints(
             [
-                Constraint::Length(4),
+                Constraint::Length(pipeline_height(app)),
                 Constraint::Min(5),
                 Constraint::Length(1),
             ]
@@ -727,25 +959,85 @@ fn draw_pipeline(
         pipeline_spans.push(Span::styled(stage.label(), style));
     }
 
-    let feedback = pipeline_feedback_line(app, outcome);
+    let mut lines = vec![Line::from(pipeline_spans)];
+    match &app.apply_error {
+        // The apply error explains the process error, so it wins over it.
+        Some(error) => lines.extend(
+            error
+                .lines()
+                .map(|line| Line::from(Span::styled(line, Style::default().fg(Color::Red)))),
+        ),
+        None => {
+            let feedback = pipeline_feedback_line(app, outcome);
+            lines.push(Line::from(Span::styled(
+                feedback,
+                Style::default().fg(Color::Yellow),
+            )));
+        }
+    }
 
-    let lines = vec![
-        Line::from(pipeline_spans),
-        Line::from(Span::styled(feedback, Style::default().fg(Color::Yellow))),
-    ];
+    let mut block = Block::bordered().title_top(if app.follow_pipeline {
+        "pipeline (following)"
+    } else {
+        "pipeline"
+    });
+    if let Some(badge) = pipeline_badge(app) {
+        block = block.title_top(badge.right_aligned());
+    }
 
     let widget = Paragraph::new(Text::from(lines))
-        .block(Block::bordered().title_top(if app.follow_pipeline {
-            "pipeline (following)"
-        } else {
-            "pipeline"
-        }))
+        .block(block)
         .alignment(Alignment::Left)
-        .wrap(Wrap { trim: true });
+        // Untrimmed so the caret line of a source excerpt keeps its indent.
+        .wrap(Wrap { trim: false });
 
     frame.render_widget(widget, area);
 }
 
+/// Warning and error counts from stderr, so they're noticed without opening
+/// the stderr page, the log level once the user has changed it, and whether
+/// the apply is paused. `None` while there's none of those.
+fn pipeline_badge(app: &TuiApp) -> Option<Line<'static>> {
+    let mut spans = Vec::new();
+    if app.paused {
+        spans.push(Span::styled(
+            " paused ",
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    if let Some(level) = app.log_level.filter(|_| app.log_changed) {
+        spans.push(Span::styled(
+            format!(" log {} ", level.as_filter()),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    if app.stderr_warnings > 0 {
+        spans.push(Span::styled(
+            format!(" {} warn ", app.stderr_warnings),
+            level_style(LogLevel::Warn),
+        ));
+    }
+    if app.stderr_errors > 0 {
+        spans.push(Span::styled(
+            format!(" {} error ", app.stderr_errors),
+            level_style(LogLevel::Error),
+        ));
+    }
+    (!spans.is_empty()).then(|| Line::from(spans))
+}
+
+/// Borders + stage row + feedback: one feedback line normally, or the whole
+/// rendered apply error (capped so the main pane stays usable).
+fn pipeline_height(app: &TuiApp) -> u16 {
+    const MAX_FEEDBACK_LINES: usize = 9;
+    let feedback_lines = app.apply_error.as_ref().map_or(1, |error| {
+        error.lines().count().clamp(1, MAX_FEEDBACK_LINES)
+    });
+    3 + feedback_lines as u16
+}
+
 fn pipeline_feedback_line(app: &TuiApp, outcome: Option<&Result<(), TuiError>>) -> String {
     if let Some(Err(err)) = outcome {
         return format!("Process error: {err}");
@@ -772,6 +1064,9 @@ fn pipeline_feedback_line(app: &TuiApp, outcome: Option<&Result<(), TuiError>>)
 
         AppView::Operations { .. } => "Operations tree planned.".to_string(),
 
+        AppView::OperationsApply {
+            simulated: true, ..
+        } => "Simulating operations epochs, applying nothing.".to_string(),
         AppView::OperationsApply { .. } => "Applying operations epochs.".to_string(),
 
         AppView::Done { .. } => {
@@ -841,43 +1136,97 @@ fn draw_stderr_page(frame: &mut ratatui::Frame<'_>, area: Rect, app: &mut TuiApp
     let inner_height = area.height.saturating_sub(2) as usize;
     app.stderr_view_height = inner_height as u16;
 
-    let total_lines = app.stderr_lines_count.max(1);
+    let lines: Vec<Line> = app
+        .stderr_lines
+        .iter()
+        .filter(|line| app.stderr_filter.allows(line))
+        .map(|line| {
+            let style = line
+                .level
+                .map_or(Style::default().fg(Color::Red), level_style);
+            Line::from(Span::styled(line.text.as_str(), style))
+        })
+        .collect();
+
+    let total_lines = lines.len().max(1);
     let max_scroll = total_lines.saturating_sub(inner_height) as u16;
 
     if app.stderr_follow || app.stderr_scroll > max_scroll {
         app.stderr_scroll = max_scroll;
     }
 
-    let title = if app.stderr_follow {
-        "stderr (following) - press e to return"
-    } else {
-        "stderr - press e to return"
-    };
+    let mut title = String::from("stderr");
+    match (app.stderr_follow, app.stderr_filter) {
+        (true, LevelFilter::All) => title.push_str(" (following)"),
+        (true, filter) => title.push_str(&format!(" (following, {filter})")),
+        (false, LevelFilter::All) => {}
+        (false, filter) => title.push_str(&format!(" ({filter})")),
+    }
+    title.push_str(" - press e to return");
 
-    let widget = if app.stderr_buffer.is_empty() {
-        Paragraph::new("<no stderr output>")
+    let widget = if lines.is_empty() {
+        let placeholder = if app.stderr_lines.is_empty() {
+            "<no stderr output>".to_string()
+        } else {
+            format!("<no {} lines>", app.stderr_filter)
+        };
+        Paragraph::new(placeholder)
             .block(Block::default().borders(Borders::ALL).title(title))
             .alignment(Alignment::Left)
             .wrap(Wrap { trim: false })
             .style(Style::default().fg(Color::DarkGray))
     } else {
-        Paragraph::new(app.stderr_buffer.as_str())
+        Paragraph::new(Text::from(lines))
             .block(Block::default().borders(Borders::ALL).title(title))
             .alignment(Alignment::Left)
             .wrap(Wrap { trim: false })
             .scroll((app.stderr_scroll, 0))
-            .style(Style::default().fg(Color::Red))
     };
 
     frame.render_widget(widget, area);
 }
 
+fn level_style(level: LogLevel) -> Style {
+    match level {
+        LogLevel::Error => Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        LogLevel::Warn => Style::default().fg(Color::Yellow),
+        LogLevel::Info => Style::default(),
+        LogLevel::Debug | LogLevel::Trace => Style::default().fg(Color::DarkGray),
+    }
+}
+
 fn draw_help(frame: &mut ratatui::Frame, area: Rect, app: &TuiApp) {
+    let key = |action| app.keys.hint(action);
     let hints = match app.page {
-        UiPage::Main => {
-            "Left/Right stages  Up/Down move  Enter toggle tree  f follow  e stderr  q quit"
-        }
-        UiPage::Stderr => "Up/Down scroll  PgUp/PgDn page  g top  G/end bottom  e back  q quit",
+        UiPage::Main => format!(
+            "{}/{} stages  {}/{} move  {} toggle tree  {} follow  {}/{} log  {} pause  {} cancel  {} stderr  {} quit",
+            key(Action::PrevStage),
+            key(Action::NextStage),
+            key(Action::Up),
+            key(Action::Down),
+            key(Action::Toggle),
+            key(Action::ToggleFollow),
+            key(Action::LogMore),
+            key(Action::LogLess),
+            key(Action::Pause),
+            key(Action::Cancel),
+            key(Action::ToggleStderr),
+            key(Action::Quit),
+        ),
+        UiPage::Stderr => format!(
+            "{}/{} scroll  {}/{} page  {} top  {} bottom  {} filter  {}/{} log  {} back  {} quit",
+            key(Action::Up),
+            key(Action::Down),
+            key(Action::PageUp),
+            key(Action::PageDown),
+            key(Action::Top),
+            key(Action::Bottom),
+            key(Action::FilterLevel),
+            key(Action::LogMore),
+            key(Action::LogLess),
+            key(Action::ToggleStderr),
+            key(Action::Quit),
+        ),
     };
 
     let lines = vec![Line::from(Span::styled(
@@ -914,7 +1263,7 @@ fn draw_apply(
 
     let l = Layout::default().direction(Direction::Vertical);
     let layout = if let Some(selected_operation) = selected_operation {
-        if selected_operation.error.is_some() {
+        if selected_operation.error().is_some() {
             l.constraints(
                 [
                     Constraint::Percentage(60),
@@ -933,37 +1282,43 @@ fn draw_apply(
             .split(area)
     };
 
-    let mut items: Vec<ListItem<'_>> = Vec::new();
-    for (epoch_index, operations) in epochs.iter().enumerate() {
-        for (operation_index, operation) in operations.iter().enumerate() {
-            let status = if operation.is_complete {
-                if operation.error.is_some() {
-                    "❌"
-                } else {
-                    "✅"
+    let height = layout[0].height.saturating_sub(2) as usize;
+    if let Some(sel) = state.selected_flat {
+        state.ensure_visible_row(sel, height);
+    }
+    let len = state.visible_len();
+    let window = visible_window(&mut state.list_offset, len, height);
+
+    // Only the operations on screen are labelled.
+    let items: Vec<ListItem<'_>> = state.flat_index_to_epoch_operation[window.clone()]
+        .iter()
+        .filter_map(|&(epoch_index, operation_index)| {
+            let operation = epochs.get(epoch_index)?.get(operation_index)?;
+            let status = match &operation.result {
+                Some(result) if result.succeeded() => {
+                    format!("✅ {:.1}s", result.duration_ms as f64 / 1000.0)
                 }
-            } else {
-                "…"
+                Some(result) => format!("❌ {:.1}s", result.duration_ms as f64 / 1000.0),
+                None => match &operation.progress {
+                    Some(progress) => format!("… {} {}%", progress.stage, progress.percent),
+                    None => "…".to_owned(),
+                },
             };
             let label = format!(
                 "[{status}] (epoch {epoch_index}, operation {operation_index}) {}",
                 operation.label
             );
-            items.push(ListItem::new(Line::from(Span::raw(label))));
-        }
-    }
+            Some(ListItem::new(Line::from(Span::raw(label))))
+        })
+        .collect();
 
     let mut list_state = ListState::default();
-    if let Some(selected) = state.selected_flat {
-        list_state.select(Some(selected));
-    }
-    *list_state.offset_mut() = state.list_offset;
-
-    let height = layout[0].height.saturating_sub(2) as usize;
-    if let Some(sel) = state.selected_flat {
-        state.ensure_visible_row(sel, height);
-        *list_state.offset_mut() = state.list_offset;
-    }
+    list_state.select(
+        state
+            .selected_flat
+            .filter(|selected| window.contains(selected))
+            .map(|selected| selected - window.start),
+    );
 
     let operations_list = List::new(items)
         .block(
@@ -980,9 +1335,17 @@ fn draw_apply(
     frame.render_stateful_widget(operations_list, layout[0], &mut list_state);
 
     if let Some(operation) = selected_operation {
-        if let Some(error) = &operation.error {
-            let operation_error_widget = Paragraph::new(error.clone())
-                .block(Block::default().borders(Borders::ALL).title("error"))
+        if let Some(error) = operation.error() {
+            let title = match operation
+                .result
+                .as_ref()
+                .and_then(|result| result.exit_code)
+            {
+                Some(exit_code) => format!("error (exit code {exit_code})"),
+                None => "error".to_owned(),
+            };
+            let operation_error_widget = Paragraph::new(error.to_owned())
+                .block(Block::default().borders(Borders::ALL).title(title))
                 .wrap(Wrap { trim: false })
                 .style(Style::default().fg(Color::White));
 
@@ -995,7 +1358,7 @@ fn draw_apply(
         let logs_layout = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
-            .split(layout[if operation.error.is_none() { 1 } else { 2 }]);
+            .split(layout[if operation.error().is_none() { 1 } else { 2 }]);
 
         let stdout_widget = Paragraph::new(stdout.clone())
             .block(Block::default().borders(Borders::ALL).title("stdout"))
@@ -1033,44 +1396,32 @@ fn draw_tree(
     tree: &FlatViewTree,
     state: &mut TreeState,
 ) {
-    let rows = build_visible_rows(tree, state);
-
     if state.selected_node.is_none() {
-        state.selected_node = rows.first().map(|r| r.index);
+        state.selected_node = state.rows(tree).first().map(|row| row.index);
     }
 
-    let selected_row = selected_row_index(&rows, state);
-
-    let items = rows
-        .iter()
-        .map(|row| {
-            let mut spans: Vec<Span> = Vec::new();
-            spans.push(Span::raw("  ".repeat(row.depth)));
-
-            if row.is_branch {
-                spans.push(Span::styled(
-                    format!("{} ", if row.is_expanded { "▼" } else { "▶" }),
-                    Style::default().fg(Color::Yellow),
-                ));
-            } else {
-                spans.push(Span::styled("• ", Style::default().fg(Color::DarkGray)));
-            }
-
-            spans.push(Span::raw(&row.label));
-
-            ListItem::new(Line::from(spans))
-        })
-        .collect::<Vec<_>>();
-
-    let mut list_state = ListState::default();
-    list_state.select(selected_row);
-    *list_state.offset_mut() = state.list_offset;
+    let selected_row = state.selected_row(tree);
 
     let inner_height = area.height.saturating_sub(2) as usize;
     if let Some(selected_row) = selected_row {
         state.ensure_visible_row(selected_row, inner_height);
-        *list_state.offset_mut() = state.list_offset;
     }
+    let rows_len = state.rows(tree).len();
+    let window = visible_window(&mut state.list_offset, rows_len, inner_height);
+
+    // Only the rows on screen are labelled, so a frame costs the same for a
+    // plan of ten nodes or ten thousand.
+    let items = state.rows(tree)[window.clone()]
+        .iter()
+        .map(|row| tree_row_item(tree, row))
+        .collect::<Vec<_>>();
+
+    let mut list_state = ListState::default();
+    list_state.select(
+        selected_row
+            .filter(|selected_row| window.contains(selected_row))
+            .map(|selected_row| selected_row - window.start),
+    );
 
     let widget = List::new(items)
         .block(Block::default().borders(Borders::ALL).title(title))
@@ -1083,13 +1434,118 @@ fn draw_tree(
     frame.render_stateful_widget(widget, area, &mut list_state);
 }
 
-#[derive(Debug, Clone)]
+/// The rows of a `len`-row list that fit in `height`, from `offset`. Pulls
+/// `offset` back if the list shrank under it, e.g. after a collapse.
+fn visible_window(offset: &mut usize, len: usize, height: usize) -> Range<usize> {
+    *offset = (*offset).min(len.saturating_sub(height));
+    *offset..(*offset + height).min(len)
+}
+
+fn tree_row_item<'a>(tree: &'a FlatViewTree, row: &TreeRow) -> ListItem<'a> {
+    let mut spans: Vec<Span> = Vec::new();
+    spans.push(Span::raw("  ".repeat(row.depth)));
+
+    if row.is_branch {
+        spans.push(Span::styled(
+            if row.is_expanded { "▼ " } else { "▶ " },
+            Style::default().fg(Color::Yellow),
+        ));
+    } else {
+        spans.push(Span::styled("• ", Style::default().fg(Color::DarkGray)));
+    }
+
+    match tree.get(row.index) {
+        Ok(FlatViewTreeNode::Branch { view, .. }) => view_spans(view, &mut spans),
+        Ok(FlatViewTreeNode::Leaf { view }) => match view {
+            ViewNode::NotStarted => spans.push(Span::raw("not started")),
+            ViewNode::Started => spans.push(Span::raw("in progress")),
+            ViewNode::Complete(v) => view_spans(v, &mut spans),
+        },
+        Err(_) => {}
+    }
+
+    ListItem::new(Line::from(spans))
+}
+
+/// Append `view` as styled spans, keeping the colours and weights of its
+/// spans (e.g. a change's severity). A paragraph is left as plain text, as a
+/// row is one line.
+fn view_spans(view: &View, out: &mut Vec<Span<'_>>) {
+    match view {
+        View::Span(span) => out.push(Span::styled(
+            span.content.to_string(),
+            view_style(&span.style),
+        )),
+        View::Fragment(fragment) => {
+            for child in &fragment.children {
+                view_spans(child, out);
+            }
+        }
+        View::Line(line) => {
+            let style = view_style(&line.style);
+            out.extend(line.spans.iter().map(|span| {
+                Span::styled(
+                    span.content.to_string(),
+                    style.patch(view_style(&span.style)),
+                )
+            }));
+        }
+        View::Paragraph(_) => out.push(Span::raw(view.to_string())),
+    }
+}
+
+fn view_style(style: &TextStyle) -> Style {
+    let mut out = Style::default();
+    if let Some(color) = &style.foreground_color {
+        out = out.fg(view_color(color));
+    }
+    if let Some(color) = &style.background_color {
+        out = out.bg(view_color(color));
+    }
+    if style.is_bold {
+        out = out.add_modifier(Modifier::BOLD);
+    }
+    if style.is_italic {
+        out = out.add_modifier(Modifier::ITALIC);
+    }
+    if style.is_underlined {
+        out = out.add_modifier(Modifier::UNDERLINED);
+    }
+    if style.is_crossed_out {
+        out = out.add_modifier(Modifier::CROSSED_OUT);
+    }
+    out
+}
+
+fn view_color(color: &ViewColor) -> Color {
+    match color {
+        ViewColor::Black => Color::Black,
+        ViewColor::Red => Color::Red,
+        ViewColor::Green => Color::Green,
+        ViewColor::Yellow => Color::Yellow,
+        ViewColor::Blue => Color::Blue,
+        ViewColor::Magenta => Color::Magenta,
+        ViewColor::Cyan => Color::Cyan,
+        ViewColor::Gray => Color::Gray,
+        ViewColor::DarkGray => Color::DarkGray,
+        ViewColor::LightRed => Color::LightRed,
+        ViewColor::LightGreen => Color::LightGreen,
+        ViewColor::LightYellow => Color::LightYellow,
+        ViewColor::LightBlue => Color::LightBlue,
+        ViewColor::LightMagenta => Color::LightMagenta,
+        ViewColor::LightCyan => Color::LightCyan,
+        ViewColor::White => Color::White,
+    }
+}
+
+/// A visible row of a stage's tree. Labels are left to [`tree_row_item`], so
+/// rows are cheap to build for the whole tree.
+#[derive(Debug, Clone, Copy)]
 struct TreeRow {
     index: usize,
     depth: usize,
     is_branch: bool,
     is_expanded: bool,
-    label: String,
 }
 
 fn build_visible_rows(tree: &FlatViewTree, state: &TreeState) -> Vec<TreeRow> {
@@ -1126,23 +1582,16 @@ fn build_visible_rows_rec(
     };
 
     match node {
-        FlatViewTreeNode::Leaf { view } => {
-            let label = match view {
-                ViewNode::NotStarted => "not started".to_string(),
-                ViewNode::Started => "in progress".to_string(),
-                ViewNode::Complete(v) => v.to_string(),
-            };
-
+        FlatViewTreeNode::Leaf { .. } => {
             out.push(TreeRow {
                 index,
                 depth,
                 is_branch: false,
                 is_expanded: false,
-                label,
             });
         }
 
-        FlatViewTreeNode::Branch { view, children } => {
+        FlatViewTreeNode::Branch { children, .. } => {
             let is_expanded = state.is_expanded(index);
 
             out.push(TreeRow {
@@ -1150,7 +1599,6 @@ fn build_visible_rows_rec(
                 depth,
                 is_branch: true,
                 is_expanded,
-                label: view.to_string(),
             });
 
             if is_expanded {
@@ -1162,13 +1610,9 @@ fn build_visible_rows_rec(
     }
 }
 
-fn selected_row_index(rows: &[TreeRow], state: &TreeState) -> Option<usize> {
-    let selected_node = state.selected_node?;
-    rows.iter().position(|r| r.index == selected_node)
-}
-
 fn tree_move_selection(tree: &FlatViewTree, state: &mut TreeState, delta: i32) {
-    let rows = build_visible_rows(tree, state);
+    let current_row = state.selected_row(tree).unwrap_or(0);
+    let rows = state.rows(tree);
 
     if rows.is_empty() {
         state.selected_node = None;
@@ -1176,13 +1620,12 @@ fn tree_move_selection(tree: &FlatViewTree, state: &mut TreeState, delta: i32) {
         return;
     }
 
-    let current_row = selected_row_index(&rows, state).unwrap_or(0);
-
     let next_row = if delta >= 0 {
         (current_row + delta as usize).min(rows.len() - 1)
     } else {
         current_row.saturating_sub((-delta) as usize)
     };
 
-    state.selected_node = Some(rows[next_row].index);
+    let next_node = rows[next_row].index;
+    state.selected_node = Some(next_node);
 }
@@ -2,7 +2,7 @@
 //! which describes the machine lusid is currently running on.
 //!
 //! A `Machine` names the intended hostname/arch/OS (and, if it should be materialized as
-//! a VM, [`MachineVmOptions`] covering cpu/memory/graphics). Wired into the `vm` crate as
+//! a VM, [`MachineVmOptions`] covering cpu/memory/graphics/devices). Wired into the `vm` crate as
 //! the input to `Instance::start`.
 //
 // Note(cc): this crate is deliberately small. As the product picks up remote deployment,
@@ -10,6 +10,7 @@
 
 use lusid_system::{Arch, CpuCount, DiskSize, Hostname, MemorySize, Os};
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
 /// Declarative spec of a machine we want to provision.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
@@ -32,4 +33,76 @@ pub struct MachineVmOptions {
     /// filesystem to fill the disk on first boot.
     pub disk_size: Option<DiskSize>,
     pub graphics: Option<bool>,
+    /// Extra disks, attached as virtio drives after the root disk. Relative
+    /// paths are resolved against the config file's directory.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disks: Vec<MachineVmDisk>,
+    /// Host USB devices to pass through to the guest. The user running lusid
+    /// needs access to the device under `/dev/bus/usb`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub usb: Vec<MachineVmUsbDevice>,
+    /// Host bridge (e.g. `br0`) to attach a second NIC to, so the guest is
+    /// reachable on the host's network. Goes through `qemu-bridge-helper`,
+    /// which must allow the bridge in `/etc/qemu/bridge.conf`. The user-mode
+    /// NIC carrying SSH stays either way.
+    pub bridge: Option<String>,
+    /// Raw QEMU arguments for anything the options above don't cover,
+    /// appended after everything lusid sets. Arguments lusid manages itself
+    /// (e.g. `-kernel`, `-daemonize`, `-m`) are rejected at start.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub qemu_args: Vec<String>,
+}
+
+/// An extra disk image for a VM.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct MachineVmDisk {
+    pub path: PathBuf,
+    /// Image format; inferred from the extension when omitted (`.qcow2` is
+    /// qcow2, anything else raw).
+    pub format: Option<MachineVmDiskFormat>,
+    #[serde(default)]
+    pub readonly: bool,
+}
+
+impl MachineVmDisk {
+    pub fn format(&self) -> MachineVmDiskFormat {
+        self.format.unwrap_or_else(
+            || match self.path.extension().and_then(|ext| ext.to_str()) {
+                Some("qcow2") => MachineVmDiskFormat::Qcow2,
+                _ => MachineVmDiskFormat::Raw,
+            },
+        )
+    }
+
+    /// This disk with a relative `path` resolved against `base_dir`.
+    pub fn resolve(mut self, base_dir: &Path) -> Self {
+        if self.path.is_relative() {
+            self.path = base_dir.join(&self.path);
+        }
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MachineVmDiskFormat {
+    Raw,
+    Qcow2,
+}
+
+impl MachineVmDiskFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MachineVmDiskFormat::Raw => "raw",
+            MachineVmDiskFormat::Qcow2 => "qcow2",
+        }
+    }
+}
+
+/// A host USB device, by vendor and product id as shown by `lsusb`
+/// (`vendor_id = 0x046d`, `product_id = 0xc52b` in TOML).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct MachineVmUsbDevice {
+    pub vendor_id: u16,
+    pub product_id: u16,
 }
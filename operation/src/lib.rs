@@ -9,8 +9,14 @@
 //!
 //! - **`merge`** — coalesce same-type operations in one epoch (e.g. combine
 //!   multiple `apt install` calls into one).
+//! - **`batch`** — after merging, optionally fold many operations into one
+//!   composite (e.g. many small file writes into one), to cut per-operation
+//!   overhead.
 //! - **`apply`** — run the operation against the machine and return a future plus
 //!   streaming stdout/stderr that the TUI can tail.
+//! - **`script`** — the equivalent shell command line, where there is one.
+//! - **`severity`** — whether the operation is [`Severity::Safe`], disrupts
+//!   something running, or destroys something (e.g. removing a directory).
 //!
 //! The crate-level [`Operation`] / [`OperationApplyError`] / [`OperationApplyOutput`]
 //! / [`OperationApplyStdout`] / [`OperationApplyStderr`] enums are thin dispatchers.
@@ -20,12 +26,12 @@
 use async_trait::async_trait;
 use core::task;
 use lusid_ctx::Context;
-use lusid_view::Render;
+use lusid_view::{Color, Render, Span, TextStyle, View};
 use pin_project::pin_project;
 use std::{
     fmt::{Debug, Display},
     future::Future,
-    pin::{Pin, pin},
+    pin::Pin,
     task::Poll,
 };
 use thiserror::Error;
@@ -33,18 +39,37 @@ use tokio::io::AsyncRead;
 
 pub mod operations;
 
+#[cfg(test)]
+mod render_snapshots;
+
 use crate::operations::{
+    apk::{Apk, ApkOperation},
     apt::{Apt, AptOperation},
     apt_repo::{AptRepo, AptRepoOperation},
+    aur::{Aur, AurOperation},
+    brew::{Brew, BrewOperation},
     command::{Command, CommandOperation},
+    cron::{Cron, CronOperation},
     directory::{Directory, DirectoryOperation},
+    dnf::{Dnf, DnfOperation},
     file::{File, FileOperation},
+    firewall::{Firewall, FirewallOperation},
     git::{Git, GitOperation},
+    gpg::{Gpg, GpgOperation},
     group::{Group, GroupOperation},
+    launchd::{Launchd, LaunchdOperation},
+    networkd::{Networkd, NetworkdOperation},
+    nix::{Nix, NixOperation},
     pacman::{Pacman, PacmanOperation},
+    pip::{Pip, PipOperation},
     podman::{Podman, PodmanOperation},
+    rustup::{Rustup, RustupOperation},
+    service::{Service, ServiceOperation},
     systemd::{Systemd, SystemdOperation},
+    time::{Time, TimeOperation},
+    tls_cert::{TlsCert, TlsCertOperation},
     user::{User, UserOperation},
+    wireguard::{Wireguard, WireguardOperation},
 };
 
 /// One family of operations (apt, pacman, file, …). Implementors are zero-sized
@@ -60,6 +85,47 @@ pub trait OperationType {
     /// (file, command, git) the order matters, so `merge` is a no-op.
     fn merge(operations: Vec<Self::Operation>) -> Vec<Self::Operation>;
 
+    /// Fold merged operations of one epoch into fewer composite operations.
+    ///
+    /// Where `merge` combines operations that mean the same thing (two
+    /// installs into one install), `batch` combines operations that are cheap
+    /// on their own but add up: every operation applied is its own round of
+    /// progress updates streamed back from the target, so a dotfiles plan of
+    /// a hundred small files pays that a hundred times. Operations in one
+    /// epoch are independent, so a composite may apply them in any order.
+    /// Defaults to no batching.
+    fn batch(operations: Vec<Self::Operation>) -> Vec<Self::Operation> {
+        operations
+    }
+
+    /// The operation as a single shell command line, for exporting a plan as a
+    /// script (`lusid plan export-script`), or `None` when it isn't one
+    /// command (e.g. file writes, which lusid does in-process).
+    fn script(operation: &Self::Operation) -> Option<String>;
+
+    /// A dry run of the operation, for `lusid-apply --check`: a command that
+    /// reports what applying it would do without doing it, like
+    /// `apt-get install --simulate`. Defaults to `None`, for tools without
+    /// one; a check describes those by their [`script`](Self::script).
+    fn simulate(_operation: &Self::Operation) -> Option<lusid_cmd::Command> {
+        None
+    }
+
+    /// How much applying the operation disturbs the machine. Defaults to
+    /// [`Severity::Safe`]; families that stop or delete things override it.
+    fn severity(_operation: &Self::Operation) -> Severity {
+        Severity::Safe
+    }
+
+    /// How to parse lines the running operation writes to stderr as
+    /// progress reports, for tools that draw a progress meter there, like
+    /// `git --progress`. Defaults to `None`, for operations that report none.
+    /// Only an operation with a parser has its stderr split on the lone `\r`
+    /// a meter redraws its line with.
+    fn progress(_operation: &Self::Operation) -> Option<ProgressParser> {
+        None
+    }
+
     /// Failure returned when `apply`'s future resolves.
     type ApplyError;
 
@@ -81,6 +147,61 @@ pub trait OperationType {
     ) -> Result<(Self::ApplyOutput, Self::ApplyStdout, Self::ApplyStderr), Self::ApplyError>;
 }
 
+/// How much applying an operation disturbs the machine, worst last, so the
+/// severity of several operations is their `max`. `lusid-apply` refuses to
+/// apply destructive operations unless told to (`--allow-destructive`), and
+/// the TUI colours changes by their worst operation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Creates or updates something, leaving what's running alone.
+    #[default]
+    Safe,
+    /// Interrupts something running, like stopping a service, but loses
+    /// nothing that re-applying wouldn't bring back.
+    Disruptive,
+    /// Deletes something re-applying can't bring back, like a directory or a
+    /// user account.
+    Destructive,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Safe => write!(f, "safe"),
+            Severity::Disruptive => write!(f, "disruptive"),
+            Severity::Destructive => write!(f, "destructive"),
+        }
+    }
+}
+
+impl Render for Severity {
+    fn render(&self) -> View {
+        let color = match self {
+            Severity::Safe => Color::Green,
+            Severity::Disruptive => Color::Yellow,
+            Severity::Destructive => Color::Red,
+        };
+        View::Span(Span::new_styled(
+            self.to_string(),
+            TextStyle::new().fg(color).bold(),
+        ))
+    }
+}
+
+/// How far along a running operation is, parsed from its stderr by
+/// [`OperationType::progress`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Progress {
+    /// What it's doing, e.g. git's `Receiving objects`.
+    pub stage: String,
+    /// From 0 to 100.
+    pub percent: u8,
+}
+
+/// Parses a line of an operation's stderr as a [`Progress`] report, if it is
+/// one (see [`OperationType::progress`]).
+pub type ProgressParser = fn(&str) -> Option<Progress>;
+
 /// Dispatcher over every operation family. Every leaf of the per-epoch causality
 /// tree is an `Operation`.
 #[derive(Debug, Clone)]
@@ -88,19 +209,36 @@ pub enum Operation {
     Apt(AptOperation),
     AptRepo(AptRepoOperation),
     Pacman(PacmanOperation),
+    Aur(AurOperation),
+    Dnf(DnfOperation),
+    Apk(ApkOperation),
+    Nix(NixOperation),
     Podman(PodmanOperation),
     File(FileOperation),
     Directory(DirectoryOperation),
     Command(CommandOperation),
     Git(GitOperation),
     Systemd(SystemdOperation),
+    Service(ServiceOperation),
     User(UserOperation),
     Group(GroupOperation),
+    Cron(CronOperation),
+    Pip(PipOperation),
+    Rustup(RustupOperation),
+    Brew(BrewOperation),
+    Launchd(LaunchdOperation),
+    Firewall(FirewallOperation),
+    Time(TimeOperation),
+    Networkd(NetworkdOperation),
+    Wireguard(WireguardOperation),
+    TlsCert(TlsCertOperation),
+    Gpg(GpgOperation),
 }
 
 impl Operation {
     /// Partition `operations` by family, merge each family via its [`OperationType::merge`]
-    /// impl, and re-wrap in family order (apt, pacman, file, command, git).
+    /// impl then batch it via [`OperationType::batch`], and re-wrap in family order
+    /// (apt, pacman, file, command, git).
     ///
     /// Called once per epoch before `apply` — the whole point is to collapse e.g. 20
     /// separate `apt install` operations into one multi-package install.
@@ -109,32 +247,136 @@ impl Operation {
             apt,
             apt_repo,
             pacman,
+            aur,
+            dnf,
+            apk,
+            nix,
             podman,
             file,
             directory,
             command,
             git,
             systemd,
+            service,
             user,
             group,
+            cron,
+            pip,
+            rustup,
+            brew,
+            launchd,
+            firewall,
+            time,
+            networkd,
+            wireguard,
+            tls_cert,
+            gpg,
         } = partition_by_type(operations);
 
         std::iter::empty()
-            .chain(Apt::merge(apt).into_iter().map(Operation::Apt))
-            .chain(AptRepo::merge(apt_repo).into_iter().map(Operation::AptRepo))
-            .chain(Pacman::merge(pacman).into_iter().map(Operation::Pacman))
-            .chain(Podman::merge(podman).into_iter().map(Operation::Podman))
-            .chain(File::merge(file).into_iter().map(Operation::File))
+            .chain(Apt::batch(Apt::merge(apt)).into_iter().map(Operation::Apt))
+            .chain(
+                AptRepo::batch(AptRepo::merge(apt_repo))
+                    .into_iter()
+                    .map(Operation::AptRepo),
+            )
+            .chain(
+                Pacman::batch(Pacman::merge(pacman))
+                    .into_iter()
+                    .map(Operation::Pacman),
+            )
+            .chain(Aur::batch(Aur::merge(aur)).into_iter().map(Operation::Aur))
+            .chain(Dnf::batch(Dnf::merge(dnf)).into_iter().map(Operation::Dnf))
+            .chain(Apk::batch(Apk::merge(apk)).into_iter().map(Operation::Apk))
+            .chain(Nix::batch(Nix::merge(nix)).into_iter().map(Operation::Nix))
             .chain(
-                Directory::merge(directory)
+                Brew::batch(Brew::merge(brew))
+                    .into_iter()
+                    .map(Operation::Brew),
+            )
+            .chain(
+                Podman::batch(Podman::merge(podman))
+                    .into_iter()
+                    .map(Operation::Podman),
+            )
+            .chain(
+                File::batch(File::merge(file))
+                    .into_iter()
+                    .map(Operation::File),
+            )
+            .chain(
+                Directory::batch(Directory::merge(directory))
                     .into_iter()
                     .map(Operation::Directory),
             )
-            .chain(Command::merge(command).into_iter().map(Operation::Command))
-            .chain(Git::merge(git).into_iter().map(Operation::Git))
-            .chain(Systemd::merge(systemd).into_iter().map(Operation::Systemd))
-            .chain(User::merge(user).into_iter().map(Operation::User))
-            .chain(Group::merge(group).into_iter().map(Operation::Group))
+            .chain(
+                Command::batch(Command::merge(command))
+                    .into_iter()
+                    .map(Operation::Command),
+            )
+            .chain(Git::batch(Git::merge(git)).into_iter().map(Operation::Git))
+            .chain(
+                Systemd::batch(Systemd::merge(systemd))
+                    .into_iter()
+                    .map(Operation::Systemd),
+            )
+            .chain(
+                Service::batch(Service::merge(service))
+                    .into_iter()
+                    .map(Operation::Service),
+            )
+            .chain(
+                Launchd::batch(Launchd::merge(launchd))
+                    .into_iter()
+                    .map(Operation::Launchd),
+            )
+            .chain(
+                Firewall::batch(Firewall::merge(firewall))
+                    .into_iter()
+                    .map(Operation::Firewall),
+            )
+            .chain(
+                Time::batch(Time::merge(time))
+                    .into_iter()
+                    .map(Operation::Time),
+            )
+            .chain(
+                Networkd::batch(Networkd::merge(networkd))
+                    .into_iter()
+                    .map(Operation::Networkd),
+            )
+            .chain(
+                Wireguard::batch(Wireguard::merge(wireguard))
+                    .into_iter()
+                    .map(Operation::Wireguard),
+            )
+            .chain(
+                TlsCert::batch(TlsCert::merge(tls_cert))
+                    .into_iter()
+                    .map(Operation::TlsCert),
+            )
+            .chain(Gpg::batch(Gpg::merge(gpg)).into_iter().map(Operation::Gpg))
+            .chain(
+                User::batch(User::merge(user))
+                    .into_iter()
+                    .map(Operation::User),
+            )
+            .chain(
+                Group::batch(Group::merge(group))
+                    .into_iter()
+                    .map(Operation::Group),
+            )
+            .chain(
+                Cron::batch(Cron::merge(cron))
+                    .into_iter()
+                    .map(Operation::Cron),
+            )
+            .chain(Pip::batch(Pip::merge(pip)).into_iter().map(Operation::Pip))
+            .chain(
+                Rustup::batch(Rustup::merge(rustup))
+                    .into_iter()
+                    .map(Operation::Rustup),
+            )
             .collect()
     }
 }
@@ -143,37 +385,126 @@ impl Operation {
 #[derive(Error, Debug)]
 pub enum OperationApplyError {
     #[error("apt operation failed: {0:?}")]
-    Apt(<Apt as OperationType>::ApplyError),
+    Apt(#[source] <Apt as OperationType>::ApplyError),
 
     #[error("apt-repo operation failed: {0:?}")]
-    AptRepo(<AptRepo as OperationType>::ApplyError),
+    AptRepo(#[source] <AptRepo as OperationType>::ApplyError),
 
     #[error("pacman operation failed: {0:?}")]
-    Pacman(<Pacman as OperationType>::ApplyError),
+    Pacman(#[source] <Pacman as OperationType>::ApplyError),
+
+    #[error("aur operation failed: {0:?}")]
+    Aur(#[source] <Aur as OperationType>::ApplyError),
+
+    #[error("dnf operation failed: {0:?}")]
+    Dnf(#[source] <Dnf as OperationType>::ApplyError),
+
+    #[error("apk operation failed: {0:?}")]
+    Apk(#[source] <Apk as OperationType>::ApplyError),
+
+    #[error("nix operation failed: {0:?}")]
+    Nix(#[source] <Nix as OperationType>::ApplyError),
 
     #[error("podman operation failed: {0:?}")]
-    Podman(<Podman as OperationType>::ApplyError),
+    Podman(#[source] <Podman as OperationType>::ApplyError),
 
     #[error("file operation failed: {0:?}")]
-    File(<File as OperationType>::ApplyError),
+    File(#[source] <File as OperationType>::ApplyError),
 
     #[error("directory operation failed: {0:?}")]
-    Directory(<Directory as OperationType>::ApplyError),
+    Directory(#[source] <Directory as OperationType>::ApplyError),
 
     #[error("command operation failed: {0:?}")]
-    Command(<Command as OperationType>::ApplyError),
+    Command(#[source] <Command as OperationType>::ApplyError),
 
     #[error("git operation failed: {0:?}")]
-    Git(<Git as OperationType>::ApplyError),
+    Git(#[source] <Git as OperationType>::ApplyError),
 
     #[error("systemd operation failed: {0:?}")]
-    Systemd(<Systemd as OperationType>::ApplyError),
+    Systemd(#[source] <Systemd as OperationType>::ApplyError),
+
+    #[error("service operation failed: {0:?}")]
+    Service(#[source] <Service as OperationType>::ApplyError),
 
     #[error("user operation failed: {0:?}")]
-    User(<User as OperationType>::ApplyError),
+    User(#[source] <User as OperationType>::ApplyError),
 
     #[error("group operation failed: {0:?}")]
-    Group(<Group as OperationType>::ApplyError),
+    Group(#[source] <Group as OperationType>::ApplyError),
+
+    #[error("cron operation failed: {0:?}")]
+    Cron(#[source] <Cron as OperationType>::ApplyError),
+
+    #[error("pip operation failed: {0:?}")]
+    Pip(#[source] <Pip as OperationType>::ApplyError),
+
+    #[error("rustup operation failed: {0:?}")]
+    Rustup(#[source] <Rustup as OperationType>::ApplyError),
+
+    #[error("brew operation failed: {0:?}")]
+    Brew(#[source] <Brew as OperationType>::ApplyError),
+
+    #[error("launchd operation failed: {0:?}")]
+    Launchd(#[source] <Launchd as OperationType>::ApplyError),
+
+    #[error("firewall operation failed: {0:?}")]
+    Firewall(#[source] <Firewall as OperationType>::ApplyError),
+
+    #[error("time operation failed: {0:?}")]
+    Time(#[source] <Time as OperationType>::ApplyError),
+
+    #[error("networkd operation failed: {0:?}")]
+    Networkd(#[source] <Networkd as OperationType>::ApplyError),
+
+    #[error("wireguard operation failed: {0:?}")]
+    Wireguard(#[source] <Wireguard as OperationType>::ApplyError),
+
+    #[error("tls-cert operation failed: {0:?}")]
+    TlsCert(#[source] <TlsCert as OperationType>::ApplyError),
+
+    #[error("gpg operation failed: {0:?}")]
+    Gpg(#[source] <Gpg as OperationType>::ApplyError),
+}
+
+impl OperationApplyError {
+    /// Stable, machine-readable code for this failure: `operation.<family>`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            OperationApplyError::Apt(_) => "operation.apt",
+            OperationApplyError::AptRepo(_) => "operation.apt-repo",
+            OperationApplyError::Pacman(_) => "operation.pacman",
+            OperationApplyError::Aur(_) => "operation.aur",
+            OperationApplyError::Dnf(_) => "operation.dnf",
+            OperationApplyError::Apk(_) => "operation.apk",
+            OperationApplyError::Nix(_) => "operation.nix",
+            OperationApplyError::Podman(_) => "operation.podman",
+            OperationApplyError::File(_) => "operation.file",
+            OperationApplyError::Directory(_) => "operation.directory",
+            OperationApplyError::Command(_) => "operation.command",
+            OperationApplyError::Git(_) => "operation.git",
+            OperationApplyError::Systemd(_) => "operation.systemd",
+            OperationApplyError::Service(_) => "operation.service",
+            OperationApplyError::User(_) => "operation.user",
+            OperationApplyError::Group(_) => "operation.group",
+            OperationApplyError::Cron(_) => "operation.cron",
+            OperationApplyError::Pip(_) => "operation.pip",
+            OperationApplyError::Rustup(_) => "operation.rustup",
+            OperationApplyError::Brew(_) => "operation.brew",
+            OperationApplyError::Launchd(_) => "operation.launchd",
+            OperationApplyError::Firewall(_) => "operation.firewall",
+            OperationApplyError::Time(_) => "operation.time",
+            OperationApplyError::Networkd(_) => "operation.networkd",
+            OperationApplyError::Wireguard(_) => "operation.wireguard",
+            OperationApplyError::TlsCert(_) => "operation.tls-cert",
+            OperationApplyError::Gpg(_) => "operation.gpg",
+        }
+    }
+
+    /// Exit code of the command that failed, for operations that run one and
+    /// saw it exit unsuccessfully.
+    pub fn exit_code(&self) -> Option<i32> {
+        lusid_cmd::exit_code(self)
+    }
 }
 
 /// Unified completion future for any operation. `Future::poll` forwards to the active
@@ -183,14 +514,30 @@ pub enum OperationApplyOutput {
     Apt(#[pin] <Apt as OperationType>::ApplyOutput),
     AptRepo(#[pin] <AptRepo as OperationType>::ApplyOutput),
     Pacman(#[pin] <Pacman as OperationType>::ApplyOutput),
+    Aur(#[pin] <Aur as OperationType>::ApplyOutput),
+    Dnf(#[pin] <Dnf as OperationType>::ApplyOutput),
+    Apk(#[pin] <Apk as OperationType>::ApplyOutput),
+    Nix(#[pin] <Nix as OperationType>::ApplyOutput),
     Podman(#[pin] <Podman as OperationType>::ApplyOutput),
     File(#[pin] <File as OperationType>::ApplyOutput),
     Directory(#[pin] <Directory as OperationType>::ApplyOutput),
     Command(#[pin] <Command as OperationType>::ApplyOutput),
     Git(#[pin] <Git as OperationType>::ApplyOutput),
     Systemd(#[pin] <Systemd as OperationType>::ApplyOutput),
+    Service(#[pin] <Service as OperationType>::ApplyOutput),
     User(#[pin] <User as OperationType>::ApplyOutput),
     Group(#[pin] <Group as OperationType>::ApplyOutput),
+    Cron(#[pin] <Cron as OperationType>::ApplyOutput),
+    Pip(#[pin] <Pip as OperationType>::ApplyOutput),
+    Rustup(#[pin] <Rustup as OperationType>::ApplyOutput),
+    Brew(#[pin] <Brew as OperationType>::ApplyOutput),
+    Launchd(#[pin] <Launchd as OperationType>::ApplyOutput),
+    Firewall(#[pin] <Firewall as OperationType>::ApplyOutput),
+    Time(#[pin] <Time as OperationType>::ApplyOutput),
+    Networkd(#[pin] <Networkd as OperationType>::ApplyOutput),
+    Wireguard(#[pin] <Wireguard as OperationType>::ApplyOutput),
+    TlsCert(#[pin] <TlsCert as OperationType>::ApplyOutput),
+    Gpg(#[pin] <Gpg as OperationType>::ApplyOutput),
 }
 
 impl Future for OperationApplyOutput {
@@ -202,14 +549,30 @@ impl Future for OperationApplyOutput {
             Apt(fut) => fut.poll(cx).map_err(OperationApplyError::Apt),
             AptRepo(fut) => fut.poll(cx).map_err(OperationApplyError::AptRepo),
             Pacman(fut) => fut.poll(cx).map_err(OperationApplyError::Pacman),
+            Aur(fut) => fut.poll(cx).map_err(OperationApplyError::Aur),
+            Dnf(fut) => fut.poll(cx).map_err(OperationApplyError::Dnf),
+            Apk(fut) => fut.poll(cx).map_err(OperationApplyError::Apk),
+            Nix(fut) => fut.poll(cx).map_err(OperationApplyError::Nix),
             Podman(fut) => fut.poll(cx).map_err(OperationApplyError::Podman),
             File(fut) => fut.poll(cx).map_err(OperationApplyError::File),
             Directory(fut) => fut.poll(cx).map_err(OperationApplyError::Directory),
             Command(fut) => fut.poll(cx).map_err(OperationApplyError::Command),
             Git(fut) => fut.poll(cx).map_err(OperationApplyError::Git),
             Systemd(fut) => fut.poll(cx).map_err(OperationApplyError::Systemd),
+            Service(fut) => fut.poll(cx).map_err(OperationApplyError::Service),
             User(fut) => fut.poll(cx).map_err(OperationApplyError::User),
             Group(fut) => fut.poll(cx).map_err(OperationApplyError::Group),
+            Cron(fut) => fut.poll(cx).map_err(OperationApplyError::Cron),
+            Pip(fut) => fut.poll(cx).map_err(OperationApplyError::Pip),
+            Rustup(fut) => fut.poll(cx).map_err(OperationApplyError::Rustup),
+            Brew(fut) => fut.poll(cx).map_err(OperationApplyError::Brew),
+            Launchd(fut) => fut.poll(cx).map_err(OperationApplyError::Launchd),
+            Firewall(fut) => fut.poll(cx).map_err(OperationApplyError::Firewall),
+            Time(fut) => fut.poll(cx).map_err(OperationApplyError::Time),
+            Networkd(fut) => fut.poll(cx).map_err(OperationApplyError::Networkd),
+            Wireguard(fut) => fut.poll(cx).map_err(OperationApplyError::Wireguard),
+            TlsCert(fut) => fut.poll(cx).map_err(OperationApplyError::TlsCert),
+            Gpg(fut) => fut.poll(cx).map_err(OperationApplyError::Gpg),
         }
     }
 }
@@ -221,14 +584,30 @@ pub enum OperationApplyStdout {
     Apt(#[pin] <Apt as OperationType>::ApplyStdout),
     AptRepo(#[pin] <AptRepo as OperationType>::ApplyStdout),
     Pacman(#[pin] <Pacman as OperationType>::ApplyStdout),
+    Aur(#[pin] <Aur as OperationType>::ApplyStdout),
+    Dnf(#[pin] <Dnf as OperationType>::ApplyStdout),
+    Apk(#[pin] <Apk as OperationType>::ApplyStdout),
+    Nix(#[pin] <Nix as OperationType>::ApplyStdout),
     Podman(#[pin] <Podman as OperationType>::ApplyStdout),
     File(#[pin] <File as OperationType>::ApplyStdout),
     Directory(#[pin] <Directory as OperationType>::ApplyStdout),
     Command(#[pin] <Command as OperationType>::ApplyStdout),
     Git(#[pin] <Git as OperationType>::ApplyStdout),
     Systemd(#[pin] <Systemd as OperationType>::ApplyStdout),
+    Service(#[pin] <Service as OperationType>::ApplyStdout),
     User(#[pin] <User as OperationType>::ApplyStdout),
     Group(#[pin] <Group as OperationType>::ApplyStdout),
+    Cron(#[pin] <Cron as OperationType>::ApplyStdout),
+    Pip(#[pin] <Pip as OperationType>::ApplyStdout),
+    Rustup(#[pin] <Rustup as OperationType>::ApplyStdout),
+    Brew(#[pin] <Brew as OperationType>::ApplyStdout),
+    Launchd(#[pin] <Launchd as OperationType>::ApplyStdout),
+    Firewall(#[pin] <Firewall as OperationType>::ApplyStdout),
+    Time(#[pin] <Time as OperationType>::ApplyStdout),
+    Networkd(#[pin] <Networkd as OperationType>::ApplyStdout),
+    Wireguard(#[pin] <Wireguard as OperationType>::ApplyStdout),
+    TlsCert(#[pin] <TlsCert as OperationType>::ApplyStdout),
+    Gpg(#[pin] <Gpg as OperationType>::ApplyStdout),
 }
 
 impl AsyncRead for OperationApplyStdout {
@@ -242,14 +621,30 @@ impl AsyncRead for OperationApplyStdout {
             Apt(stream) => stream.poll_read(cx, buf),
             AptRepo(stream) => stream.poll_read(cx, buf),
             Pacman(stream) => stream.poll_read(cx, buf),
+            Aur(stream) => stream.poll_read(cx, buf),
+            Dnf(stream) => stream.poll_read(cx, buf),
+            Apk(stream) => stream.poll_read(cx, buf),
+            Nix(stream) => stream.poll_read(cx, buf),
             Podman(stream) => stream.poll_read(cx, buf),
             File(stream) => stream.poll_read(cx, buf),
             Directory(stream) => stream.poll_read(cx, buf),
             Command(stream) => stream.poll_read(cx, buf),
             Git(stream) => stream.poll_read(cx, buf),
             Systemd(stream) => stream.poll_read(cx, buf),
+            Service(stream) => stream.poll_read(cx, buf),
             User(stream) => stream.poll_read(cx, buf),
             Group(stream) => stream.poll_read(cx, buf),
+            Cron(stream) => stream.poll_read(cx, buf),
+            Pip(stream) => stream.poll_read(cx, buf),
+            Rustup(stream) => stream.poll_read(cx, buf),
+            Brew(stream) => stream.poll_read(cx, buf),
+            Launchd(stream) => stream.poll_read(cx, buf),
+            Firewall(stream) => stream.poll_read(cx, buf),
+            Time(stream) => stream.poll_read(cx, buf),
+            Networkd(stream) => stream.poll_read(cx, buf),
+            Wireguard(stream) => stream.poll_read(cx, buf),
+            TlsCert(stream) => stream.poll_read(cx, buf),
+            Gpg(stream) => stream.poll_read(cx, buf),
         }
     }
 }
@@ -261,14 +656,30 @@ pub enum OperationApplyStderr {
     Apt(#[pin] <Apt as OperationType>::ApplyStderr),
     AptRepo(#[pin] <AptRepo as OperationType>::ApplyStderr),
     Pacman(#[pin] <Pacman as OperationType>::ApplyStderr),
+    Aur(#[pin] <Aur as OperationType>::ApplyStderr),
+    Dnf(#[pin] <Dnf as OperationType>::ApplyStderr),
+    Apk(#[pin] <Apk as OperationType>::ApplyStderr),
+    Nix(#[pin] <Nix as OperationType>::ApplyStderr),
     Podman(#[pin] <Podman as OperationType>::ApplyStderr),
     File(#[pin] <File as OperationType>::ApplyStderr),
     Directory(#[pin] <Directory as OperationType>::ApplyStderr),
     Command(#[pin] <Command as OperationType>::ApplyStderr),
     Git(#[pin] <Git as OperationType>::ApplyStderr),
     Systemd(#[pin] <Systemd as OperationType>::ApplyStderr),
+    Service(#[pin] <Service as OperationType>::ApplyStderr),
     User(#[pin] <User as OperationType>::ApplyStderr),
     Group(#[pin] <Group as OperationType>::ApplyStderr),
+    Cron(#[pin] <Cron as OperationType>::ApplyStderr),
+    Pip(#[pin] <Pip as OperationType>::ApplyStderr),
+    Rustup(#[pin] <Rustup as OperationType>::ApplyStderr),
+    Brew(#[pin] <Brew as OperationType>::ApplyStderr),
+    Launchd(#[pin] <Launchd as OperationType>::ApplyStderr),
+    Firewall(#[pin] <Firewall as OperationType>::ApplyStderr),
+    Time(#[pin] <Time as OperationType>::ApplyStderr),
+    Networkd(#[pin] <Networkd as OperationType>::ApplyStderr),
+    Wireguard(#[pin] <Wireguard as OperationType>::ApplyStderr),
+    TlsCert(#[pin] <TlsCert as OperationType>::ApplyStderr),
+    Gpg(#[pin] <Gpg as OperationType>::ApplyStderr),
 }
 
 impl AsyncRead for OperationApplyStderr {
@@ -282,14 +693,30 @@ impl AsyncRead for OperationApplyStderr {
             Apt(stream) => stream.poll_read(cx, buf),
             AptRepo(stream) => stream.poll_read(cx, buf),
             Pacman(stream) => stream.poll_read(cx, buf),
+            Aur(stream) => stream.poll_read(cx, buf),
+            Dnf(stream) => stream.poll_read(cx, buf),
+            Apk(stream) => stream.poll_read(cx, buf),
+            Nix(stream) => stream.poll_read(cx, buf),
             Podman(stream) => stream.poll_read(cx, buf),
             File(stream) => stream.poll_read(cx, buf),
             Directory(stream) => stream.poll_read(cx, buf),
             Command(stream) => stream.poll_read(cx, buf),
             Git(stream) => stream.poll_read(cx, buf),
             Systemd(stream) => stream.poll_read(cx, buf),
+            Service(stream) => stream.poll_read(cx, buf),
             User(stream) => stream.poll_read(cx, buf),
             Group(stream) => stream.poll_read(cx, buf),
+            Cron(stream) => stream.poll_read(cx, buf),
+            Pip(stream) => stream.poll_read(cx, buf),
+            Rustup(stream) => stream.poll_read(cx, buf),
+            Brew(stream) => stream.poll_read(cx, buf),
+            Launchd(stream) => stream.poll_read(cx, buf),
+            Firewall(stream) => stream.poll_read(cx, buf),
+            Time(stream) => stream.poll_read(cx, buf),
+            Networkd(stream) => stream.poll_read(cx, buf),
+            Wireguard(stream) => stream.poll_read(cx, buf),
+            TlsCert(stream) => stream.poll_read(cx, buf),
+            Gpg(stream) => stream.poll_read(cx, buf),
         }
     }
 }
@@ -340,6 +767,46 @@ impl Operation {
                     OperationApplyStderr::Pacman(stderr),
                 ))
             }
+            Operation::Aur(op) => {
+                let (output, stdout, stderr) = Aur::apply(ctx, op)
+                    .await
+                    .map_err(OperationApplyError::Aur)?;
+                Ok((
+                    OperationApplyOutput::Aur(output),
+                    OperationApplyStdout::Aur(stdout),
+                    OperationApplyStderr::Aur(stderr),
+                ))
+            }
+            Operation::Dnf(op) => {
+                let (output, stdout, stderr) = Dnf::apply(ctx, op)
+                    .await
+                    .map_err(OperationApplyError::Dnf)?;
+                Ok((
+                    OperationApplyOutput::Dnf(output),
+                    OperationApplyStdout::Dnf(stdout),
+                    OperationApplyStderr::Dnf(stderr),
+                ))
+            }
+            Operation::Apk(op) => {
+                let (output, stdout, stderr) = Apk::apply(ctx, op)
+                    .await
+                    .map_err(OperationApplyError::Apk)?;
+                Ok((
+                    OperationApplyOutput::Apk(output),
+                    OperationApplyStdout::Apk(stdout),
+                    OperationApplyStderr::Apk(stderr),
+                ))
+            }
+            Operation::Nix(op) => {
+                let (output, stdout, stderr) = Nix::apply(ctx, op)
+                    .await
+                    .map_err(OperationApplyError::Nix)?;
+                Ok((
+                    OperationApplyOutput::Nix(output),
+                    OperationApplyStdout::Nix(stdout),
+                    OperationApplyStderr::Nix(stderr),
+                ))
+            }
             Operation::Podman(op) => {
                 let (output, stdout, stderr) = Podman::apply(ctx, op)
                     .await
@@ -400,6 +867,16 @@ impl Operation {
                     OperationApplyStderr::Systemd(stderr),
                 ))
             }
+            Operation::Service(op) => {
+                let (output, stdout, stderr) = Service::apply(ctx, op)
+                    .await
+                    .map_err(OperationApplyError::Service)?;
+                Ok((
+                    OperationApplyOutput::Service(output),
+                    OperationApplyStdout::Service(stdout),
+                    OperationApplyStderr::Service(stderr),
+                ))
+            }
             Operation::User(op) => {
                 let (output, stdout, stderr) = User::apply(ctx, op)
                     .await
@@ -420,6 +897,250 @@ impl Operation {
                     OperationApplyStderr::Group(stderr),
                 ))
             }
+            Operation::Cron(op) => {
+                let (output, stdout, stderr) = Cron::apply(ctx, op)
+                    .await
+                    .map_err(OperationApplyError::Cron)?;
+                Ok((
+                    OperationApplyOutput::Cron(output),
+                    OperationApplyStdout::Cron(stdout),
+                    OperationApplyStderr::Cron(stderr),
+                ))
+            }
+            Operation::Pip(op) => {
+                let (output, stdout, stderr) = Pip::apply(ctx, op)
+                    .await
+                    .map_err(OperationApplyError::Pip)?;
+                Ok((
+                    OperationApplyOutput::Pip(output),
+                    OperationApplyStdout::Pip(stdout),
+                    OperationApplyStderr::Pip(stderr),
+                ))
+            }
+            Operation::Rustup(op) => {
+                let (output, stdout, stderr) = Rustup::apply(ctx, op)
+                    .await
+                    .map_err(OperationApplyError::Rustup)?;
+                Ok((
+                    OperationApplyOutput::Rustup(output),
+                    OperationApplyStdout::Rustup(stdout),
+                    OperationApplyStderr::Rustup(stderr),
+                ))
+            }
+            Operation::Brew(op) => {
+                let (output, stdout, stderr) = Brew::apply(ctx, op)
+                    .await
+                    .map_err(OperationApplyError::Brew)?;
+                Ok((
+                    OperationApplyOutput::Brew(output),
+                    OperationApplyStdout::Brew(stdout),
+                    OperationApplyStderr::Brew(stderr),
+                ))
+            }
+            Operation::Launchd(op) => {
+                let (output, stdout, stderr) = Launchd::apply(ctx, op)
+                    .await
+                    .map_err(OperationApplyError::Launchd)?;
+                Ok((
+                    OperationApplyOutput::Launchd(output),
+                    OperationApplyStdout::Launchd(stdout),
+                    OperationApplyStderr::Launchd(stderr),
+                ))
+            }
+            Operation::Firewall(op) => {
+                let (output, stdout, stderr) = Firewall::apply(ctx, op)
+                    .await
+                    .map_err(OperationApplyError::Firewall)?;
+                Ok((
+                    OperationApplyOutput::Firewall(output),
+                    OperationApplyStdout::Firewall(stdout),
+                    OperationApplyStderr::Firewall(stderr),
+                ))
+            }
+            Operation::Time(op) => {
+                let (output, stdout, stderr) = Time::apply(ctx, op)
+                    .await
+                    .map_err(OperationApplyError::Time)?;
+                Ok((
+                    OperationApplyOutput::Time(output),
+                    OperationApplyStdout::Time(stdout),
+                    OperationApplyStderr::Time(stderr),
+                ))
+            }
+            Operation::Networkd(op) => {
+                let (output, stdout, stderr) = Networkd::apply(ctx, op)
+                    .await
+                    .map_err(OperationApplyError::Networkd)?;
+                Ok((
+                    OperationApplyOutput::Networkd(output),
+                    OperationApplyStdout::Networkd(stdout),
+                    OperationApplyStderr::Networkd(stderr),
+                ))
+            }
+            Operation::Wireguard(op) => {
+                let (output, stdout, stderr) = Wireguard::apply(ctx, op)
+                    .await
+                    .map_err(OperationApplyError::Wireguard)?;
+                Ok((
+                    OperationApplyOutput::Wireguard(output),
+                    OperationApplyStdout::Wireguard(stdout),
+                    OperationApplyStderr::Wireguard(stderr),
+                ))
+            }
+            Operation::TlsCert(op) => {
+                let (output, stdout, stderr) = TlsCert::apply(ctx, op)
+                    .await
+                    .map_err(OperationApplyError::TlsCert)?;
+                Ok((
+                    OperationApplyOutput::TlsCert(output),
+                    OperationApplyStdout::TlsCert(stdout),
+                    OperationApplyStderr::TlsCert(stderr),
+                ))
+            }
+            Operation::Gpg(op) => {
+                let (output, stdout, stderr) = Gpg::apply(ctx, op)
+                    .await
+                    .map_err(OperationApplyError::Gpg)?;
+                Ok((
+                    OperationApplyOutput::Gpg(output),
+                    OperationApplyStdout::Gpg(stdout),
+                    OperationApplyStderr::Gpg(stderr),
+                ))
+            }
+        }
+    }
+}
+
+impl Operation {
+    /// See [`OperationType::severity`].
+    pub fn severity(&self) -> Severity {
+        match self {
+            Operation::Apt(op) => Apt::severity(op),
+            Operation::AptRepo(op) => AptRepo::severity(op),
+            Operation::Pacman(op) => Pacman::severity(op),
+            Operation::Aur(op) => Aur::severity(op),
+            Operation::Dnf(op) => Dnf::severity(op),
+            Operation::Apk(op) => Apk::severity(op),
+            Operation::Nix(op) => Nix::severity(op),
+            Operation::Podman(op) => Podman::severity(op),
+            Operation::File(op) => File::severity(op),
+            Operation::Directory(op) => Directory::severity(op),
+            Operation::Command(op) => Command::severity(op),
+            Operation::Git(op) => Git::severity(op),
+            Operation::Systemd(op) => Systemd::severity(op),
+            Operation::Service(op) => Service::severity(op),
+            Operation::User(op) => User::severity(op),
+            Operation::Group(op) => Group::severity(op),
+            Operation::Cron(op) => Cron::severity(op),
+            Operation::Pip(op) => Pip::severity(op),
+            Operation::Rustup(op) => Rustup::severity(op),
+            Operation::Brew(op) => Brew::severity(op),
+            Operation::Launchd(op) => Launchd::severity(op),
+            Operation::Firewall(op) => Firewall::severity(op),
+            Operation::Time(op) => Time::severity(op),
+            Operation::Networkd(op) => Networkd::severity(op),
+            Operation::Wireguard(op) => Wireguard::severity(op),
+            Operation::TlsCert(op) => TlsCert::severity(op),
+            Operation::Gpg(op) => Gpg::severity(op),
+        }
+    }
+
+    /// See [`OperationType::script`].
+    pub fn script(&self) -> Option<String> {
+        match self {
+            Operation::Apt(op) => Apt::script(op),
+            Operation::AptRepo(op) => AptRepo::script(op),
+            Operation::Pacman(op) => Pacman::script(op),
+            Operation::Aur(op) => Aur::script(op),
+            Operation::Dnf(op) => Dnf::script(op),
+            Operation::Apk(op) => Apk::script(op),
+            Operation::Nix(op) => Nix::script(op),
+            Operation::Podman(op) => Podman::script(op),
+            Operation::File(op) => File::script(op),
+            Operation::Directory(op) => Directory::script(op),
+            Operation::Command(op) => Command::script(op),
+            Operation::Git(op) => Git::script(op),
+            Operation::Systemd(op) => Systemd::script(op),
+            Operation::Service(op) => Service::script(op),
+            Operation::User(op) => User::script(op),
+            Operation::Group(op) => Group::script(op),
+            Operation::Cron(op) => Cron::script(op),
+            Operation::Pip(op) => Pip::script(op),
+            Operation::Rustup(op) => Rustup::script(op),
+            Operation::Brew(op) => Brew::script(op),
+            Operation::Launchd(op) => Launchd::script(op),
+            Operation::Firewall(op) => Firewall::script(op),
+            Operation::Time(op) => Time::script(op),
+            Operation::Networkd(op) => Networkd::script(op),
+            Operation::Wireguard(op) => Wireguard::script(op),
+            Operation::TlsCert(op) => TlsCert::script(op),
+            Operation::Gpg(op) => Gpg::script(op),
+        }
+    }
+
+    /// See [`OperationType::simulate`].
+    pub fn simulate(&self) -> Option<lusid_cmd::Command> {
+        match self {
+            Operation::Apt(op) => Apt::simulate(op),
+            Operation::AptRepo(op) => AptRepo::simulate(op),
+            Operation::Pacman(op) => Pacman::simulate(op),
+            Operation::Aur(op) => Aur::simulate(op),
+            Operation::Dnf(op) => Dnf::simulate(op),
+            Operation::Apk(op) => Apk::simulate(op),
+            Operation::Nix(op) => Nix::simulate(op),
+            Operation::Podman(op) => Podman::simulate(op),
+            Operation::File(op) => File::simulate(op),
+            Operation::Directory(op) => Directory::simulate(op),
+            Operation::Command(op) => Command::simulate(op),
+            Operation::Git(op) => Git::simulate(op),
+            Operation::Systemd(op) => Systemd::simulate(op),
+            Operation::Service(op) => Service::simulate(op),
+            Operation::User(op) => User::simulate(op),
+            Operation::Group(op) => Group::simulate(op),
+            Operation::Cron(op) => Cron::simulate(op),
+            Operation::Pip(op) => Pip::simulate(op),
+            Operation::Rustup(op) => Rustup::simulate(op),
+            Operation::Brew(op) => Brew::simulate(op),
+            Operation::Launchd(op) => Launchd::simulate(op),
+            Operation::Firewall(op) => Firewall::simulate(op),
+            Operation::Time(op) => Time::simulate(op),
+            Operation::Networkd(op) => Networkd::simulate(op),
+            Operation::Wireguard(op) => Wireguard::simulate(op),
+            Operation::TlsCert(op) => TlsCert::simulate(op),
+            Operation::Gpg(op) => Gpg::simulate(op),
+        }
+    }
+
+    /// See [`OperationType::progress`].
+    pub fn progress(&self) -> Option<ProgressParser> {
+        match self {
+            Operation::Apt(op) => Apt::progress(op),
+            Operation::AptRepo(op) => AptRepo::progress(op),
+            Operation::Pacman(op) => Pacman::progress(op),
+            Operation::Aur(op) => Aur::progress(op),
+            Operation::Dnf(op) => Dnf::progress(op),
+            Operation::Apk(op) => Apk::progress(op),
+            Operation::Nix(op) => Nix::progress(op),
+            Operation::Podman(op) => Podman::progress(op),
+            Operation::File(op) => File::progress(op),
+            Operation::Directory(op) => Directory::progress(op),
+            Operation::Command(op) => Command::progress(op),
+            Operation::Git(op) => Git::progress(op),
+            Operation::Systemd(op) => Systemd::progress(op),
+            Operation::Service(op) => Service::progress(op),
+            Operation::User(op) => User::progress(op),
+            Operation::Group(op) => Group::progress(op),
+            Operation::Cron(op) => Cron::progress(op),
+            Operation::Pip(op) => Pip::progress(op),
+            Operation::Rustup(op) => Rustup::progress(op),
+            Operation::Brew(op) => Brew::progress(op),
+            Operation::Launchd(op) => Launchd::progress(op),
+            Operation::Firewall(op) => Firewall::progress(op),
+            Operation::Time(op) => Time::progress(op),
+            Operation::Networkd(op) => Networkd::progress(op),
+            Operation::Wireguard(op) => Wireguard::progress(op),
+            Operation::TlsCert(op) => TlsCert::progress(op),
+            Operation::Gpg(op) => Gpg::progress(op),
         }
     }
 }
@@ -431,14 +1152,30 @@ impl Display for Operation {
             Apt(op) => Display::fmt(op, f),
             AptRepo(op) => Display::fmt(op, f),
             Pacman(op) => Display::fmt(op, f),
+            Aur(op) => Display::fmt(op, f),
+            Dnf(op) => Display::fmt(op, f),
+            Apk(op) => Display::fmt(op, f),
+            Nix(op) => Display::fmt(op, f),
             Podman(op) => Display::fmt(op, f),
             File(op) => Display::fmt(op, f),
             Directory(op) => Display::fmt(op, f),
             Command(op) => Display::fmt(op, f),
             Git(op) => Display::fmt(op, f),
             Systemd(op) => Display::fmt(op, f),
+            Service(op) => Display::fmt(op, f),
             User(op) => Display::fmt(op, f),
             Group(op) => Display::fmt(op, f),
+            Cron(op) => Display::fmt(op, f),
+            Pip(op) => Display::fmt(op, f),
+            Rustup(op) => Display::fmt(op, f),
+            Brew(op) => Display::fmt(op, f),
+            Launchd(op) => Display::fmt(op, f),
+            Firewall(op) => Display::fmt(op, f),
+            Time(op) => Display::fmt(op, f),
+            Networkd(op) => Display::fmt(op, f),
+            Wireguard(op) => Display::fmt(op, f),
+            TlsCert(op) => Display::fmt(op, f),
+            Gpg(op) => Display::fmt(op, f),
         }
     }
 }
@@ -452,12 +1189,28 @@ impl Render for Operation {
             File(params) => params.render(),
             Directory(params) => params.render(),
             Pacman(params) => params.render(),
+            Aur(params) => params.render(),
+            Dnf(params) => params.render(),
+            Apk(params) => params.render(),
+            Nix(params) => params.render(),
             Podman(params) => params.render(),
             Command(params) => params.render(),
             Git(params) => params.render(),
             Systemd(params) => params.render(),
+            Service(params) => params.render(),
             User(params) => params.render(),
             Group(params) => params.render(),
+            Cron(params) => params.render(),
+            Pip(params) => params.render(),
+            Rustup(params) => params.render(),
+            Brew(params) => params.render(),
+            Launchd(params) => params.render(),
+            Firewall(params) => params.render(),
+            Time(params) => params.render(),
+            Networkd(params) => params.render(),
+            Wireguard(params) => params.render(),
+            TlsCert(params) => params.render(),
+            Gpg(params) => params.render(),
         }
     }
 }
@@ -468,14 +1221,30 @@ pub struct OperationsByType {
     apt: Vec<AptOperation>,
     apt_repo: Vec<AptRepoOperation>,
     pacman: Vec<PacmanOperation>,
+    aur: Vec<AurOperation>,
+    dnf: Vec<DnfOperation>,
+    apk: Vec<ApkOperation>,
+    nix: Vec<NixOperation>,
     podman: Vec<PodmanOperation>,
     file: Vec<FileOperation>,
     directory: Vec<DirectoryOperation>,
     command: Vec<CommandOperation>,
     git: Vec<GitOperation>,
     systemd: Vec<SystemdOperation>,
+    service: Vec<ServiceOperation>,
     user: Vec<UserOperation>,
     group: Vec<GroupOperation>,
+    cron: Vec<CronOperation>,
+    pip: Vec<PipOperation>,
+    rustup: Vec<RustupOperation>,
+    brew: Vec<BrewOperation>,
+    launchd: Vec<LaunchdOperation>,
+    firewall: Vec<FirewallOperation>,
+    time: Vec<TimeOperation>,
+    networkd: Vec<NetworkdOperation>,
+    wireguard: Vec<WireguardOperation>,
+    tls_cert: Vec<TlsCertOperation>,
+    gpg: Vec<GpgOperation>,
 }
 
 /// Bucket a mixed iterator of operations into per-family vectors.
@@ -483,40 +1252,570 @@ fn partition_by_type(operations: impl IntoIterator<Item = Operation>) -> Operati
     let mut apt: Vec<AptOperation> = Vec::new();
     let mut apt_repo: Vec<AptRepoOperation> = Vec::new();
     let mut pacman: Vec<PacmanOperation> = Vec::new();
+    let mut aur: Vec<AurOperation> = Vec::new();
+    let mut dnf: Vec<DnfOperation> = Vec::new();
+    let mut apk: Vec<ApkOperation> = Vec::new();
+    let mut nix: Vec<NixOperation> = Vec::new();
     let mut podman: Vec<PodmanOperation> = Vec::new();
     let mut file: Vec<FileOperation> = Vec::new();
     let mut directory: Vec<DirectoryOperation> = Vec::new();
     let mut command: Vec<CommandOperation> = Vec::new();
     let mut git: Vec<GitOperation> = Vec::new();
     let mut systemd: Vec<SystemdOperation> = Vec::new();
+    let mut service: Vec<ServiceOperation> = Vec::new();
     let mut user: Vec<UserOperation> = Vec::new();
     let mut group: Vec<GroupOperation> = Vec::new();
+    let mut cron: Vec<CronOperation> = Vec::new();
+    let mut pip: Vec<PipOperation> = Vec::new();
+    let mut rustup: Vec<RustupOperation> = Vec::new();
+    let mut brew: Vec<BrewOperation> = Vec::new();
+    let mut launchd: Vec<LaunchdOperation> = Vec::new();
+    let mut firewall: Vec<FirewallOperation> = Vec::new();
+    let mut time: Vec<TimeOperation> = Vec::new();
+    let mut networkd: Vec<NetworkdOperation> = Vec::new();
+    let mut wireguard: Vec<WireguardOperation> = Vec::new();
+    let mut tls_cert: Vec<TlsCertOperation> = Vec::new();
+    let mut gpg: Vec<GpgOperation> = Vec::new();
     for operation in operations.into_iter() {
         match operation {
             Operation::Apt(op) => apt.push(op),
             Operation::AptRepo(op) => apt_repo.push(op),
             Operation::Pacman(op) => pacman.push(op),
+            Operation::Aur(op) => aur.push(op),
+            Operation::Dnf(op) => dnf.push(op),
+            Operation::Apk(op) => apk.push(op),
+            Operation::Nix(op) => nix.push(op),
             Operation::Podman(op) => podman.push(op),
             Operation::File(op) => file.push(op),
             Operation::Directory(op) => directory.push(op),
             Operation::Command(op) => command.push(op),
             Operation::Git(op) => git.push(op),
             Operation::Systemd(op) => systemd.push(op),
+            Operation::Service(op) => service.push(op),
             Operation::User(op) => user.push(op),
             Operation::Group(op) => group.push(op),
+            Operation::Cron(op) => cron.push(op),
+            Operation::Pip(op) => pip.push(op),
+            Operation::Rustup(op) => rustup.push(op),
+            Operation::Brew(op) => brew.push(op),
+            Operation::Launchd(op) => launchd.push(op),
+            Operation::Firewall(op) => firewall.push(op),
+            Operation::Time(op) => time.push(op),
+            Operation::Networkd(op) => networkd.push(op),
+            Operation::Wireguard(op) => wireguard.push(op),
+            Operation::TlsCert(op) => tls_cert.push(op),
+            Operation::Gpg(op) => gpg.push(op),
         }
     }
     OperationsByType {
         apt,
         apt_repo,
         pacman,
+        aur,
+        dnf,
+        apk,
+        nix,
         podman,
         file,
         directory,
         command,
         git,
         systemd,
+        service,
         user,
         group,
+        cron,
+        pip,
+        rustup,
+        brew,
+        launchd,
+        firewall,
+        time,
+        networkd,
+        wireguard,
+        tls_cert,
+        gpg,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::ExitStatus;
+
+    use lusid_causality::Shuffler;
+    use lusid_cmd::{CommandError, CommandExit};
+
+    use crate::operations::apt::AptApplyError;
+
+    use super::*;
+
+    fn merged_labels(operations: Vec<Operation>) -> Vec<String> {
+        Operation::merge(operations)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn script_is_the_command_line() {
+        let install = Operation::Apt(AptOperation::Install {
+            packages: vec!["curl".into(), "git".into()],
+        });
+        assert_eq!(
+            install.script().as_deref(),
+            Some("sudo -n 'DEBIAN_FRONTEND=noninteractive' apt-get install -y curl git")
+        );
+
+        let shell = Operation::Command(CommandOperation {
+            command: "command -v rg || cargo install ripgrep".into(),
+            executor: operations::command::CommandExecutor::Shell,
+        });
+        assert_eq!(
+            shell.script().as_deref(),
+            Some("sh -c 'command -v rg || cargo install ripgrep'")
+        );
+
+        let create = Operation::Directory(DirectoryOperation::Create {
+            path: operations::file::FilePath::new("/srv/app"),
+        });
+        assert_eq!(create.script(), None);
+    }
+
+    #[test]
+    fn package_installs_simulate_without_root() {
+        let install = Operation::Apt(AptOperation::Install {
+            packages: vec!["curl".into(), "git".into()],
+        });
+        assert_eq!(
+            install.simulate().map(|cmd| cmd.to_shell()).as_deref(),
+            Some("apt-get install --simulate curl git")
+        );
+        let pacman = Operation::Pacman(PacmanOperation::Install {
+            packages: vec!["ripgrep".into()],
+        });
+        assert_eq!(
+            pacman.simulate().map(|cmd| cmd.to_shell()).as_deref(),
+            Some("pacman -S --print --print-format '%n %v' --needed '--color=never' -- ripgrep")
+        );
+        assert!(Operation::Apt(AptOperation::Update).simulate().is_none());
+    }
+
+    #[test]
+    fn removals_are_destructive() {
+        let path = || operations::file::FilePath::new("/srv/app");
+        let severities = [
+            Operation::Directory(DirectoryOperation::Create { path: path() }),
+            Operation::Service(ServiceOperation::Restart {
+                unit: "nginx".into(),
+            }),
+            Operation::Directory(DirectoryOperation::Remove { path: path() }),
+            Operation::User(UserOperation::Delete {
+                name: "alice".into(),
+                remove_home: false,
+            }),
+        ]
+        .map(|operation| operation.severity());
+        assert_eq!(
+            severities,
+            [
+                Severity::Safe,
+                Severity::Disruptive,
+                Severity::Destructive,
+                Severity::Destructive,
+            ]
+        );
+        assert_eq!(severities.into_iter().max(), Some(Severity::Destructive));
+    }
+
+    #[test]
+    fn merge_is_independent_of_input_order() {
+        let operations = vec![
+            Operation::Apt(AptOperation::Install {
+                packages: vec!["curl".into(), "git".into()],
+            }),
+            Operation::Service(ServiceOperation::Start {
+                unit: "nginx".into(),
+            }),
+            Operation::Apt(AptOperation::Update),
+            Operation::Pacman(PacmanOperation::Install {
+                packages: vec!["ripgrep".into()],
+            }),
+            Operation::Apt(AptOperation::Install {
+                packages: vec!["nginx".into(), "curl".into()],
+            }),
+            Operation::Service(ServiceOperation::Enable {
+                unit: "nginx".into(),
+            }),
+        ];
+
+        // Families that coalesce (apt, pacman) must merge to exactly the same
+        // operations; pass-through families keep their relative input order,
+        // so only compare those as a multiset.
+        let mut expected = merged_labels(operations.clone());
+        assert_eq!(
+            expected[..2],
+            [
+                "Apt::Update".to_string(),
+                "Apt::Install(packages = [curl, git, nginx])".to_string()
+            ]
+        );
+        expected.sort();
+
+        for seed in 0..32 {
+            let mut shuffled = operations.clone();
+            Shuffler::new(seed).shuffle(&mut shuffled);
+            let mut labels = merged_labels(shuffled);
+            labels.sort();
+            assert_eq!(labels, expected, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn group_memberships_merge_per_user() {
+        let add_user = |name: &str, user: &str| {
+            Operation::Group(GroupOperation::AddUser {
+                name: name.into(),
+                user: user.into(),
+            })
+        };
+        let operations = vec![
+            add_user("wheel", "me"),
+            Operation::Group(GroupOperation::Add {
+                name: "media".into(),
+                gid: None,
+                system: false,
+            }),
+            add_user("docker", "me"),
+            add_user("docker", "ci"),
+            add_user("docker", "me"),
+        ];
+
+        assert_eq!(
+            merged_labels(operations.clone()),
+            [
+                "Group::AddUserToGroups(user = me, groups = [docker, wheel])",
+                "Group::Add(name = media)",
+                "Group::AddUser(name = docker, user = ci)",
+            ]
+        );
+        assert_eq!(
+            Operation::merge(operations)[0].script().as_deref(),
+            Some("sudo -n usermod -aG docker,wheel -- me")
+        );
+    }
+
+    #[test]
+    fn pip_installs_merge_per_target() {
+        use operations::pip::PipTarget;
+
+        let venv = || operations::file::FilePath::new("/opt/tools");
+        let install = |target: PipTarget, requirement: &str| {
+            Operation::Pip(PipOperation::Install {
+                target,
+                requirements: vec![requirement.into()],
+            })
+        };
+        let operations = vec![
+            install(PipTarget::Venv { path: venv() }, "requests"),
+            install(PipTarget::Pipx, "httpie"),
+            Operation::Pip(PipOperation::CreateVenv { path: venv() }),
+            install(PipTarget::Pipx, "black==24.1.0"),
+            Operation::Pip(PipOperation::CreateVenv { path: venv() }),
+        ];
+
+        assert_eq!(
+            merged_labels(operations.clone()),
+            [
+                "Pip::CreateVenv(path = /opt/tools)",
+                "Pip::Install(target = pipx, requirements = [black==24.1.0, httpie])",
+                "Pip::Install(target = venv /opt/tools, requirements = [requests])",
+            ]
+        );
+        assert_eq!(
+            Operation::merge(operations)[2].script().as_deref(),
+            Some("/opt/tools/bin/pip install --disable-pip-version-check requests")
+        );
+    }
+
+    #[test]
+    fn rustup_operations_merge_per_toolchain() {
+        let components = |toolchain: &str, components: &[&str]| {
+            Operation::Rustup(RustupOperation::AddComponents {
+                toolchain: toolchain.into(),
+                components: components.iter().map(|c| c.to_string()).collect(),
+            })
+        };
+        let operations = vec![
+            components("stable", &["rustfmt"]),
+            Operation::Rustup(RustupOperation::AddTargets {
+                toolchain: "nightly".into(),
+                targets: vec!["wasm32-unknown-unknown".into()],
+            }),
+            components("stable", &["clippy"]),
+            Operation::Rustup(RustupOperation::Install {
+                toolchain: "nightly".into(),
+                components: vec![],
+                targets: vec![],
+            }),
+            components("nightly", &["miri"]),
+        ];
+
+        assert_eq!(
+            merged_labels(operations.clone()),
+            [
+                "Rustup::Install(toolchain = nightly, components = [miri], targets = [wasm32-unknown-unknown])",
+                "Rustup::AddComponents(toolchain = stable, components = [clippy, rustfmt])",
+            ]
+        );
+        assert_eq!(
+            Operation::merge(operations)[0].script().as_deref(),
+            Some(
+                "rustup toolchain install --no-self-update --profile minimal --component miri --target wasm32-unknown-unknown -- nightly"
+            )
+        );
+    }
+
+    #[test]
+    fn brew_operations_merge_formulae_and_casks() {
+        let operations = vec![
+            Operation::Brew(BrewOperation::InstallFormulae {
+                formulae: vec!["ripgrep".into()],
+            }),
+            Operation::Brew(BrewOperation::InstallCasks {
+                casks: vec!["firefox".into()],
+            }),
+            Operation::Brew(BrewOperation::InstallFormulae {
+                formulae: vec!["git".into(), "ripgrep".into()],
+            }),
+        ];
+
+        assert_eq!(
+            merged_labels(operations.clone()),
+            [
+                "Brew::InstallFormulae([git, ripgrep])",
+                "Brew::InstallCasks([firefox])",
+            ]
+        );
+        assert_eq!(
+            Operation::merge(operations)[0].script().as_deref(),
+            Some(
+                "HOMEBREW_NO_AUTO_UPDATE=1 HOMEBREW_NO_ENV_HINTS=1 brew install --formula -- git ripgrep"
+            )
+        );
+    }
+
+    #[test]
+    fn dnf_removals_merge_before_installs() {
+        let operations = vec![
+            Operation::Dnf(DnfOperation::Install {
+                packages: vec!["iptables-services".into()],
+            }),
+            Operation::Dnf(DnfOperation::Remove {
+                packages: vec!["firewalld".into()],
+            }),
+            Operation::Dnf(DnfOperation::Install {
+                packages: vec!["curl".into(), "iptables-services".into()],
+            }),
+        ];
+
+        assert_eq!(
+            merged_labels(operations.clone()),
+            [
+                "Dnf::Remove(packages = [firewalld])",
+                "Dnf::Install(packages = [curl, iptables-services])",
+            ]
+        );
+        let merged = Operation::merge(operations);
+        assert_eq!(merged[0].severity(), Severity::Destructive);
+        assert_eq!(
+            merged[1].script().as_deref(),
+            Some("sudo -n dnf --assumeyes '--color=never' install -- curl iptables-services")
+        );
+    }
+
+    #[test]
+    fn apk_installs_merge_into_one_add() {
+        let operations = vec![
+            Operation::Apk(ApkOperation::Install {
+                packages: vec!["nginx".into()],
+            }),
+            Operation::Apk(ApkOperation::Install {
+                packages: vec!["curl".into(), "nginx".into()],
+            }),
+        ];
+
+        assert_eq!(
+            merged_labels(operations.clone()),
+            ["Apk::Install(packages = [curl, nginx])"]
+        );
+        assert_eq!(
+            Operation::merge(operations)[0].script().as_deref(),
+            Some("sudo -n apk add --no-progress --update-cache -- curl nginx")
+        );
+    }
+
+    #[test]
+    fn aur_installs_merge_per_helper_and_user() {
+        let install = |helper: &str, user: &str, package: &str| {
+            Operation::Aur(AurOperation::Install {
+                helper: helper.into(),
+                user: user.into(),
+                packages: vec![package.into()],
+            })
+        };
+        let operations = vec![
+            install("paru", "mikey", "zoom"),
+            install("yay", "mikey", "spotify"),
+            install("paru", "mikey", "visual-studio-code-bin"),
+        ];
+
+        assert_eq!(
+            merged_labels(operations.clone()),
+            [
+                "Aur::Install(helper = paru, user = mikey, packages = [visual-studio-code-bin, zoom])",
+                "Aur::Install(helper = yay, user = mikey, packages = [spotify])"
+            ]
+        );
+        assert_eq!(
+            Operation::merge(operations)[0].script().as_deref(),
+            Some("sudo -n -u mikey paru -S --noconfirm --needed -- visual-studio-code-bin zoom")
+        );
+    }
+
+    #[test]
+    fn nix_removals_merge_before_installs() {
+        let operations = vec![
+            Operation::Nix(NixOperation::Install {
+                installables: vec!["nixpkgs#ripgrep".into()],
+            }),
+            Operation::Nix(NixOperation::Remove {
+                elements: vec!["fd".into()],
+            }),
+            Operation::Nix(NixOperation::Install {
+                installables: vec!["nixpkgs#fd".into(), "nixpkgs#ripgrep".into()],
+            }),
+        ];
+
+        assert_eq!(
+            merged_labels(operations.clone()),
+            [
+                "Nix::Remove([fd])",
+                "Nix::Install([nixpkgs#fd, nixpkgs#ripgrep])"
+            ]
+        );
+        assert_eq!(
+            Operation::merge(operations)[1].script().as_deref(),
+            Some(
+                "nix --extra-experimental-features 'nix-command flakes' profile install -- 'nixpkgs#fd' 'nixpkgs#ripgrep'"
+            )
+        );
+    }
+
+    #[test]
+    fn podman_pulls_merge_into_one() {
+        let pull = |image: &str| {
+            Operation::Podman(PodmanOperation::Pull {
+                images: vec![image.into()],
+            })
+        };
+        let operations = vec![
+            Operation::Podman(PodmanOperation::Start { name: "db".into() }),
+            pull("docker.io/library/redis:7"),
+            pull("docker.io/library/nginx:1.27"),
+            pull("docker.io/library/redis:7"),
+        ];
+
+        assert_eq!(
+            merged_labels(operations.clone()),
+            [
+                "Podman::Start(db)",
+                "Podman::Pull(images = [docker.io/library/nginx:1.27, docker.io/library/redis:7])",
+            ]
+        );
+        assert_eq!(
+            Operation::merge(operations)[1].script().as_deref(),
+            Some("podman pull -- docker.io/library/nginx:1.27 docker.io/library/redis:7")
+        );
+    }
+
+    #[test]
+    fn tls_certs_name_ip_addresses_as_such() {
+        let operation = Operation::TlsCert(TlsCertOperation::SelfSigned {
+            cert_path: operations::file::FilePath::new("/etc/ssl/lusid/home.lan.crt"),
+            key_path: operations::file::FilePath::new("/etc/ssl/lusid/home.lan.key"),
+            names: vec!["home.lan".into(), "192.168.1.10".into(), "::1".into()],
+            days: 365,
+        });
+        let script = operation.script().expect("script");
+        assert!(script.starts_with("sudo -n sh -c 'umask 077 && openssl req -x509"));
+        assert!(script.ends_with(
+            " tls-cert /etc/ssl/lusid/home.lan.key /etc/ssl/lusid/home.lan.crt 365 home.lan DNS:home.lan,IP:192.168.1.10,IP:::1"
+        ));
+    }
+
+    #[test]
+    fn git_config_with_many_values_replaces_them_all() {
+        use crate::operations::git::GitConfigScope;
+
+        let set = |values: &[&str]| {
+            Operation::Git(GitOperation::SetConfig {
+                scope: GitConfigScope::Global {
+                    user: Some("me".into()),
+                },
+                key: "credential.helper".into(),
+                values: values.iter().map(ToString::to_string).collect(),
+            })
+        };
+        assert_eq!(
+            set(&["store"]).script().as_deref(),
+            Some("sudo -n -u me git config --global --replace-all credential.helper store")
+        );
+        let script = set(&["", "store"]).script().expect("script");
+        assert!(script.starts_with("sudo -n -u me sh -c "));
+        assert!(script.ends_with(" git-config --global credential.helper '' store"));
+    }
+
+    #[test]
+    fn gpg_keys_from_a_keyserver_have_a_script_but_fetched_ones_dont() {
+        use crate::operations::gpg::{GpgKeySource, GpgKeyring};
+
+        let import = |source| {
+            Operation::Gpg(GpgOperation::ImportKey {
+                fingerprint: "FAFC2B9B27A58C139883BB57EFDBFB83254EFF84".into(),
+                source,
+                keyring: GpgKeyring::File {
+                    path: operations::file::FilePath::new("/etc/apt/keyrings/example.gpg"),
+                },
+            })
+        };
+        assert_eq!(
+            import(GpgKeySource::Url {
+                url: "https://example.com/key.asc".into()
+            })
+            .script(),
+            None
+        );
+        let script = import(GpgKeySource::Keyserver {
+            keyserver: "hkps://keys.openpgp.org".into(),
+        })
+        .script()
+        .expect("script");
+        assert!(script.starts_with("sudo -n sh -c "));
+        assert!(script.ends_with(
+            " gpg-key FAFC2B9B27A58C139883BB57EFDBFB83254EFF84 hkps://keys.openpgp.org /etc/apt/keyrings/example.gpg"
+        ));
+    }
+
+    #[test]
+    fn failed_commands_report_their_exit_code() {
+        let error = OperationApplyError::Apt(AptApplyError::Command(CommandError::Exit {
+            command: "apt-get install -y curl".into(),
+            exit: CommandExit {
+                status: ExitStatus::from_raw(100 << 8),
+            },
+        }));
+        assert_eq!(error.exit_code(), Some(100));
+        assert_eq!(error.code(), "operation.apt");
     }
 }
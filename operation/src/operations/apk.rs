@@ -0,0 +1,106 @@
+use async_trait::async_trait;
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_view::impl_display_render;
+use std::{collections::BTreeSet, fmt::Display, pin::Pin};
+use thiserror::Error;
+use tokio::process::{ChildStderr, ChildStdout};
+use tracing::info;
+
+use crate::OperationType;
+
+#[derive(Debug, Clone)]
+pub enum ApkOperation {
+    Install { packages: Vec<String> },
+}
+
+impl Display for ApkOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApkOperation::Install { packages } => {
+                write!(f, "Apk::Install(packages = [{}])", packages.join(", "))
+            }
+        }
+    }
+}
+
+impl_display_render!(ApkOperation);
+
+#[derive(Error, Debug)]
+pub enum ApkApplyError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+}
+
+#[derive(Debug, Clone)]
+pub struct Apk;
+
+#[async_trait]
+impl OperationType for Apk {
+    type Operation = ApkOperation;
+
+    fn merge(operations: Vec<Self::Operation>) -> Vec<Self::Operation> {
+        let mut install: BTreeSet<String> = BTreeSet::new();
+
+        for operation in operations {
+            match operation {
+                ApkOperation::Install { packages } => install.extend(packages),
+            }
+        }
+
+        if install.is_empty() {
+            return Vec::new();
+        }
+        vec![ApkOperation::Install {
+            packages: install.into_iter().collect(),
+        }]
+    }
+
+    fn script(operation: &Self::Operation) -> Option<String> {
+        Some(command(operation).to_shell())
+    }
+
+    type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
+    type ApplyError = ApkApplyError;
+    type ApplyStdout = ChildStdout;
+    type ApplyStderr = ChildStderr;
+
+    async fn apply(
+        _ctx: &mut Context,
+        operation: &Self::Operation,
+    ) -> Result<(Self::ApplyOutput, Self::ApplyStdout, Self::ApplyStderr), Self::ApplyError> {
+        match operation {
+            ApkOperation::Install { packages } => {
+                info!("[apk] install: {}", packages.join(", "));
+            }
+        }
+        let output = command(operation).output_checked().await?;
+        Ok((
+            Box::pin(async move {
+                output.status.await?;
+                Ok(())
+            }),
+            output.stdout,
+            output.stderr,
+        ))
+    }
+}
+
+/// The command `operation` runs, shared by [`OperationType::apply`] and
+/// [`OperationType::script`].
+///
+/// `--update-cache` fetches the package indexes first: container images ship
+/// without them, and a cached index goes stale as mirrors drop old versions.
+fn command(operation: &ApkOperation) -> Command {
+    match operation {
+        ApkOperation::Install { packages } => {
+            let mut cmd = Command::new("apk");
+            cmd.arg("add")
+                .arg("--no-progress")
+                .arg("--update-cache")
+                .arg("--")
+                .args(packages);
+            cmd.sudo()
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use async_trait::async_trait;
 use lusid_cmd::{Command, CommandError};
-use lusid_ctx::Context;
+use lusid_ctx::{Context, DownloadLimits};
 use lusid_view::impl_display_render;
 use std::{collections::BTreeSet, fmt::Display, pin::Pin};
 use thiserror::Error;
@@ -70,47 +70,85 @@ impl OperationType for Apt {
         operations
     }
 
+    fn script(operation: &Self::Operation) -> Option<String> {
+        Some(command(operation, DownloadLimits::default()).to_shell())
+    }
+
+    // Needs no root. It reads the package lists as they are, so doesn't see
+    // an update the same apply would have run first.
+    fn simulate(operation: &Self::Operation) -> Option<Command> {
+        let AptOperation::Install { packages } = operation else {
+            return None;
+        };
+        let mut cmd = Command::new("apt-get");
+        cmd.arg("install").arg("--simulate").args(packages);
+        Some(cmd)
+    }
+
     type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
     type ApplyError = AptApplyError;
     type ApplyStdout = ChildStdout;
     type ApplyStderr = ChildStderr;
 
     async fn apply(
-        _ctx: &mut Context,
+        ctx: &mut Context,
         operation: &Self::Operation,
     ) -> Result<(Self::ApplyOutput, Self::ApplyStdout, Self::ApplyStderr), Self::ApplyError> {
         match operation {
             AptOperation::Update => {
                 info!("[apt] update");
-                let mut cmd = Command::new("apt-get");
-                cmd.env("DEBIAN_FRONTEND", "noninteractive").arg("update");
-                let output = cmd.sudo().output().await?;
-                Ok((
-                    Box::pin(async move {
-                        output.status.await?;
-                        Ok(())
-                    }),
-                    output.stdout,
-                    output.stderr,
-                ))
             }
             AptOperation::Install { packages } => {
                 info!("[apt] install: {}", packages.join(", "));
-                let mut cmd = Command::new("apt-get");
-                cmd.env("DEBIAN_FRONTEND", "noninteractive")
-                    .arg("install")
-                    .arg("-y")
-                    .args(packages);
-                let output = cmd.sudo().output().await?;
-                Ok((
-                    Box::pin(async move {
-                        output.status.await?;
-                        Ok(())
-                    }),
-                    output.stdout,
-                    output.stderr,
-                ))
             }
         }
+        let output = command(operation, ctx.download_limits())
+            .output_checked()
+            .await?;
+        Ok((
+            Box::pin(async move {
+                output.status.await?;
+                Ok(())
+            }),
+            output.stdout,
+            output.stderr,
+        ))
+    }
+}
+
+/// The command `operation` runs, shared by [`OperationType::apply`] and
+/// [`OperationType::script`].
+fn command(operation: &AptOperation, limits: DownloadLimits) -> Command {
+    let mut cmd = Command::new("apt-get");
+    cmd.env("DEBIAN_FRONTEND", "noninteractive");
+    for option in download_options(limits) {
+        cmd.arg("-o").arg(option);
+    }
+    match operation {
+        AptOperation::Update => {
+            cmd.arg("update");
+            cmd.sudo()
+        }
+        AptOperation::Install { packages } => {
+            cmd.arg("install").arg("-y").args(packages);
+            cmd.sudo()
+        }
+    }
+}
+
+/// `apt-get -o` options to keep apt's own downloads within `limits`.
+///
+/// apt can't cap its connections at a number: any `max_parallel` switches it
+/// to one queue per access method (`http`, `https`, ...) rather than one per
+/// host, its most conservative mode. `Dl-Limit` is per connection.
+fn download_options(limits: DownloadLimits) -> Vec<String> {
+    let mut options = Vec::new();
+    if limits.max_parallel.is_some() {
+        options.push("Acquire::Queue-Mode=access".to_owned());
+    }
+    if let Some(max_kib_per_sec) = limits.max_kib_per_sec {
+        options.push(format!("Acquire::http::Dl-Limit={max_kib_per_sec}"));
+        options.push(format!("Acquire::https::Dl-Limit={max_kib_per_sec}"));
     }
+    options
 }
@@ -1,10 +1,10 @@
-use std::{fmt::Display, path::PathBuf, pin::Pin};
+use std::{fmt::Display, pin::Pin};
 
 use async_trait::async_trait;
 use lusid_cmd::{Command, CommandError};
 use lusid_ctx::Context;
-use lusid_fs::{self as fs, FsError};
-use lusid_http::HttpError;
+use lusid_fs::FsError;
+use lusid_http::{Checksum, HttpError};
 use lusid_view::impl_display_render;
 use thiserror::Error;
 use tokio::process::{ChildStderr, ChildStdout};
@@ -12,6 +12,7 @@ use tracing::info;
 
 use crate::OperationType;
 use crate::operations::file::FilePath;
+use crate::operations::stage::stage_file;
 
 const STAGE_SUBDIR: &str = "apt-repo";
 
@@ -21,11 +22,13 @@ pub enum AptRepoOperation {
     /// `install -d` is a no-op when the directory already exists.
     EnsureKeyringsDir { path: FilePath },
 
-    /// Stream `url` into a user-writable cache, then `sudo install` it to `path`
-    /// with mode 0644 so apt can read it under any sudo umask.
+    /// Fetch `url` into the host's artifact cache, then `sudo install` it to
+    /// `path` with mode 0644 so apt can read it under any sudo umask. With a
+    /// `sha256`, a key already in the cache isn't downloaded again.
     DownloadKey {
         name: String,
         url: String,
+        sha256: Option<String>,
         path: FilePath,
     },
 
@@ -44,10 +47,18 @@ impl Display for AptRepoOperation {
             AptRepoOperation::EnsureKeyringsDir { path } => {
                 write!(f, "AptRepo::EnsureKeyringsDir(path = {path})")
             }
-            AptRepoOperation::DownloadKey { name, url, path } => write!(
-                f,
-                "AptRepo::DownloadKey(name = {name}, url = {url}, path = {path})"
-            ),
+            AptRepoOperation::DownloadKey {
+                name,
+                url,
+                sha256,
+                path,
+            } => {
+                write!(f, "AptRepo::DownloadKey(name = {name}, url = {url}, ")?;
+                if let Some(sha256) = sha256 {
+                    write!(f, "sha256 = {sha256}, ")?;
+                }
+                write!(f, "path = {path})")
+            }
             AptRepoOperation::WriteSources {
                 name,
                 path,
@@ -91,6 +102,11 @@ impl OperationType for AptRepo {
         operations
     }
 
+    // Keys are downloaded and source lists staged in-process before `install`.
+    fn script(_operation: &Self::Operation) -> Option<String> {
+        None
+    }
+
     type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
     type ApplyError = AptRepoApplyError;
     type ApplyStdout = ChildStdout;
@@ -105,7 +121,7 @@ impl OperationType for AptRepo {
                 info!(path = %path, "[apt-repo] ensure keyrings dir");
                 let mut cmd = Command::new("install");
                 cmd.arg("-d").arg("-m").arg("0755").arg(path.as_path());
-                let output = cmd.sudo().output().await?;
+                let output = cmd.sudo().output_checked().await?;
                 Ok((
                     Box::pin(async move {
                         output.status.await?;
@@ -115,33 +131,30 @@ impl OperationType for AptRepo {
                     output.stderr,
                 ))
             }
-            AptRepoOperation::DownloadKey { name, url, path } => {
+            AptRepoOperation::DownloadKey {
+                name,
+                url,
+                sha256,
+                path,
+            } => {
                 info!(name = %name, url = %url, path = %path, "[apt-repo] download key");
 
-                let stage_path = stage_path_for(ctx, name, "asc").await?;
-                // HttpClient::download_file no-ops if the destination already
-                // exists; a stale stage from a prior failed run would silently
-                // be reused, so clear it first.
-                if fs::path_exists(&stage_path).await? {
-                    fs::remove_file(&stage_path).await?;
-                }
-                ctx.http_client().download_file(url, &stage_path).await?;
+                // The cached key stays put for the next run (or machine) that
+                // needs it, so unlike the sources stage it isn't cleaned up.
+                let cached_path = ctx
+                    .artifact_cache()
+                    .fetch(url, sha256.as_deref().map(Checksum::Sha256))
+                    .await?;
 
                 let mut cmd = Command::new("install");
                 cmd.arg("-m")
                     .arg("0644")
-                    .arg(&stage_path)
+                    .arg(&cached_path)
                     .arg(path.as_path());
-                let output = cmd.sudo().output().await?;
-                let cleanup_path = stage_path.clone();
+                let output = cmd.sudo().output_checked().await?;
                 Ok((
                     Box::pin(async move {
-                        let result = output.status.await;
-                        // Best-effort cleanup of the cache stage even if the
-                        // sudo install failed — the stage is in a user-owned
-                        // XDG dir and never references state another op needs.
-                        let _ = tokio::fs::remove_file(&cleanup_path).await;
-                        result?;
+                        output.status.await?;
                         Ok(())
                     }),
                     output.stdout,
@@ -155,15 +168,20 @@ impl OperationType for AptRepo {
             } => {
                 info!(name = %name, path = %path, "[apt-repo] write sources");
 
-                let stage_path = stage_path_for(ctx, name, "sources").await?;
-                fs::write_file_atomic(&stage_path, content.as_bytes()).await?;
+                let stage_path = stage_file(
+                    ctx,
+                    STAGE_SUBDIR,
+                    &format!("{name}.sources"),
+                    content.as_bytes(),
+                )
+                .await?;
 
                 let mut cmd = Command::new("install");
                 cmd.arg("-m")
                     .arg("0644")
                     .arg(&stage_path)
                     .arg(path.as_path());
-                let output = cmd.sudo().output().await?;
+                let output = cmd.sudo().output_checked().await?;
                 let cleanup_path = stage_path.clone();
                 Ok((
                     Box::pin(async move {
@@ -179,13 +197,3 @@ impl OperationType for AptRepo {
         }
     }
 }
-
-async fn stage_path_for(
-    ctx: &Context,
-    name: &str,
-    extension: &str,
-) -> Result<PathBuf, AptRepoApplyError> {
-    let stage_dir = ctx.paths().cache_dir().join(STAGE_SUBDIR);
-    fs::create_dir(&stage_dir).await?;
-    Ok(stage_dir.join(format!("{name}.{extension}")))
-}
@@ -0,0 +1,145 @@
+//! Arch User Repository packages, built and installed by an AUR helper like
+//! paru or yay.
+//!
+//! Unlike pacman these don't run as root: makepkg refuses to, so the helper
+//! runs as the building user through `sudo -u`, and escalates itself for the
+//! `pacman -U` at the end.
+
+use async_trait::async_trait;
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_view::impl_display_render;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    pin::Pin,
+};
+use thiserror::Error;
+use tokio::process::{ChildStderr, ChildStdout};
+use tracing::info;
+
+use crate::OperationType;
+
+#[derive(Debug, Clone)]
+pub enum AurOperation {
+    Install {
+        helper: String,
+        user: String,
+        packages: Vec<String>,
+    },
+}
+
+impl Display for AurOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AurOperation::Install {
+                helper,
+                user,
+                packages,
+            } => write!(
+                f,
+                "Aur::Install(helper = {helper}, user = {user}, packages = [{}])",
+                packages.join(", ")
+            ),
+        }
+    }
+}
+
+impl_display_render!(AurOperation);
+
+#[derive(Error, Debug)]
+pub enum AurApplyError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+}
+
+#[derive(Debug, Clone)]
+pub struct Aur;
+
+#[async_trait]
+impl OperationType for Aur {
+    type Operation = AurOperation;
+
+    // One install per helper and user, as each runs as its own command.
+    fn merge(operations: Vec<Self::Operation>) -> Vec<Self::Operation> {
+        let mut installs: BTreeMap<(String, String), BTreeSet<String>> = BTreeMap::new();
+
+        for operation in operations {
+            match operation {
+                AurOperation::Install {
+                    helper,
+                    user,
+                    packages,
+                } => installs.entry((helper, user)).or_default().extend(packages),
+            }
+        }
+
+        installs
+            .into_iter()
+            .map(|((helper, user), packages)| AurOperation::Install {
+                helper,
+                user,
+                packages: packages.into_iter().collect(),
+            })
+            .collect()
+    }
+
+    fn script(operation: &Self::Operation) -> Option<String> {
+        Some(command(operation).to_shell())
+    }
+
+    type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
+    type ApplyError = AurApplyError;
+    type ApplyStdout = ChildStdout;
+    type ApplyStderr = ChildStderr;
+
+    async fn apply(
+        _ctx: &mut Context,
+        operation: &Self::Operation,
+    ) -> Result<(Self::ApplyOutput, Self::ApplyStdout, Self::ApplyStderr), Self::ApplyError> {
+        match operation {
+            AurOperation::Install {
+                helper,
+                user,
+                packages,
+            } => {
+                info!(
+                    "[aur] install as {user} with {helper}: {}",
+                    packages.join(", ")
+                );
+            }
+        }
+        let output = command(operation).output_checked().await?;
+        Ok((
+            Box::pin(async move {
+                output.status.await?;
+                Ok(())
+            }),
+            output.stdout,
+            output.stderr,
+        ))
+    }
+}
+
+/// The command `operation` runs, shared by [`OperationType::apply`] and
+/// [`OperationType::script`].
+// Note(cc): `--noconfirm` answers pacman's prompts, but a helper set to have
+// PKGBUILDs reviewed before building may still ask for that; configure it
+// not to (`SkipReview` for paru) on machines lusid applies.
+fn command(operation: &AurOperation) -> Command {
+    match operation {
+        AurOperation::Install {
+            helper,
+            user,
+            packages,
+        } => {
+            let mut cmd = Command::new(helper);
+            cmd.arg("-S")
+                .arg("--noconfirm")
+                .arg("--needed")
+                .arg("--")
+                .args(packages);
+            cmd.sudo_as(Some(user.as_str()), None)
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Homebrew packages, on macOS or (as Linuxbrew) on Linux.
+//!
+//! Homebrew refuses to run as root, so unlike apt and pacman these run as the
+//! apply user, who must own the Homebrew prefix.
+
+use async_trait::async_trait;
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_view::impl_display_render;
+use std::{collections::BTreeSet, env, fmt::Display, path::Path, pin::Pin};
+use thiserror::Error;
+use tokio::process::{ChildStderr, ChildStdout};
+use tracing::info;
+
+use crate::OperationType;
+
+#[derive(Debug, Clone)]
+pub enum BrewOperation {
+    InstallFormulae { formulae: Vec<String> },
+    InstallCasks { casks: Vec<String> },
+}
+
+impl Display for BrewOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BrewOperation::InstallFormulae { formulae } => {
+                write!(f, "Brew::InstallFormulae([{}])", formulae.join(", "))
+            }
+            BrewOperation::InstallCasks { casks } => {
+                write!(f, "Brew::InstallCasks([{}])", casks.join(", "))
+            }
+        }
+    }
+}
+
+impl_display_render!(BrewOperation);
+
+#[derive(Error, Debug)]
+pub enum BrewApplyError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+}
+
+#[derive(Debug, Clone)]
+pub struct Brew;
+
+#[async_trait]
+impl OperationType for Brew {
+    type Operation = BrewOperation;
+
+    // One `brew install` for every formula in the epoch, and one for every
+    // cask.
+    fn merge(operations: Vec<Self::Operation>) -> Vec<Self::Operation> {
+        let mut formulae: BTreeSet<String> = BTreeSet::new();
+        let mut casks: BTreeSet<String> = BTreeSet::new();
+        for operation in operations {
+            match operation {
+                BrewOperation::InstallFormulae { formulae: more } => formulae.extend(more),
+                BrewOperation::InstallCasks { casks: more } => casks.extend(more),
+            }
+        }
+
+        let mut operations = Vec::new();
+        if !formulae.is_empty() {
+            operations.push(BrewOperation::InstallFormulae {
+                formulae: formulae.into_iter().collect(),
+            });
+        }
+        if !casks.is_empty() {
+            operations.push(BrewOperation::InstallCasks {
+                casks: casks.into_iter().collect(),
+            });
+        }
+        operations
+    }
+
+    fn script(operation: &Self::Operation) -> Option<String> {
+        Some(command(operation).to_shell())
+    }
+
+    type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
+    type ApplyError = BrewApplyError;
+    type ApplyStdout = ChildStdout;
+    type ApplyStderr = ChildStderr;
+
+    async fn apply(
+        _ctx: &mut Context,
+        operation: &Self::Operation,
+    ) -> Result<(Self::ApplyOutput, Self::ApplyStdout, Self::ApplyStderr), Self::ApplyError> {
+        info!("[brew] {}", operation);
+        let output = command(operation).output_checked().await?;
+        Ok((
+            Box::pin(async move {
+                output.status.await?;
+                Ok(())
+            }),
+            output.stdout,
+            output.stderr,
+        ))
+    }
+}
+
+/// Where Homebrew installs `brew` by default: on Apple silicon, and on Linux.
+/// Intel Macs use `/usr/local/bin`, which is always on `PATH`.
+const DEFAULT_PROGRAMS: &[&str] = &[
+    "/opt/homebrew/bin/brew",
+    "/home/linuxbrew/.linuxbrew/bin/brew",
+];
+
+/// The `brew` to run: the one on `PATH`, else the one at a default prefix.
+/// Homebrew adds itself to `PATH` from the shell's profile, which a
+/// non-interactive shell, like one over ssh, doesn't read.
+pub fn program() -> &'static str {
+    let on_path = env::var_os("PATH")
+        .is_some_and(|path| env::split_paths(&path).any(|dir| dir.join("brew").is_file()));
+    if on_path {
+        return "brew";
+    }
+    DEFAULT_PROGRAMS
+        .iter()
+        .find(|program| Path::new(program).is_file())
+        .unwrap_or(&"brew")
+}
+
+/// The command `operation` runs, shared by [`OperationType::apply`] and
+/// [`OperationType::script`].
+fn command(operation: &BrewOperation) -> Command {
+    let mut cmd = Command::new(program());
+    // Homebrew otherwise updates itself before an install, which is slow and
+    // makes an apply depend on what was pushed to Homebrew that minute.
+    cmd.env("HOMEBREW_NO_AUTO_UPDATE", "1")
+        .env("HOMEBREW_NO_ENV_HINTS", "1")
+        .arg("install");
+    match operation {
+        BrewOperation::InstallFormulae { formulae } => {
+            cmd.arg("--formula").arg("--").args(formulae);
+        }
+        BrewOperation::InstallCasks { casks } => {
+            cmd.arg("--cask").arg("--").args(casks);
+        }
+    }
+    cmd
+}
@@ -50,6 +50,10 @@ impl OperationType for Command {
         operations
     }
 
+    fn script(operation: &Self::Operation) -> Option<String> {
+        run_command(operation).ok().map(|cmd| cmd.to_shell())
+    }
+
     type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
     type ApplyError = CommandApplyError;
     type ApplyStdout = ChildStdout;
@@ -59,16 +63,10 @@ impl OperationType for Command {
         _ctx: &mut Context,
         operation: &Self::Operation,
     ) -> Result<(Self::ApplyOutput, Self::ApplyStdout, Self::ApplyStderr), Self::ApplyError> {
-        let CommandOperation { command, executor } = operation;
-        info!("[command] run: {command}");
+        info!("[command] run: {}", operation.command);
 
-        let mut cmd = match executor {
-            CommandExecutor::Direct => {
-                RunCommand::from_str(command).map_err(CommandApplyError::ParseCommand)
-            }
-            CommandExecutor::Shell => Ok(RunCommand::new_sh(command)),
-        }?;
-        let output = cmd.output().await?;
+        let mut cmd = run_command(operation)?;
+        let output = cmd.output_checked().await?;
         Ok((
             Box::pin(async move {
                 output.status.await?;
@@ -79,3 +77,15 @@ impl OperationType for Command {
         ))
     }
 }
+
+/// The command `operation` runs, shared by [`OperationType::apply`] and
+/// [`OperationType::script`].
+fn run_command(operation: &CommandOperation) -> Result<RunCommand, CommandApplyError> {
+    let CommandOperation { command, executor } = operation;
+    match executor {
+        CommandExecutor::Direct => {
+            RunCommand::from_str(command).map_err(CommandApplyError::ParseCommand)
+        }
+        CommandExecutor::Shell => Ok(RunCommand::new_sh(command)),
+    }
+}
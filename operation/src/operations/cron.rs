@@ -0,0 +1,281 @@
+//! Crontab entries, each managed on its own.
+//!
+//! An entry is a marker comment naming it, then its job line:
+//!
+//! ```text
+//! # lusid: backup
+//! 0 3 * * * /usr/local/bin/backup
+//! ```
+//!
+//! Setting or removing an entry edits just its two lines, so jobs added by
+//! hand or by other tools are left as they are. The edited crontab is
+//! installed with `crontab -`.
+
+use async_trait::async_trait;
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_view::impl_display_render;
+use std::{fmt::Display, pin::Pin};
+use thiserror::Error;
+use tokio::io::AsyncWriteExt;
+use tokio::process::{ChildStderr, ChildStdout};
+use tracing::info;
+
+use crate::{OperationType, Severity};
+
+const MARKER_PREFIX: &str = "# lusid: ";
+
+#[derive(Debug, Clone)]
+pub enum CronOperation {
+    /// Add the entry `name`, or replace its job line, in `user`'s crontab
+    /// (the apply user's when `None`).
+    Set {
+        user: Option<String>,
+        name: String,
+        job: String,
+    },
+    /// Remove the entry `name` from `user`'s crontab.
+    Remove { user: Option<String>, name: String },
+}
+
+impl Display for CronOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CronOperation::Set { user, name, job } => {
+                write!(f, "Cron::Set(name = {name}, ")?;
+                if let Some(user) = user {
+                    write!(f, "user = {user}, ")?;
+                }
+                write!(f, "job = {job})")
+            }
+            CronOperation::Remove { user, name } => {
+                write!(f, "Cron::Remove(name = {name}")?;
+                if let Some(user) = user {
+                    write!(f, ", user = {user}")?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl_display_render!(CronOperation);
+
+#[derive(Error, Debug)]
+pub enum CronApplyError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+
+    #[error("failed to read crontab: {stderr}")]
+    ReadCrontab { stderr: String },
+
+    #[error("failed to write crontab: {0}")]
+    WriteCrontab(#[source] std::io::Error),
+}
+
+#[derive(Debug, Clone)]
+pub struct Cron;
+
+#[async_trait]
+impl OperationType for Cron {
+    type Operation = CronOperation;
+
+    // Note(cc): merge is a no-op. Each operation reads, edits and installs the
+    // crontab, so several entries for one user cost a round of `crontab` each.
+    // They apply one after another, so edits don't clobber each other; folding
+    // a user's entries into one edit is worth it if plans grow many jobs.
+    fn merge(operations: Vec<Self::Operation>) -> Vec<Self::Operation> {
+        operations
+    }
+
+    // The crontab is edited in-process between `crontab -l` and `crontab -`.
+    fn script(_operation: &Self::Operation) -> Option<String> {
+        None
+    }
+
+    fn severity(operation: &Self::Operation) -> Severity {
+        match operation {
+            CronOperation::Remove { .. } => Severity::Disruptive,
+            CronOperation::Set { .. } => Severity::Safe,
+        }
+    }
+
+    type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
+    type ApplyError = CronApplyError;
+    type ApplyStdout = ChildStdout;
+    type ApplyStderr = ChildStderr;
+
+    async fn apply(
+        _ctx: &mut Context,
+        operation: &Self::Operation,
+    ) -> Result<(Self::ApplyOutput, Self::ApplyStdout, Self::ApplyStderr), Self::ApplyError> {
+        let (user, edited) = match operation {
+            CronOperation::Set { user, name, job } => {
+                info!(name = %name, "[cron] set");
+                let crontab = read_crontab(user.as_deref()).await?;
+                (user, set_entry(&crontab, name, job))
+            }
+            CronOperation::Remove { user, name } => {
+                info!(name = %name, "[cron] remove");
+                let crontab = read_crontab(user.as_deref()).await?;
+                (user, remove_entry(&crontab, name))
+            }
+        };
+
+        let (mut stdin, output) = crontab_command(user.as_deref(), "-")
+            .output_with_stdin_checked()
+            .await?;
+        stdin
+            .write_all(edited.as_bytes())
+            .await
+            .map_err(CronApplyError::WriteCrontab)?;
+        drop(stdin);
+        Ok((
+            Box::pin(async move {
+                output.status.await?;
+                Ok(())
+            }),
+            output.stdout,
+            output.stderr,
+        ))
+    }
+}
+
+/// `crontab <arg>` for `user`, through `sudo -u` when it's someone else's.
+fn crontab_command(user: Option<&str>, arg: &str) -> Command {
+    let mut cmd = Command::new("crontab");
+    cmd.arg(arg);
+    match user {
+        Some(user) => cmd.sudo_as(Some(user), None),
+        None => cmd,
+    }
+}
+
+/// The crontab of `user`, empty if they don't have one yet.
+pub async fn read_crontab(user: Option<&str>) -> Result<String, CronApplyError> {
+    let outcome = crontab_command(user, "-l").outcome().await?;
+    let stderr = String::from_utf8_lossy(&outcome.stderr);
+    if !outcome.status.success() {
+        // cronie, vixie-cron and busybox all say "no crontab for <user>".
+        if stderr.contains("no crontab for") {
+            return Ok(String::new());
+        }
+        return Err(CronApplyError::ReadCrontab {
+            stderr: stderr.trim().to_owned(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&outcome.stdout).into_owned())
+}
+
+/// The job line of entry `name` in `crontab`, if it has one.
+pub fn find_entry<'a>(crontab: &'a str, name: &str) -> Option<&'a str> {
+    let mut lines = crontab.lines();
+    while let Some(line) = lines.next() {
+        if is_marker(line, name) {
+            return lines.next();
+        }
+    }
+    None
+}
+
+/// `crontab` with entry `name` set to `job`, replacing it in place if it's
+/// there and appending it otherwise.
+pub fn set_entry(crontab: &str, name: &str, job: &str) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    let mut found = false;
+    let mut skip_job = false;
+    for line in crontab.lines() {
+        if skip_job {
+            skip_job = false;
+            continue;
+        }
+        lines.push(line);
+        if is_marker(line, name) && !found {
+            found = true;
+            skip_job = true;
+            lines.push(job);
+        }
+    }
+    let marker = format!("{MARKER_PREFIX}{name}");
+    if !found {
+        lines.push(&marker);
+        lines.push(job);
+    }
+    join_lines(&lines)
+}
+
+/// `crontab` without entry `name`.
+pub fn remove_entry(crontab: &str, name: &str) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    let mut skip_job = false;
+    for line in crontab.lines() {
+        if skip_job {
+            skip_job = false;
+            continue;
+        }
+        if is_marker(line, name) {
+            skip_job = true;
+            continue;
+        }
+        lines.push(line);
+    }
+    join_lines(&lines)
+}
+
+fn is_marker(line: &str, name: &str) -> bool {
+    line.strip_prefix(MARKER_PREFIX) == Some(name)
+}
+
+/// Lines as a crontab, which must end in a newline for cron to read the last
+/// job.
+fn join_lines(lines: &[&str]) -> String {
+    let mut crontab = lines.join("\n");
+    if !crontab.is_empty() {
+        crontab.push('\n');
+    }
+    crontab
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CRONTAB: &str = "MAILTO=me@example.com\n\
+        # lusid: backup\n\
+        0 3 * * * /usr/local/bin/backup\n\
+        */5 * * * * /home/me/bin/by-hand\n";
+
+    #[test]
+    fn entries_are_found_by_marker() {
+        assert_eq!(
+            find_entry(CRONTAB, "backup"),
+            Some("0 3 * * * /usr/local/bin/backup")
+        );
+        assert_eq!(find_entry(CRONTAB, "missing"), None);
+        assert_eq!(find_entry("", "backup"), None);
+    }
+
+    #[test]
+    fn set_replaces_only_its_entry() {
+        assert_eq!(
+            set_entry(CRONTAB, "backup", "0 4 * * * /usr/local/bin/backup"),
+            "MAILTO=me@example.com\n\
+             # lusid: backup\n\
+             0 4 * * * /usr/local/bin/backup\n\
+             */5 * * * * /home/me/bin/by-hand\n"
+        );
+        assert_eq!(
+            set_entry("", "rotate", "@daily /usr/sbin/logrotate"),
+            "# lusid: rotate\n@daily /usr/sbin/logrotate\n"
+        );
+    }
+
+    #[test]
+    fn remove_leaves_other_lines() {
+        assert_eq!(
+            remove_entry(CRONTAB, "backup"),
+            "MAILTO=me@example.com\n*/5 * * * * /home/me/bin/by-hand\n"
+        );
+        assert_eq!(remove_entry(CRONTAB, "missing"), CRONTAB);
+    }
+}
@@ -6,8 +6,8 @@ use std::{fmt::Display, pin::Pin};
 use tokio::io::AsyncRead;
 use tracing::info;
 
-use crate::OperationType;
 use crate::operations::file::{FileGroup, FileMode, FilePath, FileUser};
+use crate::{OperationType, Severity};
 
 #[derive(Debug, Clone)]
 pub enum DirectoryOperation {
@@ -92,6 +92,19 @@ impl OperationType for Directory {
         operations
     }
 
+    // Directories are created, copied and changed in-process, not by a command.
+    fn script(_operation: &Self::Operation) -> Option<String> {
+        None
+    }
+
+    // Removal is recursive, so it takes whatever was in the directory.
+    fn severity(operation: &Self::Operation) -> Severity {
+        match operation {
+            DirectoryOperation::Remove { .. } => Severity::Destructive,
+            _ => Severity::Safe,
+        }
+    }
+
     type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
     type ApplyError = FsError;
 
@@ -0,0 +1,156 @@
+use async_trait::async_trait;
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::{Context, DownloadLimits};
+use lusid_view::impl_display_render;
+use std::{collections::BTreeSet, fmt::Display, pin::Pin};
+use thiserror::Error;
+use tokio::process::{ChildStderr, ChildStdout};
+use tracing::info;
+
+use crate::{OperationType, Severity};
+
+#[derive(Debug, Clone)]
+pub enum DnfOperation {
+    Install { packages: Vec<String> },
+    Remove { packages: Vec<String> },
+}
+
+impl Display for DnfOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DnfOperation::Install { packages } => {
+                write!(f, "Dnf::Install(packages = [{}])", packages.join(", "))
+            }
+            DnfOperation::Remove { packages } => {
+                write!(f, "Dnf::Remove(packages = [{}])", packages.join(", "))
+            }
+        }
+    }
+}
+
+impl_display_render!(DnfOperation);
+
+#[derive(Error, Debug)]
+pub enum DnfApplyError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+}
+
+#[derive(Debug, Clone)]
+pub struct Dnf;
+
+#[async_trait]
+impl OperationType for Dnf {
+    type Operation = DnfOperation;
+
+    // Removals go first, so swapping one package for another that conflicts
+    // with it (`iptables-services` for `firewalld`) works in one epoch. dnf
+    // refreshes stale metadata itself, so there's no `Update` to merge.
+    fn merge(operations: Vec<Self::Operation>) -> Vec<Self::Operation> {
+        let mut install: BTreeSet<String> = BTreeSet::new();
+        let mut remove: BTreeSet<String> = BTreeSet::new();
+
+        for operation in operations {
+            match operation {
+                DnfOperation::Install { packages } => install.extend(packages),
+                DnfOperation::Remove { packages } => remove.extend(packages),
+            }
+        }
+
+        let mut operations = Vec::new();
+        if !remove.is_empty() {
+            operations.push(DnfOperation::Remove {
+                packages: remove.into_iter().collect(),
+            });
+        }
+        if !install.is_empty() {
+            operations.push(DnfOperation::Install {
+                packages: install.into_iter().collect(),
+            });
+        }
+        operations
+    }
+
+    fn script(operation: &Self::Operation) -> Option<String> {
+        Some(command(operation, DownloadLimits::default()).to_shell())
+    }
+
+    // `dnf remove` also removes whatever depends on the package, and keeps
+    // edited config files only as `.rpmsave`s.
+    fn severity(operation: &Self::Operation) -> Severity {
+        match operation {
+            DnfOperation::Install { .. } => Severity::Safe,
+            DnfOperation::Remove { .. } => Severity::Destructive,
+        }
+    }
+
+    type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
+    type ApplyError = DnfApplyError;
+    type ApplyStdout = ChildStdout;
+    type ApplyStderr = ChildStderr;
+
+    async fn apply(
+        ctx: &mut Context,
+        operation: &Self::Operation,
+    ) -> Result<(Self::ApplyOutput, Self::ApplyStdout, Self::ApplyStderr), Self::ApplyError> {
+        match operation {
+            DnfOperation::Install { packages } => {
+                info!("[dnf] install: {}", packages.join(", "));
+            }
+            DnfOperation::Remove { packages } => {
+                info!("[dnf] remove: {}", packages.join(", "));
+            }
+        }
+        let output = command(operation, ctx.download_limits())
+            .output_checked()
+            .await?;
+        Ok((
+            Box::pin(async move {
+                output.status.await?;
+                Ok(())
+            }),
+            output.stdout,
+            output.stderr,
+        ))
+    }
+}
+
+/// The command `operation` runs, shared by [`OperationType::apply`] and
+/// [`OperationType::script`].
+// Note(cc): `dnf` only. RHEL 7's `yum` takes the same arguments but is past
+// its end of life, so it isn't worth a fallback.
+fn command(operation: &DnfOperation, limits: DownloadLimits) -> Command {
+    let mut cmd = Command::new("dnf");
+    cmd.arg("--assumeyes").arg("--color=never");
+    for option in download_options(limits) {
+        cmd.arg(format!("--setopt={option}"));
+    }
+    match operation {
+        DnfOperation::Install { packages } => {
+            cmd.arg("install").arg("--").args(packages);
+            cmd.sudo()
+        }
+        DnfOperation::Remove { packages } => {
+            cmd.arg("remove").arg("--").args(packages);
+            cmd.sudo()
+        }
+    }
+}
+
+/// dnf options to keep its own downloads within `limits`. Unlike apt, dnf
+/// can cap its parallel downloads at a number; `throttle` is across all of
+/// them.
+fn download_options(limits: DownloadLimits) -> Vec<String> {
+    let mut options = Vec::new();
+    if let Some(max_parallel) = limits.max_parallel {
+        // dnf refuses anything outside 1..=20.
+        options.push(format!(
+            "max_parallel_downloads={}",
+            max_parallel.clamp(1, 20)
+        ));
+    }
+    if let Some(max_kib_per_sec) = limits.max_kib_per_sec {
+        options.push(format!("throttle={max_kib_per_sec}k"));
+    }
+    options
+}
@@ -4,16 +4,22 @@ use lusid_ctx::Context;
 use lusid_fs::{self as fs, FsError};
 use lusid_view::impl_display_render;
 use secrecy::ExposeSecret;
+use serde::{Serialize, Serializer};
 use std::{
     fmt::{Debug, Display},
     path::Path,
     pin::Pin,
+    sync::Arc,
 };
 use thiserror::Error;
 use tokio::io::AsyncRead;
 use tracing::info;
 
-use crate::OperationType;
+use crate::{OperationType, Severity};
+
+/// Most bytes an inline write may have and still be batched into a
+/// [`FileOperation::WriteMany`]; bigger writes already dwarf the overhead.
+const BATCH_MAX_BYTES: usize = 64 * 1024;
 
 /// Errors from applying a [`FileOperation`]: filesystem I/O or a missing
 /// secret lookup during [`FileSource::Secret`] resolution.
@@ -33,7 +39,9 @@ pub enum FileApplyError {
 
 #[derive(Debug, Clone)]
 pub enum FileSource {
-    Contents(Vec<u8>),
+    /// Inline contents. Shared rather than owned, so the clones as a write
+    /// moves through the change and operation trees don't copy the bytes.
+    Contents(Arc<[u8]>),
 
     /// Copy the file at this host path into `path` atomically.
     Path(FilePath),
@@ -44,19 +52,93 @@ pub enum FileSource {
     Secret(String),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Why a string was rejected as a [`FilePath`].
+#[derive(Debug, Clone, PartialEq, Eq, Error, DisplaydocDisplay)]
+pub enum FilePathError {
+    /// path {value:?} must be absolute
+    NotAbsolute { value: String },
+
+    /// path {value:?} contains a NUL byte
+    Nul { value: String },
+}
+
+/// A path on the target machine. Paths built with [`FilePath::parse`] (and
+/// [`join`](FilePath::join), [`parent`](FilePath::parent)) are absolute,
+/// NUL-free, and lexically normalized: no `.` or `..` segments, no repeated
+/// or trailing slashes.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 pub struct FilePath(String);
 
 impl FilePath {
+    /// Wrap `value` as-is, without validation. For paths that are already
+    /// known good (tempdirs, host paths resolved by the params layer); plan
+    /// input goes through [`FilePath::parse`].
     pub fn new(value: impl Into<String>) -> Self {
         Self(value.into())
     }
 
+    /// Validate and normalize an absolute path.
+    ///
+    /// Note(cc): `..` is resolved lexically, so `/a/link/..` is `/a` even when
+    /// `link` is a symlink the kernel would follow elsewhere. That matches
+    /// what a plan author reads, and `..` above `/` stays at `/` as in POSIX.
+    pub fn parse(value: impl Into<String>) -> Result<Self, FilePathError> {
+        let value = value.into();
+        if value.contains('\0') {
+            return Err(FilePathError::Nul { value });
+        }
+        if !value.starts_with('/') {
+            return Err(FilePathError::NotAbsolute { value });
+        }
+        Ok(Self(normalize(&value)))
+    }
+
+    /// `segment` appended below this path, normalized. A leading `/` on
+    /// `segment` doesn't replace the path (unlike [`Path::join`]), but `..`
+    /// segments can still climb out of it.
+    pub fn join(&self, segment: &str) -> Result<Self, FilePathError> {
+        if segment.contains('\0') {
+            return Err(FilePathError::Nul {
+                value: segment.to_string(),
+            });
+        }
+        Ok(Self(normalize(&format!("{}/{segment}", self.0))))
+    }
+
+    /// The directory containing this path, or `None` for `/`.
+    pub fn parent(&self) -> Option<Self> {
+        let normalized = normalize(&self.0);
+        if normalized == "/" {
+            return None;
+        }
+        match normalized.rfind('/') {
+            Some(0) => Some(Self("/".to_string())),
+            Some(index) => Some(Self(normalized[..index].to_string())),
+            None => None,
+        }
+    }
+
     pub fn as_path(&self) -> &Path {
         Path::new(&self.0)
     }
 }
 
+/// Lexically normalize an absolute path: drop empty and `.` segments, and
+/// resolve `..` against the segments before it.
+fn normalize(value: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in value.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    format!("/{}", segments.join("/"))
+}
+
 impl Display for FilePath {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.0)
@@ -82,7 +164,14 @@ impl Display for FileMode {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// As an octal string, like `"0644"`, the way modes are written.
+impl Serialize for FileMode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("{:04o}", self.0))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct FileUser(String);
 
 impl FileUser {
@@ -101,7 +190,7 @@ impl Display for FileUser {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct FileGroup(String);
 
 impl FileGroup {
@@ -126,6 +215,11 @@ pub enum FileOperation {
         path: FilePath,
         source: FileSource,
     },
+    /// Several small inline writes, batched as one operation by
+    /// [`File::batch`]. Each file is written atomically on its own.
+    WriteMany {
+        files: Vec<(FilePath, Arc<[u8]>)>,
+    },
     /// Atomically create (or replace) a symlink at `path` targeting `source`.
     /// Emitted by `@core/file state: "linked"`.
     CreateSymlink {
@@ -165,6 +259,15 @@ impl Display for FileOperation {
                     write!(f, "File::Write(path = {}, source = Secret({}))", path, name)
                 }
             },
+            FileOperation::WriteMany { files } => {
+                let paths: Vec<String> = files.iter().map(|(path, _)| path.to_string()).collect();
+                let bytes: usize = files.iter().map(|(_, contents)| contents.len()).sum();
+                write!(
+                    f,
+                    "File::WriteMany(paths = [{}], {bytes} bytes)",
+                    paths.join(", ")
+                )
+            }
             FileOperation::CreateSymlink { source, path } => write!(
                 f,
                 "File::CreateSymlink(source = {}, path = {})",
@@ -193,9 +296,9 @@ impl_display_render!(FileOperation);
 /// - `Copy` covers a path-sourced copy.
 ///
 /// Resolved up-front so the inner async block doesn't borrow `ctx` (and so
-/// secret plaintext lives only as long as the `Vec<u8>` it's copied into).
+/// secret plaintext lives only as long as the buffer it's copied into).
 enum WriteSource {
-    Bytes(Vec<u8>),
+    Bytes(Arc<[u8]>),
     Copy(FilePath),
 }
 
@@ -210,6 +313,47 @@ impl OperationType for File {
         operations
     }
 
+    // Small inline writes, e.g. a dotfiles plan's config files, fold into one
+    // `WriteMany`. Copies and secrets stay separate: a copy is bounded by the
+    // source file rather than the plan, and a secret is looked up at apply.
+    fn batch(operations: Vec<Self::Operation>) -> Vec<Self::Operation> {
+        let mut batched: Vec<Self::Operation> = Vec::with_capacity(operations.len());
+        let mut files: Vec<(FilePath, Arc<[u8]>)> = Vec::new();
+        for operation in operations {
+            match operation {
+                FileOperation::Write {
+                    path,
+                    source: FileSource::Contents(contents),
+                } if contents.len() <= BATCH_MAX_BYTES => files.push((path, contents)),
+                operation => batched.push(operation),
+            }
+        }
+        match files.len() {
+            0 => {}
+            1 => {
+                let (path, contents) = files.remove(0);
+                batched.push(FileOperation::Write {
+                    path,
+                    source: FileSource::Contents(contents),
+                });
+            }
+            _ => batched.push(FileOperation::WriteMany { files }),
+        }
+        batched
+    }
+
+    // Contents are written and metadata changed in-process, not by a command.
+    fn script(_operation: &Self::Operation) -> Option<String> {
+        None
+    }
+
+    fn severity(operation: &Self::Operation) -> Severity {
+        match operation {
+            FileOperation::Remove { .. } => Severity::Destructive,
+            _ => Severity::Safe,
+        }
+    }
+
     type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
     type ApplyError = FileApplyError;
 
@@ -240,7 +384,7 @@ impl OperationType for File {
                             .secrets()
                             .get(&name)
                             .ok_or_else(|| FileApplyError::MissingSecret { name: name.clone() })?;
-                        WriteSource::Bytes(secret.expose_secret().as_bytes().to_vec())
+                        WriteSource::Bytes(secret.expose_secret().as_bytes().into())
                     }
                 };
                 Ok((
@@ -259,6 +403,20 @@ impl OperationType for File {
                     stderr,
                 ))
             }
+            FileOperation::WriteMany { files } => {
+                info!("[file] write contents of {} files", files.len());
+                Ok((
+                    Box::pin(async move {
+                        for (path, contents) in files {
+                            info!("[file] write contents: {} ({} bytes)", path, contents.len());
+                            fs::write_file_atomic(path.as_path(), &contents).await?;
+                        }
+                        Ok(())
+                    }),
+                    stdout,
+                    stderr,
+                ))
+            }
             FileOperation::CreateSymlink { source, path } => {
                 info!("[file] create symlink: {} -> {}", path, source);
                 Ok((
@@ -314,3 +472,103 @@ impl OperationType for File {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_normalizes() {
+        let path = |value: &str| FilePath::parse(value).map(|path| path.to_string());
+        assert_eq!(
+            path("/etc/nginx/nginx.conf"),
+            Ok("/etc/nginx/nginx.conf".into())
+        );
+        assert_eq!(
+            path("/etc//nginx/./conf.d/../nginx.conf/"),
+            Ok("/etc/nginx/nginx.conf".into())
+        );
+        assert_eq!(path("/../etc"), Ok("/etc".into()));
+        assert_eq!(path("/"), Ok("/".into()));
+        assert!(matches!(
+            path("etc/nginx"),
+            Err(FilePathError::NotAbsolute { .. })
+        ));
+        assert!(matches!(
+            path("/etc/ng\0inx"),
+            Err(FilePathError::Nul { .. })
+        ));
+    }
+
+    #[test]
+    fn join_and_parent() {
+        let etc = FilePath::parse("/etc").unwrap();
+        assert_eq!(
+            etc.join("nginx/nginx.conf").unwrap().to_string(),
+            "/etc/nginx/nginx.conf"
+        );
+        assert_eq!(etc.join("/nginx").unwrap().to_string(), "/etc/nginx");
+        assert_eq!(etc.join("../var").unwrap().to_string(), "/var");
+        assert_eq!(etc.parent().map(|path| path.to_string()), Some("/".into()));
+        assert_eq!(FilePath::parse("/").unwrap().parent(), None);
+        assert_eq!(
+            FilePath::parse("/etc/nginx/nginx.conf")
+                .unwrap()
+                .parent()
+                .map(|path| path.to_string()),
+            Some("/etc/nginx".into())
+        );
+    }
+
+    #[test]
+    fn small_inline_writes_are_batched() {
+        let write = |path: &str, contents: Vec<u8>| FileOperation::Write {
+            path: FilePath::new(path),
+            source: FileSource::Contents(contents.into()),
+        };
+        let batched: Vec<String> = File::batch(vec![
+            write("/home/me/.gitconfig", b"[user]\n".to_vec()),
+            FileOperation::Remove {
+                path: FilePath::new("/home/me/.old"),
+            },
+            write("/home/me/.bashrc", b"set -o vi\n".to_vec()),
+            write("/home/me/big.bin", vec![0; BATCH_MAX_BYTES + 1]),
+            FileOperation::Write {
+                path: FilePath::new("/home/me/.token"),
+                source: FileSource::Secret("token".into()),
+            },
+        ])
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+
+        assert_eq!(
+            batched,
+            [
+                "File::Remove(path = /home/me/.old)".to_string(),
+                format!(
+                    "File::Write(path = /home/me/big.bin, source = Contents({} bytes))",
+                    BATCH_MAX_BYTES + 1
+                ),
+                "File::Write(path = /home/me/.token, source = Secret(token))".to_string(),
+                "File::WriteMany(paths = [/home/me/.gitconfig, /home/me/.bashrc], 17 bytes)"
+                    .to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_lone_write_is_not_batched() {
+        let write = FileOperation::Write {
+            path: FilePath::new("/home/me/.gitconfig"),
+            source: FileSource::Contents(b"[user]\n".as_slice().into()),
+        };
+        assert_eq!(
+            File::batch(vec![write.clone()])
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [write.to_string()]
+        );
+    }
+}
@@ -0,0 +1,379 @@
+//! Firewall rules, managed with `ufw`.
+//!
+//! A rule is added or deleted on its own, so rules added by hand or by other
+//! tools are left as they are. The current rules are read with `ufw show
+//! added`, which lists them as the `ufw` commands that would add them, in
+//! order, whether or not the firewall is enabled.
+//!
+//! ufw applies the first rule a packet matches, and appends each rule it
+//! adds, so rules added in one apply keep the order their operations run in.
+//
+// Note(cc): a rule added to a ruleset that already has rules lands after all
+// of them, not where the plan lists it. Placing it with `ufw insert <n>`
+// needs its neighbours' positions, which shift as each rule goes in.
+//
+// TODO(cc): an nftables backend, for hosts without ufw. `nft -j list
+// ruleset` gives the ruleset as JSON, but rules there are matched by handle,
+// so deleting one means finding it first.
+
+use async_trait::async_trait;
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_view::impl_display_render;
+use serde::Serialize;
+use std::{fmt::Display, pin::Pin};
+use thiserror::Error;
+use tokio::process::{ChildStderr, ChildStdout};
+use tracing::info;
+
+use crate::{OperationType, Severity};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FirewallAction {
+    Allow,
+    Deny,
+    /// Deny, telling the sender so.
+    Reject,
+    /// Allow, unless the address has connected 6 or more times in 30
+    /// seconds.
+    Limit,
+}
+
+impl FirewallAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FirewallAction::Allow => "allow",
+            FirewallAction::Deny => "deny",
+            FirewallAction::Reject => "reject",
+            FirewallAction::Limit => "limit",
+        }
+    }
+
+    fn parse(action: &str) -> Option<Self> {
+        match action {
+            "allow" => Some(FirewallAction::Allow),
+            "deny" => Some(FirewallAction::Deny),
+            "reject" => Some(FirewallAction::Reject),
+            "limit" => Some(FirewallAction::Limit),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FirewallProtocol {
+    Tcp,
+    Udp,
+}
+
+impl FirewallProtocol {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FirewallProtocol::Tcp => "tcp",
+            FirewallProtocol::Udp => "udp",
+        }
+    }
+
+    fn parse(protocol: &str) -> Option<Self> {
+        match protocol {
+            "tcp" => Some(FirewallProtocol::Tcp),
+            "udp" => Some(FirewallProtocol::Udp),
+            _ => None,
+        }
+    }
+}
+
+/// An incoming rule: what to do with traffic to `port` over `protocol` from
+/// `from`. `None` matches any.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FirewallRule {
+    pub action: FirewallAction,
+    /// A port, or a `first:last` range.
+    pub port: Option<String>,
+    pub protocol: Option<FirewallProtocol>,
+    /// Source address or CIDR block.
+    pub from: Option<String>,
+}
+
+impl FirewallRule {
+    /// The `ufw` arguments adding this rule, in the form `ufw show added`
+    /// lists it.
+    pub fn args(&self) -> Vec<String> {
+        let Self {
+            action,
+            port,
+            protocol,
+            from,
+        } = self;
+        let mut args = vec![action.as_str().to_owned()];
+        match (port, from) {
+            (Some(port), None) => {
+                args.push(match protocol {
+                    Some(protocol) => format!("{port}/{}", protocol.as_str()),
+                    None => port.clone(),
+                });
+            }
+            (port, from) => {
+                args.extend([
+                    "from".to_owned(),
+                    from.as_deref().unwrap_or("any").to_owned(),
+                ]);
+                if let Some(port) = port {
+                    args.extend(["to", "any", "port", port].map(str::to_owned));
+                }
+                if let Some(protocol) = protocol {
+                    args.extend(["proto".to_owned(), protocol.as_str().to_owned()]);
+                }
+            }
+        }
+        args
+    }
+}
+
+impl Display for FirewallRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.args().join(" "))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FirewallOperation {
+    Add { rule: FirewallRule },
+    Delete { rule: FirewallRule },
+}
+
+impl Display for FirewallOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FirewallOperation::Add { rule } => write!(f, "Firewall::Add({rule})"),
+            FirewallOperation::Delete { rule } => write!(f, "Firewall::Delete({rule})"),
+        }
+    }
+}
+
+impl_display_render!(FirewallOperation);
+
+#[derive(Error, Debug)]
+pub enum FirewallApplyError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+}
+
+#[derive(Debug, Clone)]
+pub struct Firewall;
+
+#[async_trait]
+impl OperationType for Firewall {
+    type Operation = FirewallOperation;
+
+    // Note(cc): merge is a no-op, and must stay order-preserving: ufw takes
+    // one rule per call, and the order rules are added in is the order they
+    // match in.
+    fn merge(operations: Vec<Self::Operation>) -> Vec<Self::Operation> {
+        operations
+    }
+
+    fn script(operation: &Self::Operation) -> Option<String> {
+        Some(command(operation).to_shell())
+    }
+
+    // Any rule but a new allow changes what traffic gets through, which can
+    // cut off a running service, or the SSH session applying the plan.
+    fn severity(operation: &Self::Operation) -> Severity {
+        match operation {
+            FirewallOperation::Add { rule } => match rule.action {
+                FirewallAction::Allow | FirewallAction::Limit => Severity::Safe,
+                FirewallAction::Deny | FirewallAction::Reject => Severity::Disruptive,
+            },
+            FirewallOperation::Delete { .. } => Severity::Disruptive,
+        }
+    }
+
+    type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
+    type ApplyError = FirewallApplyError;
+    type ApplyStdout = ChildStdout;
+    type ApplyStderr = ChildStderr;
+
+    async fn apply(
+        _ctx: &mut Context,
+        operation: &Self::Operation,
+    ) -> Result<(Self::ApplyOutput, Self::ApplyStdout, Self::ApplyStderr), Self::ApplyError> {
+        info!("[firewall] {}", operation);
+        let output = command(operation).output_checked().await?;
+        Ok((
+            Box::pin(async move {
+                output.status.await?;
+                Ok(())
+            }),
+            output.stdout,
+            output.stderr,
+        ))
+    }
+}
+
+/// The `ufw` command `operation` runs, shared by [`OperationType::apply`] and
+/// [`OperationType::script`].
+fn command(operation: &FirewallOperation) -> Command {
+    let mut cmd = Command::new("ufw");
+    match operation {
+        FirewallOperation::Add { rule } => {
+            cmd.args(rule.args());
+        }
+        FirewallOperation::Delete { rule } => {
+            cmd.arg("delete").args(rule.args());
+        }
+    }
+    cmd.sudo()
+}
+
+/// The rules ufw has, in order, as listed by `ufw show added`.
+pub async fn read_rules() -> Result<Vec<FirewallRule>, FirewallApplyError> {
+    let mut cmd = Command::new("ufw");
+    cmd.args(["show", "added"]);
+    let stdout = cmd.sudo().run().await?;
+    Ok(parse_added(&String::from_utf8_lossy(&stdout)))
+}
+
+/// Parse `ufw show added` output. Rules a [`FirewallRule`] can't express,
+/// like outgoing or routed rules or ones naming an interface, are skipped:
+/// no plan rule can match them.
+pub fn parse_added(output: &str) -> Vec<FirewallRule> {
+    output
+        .lines()
+        .filter_map(|line| parse_rule(line.trim()))
+        .collect()
+}
+
+fn parse_rule(line: &str) -> Option<FirewallRule> {
+    let line = line.strip_prefix("ufw ")?;
+    // A rule's comment doesn't change what it matches.
+    let line = match line.find(" comment ") {
+        Some(index) => &line[..index],
+        None => line,
+    };
+    let mut tokens = line.split_whitespace();
+    let action = FirewallAction::parse(tokens.next()?)?;
+    let mut next = tokens.next()?;
+    if next == "in" {
+        next = tokens.next()?;
+    }
+
+    if !matches!(next, "from" | "to" | "proto") {
+        // The short form, `<port>[/<protocol>]`.
+        if tokens.next().is_some() {
+            return None;
+        }
+        let (port, protocol) = match next.split_once('/') {
+            Some((port, protocol)) => (port, Some(FirewallProtocol::parse(protocol)?)),
+            None => (next, None),
+        };
+        return Some(FirewallRule {
+            action,
+            port: Some(port.to_owned()),
+            protocol,
+            from: None,
+        });
+    }
+
+    let mut rule = FirewallRule {
+        action,
+        port: None,
+        protocol: None,
+        from: None,
+    };
+    // `port` names the source port after `from`, and the destination port
+    // after `to`; only the latter is expressible.
+    let mut after_to = false;
+    let mut key = Some(next);
+    while let Some(name) = key {
+        let value = tokens.next()?;
+        match name {
+            "from" => {
+                after_to = false;
+                rule.from = (value != "any").then(|| value.to_owned());
+            }
+            "to" if value == "any" => after_to = true,
+            "port" if after_to => rule.port = Some(value.to_owned()),
+            "proto" => rule.protocol = Some(FirewallProtocol::parse(value)?),
+            _ => return None,
+        }
+        key = tokens.next();
+    }
+    Some(rule)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(
+        action: FirewallAction,
+        port: Option<&str>,
+        protocol: Option<FirewallProtocol>,
+        from: Option<&str>,
+    ) -> FirewallRule {
+        FirewallRule {
+            action,
+            port: port.map(str::to_owned),
+            protocol,
+            from: from.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn parses_show_added() {
+        let output = "Added user rules (see 'ufw status' for running firewall):\n\
+                      ufw allow 22/tcp\n\
+                      ufw limit 2222\n\
+                      ufw allow from 10.0.0.0/8 to any port 5432 proto tcp comment 'postgres'\n\
+                      ufw deny from 203.0.113.4\n\
+                      ufw allow out 53/udp\n\
+                      ufw allow in on eth0 to any port 80\n\
+                      ufw allow from 10.0.0.1 port 1234\n";
+        assert_eq!(
+            parse_added(output),
+            vec![
+                rule(
+                    FirewallAction::Allow,
+                    Some("22"),
+                    Some(FirewallProtocol::Tcp),
+                    None
+                ),
+                rule(FirewallAction::Limit, Some("2222"), None, None),
+                rule(
+                    FirewallAction::Allow,
+                    Some("5432"),
+                    Some(FirewallProtocol::Tcp),
+                    Some("10.0.0.0/8")
+                ),
+                rule(FirewallAction::Deny, None, None, Some("203.0.113.4")),
+            ]
+        );
+        assert!(parse_added("(None)\n").is_empty());
+    }
+
+    #[test]
+    fn args_round_trip() {
+        let rules = [
+            rule(
+                FirewallAction::Allow,
+                Some("8000:8100"),
+                Some(FirewallProtocol::Udp),
+                None,
+            ),
+            rule(
+                FirewallAction::Reject,
+                Some("25"),
+                None,
+                Some("192.0.2.0/24"),
+            ),
+            rule(FirewallAction::Deny, None, None, Some("203.0.113.4")),
+        ];
+        for rule in rules {
+            let line = format!("ufw {rule}");
+            assert_eq!(parse_rule(&line), Some(rule), "{line}");
+        }
+    }
+}
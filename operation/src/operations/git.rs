@@ -2,55 +2,189 @@ use async_trait::async_trait;
 use lusid_cmd::{Command, CommandError};
 use lusid_ctx::Context;
 use lusid_view::impl_display_render;
+use serde::Serialize;
 use std::{fmt::Display, pin::Pin};
 use thiserror::Error;
 use tokio::process::{ChildStderr, ChildStdout};
 use tracing::info;
 
-use crate::OperationType;
+use crate::{OperationType, Progress, ProgressParser};
 
 use crate::operations::file::FilePath;
 
+/// The user and/or group git runs as. When either is set, git commands are
+/// wrapped in `sudo -u` / `sudo -g`, so a clone into a user's home is owned by
+/// that user (and later fetches and pulls don't trip git's `safe.directory`
+/// ownership check). When neither is set, git runs as the apply user.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GitOwner {
+    pub user: Option<String>,
+    pub group: Option<String>,
+}
+
+impl GitOwner {
+    pub fn is_set(&self) -> bool {
+        self.user.is_some() || self.group.is_some()
+    }
+
+    /// Wrap a fully-built git command to run as this owner.
+    pub fn wrap(&self, cmd: Command) -> Command {
+        if self.is_set() {
+            cmd.sudo_as(self.user.as_deref(), self.group.as_deref())
+        } else {
+            cmd
+        }
+    }
+}
+
+impl Display for GitOwner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(user) = &self.user {
+            write!(f, "{user}")?;
+        }
+        if let Some(group) = &self.group {
+            write!(f, ":{group}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Which config file `git config` reads and writes: a user's `~/.gitconfig`
+/// (`--global`), or the machine's `/etc/gitconfig` (`--system`).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GitConfigScope {
+    /// `user`'s, through `sudo -u`, or the apply user's when not set.
+    Global { user: Option<String> },
+    /// Written as root.
+    System,
+}
+
+impl GitConfigScope {
+    pub fn flag(&self) -> &'static str {
+        match self {
+            GitConfigScope::Global { .. } => "--global",
+            GitConfigScope::System => "--system",
+        }
+    }
+
+    /// Wrap a fully-built `git config` command to run as whoever owns the
+    /// file.
+    // Note(cc): `~/.gitconfig` is found through `HOME`, so this relies on
+    // sudo setting it to the target user's, as sudoers' `always_set_home`
+    // (or sudo 1.9's default) does.
+    pub fn wrap(&self, cmd: Command) -> Command {
+        match self {
+            GitConfigScope::Global { user: None } => cmd,
+            GitConfigScope::Global { user: Some(user) } => cmd.sudo_as(Some(user), None),
+            GitConfigScope::System => cmd.sudo(),
+        }
+    }
+}
+
+impl Display for GitConfigScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GitConfigScope::Global { user: None } => write!(f, "global"),
+            GitConfigScope::Global { user: Some(user) } => write!(f, "global for {user}"),
+            GitConfigScope::System => write!(f, "system"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum GitOperation {
     Clone {
         repo: String,
         path: FilePath,
+        depth: Option<u32>,
+        owner: GitOwner,
     },
     Fetch {
         path: FilePath,
+        owner: GitOwner,
     },
     Checkout {
         path: FilePath,
         version: String,
         force: bool,
+        owner: GitOwner,
     },
     Pull {
         path: FilePath,
+        owner: GitOwner,
+    },
+    /// Set `key` to `values`, replacing whatever values it had. A key with
+    /// many values, like `credential.helper`, has them in order.
+    SetConfig {
+        scope: GitConfigScope,
+        key: String,
+        values: Vec<String>,
+    },
+    UnsetConfig {
+        scope: GitConfigScope,
+        key: String,
     },
 }
 
 impl Display for GitOperation {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            GitOperation::Clone { repo, path } => {
-                write!(f, "Git::Clone(repo = {}, path = {})", repo, path)
+            GitOperation::Clone {
+                repo,
+                path,
+                depth,
+                owner,
+            } => {
+                write!(f, "Git::Clone(repo = {}, path = {}", repo, path)?;
+                if let Some(depth) = depth {
+                    write!(f, ", depth = {depth}")?;
+                }
+                write!(f, "{})", Owner(owner))
+            }
+            GitOperation::Fetch { path, owner } => {
+                write!(f, "Git::Fetch(path = {}{})", path, Owner(owner))
             }
-            GitOperation::Fetch { path } => write!(f, "Git::Fetch(path = {})", path),
             GitOperation::Checkout {
                 path,
                 version,
                 force,
+                owner,
             } => write!(
                 f,
-                "Git::Checkout(path = {}, version = {}, force = {})",
-                path, version, force
+                "Git::Checkout(path = {}, version = {}, force = {}{})",
+                path,
+                version,
+                force,
+                Owner(owner)
             ),
-            GitOperation::Pull { path } => write!(f, "Git::Pull(path = {})", path),
+            GitOperation::Pull { path, owner } => {
+                write!(f, "Git::Pull(path = {}{})", path, Owner(owner))
+            }
+            GitOperation::SetConfig { scope, key, values } => write!(
+                f,
+                "Git::SetConfig({scope}, {key} = [{}])",
+                values.join(", ")
+            ),
+            GitOperation::UnsetConfig { scope, key } => {
+                write!(f, "Git::UnsetConfig({scope}, {key})")
+            }
         }
     }
 }
 
+/// Renders `, owner = user:group`, or nothing when the owner isn't set.
+struct Owner<'a>(&'a GitOwner);
+
+impl Display for Owner<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_set() {
+            write!(f, ", owner = {}", self.0)?;
+        }
+        Ok(())
+    }
+}
+
 impl_display_render!(GitOperation);
 
 #[derive(Error, Debug)]
@@ -70,6 +204,19 @@ impl OperationType for Git {
         operations
     }
 
+    fn script(operation: &Self::Operation) -> Option<String> {
+        Some(command(operation).to_shell())
+    }
+
+    // Clones and fetches are run with `--progress`, so a large repo shows
+    // how far along it is rather than sitting silent.
+    fn progress(operation: &Self::Operation) -> Option<ProgressParser> {
+        match operation {
+            GitOperation::Clone { .. } | GitOperation::Fetch { .. } => Some(parse_progress),
+            _ => None,
+        }
+    }
+
     type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
     type ApplyError = GitApplyError;
     type ApplyStdout = ChildStdout;
@@ -80,74 +227,155 @@ impl OperationType for Git {
         operation: &Self::Operation,
     ) -> Result<(Self::ApplyOutput, Self::ApplyStdout, Self::ApplyStderr), Self::ApplyError> {
         match operation {
-            GitOperation::Clone { repo, path } => {
+            GitOperation::Clone { repo, path, .. } => {
                 info!("[git] clone: {} -> {}", repo, path);
-                let mut cmd = Command::new("git");
-                cmd.arg("clone").arg(repo).arg(path.as_path());
-                let output = cmd.output().await?;
-                Ok((
-                    Box::pin(async move {
-                        output.status.await?;
-                        Ok(())
-                    }),
-                    output.stdout,
-                    output.stderr,
-                ))
             }
-            GitOperation::Fetch { path } => {
+            GitOperation::Fetch { path, .. } => {
                 info!("[git] fetch: {}", path);
-                let mut cmd = Command::new("git");
-                cmd.arg("-C")
-                    .arg(path.as_path())
-                    .args(["fetch", "--all", "--prune"]);
-                let output = cmd.output().await?;
-                Ok((
-                    Box::pin(async move {
-                        output.status.await?;
-                        Ok(())
-                    }),
-                    output.stdout,
-                    output.stderr,
-                ))
             }
-            GitOperation::Checkout {
-                path,
-                version,
-                force,
-            } => {
+            GitOperation::Checkout { path, version, .. } => {
                 info!("[git] checkout: {} -> {}", path, version);
-                let mut cmd = Command::new("git");
-                cmd.arg("-C").arg(path.as_path()).arg("checkout");
-                if *force {
-                    cmd.arg("-f");
-                }
-                cmd.arg(version);
-                let output = cmd.output().await?;
-                Ok((
-                    Box::pin(async move {
-                        output.status.await?;
-                        Ok(())
-                    }),
-                    output.stdout,
-                    output.stderr,
-                ))
             }
-            GitOperation::Pull { path } => {
+            GitOperation::Pull { path, .. } => {
                 info!("[git] pull: {}", path);
-                let mut cmd = Command::new("git");
-                cmd.arg("-C")
-                    .arg(path.as_path())
-                    .args(["pull", "--ff-only"]);
-                let output = cmd.output().await?;
-                Ok((
-                    Box::pin(async move {
-                        output.status.await?;
-                        Ok(())
-                    }),
-                    output.stdout,
-                    output.stderr,
-                ))
+            }
+            GitOperation::SetConfig { scope, key, .. } => {
+                info!("[git] set config: {} ({})", key, scope);
+            }
+            GitOperation::UnsetConfig { scope, key } => {
+                info!("[git] unset config: {} ({})", key, scope);
             }
         }
+        let output = command(operation).output_checked().await?;
+        Ok((
+            Box::pin(async move {
+                output.status.await?;
+                Ok(())
+            }),
+            output.stdout,
+            output.stderr,
+        ))
+    }
+}
+
+/// The command `operation` runs, shared by [`OperationType::apply`] and
+/// [`OperationType::script`].
+fn command(operation: &GitOperation) -> Command {
+    match operation {
+        GitOperation::Clone {
+            repo,
+            path,
+            depth,
+            owner,
+        } => {
+            let mut cmd = Command::new("git");
+            cmd.args(["clone", "--progress"]);
+            if let Some(depth) = depth {
+                cmd.arg("--depth").arg(depth.to_string());
+            }
+            cmd.arg(repo).arg(path.as_path());
+            owner.wrap(cmd)
+        }
+        GitOperation::Fetch { path, owner } => {
+            let mut cmd = Command::new("git");
+            cmd.arg("-C")
+                .arg(path.as_path())
+                .args(["fetch", "--all", "--prune", "--progress"]);
+            owner.wrap(cmd)
+        }
+        GitOperation::Checkout {
+            path,
+            version,
+            force,
+            owner,
+        } => {
+            let mut cmd = Command::new("git");
+            cmd.arg("-C").arg(path.as_path()).arg("checkout");
+            if *force {
+                cmd.arg("-f");
+            }
+            cmd.arg(version);
+            owner.wrap(cmd)
+        }
+        GitOperation::Pull { path, owner } => {
+            let mut cmd = Command::new("git");
+            cmd.arg("-C")
+                .arg(path.as_path())
+                .args(["pull", "--ff-only"]);
+            owner.wrap(cmd)
+        }
+        GitOperation::SetConfig { scope, key, values } => {
+            let cmd = match values.as_slice() {
+                [value] => {
+                    let mut cmd = Command::new("git");
+                    cmd.args(["config", scope.flag(), "--replace-all"])
+                        .arg(key)
+                        .arg(value);
+                    cmd
+                }
+                values => {
+                    let mut cmd = Command::new_sh(SET_CONFIG_VALUES_SCRIPT);
+                    cmd.args(["git-config", scope.flag()]).arg(key).args(values);
+                    cmd
+                }
+            };
+            scope.wrap(cmd)
+        }
+        GitOperation::UnsetConfig { scope, key } => {
+            let mut cmd = Command::new("git");
+            cmd.args(["config", scope.flag(), "--unset-all"]).arg(key);
+            scope.wrap(cmd)
+        }
+    }
+}
+
+// Many values are added one by one, after unsetting any old ones; `git config
+// --unset-all` exits 5 when there were none.
+const SET_CONFIG_VALUES_SCRIPT: &str = r#"scope="$1" key="$2" && shift 2 && { git config "$scope" --unset-all "$key" || [ $? -eq 5 ]; } && for value do git config "$scope" --add "$key" "$value" || exit; done"#;
+
+/// Parse one of git's progress lines, like
+/// `Receiving objects:  45% (450/1000), 1.20 MiB | 2.40 MiB/s`, or the
+/// server's, like `remote: Compressing objects: 100% (3/3), done.`.
+fn parse_progress(line: &str) -> Option<Progress> {
+    let line = line.trim();
+    let line = line.strip_prefix("remote:").map_or(line, str::trim_start);
+    let (stage, rest) = line.split_once(": ")?;
+    let (percent, _) = rest.trim_start().split_once('%')?;
+    let percent: u8 = percent.parse().ok()?;
+    if percent > 100 {
+        return None;
+    }
+    Some(Progress {
+        stage: stage.to_owned(),
+        percent,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(stage: &str, percent: u8) -> Option<Progress> {
+        Some(Progress {
+            stage: stage.to_owned(),
+            percent,
+        })
+    }
+
+    #[test]
+    fn parses_progress_lines() {
+        assert_eq!(
+            parse_progress("Receiving objects:  45% (450/1000), 1.20 MiB | 2.40 MiB/s"),
+            progress("Receiving objects", 45)
+        );
+        assert_eq!(
+            parse_progress("remote: Compressing objects: 100% (3/3), done."),
+            progress("Compressing objects", 100)
+        );
+        assert_eq!(parse_progress("Cloning into 'lusid'..."), None);
+        assert_eq!(
+            parse_progress("remote: Enumerating objects: 5, done."),
+            None
+        );
     }
 }
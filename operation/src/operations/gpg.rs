@@ -0,0 +1,211 @@
+//! GPG public keys, imported by fingerprint into a user's keyring or written
+//! as a keyring file of their own, like apt's under `/etc/apt/keyrings/`.
+//!
+//! A key is first imported into a throwaway GnuPG home and checked against
+//! the fingerprint it was asked for. Only that key is then exported into its
+//! keyring, so a key file or keyserver handing back something else fails the
+//! import and leaves the keyring as it was.
+
+use async_trait::async_trait;
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_fs::{self as fs, FsError};
+use lusid_http::HttpError;
+use lusid_view::impl_display_render;
+use serde::Serialize;
+use std::{fmt::Display, pin::Pin};
+use thiserror::Error;
+use tokio::io::AsyncWriteExt;
+use tokio::process::{ChildStderr, ChildStdout};
+use tracing::info;
+
+use crate::OperationType;
+use crate::operations::file::FilePath;
+
+/// Where a key is imported into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GpgKeyring {
+    /// `user`'s GnuPG home, through `sudo -u`, or the apply user's when not
+    /// set.
+    User { user: Option<String> },
+    /// A keyring file holding just this key, written as root with mode 0644,
+    /// e.g. for an apt source's `Signed-By`.
+    File { path: FilePath },
+}
+
+impl GpgKeyring {
+    /// Wrap a fully-built command to run as whoever owns the keyring.
+    // Note(cc): a user's keyring is found through `HOME`, so this relies on
+    // sudo setting it to the target user's, as for `GitConfigScope::wrap`.
+    pub fn wrap(&self, cmd: Command) -> Command {
+        match self {
+            GpgKeyring::User { user: None } => cmd,
+            GpgKeyring::User { user: Some(user) } => cmd.sudo_as(Some(user), None),
+            GpgKeyring::File { .. } => cmd.sudo(),
+        }
+    }
+}
+
+impl Display for GpgKeyring {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GpgKeyring::User { user: None } => write!(f, "user keyring"),
+            GpgKeyring::User { user: Some(user) } => write!(f, "{user}'s keyring"),
+            GpgKeyring::File { path } => write!(f, "keyring {path}"),
+        }
+    }
+}
+
+/// Where a key is fetched from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GpgKeySource {
+    /// A key file, armored or not, fetched through the host's artifact cache.
+    Url { url: String },
+    /// A keyserver, e.g. `hkps://keys.openpgp.org`, asked for the key by its
+    /// fingerprint.
+    Keyserver { keyserver: String },
+}
+
+impl Display for GpgKeySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GpgKeySource::Url { url } => write!(f, "url = {url}"),
+            GpgKeySource::Keyserver { keyserver } => write!(f, "keyserver = {keyserver}"),
+        }
+    }
+}
+
+/// Emitted by `@core/gpg-key`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GpgOperation {
+    /// Import the key with `fingerprint` (40 or 64 upper-case hex digits)
+    /// from `source` into `keyring`, failing if `source` doesn't have it.
+    /// A keyring file is replaced whole.
+    ImportKey {
+        fingerprint: String,
+        source: GpgKeySource,
+        keyring: GpgKeyring,
+    },
+}
+
+impl Display for GpgOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GpgOperation::ImportKey {
+                fingerprint,
+                source,
+                keyring,
+            } => write!(
+                f,
+                "Gpg::ImportKey(fingerprint = {fingerprint}, {source}, into = {keyring})"
+            ),
+        }
+    }
+}
+
+impl_display_render!(GpgOperation);
+
+#[derive(Error, Debug)]
+pub enum GpgApplyError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+
+    #[error(transparent)]
+    Fs(#[from] FsError),
+
+    #[error(transparent)]
+    Http(#[from] HttpError),
+
+    #[error("failed to write gpg key: {0}")]
+    WriteKey(#[source] std::io::Error),
+}
+
+#[derive(Debug, Clone)]
+pub struct Gpg;
+
+#[async_trait]
+impl OperationType for Gpg {
+    type Operation = GpgOperation;
+
+    // Each key is its own.
+    fn merge(operations: Vec<Self::Operation>) -> Vec<Self::Operation> {
+        operations
+    }
+
+    // A key file is fetched in-process and piped in.
+    fn script(operation: &Self::Operation) -> Option<String> {
+        let GpgOperation::ImportKey { source, .. } = operation;
+        match source {
+            GpgKeySource::Url { .. } => None,
+            GpgKeySource::Keyserver { .. } => Some(command(operation).to_shell()),
+        }
+    }
+
+    type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
+    type ApplyError = GpgApplyError;
+    type ApplyStdout = ChildStdout;
+    type ApplyStderr = ChildStderr;
+
+    async fn apply(
+        ctx: &mut Context,
+        operation: &Self::Operation,
+    ) -> Result<(Self::ApplyOutput, Self::ApplyStdout, Self::ApplyStderr), Self::ApplyError> {
+        let GpgOperation::ImportKey {
+            fingerprint,
+            source,
+            keyring,
+        } = operation;
+        info!(fingerprint = %fingerprint, "[gpg] import key into {keyring}");
+
+        let output = match source {
+            GpgKeySource::Url { url } => {
+                let cached_path = ctx.artifact_cache().fetch(url, None).await?;
+                let key = fs::read_file_to_bytes(&cached_path).await?;
+                let (mut stdin, output) = command(operation).output_with_stdin_checked().await?;
+                stdin
+                    .write_all(&key)
+                    .await
+                    .map_err(GpgApplyError::WriteKey)?;
+                drop(stdin);
+                output
+            }
+            GpgKeySource::Keyserver { .. } => command(operation).output_checked().await?,
+        };
+        Ok((
+            Box::pin(async move {
+                output.status.await?;
+                Ok(())
+            }),
+            output.stdout,
+            output.stderr,
+        ))
+    }
+}
+
+// `gpg-key` is `$0` and the fingerprint `$1`. The key comes from the
+// keyserver in `$2`, or else stdin. `$3` is the keyring file to write, or
+// empty for the user's own keyring, whose home is noted before `GNUPGHOME`
+// points at the throwaway one.
+const IMPORT_KEY_SCRIPT: &str = r#"fpr="$1" keyserver="$2" keyring="$3" && home="${GNUPGHOME:-$HOME/.gnupg}" && tmp=$(mktemp -d) && trap 'rm -rf "$tmp"' EXIT && export GNUPGHOME="$tmp" && if [ -n "$keyserver" ]; then gpg --batch --quiet --keyserver "$keyserver" --recv-keys "$fpr"; else gpg --batch --quiet --import; fi && got=$(gpg --batch --with-colons --list-keys | awk -F: '$1 == "pub" { p = 1; next } p && $1 == "fpr" { print $10; p = 0 }') && if ! printf '%s\n' "$got" | grep -qx "$fpr"; then echo "expected key $fpr, got:" $got >&2; exit 1; fi && if [ -n "$keyring" ]; then gpg --batch --export "$fpr" > "$tmp/key.gpg" && install -m 0644 "$tmp/key.gpg" "$keyring"; else gpg --batch --export "$fpr" | gpg --homedir "$home" --batch --quiet --import; fi"#;
+
+/// The command `operation` runs, shared by [`OperationType::apply`] and
+/// [`OperationType::script`].
+fn command(operation: &GpgOperation) -> Command {
+    let GpgOperation::ImportKey {
+        fingerprint,
+        source,
+        keyring,
+    } = operation;
+    let mut cmd = Command::new_sh(IMPORT_KEY_SCRIPT);
+    cmd.arg("gpg-key").arg(fingerprint);
+    match source {
+        GpgKeySource::Url { .. } => cmd.arg(""),
+        GpgKeySource::Keyserver { keyserver } => cmd.arg(keyserver),
+    };
+    match keyring {
+        GpgKeyring::User { .. } => cmd.arg(""),
+        GpgKeyring::File { path } => cmd.arg(path.as_path()),
+    };
+    keyring.wrap(cmd)
+}
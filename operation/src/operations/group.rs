@@ -2,12 +2,16 @@ use async_trait::async_trait;
 use lusid_cmd::{Command, CommandError};
 use lusid_ctx::Context;
 use lusid_view::impl_display_render;
-use std::{fmt::Display, pin::Pin};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    pin::Pin,
+};
 use thiserror::Error;
 use tokio::process::{ChildStderr, ChildStdout};
 use tracing::info;
 
-use crate::OperationType;
+use crate::{OperationType, Severity};
 
 #[derive(Debug, Clone)]
 pub enum GroupOperation {
@@ -29,6 +33,13 @@ pub enum GroupOperation {
         name: String,
         user: String,
     },
+    /// Append `user` as a supplementary member of several groups at once, via
+    /// `usermod -aG`. Never emitted by a resource: [`Group::merge`] folds a
+    /// user's `AddUser`s in one epoch into this.
+    AddUserToGroups {
+        user: String,
+        groups: Vec<String>,
+    },
     Delete {
         name: String,
     },
@@ -42,6 +53,11 @@ impl Display for GroupOperation {
             GroupOperation::AddUser { name, user } => {
                 write!(f, "Group::AddUser(name = {name}, user = {user})")
             }
+            GroupOperation::AddUserToGroups { user, groups } => write!(
+                f,
+                "Group::AddUserToGroups(user = {user}, groups = [{}])",
+                groups.join(", ")
+            ),
             GroupOperation::Delete { name } => write!(f, "Group::Delete(name = {name})"),
         }
     }
@@ -65,8 +81,55 @@ impl OperationType for Group {
     // Note(cc): group operations mutate a single named group per call. As with
     // `UserOperation::merge`, ordering of add/modify/delete/add-user for the
     // same name would need to be preserved; not worth coalescing.
+    //
+    // Memberships are the exception: several groups appending the same user
+    // in one epoch become one `usermod -aG` for that user, in place of the
+    // user's first `AddUser`, with the groups sorted so the command doesn't
+    // depend on scheduling. A user added to just one group keeps its
+    // `gpasswd -a`.
     fn merge(operations: Vec<Self::Operation>) -> Vec<Self::Operation> {
-        operations
+        let mut groups_by_user: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for operation in &operations {
+            if let GroupOperation::AddUser { name, user } = operation {
+                groups_by_user
+                    .entry(user.clone())
+                    .or_default()
+                    .insert(name.clone());
+            }
+        }
+
+        let mut merged: Vec<Self::Operation> = Vec::with_capacity(operations.len());
+        for operation in operations {
+            match operation {
+                GroupOperation::AddUser { name, user } => {
+                    let Some(groups) = groups_by_user.remove(&user) else {
+                        // Already folded into this user's earlier operation.
+                        continue;
+                    };
+                    if groups.len() == 1 {
+                        merged.push(GroupOperation::AddUser { name, user });
+                    } else {
+                        merged.push(GroupOperation::AddUserToGroups {
+                            user,
+                            groups: groups.into_iter().collect(),
+                        });
+                    }
+                }
+                operation => merged.push(operation),
+            }
+        }
+        merged
+    }
+
+    fn script(operation: &Self::Operation) -> Option<String> {
+        Some(command(operation).to_shell())
+    }
+
+    fn severity(operation: &Self::Operation) -> Severity {
+        match operation {
+            GroupOperation::Delete { .. } => Severity::Destructive,
+            _ => Severity::Safe,
+        }
     }
 
     type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
@@ -79,71 +142,75 @@ impl OperationType for Group {
         operation: &Self::Operation,
     ) -> Result<(Self::ApplyOutput, Self::ApplyStdout, Self::ApplyStderr), Self::ApplyError> {
         match operation {
-            GroupOperation::Add { name, gid, system } => {
+            GroupOperation::Add { name, .. } => {
                 info!("[group] add: {}", name);
-                let mut cmd = Command::new("groupadd");
-                if let Some(gid) = gid {
-                    cmd.arg("-g").arg(gid.to_string());
-                }
-                if *system {
-                    cmd.arg("-r");
-                }
-                cmd.arg("--").arg(name);
-                let output = cmd.sudo().output().await?;
-                Ok((
-                    Box::pin(async move {
-                        output.status.await?;
-                        Ok(())
-                    }),
-                    output.stdout,
-                    output.stderr,
-                ))
             }
-            GroupOperation::Modify { name, gid } => {
+            GroupOperation::Modify { name, .. } => {
                 info!("[group] modify: {}", name);
-                let mut cmd = Command::new("groupmod");
-                if let Some(gid) = gid {
-                    cmd.arg("-g").arg(gid.to_string());
-                }
-                cmd.arg("--").arg(name);
-                let output = cmd.sudo().output().await?;
-                Ok((
-                    Box::pin(async move {
-                        output.status.await?;
-                        Ok(())
-                    }),
-                    output.stdout,
-                    output.stderr,
-                ))
             }
             GroupOperation::AddUser { name, user } => {
                 info!("[group] add user: {} <- {}", name, user);
-                let mut cmd = Command::new("gpasswd");
-                cmd.arg("-a").arg(user).arg("--").arg(name);
-                let output = cmd.sudo().output().await?;
-                Ok((
-                    Box::pin(async move {
-                        output.status.await?;
-                        Ok(())
-                    }),
-                    output.stdout,
-                    output.stderr,
-                ))
+            }
+            GroupOperation::AddUserToGroups { user, groups } => {
+                info!(
+                    "[group] add user to groups: {} <- {}",
+                    groups.join(", "),
+                    user
+                );
             }
             GroupOperation::Delete { name } => {
                 info!("[group] delete: {}", name);
-                let mut cmd = Command::new("groupdel");
-                cmd.arg("--").arg(name);
-                let output = cmd.sudo().output().await?;
-                Ok((
-                    Box::pin(async move {
-                        output.status.await?;
-                        Ok(())
-                    }),
-                    output.stdout,
-                    output.stderr,
-                ))
             }
         }
+        let output = command(operation).output_checked().await?;
+        Ok((
+            Box::pin(async move {
+                output.status.await?;
+                Ok(())
+            }),
+            output.stdout,
+            output.stderr,
+        ))
+    }
+}
+
+/// The command `operation` runs, shared by [`OperationType::apply`] and
+/// [`OperationType::script`].
+fn command(operation: &GroupOperation) -> Command {
+    match operation {
+        GroupOperation::Add { name, gid, system } => {
+            let mut cmd = Command::new("groupadd");
+            if let Some(gid) = gid {
+                cmd.arg("-g").arg(gid.to_string());
+            }
+            if *system {
+                cmd.arg("-r");
+            }
+            cmd.arg("--").arg(name);
+            cmd.sudo()
+        }
+        GroupOperation::Modify { name, gid } => {
+            let mut cmd = Command::new("groupmod");
+            if let Some(gid) = gid {
+                cmd.arg("-g").arg(gid.to_string());
+            }
+            cmd.arg("--").arg(name);
+            cmd.sudo()
+        }
+        GroupOperation::AddUser { name, user } => {
+            let mut cmd = Command::new("gpasswd");
+            cmd.arg("-a").arg(user).arg("--").arg(name);
+            cmd.sudo()
+        }
+        GroupOperation::AddUserToGroups { user, groups } => {
+            let mut cmd = Command::new("usermod");
+            cmd.arg("-aG").arg(groups.join(",")).arg("--").arg(user);
+            cmd.sudo()
+        }
+        GroupOperation::Delete { name } => {
+            let mut cmd = Command::new("groupdel");
+            cmd.arg("--").arg(name);
+            cmd.sudo()
+        }
     }
 }
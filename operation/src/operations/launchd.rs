@@ -0,0 +1,136 @@
+//! macOS services, managed with `launchctl`.
+//!
+//! A service is addressed by its launchd domain and label: `system` for the
+//! daemons in `/Library/LaunchDaemons`, which needs `sudo`, or `gui/<uid>` for
+//! a logged-in user's agents, which runs as the apply user.
+
+use async_trait::async_trait;
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_view::impl_display_render;
+use std::{fmt::Display, pin::Pin};
+use thiserror::Error;
+use tokio::process::{ChildStderr, ChildStdout};
+use tracing::info;
+
+use crate::operations::file::FilePath;
+use crate::{OperationType, Severity};
+
+/// The launchd domain of system daemons.
+pub const SYSTEM_DOMAIN: &str = "system";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LaunchdOperation {
+    /// Clear the service's disabled override, so it may be loaded.
+    Enable { domain: String, label: String },
+    /// Set the service's disabled override, so it won't load at boot or login.
+    Disable { domain: String, label: String },
+    /// Load the service from its plist, which starts it if it runs at load.
+    Bootstrap { domain: String, plist: FilePath },
+    /// Unload the service, stopping it.
+    Bootout { domain: String, label: String },
+}
+
+impl Display for LaunchdOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LaunchdOperation::Enable { domain, label } => {
+                write!(f, "Launchd::Enable({domain}/{label})")
+            }
+            LaunchdOperation::Disable { domain, label } => {
+                write!(f, "Launchd::Disable({domain}/{label})")
+            }
+            LaunchdOperation::Bootstrap { domain, plist } => {
+                write!(f, "Launchd::Bootstrap({domain}, {plist})")
+            }
+            LaunchdOperation::Bootout { domain, label } => {
+                write!(f, "Launchd::Bootout({domain}/{label})")
+            }
+        }
+    }
+}
+
+impl_display_render!(LaunchdOperation);
+
+#[derive(Error, Debug)]
+pub enum LaunchdApplyError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+}
+
+#[derive(Debug, Clone)]
+pub struct Launchd;
+
+#[async_trait]
+impl OperationType for Launchd {
+    type Operation = LaunchdOperation;
+
+    // Note(cc): like systemd's, merge is a no-op: `launchctl` takes one
+    // service per call.
+    fn merge(operations: Vec<Self::Operation>) -> Vec<Self::Operation> {
+        operations
+    }
+
+    fn script(operation: &Self::Operation) -> Option<String> {
+        Some(command(operation).to_shell())
+    }
+
+    fn severity(operation: &Self::Operation) -> Severity {
+        match operation {
+            LaunchdOperation::Disable { .. } | LaunchdOperation::Bootout { .. } => {
+                Severity::Disruptive
+            }
+            LaunchdOperation::Enable { .. } | LaunchdOperation::Bootstrap { .. } => Severity::Safe,
+        }
+    }
+
+    type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
+    type ApplyError = LaunchdApplyError;
+    type ApplyStdout = ChildStdout;
+    type ApplyStderr = ChildStderr;
+
+    async fn apply(
+        _ctx: &mut Context,
+        operation: &Self::Operation,
+    ) -> Result<(Self::ApplyOutput, Self::ApplyStdout, Self::ApplyStderr), Self::ApplyError> {
+        info!("[launchd] {}", operation);
+        let output = command(operation).output_checked().await?;
+        Ok((
+            Box::pin(async move {
+                output.status.await?;
+                Ok(())
+            }),
+            output.stdout,
+            output.stderr,
+        ))
+    }
+}
+
+/// The `launchctl` command `operation` runs, shared by
+/// [`OperationType::apply`] and [`OperationType::script`].
+fn command(operation: &LaunchdOperation) -> Command {
+    let mut cmd = Command::new("launchctl");
+    let domain = match operation {
+        LaunchdOperation::Enable { domain, label } => {
+            cmd.arg("enable").arg(format!("{domain}/{label}"));
+            domain
+        }
+        LaunchdOperation::Disable { domain, label } => {
+            cmd.arg("disable").arg(format!("{domain}/{label}"));
+            domain
+        }
+        LaunchdOperation::Bootstrap { domain, plist } => {
+            cmd.arg("bootstrap").arg(domain).arg(plist.as_path());
+            domain
+        }
+        LaunchdOperation::Bootout { domain, label } => {
+            cmd.arg("bootout").arg(format!("{domain}/{label}"));
+            domain
+        }
+    };
+    if domain == SYSTEM_DOMAIN {
+        cmd.sudo()
+    } else {
+        cmd
+    }
+}
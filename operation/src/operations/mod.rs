@@ -1,11 +1,28 @@
+pub mod apk;
 pub mod apt;
 pub mod apt_repo;
+pub mod aur;
+pub mod brew;
 pub mod command;
+pub mod cron;
 pub mod directory;
+pub mod dnf;
 pub mod file;
+pub mod firewall;
 pub mod git;
+pub mod gpg;
 pub mod group;
+pub mod launchd;
+pub mod networkd;
+pub mod nix;
 pub mod pacman;
+pub mod pip;
 pub mod podman;
+pub mod rustup;
+pub mod service;
+pub(crate) mod stage;
 pub mod systemd;
+pub mod time;
+pub mod tls_cert;
 pub mod user;
+pub mod wireguard;
@@ -0,0 +1,156 @@
+//! systemd-networkd's `.network` and `.netdev` files, and `networkctl
+//! reload` to have it pick them up.
+
+use async_trait::async_trait;
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_fs::FsError;
+use lusid_view::impl_display_render;
+use std::{fmt::Display, pin::Pin};
+use thiserror::Error;
+use tokio::process::{ChildStderr, ChildStdout};
+use tracing::info;
+
+use crate::operations::file::FilePath;
+use crate::operations::stage::stage_file;
+use crate::{OperationType, Severity};
+
+const STAGE_SUBDIR: &str = "networkd";
+
+/// `WriteConfig` and `Reload` are emitted by `@core/networkd`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkdOperation {
+    /// Stage `content` to a user-writable cache, then `sudo install` it to
+    /// `path` with mode 0644. `name` is the file's name, e.g.
+    /// `10-br0.netdev`.
+    WriteConfig {
+        name: String,
+        path: FilePath,
+        content: String,
+    },
+
+    /// `networkctl reload`: create netdevs for new `.netdev` files, and
+    /// reconfigure the links matched by new or changed `.network` files.
+    Reload,
+}
+
+impl Display for NetworkdOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetworkdOperation::WriteConfig {
+                name,
+                path,
+                content,
+            } => write!(
+                f,
+                "Networkd::WriteConfig(name = {name}, path = {path}, {} bytes)",
+                content.len()
+            ),
+            NetworkdOperation::Reload => write!(f, "Networkd::Reload"),
+        }
+    }
+}
+
+impl_display_render!(NetworkdOperation);
+
+#[derive(Error, Debug)]
+pub enum NetworkdApplyError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+
+    #[error(transparent)]
+    Fs(#[from] FsError),
+}
+
+#[derive(Debug, Clone)]
+pub struct Networkd;
+
+#[async_trait]
+impl OperationType for Networkd {
+    type Operation = NetworkdOperation;
+
+    // One reload per epoch picks up every file written before it.
+    fn merge(operations: Vec<Self::Operation>) -> Vec<Self::Operation> {
+        let mut merged: Vec<Self::Operation> = Vec::with_capacity(operations.len());
+        for operation in operations {
+            if operation == NetworkdOperation::Reload && merged.contains(&operation) {
+                continue;
+            }
+            merged.push(operation);
+        }
+        merged
+    }
+
+    // Config files are staged in-process before `install`, like systemd's
+    // unit files.
+    fn script(operation: &Self::Operation) -> Option<String> {
+        match operation {
+            NetworkdOperation::WriteConfig { .. } => None,
+            NetworkdOperation::Reload => Some(reload().to_shell()),
+        }
+    }
+
+    // A reload reconfigures links, which can drop connections over them,
+    // including the one lusid is applying over.
+    fn severity(operation: &Self::Operation) -> Severity {
+        match operation {
+            NetworkdOperation::WriteConfig { .. } => Severity::Safe,
+            NetworkdOperation::Reload => Severity::Disruptive,
+        }
+    }
+
+    type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
+    type ApplyError = NetworkdApplyError;
+    type ApplyStdout = ChildStdout;
+    type ApplyStderr = ChildStderr;
+
+    async fn apply(
+        ctx: &mut Context,
+        operation: &Self::Operation,
+    ) -> Result<(Self::ApplyOutput, Self::ApplyStdout, Self::ApplyStderr), Self::ApplyError> {
+        let (mut cmd, stage_path) = match operation {
+            NetworkdOperation::WriteConfig {
+                name,
+                path,
+                content,
+            } => {
+                info!(name = %name, path = %path, "[networkd] write config");
+
+                let stage_path = stage_file(ctx, STAGE_SUBDIR, name, content.as_bytes()).await?;
+
+                let mut cmd = Command::new("install");
+                cmd.arg("-m")
+                    .arg("0644")
+                    .arg(&stage_path)
+                    .arg(path.as_path());
+                (cmd.sudo(), Some(stage_path))
+            }
+            NetworkdOperation::Reload => {
+                info!("[networkd] reload");
+                (reload(), None)
+            }
+        };
+
+        let output = cmd.output_checked().await?;
+        Ok((
+            Box::pin(async move {
+                let result = output.status.await;
+                if let Some(stage_path) = stage_path {
+                    let _ = tokio::fs::remove_file(&stage_path).await;
+                }
+                result?;
+                Ok(())
+            }),
+            output.stdout,
+            output.stderr,
+        ))
+    }
+}
+
+/// `networkctl reload`, shared by [`OperationType::apply`] and
+/// [`OperationType::script`].
+fn reload() -> Command {
+    let mut cmd = Command::new("networkctl");
+    cmd.arg("reload");
+    cmd.sudo()
+}
@@ -0,0 +1,142 @@
+//! Packages in the apply user's Nix profile, installed and removed with
+//! `nix profile`.
+//!
+//! A profile belongs to its user, so unlike apt and pacman these don't run
+//! through sudo.
+
+use async_trait::async_trait;
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_view::impl_display_render;
+use std::{collections::BTreeSet, fmt::Display, pin::Pin};
+use thiserror::Error;
+use tokio::process::{ChildStderr, ChildStdout};
+use tracing::info;
+
+use crate::{OperationType, Severity};
+
+#[derive(Debug, Clone)]
+pub enum NixOperation {
+    /// Install flake outputs, like `nixpkgs#ripgrep`.
+    Install { installables: Vec<String> },
+    /// Remove profile elements, by the names `nix profile list` gives them.
+    Remove { elements: Vec<String> },
+}
+
+impl Display for NixOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NixOperation::Install { installables } => {
+                write!(f, "Nix::Install([{}])", installables.join(", "))
+            }
+            NixOperation::Remove { elements } => {
+                write!(f, "Nix::Remove([{}])", elements.join(", "))
+            }
+        }
+    }
+}
+
+impl_display_render!(NixOperation);
+
+#[derive(Error, Debug)]
+pub enum NixApplyError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+}
+
+#[derive(Debug, Clone)]
+pub struct Nix;
+
+#[async_trait]
+impl OperationType for Nix {
+    type Operation = NixOperation;
+
+    // Removals go first, so replacing a package with one from another flake
+    // doesn't collide on the same files in the profile.
+    fn merge(operations: Vec<Self::Operation>) -> Vec<Self::Operation> {
+        let mut install: BTreeSet<String> = BTreeSet::new();
+        let mut remove: BTreeSet<String> = BTreeSet::new();
+
+        for operation in operations {
+            match operation {
+                NixOperation::Install { installables } => install.extend(installables),
+                NixOperation::Remove { elements } => remove.extend(elements),
+            }
+        }
+
+        let mut operations = Vec::new();
+        if !remove.is_empty() {
+            operations.push(NixOperation::Remove {
+                elements: remove.into_iter().collect(),
+            });
+        }
+        if !install.is_empty() {
+            operations.push(NixOperation::Install {
+                installables: install.into_iter().collect(),
+            });
+        }
+        operations
+    }
+
+    fn script(operation: &Self::Operation) -> Option<String> {
+        Some(command(operation).to_shell())
+    }
+
+    // `nix profile rollback` undoes a removal, so unlike an apt or dnf one
+    // it isn't destructive.
+    fn severity(_operation: &Self::Operation) -> Severity {
+        Severity::Safe
+    }
+
+    type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
+    type ApplyError = NixApplyError;
+    type ApplyStdout = ChildStdout;
+    type ApplyStderr = ChildStderr;
+
+    async fn apply(
+        _ctx: &mut Context,
+        operation: &Self::Operation,
+    ) -> Result<(Self::ApplyOutput, Self::ApplyStdout, Self::ApplyStderr), Self::ApplyError> {
+        match operation {
+            NixOperation::Install { installables } => {
+                info!("[nix] install: {}", installables.join(", "));
+            }
+            NixOperation::Remove { elements } => {
+                info!("[nix] remove: {}", elements.join(", "));
+            }
+        }
+        let output = command(operation).output_checked().await?;
+        Ok((
+            Box::pin(async move {
+                output.status.await?;
+                Ok(())
+            }),
+            output.stdout,
+            output.stderr,
+        ))
+    }
+}
+
+/// `nix` with the experimental `nix profile` command enabled, which most
+/// installs still leave off.
+pub fn nix() -> Command {
+    let mut cmd = Command::new("nix");
+    cmd.arg("--extra-experimental-features")
+        .arg("nix-command flakes");
+    cmd
+}
+
+/// The command `operation` runs, shared by [`OperationType::apply`] and
+/// [`OperationType::script`].
+fn command(operation: &NixOperation) -> Command {
+    let mut cmd = nix();
+    match operation {
+        NixOperation::Install { installables } => {
+            cmd.args(["profile", "install", "--"]).args(installables);
+        }
+        NixOperation::Remove { elements } => {
+            cmd.args(["profile", "remove", "--"]).args(elements);
+        }
+    }
+    cmd
+}
@@ -70,6 +70,27 @@ impl OperationType for Pacman {
         operations
     }
 
+    fn script(operation: &Self::Operation) -> Option<String> {
+        Some(command(operation).to_shell())
+    }
+
+    // The packages `-S` would install, dependencies too, without root.
+    fn simulate(operation: &Self::Operation) -> Option<Command> {
+        let PacmanOperation::Install { packages } = operation else {
+            return None;
+        };
+        let mut cmd = Command::new("pacman");
+        cmd.arg("-S")
+            .arg("--print")
+            .arg("--print-format")
+            .arg("%n %v")
+            .arg("--needed")
+            .arg("--color=never")
+            .arg("--")
+            .args(packages);
+        Some(cmd)
+    }
+
     type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
     type ApplyError = PacmanApplyError;
     type ApplyStdout = ChildStdout;
@@ -82,37 +103,41 @@ impl OperationType for Pacman {
         match operation {
             PacmanOperation::Upgrade => {
                 info!("[pacman] upgrade");
-                let mut cmd = Command::new("pacman");
-                cmd.arg("-Syu").arg("--noconfirm").arg("--color=never");
-                let output = cmd.sudo().output().await?;
-                Ok((
-                    Box::pin(async move {
-                        output.status.await?;
-                        Ok(())
-                    }),
-                    output.stdout,
-                    output.stderr,
-                ))
             }
             PacmanOperation::Install { packages } => {
                 info!("[pacman] install: {}", packages.join(", "));
-                let mut cmd = Command::new("pacman");
-                cmd.arg("-S")
-                    .arg("--noconfirm")
-                    .arg("--needed")
-                    .arg("--color=never")
-                    .arg("--")
-                    .args(packages);
-                let output = cmd.sudo().output().await?;
-                Ok((
-                    Box::pin(async move {
-                        output.status.await?;
-                        Ok(())
-                    }),
-                    output.stdout,
-                    output.stderr,
-                ))
             }
         }
+        let output = command(operation).output_checked().await?;
+        Ok((
+            Box::pin(async move {
+                output.status.await?;
+                Ok(())
+            }),
+            output.stdout,
+            output.stderr,
+        ))
+    }
+}
+
+/// The command `operation` runs, shared by [`OperationType::apply`] and
+/// [`OperationType::script`].
+fn command(operation: &PacmanOperation) -> Command {
+    match operation {
+        PacmanOperation::Upgrade => {
+            let mut cmd = Command::new("pacman");
+            cmd.arg("-Syu").arg("--noconfirm").arg("--color=never");
+            cmd.sudo()
+        }
+        PacmanOperation::Install { packages } => {
+            let mut cmd = Command::new("pacman");
+            cmd.arg("-S")
+                .arg("--noconfirm")
+                .arg("--needed")
+                .arg("--color=never")
+                .arg("--")
+                .args(packages);
+            cmd.sudo()
+        }
     }
 }
@@ -0,0 +1,188 @@
+//! Python packages, installed with pipx or into a virtualenv with its own pip.
+//!
+//! Requirements are `name` or `name==version`, as pip and pipx take them.
+//! Both run as the apply user: pipx installs into that user's
+//! `~/.local/share/pipx`, and a virtualenv must be somewhere they can write.
+
+use async_trait::async_trait;
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_view::impl_display_render;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    pin::Pin,
+};
+use thiserror::Error;
+use tokio::process::{ChildStderr, ChildStdout};
+use tracing::info;
+
+use crate::OperationType;
+use crate::operations::file::FilePath;
+
+/// Where Python packages are installed.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PipTarget {
+    /// As pipx apps, each in a virtualenv of its own.
+    Pipx,
+    /// Into the virtualenv at `path`.
+    Venv { path: FilePath },
+}
+
+impl PipTarget {
+    /// The virtualenv's pip, run directly so the venv needn't be activated.
+    pub fn venv_pip(path: &FilePath) -> String {
+        format!("{path}/bin/pip")
+    }
+}
+
+impl Display for PipTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PipTarget::Pipx => write!(f, "pipx"),
+            PipTarget::Venv { path } => write!(f, "venv {path}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum PipOperation {
+    /// Create a virtualenv at `path` with `python3 -m venv`.
+    CreateVenv { path: FilePath },
+    /// Install `requirements` into `target`, replacing any installed version
+    /// that doesn't match a pin.
+    Install {
+        target: PipTarget,
+        requirements: Vec<String>,
+    },
+}
+
+impl Display for PipOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PipOperation::CreateVenv { path } => write!(f, "Pip::CreateVenv(path = {path})"),
+            PipOperation::Install {
+                target,
+                requirements,
+            } => write!(
+                f,
+                "Pip::Install(target = {target}, requirements = [{}])",
+                requirements.join(", ")
+            ),
+        }
+    }
+}
+
+impl_display_render!(PipOperation);
+
+#[derive(Error, Debug)]
+pub enum PipApplyError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+}
+
+#[derive(Debug, Clone)]
+pub struct Pip;
+
+#[async_trait]
+impl OperationType for Pip {
+    type Operation = PipOperation;
+
+    // Virtualenvs are created once each, then installs are merged into one
+    // per target, so an epoch costs one pip (or pipx) run per target. A
+    // resource's install requires its venv, so the two are never merged
+    // together.
+    fn merge(operations: Vec<Self::Operation>) -> Vec<Self::Operation> {
+        let mut venvs: BTreeSet<FilePath> = BTreeSet::new();
+        let mut installs: BTreeMap<PipTarget, BTreeSet<String>> = BTreeMap::new();
+        for operation in operations {
+            match operation {
+                PipOperation::CreateVenv { path } => {
+                    venvs.insert(path);
+                }
+                PipOperation::Install {
+                    target,
+                    requirements,
+                } => installs.entry(target).or_default().extend(requirements),
+            }
+        }
+
+        let venvs = venvs
+            .into_iter()
+            .map(|path| PipOperation::CreateVenv { path });
+        let installs = installs
+            .into_iter()
+            .map(|(target, requirements)| PipOperation::Install {
+                target,
+                requirements: requirements.into_iter().collect(),
+            });
+        venvs.chain(installs).collect()
+    }
+
+    fn script(operation: &Self::Operation) -> Option<String> {
+        Some(command(operation).to_shell())
+    }
+
+    type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
+    type ApplyError = PipApplyError;
+    type ApplyStdout = ChildStdout;
+    type ApplyStderr = ChildStderr;
+
+    async fn apply(
+        _ctx: &mut Context,
+        operation: &Self::Operation,
+    ) -> Result<(Self::ApplyOutput, Self::ApplyStdout, Self::ApplyStderr), Self::ApplyError> {
+        match operation {
+            PipOperation::CreateVenv { path } => {
+                info!("[pip] create venv: {}", path);
+            }
+            PipOperation::Install {
+                target,
+                requirements,
+            } => {
+                info!("[pip] install into {}: {}", target, requirements.join(", "));
+            }
+        }
+        let output = command(operation).output_checked().await?;
+        Ok((
+            Box::pin(async move {
+                output.status.await?;
+                Ok(())
+            }),
+            output.stdout,
+            output.stderr,
+        ))
+    }
+}
+
+/// The command `operation` runs, shared by [`OperationType::apply`] and
+/// [`OperationType::script`].
+fn command(operation: &PipOperation) -> Command {
+    match operation {
+        PipOperation::CreateVenv { path } => {
+            let mut cmd = Command::new("python3");
+            cmd.arg("-m").arg("venv").arg("--").arg(path.as_path());
+            cmd
+        }
+        // `--force` so a pinned version replaces whatever's installed; pipx
+        // otherwise skips a package it already has.
+        PipOperation::Install {
+            target: PipTarget::Pipx,
+            requirements,
+        } => {
+            let mut cmd = Command::new("pipx");
+            cmd.arg("install").arg("--force").args(requirements);
+            cmd
+        }
+        PipOperation::Install {
+            target: PipTarget::Venv { path },
+            requirements,
+        } => {
+            let mut cmd = Command::new(PipTarget::venv_pip(path));
+            cmd.arg("install")
+                .arg("--disable-pip-version-check")
+                .args(requirements);
+            cmd
+        }
+    }
+}
@@ -2,12 +2,12 @@ use async_trait::async_trait;
 use lusid_cmd::{Command, CommandError};
 use lusid_ctx::Context;
 use lusid_view::impl_display_render;
-use std::{fmt::Display, pin::Pin};
+use std::{collections::BTreeSet, fmt::Display, pin::Pin};
 use thiserror::Error;
 use tokio::process::{ChildStderr, ChildStdout};
 use tracing::info;
 
-use crate::OperationType;
+use crate::{OperationType, Severity};
 
 /// Label key written on every container lusid creates. Its value is the
 /// resource layer's `config_hash` of the declared spec, used by drift
@@ -18,9 +18,15 @@ pub const CONFIG_HASH_LABEL: &str = "lusid.config-hash";
 
 #[derive(Debug, Clone)]
 pub enum PodmanOperation {
+    /// Pull `images` into local storage with one `podman pull`. Emitted one
+    /// image at a time by `@core/podman-image`; [`Podman::merge`] folds the
+    /// pulls in one epoch into a single operation.
+    Pull {
+        images: Vec<String>,
+    },
     /// Create a container from `image` under `name`. `--pull=missing` is used
-    /// so the image is fetched inline when it isn't already present locally —
-    /// keeps the operation set small without exposing a separate Pull op.
+    /// so the image is fetched inline when it isn't already present locally;
+    /// declare the image with `@core/podman-image` to pull it ahead of time.
     /// `config_hash` is written as the [`CONFIG_HASH_LABEL`] label so the
     /// next state observation can detect drift without re-deriving fields
     /// from podman's normalised inspect output.
@@ -50,6 +56,9 @@ pub enum PodmanOperation {
 impl Display for PodmanOperation {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            PodmanOperation::Pull { images } => {
+                write!(f, "Podman::Pull(images = [{}])", images.join(", "))
+            }
             PodmanOperation::Create { name, image, .. } => {
                 write!(f, "Podman::Create(name = {name}, image = {image})")
             }
@@ -75,13 +84,53 @@ pub struct Podman;
 impl OperationType for Podman {
     type Operation = PodmanOperation;
 
-    // Note(cc): merge is a no-op. Each op targets a single named container and
-    // ordering matters (create before start, remove before recreate) — that
-    // ordering is already expressed in the causality tree, so merging would
-    // have to respect it. Not worth the complexity for the typical "handful of
-    // containers per plan" case.
+    // Note(cc): container ops are passed through. Each targets a single named
+    // container and ordering matters (create before start, remove before
+    // recreate) — that ordering is already expressed in the causality tree, so
+    // merging would have to respect it. Not worth the complexity for the
+    // typical "handful of containers per plan" case.
+    //
+    // Pulls are the exception: every pull in the epoch becomes one `podman
+    // pull`, in place of the first, with the images deduplicated and sorted so
+    // the command doesn't depend on scheduling.
     fn merge(operations: Vec<Self::Operation>) -> Vec<Self::Operation> {
-        operations
+        let mut images: BTreeSet<String> = BTreeSet::new();
+        for operation in &operations {
+            if let PodmanOperation::Pull { images: pull } = operation {
+                images.extend(pull.iter().cloned());
+            }
+        }
+
+        let mut merged: Vec<Self::Operation> = Vec::with_capacity(operations.len());
+        let mut pulled = false;
+        for operation in operations {
+            match operation {
+                PodmanOperation::Pull { .. } => {
+                    if !pulled {
+                        pulled = true;
+                        merged.push(PodmanOperation::Pull {
+                            images: std::mem::take(&mut images).into_iter().collect(),
+                        });
+                    }
+                }
+                operation => merged.push(operation),
+            }
+        }
+        merged
+    }
+
+    fn script(operation: &Self::Operation) -> Option<String> {
+        Some(command(operation).to_shell())
+    }
+
+    // A removed container loses its writable layer, but containers are
+    // recreated from their image on drift, so that's routine rather than
+    // destructive. Volumes outlive it, as `rm` is run without `--volumes`.
+    fn severity(operation: &Self::Operation) -> Severity {
+        match operation {
+            PodmanOperation::Stop { .. } | PodmanOperation::Remove { .. } => Severity::Disruptive,
+            _ => Severity::Safe,
+        }
     }
 
     type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
@@ -94,92 +143,92 @@ impl OperationType for Podman {
         operation: &Self::Operation,
     ) -> Result<(Self::ApplyOutput, Self::ApplyStdout, Self::ApplyStderr), Self::ApplyError> {
         match operation {
-            PodmanOperation::Create {
-                name,
-                image,
-                command,
-                env,
-                ports,
-                volumes,
-                restart_policy,
-                config_hash,
-            } => {
+            PodmanOperation::Pull { images } => {
+                info!("[podman] pull: {}", images.join(", "));
+            }
+            PodmanOperation::Create { name, image, .. } => {
                 info!("[podman] create: {} from {}", name, image);
-                let mut cmd = Command::new("podman");
-                cmd.arg("create")
-                    .arg("--pull=missing")
-                    .arg("--name")
-                    .arg(name)
-                    .arg("--label")
-                    .arg(format!("{CONFIG_HASH_LABEL}={config_hash}"));
-                if let Some(policy) = restart_policy {
-                    cmd.arg("--restart").arg(policy);
-                }
-                for value in env {
-                    cmd.arg("-e").arg(value);
-                }
-                for mapping in ports {
-                    cmd.arg("-p").arg(mapping);
-                }
-                for mapping in volumes {
-                    cmd.arg("-v").arg(mapping);
-                }
-                cmd.arg("--").arg(image);
-                if let Some(command) = command {
-                    cmd.args(command);
-                }
-                let output = cmd.output().await?;
-                Ok((
-                    Box::pin(async move {
-                        output.status.await?;
-                        Ok(())
-                    }),
-                    output.stdout,
-                    output.stderr,
-                ))
             }
             PodmanOperation::Start { name } => {
                 info!("[podman] start: {}", name);
-                let mut cmd = Command::new("podman");
-                cmd.arg("start").arg("--").arg(name);
-                let output = cmd.output().await?;
-                Ok((
-                    Box::pin(async move {
-                        output.status.await?;
-                        Ok(())
-                    }),
-                    output.stdout,
-                    output.stderr,
-                ))
             }
             PodmanOperation::Stop { name } => {
                 info!("[podman] stop: {}", name);
-                let mut cmd = Command::new("podman");
-                cmd.arg("stop").arg("--").arg(name);
-                let output = cmd.output().await?;
-                Ok((
-                    Box::pin(async move {
-                        output.status.await?;
-                        Ok(())
-                    }),
-                    output.stdout,
-                    output.stderr,
-                ))
             }
             PodmanOperation::Remove { name } => {
                 info!("[podman] remove: {}", name);
-                let mut cmd = Command::new("podman");
-                cmd.arg("rm").arg("--force").arg("--").arg(name);
-                let output = cmd.output().await?;
-                Ok((
-                    Box::pin(async move {
-                        output.status.await?;
-                        Ok(())
-                    }),
-                    output.stdout,
-                    output.stderr,
-                ))
             }
         }
+        let output = command(operation).output_checked().await?;
+        Ok((
+            Box::pin(async move {
+                output.status.await?;
+                Ok(())
+            }),
+            output.stdout,
+            output.stderr,
+        ))
+    }
+}
+
+/// The command `operation` runs, shared by [`OperationType::apply`] and
+/// [`OperationType::script`].
+fn command(operation: &PodmanOperation) -> Command {
+    match operation {
+        PodmanOperation::Pull { images } => {
+            let mut cmd = Command::new("podman");
+            cmd.arg("pull").arg("--").args(images);
+            cmd
+        }
+        PodmanOperation::Create {
+            name,
+            image,
+            command,
+            env,
+            ports,
+            volumes,
+            restart_policy,
+            config_hash,
+        } => {
+            let mut cmd = Command::new("podman");
+            cmd.arg("create")
+                .arg("--pull=missing")
+                .arg("--name")
+                .arg(name)
+                .arg("--label")
+                .arg(format!("{CONFIG_HASH_LABEL}={config_hash}"));
+            if let Some(policy) = restart_policy {
+                cmd.arg("--restart").arg(policy);
+            }
+            for value in env {
+                cmd.arg("-e").arg(value);
+            }
+            for mapping in ports {
+                cmd.arg("-p").arg(mapping);
+            }
+            for mapping in volumes {
+                cmd.arg("-v").arg(mapping);
+            }
+            cmd.arg("--").arg(image);
+            if let Some(command) = command {
+                cmd.args(command);
+            }
+            cmd
+        }
+        PodmanOperation::Start { name } => {
+            let mut cmd = Command::new("podman");
+            cmd.arg("start").arg("--").arg(name);
+            cmd
+        }
+        PodmanOperation::Stop { name } => {
+            let mut cmd = Command::new("podman");
+            cmd.arg("stop").arg("--").arg(name);
+            cmd
+        }
+        PodmanOperation::Remove { name } => {
+            let mut cmd = Command::new("podman");
+            cmd.arg("rm").arg("--force").arg("--").arg(name);
+            cmd
+        }
     }
 }
@@ -0,0 +1,219 @@
+//! Rust toolchains, components and targets, installed with rustup.
+//!
+//! rustup runs as the apply user and installs into their `~/.rustup`, so it
+//! must be on that user's `PATH` (usually via `~/.cargo/bin`).
+
+use async_trait::async_trait;
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_view::impl_display_render;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    pin::Pin,
+};
+use thiserror::Error;
+use tokio::process::{ChildStderr, ChildStdout};
+use tracing::info;
+
+use crate::OperationType;
+
+#[derive(Debug, Clone)]
+pub enum RustupOperation {
+    /// Install `toolchain` with the minimal profile, plus `components` and
+    /// `targets`.
+    Install {
+        toolchain: String,
+        components: Vec<String>,
+        targets: Vec<String>,
+    },
+    /// Add `components` to the installed `toolchain`.
+    AddComponents {
+        toolchain: String,
+        components: Vec<String>,
+    },
+    /// Add `targets` to the installed `toolchain`.
+    AddTargets {
+        toolchain: String,
+        targets: Vec<String>,
+    },
+}
+
+impl Display for RustupOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RustupOperation::Install {
+                toolchain,
+                components,
+                targets,
+            } => write!(
+                f,
+                "Rustup::Install(toolchain = {toolchain}, components = [{}], targets = [{}])",
+                components.join(", "),
+                targets.join(", ")
+            ),
+            RustupOperation::AddComponents {
+                toolchain,
+                components,
+            } => write!(
+                f,
+                "Rustup::AddComponents(toolchain = {toolchain}, components = [{}])",
+                components.join(", ")
+            ),
+            RustupOperation::AddTargets { toolchain, targets } => write!(
+                f,
+                "Rustup::AddTargets(toolchain = {toolchain}, targets = [{}])",
+                targets.join(", ")
+            ),
+        }
+    }
+}
+
+impl_display_render!(RustupOperation);
+
+#[derive(Error, Debug)]
+pub enum RustupApplyError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+}
+
+/// What one epoch asks of one toolchain.
+#[derive(Default)]
+struct ToolchainWants {
+    install: bool,
+    components: BTreeSet<String>,
+    targets: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Rustup;
+
+#[async_trait]
+impl OperationType for Rustup {
+    type Operation = RustupOperation;
+
+    // Merged per toolchain: a toolchain being installed takes the epoch's
+    // components and targets for it along in the one `rustup toolchain
+    // install`; otherwise they're one `rustup component add` and one `rustup
+    // target add` each.
+    fn merge(operations: Vec<Self::Operation>) -> Vec<Self::Operation> {
+        let mut toolchains: BTreeMap<String, ToolchainWants> = BTreeMap::new();
+        for operation in operations {
+            match operation {
+                RustupOperation::Install {
+                    toolchain,
+                    components,
+                    targets,
+                } => {
+                    let wants = toolchains.entry(toolchain).or_default();
+                    wants.install = true;
+                    wants.components.extend(components);
+                    wants.targets.extend(targets);
+                }
+                RustupOperation::AddComponents {
+                    toolchain,
+                    components,
+                } => toolchains
+                    .entry(toolchain)
+                    .or_default()
+                    .components
+                    .extend(components),
+                RustupOperation::AddTargets { toolchain, targets } => toolchains
+                    .entry(toolchain)
+                    .or_default()
+                    .targets
+                    .extend(targets),
+            }
+        }
+
+        let mut merged = Vec::new();
+        for (toolchain, wants) in toolchains {
+            let components: Vec<String> = wants.components.into_iter().collect();
+            let targets: Vec<String> = wants.targets.into_iter().collect();
+            if wants.install {
+                merged.push(RustupOperation::Install {
+                    toolchain,
+                    components,
+                    targets,
+                });
+                continue;
+            }
+            if !components.is_empty() {
+                merged.push(RustupOperation::AddComponents {
+                    toolchain: toolchain.clone(),
+                    components,
+                });
+            }
+            if !targets.is_empty() {
+                merged.push(RustupOperation::AddTargets { toolchain, targets });
+            }
+        }
+        merged
+    }
+
+    fn script(operation: &Self::Operation) -> Option<String> {
+        Some(command(operation).to_shell())
+    }
+
+    type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
+    type ApplyError = RustupApplyError;
+    type ApplyStdout = ChildStdout;
+    type ApplyStderr = ChildStderr;
+
+    async fn apply(
+        _ctx: &mut Context,
+        operation: &Self::Operation,
+    ) -> Result<(Self::ApplyOutput, Self::ApplyStdout, Self::ApplyStderr), Self::ApplyError> {
+        info!("[rustup] {}", operation);
+        let output = command(operation).output_checked().await?;
+        Ok((
+            Box::pin(async move {
+                output.status.await?;
+                Ok(())
+            }),
+            output.stdout,
+            output.stderr,
+        ))
+    }
+}
+
+/// The command `operation` runs, shared by [`OperationType::apply`] and
+/// [`OperationType::script`].
+fn command(operation: &RustupOperation) -> Command {
+    let mut cmd = Command::new("rustup");
+    match operation {
+        // `--no-self-update` so installing a toolchain never updates rustup
+        // itself as a side effect.
+        RustupOperation::Install {
+            toolchain,
+            components,
+            targets,
+        } => {
+            cmd.args(["toolchain", "install", "--no-self-update"])
+                .args(["--profile", "minimal"]);
+            if !components.is_empty() {
+                cmd.arg("--component").arg(components.join(","));
+            }
+            if !targets.is_empty() {
+                cmd.arg("--target").arg(targets.join(","));
+            }
+            cmd.arg("--").arg(toolchain);
+        }
+        RustupOperation::AddComponents {
+            toolchain,
+            components,
+        } => {
+            cmd.args(["component", "add", "--toolchain"])
+                .arg(toolchain)
+                .arg("--")
+                .args(components);
+        }
+        RustupOperation::AddTargets { toolchain, targets } => {
+            cmd.args(["target", "add", "--toolchain"])
+                .arg(toolchain)
+                .arg("--")
+                .args(targets);
+        }
+    }
+    cmd
+}
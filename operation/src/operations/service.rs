@@ -0,0 +1,243 @@
+//! Controlling systemd units through `systemctl`: starting, stopping,
+//! restarting, reloading, enabling and disabling them.
+//!
+//! Shared by every resource that acts on a unit: `@core/systemd` brings one
+//! to its declared state, and a file's `restarts`, a changed unit file or a
+//! WireGuard interface's config restart one after the change.
+
+use async_trait::async_trait;
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_view::impl_display_render;
+use std::{fmt::Display, pin::Pin};
+use thiserror::Error;
+use tokio::process::{ChildStderr, ChildStdout};
+use tracing::info;
+
+use crate::{OperationType, Severity};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServiceOperation {
+    Start {
+        unit: String,
+    },
+    Stop {
+        unit: String,
+    },
+    Restart {
+        unit: String,
+    },
+    /// Have the unit re-read its configuration without stopping, e.g.
+    /// nginx's graceful reload.
+    Reload {
+        unit: String,
+    },
+    Enable {
+        unit: String,
+    },
+    Disable {
+        unit: String,
+    },
+}
+
+impl ServiceOperation {
+    pub fn unit(&self) -> &str {
+        match self {
+            ServiceOperation::Start { unit }
+            | ServiceOperation::Stop { unit }
+            | ServiceOperation::Restart { unit }
+            | ServiceOperation::Reload { unit }
+            | ServiceOperation::Enable { unit }
+            | ServiceOperation::Disable { unit } => unit,
+        }
+    }
+
+    fn verb(&self) -> &'static str {
+        match self {
+            ServiceOperation::Start { .. } => "start",
+            ServiceOperation::Stop { .. } => "stop",
+            ServiceOperation::Restart { .. } => "restart",
+            ServiceOperation::Reload { .. } => "reload",
+            ServiceOperation::Enable { .. } => "enable",
+            ServiceOperation::Disable { .. } => "disable",
+        }
+    }
+}
+
+impl Display for ServiceOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ServiceOperation::Start { .. } => "Start",
+            ServiceOperation::Stop { .. } => "Stop",
+            ServiceOperation::Restart { .. } => "Restart",
+            ServiceOperation::Reload { .. } => "Reload",
+            ServiceOperation::Enable { .. } => "Enable",
+            ServiceOperation::Disable { .. } => "Disable",
+        };
+        write!(f, "Service::{name}({})", self.unit())
+    }
+}
+
+impl_display_render!(ServiceOperation);
+
+#[derive(Error, Debug)]
+pub enum ServiceApplyError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+}
+
+#[derive(Debug, Clone)]
+pub struct Service;
+
+#[async_trait]
+impl OperationType for Service {
+    type Operation = ServiceOperation;
+
+    // Several changes can ask the same unit to pick them up in one epoch,
+    // e.g. two config files both naming nginx in `restarts`: one restart, or
+    // one reload, picks up all of them. A restart re-reads the configuration
+    // too, so it also stands in for any reload of its unit, in the place of
+    // whichever came first.
+    //
+    // Other verbs pass through in order: a start and a stop of one unit in
+    // one epoch would be two resources fighting over it, and dropping either
+    // would hide that.
+    fn merge(operations: Vec<Self::Operation>) -> Vec<Self::Operation> {
+        let mut merged: Vec<Self::Operation> = Vec::with_capacity(operations.len());
+        for operation in operations {
+            match &operation {
+                ServiceOperation::Restart { unit } => {
+                    let reload = merged.iter().position(|merged| {
+                        matches!(merged, ServiceOperation::Reload { unit: other } if other == unit)
+                    });
+                    if let Some(reload) = reload {
+                        merged[reload] = operation;
+                        continue;
+                    }
+                    if merged.contains(&operation) {
+                        continue;
+                    }
+                }
+                ServiceOperation::Reload { unit } => {
+                    let picked_up = merged.iter().any(|merged| {
+                        matches!(
+                            merged,
+                            ServiceOperation::Restart { unit: other }
+                                | ServiceOperation::Reload { unit: other }
+                                if other == unit
+                        )
+                    });
+                    if picked_up {
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+            merged.push(operation);
+        }
+        merged
+    }
+
+    fn script(operation: &Self::Operation) -> Option<String> {
+        Some(command(operation).to_shell())
+    }
+
+    fn severity(operation: &Self::Operation) -> Severity {
+        match operation {
+            ServiceOperation::Stop { .. }
+            | ServiceOperation::Restart { .. }
+            | ServiceOperation::Disable { .. } => Severity::Disruptive,
+            ServiceOperation::Start { .. }
+            | ServiceOperation::Reload { .. }
+            | ServiceOperation::Enable { .. } => Severity::Safe,
+        }
+    }
+
+    type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
+    type ApplyError = ServiceApplyError;
+    type ApplyStdout = ChildStdout;
+    type ApplyStderr = ChildStderr;
+
+    async fn apply(
+        _ctx: &mut Context,
+        operation: &Self::Operation,
+    ) -> Result<(Self::ApplyOutput, Self::ApplyStdout, Self::ApplyStderr), Self::ApplyError> {
+        info!("[service] {}: {}", operation.verb(), operation.unit());
+        let output = command(operation).output_checked().await?;
+        Ok((
+            Box::pin(async move {
+                output.status.await?;
+                Ok(())
+            }),
+            output.stdout,
+            output.stderr,
+        ))
+    }
+}
+
+/// The `systemctl` command `operation` runs, shared by
+/// [`OperationType::apply`] and [`OperationType::script`].
+fn command(operation: &ServiceOperation) -> Command {
+    let mut cmd = Command::new("systemctl");
+    cmd.arg("--no-ask-password")
+        .arg(operation.verb())
+        .arg(operation.unit());
+    cmd.sudo()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn restart(unit: &str) -> ServiceOperation {
+        ServiceOperation::Restart { unit: unit.into() }
+    }
+
+    fn reload(unit: &str) -> ServiceOperation {
+        ServiceOperation::Reload { unit: unit.into() }
+    }
+
+    #[test]
+    fn merge_collapses_repeats_of_a_unit() {
+        let merged = Service::merge(vec![
+            restart("nginx.service"),
+            reload("caddy.service"),
+            restart("nginx.service"),
+            reload("caddy.service"),
+        ]);
+        assert_eq!(
+            merged,
+            vec![restart("nginx.service"), reload("caddy.service")]
+        );
+    }
+
+    #[test]
+    fn merge_restart_stands_in_for_a_reload() {
+        let merged = Service::merge(vec![
+            reload("nginx.service"),
+            reload("caddy.service"),
+            restart("nginx.service"),
+        ]);
+        assert_eq!(
+            merged,
+            vec![restart("nginx.service"), reload("caddy.service")]
+        );
+
+        let merged = Service::merge(vec![restart("nginx.service"), reload("nginx.service")]);
+        assert_eq!(merged, vec![restart("nginx.service")]);
+    }
+
+    #[test]
+    fn merge_keeps_other_verbs() {
+        let operations = vec![
+            ServiceOperation::Enable {
+                unit: "nginx.service".into(),
+            },
+            ServiceOperation::Start {
+                unit: "nginx.service".into(),
+            },
+            reload("nginx.service"),
+        ];
+        assert_eq!(Service::merge(operations.clone()), operations);
+    }
+}
@@ -0,0 +1,25 @@
+//! Files staged in the cache dir, for `sudo install` to copy where only root
+//! can write.
+
+use std::path::PathBuf;
+
+use lusid_ctx::Context;
+use lusid_fs::{self as fs, FsError};
+
+/// Write `content` to a file named for `name` in the cache dir's `subdir`,
+/// returning its path. The caller removes it once installed.
+///
+/// The file's name also holds this process's id, as every `lusid-apply` on
+/// the host shares the cache dir.
+pub(crate) async fn stage_file(
+    ctx: &Context,
+    subdir: &str,
+    name: &str,
+    content: &[u8],
+) -> Result<PathBuf, FsError> {
+    let stage_dir = ctx.paths().cache_dir().join(subdir);
+    fs::create_dir(&stage_dir).await?;
+    let stage_path = stage_dir.join(format!("{name}.{}", std::process::id()));
+    fs::write_file_atomic(&stage_path, content).await?;
+    Ok(stage_path)
+}
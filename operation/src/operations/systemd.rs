@@ -1,6 +1,7 @@
 use async_trait::async_trait;
 use lusid_cmd::{Command, CommandError};
 use lusid_ctx::Context;
+use lusid_fs::FsError;
 use lusid_view::impl_display_render;
 use std::{fmt::Display, pin::Pin};
 use thiserror::Error;
@@ -8,24 +9,40 @@ use tokio::process::{ChildStderr, ChildStdout};
 use tracing::info;
 
 use crate::OperationType;
+use crate::operations::file::FilePath;
+use crate::operations::stage::stage_file;
 
-#[derive(Debug, Clone)]
+const STAGE_SUBDIR: &str = "systemd";
+
+/// Unit files, emitted by `@core/systemd-unit`. Starting, stopping and the
+/// like are [`ServiceOperation`](crate::operations::service::ServiceOperation)s.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SystemdOperation {
-    Enable { name: String },
-    Disable { name: String },
-    Start { name: String },
-    Stop { name: String },
+    /// Stage `content` to a user-writable cache, then `sudo install` it to
+    /// `path` with mode 0644.
+    WriteUnit {
+        name: String,
+        path: FilePath,
+        content: String,
+    },
+
+    /// `systemctl daemon-reload`, so systemd picks up changed unit files.
+    DaemonReload,
 }
 
 impl Display for SystemdOperation {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            SystemdOperation::Enable { name } => write!(f, "Systemd::Enable({name})"),
-            SystemdOperation::Disable { name } => {
-                write!(f, "Systemd::Disable({name})")
-            }
-            SystemdOperation::Start { name } => write!(f, "Systemd::Start({name})"),
-            SystemdOperation::Stop { name } => write!(f, "Systemd::Stop({name})"),
+            SystemdOperation::WriteUnit {
+                name,
+                path,
+                content,
+            } => write!(
+                f,
+                "Systemd::WriteUnit(name = {name}, path = {path}, {} bytes)",
+                content.len()
+            ),
+            SystemdOperation::DaemonReload => write!(f, "Systemd::DaemonReload"),
         }
     }
 }
@@ -36,6 +53,9 @@ impl_display_render!(SystemdOperation);
 pub enum SystemdApplyError {
     #[error(transparent)]
     Command(#[from] CommandError),
+
+    #[error(transparent)]
+    Fs(#[from] FsError),
 }
 
 #[derive(Debug, Clone)]
@@ -45,12 +65,22 @@ pub struct Systemd;
 impl OperationType for Systemd {
     type Operation = SystemdOperation;
 
-    // Note(cc): merge is a no-op. `systemctl enable|start` accepts multiple units but
-    // the operations here are per-verb-per-unit; coalescing would save at most a fork
-    // per unit, which isn't worth the extra complexity while plans manage a handful
-    // of units at a time. Revisit if plans start listing dozens of systemd units.
+    // One daemon-reload per epoch picks up every unit file written before it.
     fn merge(operations: Vec<Self::Operation>) -> Vec<Self::Operation> {
-        operations
+        let mut merged: Vec<Self::Operation> = Vec::with_capacity(operations.len());
+        for operation in operations {
+            if operation == SystemdOperation::DaemonReload && merged.contains(&operation) {
+                continue;
+            }
+            merged.push(operation);
+        }
+        merged
+    }
+
+    // Unit files are staged in-process before `install`, like apt-repo's
+    // source lists.
+    fn script(operation: &Self::Operation) -> Option<String> {
+        command(operation).map(|cmd| cmd.to_shell())
     }
 
     type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
@@ -59,23 +89,41 @@ impl OperationType for Systemd {
     type ApplyStderr = ChildStderr;
 
     async fn apply(
-        _ctx: &mut Context,
+        ctx: &mut Context,
         operation: &Self::Operation,
     ) -> Result<(Self::ApplyOutput, Self::ApplyStdout, Self::ApplyStderr), Self::ApplyError> {
-        let (verb, name) = match operation {
-            SystemdOperation::Enable { name } => ("enable", name),
-            SystemdOperation::Disable { name } => ("disable", name),
-            SystemdOperation::Start { name } => ("start", name),
-            SystemdOperation::Stop { name } => ("stop", name),
+        let (mut cmd, stage_path) = match operation {
+            SystemdOperation::WriteUnit {
+                name,
+                path,
+                content,
+            } => {
+                info!(name = %name, path = %path, "[systemd] write unit");
+
+                let stage_path = stage_file(ctx, STAGE_SUBDIR, name, content.as_bytes()).await?;
+
+                let mut cmd = Command::new("install");
+                cmd.arg("-m")
+                    .arg("0644")
+                    .arg(&stage_path)
+                    .arg(path.as_path());
+                (cmd.sudo(), Some(stage_path))
+            }
+            SystemdOperation::DaemonReload => {
+                info!("[systemd] daemon-reload");
+                let cmd = command(operation).expect("daemon-reload is a systemctl verb");
+                (cmd, None)
+            }
         };
-        info!("[systemd] {verb}: {name}");
 
-        let mut cmd = Command::new("systemctl");
-        cmd.arg("--no-ask-password").arg(verb).arg(name);
-        let output = cmd.sudo().output().await?;
+        let output = cmd.output_checked().await?;
         Ok((
             Box::pin(async move {
-                output.status.await?;
+                let result = output.status.await;
+                if let Some(stage_path) = stage_path {
+                    let _ = tokio::fs::remove_file(&stage_path).await;
+                }
+                result?;
                 Ok(())
             }),
             output.stdout,
@@ -83,3 +131,15 @@ impl OperationType for Systemd {
         ))
     }
 }
+
+/// The `systemctl` command `operation` runs, shared by
+/// [`OperationType::apply`] and [`OperationType::script`]. `None` for
+/// `WriteUnit`, which isn't a `systemctl` verb.
+fn command(operation: &SystemdOperation) -> Option<Command> {
+    if matches!(operation, SystemdOperation::WriteUnit { .. }) {
+        return None;
+    }
+    let mut cmd = Command::new("systemctl");
+    cmd.arg("--no-ask-password").arg("daemon-reload");
+    Some(cmd.sudo())
+}
@@ -0,0 +1,106 @@
+//! The system clock's timezone and network time sync, managed with
+//! `timedatectl`.
+
+use async_trait::async_trait;
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_view::impl_display_render;
+use std::{fmt::Display, pin::Pin};
+use thiserror::Error;
+use tokio::process::{ChildStderr, ChildStdout};
+use tracing::info;
+
+use crate::{OperationType, Severity};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimeOperation {
+    /// Set the timezone, an IANA name like `Europe/Berlin`.
+    SetTimezone { timezone: String },
+    /// Turn network time sync on or off.
+    SetNtp { enabled: bool },
+}
+
+impl Display for TimeOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimeOperation::SetTimezone { timezone } => {
+                write!(f, "Time::SetTimezone({timezone})")
+            }
+            TimeOperation::SetNtp { enabled } => write!(f, "Time::SetNtp({enabled})"),
+        }
+    }
+}
+
+impl_display_render!(TimeOperation);
+
+#[derive(Error, Debug)]
+pub enum TimeApplyError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+}
+
+#[derive(Debug, Clone)]
+pub struct Time;
+
+#[async_trait]
+impl OperationType for Time {
+    type Operation = TimeOperation;
+
+    // A machine has one timezone, so of several operations setting it the
+    // last wins; the same for NTP.
+    fn merge(operations: Vec<Self::Operation>) -> Vec<Self::Operation> {
+        let mut timezone = None;
+        let mut ntp = None;
+        for operation in operations {
+            match operation {
+                TimeOperation::SetTimezone { .. } => timezone = Some(operation),
+                TimeOperation::SetNtp { .. } => ntp = Some(operation),
+            }
+        }
+        timezone.into_iter().chain(ntp).collect()
+    }
+
+    fn script(operation: &Self::Operation) -> Option<String> {
+        Some(command(operation).to_shell())
+    }
+
+    fn severity(_operation: &Self::Operation) -> Severity {
+        Severity::Safe
+    }
+
+    type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
+    type ApplyError = TimeApplyError;
+    type ApplyStdout = ChildStdout;
+    type ApplyStderr = ChildStderr;
+
+    async fn apply(
+        _ctx: &mut Context,
+        operation: &Self::Operation,
+    ) -> Result<(Self::ApplyOutput, Self::ApplyStdout, Self::ApplyStderr), Self::ApplyError> {
+        info!("[time] {}", operation);
+        let output = command(operation).output_checked().await?;
+        Ok((
+            Box::pin(async move {
+                output.status.await?;
+                Ok(())
+            }),
+            output.stdout,
+            output.stderr,
+        ))
+    }
+}
+
+/// The `timedatectl` command `operation` runs, shared by
+/// [`OperationType::apply`] and [`OperationType::script`].
+fn command(operation: &TimeOperation) -> Command {
+    let mut cmd = Command::new("timedatectl");
+    match operation {
+        TimeOperation::SetTimezone { timezone } => {
+            cmd.arg("set-timezone").arg(timezone);
+        }
+        TimeOperation::SetNtp { enabled } => {
+            cmd.arg("set-ntp").arg(enabled.to_string());
+        }
+    }
+    cmd.sudo()
+}
@@ -0,0 +1,213 @@
+//! TLS certificates and their private keys: self-signed with openssl, or
+//! issued by an ACME CA through certbot.
+//!
+//! Either way the pair is written as root, the key with mode 0600 and the
+//! certificate with mode 0644. A self-signed pair is generated beside its
+//! paths and moved into place, so a failed run leaves the old pair as it was.
+//! An ACME pair is copied out of certbot's `/etc/letsencrypt/live/<name>/`,
+//! where `<name>` is the first domain.
+
+use async_trait::async_trait;
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_view::impl_display_render;
+use std::{fmt::Display, net::IpAddr, pin::Pin};
+use thiserror::Error;
+use tokio::process::{ChildStderr, ChildStdout};
+use tracing::info;
+
+use crate::OperationType;
+use crate::operations::file::FilePath;
+
+/// Emitted by `@core/tls-cert`, whether the pair is missing, expiring or for
+/// other names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TlsCertOperation {
+    /// Generate a P-256 key and a certificate for `names` signed by it,
+    /// valid for `days`. The first name is also the subject's common name.
+    SelfSigned {
+        cert_path: FilePath,
+        key_path: FilePath,
+        names: Vec<String>,
+        days: u32,
+    },
+    /// Have certbot issue a certificate for `domains`, answering the HTTP
+    /// challenge from its own server, or by writing into `webroot` for a web
+    /// server already on port 80. certbot keeps a certificate it already has
+    /// for these domains until its own renewal window, unless `renew`.
+    Acme {
+        cert_path: FilePath,
+        key_path: FilePath,
+        domains: Vec<String>,
+        email: String,
+        webroot: Option<String>,
+        renew: bool,
+    },
+}
+
+impl Display for TlsCertOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TlsCertOperation::SelfSigned {
+                cert_path,
+                key_path,
+                names,
+                days,
+            } => write!(
+                f,
+                "TlsCert::SelfSigned(cert = {cert_path}, key = {key_path}, names = [{}], days = {days})",
+                names.join(", ")
+            ),
+            TlsCertOperation::Acme {
+                cert_path,
+                key_path,
+                domains,
+                email,
+                webroot,
+                renew,
+            } => {
+                write!(
+                    f,
+                    "TlsCert::Acme(cert = {cert_path}, key = {key_path}, domains = [{}], email = {email}",
+                    domains.join(", ")
+                )?;
+                if let Some(webroot) = webroot {
+                    write!(f, ", webroot = {webroot}")?;
+                }
+                if *renew {
+                    write!(f, ", renew")?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl_display_render!(TlsCertOperation);
+
+#[derive(Error, Debug)]
+pub enum TlsCertApplyError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+}
+
+#[derive(Debug, Clone)]
+pub struct TlsCert;
+
+#[async_trait]
+impl OperationType for TlsCert {
+    type Operation = TlsCertOperation;
+
+    // Each pair is its own.
+    fn merge(operations: Vec<Self::Operation>) -> Vec<Self::Operation> {
+        operations
+    }
+
+    fn script(operation: &Self::Operation) -> Option<String> {
+        Some(command(operation).to_shell())
+    }
+
+    type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
+    type ApplyError = TlsCertApplyError;
+    type ApplyStdout = ChildStdout;
+    type ApplyStderr = ChildStderr;
+
+    async fn apply(
+        _ctx: &mut Context,
+        operation: &Self::Operation,
+    ) -> Result<(Self::ApplyOutput, Self::ApplyStdout, Self::ApplyStderr), Self::ApplyError> {
+        match operation {
+            TlsCertOperation::SelfSigned {
+                cert_path, names, ..
+            } => {
+                info!(cert = %cert_path, "[tls-cert] self-sign: {}", names.join(", "));
+            }
+            TlsCertOperation::Acme {
+                cert_path, domains, ..
+            } => {
+                info!(cert = %cert_path, "[tls-cert] acme: {}", domains.join(", "));
+            }
+        }
+        let output = command(operation).output_checked().await?;
+        Ok((
+            Box::pin(async move {
+                output.status.await?;
+                Ok(())
+            }),
+            output.stdout,
+            output.stderr,
+        ))
+    }
+}
+
+// Note(cc): `-addext` needs OpenSSL 1.1.1 or later.
+const SELF_SIGNED_SCRIPT: &str = r#"umask 077 && openssl req -x509 -newkey ec -pkeyopt ec_paramgen_curve:prime256v1 -nodes -keyout "$1.new" -out "$2.new" -days "$3" -subj "/CN=$4" -addext "subjectAltName=$5" && chmod 0644 "$2.new" && mv "$1.new" "$1" && mv "$2.new" "$2""#;
+
+// certbot's own arguments follow the three named ones.
+const ACME_SCRIPT: &str = r#"name="$1" cert="$2" key="$3" && shift 3 && certbot certonly --non-interactive --agree-tos --expand --cert-name "$name" "$@" && install -m 0644 "/etc/letsencrypt/live/$name/fullchain.pem" "$cert" && install -m 0600 "/etc/letsencrypt/live/$name/privkey.pem" "$key""#;
+
+/// The command `operation` runs, shared by [`OperationType::apply`] and
+/// [`OperationType::script`].
+fn command(operation: &TlsCertOperation) -> Command {
+    match operation {
+        TlsCertOperation::SelfSigned {
+            cert_path,
+            key_path,
+            names,
+            days,
+        } => {
+            let common_name = names.first().map(String::as_str).unwrap_or_default();
+            let mut cmd = Command::new_sh(SELF_SIGNED_SCRIPT);
+            cmd.arg("tls-cert")
+                .arg(key_path.as_path())
+                .arg(cert_path.as_path())
+                .arg(days.to_string())
+                .arg(common_name)
+                .arg(subject_alt_names(names));
+            cmd.sudo()
+        }
+        TlsCertOperation::Acme {
+            cert_path,
+            key_path,
+            domains,
+            email,
+            webroot,
+            renew,
+        } => {
+            let name = domains.first().map(String::as_str).unwrap_or_default();
+            let mut cmd = Command::new_sh(ACME_SCRIPT);
+            cmd.arg("tls-cert")
+                .arg(name)
+                .arg(cert_path.as_path())
+                .arg(key_path.as_path())
+                .arg(if *renew {
+                    "--force-renewal"
+                } else {
+                    "--keep-until-expiring"
+                })
+                .arg("--email")
+                .arg(email);
+            match webroot {
+                Some(webroot) => cmd.arg("--webroot").arg("-w").arg(webroot),
+                None => cmd.arg("--standalone"),
+            };
+            for domain in domains {
+                cmd.arg("-d").arg(domain);
+            }
+            cmd.sudo()
+        }
+    }
+}
+
+/// `names` as a subjectAltName extension's value: each an IP address entry
+/// if it parses as one, else a DNS entry.
+pub fn subject_alt_names(names: &[String]) -> String {
+    names
+        .iter()
+        .map(|name| match name.parse::<IpAddr>() {
+            Ok(ip) => format!("IP:{ip}"),
+            Err(_) => format!("DNS:{name}"),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
@@ -7,7 +7,7 @@ use thiserror::Error;
 use tokio::process::{ChildStderr, ChildStdout};
 use tracing::info;
 
-use crate::OperationType;
+use crate::{OperationType, Severity};
 
 use crate::operations::file::FilePath;
 
@@ -81,6 +81,19 @@ impl OperationType for User {
         operations
     }
 
+    fn script(operation: &Self::Operation) -> Option<String> {
+        Some(command(operation).to_shell())
+    }
+
+    // Even keeping the home directory, a recreated account can't get back
+    // the deleted one's password or files outside home.
+    fn severity(operation: &Self::Operation) -> Severity {
+        match operation {
+            UserOperation::Delete { .. } => Severity::Destructive,
+            _ => Severity::Safe,
+        }
+    }
+
     type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
     type ApplyError = UserApplyError;
     type ApplyStdout = ChildStdout;
@@ -91,118 +104,116 @@ impl OperationType for User {
         operation: &Self::Operation,
     ) -> Result<(Self::ApplyOutput, Self::ApplyStdout, Self::ApplyStderr), Self::ApplyError> {
         match operation {
-            UserOperation::Add {
-                name,
-                uid,
-                primary_group,
-                append_groups,
-                comment,
-                home,
-                shell,
-                system,
-                create_home,
-            } => {
+            UserOperation::Add { name, .. } => {
                 info!("[user] add: {}", name);
-                let mut cmd = Command::new("useradd");
-                if let Some(uid) = uid {
-                    cmd.arg("-u").arg(uid.to_string());
-                }
-                if let Some(group) = primary_group {
-                    cmd.arg("-g").arg(group);
-                }
-                if !append_groups.is_empty() {
-                    cmd.arg("-G").arg(append_groups.join(","));
-                }
-                if let Some(comment) = comment {
-                    cmd.arg("-c").arg(comment);
-                }
-                if let Some(home) = home {
-                    cmd.arg("-d").arg(home.as_path());
-                }
-                if let Some(shell) = shell {
-                    cmd.arg("-s").arg(shell);
-                }
-                if *system {
-                    cmd.arg("-r");
-                }
-                if *create_home {
-                    cmd.arg("-m");
-                } else {
-                    cmd.arg("-M");
-                }
-                cmd.arg("--").arg(name);
-                let output = cmd.sudo().output().await?;
-                Ok((
-                    Box::pin(async move {
-                        output.status.await?;
-                        Ok(())
-                    }),
-                    output.stdout,
-                    output.stderr,
-                ))
-            }
-            UserOperation::Modify {
-                name,
-                uid,
-                primary_group,
-                append_groups,
-                comment,
-                home,
-                shell,
-            } => {
+            }
+            UserOperation::Modify { name, .. } => {
                 info!("[user] modify: {}", name);
-                let mut cmd = Command::new("usermod");
-                if let Some(uid) = uid {
-                    cmd.arg("-u").arg(uid.to_string());
-                }
-                if let Some(group) = primary_group {
-                    cmd.arg("-g").arg(group);
-                }
-                if let Some(groups) = append_groups {
-                    // `-aG` appends rather than replacing: groups the user is already
-                    // a member of are untouched, including ones not listed here.
-                    cmd.arg("-aG").arg(groups.join(","));
-                }
-                if let Some(comment) = comment {
-                    cmd.arg("-c").arg(comment);
-                }
-                if let Some(home) = home {
-                    // Note(cc): we set the home path in /etc/passwd but don't pass `-m` to
-                    // move the existing home contents — that would touch user data, which is
-                    // out of scope for a declarative "this is the home dir" statement.
-                    cmd.arg("-d").arg(home.as_path());
-                }
-                if let Some(shell) = shell {
-                    cmd.arg("-s").arg(shell);
-                }
-                cmd.arg("--").arg(name);
-                let output = cmd.sudo().output().await?;
-                Ok((
-                    Box::pin(async move {
-                        output.status.await?;
-                        Ok(())
-                    }),
-                    output.stdout,
-                    output.stderr,
-                ))
             }
             UserOperation::Delete { name, remove_home } => {
                 info!("[user] delete: {} (remove_home = {})", name, remove_home);
-                let mut cmd = Command::new("userdel");
-                if *remove_home {
-                    cmd.arg("-r");
-                }
-                cmd.arg("--").arg(name);
-                let output = cmd.sudo().output().await?;
-                Ok((
-                    Box::pin(async move {
-                        output.status.await?;
-                        Ok(())
-                    }),
-                    output.stdout,
-                    output.stderr,
-                ))
             }
         }
+        let output = command(operation).output_checked().await?;
+        Ok((
+            Box::pin(async move {
+                output.status.await?;
+                Ok(())
+            }),
+            output.stdout,
+            output.stderr,
+        ))
+    }
+}
+
+/// The command `operation` runs, shared by [`OperationType::apply`] and
+/// [`OperationType::script`].
+fn command(operation: &UserOperation) -> Command {
+    match operation {
+        UserOperation::Add {
+            name,
+            uid,
+            primary_group,
+            append_groups,
+            comment,
+            home,
+            shell,
+            system,
+            create_home,
+        } => {
+            let mut cmd = Command::new("useradd");
+            if let Some(uid) = uid {
+                cmd.arg("-u").arg(uid.to_string());
+            }
+            if let Some(group) = primary_group {
+                cmd.arg("-g").arg(group);
+            }
+            if !append_groups.is_empty() {
+                cmd.arg("-G").arg(append_groups.join(","));
+            }
+            if let Some(comment) = comment {
+                cmd.arg("-c").arg(comment);
+            }
+            if let Some(home) = home {
+                cmd.arg("-d").arg(home.as_path());
+            }
+            if let Some(shell) = shell {
+                cmd.arg("-s").arg(shell);
+            }
+            if *system {
+                cmd.arg("-r");
+            }
+            if *create_home {
+                cmd.arg("-m");
+            } else {
+                cmd.arg("-M");
+            }
+            cmd.arg("--").arg(name);
+            cmd.sudo()
+        }
+        UserOperation::Modify {
+            name,
+            uid,
+            primary_group,
+            append_groups,
+            comment,
+            home,
+            shell,
+        } => {
+            let mut cmd = Command::new("usermod");
+            if let Some(uid) = uid {
+                cmd.arg("-u").arg(uid.to_string());
+            }
+            if let Some(group) = primary_group {
+                cmd.arg("-g").arg(group);
+            }
+            if let Some(groups) = append_groups {
+                // `-aG` appends rather than replacing: groups the user is already
+                // a member of are untouched, including ones not listed here.
+                cmd.arg("-aG").arg(groups.join(","));
+            }
+            if let Some(comment) = comment {
+                cmd.arg("-c").arg(comment);
+            }
+            if let Some(home) = home {
+                // Note(cc): we set the home path in /etc/passwd but don't pass `-m` to
+                // move the existing home contents — that would touch user data, which is
+                // out of scope for a declarative "this is the home dir" statement.
+                cmd.arg("-d").arg(home.as_path());
+            }
+            if let Some(shell) = shell {
+                cmd.arg("-s").arg(shell);
+            }
+            cmd.arg("--").arg(name);
+            cmd.sudo()
+        }
+        UserOperation::Delete { name, remove_home } => {
+            let mut cmd = Command::new("userdel");
+            if *remove_home {
+                cmd.arg("-r");
+            }
+            cmd.arg("--").arg(name);
+            cmd.sudo()
+        }
     }
 }
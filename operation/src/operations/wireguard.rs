@@ -0,0 +1,151 @@
+//! WireGuard interfaces' wg-quick configs, under `/etc/wireguard/`.
+//!
+//! A config can hold preshared keys, so it's piped to `sudo install` rather
+//! than staged in the cache dir like systemd's and networkd's files, and it's
+//! shown by its size alone.
+
+use async_trait::async_trait;
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_view::impl_display_render;
+use std::{fmt::Display, pin::Pin};
+use thiserror::Error;
+use tokio::io::AsyncWriteExt;
+use tokio::process::{ChildStderr, ChildStdout};
+use tracing::info;
+
+use crate::operations::file::FilePath;
+use crate::{OperationType, Severity};
+
+/// `WriteConfig` is emitted by `@core/wireguard`, which restarts the
+/// interface with [`ServiceOperation::Restart`](crate::operations::service::ServiceOperation::Restart).
+#[derive(Clone, PartialEq, Eq)]
+pub enum WireguardOperation {
+    /// Write `content` to `path` with mode 0600, through `sudo install`'s
+    /// stdin. `interface` is the interface it configures, e.g. `wg0`.
+    WriteConfig {
+        interface: String,
+        path: FilePath,
+        content: String,
+    },
+}
+
+// Never show the config via Debug: it holds preshared keys.
+impl std::fmt::Debug for WireguardOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WireguardOperation::WriteConfig {
+                interface, path, ..
+            } => f
+                .debug_struct("WriteConfig")
+                .field("interface", interface)
+                .field("path", path)
+                .field("content", &format_args!("<redacted>"))
+                .finish(),
+        }
+    }
+}
+
+impl Display for WireguardOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WireguardOperation::WriteConfig {
+                interface,
+                path,
+                content,
+            } => write!(
+                f,
+                "Wireguard::WriteConfig(interface = {interface}, path = {path}, {} bytes)",
+                content.len()
+            ),
+        }
+    }
+}
+
+impl_display_render!(WireguardOperation);
+
+#[derive(Error, Debug)]
+pub enum WireguardApplyError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+
+    #[error("failed to write wireguard config: {0}")]
+    WriteConfig(#[source] std::io::Error),
+}
+
+#[derive(Debug, Clone)]
+pub struct Wireguard;
+
+#[async_trait]
+impl OperationType for Wireguard {
+    type Operation = WireguardOperation;
+
+    fn merge(operations: Vec<Self::Operation>) -> Vec<Self::Operation> {
+        operations
+    }
+
+    // The config is written in-process, and a script would show its keys.
+    fn script(_operation: &Self::Operation) -> Option<String> {
+        None
+    }
+
+    fn severity(operation: &Self::Operation) -> Severity {
+        match operation {
+            WireguardOperation::WriteConfig { .. } => Severity::Safe,
+        }
+    }
+
+    type ApplyOutput = Pin<Box<dyn Future<Output = Result<(), Self::ApplyError>> + Send + 'static>>;
+    type ApplyError = WireguardApplyError;
+    type ApplyStdout = ChildStdout;
+    type ApplyStderr = ChildStderr;
+
+    async fn apply(
+        _ctx: &mut Context,
+        operation: &Self::Operation,
+    ) -> Result<(Self::ApplyOutput, Self::ApplyStdout, Self::ApplyStderr), Self::ApplyError> {
+        let WireguardOperation::WriteConfig {
+            interface,
+            path,
+            content,
+        } = operation;
+        info!(interface = %interface, path = %path, "[wireguard] write config");
+
+        let mut cmd = Command::new("install");
+        cmd.arg("-m")
+            .arg("0600")
+            .arg("/dev/stdin")
+            .arg(path.as_path());
+        let (mut stdin, output) = cmd.sudo().output_with_stdin_checked().await?;
+        stdin
+            .write_all(content.as_bytes())
+            .await
+            .map_err(WireguardApplyError::WriteConfig)?;
+        drop(stdin);
+        Ok((
+            Box::pin(async move {
+                output.status.await?;
+                Ok(())
+            }),
+            output.stdout,
+            output.stderr,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_redacts_the_config() {
+        let operation = WireguardOperation::WriteConfig {
+            interface: "wg0".into(),
+            path: FilePath::new("/etc/wireguard/wg0.conf"),
+            content: "[Peer]\nPresharedKey = /UwcSPg38hW/D9Y3tcS1FOV0K1wuURMbS0sesJEP5ak=\n".into(),
+        };
+        let shown = format!("{operation:?}");
+        assert!(shown.contains("content: <redacted>"));
+        assert!(!shown.contains("/UwcSPg38hW"));
+    }
+}
@@ -0,0 +1,457 @@
+//! Golden snapshot of every operation variant, as rendered through
+//! [`Operation`]. See `lusid_view::Snapshot` for how to update it.
+
+use lusid_view::Snapshot;
+
+use crate::Operation;
+use crate::operations::{
+    apk::ApkOperation,
+    apt::AptOperation,
+    apt_repo::AptRepoOperation,
+    aur::AurOperation,
+    brew::BrewOperation,
+    command::{CommandExecutor, CommandOperation},
+    cron::CronOperation,
+    directory::DirectoryOperation,
+    dnf::DnfOperation,
+    file::{FileGroup, FileMode, FileOperation, FilePath, FileSource, FileUser},
+    firewall::{FirewallAction, FirewallOperation, FirewallProtocol, FirewallRule},
+    git::{GitConfigScope, GitOperation, GitOwner},
+    gpg::{GpgKeySource, GpgKeyring, GpgOperation},
+    group::GroupOperation,
+    launchd::LaunchdOperation,
+    networkd::NetworkdOperation,
+    nix::NixOperation,
+    pacman::PacmanOperation,
+    pip::{PipOperation, PipTarget},
+    podman::PodmanOperation,
+    rustup::RustupOperation,
+    service::ServiceOperation,
+    systemd::SystemdOperation,
+    time::TimeOperation,
+    tls_cert::TlsCertOperation,
+    user::UserOperation,
+    wireguard::WireguardOperation,
+};
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+#[test]
+fn operations() {
+    let path = || FilePath::new("/home/me/.gitconfig");
+    let source = || FilePath::new("/home/me/dotfiles/gitconfig");
+    let dir_path = || FilePath::new("/home/me/.config/nvim");
+    let dir_source = || FilePath::new("/home/me/dotfiles/nvim");
+    let repo_path = || FilePath::new("/home/me/src/lusid");
+    let venv = || FilePath::new("/home/me/.venvs/tools");
+
+    Snapshot::new()
+        .section("apt")
+        .render(&Operation::Apt(AptOperation::Update))
+        .render(&Operation::Apt(AptOperation::Install {
+            packages: strings(&["curl", "git"]),
+        }))
+        .section("apt-repo")
+        .render(&Operation::AptRepo(AptRepoOperation::EnsureKeyringsDir {
+            path: FilePath::new("/etc/apt/keyrings"),
+        }))
+        .render(&Operation::AptRepo(AptRepoOperation::DownloadKey {
+            name: "docker".into(),
+            url: "https://download.docker.com/linux/debian/gpg".into(),
+            sha256: None,
+            path: FilePath::new("/etc/apt/keyrings/docker.asc"),
+        }))
+        .render(&Operation::AptRepo(AptRepoOperation::WriteSources {
+            name: "docker".into(),
+            path: FilePath::new("/etc/apt/sources.list.d/docker.sources"),
+            content: "Types: deb\n".into(),
+        }))
+        .section("pacman")
+        .render(&Operation::Pacman(PacmanOperation::Upgrade))
+        .render(&Operation::Pacman(PacmanOperation::Install {
+            packages: strings(&["neovim", "ripgrep"]),
+        }))
+        .section("aur")
+        .render(&Operation::Aur(AurOperation::Install {
+            helper: "paru".into(),
+            user: "mikey".into(),
+            packages: strings(&["visual-studio-code-bin", "zoom"]),
+        }))
+        .section("dnf")
+        .render(&Operation::Dnf(DnfOperation::Install {
+            packages: strings(&["neovim", "ripgrep"]),
+        }))
+        .render(&Operation::Dnf(DnfOperation::Remove {
+            packages: strings(&["nano"]),
+        }))
+        .section("apk")
+        .render(&Operation::Apk(ApkOperation::Install {
+            packages: strings(&["neovim", "ripgrep"]),
+        }))
+        .section("podman")
+        .render(&Operation::Podman(PodmanOperation::Pull {
+            images: strings(&["docker.io/library/nginx:1.27", "docker.io/library/redis:7"]),
+        }))
+        .render(&Operation::Podman(PodmanOperation::Create {
+            name: "web".into(),
+            image: "nginx:1.27".into(),
+            command: None,
+            env: Vec::new(),
+            ports: strings(&["8080:80"]),
+            volumes: Vec::new(),
+            restart_policy: None,
+            config_hash: "0123abc".into(),
+        }))
+        .render(&Operation::Podman(PodmanOperation::Start {
+            name: "web".into(),
+        }))
+        .render(&Operation::Podman(PodmanOperation::Stop {
+            name: "web".into(),
+        }))
+        .render(&Operation::Podman(PodmanOperation::Remove {
+            name: "web".into(),
+        }))
+        .section("file")
+        .render(&Operation::File(FileOperation::Write {
+            path: path(),
+            source: FileSource::Contents(b"[user]\n".as_slice().into()),
+        }))
+        .render(&Operation::File(FileOperation::Write {
+            path: path(),
+            source: FileSource::Path(source()),
+        }))
+        .render(&Operation::File(FileOperation::Write {
+            path: path(),
+            source: FileSource::Secret("github-token".into()),
+        }))
+        .render(&Operation::File(FileOperation::WriteMany {
+            files: vec![
+                (path(), b"[user]\n".as_slice().into()),
+                (
+                    FilePath::new("/home/me/.bashrc"),
+                    b"set -o vi\n".as_slice().into(),
+                ),
+            ],
+        }))
+        .render(&Operation::File(FileOperation::CreateSymlink {
+            source: source(),
+            path: path(),
+        }))
+        .render(&Operation::File(FileOperation::Remove { path: path() }))
+        .render(&Operation::File(FileOperation::ChangeMode {
+            path: path(),
+            mode: FileMode::new(0o644),
+        }))
+        .render(&Operation::File(FileOperation::ChangeOwner {
+            path: path(),
+            user: Some(FileUser::new("me")),
+            group: Some(FileGroup::new("staff")),
+        }))
+        .section("directory")
+        .render(&Operation::Directory(DirectoryOperation::Create {
+            path: dir_path(),
+        }))
+        .render(&Operation::Directory(DirectoryOperation::CreateSymlink {
+            source: dir_source(),
+            path: dir_path(),
+        }))
+        .render(&Operation::Directory(DirectoryOperation::CopyTree {
+            source: dir_source(),
+            path: dir_path(),
+        }))
+        .render(&Operation::Directory(DirectoryOperation::Remove {
+            path: dir_path(),
+        }))
+        .render(&Operation::Directory(DirectoryOperation::ChangeMode {
+            path: dir_path(),
+            mode: FileMode::new(0o755),
+        }))
+        .render(&Operation::Directory(DirectoryOperation::ChangeOwner {
+            path: dir_path(),
+            user: None,
+            group: None,
+        }))
+        .section("command")
+        .render(&Operation::Command(CommandOperation {
+            command: "cargo install ripgrep".into(),
+            executor: CommandExecutor::Direct,
+        }))
+        .render(&Operation::Command(CommandOperation {
+            command: "command -v rg || cargo install ripgrep".into(),
+            executor: CommandExecutor::Shell,
+        }))
+        .section("git")
+        .render(&Operation::Git(GitOperation::Clone {
+            repo: "https://github.com/ahdinosaur/lusid".into(),
+            path: repo_path(),
+            depth: None,
+            owner: GitOwner::default(),
+        }))
+        .render(&Operation::Git(GitOperation::Clone {
+            repo: "https://github.com/ahdinosaur/lusid".into(),
+            path: repo_path(),
+            depth: Some(1),
+            owner: GitOwner {
+                user: Some("me".into()),
+                group: Some("me".into()),
+            },
+        }))
+        .render(&Operation::Git(GitOperation::Fetch {
+            path: repo_path(),
+            owner: GitOwner::default(),
+        }))
+        .render(&Operation::Git(GitOperation::Checkout {
+            path: repo_path(),
+            version: "main".into(),
+            force: true,
+            owner: GitOwner::default(),
+        }))
+        .render(&Operation::Git(GitOperation::Pull {
+            path: repo_path(),
+            owner: GitOwner {
+                user: Some("me".into()),
+                group: None,
+            },
+        }))
+        .render(&Operation::Git(GitOperation::SetConfig {
+            scope: GitConfigScope::Global {
+                user: Some("me".into()),
+            },
+            key: "user.name".into(),
+            values: strings(&["Mikey"]),
+        }))
+        .render(&Operation::Git(GitOperation::SetConfig {
+            scope: GitConfigScope::System,
+            key: "credential.helper".into(),
+            values: strings(&["", "cache --timeout=3600"]),
+        }))
+        .render(&Operation::Git(GitOperation::UnsetConfig {
+            scope: GitConfigScope::Global { user: None },
+            key: "alias.co".into(),
+        }))
+        .section("systemd")
+        .render(&Operation::Systemd(SystemdOperation::WriteUnit {
+            name: "myapp.service".into(),
+            path: FilePath::new("/etc/systemd/system/myapp.service"),
+            content: "[Service]\nExecStart=/usr/bin/myapp\n".into(),
+        }))
+        .render(&Operation::Systemd(SystemdOperation::DaemonReload))
+        .section("service")
+        .render(&Operation::Service(ServiceOperation::Enable {
+            unit: "nginx.service".into(),
+        }))
+        .render(&Operation::Service(ServiceOperation::Disable {
+            unit: "nginx.service".into(),
+        }))
+        .render(&Operation::Service(ServiceOperation::Start {
+            unit: "nginx.service".into(),
+        }))
+        .render(&Operation::Service(ServiceOperation::Stop {
+            unit: "nginx.service".into(),
+        }))
+        .render(&Operation::Service(ServiceOperation::Restart {
+            unit: "nginx.service".into(),
+        }))
+        .render(&Operation::Service(ServiceOperation::Reload {
+            unit: "nginx.service".into(),
+        }))
+        .section("user")
+        .render(&Operation::User(UserOperation::Add {
+            name: "me".into(),
+            uid: Some(1000),
+            primary_group: None,
+            append_groups: strings(&["docker"]),
+            comment: None,
+            home: None,
+            shell: None,
+            system: false,
+            create_home: true,
+        }))
+        .render(&Operation::User(UserOperation::Modify {
+            name: "me".into(),
+            uid: None,
+            primary_group: Some("me".into()),
+            append_groups: None,
+            comment: None,
+            home: None,
+            shell: None,
+        }))
+        .render(&Operation::User(UserOperation::Delete {
+            name: "me".into(),
+            remove_home: false,
+        }))
+        .section("group")
+        .render(&Operation::Group(GroupOperation::Add {
+            name: "docker".into(),
+            gid: None,
+            system: true,
+        }))
+        .render(&Operation::Group(GroupOperation::Modify {
+            name: "docker".into(),
+            gid: Some(998),
+        }))
+        .render(&Operation::Group(GroupOperation::AddUser {
+            name: "docker".into(),
+            user: "me".into(),
+        }))
+        .render(&Operation::Group(GroupOperation::AddUserToGroups {
+            user: "me".into(),
+            groups: strings(&["docker", "wheel"]),
+        }))
+        .render(&Operation::Group(GroupOperation::Delete {
+            name: "docker".into(),
+        }))
+        .section("cron")
+        .render(&Operation::Cron(CronOperation::Set {
+            user: None,
+            name: "backup".into(),
+            job: "0 3 * * * /usr/local/bin/backup".into(),
+        }))
+        .render(&Operation::Cron(CronOperation::Set {
+            user: Some("me".into()),
+            name: "backup".into(),
+            job: "0 3 * * * /usr/local/bin/backup".into(),
+        }))
+        .render(&Operation::Cron(CronOperation::Remove {
+            user: Some("me".into()),
+            name: "backup".into(),
+        }))
+        .section("pip")
+        .render(&Operation::Pip(PipOperation::CreateVenv { path: venv() }))
+        .render(&Operation::Pip(PipOperation::Install {
+            target: PipTarget::Pipx,
+            requirements: strings(&["black==24.1.0", "httpie"]),
+        }))
+        .render(&Operation::Pip(PipOperation::Install {
+            target: PipTarget::Venv { path: venv() },
+            requirements: strings(&["requests"]),
+        }))
+        .section("rustup")
+        .render(&Operation::Rustup(RustupOperation::Install {
+            toolchain: "stable".into(),
+            components: strings(&["clippy", "rustfmt"]),
+            targets: strings(&["wasm32-unknown-unknown"]),
+        }))
+        .render(&Operation::Rustup(RustupOperation::AddComponents {
+            toolchain: "nightly".into(),
+            components: strings(&["miri"]),
+        }))
+        .render(&Operation::Rustup(RustupOperation::AddTargets {
+            toolchain: "stable".into(),
+            targets: strings(&["aarch64-unknown-linux-gnu"]),
+        }))
+        .section("brew")
+        .render(&Operation::Brew(BrewOperation::InstallFormulae {
+            formulae: strings(&["git", "ripgrep"]),
+        }))
+        .render(&Operation::Brew(BrewOperation::InstallCasks {
+            casks: strings(&["firefox"]),
+        }))
+        .section("nix")
+        .render(&Operation::Nix(NixOperation::Install {
+            installables: strings(&["nixpkgs#ripgrep", "github:helix-editor/helix"]),
+        }))
+        .render(&Operation::Nix(NixOperation::Remove {
+            elements: strings(&["fd"]),
+        }))
+        .section("launchd")
+        .render(&Operation::Launchd(LaunchdOperation::Enable {
+            domain: "system".into(),
+            label: "com.example.agent".into(),
+        }))
+        .render(&Operation::Launchd(LaunchdOperation::Disable {
+            domain: "gui/501".into(),
+            label: "com.example.agent".into(),
+        }))
+        .render(&Operation::Launchd(LaunchdOperation::Bootstrap {
+            domain: "system".into(),
+            plist: FilePath::new("/Library/LaunchDaemons/com.example.agent.plist"),
+        }))
+        .render(&Operation::Launchd(LaunchdOperation::Bootout {
+            domain: "system".into(),
+            label: "com.example.agent".into(),
+        }))
+        .section("firewall")
+        .render(&Operation::Firewall(FirewallOperation::Add {
+            rule: FirewallRule {
+                action: FirewallAction::Allow,
+                port: Some("22".into()),
+                protocol: Some(FirewallProtocol::Tcp),
+                from: None,
+            },
+        }))
+        .render(&Operation::Firewall(FirewallOperation::Add {
+            rule: FirewallRule {
+                action: FirewallAction::Allow,
+                port: Some("5432".into()),
+                protocol: Some(FirewallProtocol::Tcp),
+                from: Some("10.0.0.0/8".into()),
+            },
+        }))
+        .render(&Operation::Firewall(FirewallOperation::Delete {
+            rule: FirewallRule {
+                action: FirewallAction::Deny,
+                port: None,
+                protocol: None,
+                from: Some("203.0.113.4".into()),
+            },
+        }))
+        .section("time")
+        .render(&Operation::Time(TimeOperation::SetTimezone {
+            timezone: "Europe/Berlin".into(),
+        }))
+        .render(&Operation::Time(TimeOperation::SetNtp { enabled: true }))
+        .section("networkd")
+        .render(&Operation::Networkd(NetworkdOperation::WriteConfig {
+            name: "10-br0.netdev".into(),
+            path: FilePath::new("/etc/systemd/network/10-br0.netdev"),
+            content: "[NetDev]\nName=br0\nKind=bridge\n".into(),
+        }))
+        .render(&Operation::Networkd(NetworkdOperation::Reload))
+        .section("wireguard")
+        .render(&Operation::Wireguard(WireguardOperation::WriteConfig {
+            interface: "wg0".into(),
+            path: FilePath::new("/etc/wireguard/wg0.conf"),
+            content: "[Interface]\nListenPort = 51820\nPostUp = wg set %i private-key /etc/wireguard/wg0.key\n".into(),
+        }))
+        .section("tls-cert")
+        .render(&Operation::TlsCert(TlsCertOperation::SelfSigned {
+            cert_path: FilePath::new("/etc/ssl/lusid/home.lan.crt"),
+            key_path: FilePath::new("/etc/ssl/lusid/home.lan.key"),
+            names: strings(&["home.lan", "192.168.1.10"]),
+            days: 365,
+        }))
+        .render(&Operation::TlsCert(TlsCertOperation::Acme {
+            cert_path: FilePath::new("/etc/nginx/tls/example.com.crt"),
+            key_path: FilePath::new("/etc/nginx/tls/example.com.key"),
+            domains: strings(&["example.com", "www.example.com"]),
+            email: "admin@example.com".into(),
+            webroot: Some("/var/www/html".into()),
+            renew: true,
+        }))
+        .section("gpg")
+        .render(&Operation::Gpg(GpgOperation::ImportKey {
+            fingerprint: "0E5776264A9A4BACEEB0EB8AB4A10636C06FECB8".into(),
+            source: GpgKeySource::Url {
+                url: "https://download.docker.com/linux/debian/gpg".into(),
+            },
+            keyring: GpgKeyring::File {
+                path: FilePath::new("/etc/apt/keyrings/docker.gpg"),
+            },
+        }))
+        .render(&Operation::Gpg(GpgOperation::ImportKey {
+            fingerprint: "FAFC2B9B27A58C139883BB57EFDBFB83254EFF84".into(),
+            source: GpgKeySource::Keyserver {
+                keyserver: "hkps://keys.openpgp.org".into(),
+            },
+            keyring: GpgKeyring::User {
+                user: Some("mikey".into()),
+            },
+        }))
+        .assert_matches(format!(
+            "{}/snapshots/operations.txt",
+            env!("CARGO_MANIFEST_DIR")
+        ));
+}
@@ -45,6 +45,32 @@
 //! most-general. Resource-side parsers normally dispatch by an explicit
 //! discriminator field (see [`StructFields::take_discriminator`]) instead of
 //! relying on first-match.
+//!
+//! # Defaults
+//!
+//! A plan's param can declare a `default`, used when the param isn't given.
+//! It's either a value, or a function of the machine being applied to, so
+//! per-host values needn't be copied into each machine's params:
+//!
+//! ```yaml
+//! params:
+//!   hostname:
+//!     type: string
+//!     default: (machine) => machine.hostname
+//! ```
+//!
+//! The machine is whatever facts the caller put on the [`ParamsContext`]
+//! (see [`ParamsContext::with_machine`]), or `null`. A default is validated
+//! like a given value.
+//!
+//! # Deprecation
+//!
+//! A param that's on its way out still validates, but using it warns. A plan
+//! marks one of its params with `deprecated: true`, or with a hint like
+//! `deprecated: "use packages instead"` (see [`ParamField::deprecated`]); a
+//! resource lists its own in a [`DeprecatedParam`] table, checked with
+//! [`deprecation_warnings`]. Either way a [`ParamWarning`] is collected on the
+//! [`ParamsContext`], for `lusid-apply` to report.
 
 pub mod parse;
 
@@ -53,7 +79,9 @@ pub use crate::parse::{
     parse_string, parse_target_path, parse_u32,
 };
 
+use std::fmt::{self, Display};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
 use displaydoc::Display;
 use indexmap::IndexMap;
@@ -76,21 +104,128 @@ use thiserror::Error;
 /// literal strings that arrive at the sub-plan boundary. That makes parent →
 /// child forwarding behave consistently regardless of how deep the recursion
 /// runs.
+///
+/// It also collects the [`ParamWarning`]s raised along the way. Clones share
+/// them, so whoever created the context can [`take`](Self::take_warnings)
+/// the lot once planning is done.
 #[derive(Debug, Clone)]
 pub struct ParamsContext {
     root_path: PathBuf,
+    machine: Option<Spanned<Value>>,
+    warnings: Arc<Mutex<Vec<ParamWarning>>>,
 }
 
 impl ParamsContext {
     pub fn new(root_path: impl Into<PathBuf>) -> Self {
         Self {
             root_path: root_path.into(),
+            machine: None,
+            warnings: Arc::default(),
+        }
+    }
+
+    /// Facts about the machine being applied to, passed to param `default`
+    /// functions.
+    pub fn with_machine(self, machine: Spanned<Value>) -> Self {
+        Self {
+            machine: Some(machine),
+            ..self
         }
     }
 
     pub fn root_path(&self) -> &Path {
         &self.root_path
     }
+
+    pub fn machine(&self) -> Option<&Spanned<Value>> {
+        self.machine.as_ref()
+    }
+
+    pub fn warn(&self, warning: ParamWarning) {
+        self.warnings
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(warning);
+    }
+
+    /// Every warning raised so far, in order, leaving none behind.
+    pub fn take_warnings(&self) -> Vec<ParamWarning> {
+        std::mem::take(
+            &mut *self
+                .warnings
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+        )
+    }
+}
+
+/// Something wrong with a param that isn't worth failing over.
+#[derive(Debug, Clone)]
+pub enum ParamWarning {
+    /// A deprecated param was used. `hint` says what to use instead.
+    Deprecated {
+        key: String,
+        hint: Option<String>,
+        span: Span,
+    },
+}
+
+impl ParamWarning {
+    /// Stable, machine-readable code for this warning.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParamWarning::Deprecated { .. } => "params.deprecated",
+        }
+    }
+
+    /// The offending value.
+    pub fn span(&self) -> &Span {
+        match self {
+            ParamWarning::Deprecated { span, .. } => span,
+        }
+    }
+}
+
+impl Display for ParamWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamWarning::Deprecated { key, hint, .. } => {
+                write!(f, "Parameter \"{key}\" is deprecated")?;
+                if let Some(hint) = hint {
+                    write!(f, ": {hint}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A deprecated param of a resource: using `key` warns, suggesting `hint`.
+#[derive(Debug, Clone, Copy)]
+pub struct DeprecatedParam {
+    pub key: &'static str,
+    pub hint: Option<&'static str>,
+}
+
+/// Warnings for each `deprecated` param set in the object `value`.
+pub fn deprecation_warnings(
+    deprecated: &[DeprecatedParam],
+    value: &Spanned<Value>,
+) -> Vec<ParamWarning> {
+    let Value::Object(object) = value.inner() else {
+        return Vec::new();
+    };
+    deprecated
+        .iter()
+        .filter_map(|param| {
+            let value = object.get(param.key)?;
+            Some(ParamWarning::Deprecated {
+                key: param.key.to_owned(),
+                hint: param.hint.map(str::to_owned),
+                span: value.span().clone(),
+            })
+        })
+        .collect()
 }
 
 /// Schema node: the allowed shape of a single value.
@@ -116,10 +251,18 @@ pub enum ParamType {
     TargetPath,
 }
 
+/// Marks a [`ParamField`] as deprecated. `hint` says what to use instead.
+#[derive(Debug, Clone, Default)]
+pub struct Deprecation {
+    pub hint: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct ParamField {
     typ: ParamType,
     optional: bool,
+    default: Option<Spanned<Value>>,
+    deprecated: Option<Deprecation>,
 }
 
 impl ParamField {
@@ -127,13 +270,31 @@ impl ParamField {
         Self {
             typ,
             optional: false,
+            default: None,
+            deprecated: None,
         }
     }
 
     pub fn with_optional(self) -> Self {
         Self {
-            typ: self.typ,
             optional: true,
+            ..self
+        }
+    }
+
+    /// Used when the param isn't given: a value, or a function called with
+    /// the machine (see the module docs).
+    pub fn with_default(self, default: Spanned<Value>) -> Self {
+        Self {
+            default: Some(default),
+            ..self
+        }
+    }
+
+    pub fn with_deprecated(self, deprecation: Deprecation) -> Self {
+        Self {
+            deprecated: Some(deprecation),
+            ..self
         }
     }
 
@@ -144,6 +305,14 @@ impl ParamField {
     pub fn optional(&self) -> &bool {
         &self.optional
     }
+
+    pub fn default(&self) -> Option<&Spanned<Value>> {
+        self.default.as_ref()
+    }
+
+    pub fn deprecated(&self) -> Option<&Deprecation> {
+        self.deprecated.as_ref()
+    }
 }
 
 /// Ordered map of field name → field schema. `IndexMap` is deliberate — we
@@ -244,6 +413,9 @@ pub enum ParamFieldFromRimuError {
     /// The "optional" property must be a boolean
     OptionalNotABoolean { span: Span },
 
+    /// The "deprecated" property must be a boolean or a string
+    DeprecatedNotABooleanOrString { span: Span },
+
     /// Invalid field type: {0:?}
     FieldType(#[from] ParamTypeFromRimuError),
 }
@@ -268,8 +440,32 @@ impl FromRimu for ParamField {
             false
         };
 
+        let default = object.swap_remove("default");
+
+        let deprecated = match object.swap_remove("deprecated") {
+            None => None,
+            Some(deprecated_value) => {
+                let (inner, span) = deprecated_value.take();
+                match inner {
+                    Value::Boolean(true) => Some(Deprecation::default()),
+                    Value::Boolean(false) => None,
+                    Value::String(hint) => Some(Deprecation { hint: Some(hint) }),
+                    _ => {
+                        return Err(ParamFieldFromRimuError::DeprecatedNotABooleanOrString {
+                            span,
+                        });
+                    }
+                }
+            }
+        };
+
         let typ = ParamType::from_rimu(Value::Object(object))?;
-        Ok(ParamField { typ, optional })
+        Ok(ParamField {
+            typ,
+            optional,
+            default,
+            deprecated,
+        })
     }
 }
 
@@ -402,6 +598,13 @@ pub enum ParamValidationError {
         key: String,
         error: Box<ValidateValueError>,
     },
+
+    /// Failed to evaluate default of parameter "{key}": {message}
+    Default {
+        key: String,
+        message: String,
+        span: Span,
+    },
 }
 
 #[derive(Debug, Clone, Error, Display)]
@@ -433,6 +636,66 @@ pub enum ParamsValidationError {
     EmptyUnion,
 }
 
+impl ValidateValueError {
+    /// The offending value's span, from the innermost nested error.
+    pub fn span(&self) -> &Span {
+        match self {
+            ValidateValueError::TypeMismatch { got_value, .. } => got_value.span(),
+            ValidateValueError::ListItem { error, .. }
+            | ValidateValueError::ObjectEntry { error, .. } => error.span(),
+        }
+    }
+}
+
+impl ParamValidationError {
+    /// Where to point a diagnostic: the offending value, or for a missing
+    /// param, its declaration in the schema.
+    pub fn span(&self) -> &Span {
+        match self {
+            ParamValidationError::MissingParam { expected_type, .. } => expected_type.span(),
+            ParamValidationError::UnknownParam { value, .. } => value.span(),
+            ParamValidationError::InvalidParam { error, .. } => error.span(),
+            ParamValidationError::Default { span, .. } => span,
+        }
+    }
+}
+
+impl ParamsStructValidationError {
+    /// Span of the first field error.
+    pub fn span(&self) -> Option<&Span> {
+        self.errors.first().map(ParamValidationError::span)
+    }
+}
+
+impl ParamsValidationError {
+    /// Stable, machine-readable code for this failure.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParamsValidationError::ValuesWithoutTypes => "params.values-without-types",
+            ParamsValidationError::TypesWithoutValues => "params.types-without-values",
+            ParamsValidationError::ValuesNotAnObject => "params.not-an-object",
+            ParamsValidationError::Struct(_) => "params.invalid",
+            ParamsValidationError::Union { .. } => "params.no-matching-case",
+            ParamsValidationError::EmptyUnion => "params.empty-union",
+        }
+    }
+
+    /// Span of the first field error, if this error came from a field. For a
+    /// union, that's the first case's first error.
+    pub fn span(&self) -> Option<&Span> {
+        match self {
+            ParamsValidationError::Struct(error) => error.span(),
+            ParamsValidationError::Union { case_errors } => case_errors
+                .iter()
+                .find_map(ParamsStructValidationError::span),
+            ParamsValidationError::ValuesWithoutTypes
+            | ParamsValidationError::TypesWithoutValues
+            | ParamsValidationError::ValuesNotAnObject
+            | ParamsValidationError::EmptyUnion => None,
+        }
+    }
+}
+
 fn mismatch(typ: &Spanned<ParamType>, value: &Spanned<Value>) -> ValidateValueError {
     ValidateValueError::TypeMismatch {
         expected_type: Box::new(typ.clone()),
@@ -537,6 +800,22 @@ fn coerce_type(
     }
 }
 
+/// A field's `default`: the value itself, or what the function returns given
+/// the machine.
+fn evaluate_default(
+    default: &Spanned<Value>,
+    ctx: &ParamsContext,
+) -> Result<Spanned<Value>, rimu::EvalError> {
+    let Value::Function(function) = default.inner() else {
+        return Ok(default.clone());
+    };
+    let machine = ctx
+        .machine()
+        .cloned()
+        .unwrap_or_else(|| Spanned::new(Value::Null, default.span().clone()));
+    rimu::call(default.span().clone(), function.clone(), &[machine])
+}
+
 fn coerce_struct(
     fields: &IndexMap<String, Spanned<ParamField>>,
     mut values: ValueObject,
@@ -544,6 +823,7 @@ fn coerce_struct(
     ctx: &ParamsContext,
 ) -> Result<Spanned<Value>, ParamsStructValidationError> {
     let mut errors: Vec<ParamValidationError> = Vec::new();
+    let mut warnings: Vec<ParamWarning> = Vec::new();
     let mut coerced: ValueObject = IndexMap::with_capacity(fields.len());
 
     // Walk the schema in declaration order: take each declared field out of
@@ -553,7 +833,16 @@ fn coerce_struct(
         let (field, field_span) = spanned_field.clone().take();
         let spanned_type = Spanned::new(field.typ().clone(), field_span);
 
-        match values.swap_remove(key) {
+        let value = values.swap_remove(key);
+        if let (Some(deprecation), Some(value)) = (field.deprecated(), &value) {
+            warnings.push(ParamWarning::Deprecated {
+                key: key.clone(),
+                hint: deprecation.hint.clone(),
+                span: value.span().clone(),
+            });
+        }
+
+        match value {
             Some(spanned_value) => match coerce_type(&spanned_type, spanned_value, ctx) {
                 Ok(coerced_value) => {
                     coerced.insert(key.clone(), coerced_value);
@@ -565,14 +854,36 @@ fn coerce_struct(
                     });
                 }
             },
-            None => {
-                if !field.optional {
-                    errors.push(ParamValidationError::MissingParam {
-                        key: key.clone(),
-                        expected_type: Box::new(spanned_type),
-                    });
+            None => match field.default() {
+                Some(default) => match evaluate_default(default, ctx) {
+                    Ok(default) => match coerce_type(&spanned_type, default, ctx) {
+                        Ok(coerced_value) => {
+                            coerced.insert(key.clone(), coerced_value);
+                        }
+                        Err(error) => {
+                            errors.push(ParamValidationError::InvalidParam {
+                                key: key.clone(),
+                                error: Box::new(error),
+                            });
+                        }
+                    },
+                    Err(error) => {
+                        errors.push(ParamValidationError::Default {
+                            key: key.clone(),
+                            message: error.to_string(),
+                            span: default.span().clone(),
+                        });
+                    }
+                },
+                None => {
+                    if !field.optional {
+                        errors.push(ParamValidationError::MissingParam {
+                            key: key.clone(),
+                            expected_type: Box::new(spanned_type),
+                        });
+                    }
                 }
-            }
+            },
         }
     }
 
@@ -584,6 +895,10 @@ fn coerce_struct(
     }
 
     if errors.is_empty() {
+        // Only now, so a union case that doesn't match can't warn.
+        for warning in warnings {
+            ctx.warn(warning);
+        }
         Ok(Spanned::new(Value::Object(coerced), span))
     } else {
         Err(ParamsStructValidationError { errors })
@@ -895,6 +1210,123 @@ mod tests {
         assert!(unwrap_object(coerced).contains_key("id"));
     }
 
+    #[test]
+    fn deprecated_field_warns_only_when_used() {
+        let mut fields = ParamsStruct::new();
+        fields.insert(
+            "pkg".into(),
+            Spanned::new(
+                ParamField::new(ParamType::String)
+                    .with_optional()
+                    .with_deprecated(Deprecation {
+                        hint: Some("use packages instead".into()),
+                    }),
+                empty_span(),
+            ),
+        );
+        let schema = Spanned::new(ParamTypes::Struct(fields), empty_span());
+        let ctx = ctx();
+
+        let value = obj(vec![], empty_span());
+        validate(Some(&schema), Some(value), &ctx).expect("ok");
+        assert!(ctx.take_warnings().is_empty());
+
+        let value = obj(vec![("pkg", Value::String("nvim".into()))], empty_span());
+        validate(Some(&schema), Some(value), &ctx).expect("ok");
+        let warnings = ctx.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].to_string(),
+            "Parameter \"pkg\" is deprecated: use packages instead"
+        );
+    }
+
+    #[test]
+    fn resource_deprecation_warnings_match_set_keys() {
+        let deprecated = [
+            DeprecatedParam {
+                key: "pkg",
+                hint: None,
+            },
+            DeprecatedParam {
+                key: "name",
+                hint: Some("use package instead"),
+            },
+        ];
+        let value = obj(vec![("name", Value::String("nvim".into()))], empty_span());
+        let warnings = deprecation_warnings(&deprecated, &value);
+        assert!(matches!(
+            warnings.as_slice(),
+            [ParamWarning::Deprecated { key, .. }] if key == "name"
+        ));
+    }
+
+    fn eval(code: &str) -> Spanned<Value> {
+        let (ast, errors) = rimu::parse(code, SourceId::empty());
+        assert!(errors.is_empty(), "{errors:?}");
+        let env = std::rc::Rc::new(std::cell::RefCell::new(rimu::Environment::new()));
+        rimu::evaluate(&ast.expect("ast"), env).expect("evaluate")
+    }
+
+    fn defaulted_schema(default: Spanned<Value>) -> Spanned<ParamTypes> {
+        let mut fields = ParamsStruct::new();
+        fields.insert(
+            "hostname".into(),
+            Spanned::new(
+                ParamField::new(ParamType::String).with_default(default),
+                empty_span(),
+            ),
+        );
+        Spanned::new(ParamTypes::Struct(fields), empty_span())
+    }
+
+    #[test]
+    fn default_fills_in_a_missing_param() {
+        let schema = defaulted_schema(Spanned::new(Value::String("web".into()), empty_span()));
+
+        let coerced = validate(Some(&schema), Some(obj(vec![], empty_span())), &ctx())
+            .expect("ok")
+            .expect("some");
+        let map = unwrap_object(coerced);
+        assert!(matches!(map["hostname"].inner(), Value::String(s) if s == "web"));
+
+        let value = obj(vec![("hostname", Value::String("db".into()))], empty_span());
+        let coerced = validate(Some(&schema), Some(value), &ctx())
+            .expect("ok")
+            .expect("some");
+        let map = unwrap_object(coerced);
+        assert!(matches!(map["hostname"].inner(), Value::String(s) if s == "db"));
+    }
+
+    #[test]
+    fn default_function_reads_the_machine() {
+        let schema = defaulted_schema(eval("(machine) => machine.hostname"));
+        let machine = obj(
+            vec![("hostname", Value::String("web-1".into()))],
+            empty_span(),
+        );
+        let ctx = ctx().with_machine(machine);
+
+        let coerced = validate(Some(&schema), Some(obj(vec![], empty_span())), &ctx)
+            .expect("ok")
+            .expect("some");
+        let map = unwrap_object(coerced);
+        assert!(matches!(map["hostname"].inner(), Value::String(s) if s == "web-1"));
+    }
+
+    #[test]
+    fn default_of_the_wrong_type_is_invalid() {
+        let schema = defaulted_schema(Spanned::new(Value::Boolean(true), empty_span()));
+        let err = validate(Some(&schema), Some(obj(vec![], empty_span())), &ctx()).unwrap_err();
+        let ParamsValidationError::Struct(boxed) = err else {
+            panic!("expected Struct error");
+        };
+        assert!(matches!(
+            boxed.errors.first(),
+            Some(ParamValidationError::InvalidParam { .. })
+        ));
+    }
+
     #[test]
     fn empty_union_is_an_error() {
         let schema = Spanned::new(ParamTypes::Union(Vec::new()), empty_span());
@@ -63,6 +63,18 @@ pub enum ParseError {
     /// Target-path string \"{value}\" must be absolute
     TargetPathNotAbsolute { value: String },
 
+    /// Invalid target-path \"{value}\": {reason}
+    InvalidTargetPath { value: String, reason: String },
+
+    /// Invalid package requirement \"{value}\": {reason}
+    InvalidRequirement { value: String, reason: String },
+
+    /// Failed to render template \"{path}\": {reason}
+    Template { path: String, reason: String },
+
+    /// Failed to read host directory \"{path}\": {reason}
+    HostDirectory { path: String, reason: String },
+
     /// Failed to parse list at index {index}: {error}
     ListItem {
         index: usize,
@@ -0,0 +1,205 @@
+//! Apt repositories before apt packages: a package from a repository that an
+//! `@core/apt-repo` item adds can only be installed once the repository's
+//! sources and key are written, and apt has updated its lists from them.
+//!
+//! Once the whole tree is planned, [`order_apt_sources`] makes every
+//! `@core/apt` item require every `@core/apt-repo` item, minting ids for
+//! items without one, as [`packages`](crate::packages) does. An apt install
+//! already runs an `apt-get update` first, so that update then sees the new
+//! sources; updates in one epoch merge into one.
+//!
+//! An edge that would close a cycle is left out: an apt-repo item may itself
+//! require an apt item, like one installing `ca-certificates` for an https
+//! repository, and that order stands.
+
+use cuid2::create_id;
+use lusid_causality::CausalityGraph;
+use lusid_resource::ResourceParams;
+use lusid_tree::Tree;
+
+use crate::{Declared, PlanNodeId, PlanTree};
+
+pub(crate) fn order_apt_sources(tree: &mut PlanTree<Declared<ResourceParams>>) {
+    if !has_apt_repos(tree) {
+        return;
+    }
+    let mut repos = Vec::new();
+    let mut installs = Vec::new();
+    collect_apt_items(tree, &mut repos, &mut installs);
+    if installs.is_empty() {
+        return;
+    }
+
+    // A tree that can't be scheduled is left as it is, for scheduling to
+    // report.
+    let Ok(mut graph) = CausalityGraph::new(tree) else {
+        return;
+    };
+
+    let mut edges: Vec<(PlanNodeId, PlanNodeId)> = Vec::new();
+    for install in &installs {
+        for repo in &repos {
+            if graph.runs_before(repo, install) || graph.runs_before(install, repo) {
+                continue;
+            }
+            graph.add_requires(install, repo);
+            edges.push((install.clone(), repo.clone()));
+        }
+    }
+    add_requires(tree, &edges);
+}
+
+fn has_apt_repos(tree: &PlanTree<Declared<ResourceParams>>) -> bool {
+    match tree {
+        Tree::Branch { children, .. } => children.iter().any(has_apt_repos),
+        Tree::Leaf { node, .. } => matches!(node.node, ResourceParams::AptRepo(_)),
+    }
+}
+
+fn collect_apt_items(
+    tree: &mut PlanTree<Declared<ResourceParams>>,
+    repos: &mut Vec<PlanNodeId>,
+    installs: &mut Vec<PlanNodeId>,
+) {
+    match tree {
+        Tree::Branch { children, .. } => {
+            for child in children {
+                collect_apt_items(child, repos, installs);
+            }
+        }
+        Tree::Leaf { meta, node } => {
+            let (ids, item_id) = match node.node {
+                ResourceParams::AptRepo(_) => (&mut *repos, "apt-repo"),
+                ResourceParams::Apt(_) => (&mut *installs, "apt"),
+                _ => return,
+            };
+            let id = meta
+                .id
+                .get_or_insert_with(|| PlanNodeId::SubItem {
+                    scope_id: create_id(),
+                    item_id: item_id.to_string(),
+                })
+                .clone();
+            ids.push(id);
+        }
+    }
+}
+
+fn add_requires(tree: &mut PlanTree<Declared<ResourceParams>>, edges: &[(PlanNodeId, PlanNodeId)]) {
+    match tree {
+        Tree::Branch { children, .. } => {
+            for child in children {
+                add_requires(child, edges);
+            }
+        }
+        Tree::Leaf { meta, .. } => {
+            let Some(id) = &meta.id else {
+                return;
+            };
+            let requires: Vec<PlanNodeId> = edges
+                .iter()
+                .filter(|(install, _)| install == id)
+                .map(|(_, repo)| repo.clone())
+                .collect();
+            meta.requires.extend(requires);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lusid_resource::resources::{apt::AptParams, apt_repo::AptRepoParams};
+
+    use super::*;
+    use crate::{PlanId, PlanMeta};
+
+    fn id(item_id: &str) -> PlanNodeId {
+        PlanNodeId::PlanItem {
+            plan_id: PlanId::Path("web.lusid".into()),
+            item_id: item_id.into(),
+        }
+    }
+
+    fn item(
+        item_id: Option<&str>,
+        requires: &[&str],
+        params: ResourceParams,
+    ) -> PlanTree<Declared<ResourceParams>> {
+        PlanTree::leaf(
+            PlanMeta {
+                id: item_id.map(id),
+                requires: requires.iter().map(|item_id| id(item_id)).collect(),
+                required_by: vec![],
+            },
+            Declared::new(params, None),
+        )
+    }
+
+    fn apt(package: &str) -> ResourceParams {
+        ResourceParams::Apt(AptParams::Package {
+            package: package.into(),
+        })
+    }
+
+    fn apt_repo(name: &str) -> ResourceParams {
+        ResourceParams::AptRepo(AptRepoParams {
+            name: name.into(),
+            uris: vec!["https://example.com/apt".into()],
+            suites: vec!["stable".into()],
+            components: vec!["main".into()],
+            key_url: "https://example.com/key.asc".into(),
+            key_sha256: None,
+            types: None,
+            architectures: None,
+            enabled: None,
+        })
+    }
+
+    fn leaves(tree: &PlanTree<Declared<ResourceParams>>) -> Vec<&PlanMeta> {
+        match tree {
+            Tree::Branch { children, .. } => children.iter().flat_map(leaves).collect(),
+            Tree::Leaf { meta, .. } => vec![meta],
+        }
+    }
+
+    #[test]
+    fn apt_items_require_every_apt_repo() {
+        let mut tree = PlanTree::branch(
+            PlanMeta::default(),
+            [
+                item(Some("nginx"), &[], apt("nginx")),
+                item(Some("nginx-repo"), &[], apt_repo("nginx")),
+                item(None, &[], apt("curl")),
+                item(Some("other-repo"), &[], apt_repo("other")),
+            ],
+        );
+        order_apt_sources(&mut tree);
+
+        let leaves = leaves(&tree);
+        let repos = vec![id("nginx-repo"), id("other-repo")];
+        assert_eq!(leaves[0].requires, repos);
+        assert!(leaves[1].requires.is_empty());
+        assert!(matches!(leaves[2].id, Some(PlanNodeId::SubItem { .. })));
+        assert_eq!(leaves[2].requires, repos);
+        assert!(leaves[3].requires.is_empty());
+    }
+
+    #[test]
+    fn keeps_an_apt_repo_after_the_apt_item_it_requires() {
+        let mut tree = PlanTree::branch(
+            PlanMeta::default(),
+            [
+                item(Some("ca-certificates"), &[], apt("ca-certificates")),
+                item(Some("nginx-repo"), &["ca-certificates"], apt_repo("nginx")),
+                item(Some("nginx"), &[], apt("nginx")),
+            ],
+        );
+        order_apt_sources(&mut tree);
+
+        let leaves = leaves(&tree);
+        assert!(leaves[0].requires.is_empty());
+        assert_eq!(leaves[1].requires, vec![id("ca-certificates")]);
+        assert_eq!(leaves[2].requires, vec![id("nginx-repo")]);
+        assert!(CausalityGraph::new(&tree).is_ok());
+    }
+}
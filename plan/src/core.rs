@@ -2,13 +2,42 @@
 //! `@core/<id>` namespace (e.g. `@core/apt`, `@core/file`). This module routes a plan
 //! item's module string to the matching [`ResourceType`] impl.
 
-use lusid_params::ParseParams;
+use lusid_params::{ParamsContext, ParseParams, deprecation_warnings};
 use lusid_resource::{
-    ResourceParams, ResourceType, apt::Apt, apt_repo::AptRepo, command::Command,
-    directory::Directory, file::File, git::Git, group::Group, pacman::Pacman, podman::Podman,
-    secret::Secret, systemd::Systemd, user::User,
+    ResourceParams, ResourceType,
+    apk::Apk,
+    apt::Apt,
+    apt_repo::AptRepo,
+    aur::Aur,
+    brew::{Brew, BrewKind},
+    command::Command,
+    cron::Cron,
+    directory::Directory,
+    dnf::Dnf,
+    file::File,
+    firewall::Firewall,
+    git::Git,
+    git_config::GitConfig,
+    gpg_key::GpgKey,
+    group::Group,
+    launchd::Launchd,
+    networkd::Networkd,
+    nix::Nix,
+    pacman::Pacman,
+    pip::Pip,
+    podman::Podman,
+    podman_image::PodmanImage,
+    rustup::Rustup,
+    secret::Secret,
+    systemd::Systemd,
+    systemd_unit::SystemdUnit,
+    time::Time,
+    tls_cert::TlsCert,
+    user::User,
+    wireguard::Wireguard,
 };
-use rimu::{Spanned, Value};
+use lusid_system::Os;
+use rimu::{Span, Spanned, Value};
 
 use crate::PlanItemToResourceError;
 
@@ -20,34 +49,129 @@ pub fn is_core_module(module: &Spanned<String>) -> Option<&str> {
 
 /// Parse `params` directly into the matching core module's typed [`ResourceParams`]
 /// variant. Errors if `id` is unknown or the params don't fit the resource's shape.
+///
+/// `module_span` is the span of the item's `module` string, which errors point
+/// at when there's no narrower span (unknown id, missing params). Deprecated
+/// params (see [`ResourceType::DEPRECATED_PARAMS`]) are warned about on `ctx`.
+///
+/// A core module that doesn't run on `os` (see [`ResourceType::PLATFORMS`])
+/// is refused here, before its params are looked at, so a plan using
+/// `@core/systemd` on macOS fails to plan rather than when `systemctl` is
+/// missing halfway through an apply. So does a `@core/brew` cask off macOS.
 pub fn core_module(
     core_module_id: &str,
+    module_span: &Span,
     params: Option<Spanned<Value>>,
+    ctx: &ParamsContext,
+    os: &Os,
 ) -> Result<ResourceParams, PlanItemToResourceError> {
     match core_module_id {
-        Apt::ID => core_module_for_resource::<Apt>(params).map(ResourceParams::Apt),
-        AptRepo::ID => core_module_for_resource::<AptRepo>(params).map(ResourceParams::AptRepo),
-        File::ID => core_module_for_resource::<File>(params).map(ResourceParams::File),
-        Directory::ID => {
-            core_module_for_resource::<Directory>(params).map(ResourceParams::Directory)
-        }
-        Pacman::ID => core_module_for_resource::<Pacman>(params).map(ResourceParams::Pacman),
-        Podman::ID => core_module_for_resource::<Podman>(params).map(ResourceParams::Podman),
-        Command::ID => core_module_for_resource::<Command>(params).map(ResourceParams::Command),
-        Git::ID => core_module_for_resource::<Git>(params).map(ResourceParams::Git),
-        Secret::ID => core_module_for_resource::<Secret>(params).map(ResourceParams::Secret),
-        Systemd::ID => core_module_for_resource::<Systemd>(params).map(ResourceParams::Systemd),
-        User::ID => core_module_for_resource::<User>(params).map(ResourceParams::User),
-        Group::ID => core_module_for_resource::<Group>(params).map(ResourceParams::Group),
+        Apt::ID => {
+            core_module_for_resource::<Apt>(module_span, params, ctx, os).map(ResourceParams::Apt)
+        }
+        AptRepo::ID => core_module_for_resource::<AptRepo>(module_span, params, ctx, os)
+            .map(ResourceParams::AptRepo),
+        Brew::ID => {
+            let params = core_module_for_resource::<Brew>(module_span, params, ctx, os)?;
+            // Casks are macOS apps, so Linuxbrew has none.
+            if params.kind() == BrewKind::Cask && !os.is_any_of(&["macos"]) {
+                return Err(PlanItemToResourceError::UnsupportedCask {
+                    os: os.clone(),
+                    span: module_span.clone(),
+                });
+            }
+            Ok(ResourceParams::Brew(params))
+        }
+        File::ID => {
+            core_module_for_resource::<File>(module_span, params, ctx, os).map(ResourceParams::File)
+        }
+        Directory::ID => core_module_for_resource::<Directory>(module_span, params, ctx, os)
+            .map(ResourceParams::Directory),
+        Pacman::ID => core_module_for_resource::<Pacman>(module_span, params, ctx, os)
+            .map(ResourceParams::Pacman),
+        Aur::ID => {
+            core_module_for_resource::<Aur>(module_span, params, ctx, os).map(ResourceParams::Aur)
+        }
+        Apk::ID => {
+            core_module_for_resource::<Apk>(module_span, params, ctx, os).map(ResourceParams::Apk)
+        }
+        Dnf::ID => {
+            core_module_for_resource::<Dnf>(module_span, params, ctx, os).map(ResourceParams::Dnf)
+        }
+        Nix::ID => {
+            core_module_for_resource::<Nix>(module_span, params, ctx, os).map(ResourceParams::Nix)
+        }
+        Podman::ID => core_module_for_resource::<Podman>(module_span, params, ctx, os)
+            .map(ResourceParams::Podman),
+        PodmanImage::ID => core_module_for_resource::<PodmanImage>(module_span, params, ctx, os)
+            .map(ResourceParams::PodmanImage),
+        Pip::ID => {
+            core_module_for_resource::<Pip>(module_span, params, ctx, os).map(ResourceParams::Pip)
+        }
+        Rustup::ID => core_module_for_resource::<Rustup>(module_span, params, ctx, os)
+            .map(ResourceParams::Rustup),
+        Command::ID => core_module_for_resource::<Command>(module_span, params, ctx, os)
+            .map(ResourceParams::Command),
+        Git::ID => {
+            core_module_for_resource::<Git>(module_span, params, ctx, os).map(ResourceParams::Git)
+        }
+        Secret::ID => core_module_for_resource::<Secret>(module_span, params, ctx, os)
+            .map(ResourceParams::Secret),
+        Systemd::ID => core_module_for_resource::<Systemd>(module_span, params, ctx, os)
+            .map(ResourceParams::Systemd),
+        SystemdUnit::ID => core_module_for_resource::<SystemdUnit>(module_span, params, ctx, os)
+            .map(ResourceParams::SystemdUnit),
+        User::ID => {
+            core_module_for_resource::<User>(module_span, params, ctx, os).map(ResourceParams::User)
+        }
+        Group::ID => core_module_for_resource::<Group>(module_span, params, ctx, os)
+            .map(ResourceParams::Group),
+        Cron::ID => {
+            core_module_for_resource::<Cron>(module_span, params, ctx, os).map(ResourceParams::Cron)
+        }
+        Launchd::ID => core_module_for_resource::<Launchd>(module_span, params, ctx, os)
+            .map(ResourceParams::Launchd),
+        Firewall::ID => core_module_for_resource::<Firewall>(module_span, params, ctx, os)
+            .map(ResourceParams::Firewall),
+        Time::ID => {
+            core_module_for_resource::<Time>(module_span, params, ctx, os).map(ResourceParams::Time)
+        }
+        Networkd::ID => core_module_for_resource::<Networkd>(module_span, params, ctx, os)
+            .map(ResourceParams::Networkd),
+        Wireguard::ID => core_module_for_resource::<Wireguard>(module_span, params, ctx, os)
+            .map(ResourceParams::Wireguard),
+        TlsCert::ID => core_module_for_resource::<TlsCert>(module_span, params, ctx, os)
+            .map(ResourceParams::TlsCert),
+        GitConfig::ID => core_module_for_resource::<GitConfig>(module_span, params, ctx, os)
+            .map(ResourceParams::GitConfig),
+        GpgKey::ID => core_module_for_resource::<GpgKey>(module_span, params, ctx, os)
+            .map(ResourceParams::GpgKey),
         other => Err(PlanItemToResourceError::UnsupportedCoreModuleId {
             id: other.to_string(),
+            span: module_span.clone(),
         }),
     }
 }
 
 fn core_module_for_resource<R: ResourceType>(
+    module_span: &Span,
     params_value: Option<Spanned<Value>>,
+    ctx: &ParamsContext,
+    os: &Os,
 ) -> Result<R::Params, PlanItemToResourceError> {
-    let params_value = params_value.ok_or(PlanItemToResourceError::MissingParams)?;
+    if !os.is_any_of(R::PLATFORMS) {
+        return Err(PlanItemToResourceError::UnsupportedPlatform {
+            id: R::ID.to_string(),
+            os: os.clone(),
+            platforms: R::PLATFORMS.join(", "),
+            span: module_span.clone(),
+        });
+    }
+    let params_value = params_value.ok_or_else(|| PlanItemToResourceError::MissingParams {
+        span: module_span.clone(),
+    })?;
+    for warning in deprecation_warnings(R::DEPRECATED_PARAMS, &params_value) {
+        ctx.warn(warning);
+    }
     R::Params::parse_params(params_value).map_err(PlanItemToResourceError::Parse)
 }
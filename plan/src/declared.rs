@@ -0,0 +1,120 @@
+//! Where a plan item is declared, carried alongside its resource params and
+//! everything they expand to (resources, then changes), so what's about to
+//! change can be traced back to the plan line responsible for it. The item's
+//! retry policy rides along too, for its operations to be applied with.
+
+use std::fmt::Display;
+use std::path::Path;
+
+use lusid_causality::CausalityMeta;
+use lusid_tree::Tree;
+use lusid_view::{Fragment, Render, View};
+use rimu::Span;
+use rimu_interop::line_number;
+
+use crate::{PlanId, PlanTree, RetryPolicy, map_plan_subitems};
+
+/// `node`, declared by the plan item at `location`: `<plan>:<line>`, the plan
+/// relative to the project root, e.g. `plans/web.lusid:42`. `None` when the
+/// item's span doesn't point into the plan that returned it, e.g. an item
+/// built by a parent plan and passed in as a param. `retry` is the item's
+/// retry policy, if it has one.
+#[derive(Debug, Clone)]
+pub struct Declared<Node> {
+    pub node: Node,
+    pub location: Option<String>,
+    pub retry: Option<RetryPolicy>,
+}
+
+impl<Node> Declared<Node> {
+    pub fn new(node: Node, location: Option<String>) -> Self {
+        Self {
+            node,
+            location,
+            retry: None,
+        }
+    }
+
+    pub fn with_retry(self, retry: Option<RetryPolicy>) -> Self {
+        Self { retry, ..self }
+    }
+
+    /// `retry`, unless it already has a retry policy.
+    pub fn or_retry(self, retry: &RetryPolicy) -> Self {
+        match self.retry {
+            Some(_) => self,
+            None => self.with_retry(Some(retry.clone())),
+        }
+    }
+
+    pub fn map<NextNode>(self, map: impl FnOnce(Node) -> NextNode) -> Declared<NextNode> {
+        Declared {
+            node: map(self.node),
+            location: self.location,
+            retry: self.retry,
+        }
+    }
+}
+
+impl<Node: Display> Display for Declared<Node> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self { node, location, .. } = self;
+        match location {
+            Some(location) => write!(f, "{node} (declared at {location})"),
+            None => write!(f, "{node}"),
+        }
+    }
+}
+
+impl<Node: Render> Render for Declared<Node> {
+    fn render(&self) -> View {
+        match &self.location {
+            Some(location) => View::Fragment(Fragment::new(vec![
+                self.node.render(),
+                View::Span(format!(" (declared at {location})")),
+            ])),
+            None => self.node.render(),
+        }
+    }
+}
+
+/// [`map_plan_subitems`] for a declared node: everything it expands to is
+/// declared where it is.
+pub fn map_declared_subitems<Node, NextNode, MapFn, MapFnIter>(
+    node: Declared<Node>,
+    map: MapFn,
+) -> impl Iterator<Item = PlanTree<Declared<NextNode>>>
+where
+    MapFn: Fn(Node) -> MapFnIter,
+    MapFnIter: IntoIterator<Item = Tree<NextNode, CausalityMeta<String>>>,
+{
+    let Declared {
+        node,
+        location,
+        retry,
+    } = node;
+    map_plan_subitems(node, map).map(move |tree| {
+        tree.map(|node| Declared::new(node, location.clone()).with_retry(retry.clone()))
+    })
+}
+
+/// The location of `span` in `plan_id`'s `code`, if it points into it.
+pub(crate) fn location(
+    plan_id: &PlanId,
+    code: &str,
+    span: &Span,
+    root_path: &Path,
+) -> Option<String> {
+    let PlanId::Path(path) = plan_id else {
+        return None;
+    };
+    if span.source().as_str() != path.to_string_lossy() {
+        return None;
+    }
+    let path = path.strip_prefix(root_path).unwrap_or(path);
+    Some(format!(
+        "{}:{}",
+        path.display(),
+        line_number(code, span.start())
+    ))
+}
@@ -23,6 +23,25 @@ pub enum EvalError {
     InvalidPlanItem(Box<Spanned<IntoPlanItemError>>),
 }
 
+impl EvalError {
+    /// Stable error code, see [`PlanError::code`](crate::PlanError::code).
+    pub fn code(&self) -> &'static str {
+        match self {
+            EvalError::System(_) => "plan.setup.system",
+            EvalError::RimuCall(_) => "plan.setup.eval",
+            EvalError::ReturnedNotList => "plan.setup.not-a-list",
+            EvalError::InvalidPlanItem(_) => "plan.setup.invalid-item",
+        }
+    }
+
+    pub fn span(&self) -> Option<&Span> {
+        match self {
+            EvalError::InvalidPlanItem(error) => Some(error.inner().span().unwrap_or(error.span())),
+            EvalError::System(_) | EvalError::RimuCall(_) | EvalError::ReturnedNotList => None,
+        }
+    }
+}
+
 /// Call the plan's `setup` function with `(params, system)` and parse its returned list
 /// into [`PlanItem`]s.
 ///
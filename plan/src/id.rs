@@ -2,13 +2,15 @@
 //!
 //! - [`PlanId`] — where to find a plan (local path or, eventually, a git URL).
 //! - [`PlanNodeId`] — how to name a specific node inside a planned tree for causality
-//!   references (`requires` / `required_by`).
+//!   references (`requires` / `required_by` / `requires_package`).
 
 use lusid_store::StoreItemId;
 use lusid_view::impl_display_render;
-use rimu::SourceId;
+use rimu::{SourceId, Span, Spanned};
 use std::{
+    cmp::Ordering,
     fmt::Display,
+    hash::{Hash, Hasher},
     path::{Path, PathBuf},
 };
 use url::Url;
@@ -92,11 +94,77 @@ impl From<PlanId> for SourceId {
 /// - `SubItem` — an id minted *inside* a resource's expansion (e.g. the `"file"` id used
 ///   by `file` to order mode/user/group atoms). Scoped by a fresh `cuid2` so the
 ///   inner ids can never collide across resources.
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// - `Package` — a `requires_package` reference to whichever items install that
+///   package. Only ever appears in `requires`, and only until [`plan`](crate::plan)
+///   resolves it to those items' ids before returning. Keeps the span of the
+///   `requires_package` entry, so an unknown package can point at it; ids
+///   compare by package name alone.
+#[derive(Debug, Clone)]
 pub enum PlanNodeId {
     Plan(PlanId),
     PlanItem { plan_id: PlanId, item_id: String },
     SubItem { scope_id: String, item_id: String },
+    Package(Spanned<String>),
+}
+
+impl PlanNodeId {
+    /// The source span this id was written at, if it keeps one.
+    pub fn span(&self) -> Option<&Span> {
+        match self {
+            PlanNodeId::Package(package) => Some(package.span()),
+            PlanNodeId::Plan(_) | PlanNodeId::PlanItem { .. } | PlanNodeId::SubItem { .. } => None,
+        }
+    }
+
+    fn key(&self) -> PlanNodeKey<'_> {
+        match self {
+            PlanNodeId::Plan(id) => PlanNodeKey::Plan(id),
+            PlanNodeId::PlanItem { plan_id, item_id } => PlanNodeKey::PlanItem { plan_id, item_id },
+            PlanNodeId::SubItem { scope_id, item_id } => PlanNodeKey::SubItem { scope_id, item_id },
+            PlanNodeId::Package(package) => PlanNodeKey::Package(package.inner()),
+        }
+    }
+}
+
+/// [`PlanNodeId`] without spans, for comparing and hashing.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum PlanNodeKey<'a> {
+    Plan(&'a PlanId),
+    PlanItem {
+        plan_id: &'a PlanId,
+        item_id: &'a str,
+    },
+    SubItem {
+        scope_id: &'a str,
+        item_id: &'a str,
+    },
+    Package(&'a str),
+}
+
+impl PartialEq for PlanNodeId {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for PlanNodeId {}
+
+impl PartialOrd for PlanNodeId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PlanNodeId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl Hash for PlanNodeId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
 }
 
 impl Display for PlanNodeId {
@@ -109,6 +177,7 @@ impl Display for PlanNodeId {
             PlanNodeId::SubItem { scope_id, item_id } => {
                 write!(f, "SubItem(scope = {scope_id}, item = {item_id})")
             }
+            PlanNodeId::Package(package) => write!(f, "Package({})", package.inner()),
         }
     }
 }
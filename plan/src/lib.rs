@@ -12,32 +12,50 @@
 //!    - Otherwise → resolve the module as a sibling `.lusid` file, recurse, and attach
 //!      as a subtree (a branch).
 //!
-//! The result is a [`PlanTree<ResourceParams>`] whose branch/leaf metadata carries the
-//! [`PlanNodeId`] identifiers used by causality scheduling downstream.
+//! The result is a [`PlanTree`] of [`Declared`] resource params, each with the plan line
+//! of its item, whose branch/leaf metadata carries the [`PlanNodeId`] identifiers used by
+//! causality scheduling downstream. Before returning, `requires_package` references are
+//! resolved to the items installing those packages (see [`packages`]), and apt packages
+//! are ordered after the apt repositories in the plan (see [`apt_sources`]).
+//!
+//! An item including a plan module can require a compatible module `version`, checked
+//! as the module is loaded (see [`version`]). [`plan_with_modules`] also returns every
+//! plan module used, with its declared version.
 
 use displaydoc::Display;
-use lusid_params::{ParamsContext, ParamsValidationError, ParseError, validate};
-use lusid_resource::ResourceParams;
+use lusid_params::{ParamsContext, ParamsValidationError, ParseError, ParseParams, validate};
+use lusid_resource::{ResourceParams, defaults::FileDefaults};
 use lusid_store::{Store, StoreError, StoreItemId};
-use lusid_system::System;
-use rimu::{Spanned, Value};
+use lusid_system::{Os, System};
+use rimu::{Span, Spanned, Value};
 use std::{path::PathBuf, string::FromUtf8Error};
 use thiserror::Error;
 
+mod apt_sources;
 mod core;
+mod declared;
 mod eval;
 mod id;
 mod load;
 mod model;
+mod packages;
+mod retry;
 mod tree;
+mod version;
 
+pub use crate::declared::{Declared, map_declared_subitems};
 pub use crate::id::{PlanId, PlanNodeId};
+pub use crate::retry::RetryPolicy;
 pub use crate::tree::*;
+pub use crate::version::{PlanModule, PlanVersionError};
 use crate::{
+    apt_sources::order_apt_sources,
     core::{core_module, is_core_module},
     eval::{EvalError, evaluate},
     load::{LoadError, load},
     model::Plan,
+    packages::resolve_package_requires,
+    version::check_version,
 };
 
 #[derive(Debug, Error, Display)]
@@ -63,6 +81,50 @@ pub enum PlanError {
 
     /// Failed to convert plan item to resource: {0}
     PlanItemToResource(#[from] PlanItemToResourceError),
+
+    /// An item has `requires_package: "{package}"`, but no item installs package "{package}"
+    UnknownPackage { package: String, span: Span },
+
+    /// Incompatible module version: {0}
+    Version(#[from] PlanVersionError),
+
+    /// Invalid `file_defaults`: {0}
+    FileDefaults(Spanned<ParseError>),
+}
+
+impl PlanError {
+    /// Stable, machine-readable code for this failure, e.g.
+    /// `"plan.load.parse"` or `"params.invalid"`. Nested errors report their
+    /// own, more specific code; nested sub-plan errors report the sub-plan's.
+    pub fn code(&self) -> &'static str {
+        match self {
+            PlanError::StoreRead { .. } => "plan.read",
+            PlanError::InvalidUtf8(_) => "plan.invalid-utf8",
+            PlanError::Load(error) => error.code(),
+            PlanError::Validate(error) => error.code(),
+            PlanError::Eval(error) => error.code(),
+            PlanError::PlanItemToResource(error) => error.code(),
+            PlanError::UnknownPackage { .. } => "plan.unknown-package",
+            PlanError::Version(error) => error.code(),
+            PlanError::FileDefaults(_) => "plan.invalid-file-defaults",
+        }
+    }
+
+    /// The most specific plan source span this error points at, for rendering
+    /// a source excerpt (see [`rimu_interop::render_diagnostic`]). Nested
+    /// sub-plan errors point into the sub-plan's own source.
+    pub fn span(&self) -> Option<&Span> {
+        match self {
+            PlanError::StoreRead { .. } | PlanError::InvalidUtf8(_) => None,
+            PlanError::Load(error) => error.span(),
+            PlanError::Validate(error) => error.span(),
+            PlanError::Eval(error) => error.span(),
+            PlanError::PlanItemToResource(error) => error.span(),
+            PlanError::UnknownPackage { span, .. } => Some(span),
+            PlanError::Version(error) => Some(error.span()),
+            PlanError::FileDefaults(error) => Some(error.span()),
+        }
+    }
 }
 
 /// Plan a `.lusid` file recursively, producing a tree of typed resource params.
@@ -77,33 +139,65 @@ pub enum PlanError {
 /// `Value::String` for a `host-path` field if a literal one was written
 /// in-source (in which case the literal's span source anchors the resolution
 /// directly, not `ctx`).
-#[tracing::instrument(skip_all)]
 pub async fn plan(
     plan_id: PlanId,
     params_value: Option<Spanned<Value>>,
     ctx: &ParamsContext,
     store: &mut Store,
     system: &System,
-) -> Result<PlanTree<ResourceParams>, PlanError> {
+) -> Result<PlanTree<Declared<ResourceParams>>, PlanError> {
+    let (tree, _modules) = plan_with_modules(plan_id, params_value, ctx, store, system).await?;
+    Ok(tree)
+}
+
+/// [`plan`], also returning each plan module used (the root plan first, then in
+/// the order they were loaded, once each) with its declared name and version.
+#[tracing::instrument(skip_all)]
+pub async fn plan_with_modules(
+    plan_id: PlanId,
+    params_value: Option<Spanned<Value>>,
+    ctx: &ParamsContext,
+    store: &mut Store,
+    system: &System,
+) -> Result<(PlanTree<Declared<ResourceParams>>, Vec<PlanModule>), PlanError> {
     tracing::debug!("Plan {plan_id:?} with params {params_value:?}");
-    let children = plan_recursive(plan_id, params_value, ctx, store, system).await?;
-    let tree = PlanTree::Branch {
+    let mut modules = Vec::new();
+    let children = plan_recursive(
+        plan_id,
+        None,
+        params_value,
+        &FileDefaults::default(),
+        ctx,
+        store,
+        system,
+        &mut modules,
+    )
+    .await?;
+    let mut tree = PlanTree::Branch {
         children,
         meta: PlanMeta::default(),
     };
+    resolve_package_requires(&mut tree)?;
+    order_apt_sources(&mut tree);
     tracing::trace!("Planned resource tree: {:?}", tree);
-    Ok(tree)
+    Ok((tree, modules))
 }
 
-/// Inner recursive routine. Each call handles exactly one `.lusid` source: load, validate
-/// params, evaluate `setup`, and convert each returned item into a subtree.
+/// Inner recursive routine. Each call handles exactly one `.lusid` source: load, check
+/// its version against the including item's `requirement` (as `(module, version)`),
+/// validate params, evaluate `setup`, and convert each returned item into a subtree.
+/// `file_defaults` are the including plan's, which this plan's own extend.
+#[allow(clippy::too_many_arguments)]
 async fn plan_recursive(
     plan_id: PlanId,
+    requirement: Option<(&str, Spanned<String>)>,
     params_value: Option<Spanned<Value>>,
+    file_defaults: &FileDefaults,
     ctx: &ParamsContext,
     store: &mut Store,
     system: &System,
-) -> Result<Vec<PlanTree<ResourceParams>>, PlanError> {
+    modules: &mut Vec<PlanModule>,
+) -> Result<Vec<PlanTree<Declared<ResourceParams>>>, PlanError> {
     let store_item_id: StoreItemId = plan_id.clone().into();
     let bytes = store
         .read(&store_item_id)
@@ -116,12 +210,26 @@ async fn plan_recursive(
     let plan = load(&code, &plan_id)?;
 
     let Plan {
-        name: _,
-        version: _,
+        name,
+        version,
         params: param_types,
+        file_defaults: own_file_defaults,
         setup,
     } = plan.into_inner();
 
+    let name = name.map(|name| name.into_inner().0);
+    let version = version.map(|version| version.into_inner().0);
+    if let Some((module, requirement)) = requirement {
+        check_version(module, version.as_deref(), &requirement)?;
+    }
+    if !modules.iter().any(|module| module.plan_id == plan_id) {
+        modules.push(PlanModule {
+            plan_id: plan_id.clone(),
+            name,
+            version,
+        });
+    }
+
     // `validate` returns the coerced params value: relative `host-path`
     // strings have been rewritten into `Value::HostPath`, etc. Feeding the
     // coerced value into `evaluate` is what makes parent → sub-plan
@@ -129,12 +237,29 @@ async fn plan_recursive(
     // `validate`, it's already typed and just passes through.
     let coerced_params = validate(param_types.as_ref(), params_value, ctx)?;
 
+    let file_defaults = match own_file_defaults {
+        Some(value) => FileDefaults::parse_params(value)
+            .map_err(PlanError::FileDefaults)?
+            .inherit(file_defaults),
+        None => file_defaults.clone(),
+    };
+
     let plan_items = evaluate(setup, coerced_params, system)?;
 
+    let root_path = ctx.root_path();
+    let root_path = root_path.canonicalize().unwrap_or(root_path.to_path_buf());
     let mut resources = Vec::with_capacity(plan_items.len());
     for plan_item in plan_items {
+        let location = declared::location(&plan_id, &code, plan_item.span(), &root_path);
         let node = Box::pin(plan_item_to_resource(
-            plan_item, &plan_id, ctx, store, system,
+            plan_item,
+            location,
+            &plan_id,
+            &file_defaults,
+            ctx,
+            store,
+            system,
+            modules,
         ))
         .await?;
         resources.push(node);
@@ -146,35 +271,84 @@ async fn plan_recursive(
 #[derive(Debug, Error, Display)]
 pub enum PlanItemToResourceError {
     /// Missing required parameters in plan item
-    MissingParams,
+    MissingParams { span: Span },
 
     /// Failed to parse parameters for resource: {0}
     Parse(Spanned<ParseError>),
 
     /// Unsupported core module id \"{id}\"
-    UnsupportedCoreModuleId { id: String },
+    UnsupportedCoreModuleId { id: String, span: Span },
+
+    /// Core module \"@core/{id}\" doesn't run on {os}, only on: {platforms}
+    UnsupportedPlatform {
+        id: String,
+        os: Os,
+        platforms: String,
+        span: Span,
+    },
+
+    /// Core module \"@core/brew\" only installs casks on macOS, not on {os}
+    UnsupportedCask { os: Os, span: Span },
 
     /// Failed to compute subtree for nested plan: {0}
     PlanSubtree(#[from] Box<PlanError>),
+
+    /// {0}
+    Version(#[from] PlanVersionError),
+}
+
+impl PlanItemToResourceError {
+    /// Stable error code, see [`PlanError::code`].
+    pub fn code(&self) -> &'static str {
+        match self {
+            PlanItemToResourceError::MissingParams { .. } => "plan.item.missing-params",
+            PlanItemToResourceError::Parse(_) => "plan.item.invalid-params",
+            PlanItemToResourceError::UnsupportedCoreModuleId { .. } => "plan.item.unknown-module",
+            PlanItemToResourceError::UnsupportedPlatform { .. }
+            | PlanItemToResourceError::UnsupportedCask { .. } => "plan.item.unsupported-platform",
+            PlanItemToResourceError::PlanSubtree(error) => error.code(),
+            PlanItemToResourceError::Version(error) => error.code(),
+        }
+    }
+
+    pub fn span(&self) -> Option<&Span> {
+        match self {
+            PlanItemToResourceError::MissingParams { span }
+            | PlanItemToResourceError::UnsupportedCoreModuleId { span, .. }
+            | PlanItemToResourceError::UnsupportedPlatform { span, .. }
+            | PlanItemToResourceError::UnsupportedCask { span, .. } => Some(span),
+            PlanItemToResourceError::Parse(error) => Some(error.span()),
+            PlanItemToResourceError::PlanSubtree(error) => error.span(),
+            PlanItemToResourceError::Version(error) => Some(error.span()),
+        }
+    }
 }
 
 /// Lower a single `PlanItem` to a subtree. Core modules produce a leaf with
 /// [`ResourceParams`]; every other module name is treated as a path relative to the
-/// parent plan and recursed into as a branch.
+/// parent plan and recursed into as a branch. `location` is where the item is
+/// declared, for the leaf.
+#[allow(clippy::too_many_arguments)]
 async fn plan_item_to_resource(
     plan_item: Spanned<crate::model::PlanItem>,
+    location: Option<String>,
     current_plan_id: &PlanId,
+    file_defaults: &FileDefaults,
     ctx: &ParamsContext,
     store: &mut Store,
     system: &System,
-) -> Result<PlanTree<ResourceParams>, PlanItemToResourceError> {
+    modules: &mut Vec<PlanModule>,
+) -> Result<PlanTree<Declared<ResourceParams>>, PlanItemToResourceError> {
     let (plan_item, _span) = plan_item.take();
     let crate::model::PlanItem {
         id: item_id,
         ref module,
+        version,
         params: params_value,
         requires,
         required_by,
+        requires_package,
+        retry,
     } = plan_item;
 
     let id = item_id.map(|id| PlanNodeId::PlanItem {
@@ -188,6 +362,7 @@ async fn plan_item_to_resource(
             plan_id: current_plan_id.clone(),
             item_id,
         })
+        .chain(requires_package.into_iter().map(PlanNodeId::Package))
         .collect();
     let required_by = required_by
         .into_iter()
@@ -199,21 +374,48 @@ async fn plan_item_to_resource(
         .collect();
 
     if let Some(core_module_id) = is_core_module(module) {
-        let params = core_module(core_module_id, params_value)?;
+        if let Some(version) = version {
+            return Err(PlanVersionError::CoreModule {
+                module: module.inner().clone(),
+                span: version.span().clone(),
+            }
+            .into());
+        }
+        let mut params = core_module(core_module_id, module.span(), params_value, ctx, &system.os)?;
+        file_defaults.apply(&mut params);
         Ok(PlanTree::Leaf {
             meta: PlanMeta {
                 id,
                 requires,
                 required_by,
             },
-            node: params,
+            node: Declared::new(params, location).with_retry(retry),
         })
     } else {
         let path = PathBuf::from(module.inner());
         let plan_id = current_plan_id.join(path);
-        let children = plan_recursive(plan_id, params_value, ctx, store, system)
-            .await
-            .map_err(Box::new)?;
+        let requirement = version.map(|version| (module.inner().as_str(), version));
+        let children = plan_recursive(
+            plan_id,
+            requirement,
+            params_value,
+            file_defaults,
+            ctx,
+            store,
+            system,
+            modules,
+        )
+        .await
+        .map_err(Box::new)?;
+        // Items in the module without a retry policy of their own take this
+        // item's.
+        let children = match &retry {
+            Some(retry) => children
+                .into_iter()
+                .map(|tree| tree.map(|node| node.or_retry(retry)))
+                .collect(),
+            None => children,
+        };
         Ok(PlanTree::Branch {
             meta: PlanMeta {
                 id,
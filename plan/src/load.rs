@@ -4,7 +4,7 @@
 use std::{cell::RefCell, rc::Rc};
 
 use displaydoc::Display;
-use rimu::Spanned;
+use rimu::{Span, Spanned};
 use rimu_interop::FromRimu;
 use thiserror::Error;
 
@@ -28,6 +28,28 @@ pub enum LoadError {
     PlanFromRimu(Box<Spanned<PlanFromRimuError>>),
 }
 
+impl LoadError {
+    /// Stable error code, see [`PlanError::code`](crate::PlanError::code).
+    pub fn code(&self) -> &'static str {
+        match self {
+            LoadError::RimuParse(_) => "plan.load.parse",
+            LoadError::NoCode => "plan.load.no-code",
+            LoadError::RimuEval(_) => "plan.load.eval",
+            LoadError::PlanFromRimu(_) => "plan.load.invalid-plan",
+        }
+    }
+
+    // TODO(cc): point into the source for `RimuParse` / `RimuEval` too. Both
+    // carry spans, but rimu renders its own reports for them; wire those up
+    // once we render every plan error through one path.
+    pub fn span(&self) -> Option<&Span> {
+        match self {
+            LoadError::PlanFromRimu(error) => Some(error.inner().span().unwrap_or(error.span())),
+            LoadError::RimuParse(_) | LoadError::NoCode | LoadError::RimuEval(_) => None,
+        }
+    }
+}
+
 /// Parse Rimu source, evaluate it against an empty environment, and project the
 /// resulting value into a [`Plan`] (name, version, params schema, setup function).
 ///
@@ -4,8 +4,11 @@ use displaydoc::Display;
 use lusid_params::{ParamTypes, ParamTypesFromRimuError};
 use rimu::{Function, Span, Spanned, Value};
 use rimu_interop::FromRimu;
+use std::time::Duration;
 use thiserror::Error;
 
+use crate::retry::RetryPolicy;
+
 #[derive(Debug, Clone)]
 pub struct Name(pub String);
 
@@ -49,13 +52,20 @@ impl FromRimu for Version {
 /// An item from setup's returned list.
 /// Example:
 ///   { module: "@core/pkg", id: "install-nvim", params: { package: "nvim" } }
+///
+/// `version` is a semver requirement (e.g. `">=1.2"`) on the `version` the
+/// included plan module declares; see [`crate::version`]. `retry` is how its
+/// failed operations are retried; see [`crate::retry`].
 #[derive(Debug, Clone)]
 pub struct PlanItem {
     pub id: Option<Spanned<String>>,
     pub module: Spanned<String>,
+    pub version: Option<Spanned<String>>,
     pub params: Option<Spanned<Value>>,
     pub requires: Vec<Spanned<String>>,
     pub required_by: Vec<Spanned<String>>,
+    pub requires_package: Vec<Spanned<String>>,
+    pub retry: Option<RetryPolicy>,
 }
 
 #[derive(Debug, Clone, Error, Display)]
@@ -68,6 +78,8 @@ pub enum IntoPlanItemError {
     ModuleNotAString { span: Span },
     /// Property "id" must be a string
     IdNotAString { span: Span },
+    /// Property "version" must be a string
+    VersionNotAString { span: Span },
     /// Property "requires" must be a list
     RequiresNotAList { span: Span },
     /// "requires" list item must be a string
@@ -76,6 +88,49 @@ pub enum IntoPlanItemError {
     RequiredByNotAList { span: Span },
     /// "required_by" list item must be a string
     RequiredByItemNotAString { item_span: Span },
+    /// Property "requires_package" must be a string or a list
+    RequiresPackageNotAStringOrList { span: Span },
+    /// "requires_package" list item must be a string
+    RequiresPackageItemNotAString { item_span: Span },
+    /// Property "retry" must be an object
+    RetryNotAnObject { span: Span },
+    /// Missing property: "retry.count"
+    RetryCountMissing { span: Span },
+    /// Property "retry.count" must be a whole number
+    RetryCountNotANumber { span: Span },
+    /// Property "retry.delay" must be a whole number of seconds
+    RetryDelayNotANumber { span: Span },
+    /// Property "retry.on_exit_codes" must be a list
+    RetryExitCodesNotAList { span: Span },
+    /// "retry.on_exit_codes" list item must be a whole number
+    RetryExitCodeNotANumber { item_span: Span },
+    /// Unknown property: "retry.{property}"
+    RetryUnknownProperty { property: String, span: Span },
+}
+
+impl IntoPlanItemError {
+    /// The offending property's span, when narrower than the whole item.
+    pub fn span(&self) -> Option<&Span> {
+        match self {
+            IntoPlanItemError::NotAnObject | IntoPlanItemError::ModuleMissing => None,
+            IntoPlanItemError::ModuleNotAString { span }
+            | IntoPlanItemError::IdNotAString { span }
+            | IntoPlanItemError::VersionNotAString { span }
+            | IntoPlanItemError::RequiresNotAList { span }
+            | IntoPlanItemError::RequiredByNotAList { span }
+            | IntoPlanItemError::RequiresPackageNotAStringOrList { span }
+            | IntoPlanItemError::RetryNotAnObject { span }
+            | IntoPlanItemError::RetryCountMissing { span }
+            | IntoPlanItemError::RetryCountNotANumber { span }
+            | IntoPlanItemError::RetryDelayNotANumber { span }
+            | IntoPlanItemError::RetryExitCodesNotAList { span }
+            | IntoPlanItemError::RetryUnknownProperty { span, .. } => Some(span),
+            IntoPlanItemError::RequiresItemNotAString { item_span }
+            | IntoPlanItemError::RequiredByItemNotAString { item_span }
+            | IntoPlanItemError::RequiresPackageItemNotAString { item_span }
+            | IntoPlanItemError::RetryExitCodeNotANumber { item_span } => Some(item_span),
+        }
+    }
 }
 
 impl FromRimu for PlanItem {
@@ -110,6 +165,17 @@ impl FromRimu for PlanItem {
             })
             .transpose()?;
 
+        let version = object
+            .swap_remove("version")
+            .map(|sp| {
+                let (value, span) = sp.clone().take();
+                match value {
+                    Value::String(s) => Ok(Spanned::new(s, span)),
+                    _ => Err(IntoPlanItemError::VersionNotAString { span }),
+                }
+            })
+            .transpose()?;
+
         let params = object.swap_remove("params");
 
         let requires = match object.swap_remove("requires") {
@@ -162,16 +228,119 @@ impl FromRimu for PlanItem {
             }
         };
 
+        // A single package name or a list of them, since one is the norm.
+        let requires_package = match object.swap_remove("requires_package") {
+            None => Vec::new(),
+            Some(value) => {
+                let (value, span) = value.clone().take();
+                match value {
+                    Value::String(s) => vec![Spanned::new(s, span)],
+                    Value::List(items) => {
+                        let mut out = Vec::with_capacity(items.len());
+                        for item in items {
+                            let (item_value, item_span) = item.clone().take();
+                            match item_value {
+                                Value::String(s) => out.push(Spanned::new(s, item_span)),
+                                _ => {
+                                    return Err(IntoPlanItemError::RequiresPackageItemNotAString {
+                                        item_span,
+                                    });
+                                }
+                            }
+                        }
+                        out
+                    }
+                    _ => {
+                        return Err(IntoPlanItemError::RequiresPackageNotAStringOrList { span });
+                    }
+                }
+            }
+        };
+
+        let retry = object.swap_remove("retry").map(retry_policy).transpose()?;
+
         Ok(PlanItem {
             id,
             module,
+            version,
             params,
             requires,
             required_by,
+            requires_package,
+            retry,
         })
     }
 }
 
+/// A plan item's `retry` object, as a [`RetryPolicy`].
+fn retry_policy(value: Spanned<Value>) -> Result<RetryPolicy, IntoPlanItemError> {
+    let (value, span) = value.take();
+    let Value::Object(mut object) = value else {
+        return Err(IntoPlanItemError::RetryNotAnObject { span });
+    };
+
+    let count = match object.swap_remove("count") {
+        Some(count) => {
+            let (count, span) = count.take();
+            match count {
+                Value::Number(count) => count
+                    .to_u32()
+                    .ok_or(IntoPlanItemError::RetryCountNotANumber { span })?,
+                _ => return Err(IntoPlanItemError::RetryCountNotANumber { span }),
+            }
+        }
+        None => return Err(IntoPlanItemError::RetryCountMissing { span }),
+    };
+
+    let delay = match object.swap_remove("delay") {
+        Some(delay) => {
+            let (delay, span) = delay.take();
+            match delay {
+                Value::Number(delay) => delay
+                    .to_u32()
+                    .map(|secs| Duration::from_secs(secs.into()))
+                    .ok_or(IntoPlanItemError::RetryDelayNotANumber { span })?,
+                _ => return Err(IntoPlanItemError::RetryDelayNotANumber { span }),
+            }
+        }
+        None => RetryPolicy::DEFAULT_DELAY,
+    };
+
+    let on_exit_codes = match object.swap_remove("on_exit_codes") {
+        None => Vec::new(),
+        Some(value) => {
+            let (value, span) = value.take();
+            let Value::List(items) = value else {
+                return Err(IntoPlanItemError::RetryExitCodesNotAList { span });
+            };
+            let mut out = Vec::with_capacity(items.len());
+            for item in items {
+                let (item_value, item_span) = item.take();
+                let code = match item_value {
+                    Value::Number(code) => code.to_u32().and_then(|code| i32::try_from(code).ok()),
+                    _ => None,
+                };
+                out.push(code.ok_or(IntoPlanItemError::RetryExitCodeNotANumber { item_span })?);
+            }
+            out
+        }
+    };
+
+    // A misspelt property would otherwise be quietly left at its default.
+    if let Some((property, value)) = object.into_iter().next() {
+        return Err(IntoPlanItemError::RetryUnknownProperty {
+            property,
+            span: value.span().clone(),
+        });
+    }
+
+    Ok(RetryPolicy {
+        count,
+        delay,
+        on_exit_codes,
+    })
+}
+
 #[derive(Debug, Clone)]
 pub struct SetupFunction(pub Function);
 
@@ -197,6 +366,9 @@ pub struct Plan {
     pub name: Option<Spanned<Name>>,
     pub version: Option<Spanned<Version>>,
     pub params: Option<Spanned<ParamTypes>>,
+    /// Default file and directory permissions, parsed as
+    /// [`lusid_resource::defaults::FileDefaults`] while planning.
+    pub file_defaults: Option<Spanned<Value>>,
     /// setup: (params, system) => list of PlanItem
     pub setup: Spanned<SetupFunction>,
 }
@@ -217,6 +389,19 @@ pub enum PlanFromRimuError {
     SetupNotAFunction(Spanned<SetupFunctionFromRimuError>),
 }
 
+impl PlanFromRimuError {
+    /// The offending property's span, when narrower than the whole plan.
+    pub fn span(&self) -> Option<&Span> {
+        match self {
+            PlanFromRimuError::NotAnObject | PlanFromRimuError::SetupMissing => None,
+            PlanFromRimuError::Name(error) => Some(error.span()),
+            PlanFromRimuError::Version(error) => Some(error.span()),
+            PlanFromRimuError::Params(error) => Some(error.span()),
+            PlanFromRimuError::SetupNotAFunction(error) => Some(error.span()),
+        }
+    }
+}
+
 impl FromRimu for Plan {
     type Error = PlanFromRimuError;
 
@@ -240,6 +425,8 @@ impl FromRimu for Plan {
             .map(|params| ParamTypes::from_rimu_spanned(params).map_err(PlanFromRimuError::Params))
             .transpose()?;
 
+        let file_defaults = object.swap_remove("file_defaults");
+
         let setup_sp = object
             .swap_remove("setup")
             .ok_or(PlanFromRimuError::SetupMissing)?;
@@ -250,6 +437,7 @@ impl FromRimu for Plan {
             name,
             version,
             params,
+            file_defaults,
             setup,
         })
     }
@@ -0,0 +1,235 @@
+//! `requires_package`: a plan item can depend on a system package being
+//! installed (e.g. an nginx config file on the `nginx` package) without
+//! knowing which item installs it, or even which plan that item lives in.
+//!
+//! While planning, each `requires_package` entry becomes a placeholder
+//! [`PlanNodeId::Package`] in the item's `requires`. Once the whole tree is
+//! planned, [`resolve_package_requires`] replaces each placeholder with the
+//! ids of every leaf installing that package (see
+//! [`ResourceParams::installed_packages`]), minting an id for any such leaf
+//! without one.
+
+use std::collections::BTreeMap;
+
+use cuid2::create_id;
+use lusid_resource::ResourceParams;
+use lusid_tree::Tree;
+
+use crate::{Declared, PlanError, PlanMeta, PlanNodeId, PlanTree};
+
+pub(crate) fn resolve_package_requires(
+    tree: &mut PlanTree<Declared<ResourceParams>>,
+) -> Result<(), PlanError> {
+    if !has_package_requires(tree) {
+        return Ok(());
+    }
+    let mut installers = BTreeMap::new();
+    collect_installers(tree, &mut installers);
+    resolve(tree, &installers)
+}
+
+fn has_package_requires(tree: &PlanTree<Declared<ResourceParams>>) -> bool {
+    let meta = match tree {
+        Tree::Branch { meta, children } => {
+            if children.iter().any(has_package_requires) {
+                return true;
+            }
+            meta
+        }
+        Tree::Leaf { meta, .. } => meta,
+    };
+    meta.requires
+        .iter()
+        .any(|id| matches!(id, PlanNodeId::Package(_)))
+}
+
+fn collect_installers(
+    tree: &mut PlanTree<Declared<ResourceParams>>,
+    installers: &mut BTreeMap<String, Vec<PlanNodeId>>,
+) {
+    match tree {
+        Tree::Branch { children, .. } => {
+            for child in children {
+                collect_installers(child, installers);
+            }
+        }
+        Tree::Leaf { meta, node } => {
+            let packages = node.node.installed_packages();
+            if packages.is_empty() {
+                return;
+            }
+            let id = meta
+                .id
+                .get_or_insert_with(|| PlanNodeId::SubItem {
+                    scope_id: create_id(),
+                    item_id: "packages".to_string(),
+                })
+                .clone();
+            for package in packages {
+                installers
+                    .entry(package.to_string())
+                    .or_default()
+                    .push(id.clone());
+            }
+        }
+    }
+}
+
+fn resolve(
+    tree: &mut PlanTree<Declared<ResourceParams>>,
+    installers: &BTreeMap<String, Vec<PlanNodeId>>,
+) -> Result<(), PlanError> {
+    let meta = match tree {
+        Tree::Branch { meta, children } => {
+            for child in children {
+                resolve(child, installers)?;
+            }
+            meta
+        }
+        Tree::Leaf { meta, .. } => meta,
+    };
+    resolve_meta(meta, installers)
+}
+
+fn resolve_meta(
+    meta: &mut PlanMeta,
+    installers: &BTreeMap<String, Vec<PlanNodeId>>,
+) -> Result<(), PlanError> {
+    let mut requires = Vec::with_capacity(meta.requires.len());
+    for id in std::mem::take(&mut meta.requires) {
+        let PlanNodeId::Package(package) = id else {
+            requires.push(id);
+            continue;
+        };
+        let Some(ids) = installers.get(package.inner()) else {
+            let (package, span) = package.take();
+            return Err(PlanError::UnknownPackage { package, span });
+        };
+        // An item installing the very package it requires doesn't depend on
+        // itself; the edge would only be a cycle.
+        requires.extend(
+            ids.iter()
+                .filter(|id| meta.id.as_ref() != Some(*id))
+                .cloned(),
+        );
+    }
+    meta.requires = requires;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use lusid_resource::resources::apt::AptParams;
+    use rimu::{SourceId, Span, Spanned};
+
+    use super::*;
+    use crate::PlanId;
+
+    fn id(plan: &str, item_id: &str) -> PlanNodeId {
+        PlanNodeId::PlanItem {
+            plan_id: PlanId::Path(plan.into()),
+            item_id: item_id.into(),
+        }
+    }
+
+    fn package(package: &str, start: usize) -> PlanNodeId {
+        let span = Span::new(SourceId::empty(), start, start + package.len());
+        PlanNodeId::Package(Spanned::new(package.into(), span))
+    }
+
+    fn apt(
+        id: Option<PlanNodeId>,
+        requires: Vec<PlanNodeId>,
+        packages: &[&str],
+    ) -> PlanTree<Declared<ResourceParams>> {
+        let packages = packages.iter().map(|package| package.to_string()).collect();
+        PlanTree::leaf(
+            PlanMeta {
+                id,
+                requires,
+                required_by: vec![],
+            },
+            Declared::new(ResourceParams::Apt(AptParams::Packages { packages }), None),
+        )
+    }
+
+    fn leaves(tree: &PlanTree<Declared<ResourceParams>>) -> Vec<&PlanMeta> {
+        match tree {
+            Tree::Branch { children, .. } => children.iter().flat_map(leaves).collect(),
+            Tree::Leaf { meta, .. } => vec![meta],
+        }
+    }
+
+    #[test]
+    fn finds_a_package_installed_in_another_plan() {
+        let mut tree = PlanTree::branch(
+            PlanMeta::default(),
+            [
+                PlanTree::branch(
+                    PlanMeta::default(),
+                    [apt(
+                        Some(id("web.lusid", "certbot")),
+                        vec![package("nginx", 0)],
+                        &["certbot"],
+                    )],
+                ),
+                PlanTree::branch(PlanMeta::default(), [apt(None, vec![], &["nginx"])]),
+            ],
+        );
+        resolve_package_requires(&mut tree).unwrap();
+
+        let leaves = leaves(&tree);
+        let Some(installer @ PlanNodeId::SubItem { .. }) = &leaves[1].id else {
+            panic!("expected a minted id, got {:?}", leaves[1].id);
+        };
+        assert_eq!(leaves[0].requires, vec![installer.clone()]);
+    }
+
+    #[test]
+    fn requires_every_item_installing_a_package() {
+        let mut tree = PlanTree::branch(
+            PlanMeta::default(),
+            [
+                apt(Some(id("web.lusid", "web")), vec![], &["nginx", "curl"]),
+                apt(
+                    Some(id("proxy.lusid", "proxy")),
+                    vec![package("nginx", 0)],
+                    &["nginx"],
+                ),
+                apt(
+                    Some(id("web.lusid", "site")),
+                    vec![package("nginx", 0)],
+                    &[],
+                ),
+            ],
+        );
+        resolve_package_requires(&mut tree).unwrap();
+
+        let leaves = leaves(&tree);
+        // Not itself, though it installs nginx too.
+        assert_eq!(leaves[1].requires, vec![id("web.lusid", "web")]);
+        assert_eq!(
+            leaves[2].requires,
+            vec![id("web.lusid", "web"), id("proxy.lusid", "proxy")]
+        );
+    }
+
+    #[test]
+    fn an_unknown_package_points_at_its_requires_package() {
+        let mut tree = PlanTree::branch(
+            PlanMeta::default(),
+            [apt(
+                Some(id("web.lusid", "site")),
+                vec![package("ngnix", 42)],
+                &["curl"],
+            )],
+        );
+        let error = resolve_package_requires(&mut tree).unwrap_err();
+
+        let PlanError::UnknownPackage { package, span } = error else {
+            panic!("expected an unknown package, got {error:?}");
+        };
+        assert_eq!(package, "ngnix");
+        assert_eq!((span.start(), span.end()), (42, 47));
+    }
+}
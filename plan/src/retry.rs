@@ -0,0 +1,116 @@
+//! Plan item retry policies.
+//!
+//! An item can ask for its operations to be retried when they fail, for
+//! failures that tend to pass on their own, like a flaky mirror or another
+//! process holding the apt lock:
+//!
+//! ```yaml
+//! - module: "@core/apt"
+//!   params: { package: "nginx" }
+//!   retry: { count: 3, delay: 5, on_exit_codes: [100] }
+//! ```
+//!
+//! `count` is how many times to retry after the first attempt. The first
+//! retry waits `delay` seconds (default 5), and each one after waits twice
+//! as long as the last, up to 5 minutes. With `on_exit_codes`, only a failed command exiting
+//! with one of those codes is retried.
+//!
+//! An item including a plan module passes its policy on to every item in
+//! it that doesn't have its own. The retries themselves happen as the
+//! operations are applied, in `lusid-apply`.
+
+use std::time::Duration;
+
+/// How an item's failed operations are retried.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub count: u32,
+    pub delay: Duration,
+    /// Retry only failures exiting with one of these, or any failure if
+    /// empty.
+    pub on_exit_codes: Vec<i32>,
+}
+
+impl RetryPolicy {
+    pub const DEFAULT_DELAY: Duration = Duration::from_secs(5);
+    /// The longest wait before a retry, however many came before it.
+    pub const MAX_DELAY: Duration = Duration::from_secs(5 * 60);
+
+    /// Whether to retry after `retries` retries so far, for a failure
+    /// exiting with `exit_code`. A failure that didn't exit, e.g. one that
+    /// couldn't start the command, is only retried when no exit codes are
+    /// given.
+    pub fn retries(&self, retries: u32, exit_code: Option<i32>) -> bool {
+        if retries >= self.count {
+            return false;
+        }
+        if self.on_exit_codes.is_empty() {
+            return true;
+        }
+        exit_code.is_some_and(|code| self.on_exit_codes.contains(&code))
+    }
+
+    /// How long to wait before retry number `retry`, counting from 0. A
+    /// `delay` longer than [`RetryPolicy::MAX_DELAY`] is kept as given.
+    pub fn delay_before(&self, retry: u32) -> Duration {
+        self.delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(Self::MAX_DELAY.max(self.delay))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(count: u32, on_exit_codes: Vec<i32>) -> RetryPolicy {
+        RetryPolicy {
+            count,
+            delay: RetryPolicy::DEFAULT_DELAY,
+            on_exit_codes,
+        }
+    }
+
+    #[test]
+    fn retries_up_to_count() {
+        let retry = policy(2, vec![]);
+        assert!(retry.retries(0, Some(1)));
+        assert!(retry.retries(1, None));
+        assert!(!retry.retries(2, Some(1)));
+        assert!(!policy(0, vec![]).retries(0, Some(1)));
+    }
+
+    #[test]
+    fn retries_only_listed_exit_codes() {
+        let retry = policy(3, vec![100]);
+        assert!(retry.retries(0, Some(100)));
+        assert!(!retry.retries(0, Some(1)));
+        assert!(!retry.retries(0, None));
+        assert!(!retry.retries(3, Some(100)));
+    }
+
+    #[test]
+    fn delay_doubles_each_retry() {
+        let retry = RetryPolicy {
+            delay: Duration::from_secs(2),
+            ..policy(4, vec![])
+        };
+        let delays: Vec<_> = (0..4).map(|n| retry.delay_before(n)).collect();
+        assert_eq!(delays, [2, 4, 8, 16].map(Duration::from_secs).to_vec());
+    }
+
+    #[test]
+    fn delay_stops_doubling_at_the_cap() {
+        let retry = policy(20, vec![]);
+        assert_eq!(retry.delay_before(5), Duration::from_secs(160));
+        assert_eq!(retry.delay_before(6), RetryPolicy::MAX_DELAY);
+        assert_eq!(retry.delay_before(19), RetryPolicy::MAX_DELAY);
+        assert_eq!(retry.delay_before(u32::MAX), RetryPolicy::MAX_DELAY);
+
+        let slow = RetryPolicy {
+            delay: Duration::from_secs(600),
+            ..policy(3, vec![])
+        };
+        assert_eq!(slow.delay_before(2), Duration::from_secs(600));
+    }
+}
@@ -58,15 +58,20 @@ where
 }
 
 /// Convert a [`PlanTree`] into a [`ViewTree`] for TUI display. Branch labels use the
-/// branch's `PlanNodeId` (rendered) or `.` if the branch is anonymous.
-pub fn render_plan_tree<Node>(tree: PlanTree<Node>) -> ViewTree
+/// branch's `PlanNodeId` (rendered) or `.` if the branch is anonymous. Borrows the
+/// tree, so streaming a view of it doesn't clone its nodes.
+pub fn render_plan_tree<Node>(tree: &PlanTree<Node>) -> ViewTree
 where
     Node: Render,
 {
     match tree {
         Tree::Branch { meta, children } => ViewTree::Branch {
-            view: meta.id.map(|id| id.render()).unwrap_or(".".render()),
-            children: children.into_iter().map(render_plan_tree).collect(),
+            view: meta
+                .id
+                .as_ref()
+                .map(|id| id.render())
+                .unwrap_or(".".render()),
+            children: children.iter().map(render_plan_tree).collect(),
         },
         Tree::Leaf { meta: _, node } => ViewTree::Leaf {
             view: node.render(),
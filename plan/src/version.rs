@@ -0,0 +1,149 @@
+//! Plan module versions.
+//!
+//! A plan declares its own `version` (e.g. `"1.2.0"`), and an item including
+//! it as a module can require a compatible one:
+//!
+//! ```yaml
+//! - module: "./base.lusid"
+//!   version: ">=1.2"
+//! ```
+//!
+//! Requirements use Cargo's semver syntax and are checked as each module is
+//! loaded, so an incompatible module fails planning before anything is
+//! probed. Versions may leave off trailing components (`"1.2"` is `1.2.0`).
+//!
+//! Every plan module loaded, with its declared version, is collected as a
+//! [`PlanModule`] (see [`plan_with_modules`](crate::plan_with_modules)).
+//!
+//! Note(cc): lusid has no lockfile yet to pin module versions in; for now
+//! they are only reported, in the `ResourceParams` update of an apply.
+
+use displaydoc::Display;
+use rimu::{Span, Spanned};
+use semver::{Version, VersionReq};
+use thiserror::Error;
+
+use crate::PlanId;
+
+/// A plan module used by a plan, including the root plan itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanModule {
+    pub plan_id: PlanId,
+    pub name: Option<String>,
+    pub version: Option<String>,
+}
+
+#[derive(Debug, Error, Display)]
+pub enum PlanVersionError {
+    /// Invalid version requirement "{requirement}": {message}
+    InvalidRequirement {
+        requirement: String,
+        message: String,
+        span: Span,
+    },
+
+    /// Core module "{module}" can't have a version requirement
+    CoreModule { module: String, span: Span },
+
+    /// Module "{module}" must be version "{requirement}", but declares no version
+    Missing {
+        module: String,
+        requirement: String,
+        span: Span,
+    },
+
+    /// Module "{module}" declares version "{version}", which isn't a semantic version: {message}
+    InvalidVersion {
+        module: String,
+        version: String,
+        message: String,
+        span: Span,
+    },
+
+    /// Module "{module}" is version {version}, but "{requirement}" is required
+    Mismatch {
+        module: String,
+        version: String,
+        requirement: String,
+        span: Span,
+    },
+}
+
+impl PlanVersionError {
+    /// Stable error code, see [`PlanError::code`](crate::PlanError::code).
+    pub fn code(&self) -> &'static str {
+        match self {
+            PlanVersionError::InvalidRequirement { .. } => "plan.version.invalid-requirement",
+            PlanVersionError::CoreModule { .. } => "plan.version.core-module",
+            PlanVersionError::Missing { .. } => "plan.version.missing",
+            PlanVersionError::InvalidVersion { .. } => "plan.version.invalid",
+            PlanVersionError::Mismatch { .. } => "plan.version.mismatch",
+        }
+    }
+
+    /// The item's `version` requirement, in the including plan's source.
+    pub fn span(&self) -> &Span {
+        match self {
+            PlanVersionError::InvalidRequirement { span, .. }
+            | PlanVersionError::CoreModule { span, .. }
+            | PlanVersionError::Missing { span, .. }
+            | PlanVersionError::InvalidVersion { span, .. }
+            | PlanVersionError::Mismatch { span, .. } => span,
+        }
+    }
+}
+
+/// Check a loaded module's declared `version` against the including item's
+/// `requirement`.
+pub(crate) fn check_version(
+    module: &str,
+    version: Option<&str>,
+    requirement: &Spanned<String>,
+) -> Result<(), PlanVersionError> {
+    let span = requirement.span();
+    let requirement = requirement.inner();
+
+    let version_req =
+        VersionReq::parse(requirement).map_err(|error| PlanVersionError::InvalidRequirement {
+            requirement: requirement.clone(),
+            message: error.to_string(),
+            span: span.clone(),
+        })?;
+
+    let Some(version) = version else {
+        return Err(PlanVersionError::Missing {
+            module: module.to_owned(),
+            requirement: requirement.clone(),
+            span: span.clone(),
+        });
+    };
+
+    let parsed = parse_version(version).map_err(|error| PlanVersionError::InvalidVersion {
+        module: module.to_owned(),
+        version: version.to_owned(),
+        message: error.to_string(),
+        span: span.clone(),
+    })?;
+
+    if !version_req.matches(&parsed) {
+        return Err(PlanVersionError::Mismatch {
+            module: module.to_owned(),
+            version: version.to_owned(),
+            requirement: requirement.clone(),
+            span: span.clone(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Parse a declared plan version, padding a bare `"1"` or `"1.2"` with zeros.
+fn parse_version(version: &str) -> Result<Version, semver::Error> {
+    let version = version.trim();
+    let is_short = version.chars().all(|c| c.is_ascii_digit() || c == '.');
+    match version.matches('.').count() {
+        0 if is_short => Version::parse(&format!("{version}.0.0")),
+        1 if is_short => Version::parse(&format!("{version}.0")),
+        _ => Version::parse(version),
+    }
+}
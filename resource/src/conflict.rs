@@ -0,0 +1,488 @@
+//! Two plan items managing the same thing, wanting different things of it.
+//!
+//! Left alone, each would change the machine to match itself on every apply,
+//! undoing the other. [`Claims`] catches this before any state is probed: it
+//! takes each resource's claim on what it manages (a path, a path's mode, a
+//! package, a user) and refuses a second claim that disagrees with the first,
+//! naming both resources and where they're declared.
+//!
+//! Claims that merely overlap are fine: two plans installing `nginx`, or one
+//! wanting a file present and another wanting it with some contents.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use lusid_operation::operations::file::FilePath;
+use lusid_operation::operations::git::GitConfigScope;
+use lusid_operation::operations::gpg::GpgKeyring;
+use thiserror::Error;
+
+use crate::Resource;
+use crate::resources::cron::CronResource;
+use crate::resources::directory::DirectoryResource;
+use crate::resources::dnf::DnfResource;
+use crate::resources::file::FileResource;
+use crate::resources::gpg_key::GpgKeyResource;
+use crate::resources::group::GroupResource;
+use crate::resources::nix::NixResource;
+use crate::resources::systemd::SystemdResource;
+use crate::resources::systemd_unit::SystemdUnitResource;
+use crate::resources::user::UserResource;
+
+/// What a resource manages.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Identity {
+    /// Whether a path exists, and what it is.
+    Path(FilePath),
+    PathMode(FilePath),
+    PathUser(FilePath),
+    PathGroup(FilePath),
+    Package {
+        manager: String,
+        name: String,
+    },
+    User(String),
+    Group(String),
+    SystemdUnit(String),
+    Cron {
+        user: Option<String>,
+        name: String,
+    },
+    GitConfig {
+        scope: GitConfigScope,
+        key: String,
+    },
+}
+
+impl Display for Identity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Identity::Path(path) => write!(f, "path {path}"),
+            Identity::PathMode(path) => write!(f, "the mode of {path}"),
+            Identity::PathUser(path) => write!(f, "the user of {path}"),
+            Identity::PathGroup(path) => write!(f, "the group of {path}"),
+            Identity::Package { manager, name } => write!(f, "{manager} package {name}"),
+            Identity::User(name) => write!(f, "user {name}"),
+            Identity::Group(name) => write!(f, "group {name}"),
+            Identity::SystemdUnit(name) => write!(f, "systemd unit {name}"),
+            Identity::Cron { user, name } => match user {
+                Some(user) => write!(f, "{user}'s cron entry {name}"),
+                None => write!(f, "cron entry {name}"),
+            },
+            Identity::GitConfig { scope, key } => write!(f, "{scope} git config {key}"),
+        }
+    }
+}
+
+/// What a resource wants of its [`Identity`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Desired {
+    /// Exists as a `kind`, whatever else is said of it.
+    Present(&'static str),
+    Absent,
+    /// Exists as a `kind`, exactly as described.
+    Exactly(&'static str, String),
+}
+
+impl Desired {
+    fn agrees(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Desired::Absent, Desired::Absent) => true,
+            (Desired::Absent, _) | (_, Desired::Absent) => false,
+            (Desired::Present(kind), Desired::Present(other) | Desired::Exactly(other, _))
+            | (Desired::Exactly(kind, _), Desired::Present(other)) => kind == other,
+            (exactly, other) => exactly == other,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("{first} and {second} both manage {identity}, and disagree on it")]
+pub struct ConflictError {
+    pub identity: Identity,
+    pub first: String,
+    pub second: String,
+}
+
+impl ConflictError {
+    /// Stable, machine-readable code for this failure.
+    pub fn code(&self) -> &'static str {
+        "resource.conflict"
+    }
+}
+
+/// The claims of every resource seen so far, each with the first resource to
+/// make it.
+#[derive(Debug, Default)]
+pub struct Claims {
+    claims: BTreeMap<Identity, (Desired, String)>,
+}
+
+impl Claims {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Claim what `resource` manages, or refuse if an earlier resource
+    /// claimed it and wants something else of it. `declared` names the
+    /// resource in the error, e.g. with where it's declared.
+    pub fn claim(
+        &mut self,
+        resource: &Resource,
+        declared: impl Into<String>,
+    ) -> Result<(), ConflictError> {
+        let Some((identity, desired)) = claim(resource) else {
+            return Ok(());
+        };
+        let declared = declared.into();
+        match self.claims.get(&identity) {
+            Some((first_desired, first)) => {
+                if first_desired.agrees(&desired) {
+                    return Ok(());
+                }
+                Err(ConflictError {
+                    identity,
+                    first: first.clone(),
+                    second: declared,
+                })
+            }
+            None => {
+                self.claims.insert(identity, (desired, declared));
+                Ok(())
+            }
+        }
+    }
+}
+
+fn claim(resource: &Resource) -> Option<(Identity, Desired)> {
+    use Desired::{Absent, Exactly, Present};
+
+    let claim = match resource {
+        Resource::File(file) => match file {
+            FileResource::Sourced { source, path, .. } => (
+                Identity::Path(path.clone()),
+                Exactly("file", format!("copy of {source}")),
+            ),
+            FileResource::Contents { contents, path, .. } => (
+                Identity::Path(path.clone()),
+                Exactly("file", contents.clone()),
+            ),
+            FileResource::Linked { source, path, .. } => (
+                Identity::Path(path.clone()),
+                Exactly("symlink", source.to_string()),
+            ),
+            FileResource::Secret { name, path, .. } => (
+                Identity::Path(path.clone()),
+                Exactly("file", format!("secret {name}")),
+            ),
+            FileResource::Present { path, .. } => (Identity::Path(path.clone()), Present("file")),
+            FileResource::Absent { path, .. } => (Identity::Path(path.clone()), Absent),
+            FileResource::Mode { path, mode } => (
+                Identity::PathMode(path.clone()),
+                Exactly("mode", mode.to_string()),
+            ),
+            FileResource::User { path, user } => (
+                Identity::PathUser(path.clone()),
+                Exactly("user", user.to_string()),
+            ),
+            FileResource::Group { path, group } => (
+                Identity::PathGroup(path.clone()),
+                Exactly("group", group.to_string()),
+            ),
+            // Any number of files can share an ancestor; the first to create
+            // it decides its mode, user and group.
+            FileResource::Parent { .. } => return None,
+            // Each file it keeps is claimed by its own `Sourced`.
+            // Note(cc): another item's file under a `delete` sync is
+            // extraneous to it, so one removes what the other writes. Claims
+            // are per path, so can't catch that yet.
+            FileResource::Extraneous { .. } => return None,
+        },
+        Resource::Directory(directory) => match directory {
+            DirectoryResource::Sourced { source, path } => (
+                Identity::Path(path.clone()),
+                Exactly("directory", format!("copy of {source}")),
+            ),
+            DirectoryResource::Linked { source, path } => (
+                Identity::Path(path.clone()),
+                Exactly("symlink", source.to_string()),
+            ),
+            DirectoryResource::Present { path } => {
+                (Identity::Path(path.clone()), Present("directory"))
+            }
+            DirectoryResource::Absent { path } => (Identity::Path(path.clone()), Absent),
+            DirectoryResource::Mode { path, mode } => (
+                Identity::PathMode(path.clone()),
+                Exactly("mode", mode.to_string()),
+            ),
+            DirectoryResource::User { path, user } => (
+                Identity::PathUser(path.clone()),
+                Exactly("user", user.to_string()),
+            ),
+            DirectoryResource::Group { path, group } => (
+                Identity::PathGroup(path.clone()),
+                Exactly("group", group.to_string()),
+            ),
+        },
+        Resource::Networkd(networkd) => (
+            Identity::Path(networkd.path.clone()),
+            Exactly("file", networkd.content.clone()),
+        ),
+        Resource::Wireguard(wireguard) => (
+            Identity::Path(wireguard.path.clone()),
+            Exactly("file", wireguard.content.clone()),
+        ),
+        Resource::TlsCert(tls_cert) => (
+            Identity::Path(tls_cert.cert_path.clone()),
+            Exactly(
+                "certificate",
+                format!("[{}], {}", tls_cert.names.join(", "), tls_cert.issuer),
+            ),
+        ),
+        // A user's keyring holds many keys, but an apt keyring is one key's.
+        Resource::GpgKey(GpgKeyResource {
+            fingerprint,
+            keyring: GpgKeyring::File { path },
+            ..
+        }) => (
+            Identity::Path(path.clone()),
+            Exactly("keyring", fingerprint.clone()),
+        ),
+        Resource::SystemdUnit(SystemdUnitResource::UnitFile { path, content, .. }) => (
+            Identity::Path(path.clone()),
+            Exactly("file", content.clone()),
+        ),
+        Resource::SystemdUnit(SystemdUnitResource::Unit(unit)) | Resource::Systemd(unit) => {
+            let SystemdResource {
+                name,
+                enabled,
+                active,
+            } = unit;
+            (
+                Identity::SystemdUnit(name.clone()),
+                Exactly("unit", format!("enabled = {enabled}, active = {active}")),
+            )
+        }
+        Resource::Apt(apt) => (package("apt", &apt.package), Present("package")),
+        Resource::Pacman(pacman) => (package("pacman", &pacman.package), Present("package")),
+        Resource::Aur(aur) => (package("pacman", &aur.package), Present("package")),
+        Resource::Dnf(dnf) => (
+            package("dnf", dnf.package()),
+            match dnf {
+                DnfResource::Present { .. } => Present("package"),
+                DnfResource::Absent { .. } => Absent,
+            },
+        ),
+        Resource::Apk(apk) => (package("apk", &apk.package), Present("package")),
+        Resource::Nix(nix) => (
+            package("nix", nix.attribute()),
+            match nix {
+                NixResource::Present { .. } => Present("package"),
+                NixResource::Absent { .. } => Absent,
+            },
+        ),
+        Resource::Brew(brew) => (package("brew", &brew.package), Present("package")),
+        Resource::Pip(pip) => (
+            package(&format!("pip ({})", pip.target), &pip.package),
+            match &pip.version {
+                Some(version) => Exactly("package", version.clone()),
+                None => Present("package"),
+            },
+        ),
+        // Supplementary groups are appended, so any number of items can add
+        // to them; it's the rest that one would undo for the other.
+        Resource::User(user) => match user {
+            UserResource::Present {
+                name,
+                uid,
+                group,
+                home,
+                shell,
+                ..
+            } => (
+                Identity::User(name.clone()),
+                Exactly(
+                    "user",
+                    format!("uid = {uid:?}, group = {group:?}, home = {home:?}, shell = {shell:?}"),
+                ),
+            ),
+            UserResource::Absent { name, .. } => (Identity::User(name.clone()), Absent),
+        },
+        // Likewise a group's appended members.
+        Resource::Group(group) => match group {
+            GroupResource::Present {
+                name, gid, system, ..
+            } => (
+                Identity::Group(name.clone()),
+                Exactly("group", format!("gid = {gid:?}, system = {system}")),
+            ),
+            GroupResource::Absent { name } => (Identity::Group(name.clone()), Absent),
+        },
+        Resource::Cron(cron) => match cron {
+            CronResource::Present { name, job, user } => (
+                Identity::Cron {
+                    user: user.clone(),
+                    name: name.clone(),
+                },
+                Exactly("entry", job.clone()),
+            ),
+            CronResource::Absent { name, user } => (
+                Identity::Cron {
+                    user: user.clone(),
+                    name: name.clone(),
+                },
+                Absent,
+            ),
+        },
+        Resource::GitConfig(git_config) => (
+            Identity::GitConfig {
+                scope: git_config.scope.clone(),
+                key: git_config.key.clone(),
+            },
+            match git_config.values.as_slice() {
+                [] => Absent,
+                values => Exactly("values", values.join(", ")),
+            },
+        ),
+        // Note(cc): the rest either have no single identity to claim (a
+        // command, the firewall's rules) or aren't yet worth the arm (a git
+        // checkout's path, a podman container's name). Add them as plans
+        // start to collide on them.
+        _ => return None,
+    };
+    Some(claim)
+}
+
+fn package(manager: &str, name: &str) -> Identity {
+    Identity::Package {
+        manager: manager.to_string(),
+        name: name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_contents(path: &str, contents: &str) -> Resource {
+        Resource::File(FileResource::Contents {
+            contents: contents.into(),
+            path: FilePath::new(path),
+            restarts: None,
+        })
+    }
+
+    #[test]
+    fn refuses_disagreeing_claims_on_a_path() {
+        let mut claims = Claims::new();
+        claims
+            .claim(
+                &file_contents("/etc/motd", "hi"),
+                "first (declared at a.lusid:1)",
+            )
+            .unwrap();
+        claims
+            .claim(&file_contents("/etc/motd", "hi"), "again")
+            .unwrap();
+        let error = claims
+            .claim(
+                &file_contents("/etc/motd", "bye"),
+                "second (declared at b.lusid:2)",
+            )
+            .unwrap_err();
+        assert_eq!(error.code(), "resource.conflict");
+        assert_eq!(
+            error.to_string(),
+            "first (declared at a.lusid:1) and second (declared at b.lusid:2) both manage path /etc/motd, and disagree on it"
+        );
+
+        let directory = Resource::Directory(DirectoryResource::Present {
+            path: FilePath::new("/etc/motd"),
+        });
+        assert!(claims.claim(&directory, "directory").is_err());
+    }
+
+    #[test]
+    fn lets_overlapping_claims_through() {
+        let mut claims = Claims::new();
+        let present = Resource::File(FileResource::Present {
+            path: FilePath::new("/etc/motd"),
+            restarts: None,
+        });
+        claims.claim(&present, "present").unwrap();
+        claims
+            .claim(&file_contents("/etc/motd", "hi"), "contents")
+            .unwrap();
+
+        let nginx = || {
+            Resource::Apt(crate::resources::apt::AptResource {
+                package: "nginx".into(),
+            })
+        };
+        claims.claim(&nginx(), "one").unwrap();
+        claims.claim(&nginx(), "two").unwrap();
+    }
+
+    fn user(uid: Option<u32>, append_groups: &[&str]) -> Resource {
+        Resource::User(UserResource::Present {
+            name: "alice".into(),
+            uid,
+            group: None,
+            append_groups: Some(
+                append_groups
+                    .iter()
+                    .map(|group| group.to_string())
+                    .collect(),
+            ),
+            comment: None,
+            home: None,
+            shell: None,
+            system: false,
+            create_home: true,
+        })
+    }
+
+    fn group(gid: Option<u32>, append_users: &[&str]) -> Resource {
+        Resource::Group(GroupResource::Present {
+            name: "docker".into(),
+            gid,
+            system: false,
+            append_users: Some(append_users.iter().map(|user| user.to_string()).collect()),
+        })
+    }
+
+    #[test]
+    fn refuses_a_user_both_present_and_absent() {
+        let mut claims = Claims::new();
+        let absent = Resource::User(UserResource::Absent {
+            name: "alice".into(),
+            remove_home: false,
+        });
+        claims.claim(&absent, "absent").unwrap();
+        let error = claims.claim(&user(None, &[]), "present").unwrap_err();
+        assert_eq!(error.identity, Identity::User("alice".into()));
+    }
+
+    #[test]
+    fn lets_appended_members_through() {
+        let mut claims = Claims::new();
+        claims.claim(&group(Some(999), &["alice"]), "one").unwrap();
+        claims.claim(&group(Some(999), &["bob"]), "two").unwrap();
+        claims.claim(&user(Some(1000), &["docker"]), "one").unwrap();
+        claims.claim(&user(Some(1000), &["wheel"]), "two").unwrap();
+    }
+
+    #[test]
+    fn refuses_clashing_ids() {
+        let mut claims = Claims::new();
+        claims.claim(&group(Some(999), &["alice"]), "one").unwrap();
+        let error = claims
+            .claim(&group(Some(998), &["alice"]), "two")
+            .unwrap_err();
+        assert_eq!(error.identity, Identity::Group("docker".into()));
+
+        claims.claim(&user(Some(1000), &[]), "one").unwrap();
+        let error = claims.claim(&user(Some(1001), &[]), "two").unwrap_err();
+        assert_eq!(error.identity, Identity::User("alice".into()));
+    }
+}
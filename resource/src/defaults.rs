@@ -0,0 +1,203 @@
+//! Default permissions for the files and directories a plan creates: the
+//! mode and owner each `@core/file` and `@core/directory` item gets when it
+//! doesn't say.
+//!
+//! A plan declares them with a top-level `file_defaults` key, and nested
+//! plans inherit them field by field, so the root plan's defaults hold for
+//! the whole apply unless a module narrows them. Items that set a field keep
+//! it, and so do:
+//!
+//! - `@core/secret`, which defaults to `0o600` itself and never loosens.
+//! - `linked` files and directories, which have no permissions of their own.
+//!
+//! Directories a `parents` option creates take the directory defaults.
+
+use lusid_operation::operations::file::{FileGroup, FileMode, FileUser};
+use lusid_params::{ParseError, ParseParams, StructFields};
+use rimu::{Spanned, Value};
+
+use crate::ResourceParams;
+use crate::resources::directory::DirectoryParams;
+use crate::resources::file::{FileParams, FileParents};
+
+/// `file_defaults: { mode, directory_mode, user, group }`. Any left unset
+/// fall back to the including plan's, then to the umask and the apply user.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileDefaults {
+    /// Mode for files.
+    pub mode: Option<FileMode>,
+    /// Mode for directories.
+    pub directory_mode: Option<FileMode>,
+    pub user: Option<FileUser>,
+    pub group: Option<FileGroup>,
+}
+
+impl ParseParams for FileDefaults {
+    fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
+        let mut fields = StructFields::new(value)?;
+        let out = FileDefaults {
+            mode: fields.optional_u32("mode")?.map(FileMode::new),
+            directory_mode: fields.optional_u32("directory_mode")?.map(FileMode::new),
+            user: fields.optional_string("user")?.map(FileUser::new),
+            group: fields.optional_string("group")?.map(FileGroup::new),
+        };
+        fields.finish()?;
+        Ok(out)
+    }
+}
+
+impl FileDefaults {
+    /// These defaults, with any unset field taken from `parent`'s.
+    pub fn inherit(self, parent: &FileDefaults) -> FileDefaults {
+        FileDefaults {
+            mode: self.mode.or(parent.mode),
+            directory_mode: self.directory_mode.or(parent.directory_mode),
+            user: self.user.or_else(|| parent.user.clone()),
+            group: self.group.or_else(|| parent.group.clone()),
+        }
+    }
+
+    /// Fill in whatever `params` leaves unset.
+    pub fn apply(&self, params: &mut ResourceParams) {
+        match params {
+            ResourceParams::File(
+                FileParams::Sourced {
+                    mode,
+                    user,
+                    group,
+                    parents,
+                    ..
+                }
+                | FileParams::Contents {
+                    mode,
+                    user,
+                    group,
+                    parents,
+                    ..
+                }
+                | FileParams::Present {
+                    mode,
+                    user,
+                    group,
+                    parents,
+                    ..
+                },
+            ) => {
+                self.fill(self.mode, mode, user, group);
+                if let Some(parents) = parents {
+                    self.fill_parents(parents);
+                }
+            }
+            ResourceParams::File(
+                FileParams::Linked {
+                    parents: Some(parents),
+                    ..
+                }
+                | FileParams::Synced {
+                    parents: Some(parents),
+                    ..
+                },
+            ) => self.fill_parents(parents),
+            ResourceParams::Directory(
+                DirectoryParams::Sourced {
+                    mode, user, group, ..
+                }
+                | DirectoryParams::Present {
+                    mode, user, group, ..
+                },
+            ) => self.fill(self.directory_mode, mode, user, group),
+            _ => {}
+        }
+    }
+
+    fn fill_parents(&self, parents: &mut FileParents) {
+        let FileParents { mode, user, group } = parents;
+        self.fill(self.directory_mode, mode, user, group);
+    }
+
+    fn fill(
+        &self,
+        default_mode: Option<FileMode>,
+        mode: &mut Option<FileMode>,
+        user: &mut Option<FileUser>,
+        group: &mut Option<FileGroup>,
+    ) {
+        if mode.is_none() {
+            *mode = default_mode;
+        }
+        if user.is_none() {
+            *user = self.user.clone();
+        }
+        if group.is_none() {
+            *group = self.group.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lusid_operation::operations::file::FilePath;
+
+    fn defaults() -> FileDefaults {
+        FileDefaults {
+            mode: Some(FileMode::new(0o644)),
+            directory_mode: Some(FileMode::new(0o755)),
+            user: Some(FileUser::new("app")),
+            group: None,
+        }
+    }
+
+    #[test]
+    fn fills_only_what_an_item_leaves_unset() {
+        let mut file = ResourceParams::File(FileParams::Present {
+            path: FilePath::new("/srv/app/env"),
+            mode: Some(FileMode::new(0o640)),
+            user: None,
+            group: None,
+            restarts: None,
+            parents: Some(FileParents::default()),
+        });
+        defaults().apply(&mut file);
+        let ResourceParams::File(FileParams::Present {
+            mode,
+            user,
+            group,
+            parents: Some(parents),
+            ..
+        }) = file
+        else {
+            panic!("expected present file with parents");
+        };
+        assert_eq!(mode, Some(FileMode::new(0o640)));
+        assert_eq!(user, Some(FileUser::new("app")));
+        assert_eq!(group, None);
+        assert_eq!(parents.mode, Some(FileMode::new(0o755)));
+
+        let mut directory = ResourceParams::Directory(DirectoryParams::Present {
+            path: FilePath::new("/srv/app"),
+            mode: None,
+            user: None,
+            group: None,
+        });
+        defaults().apply(&mut directory);
+        let ResourceParams::Directory(DirectoryParams::Present { mode, .. }) = directory else {
+            panic!("expected present directory");
+        };
+        assert_eq!(mode, Some(FileMode::new(0o755)));
+    }
+
+    #[test]
+    fn inherits_unset_fields_from_the_parent() {
+        let child = FileDefaults {
+            mode: Some(FileMode::new(0o600)),
+            group: Some(FileGroup::new("app")),
+            ..FileDefaults::default()
+        };
+        let inherited = child.inherit(&defaults());
+        assert_eq!(inherited.mode, Some(FileMode::new(0o600)));
+        assert_eq!(inherited.directory_mode, Some(FileMode::new(0o755)));
+        assert_eq!(inherited.user, Some(FileUser::new("app")));
+        assert_eq!(inherited.group, Some(FileGroup::new("app")));
+    }
+}
@@ -0,0 +1,347 @@
+//! Documentation of each resource's params, for plan authors: `lusid resource
+//! docs <id>` prints a [`ResourceDoc`], and [`json_schema`] turns them all
+//! into a JSON Schema for editors to complete and check plan items with.
+//!
+//! Params are parsed by hand (see [`ParseParams`](lusid_params::ParseParams)),
+//! so there's no schema to derive this from: each [`ResourceType`] writes out
+//! [`ParamsDoc`]s, one per shape its params can take. Keep them in step with
+//! its `parse_params`.
+
+use std::fmt::{self, Display};
+
+use serde_json::{Map, Value, json};
+
+use crate::{
+    ResourceType, apk::Apk, apt::Apt, apt_repo::AptRepo, aur::Aur, brew::Brew, command::Command,
+    cron::Cron, directory::Directory, dnf::Dnf, file::File, firewall::Firewall, git::Git,
+    git_config::GitConfig, gpg_key::GpgKey, group::Group, launchd::Launchd, networkd::Networkd,
+    nix::Nix, pacman::Pacman, pip::Pip, podman::Podman, podman_image::PodmanImage, rustup::Rustup,
+    secret::Secret, systemd::Systemd, systemd_unit::SystemdUnit, time::Time, tls_cert::TlsCert,
+    user::User, wireguard::Wireguard,
+};
+
+/// The type of value a param takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamDocType {
+    String,
+    Boolean,
+    Number,
+    StringList,
+    Object,
+    /// A list of objects, their keys given in the param's description.
+    ObjectList,
+    /// A path on the machine running the apply, relative to the plan.
+    HostPath,
+    /// An absolute path on the machine being applied to.
+    TargetPath,
+    /// Exactly this string, e.g. the `state` that selects a shape.
+    Literal(&'static str),
+    /// Any value at all, e.g. the `vars` a template is rendered with.
+    Any,
+    OneOf(&'static [ParamDocType]),
+}
+
+impl ParamDocType {
+    fn json_schema(&self) -> Map<String, Value> {
+        let schema = match self {
+            ParamDocType::String | ParamDocType::HostPath => json!({ "type": "string" }),
+            ParamDocType::Boolean => json!({ "type": "boolean" }),
+            ParamDocType::Number => json!({ "type": "number" }),
+            ParamDocType::StringList => json!({ "type": "array", "items": { "type": "string" } }),
+            ParamDocType::Object => json!({ "type": "object" }),
+            ParamDocType::ObjectList => json!({ "type": "array", "items": { "type": "object" } }),
+            ParamDocType::TargetPath => json!({ "type": "string", "pattern": "^/" }),
+            ParamDocType::Literal(value) => json!({ "const": value }),
+            ParamDocType::Any => json!({}),
+            ParamDocType::OneOf(types) => {
+                let types: Vec<Value> = types
+                    .iter()
+                    .map(|typ| Value::Object(typ.json_schema()))
+                    .collect();
+                json!({ "oneOf": types })
+            }
+        };
+        match schema {
+            Value::Object(schema) => schema,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Display for ParamDocType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamDocType::String => f.write_str("string"),
+            ParamDocType::Boolean => f.write_str("boolean"),
+            ParamDocType::Number => f.write_str("number"),
+            ParamDocType::StringList => f.write_str("list of strings"),
+            ParamDocType::Object => f.write_str("object"),
+            ParamDocType::ObjectList => f.write_str("list of objects"),
+            ParamDocType::HostPath => f.write_str("host-path"),
+            ParamDocType::TargetPath => f.write_str("target-path"),
+            ParamDocType::Literal(value) => write!(f, "\"{value}\""),
+            ParamDocType::Any => f.write_str("any"),
+            ParamDocType::OneOf(types) => {
+                for (index, typ) in types.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(" or ")?;
+                    }
+                    typ.fmt(f)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// One param of a [`ParamsDoc`].
+#[derive(Debug, Clone, Copy)]
+pub struct ParamDoc {
+    pub key: &'static str,
+    pub typ: ParamDocType,
+    pub required: bool,
+    pub description: &'static str,
+}
+
+impl ParamDoc {
+    pub const fn required(key: &'static str, typ: ParamDocType, description: &'static str) -> Self {
+        Self {
+            key,
+            typ,
+            required: true,
+            description,
+        }
+    }
+
+    pub const fn optional(key: &'static str, typ: ParamDocType, description: &'static str) -> Self {
+        Self {
+            key,
+            typ,
+            required: false,
+            description,
+        }
+    }
+}
+
+/// One shape a resource's params can take, e.g. `@core/file` with
+/// `state: "contents"`.
+#[derive(Debug, Clone, Copy)]
+pub struct ParamsDoc {
+    pub description: &'static str,
+    pub params: &'static [ParamDoc],
+}
+
+impl ParamsDoc {
+    fn json_schema(&self) -> Value {
+        let properties: Map<String, Value> = self
+            .params
+            .iter()
+            .map(|param| {
+                let mut schema = param.typ.json_schema();
+                schema.insert("description".into(), param.description.into());
+                (param.key.to_owned(), Value::Object(schema))
+            })
+            .collect();
+        let required: Vec<&str> = self
+            .params
+            .iter()
+            .filter(|param| param.required)
+            .map(|param| param.key)
+            .collect();
+        json!({
+            "type": "object",
+            "description": self.description,
+            "properties": properties,
+            "required": required,
+            "additionalProperties": false,
+        })
+    }
+}
+
+/// A resource as plan authors see it: the `@core/<id>` module and its params.
+#[derive(Debug, Clone, Copy)]
+pub struct ResourceDoc {
+    pub id: &'static str,
+    pub description: &'static str,
+    pub platforms: &'static [&'static str],
+    pub params: &'static [ParamsDoc],
+}
+
+impl ResourceDoc {
+    pub fn of<R: ResourceType>() -> Self {
+        Self {
+            id: R::ID,
+            description: R::DESCRIPTION,
+            platforms: R::PLATFORMS,
+            params: R::PARAMS_DOCS,
+        }
+    }
+
+    /// JSON Schema of the item's `params`.
+    pub fn params_json_schema(&self) -> Value {
+        let cases: Vec<Value> = self.params.iter().map(ParamsDoc::json_schema).collect();
+        match <[Value; 1]>::try_from(cases) {
+            Ok([case]) => case,
+            Err(cases) => json!({ "oneOf": cases }),
+        }
+    }
+}
+
+impl Display for ResourceDoc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "@core/{}", self.id)?;
+        writeln!(f)?;
+        writeln!(f, "{}", self.description)?;
+        writeln!(f, "Platforms: {}", self.platforms.join(", "))?;
+        for case in self.params {
+            writeln!(f)?;
+            writeln!(f, "{}", case.description)?;
+            for param in case.params {
+                let required = if param.required {
+                    "required"
+                } else {
+                    "optional"
+                };
+                writeln!(f, "  {} ({}, {required})", param.key, param.typ)?;
+                writeln!(f, "      {}", param.description)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Every core resource, in `@core/<id>` order.
+pub fn resource_docs() -> Vec<ResourceDoc> {
+    let mut docs = vec![
+        ResourceDoc::of::<Apt>(),
+        ResourceDoc::of::<AptRepo>(),
+        ResourceDoc::of::<Brew>(),
+        ResourceDoc::of::<Command>(),
+        ResourceDoc::of::<Cron>(),
+        ResourceDoc::of::<Directory>(),
+        ResourceDoc::of::<File>(),
+        ResourceDoc::of::<Firewall>(),
+        ResourceDoc::of::<Git>(),
+        ResourceDoc::of::<GitConfig>(),
+        ResourceDoc::of::<GpgKey>(),
+        ResourceDoc::of::<Group>(),
+        ResourceDoc::of::<Launchd>(),
+        ResourceDoc::of::<Networkd>(),
+        ResourceDoc::of::<Wireguard>(),
+        ResourceDoc::of::<TlsCert>(),
+        ResourceDoc::of::<Pacman>(),
+        ResourceDoc::of::<Aur>(),
+        ResourceDoc::of::<Dnf>(),
+        ResourceDoc::of::<Nix>(),
+        ResourceDoc::of::<Apk>(),
+        ResourceDoc::of::<Podman>(),
+        ResourceDoc::of::<PodmanImage>(),
+        ResourceDoc::of::<Pip>(),
+        ResourceDoc::of::<Rustup>(),
+        ResourceDoc::of::<Secret>(),
+        ResourceDoc::of::<Systemd>(),
+        ResourceDoc::of::<SystemdUnit>(),
+        ResourceDoc::of::<Time>(),
+        ResourceDoc::of::<User>(),
+    ];
+    docs.sort_by_key(|doc| doc.id);
+    docs
+}
+
+/// The doc for `id`, given as `apt` or `@core/apt`.
+pub fn resource_doc(id: &str) -> Option<ResourceDoc> {
+    let id = id.strip_prefix("@core/").unwrap_or(id);
+    resource_docs().into_iter().find(|doc| doc.id == id)
+}
+
+/// A JSON Schema for plan items using core modules: each `@core/<id>` item
+/// with its `params`, which are also under `$defs/<id>`.
+pub fn json_schema() -> Value {
+    let docs = resource_docs();
+    let defs: Map<String, Value> = docs
+        .iter()
+        .map(|doc| (doc.id.to_owned(), doc.params_json_schema()))
+        .collect();
+    let string_or_list = json!({
+        "oneOf": [
+            { "type": "string" },
+            { "type": "array", "items": { "type": "string" } },
+        ],
+    });
+    let retry = json!({
+        "type": "object",
+        "properties": {
+            "count": { "type": "integer", "minimum": 0 },
+            "delay": { "type": "integer", "minimum": 0 },
+            "on_exit_codes": { "type": "array", "items": { "type": "integer" } },
+        },
+        "required": ["count"],
+    });
+    let items: Vec<Value> = docs
+        .iter()
+        .map(|doc| {
+            json!({
+                "type": "object",
+                "description": doc.description,
+                "properties": {
+                    "module": { "const": format!("@core/{}", doc.id) },
+                    "id": { "type": "string" },
+                    "params": { "$ref": format!("#/$defs/{}", doc.id) },
+                    "requires": { "type": "array", "items": { "type": "string" } },
+                    "required_by": { "type": "array", "items": { "type": "string" } },
+                    "requires_package": string_or_list,
+                    "retry": retry,
+                },
+                "required": ["module", "params"],
+                "additionalProperties": false,
+            })
+        })
+        .collect();
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "lusid core plan item",
+        "oneOf": items,
+        "$defs": defs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn every_resource_documents_its_params() {
+        for doc in resource_docs() {
+            assert!(!doc.description.is_empty(), "{}", doc.id);
+            assert!(!doc.platforms.is_empty(), "{}", doc.id);
+            assert!(!doc.params.is_empty(), "{}", doc.id);
+            for case in doc.params {
+                let mut keys = HashSet::new();
+                for param in case.params {
+                    assert!(!param.description.is_empty(), "{}.{}", doc.id, param.key);
+                    assert!(keys.insert(param.key), "{}.{} twice", doc.id, param.key);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn finds_docs_with_or_without_the_core_prefix() {
+        assert_eq!(resource_doc("apt").map(|doc| doc.id), Some("apt"));
+        assert_eq!(resource_doc("@core/apt").map(|doc| doc.id), Some("apt"));
+        assert!(resource_doc("nope").is_none());
+    }
+
+    #[test]
+    fn json_schema_defines_each_resource() {
+        let schema = json_schema();
+        for doc in resource_docs() {
+            assert!(schema["$defs"][doc.id].is_object(), "{}", doc.id);
+        }
+        let systemd = &schema["$defs"]["systemd"];
+        assert_eq!(systemd["required"], json!(["name"]));
+        assert_eq!(systemd["properties"]["enabled"]["type"], "boolean");
+    }
+}
@@ -10,6 +10,8 @@
 //!    `packages: [a, b]` param expands to two atoms (one per package). Atoms are
 //!    arranged in a [`CausalityTree`] so resource-internal ordering can be declared.
 //! 3. **State** — current observed state for an atom (e.g. Installed/NotInstalled).
+//!    Atoms of one type can be observed together, e.g. every apt package in one
+//!    `dpkg-query` (see [`ResourceType::states_bulk`]).
 //! 4. **Change** — the delta from State to the desired Resource. `None` means
 //!    "already matches".
 //! 5. **Operations** — the concrete actions (apt install, write file, etc.) derived
@@ -30,34 +32,77 @@ use async_trait::async_trait;
 use lusid_causality::CausalityTree;
 use lusid_ctx::Context;
 use lusid_fs::FsError;
-use lusid_operation::{Operation, operations::file::FilePath};
-use lusid_params::ParseParams;
+use lusid_operation::{Operation, Severity, operations::file::FilePath};
+use lusid_params::{DeprecatedParam, ParseParams};
 use lusid_view::Render;
 use rimu::Span;
+use serde::Serialize;
 use thiserror::Error;
 
+pub mod conflict;
+pub mod defaults;
+pub mod docs;
+pub mod protect;
 mod resources;
 
+#[cfg(test)]
+mod render_snapshots;
+
+use crate::docs::ParamsDoc;
+use crate::resources::apk::{Apk, ApkChange, ApkParams, ApkResource, ApkState};
 use crate::resources::apt::{Apt, AptChange, AptParams, AptResource, AptState};
 use crate::resources::apt_repo::{
     AptRepo, AptRepoChange, AptRepoParams, AptRepoResource, AptRepoState,
 };
+use crate::resources::aur::{Aur, AurChange, AurParams, AurResource, AurState};
+use crate::resources::brew::{Brew, BrewChange, BrewParams, BrewResource, BrewState};
 use crate::resources::command::{
     Command, CommandChange, CommandParams, CommandResource, CommandState,
 };
+use crate::resources::cron::{Cron, CronChange, CronParams, CronResource, CronState};
 use crate::resources::directory::{
     Directory, DirectoryChange, DirectoryParams, DirectoryResource, DirectoryState,
 };
+use crate::resources::dnf::{Dnf, DnfChange, DnfParams, DnfResource, DnfState};
 use crate::resources::file::{File, FileChange, FileParams, FileResource, FileState};
+use crate::resources::firewall::{
+    Firewall, FirewallChange, FirewallParams, FirewallResource, FirewallState,
+};
 use crate::resources::git::{Git, GitChange, GitParams, GitResource, GitState};
+use crate::resources::git_config::{
+    GitConfig, GitConfigChange, GitConfigParams, GitConfigResource, GitConfigState,
+};
+use crate::resources::gpg_key::{GpgKey, GpgKeyChange, GpgKeyParams, GpgKeyResource, GpgKeyState};
 use crate::resources::group::{Group, GroupChange, GroupParams, GroupResource, GroupState};
+use crate::resources::launchd::{
+    Launchd, LaunchdChange, LaunchdParams, LaunchdResource, LaunchdState,
+};
+use crate::resources::networkd::{
+    Networkd, NetworkdChange, NetworkdParams, NetworkdResource, NetworkdState,
+};
+use crate::resources::nix::{Nix, NixChange, NixParams, NixResource, NixState};
 use crate::resources::pacman::{Pacman, PacmanChange, PacmanParams, PacmanResource, PacmanState};
+use crate::resources::pip::{Pip, PipChange, PipParams, PipResource, PipState};
 use crate::resources::podman::{Podman, PodmanChange, PodmanParams, PodmanResource, PodmanState};
+use crate::resources::podman_image::{
+    PodmanImage, PodmanImageChange, PodmanImageParams, PodmanImageResource, PodmanImageState,
+};
+use crate::resources::rustup::{Rustup, RustupChange, RustupParams, RustupResource, RustupState};
 use crate::resources::secret::{Secret, SecretParams};
 use crate::resources::systemd::{
     Systemd, SystemdChange, SystemdParams, SystemdResource, SystemdState,
 };
+use crate::resources::systemd_unit::{
+    SystemdUnit, SystemdUnitChange, SystemdUnitParams, SystemdUnitResource, SystemdUnitState,
+};
+use crate::resources::time::{Time, TimeChange, TimeParams, TimeResource, TimeState};
+use crate::resources::tls_cert::{
+    TlsCert, TlsCertChange, TlsCertParams, TlsCertResource, TlsCertState,
+};
 use crate::resources::user::{User, UserChange, UserParams, UserResource, UserState};
+use crate::resources::wireguard::{
+    Wireguard, WireguardChange, WireguardParams, WireguardResource, WireguardState,
+};
 
 /// The full pipeline for a single resource type.
 ///
@@ -70,12 +115,30 @@ pub trait ResourceType {
     /// Stable identifier used as the `@core/<ID>` module name in plans.
     const ID: &'static str;
 
+    /// One-line summary of what the resource manages, for plan authors (see
+    /// [`docs`]).
+    const DESCRIPTION: &'static str;
+
+    /// Each shape `Params` can take, documented for plan authors. Keep in step
+    /// with `Params`'s [`ParseParams`] impl.
+    const PARAMS_DOCS: &'static [ParamsDoc];
+
+    /// Where the resource works: `linux` for any Linux, `macos`, or the
+    /// distros (as named in a machine's `os`) whose package manager it drives.
+    /// A plan using the resource on any other OS fails to plan (see
+    /// `Os::platforms` in `lusid-system`).
+    const PLATFORMS: &'static [&'static str] = &["linux"];
+
     /// User-facing params struct, parsed directly from the plan's Rimu value
     /// via [`ParseParams`]. Each variant of the struct/enum corresponds to an
     /// allowed shape — the parser does shape validation and typed extraction
     /// in one pass.
     type Params: Render + ParseParams;
 
+    /// Params that still parse but are on their way out. Setting one in a plan
+    /// warns (see [`lusid_params::deprecation_warnings`]).
+    const DEPRECATED_PARAMS: &'static [DeprecatedParam] = &[];
+
     /// Indivisible unit of managed state. One `Params` may produce many atoms (e.g. one
     /// per package in a packages list).
     type Resource: Render;
@@ -96,6 +159,26 @@ pub trait ResourceType {
         resource: &Self::Resource,
     ) -> Result<Self::State, Self::StateError>;
 
+    /// Observe many atoms at once, returning one state per resource, in order.
+    ///
+    /// Defaults to [`state`](Self::state) for each in turn. Override where one
+    /// probe can answer for many, e.g. one `dpkg-query` for every apt package,
+    /// and list the type in [`Resource::states`].
+    async fn states_bulk(
+        ctx: &mut Context,
+        resources: &[&Self::Resource],
+    ) -> Result<Vec<Self::State>, Self::StateError>
+    where
+        Self::Resource: Sync,
+        Self::State: Send,
+    {
+        let mut states = Vec::with_capacity(resources.len());
+        for resource in resources {
+            states.push(Self::state(ctx, resource).await?);
+        }
+        Ok(states)
+    }
+
     /// The delta from `State` to the desired `Resource`.
     type Change: Render;
 
@@ -116,20 +199,42 @@ pub trait ResourceType {
 /// produces are ordinary `Resource::File` atoms. The provenance ("this
 /// file was written for a @core/secret plan item") is preserved only at
 /// this `ResourceParams` layer.
-#[derive(Debug, Clone)]
+///
+/// Serialized keyed by its module's name without `@core/`, e.g.
+/// `{"apt": {"package": {"package": "nginx"}}}`, for `lusid render`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum ResourceParams {
     Apt(AptParams),
     AptRepo(AptRepoParams),
     File(FileParams),
     Directory(DirectoryParams),
     Pacman(PacmanParams),
+    Aur(AurParams),
+    Dnf(DnfParams),
+    Nix(NixParams),
+    Apk(ApkParams),
     Podman(PodmanParams),
+    PodmanImage(PodmanImageParams),
+    Pip(PipParams),
+    Rustup(RustupParams),
+    Brew(BrewParams),
+    Launchd(LaunchdParams),
+    Firewall(FirewallParams),
+    Time(TimeParams),
+    Networkd(NetworkdParams),
+    Wireguard(WireguardParams),
+    TlsCert(TlsCertParams),
+    GitConfig(GitConfigParams),
+    GpgKey(GpgKeyParams),
     Command(CommandParams),
     Git(GitParams),
     Secret(SecretParams),
     Systemd(SystemdParams),
+    SystemdUnit(SystemdUnitParams),
     User(UserParams),
     Group(GroupParams),
+    Cron(CronParams),
 }
 
 impl Display for ResourceParams {
@@ -141,13 +246,31 @@ impl Display for ResourceParams {
             File(params) => params.fmt(f),
             Directory(params) => params.fmt(f),
             Pacman(params) => params.fmt(f),
+            Aur(params) => params.fmt(f),
+            Dnf(params) => params.fmt(f),
+            Nix(params) => params.fmt(f),
+            Apk(params) => params.fmt(f),
             Podman(params) => params.fmt(f),
+            PodmanImage(params) => params.fmt(f),
+            Pip(params) => params.fmt(f),
+            Rustup(params) => params.fmt(f),
+            Brew(params) => params.fmt(f),
+            Launchd(params) => params.fmt(f),
+            Firewall(params) => params.fmt(f),
+            Time(params) => params.fmt(f),
+            Networkd(params) => params.fmt(f),
+            Wireguard(params) => params.fmt(f),
+            TlsCert(params) => params.fmt(f),
+            GitConfig(params) => params.fmt(f),
+            GpgKey(params) => params.fmt(f),
             Command(params) => params.fmt(f),
             Git(params) => params.fmt(f),
             Secret(params) => params.fmt(f),
             Systemd(params) => params.fmt(f),
+            SystemdUnit(params) => params.fmt(f),
             User(params) => params.fmt(f),
             Group(params) => params.fmt(f),
+            Cron(params) => params.fmt(f),
         }
     }
 }
@@ -161,13 +284,31 @@ impl Render for ResourceParams {
             File(params) => params.render(),
             Directory(params) => params.render(),
             Pacman(params) => params.render(),
+            Aur(params) => params.render(),
+            Dnf(params) => params.render(),
+            Nix(params) => params.render(),
+            Apk(params) => params.render(),
             Podman(params) => params.render(),
+            PodmanImage(params) => params.render(),
+            Pip(params) => params.render(),
+            Rustup(params) => params.render(),
+            Brew(params) => params.render(),
+            Launchd(params) => params.render(),
+            Firewall(params) => params.render(),
+            Time(params) => params.render(),
+            Networkd(params) => params.render(),
+            Wireguard(params) => params.render(),
+            TlsCert(params) => params.render(),
+            GitConfig(params) => params.render(),
+            GpgKey(params) => params.render(),
             Command(params) => params.render(),
             Git(params) => params.render(),
             Secret(params) => params.render(),
             Systemd(params) => params.render(),
+            SystemdUnit(params) => params.render(),
             User(params) => params.render(),
             Group(params) => params.render(),
+            Cron(params) => params.render(),
         }
     }
 }
@@ -180,12 +321,30 @@ pub enum Resource {
     File(FileResource),
     Directory(DirectoryResource),
     Pacman(PacmanResource),
+    Aur(AurResource),
+    Dnf(DnfResource),
+    Nix(NixResource),
+    Apk(ApkResource),
     Podman(PodmanResource),
+    PodmanImage(PodmanImageResource),
+    Pip(PipResource),
+    Rustup(RustupResource),
+    Brew(BrewResource),
+    Launchd(LaunchdResource),
+    Firewall(FirewallResource),
+    Time(TimeResource),
+    Networkd(NetworkdResource),
+    Wireguard(WireguardResource),
+    TlsCert(TlsCertResource),
+    GitConfig(GitConfigResource),
+    GpgKey(GpgKeyResource),
     Command(CommandResource),
     Git(GitResource),
     Systemd(SystemdResource),
+    SystemdUnit(SystemdUnitResource),
     User(UserResource),
     Group(GroupResource),
+    Cron(CronResource),
 }
 
 impl Display for Resource {
@@ -197,12 +356,30 @@ impl Display for Resource {
             File(file) => file.fmt(f),
             Directory(directory) => directory.fmt(f),
             Pacman(pacman) => pacman.fmt(f),
+            Aur(aur) => aur.fmt(f),
+            Dnf(dnf) => dnf.fmt(f),
+            Nix(nix) => nix.fmt(f),
+            Apk(apk) => apk.fmt(f),
             Podman(podman) => podman.fmt(f),
+            PodmanImage(podman_image) => podman_image.fmt(f),
+            Pip(pip) => pip.fmt(f),
+            Rustup(rustup) => rustup.fmt(f),
+            Brew(brew) => brew.fmt(f),
+            Launchd(launchd) => launchd.fmt(f),
+            Firewall(firewall) => firewall.fmt(f),
+            Time(time) => time.fmt(f),
+            Networkd(networkd) => networkd.fmt(f),
+            Wireguard(wireguard) => wireguard.fmt(f),
+            TlsCert(tls_cert) => tls_cert.fmt(f),
+            GitConfig(git_config) => git_config.fmt(f),
+            GpgKey(gpg_key) => gpg_key.fmt(f),
             Command(command) => command.fmt(f),
             Git(git) => git.fmt(f),
             Systemd(systemd) => systemd.fmt(f),
+            SystemdUnit(systemd_unit) => systemd_unit.fmt(f),
             User(user) => user.fmt(f),
             Group(group) => group.fmt(f),
+            Cron(cron) => cron.fmt(f),
         }
     }
 }
@@ -216,12 +393,30 @@ impl Render for Resource {
             File(params) => params.render(),
             Directory(params) => params.render(),
             Pacman(params) => params.render(),
+            Aur(params) => params.render(),
+            Dnf(params) => params.render(),
+            Nix(params) => params.render(),
+            Apk(params) => params.render(),
             Podman(params) => params.render(),
+            PodmanImage(params) => params.render(),
+            Pip(params) => params.render(),
+            Rustup(params) => params.render(),
+            Brew(params) => params.render(),
+            Launchd(params) => params.render(),
+            Firewall(params) => params.render(),
+            Time(params) => params.render(),
+            Networkd(params) => params.render(),
+            Wireguard(params) => params.render(),
+            TlsCert(params) => params.render(),
+            GitConfig(params) => params.render(),
+            GpgKey(params) => params.render(),
             Command(params) => params.render(),
             Git(params) => params.render(),
             Systemd(params) => params.render(),
+            SystemdUnit(params) => params.render(),
             User(params) => params.render(),
             Group(params) => params.render(),
+            Cron(params) => params.render(),
         }
     }
 }
@@ -237,12 +432,30 @@ pub enum ResourceState {
     File(FileState),
     Directory(DirectoryState),
     Pacman(PacmanState),
+    Aur(AurState),
+    Dnf(DnfState),
+    Nix(NixState),
+    Apk(ApkState),
     Podman(PodmanState),
+    PodmanImage(PodmanImageState),
+    Pip(PipState),
+    Rustup(RustupState),
+    Brew(BrewState),
+    Launchd(LaunchdState),
+    Firewall(FirewallState),
+    Time(TimeState),
+    Networkd(NetworkdState),
+    Wireguard(WireguardState),
+    TlsCert(TlsCertState),
+    GitConfig(GitConfigState),
+    GpgKey(GpgKeyState),
     Command(CommandState),
     Git(GitState),
     Systemd(SystemdState),
+    SystemdUnit(SystemdUnitState),
     User(UserState),
     Group(GroupState),
+    Cron(CronState),
 }
 
 impl Display for ResourceState {
@@ -254,12 +467,30 @@ impl Display for ResourceState {
             File(file) => file.fmt(f),
             Directory(directory) => directory.fmt(f),
             Pacman(pacman) => pacman.fmt(f),
+            Aur(aur) => aur.fmt(f),
+            Dnf(dnf) => dnf.fmt(f),
+            Nix(nix) => nix.fmt(f),
+            Apk(apk) => apk.fmt(f),
             Podman(podman) => podman.fmt(f),
+            PodmanImage(podman_image) => podman_image.fmt(f),
+            Pip(pip) => pip.fmt(f),
+            Rustup(rustup) => rustup.fmt(f),
+            Brew(brew) => brew.fmt(f),
+            Launchd(launchd) => launchd.fmt(f),
+            Firewall(firewall) => firewall.fmt(f),
+            Time(time) => time.fmt(f),
+            Networkd(networkd) => networkd.fmt(f),
+            Wireguard(wireguard) => wireguard.fmt(f),
+            TlsCert(tls_cert) => tls_cert.fmt(f),
+            GitConfig(git_config) => git_config.fmt(f),
+            GpgKey(gpg_key) => gpg_key.fmt(f),
             Command(command) => command.fmt(f),
             Git(git) => git.fmt(f),
             Systemd(systemd) => systemd.fmt(f),
+            SystemdUnit(systemd_unit) => systemd_unit.fmt(f),
             User(user) => user.fmt(f),
             Group(group) => group.fmt(f),
+            Cron(cron) => cron.fmt(f),
         }
     }
 }
@@ -273,12 +504,30 @@ impl Render for ResourceState {
             File(params) => params.render(),
             Directory(params) => params.render(),
             Pacman(params) => params.render(),
+            Aur(params) => params.render(),
+            Dnf(params) => params.render(),
+            Nix(params) => params.render(),
+            Apk(params) => params.render(),
             Podman(params) => params.render(),
+            PodmanImage(params) => params.render(),
+            Pip(params) => params.render(),
+            Rustup(params) => params.render(),
+            Brew(params) => params.render(),
+            Launchd(params) => params.render(),
+            Firewall(params) => params.render(),
+            Time(params) => params.render(),
+            Networkd(params) => params.render(),
+            Wireguard(params) => params.render(),
+            TlsCert(params) => params.render(),
+            GitConfig(params) => params.render(),
+            GpgKey(params) => params.render(),
             Command(params) => params.render(),
             Git(params) => params.render(),
             Systemd(params) => params.render(),
+            SystemdUnit(params) => params.render(),
             User(params) => params.render(),
             Group(params) => params.render(),
+            Cron(params) => params.render(),
         }
     }
 }
@@ -302,9 +551,57 @@ pub enum ResourceStateError {
     #[error("pacman state error: {0}")]
     Pacman(#[from] <Pacman as ResourceType>::StateError),
 
+    #[error("aur state error: {0}")]
+    Aur(#[from] <Aur as ResourceType>::StateError),
+
+    #[error("dnf state error: {0}")]
+    Dnf(#[from] <Dnf as ResourceType>::StateError),
+
+    #[error("nix state error: {0}")]
+    Nix(#[from] <Nix as ResourceType>::StateError),
+
+    #[error("apk state error: {0}")]
+    Apk(#[from] <Apk as ResourceType>::StateError),
+
     #[error("podman state error: {0}")]
     Podman(#[from] <Podman as ResourceType>::StateError),
 
+    #[error("podman-image state error: {0}")]
+    PodmanImage(#[from] <PodmanImage as ResourceType>::StateError),
+
+    #[error("pip state error: {0}")]
+    Pip(#[from] <Pip as ResourceType>::StateError),
+
+    #[error("rustup state error: {0}")]
+    Rustup(#[from] <Rustup as ResourceType>::StateError),
+
+    #[error("brew state error: {0}")]
+    Brew(#[from] <Brew as ResourceType>::StateError),
+
+    #[error("launchd state error: {0}")]
+    Launchd(#[from] <Launchd as ResourceType>::StateError),
+
+    #[error("firewall state error: {0}")]
+    Firewall(#[from] <Firewall as ResourceType>::StateError),
+
+    #[error("time state error: {0}")]
+    Time(#[from] <Time as ResourceType>::StateError),
+
+    #[error("networkd state error: {0}")]
+    Networkd(#[from] <Networkd as ResourceType>::StateError),
+
+    #[error("wireguard state error: {0}")]
+    Wireguard(#[from] <Wireguard as ResourceType>::StateError),
+
+    #[error("tls-cert state error: {0}")]
+    TlsCert(#[from] <TlsCert as ResourceType>::StateError),
+
+    #[error("git-config state error: {0}")]
+    GitConfig(#[from] <GitConfig as ResourceType>::StateError),
+
+    #[error("gpg-key state error: {0}")]
+    GpgKey(#[from] <GpgKey as ResourceType>::StateError),
+
     #[error("command state error: {0}")]
     Command(#[from] <Command as ResourceType>::StateError),
 
@@ -314,11 +611,54 @@ pub enum ResourceStateError {
     #[error("systemd state error: {0}")]
     Systemd(#[from] <Systemd as ResourceType>::StateError),
 
+    #[error("systemd-unit state error: {0}")]
+    SystemdUnit(#[from] <SystemdUnit as ResourceType>::StateError),
+
     #[error("user state error: {0}")]
     User(#[from] <User as ResourceType>::StateError),
 
     #[error("group state error: {0}")]
     Group(#[from] <Group as ResourceType>::StateError),
+
+    #[error("cron state error: {0}")]
+    Cron(#[from] <Cron as ResourceType>::StateError),
+}
+
+impl ResourceStateError {
+    /// Stable, machine-readable code for this failure: `state.<resource>`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ResourceStateError::Apt(_) => "state.apt",
+            ResourceStateError::AptRepo(_) => "state.apt-repo",
+            ResourceStateError::File(_) => "state.file",
+            ResourceStateError::Directory(_) => "state.directory",
+            ResourceStateError::Pacman(_) => "state.pacman",
+            ResourceStateError::Aur(_) => "state.aur",
+            ResourceStateError::Dnf(_) => "state.dnf",
+            ResourceStateError::Nix(_) => "state.nix",
+            ResourceStateError::Apk(_) => "state.apk",
+            ResourceStateError::Podman(_) => "state.podman",
+            ResourceStateError::PodmanImage(_) => "state.podman-image",
+            ResourceStateError::Pip(_) => "state.pip",
+            ResourceStateError::Rustup(_) => "state.rustup",
+            ResourceStateError::Brew(_) => "state.brew",
+            ResourceStateError::Launchd(_) => "state.launchd",
+            ResourceStateError::Firewall(_) => "state.firewall",
+            ResourceStateError::Time(_) => "state.time",
+            ResourceStateError::Networkd(_) => "state.networkd",
+            ResourceStateError::Wireguard(_) => "state.wireguard",
+            ResourceStateError::TlsCert(_) => "state.tls-cert",
+            ResourceStateError::GitConfig(_) => "state.git-config",
+            ResourceStateError::GpgKey(_) => "state.gpg-key",
+            ResourceStateError::Command(_) => "state.command",
+            ResourceStateError::Git(_) => "state.git",
+            ResourceStateError::Systemd(_) => "state.systemd",
+            ResourceStateError::SystemdUnit(_) => "state.systemd-unit",
+            ResourceStateError::User(_) => "state.user",
+            ResourceStateError::Group(_) => "state.group",
+            ResourceStateError::Cron(_) => "state.cron",
+        }
+    }
 }
 
 /// Dispatcher over every resource's `Change`.
@@ -329,12 +669,30 @@ pub enum ResourceChange {
     File(FileChange),
     Directory(DirectoryChange),
     Pacman(PacmanChange),
+    Aur(AurChange),
+    Dnf(DnfChange),
+    Nix(NixChange),
+    Apk(ApkChange),
     Podman(PodmanChange),
+    PodmanImage(PodmanImageChange),
+    Pip(PipChange),
+    Rustup(RustupChange),
+    Brew(BrewChange),
+    Launchd(LaunchdChange),
+    Firewall(FirewallChange),
+    Time(TimeChange),
+    Networkd(NetworkdChange),
+    Wireguard(WireguardChange),
+    TlsCert(TlsCertChange),
+    GitConfig(GitConfigChange),
+    GpgKey(GpgKeyChange),
     Command(CommandChange),
     Git(GitChange),
     Systemd(SystemdChange),
+    SystemdUnit(SystemdUnitChange),
     User(UserChange),
     Group(GroupChange),
+    Cron(CronChange),
 }
 
 impl Display for ResourceChange {
@@ -346,12 +704,30 @@ impl Display for ResourceChange {
             File(file) => file.fmt(f),
             Directory(directory) => directory.fmt(f),
             Pacman(pacman) => pacman.fmt(f),
+            Aur(aur) => aur.fmt(f),
+            Dnf(dnf) => dnf.fmt(f),
+            Nix(nix) => nix.fmt(f),
+            Apk(apk) => apk.fmt(f),
             Podman(podman) => podman.fmt(f),
+            PodmanImage(podman_image) => podman_image.fmt(f),
+            Pip(pip) => pip.fmt(f),
+            Rustup(rustup) => rustup.fmt(f),
+            Brew(brew) => brew.fmt(f),
+            Launchd(launchd) => launchd.fmt(f),
+            Firewall(firewall) => firewall.fmt(f),
+            Time(time) => time.fmt(f),
+            Networkd(networkd) => networkd.fmt(f),
+            Wireguard(wireguard) => wireguard.fmt(f),
+            TlsCert(tls_cert) => tls_cert.fmt(f),
+            GitConfig(git_config) => git_config.fmt(f),
+            GpgKey(gpg_key) => gpg_key.fmt(f),
             Command(command) => command.fmt(f),
             Git(git) => git.fmt(f),
             Systemd(systemd) => systemd.fmt(f),
+            SystemdUnit(systemd_unit) => systemd_unit.fmt(f),
             User(user) => user.fmt(f),
             Group(group) => group.fmt(f),
+            Cron(cron) => cron.fmt(f),
         }
     }
 }
@@ -365,12 +741,30 @@ impl Render for ResourceChange {
             File(params) => params.render(),
             Directory(params) => params.render(),
             Pacman(params) => params.render(),
+            Aur(params) => params.render(),
+            Dnf(params) => params.render(),
+            Nix(params) => params.render(),
+            Apk(params) => params.render(),
             Podman(params) => params.render(),
+            PodmanImage(params) => params.render(),
+            Pip(params) => params.render(),
+            Rustup(params) => params.render(),
+            Brew(params) => params.render(),
+            Launchd(params) => params.render(),
+            Firewall(params) => params.render(),
+            Time(params) => params.render(),
+            Networkd(params) => params.render(),
+            Wireguard(params) => params.render(),
+            TlsCert(params) => params.render(),
+            GitConfig(params) => params.render(),
+            GpgKey(params) => params.render(),
             Command(params) => params.render(),
             Git(params) => params.render(),
             Systemd(params) => params.render(),
+            SystemdUnit(params) => params.render(),
             User(params) => params.render(),
             Group(params) => params.render(),
+            Cron(params) => params.render(),
         }
     }
 }
@@ -395,13 +789,57 @@ impl ResourceParams {
             ResourceParams::File(params) => typed::<File>(params, Resource::File),
             ResourceParams::Directory(params) => typed::<Directory>(params, Resource::Directory),
             ResourceParams::Pacman(params) => typed::<Pacman>(params, Resource::Pacman),
+            ResourceParams::Aur(params) => typed::<Aur>(params, Resource::Aur),
+            ResourceParams::Dnf(params) => typed::<Dnf>(params, Resource::Dnf),
+            ResourceParams::Nix(params) => typed::<Nix>(params, Resource::Nix),
+            ResourceParams::Apk(params) => typed::<Apk>(params, Resource::Apk),
             ResourceParams::Podman(params) => typed::<Podman>(params, Resource::Podman),
+            ResourceParams::PodmanImage(params) => {
+                typed::<PodmanImage>(params, Resource::PodmanImage)
+            }
+            ResourceParams::Pip(params) => typed::<Pip>(params, Resource::Pip),
+            ResourceParams::Rustup(params) => typed::<Rustup>(params, Resource::Rustup),
+            ResourceParams::Brew(params) => typed::<Brew>(params, Resource::Brew),
+            ResourceParams::Launchd(params) => typed::<Launchd>(params, Resource::Launchd),
+            ResourceParams::Firewall(params) => typed::<Firewall>(params, Resource::Firewall),
+            ResourceParams::Time(params) => typed::<Time>(params, Resource::Time),
+            ResourceParams::Networkd(params) => typed::<Networkd>(params, Resource::Networkd),
+            ResourceParams::Wireguard(params) => typed::<Wireguard>(params, Resource::Wireguard),
+            ResourceParams::TlsCert(params) => typed::<TlsCert>(params, Resource::TlsCert),
+            ResourceParams::GitConfig(params) => typed::<GitConfig>(params, Resource::GitConfig),
+            ResourceParams::GpgKey(params) => typed::<GpgKey>(params, Resource::GpgKey),
             ResourceParams::Command(params) => typed::<Command>(params, Resource::Command),
             ResourceParams::Git(params) => typed::<Git>(params, Resource::Git),
             ResourceParams::Secret(params) => typed::<Secret>(params, Resource::File),
             ResourceParams::Systemd(params) => typed::<Systemd>(params, Resource::Systemd),
+            ResourceParams::SystemdUnit(params) => {
+                typed::<SystemdUnit>(params, Resource::SystemdUnit)
+            }
             ResourceParams::User(params) => typed::<User>(params, Resource::User),
             ResourceParams::Group(params) => typed::<Group>(params, Resource::Group),
+            ResourceParams::Cron(params) => typed::<Cron>(params, Resource::Cron),
+        }
+    }
+
+    /// System packages these params install, for resolving `requires_package`.
+    pub fn installed_packages(&self) -> Vec<&str> {
+        match self {
+            ResourceParams::Apt(AptParams::Package { package })
+            | ResourceParams::Pacman(PacmanParams::Package { package })
+            | ResourceParams::Apk(ApkParams::Package { package }) => vec![package],
+            ResourceParams::Apt(AptParams::Packages { packages })
+            | ResourceParams::Pacman(PacmanParams::Packages { packages })
+            | ResourceParams::Apk(ApkParams::Packages { packages }) => {
+                packages.iter().map(String::as_str).collect()
+            }
+            ResourceParams::Dnf(DnfParams {
+                packages,
+                present: true,
+            }) => packages.iter().map(String::as_str).collect(),
+            ResourceParams::Aur(AurParams { packages, .. }) => {
+                packages.iter().map(String::as_str).collect()
+            }
+            _ => Vec::new(),
         }
     }
 }
@@ -453,6 +891,18 @@ impl Resource {
                 )
                 .await
             }
+            Resource::Aur(resource) => {
+                typed::<Aur>(ctx, resource, ResourceState::Aur, ResourceStateError::Aur).await
+            }
+            Resource::Dnf(resource) => {
+                typed::<Dnf>(ctx, resource, ResourceState::Dnf, ResourceStateError::Dnf).await
+            }
+            Resource::Nix(resource) => {
+                typed::<Nix>(ctx, resource, ResourceState::Nix, ResourceStateError::Nix).await
+            }
+            Resource::Apk(resource) => {
+                typed::<Apk>(ctx, resource, ResourceState::Apk, ResourceStateError::Apk).await
+            }
             Resource::Podman(resource) => {
                 typed::<Podman>(
                     ctx,
@@ -462,6 +912,96 @@ impl Resource {
                 )
                 .await
             }
+            Resource::PodmanImage(resource) => {
+                typed::<PodmanImage>(
+                    ctx,
+                    resource,
+                    ResourceState::PodmanImage,
+                    ResourceStateError::PodmanImage,
+                )
+                .await
+            }
+            Resource::Pip(resource) => {
+                typed::<Pip>(ctx, resource, ResourceState::Pip, ResourceStateError::Pip).await
+            }
+            Resource::Rustup(resource) => {
+                typed::<Rustup>(
+                    ctx,
+                    resource,
+                    ResourceState::Rustup,
+                    ResourceStateError::Rustup,
+                )
+                .await
+            }
+            Resource::Brew(resource) => {
+                typed::<Brew>(ctx, resource, ResourceState::Brew, ResourceStateError::Brew).await
+            }
+            Resource::Launchd(resource) => {
+                typed::<Launchd>(
+                    ctx,
+                    resource,
+                    ResourceState::Launchd,
+                    ResourceStateError::Launchd,
+                )
+                .await
+            }
+            Resource::Firewall(resource) => {
+                typed::<Firewall>(
+                    ctx,
+                    resource,
+                    ResourceState::Firewall,
+                    ResourceStateError::Firewall,
+                )
+                .await
+            }
+            Resource::Time(resource) => {
+                typed::<Time>(ctx, resource, ResourceState::Time, ResourceStateError::Time).await
+            }
+            Resource::Networkd(resource) => {
+                typed::<Networkd>(
+                    ctx,
+                    resource,
+                    ResourceState::Networkd,
+                    ResourceStateError::Networkd,
+                )
+                .await
+            }
+            Resource::Wireguard(resource) => {
+                typed::<Wireguard>(
+                    ctx,
+                    resource,
+                    ResourceState::Wireguard,
+                    ResourceStateError::Wireguard,
+                )
+                .await
+            }
+            Resource::TlsCert(resource) => {
+                typed::<TlsCert>(
+                    ctx,
+                    resource,
+                    ResourceState::TlsCert,
+                    ResourceStateError::TlsCert,
+                )
+                .await
+            }
+            Resource::GitConfig(resource) => {
+                typed::<GitConfig>(
+                    ctx,
+                    resource,
+                    ResourceState::GitConfig,
+                    ResourceStateError::GitConfig,
+                )
+                .await
+            }
+            Resource::GpgKey(resource) => {
+                typed::<GpgKey>(
+                    ctx,
+                    resource,
+                    ResourceState::GpgKey,
+                    ResourceStateError::GpgKey,
+                )
+                .await
+            }
             Resource::Command(resource) => {
                 typed::<Command>(
                     ctx,
@@ -483,6 +1023,15 @@ impl Resource {
                 )
                 .await
             }
+            Resource::SystemdUnit(resource) => {
+                typed::<SystemdUnit>(
+                    ctx,
+                    resource,
+                    ResourceState::SystemdUnit,
+                    ResourceStateError::SystemdUnit,
+                )
+                .await
+            }
             Resource::User(resource) => {
                 typed::<User>(ctx, resource, ResourceState::User, ResourceStateError::User).await
             }
@@ -495,7 +1044,182 @@ impl Resource {
                 )
                 .await
             }
+            Resource::Cron(resource) => {
+                typed::<Cron>(ctx, resource, ResourceState::Cron, ResourceStateError::Cron).await
+            }
+        }
+    }
+
+    /// Observe many atoms, returning one [`ResourceState`] per resource, in
+    /// order.
+    ///
+    /// Atoms of a type with its own [`ResourceType::states_bulk`] are probed
+    /// together, so a plan of fifty apt packages costs one `dpkg-query`, not
+    /// fifty. The rest are probed one at a time, as by [`Self::state`].
+    pub async fn states(
+        ctx: &mut Context,
+        resources: &[&Resource],
+    ) -> Result<Vec<ResourceState>, ResourceStateError> {
+        async fn typed<R: ResourceType + Send>(
+            ctx: &mut Context,
+            resources: &[&Resource],
+            states: &mut [Option<ResourceState>],
+            select: impl Fn(&Resource) -> Option<&R::Resource>,
+            map: impl Fn(R::State) -> ResourceState,
+            map_err: impl Fn(R::StateError) -> ResourceStateError,
+        ) -> Result<(), ResourceStateError>
+        where
+            R::Resource: Sync,
+            R::State: Send,
+        {
+            let (indices, typed): (Vec<usize>, Vec<&R::Resource>) = resources
+                .iter()
+                .enumerate()
+                .filter_map(|(index, resource)| Some((index, select(resource)?)))
+                .unzip();
+            if typed.is_empty() {
+                return Ok(());
+            }
+            let typed_states = R::states_bulk(ctx, &typed).await.map_err(map_err)?;
+            for (index, state) in indices.into_iter().zip(typed_states) {
+                states[index] = Some(map(state));
+            }
+            Ok(())
+        }
+
+        let mut states: Vec<Option<ResourceState>> = resources.iter().map(|_| None).collect();
+
+        // Only the types that override `states_bulk`; the default would just
+        // call `state` for each.
+        typed::<Apt>(
+            ctx,
+            resources,
+            &mut states,
+            |resource| match resource {
+                Resource::Apt(resource) => Some(resource),
+                _ => None,
+            },
+            ResourceState::Apt,
+            ResourceStateError::Apt,
+        )
+        .await?;
+        typed::<Pacman>(
+            ctx,
+            resources,
+            &mut states,
+            |resource| match resource {
+                Resource::Pacman(resource) => Some(resource),
+                _ => None,
+            },
+            ResourceState::Pacman,
+            ResourceStateError::Pacman,
+        )
+        .await?;
+        typed::<Aur>(
+            ctx,
+            resources,
+            &mut states,
+            |resource| match resource {
+                Resource::Aur(resource) => Some(resource),
+                _ => None,
+            },
+            ResourceState::Aur,
+            ResourceStateError::Aur,
+        )
+        .await?;
+        typed::<Dnf>(
+            ctx,
+            resources,
+            &mut states,
+            |resource| match resource {
+                Resource::Dnf(resource) => Some(resource),
+                _ => None,
+            },
+            ResourceState::Dnf,
+            ResourceStateError::Dnf,
+        )
+        .await?;
+        typed::<Nix>(
+            ctx,
+            resources,
+            &mut states,
+            |resource| match resource {
+                Resource::Nix(resource) => Some(resource),
+                _ => None,
+            },
+            ResourceState::Nix,
+            ResourceStateError::Nix,
+        )
+        .await?;
+        typed::<Apk>(
+            ctx,
+            resources,
+            &mut states,
+            |resource| match resource {
+                Resource::Apk(resource) => Some(resource),
+                _ => None,
+            },
+            ResourceState::Apk,
+            ResourceStateError::Apk,
+        )
+        .await?;
+        typed::<Pip>(
+            ctx,
+            resources,
+            &mut states,
+            |resource| match resource {
+                Resource::Pip(resource) => Some(resource),
+                _ => None,
+            },
+            ResourceState::Pip,
+            ResourceStateError::Pip,
+        )
+        .await?;
+        typed::<Rustup>(
+            ctx,
+            resources,
+            &mut states,
+            |resource| match resource {
+                Resource::Rustup(resource) => Some(resource),
+                _ => None,
+            },
+            ResourceState::Rustup,
+            ResourceStateError::Rustup,
+        )
+        .await?;
+        typed::<Brew>(
+            ctx,
+            resources,
+            &mut states,
+            |resource| match resource {
+                Resource::Brew(resource) => Some(resource),
+                _ => None,
+            },
+            ResourceState::Brew,
+            ResourceStateError::Brew,
+        )
+        .await?;
+        typed::<Firewall>(
+            ctx,
+            resources,
+            &mut states,
+            |resource| match resource {
+                Resource::Firewall(resource) => Some(resource),
+                _ => None,
+            },
+            ResourceState::Firewall,
+            ResourceStateError::Firewall,
+        )
+        .await?;
+
+        let mut out = Vec::with_capacity(resources.len());
+        for (resource, state) in resources.iter().zip(states) {
+            out.push(match state {
+                Some(state) => state,
+                None => resource.state(ctx).await?,
+            });
         }
+        Ok(out)
     }
 
     /// Diff this atom against its observed state. `None` means "already correct".
@@ -527,9 +1251,57 @@ impl Resource {
             (Resource::Pacman(resource), ResourceState::Pacman(state)) => {
                 typed::<Pacman>(resource, state, ResourceChange::Pacman)
             }
+            (Resource::Aur(resource), ResourceState::Aur(state)) => {
+                typed::<Aur>(resource, state, ResourceChange::Aur)
+            }
+            (Resource::Dnf(resource), ResourceState::Dnf(state)) => {
+                typed::<Dnf>(resource, state, ResourceChange::Dnf)
+            }
+            (Resource::Nix(resource), ResourceState::Nix(state)) => {
+                typed::<Nix>(resource, state, ResourceChange::Nix)
+            }
+            (Resource::Apk(resource), ResourceState::Apk(state)) => {
+                typed::<Apk>(resource, state, ResourceChange::Apk)
+            }
             (Resource::Podman(resource), ResourceState::Podman(state)) => {
                 typed::<Podman>(resource, state, ResourceChange::Podman)
             }
+            (Resource::PodmanImage(resource), ResourceState::PodmanImage(state)) => {
+                typed::<PodmanImage>(resource, state, ResourceChange::PodmanImage)
+            }
+            (Resource::Pip(resource), ResourceState::Pip(state)) => {
+                typed::<Pip>(resource, state, ResourceChange::Pip)
+            }
+            (Resource::Rustup(resource), ResourceState::Rustup(state)) => {
+                typed::<Rustup>(resource, state, ResourceChange::Rustup)
+            }
+            (Resource::Brew(resource), ResourceState::Brew(state)) => {
+                typed::<Brew>(resource, state, ResourceChange::Brew)
+            }
+            (Resource::Launchd(resource), ResourceState::Launchd(state)) => {
+                typed::<Launchd>(resource, state, ResourceChange::Launchd)
+            }
+            (Resource::Firewall(resource), ResourceState::Firewall(state)) => {
+                typed::<Firewall>(resource, state, ResourceChange::Firewall)
+            }
+            (Resource::Time(resource), ResourceState::Time(state)) => {
+                typed::<Time>(resource, state, ResourceChange::Time)
+            }
+            (Resource::Networkd(resource), ResourceState::Networkd(state)) => {
+                typed::<Networkd>(resource, state, ResourceChange::Networkd)
+            }
+            (Resource::Wireguard(resource), ResourceState::Wireguard(state)) => {
+                typed::<Wireguard>(resource, state, ResourceChange::Wireguard)
+            }
+            (Resource::TlsCert(resource), ResourceState::TlsCert(state)) => {
+                typed::<TlsCert>(resource, state, ResourceChange::TlsCert)
+            }
+            (Resource::GitConfig(resource), ResourceState::GitConfig(state)) => {
+                typed::<GitConfig>(resource, state, ResourceChange::GitConfig)
+            }
+            (Resource::GpgKey(resource), ResourceState::GpgKey(state)) => {
+                typed::<GpgKey>(resource, state, ResourceChange::GpgKey)
+            }
             (Resource::Command(resource), ResourceState::Command(state)) => {
                 typed::<Command>(resource, state, ResourceChange::Command)
             }
@@ -539,12 +1311,18 @@ impl Resource {
             (Resource::Systemd(resource), ResourceState::Systemd(state)) => {
                 typed::<Systemd>(resource, state, ResourceChange::Systemd)
             }
+            (Resource::SystemdUnit(resource), ResourceState::SystemdUnit(state)) => {
+                typed::<SystemdUnit>(resource, state, ResourceChange::SystemdUnit)
+            }
             (Resource::User(resource), ResourceState::User(state)) => {
                 typed::<User>(resource, state, ResourceChange::User)
             }
             (Resource::Group(resource), ResourceState::Group(state)) => {
                 typed::<Group>(resource, state, ResourceChange::Group)
             }
+            (Resource::Cron(resource), ResourceState::Cron(state)) => {
+                typed::<Cron>(resource, state, ResourceChange::Cron)
+            }
             _ => {
                 // Programmer error, should never happen, or if it does should be immediately obvious.
                 panic!("Unmatched resource and state")
@@ -582,6 +1360,29 @@ pub enum HostPathValidationError {
     Fs(#[from] FsError),
 }
 
+impl HostPathValidationError {
+    /// Stable, machine-readable code for this failure.
+    pub fn code(&self) -> &'static str {
+        match self {
+            HostPathValidationError::FileSourceMissing { .. }
+            | HostPathValidationError::DirectorySourceMissing { .. } => "host-path.missing",
+            HostPathValidationError::FileSourceNotFile { .. }
+            | HostPathValidationError::DirectorySourceNotDirectory { .. } => "host-path.wrong-type",
+            HostPathValidationError::Fs(_) => "host-path.fs",
+        }
+    }
+
+    pub fn span(&self) -> Option<&Span> {
+        match self {
+            HostPathValidationError::FileSourceMissing { span, .. }
+            | HostPathValidationError::FileSourceNotFile { span, .. }
+            | HostPathValidationError::DirectorySourceMissing { span, .. }
+            | HostPathValidationError::DirectorySourceNotDirectory { span, .. } => Some(span),
+            HostPathValidationError::Fs(_) => None,
+        }
+    }
+}
+
 impl ResourceParams {
     /// Validate that any `host-path` source referenced by this params variant
     /// exists on the operator's filesystem with the expected type.
@@ -711,13 +1512,49 @@ impl ResourceChange {
             ResourceChange::File(change) => File::operations(change),
             ResourceChange::Directory(change) => Directory::operations(change),
             ResourceChange::Pacman(change) => Pacman::operations(change),
+            ResourceChange::Aur(change) => Aur::operations(change),
+            ResourceChange::Dnf(change) => Dnf::operations(change),
+            ResourceChange::Nix(change) => Nix::operations(change),
+            ResourceChange::Apk(change) => Apk::operations(change),
             ResourceChange::Podman(change) => Podman::operations(change),
+            ResourceChange::PodmanImage(change) => PodmanImage::operations(change),
+            ResourceChange::Pip(change) => Pip::operations(change),
+            ResourceChange::Rustup(change) => Rustup::operations(change),
+            ResourceChange::Brew(change) => Brew::operations(change),
+            ResourceChange::Launchd(change) => Launchd::operations(change),
+            ResourceChange::Firewall(change) => Firewall::operations(change),
+            ResourceChange::Time(change) => Time::operations(change),
+            ResourceChange::Networkd(change) => Networkd::operations(change),
+            ResourceChange::Wireguard(change) => Wireguard::operations(change),
+            ResourceChange::TlsCert(change) => TlsCert::operations(change),
+            ResourceChange::GitConfig(change) => GitConfig::operations(change),
+            ResourceChange::GpgKey(change) => GpgKey::operations(change),
             ResourceChange::Command(change) => Command::operations(change),
             ResourceChange::Git(change) => Git::operations(change),
             ResourceChange::Systemd(change) => Systemd::operations(change),
+            ResourceChange::SystemdUnit(change) => SystemdUnit::operations(change),
             ResourceChange::User(change) => User::operations(change),
             ResourceChange::Group(change) => Group::operations(change),
+            ResourceChange::Cron(change) => Cron::operations(change),
+        }
+    }
+
+    /// The worst [`Severity`] of the operations the change lowers into.
+    pub fn severity(&self) -> Severity {
+        fn worst(tree: &CausalityTree<Operation>) -> Severity {
+            match tree {
+                CausalityTree::Branch { children, .. } => {
+                    children.iter().map(worst).max().unwrap_or_default()
+                }
+                CausalityTree::Leaf { node, .. } => node.severity(),
+            }
         }
+        self.clone()
+            .operations()
+            .iter()
+            .map(worst)
+            .max()
+            .unwrap_or_default()
     }
 }
 
@@ -744,6 +1581,8 @@ mod tests {
             mode: None,
             user: None,
             group: None,
+            restarts: None,
+            parents: None,
         })
     }
 
@@ -763,6 +1602,8 @@ mod tests {
             source,
             source_span: empty_span(),
             path: FilePath::new("/tmp/lusid-validate-test-target"),
+            restarts: None,
+            parents: None,
         })
     }
 
@@ -956,6 +1797,7 @@ mod tests {
         // Non-sourced resources don't reach the filesystem at all.
         let absent = ResourceParams::File(FileParams::Absent {
             path: FilePath::new("/tmp/never-touched"),
+            restarts: None,
         });
         absent.validate_host_paths().await.expect("no-op");
     }
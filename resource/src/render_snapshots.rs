@@ -0,0 +1,1555 @@
+//! Golden snapshots of every params / resource / state / change variant, as
+//! rendered through the dispatcher enums. See `lusid_view::Snapshot` for how
+//! to update them.
+
+use lusid_operation::operations::file::{FileGroup, FileMode, FilePath, FileSource, FileUser};
+use lusid_operation::operations::firewall::{FirewallAction, FirewallProtocol, FirewallRule};
+use lusid_operation::operations::git::{GitConfigScope, GitOwner};
+use lusid_operation::operations::gpg::{GpgKeySource, GpgKeyring};
+use lusid_operation::operations::pip::PipTarget;
+use lusid_view::Snapshot;
+use rimu::{SourceId, Span};
+
+use crate::resources::{
+    apk::*, apt::*, apt_repo::*, aur::*, brew::*, command::*, cron::*, directory::*, dnf::*,
+    file::*, firewall::*, git::*, git_config::*, gpg_key::*, group::*, launchd::*, networkd::*,
+    nix::*, pacman::*, pip::*, podman::*, podman_image::*, rustup::*, secret::*, systemd::*,
+    systemd_unit::*, time::*, tls_cert::*, user::*, wireguard::*,
+};
+use crate::{Resource, ResourceChange, ResourceParams, ResourceState};
+
+fn snapshot_path(name: &str) -> String {
+    format!("{}/snapshots/{name}.txt", env!("CARGO_MANIFEST_DIR"))
+}
+
+fn empty_span() -> Span {
+    Span::new(SourceId::empty(), 0, 0)
+}
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+#[test]
+fn apt() {
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::Apt(AptParams::Package {
+            package: "curl".into(),
+        }))
+        .render(&ResourceParams::Apt(AptParams::Packages {
+            packages: strings(&["curl", "git"]),
+        }))
+        .section("resource")
+        .render(&Resource::Apt(AptResource {
+            package: "curl".into(),
+        }))
+        .section("state")
+        .render(&ResourceState::Apt(AptState::NotInstalled))
+        .render(&ResourceState::Apt(AptState::Installed))
+        .section("change")
+        .render(&ResourceChange::Apt(AptChange::Install {
+            package: "curl".into(),
+        }))
+        .assert_matches(snapshot_path("apt"));
+}
+
+#[test]
+fn apt_repo() {
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::AptRepo(AptRepoParams {
+            name: "docker".into(),
+            uris: strings(&["https://download.docker.com/linux/debian"]),
+            suites: strings(&["bookworm"]),
+            components: strings(&["stable"]),
+            key_url: "https://download.docker.com/linux/debian/gpg".into(),
+            key_sha256: None,
+            types: None,
+            architectures: Some(strings(&["amd64"])),
+            enabled: None,
+        }))
+        .section("resource")
+        .render(&Resource::AptRepo(AptRepoResource {
+            name: "docker".into(),
+            sources_path: FilePath::new("/etc/apt/sources.list.d/docker.sources"),
+            sources_content: "Types: deb\n".into(),
+            key_url: "https://download.docker.com/linux/debian/gpg".into(),
+            key_sha256: None,
+            key_path: FilePath::new("/etc/apt/keyrings/docker.asc"),
+        }))
+        .section("state")
+        .render(&ResourceState::AptRepo(AptRepoState::Absent))
+        .render(&ResourceState::AptRepo(AptRepoState::Present {
+            sources_matches: false,
+            key_present: true,
+        }))
+        .section("change")
+        .render(&ResourceChange::AptRepo(AptRepoChange::Install {
+            name: "docker".into(),
+            ensure_dir: true,
+            key: Some((
+                "https://download.docker.com/linux/debian/gpg".into(),
+                None,
+                FilePath::new("/etc/apt/keyrings/docker.asc"),
+            )),
+            sources: None,
+        }))
+        .assert_matches(snapshot_path("apt_repo"));
+}
+
+#[test]
+fn command() {
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::Command(CommandParams::Install {
+            is_installed: Some("command -v rg".into()),
+            install: "cargo install ripgrep".into(),
+            uninstall: None,
+            guards: CommandGuards::default(),
+        }))
+        .render(&ResourceParams::Command(CommandParams::Install {
+            is_installed: None,
+            install: "cargo install ripgrep".into(),
+            uninstall: None,
+            guards: CommandGuards {
+                creates: Some(FilePath::new("/home/me/.cargo/bin/rg")),
+                unless: None,
+                only_if: Some("command -v cargo".into()),
+            },
+        }))
+        .render(&ResourceParams::Command(CommandParams::Uninstall {
+            is_installed: None,
+            install: None,
+            uninstall: "cargo uninstall ripgrep".into(),
+            guards: CommandGuards::default(),
+        }))
+        .section("resource")
+        .render(&Resource::Command(CommandResource {
+            status: CommandStatus::Install,
+            is_installed: Some("command -v rg".into()),
+            install: Some("cargo install ripgrep".into()),
+            uninstall: None,
+            guards: CommandGuards::default(),
+        }))
+        .render(&Resource::Command(CommandResource {
+            status: CommandStatus::Install,
+            is_installed: None,
+            install: Some("cargo install ripgrep".into()),
+            uninstall: None,
+            guards: CommandGuards {
+                creates: None,
+                unless: Some("rg --version | grep -q 14.1".into()),
+                only_if: None,
+            },
+        }))
+        .render(&Resource::Command(CommandResource {
+            status: CommandStatus::Uninstall,
+            is_installed: None,
+            install: None,
+            uninstall: Some("cargo uninstall ripgrep".into()),
+            guards: CommandGuards::default(),
+        }))
+        .section("state")
+        .render(&ResourceState::Command(CommandState::Installed))
+        .render(&ResourceState::Command(CommandState::NotInstalled))
+        .render(&ResourceState::Command(CommandState::Unknown))
+        .render(&ResourceState::Command(CommandState::Skipped {
+            guard: "only_if = \"command -v cargo\" failed".into(),
+        }))
+        .section("change")
+        .render(&ResourceChange::Command(CommandChange::Install {
+            command: "cargo install ripgrep".into(),
+        }))
+        .render(&ResourceChange::Command(CommandChange::Uninstall {
+            command: "cargo uninstall ripgrep".into(),
+        }))
+        .assert_matches(snapshot_path("command"));
+}
+
+#[test]
+fn cron() {
+    let job = || "0 3 * * * /usr/local/bin/backup".to_string();
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::Cron(CronParams::Present {
+            name: "backup".into(),
+            schedule: "0 3 * * *".into(),
+            command: "/usr/local/bin/backup".into(),
+            user: None,
+        }))
+        .render(&ResourceParams::Cron(CronParams::Absent {
+            name: "backup".into(),
+            user: Some("me".into()),
+        }))
+        .section("resource")
+        .render(&Resource::Cron(CronResource::Present {
+            name: "backup".into(),
+            job: job(),
+            user: Some("me".into()),
+        }))
+        .render(&Resource::Cron(CronResource::Absent {
+            name: "backup".into(),
+            user: None,
+        }))
+        .section("state")
+        .render(&ResourceState::Cron(CronState::Absent))
+        .render(&ResourceState::Cron(CronState::Present { job: job() }))
+        .section("change")
+        .render(&ResourceChange::Cron(CronChange::Set {
+            name: "backup".into(),
+            job: job(),
+            user: None,
+            before: None,
+        }))
+        .render(&ResourceChange::Cron(CronChange::Set {
+            name: "backup".into(),
+            job: job(),
+            user: Some("me".into()),
+            before: Some("0 4 * * * /usr/local/bin/backup".into()),
+        }))
+        .render(&ResourceChange::Cron(CronChange::Remove {
+            name: "backup".into(),
+            user: None,
+        }))
+        .assert_matches(snapshot_path("cron"));
+}
+
+#[test]
+fn directory() {
+    let source = || FilePath::new("/home/me/dotfiles/nvim");
+    let path = || FilePath::new("/home/me/.config/nvim");
+
+    let mut snapshot = Snapshot::new();
+    snapshot
+        .section("params")
+        .render(&ResourceParams::Directory(DirectoryParams::Sourced {
+            source: source(),
+            source_span: empty_span(),
+            path: path(),
+            mode: None,
+            user: None,
+            group: None,
+        }))
+        .render(&ResourceParams::Directory(DirectoryParams::Linked {
+            source: source(),
+            source_span: empty_span(),
+            path: path(),
+        }))
+        .render(&ResourceParams::Directory(DirectoryParams::Present {
+            path: path(),
+            mode: Some(FileMode::new(0o755)),
+            user: None,
+            group: None,
+        }))
+        .render(&ResourceParams::Directory(DirectoryParams::Absent {
+            path: path(),
+        }))
+        .section("resource")
+        .render(&Resource::Directory(DirectoryResource::Sourced {
+            source: source(),
+            path: path(),
+        }))
+        .render(&Resource::Directory(DirectoryResource::Linked {
+            source: source(),
+            path: path(),
+        }))
+        .render(&Resource::Directory(DirectoryResource::Present {
+            path: path(),
+        }))
+        .render(&Resource::Directory(DirectoryResource::Absent {
+            path: path(),
+        }))
+        .render(&Resource::Directory(DirectoryResource::Mode {
+            path: path(),
+            mode: FileMode::new(0o755),
+        }))
+        .render(&Resource::Directory(DirectoryResource::User {
+            path: path(),
+            user: FileUser::new("me"),
+        }))
+        .render(&Resource::Directory(DirectoryResource::Group {
+            path: path(),
+            group: FileGroup::new("staff"),
+        }))
+        .section("state");
+    for state in [
+        DirectoryState::Sourced,
+        DirectoryState::NotSourced,
+        DirectoryState::Linked,
+        DirectoryState::NotLinked,
+        DirectoryState::Present,
+        DirectoryState::Absent,
+        DirectoryState::ModeCorrect,
+        DirectoryState::ModeIncorrect,
+        DirectoryState::UserCorrect,
+        DirectoryState::UserIncorrect,
+        DirectoryState::GroupCorrect,
+        DirectoryState::GroupIncorrect,
+    ] {
+        snapshot.render(&ResourceState::Directory(state));
+    }
+    snapshot
+        .section("change")
+        .render(&ResourceChange::Directory(DirectoryChange::Create {
+            path: path(),
+        }))
+        .render(&ResourceChange::Directory(DirectoryChange::CreateSymlink {
+            source: source(),
+            path: path(),
+        }))
+        .render(&ResourceChange::Directory(DirectoryChange::CopyTree {
+            source: source(),
+            path: path(),
+        }))
+        .render(&ResourceChange::Directory(DirectoryChange::Remove {
+            path: path(),
+        }))
+        .render(&ResourceChange::Directory(DirectoryChange::ChangeMode {
+            path: path(),
+            mode: FileMode::new(0o755),
+        }))
+        .render(&ResourceChange::Directory(DirectoryChange::ChangeOwner {
+            path: path(),
+            user: Some(FileUser::new("me")),
+            group: None,
+        }))
+        .assert_matches(snapshot_path("directory"));
+}
+
+#[test]
+fn file() {
+    let source = || FilePath::new("/home/me/dotfiles/gitconfig");
+    let path = || FilePath::new("/home/me/.gitconfig");
+
+    let mut snapshot = Snapshot::new();
+    snapshot
+        .section("params")
+        .render(&ResourceParams::File(FileParams::Sourced {
+            source: source(),
+            source_span: empty_span(),
+            path: path(),
+            mode: None,
+            user: None,
+            group: None,
+            restarts: None,
+            parents: None,
+        }))
+        .render(&ResourceParams::File(FileParams::Contents {
+            contents: "[user]\n".into(),
+            path: path(),
+            mode: None,
+            user: None,
+            group: None,
+            restarts: None,
+            parents: None,
+        }))
+        .render(&ResourceParams::File(FileParams::Linked {
+            source: source(),
+            source_span: empty_span(),
+            path: path(),
+            restarts: None,
+            parents: None,
+        }))
+        .render(&ResourceParams::File(FileParams::Synced {
+            source: FilePath::new("/home/me/dotfiles/nvim"),
+            source_span: empty_span(),
+            path: FilePath::new("/home/me/.config/nvim"),
+            entries: vec![
+                FileSyncEntry::File("init.lua".into()),
+                FileSyncEntry::Directory("lua".into()),
+                FileSyncEntry::File("lua/plugins.lua".into()),
+            ],
+            delete: true,
+            restarts: None,
+            parents: None,
+        }))
+        .render(&ResourceParams::File(FileParams::Present {
+            path: path(),
+            mode: Some(FileMode::new(0o644)),
+            user: None,
+            group: None,
+            restarts: None,
+            parents: None,
+        }))
+        .render(&ResourceParams::File(FileParams::Absent {
+            path: path(),
+            restarts: None,
+        }))
+        .section("resource")
+        .render(&Resource::File(FileResource::Sourced {
+            source: source(),
+            path: path(),
+            restarts: None,
+        }))
+        .render(&Resource::File(FileResource::Sourced {
+            source: source(),
+            path: path(),
+            restarts: Some("git-daemon.service".into()),
+        }))
+        .render(&Resource::File(FileResource::Contents {
+            contents: "[user]\n".into(),
+            path: path(),
+            restarts: None,
+        }))
+        .render(&Resource::File(FileResource::Linked {
+            source: source(),
+            path: path(),
+            restarts: None,
+        }))
+        .render(&Resource::File(FileResource::Secret {
+            name: "github-token".into(),
+            path: path(),
+            restarts: None,
+        }))
+        .render(&Resource::File(FileResource::Present {
+            path: path(),
+            restarts: None,
+        }))
+        .render(&Resource::File(FileResource::Absent {
+            path: path(),
+            restarts: None,
+        }))
+        .render(&Resource::File(FileResource::Mode {
+            path: path(),
+            mode: FileMode::new(0o644),
+        }))
+        .render(&Resource::File(FileResource::User {
+            path: path(),
+            user: FileUser::new("me"),
+        }))
+        .render(&Resource::File(FileResource::Group {
+            path: path(),
+            group: FileGroup::new("staff"),
+        }))
+        .render(&Resource::File(FileResource::Parent {
+            path: FilePath::new("/home/me"),
+            parents: FileParents::default(),
+        }))
+        .render(&Resource::File(FileResource::Extraneous {
+            path: FilePath::new("/home/me/.config/nvim"),
+            keep: strings(&["init.lua", "lua", "lua/plugins.lua"]),
+            restarts: None,
+        }))
+        .section("state");
+    for state in [
+        FileState::Sourced,
+        FileState::NotSourced {
+            current: None,
+            desired: None,
+        },
+        FileState::Linked,
+        FileState::NotLinked,
+        FileState::Present,
+        FileState::Absent,
+        FileState::ModeCorrect,
+        FileState::ModeIncorrect { current: None },
+        FileState::UserCorrect,
+        FileState::UserIncorrect { current: None },
+        FileState::GroupCorrect,
+        FileState::GroupIncorrect { current: None },
+        FileState::NoExtraneous,
+        FileState::Extraneous {
+            files: vec![FilePath::new("/home/me/.config/nvim/old.lua")],
+            directories: vec![],
+        },
+    ] {
+        snapshot.render(&ResourceState::File(state));
+    }
+    snapshot
+        .section("change")
+        .render(&ResourceChange::File(FileChange::Write {
+            path: path(),
+            source: FileSource::Contents(b"[user]\n".as_slice().into()),
+            restarts: None,
+            before: None,
+            after: None,
+        }))
+        .render(&ResourceChange::File(FileChange::Write {
+            path: path(),
+            source: FileSource::Path(source()),
+            restarts: None,
+            before: None,
+            after: None,
+        }))
+        .render(&ResourceChange::File(FileChange::Write {
+            path: path(),
+            source: FileSource::Path(source()),
+            restarts: None,
+            before: Some(FileDigest::of(b"[user]\n")),
+            after: Some(FileDigest::of(b"[user]\n\tname = me\n")),
+        }))
+        .render(&ResourceChange::File(FileChange::Write {
+            path: path(),
+            source: FileSource::Path(source()),
+            restarts: Some("git-daemon.service".into()),
+            before: None,
+            after: None,
+        }))
+        .render(&ResourceChange::File(FileChange::Write {
+            path: path(),
+            source: FileSource::Secret("github-token".into()),
+            restarts: None,
+            before: None,
+            after: None,
+        }))
+        .render(&ResourceChange::File(FileChange::CreateSymlink {
+            source: source(),
+            path: path(),
+            restarts: None,
+        }))
+        .render(&ResourceChange::File(FileChange::Remove {
+            path: path(),
+            restarts: None,
+        }))
+        .render(&ResourceChange::File(FileChange::ChangeMode {
+            path: path(),
+            mode: FileMode::new(0o644),
+            before: None,
+        }))
+        .render(&ResourceChange::File(FileChange::ChangeMode {
+            path: path(),
+            mode: FileMode::new(0o644),
+            before: Some(FileMode::new(0o600)),
+        }))
+        .render(&ResourceChange::File(FileChange::ChangeOwner {
+            path: path(),
+            user: None,
+            group: Some(FileGroup::new("staff")),
+            before: None,
+        }))
+        .render(&ResourceChange::File(FileChange::ChangeOwner {
+            path: path(),
+            user: None,
+            group: Some(FileGroup::new("staff")),
+            before: Some("me".into()),
+        }))
+        .render(&ResourceChange::File(FileChange::CreateParent {
+            path: FilePath::new("/home/me"),
+            parents: FileParents::default(),
+        }))
+        .render(&ResourceChange::File(FileChange::RemoveExtraneous {
+            path: FilePath::new("/home/me/.config/nvim"),
+            files: vec![FilePath::new("/home/me/.config/nvim/old.lua")],
+            directories: vec![FilePath::new("/home/me/.config/nvim/after")],
+            restarts: None,
+        }))
+        .assert_matches(snapshot_path("file"));
+}
+
+#[test]
+fn git() {
+    let repo = || "https://github.com/ahdinosaur/lusid".to_string();
+    let path = || FilePath::new("/home/me/src/lusid");
+
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::Git(GitParams {
+            repo: repo(),
+            path: path(),
+            version: Some("main".into()),
+            update: None,
+            force: Some(false),
+            user: Some("me".into()),
+            group: None,
+            depth: Some(1),
+        }))
+        .section("resource")
+        .render(&Resource::Git(GitResource {
+            repo: repo(),
+            path: path(),
+            version: Some("main".into()),
+            update: true,
+            force: false,
+            owner: GitOwner {
+                user: Some("me".into()),
+                group: None,
+            },
+            depth: Some(1),
+        }))
+        .section("state")
+        .render(&ResourceState::Git(GitState::Absent))
+        .render(&ResourceState::Git(GitState::Present {
+            head: Some("0123abc".into()),
+            branch: None,
+            is_dirty: false,
+            pin: None,
+        }))
+        .render(&ResourceState::Git(GitState::Present {
+            head: Some("0123abc".into()),
+            branch: None,
+            is_dirty: false,
+            pin: Some(GitPin::Tag {
+                name: "v0.1.0".into(),
+                commit: "0123abc".into(),
+            }),
+        }))
+        .section("change")
+        .render(&ResourceChange::Git(GitChange::Clone {
+            repo: repo(),
+            path: path(),
+            depth: None,
+            owner: GitOwner::default(),
+        }))
+        .render(&ResourceChange::Git(GitChange::Clone {
+            repo: repo(),
+            path: path(),
+            depth: Some(1),
+            owner: GitOwner {
+                user: Some("me".into()),
+                group: Some("me".into()),
+            },
+        }))
+        .render(&ResourceChange::Git(GitChange::Checkout {
+            path: path(),
+            version: "main".into(),
+            force: false,
+            fetch: true,
+            owner: GitOwner::default(),
+        }))
+        .render(&ResourceChange::Git(GitChange::Pull {
+            path: path(),
+            owner: GitOwner::default(),
+        }))
+        .assert_matches(snapshot_path("git"));
+}
+
+#[test]
+fn group() {
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::Group(GroupParams::Present {
+            name: "docker".into(),
+            gid: None,
+            system: Some(true),
+            append_users: Some(strings(&["me"])),
+        }))
+        .render(&ResourceParams::Group(GroupParams::Absent {
+            name: "docker".into(),
+        }))
+        .section("resource")
+        .render(&Resource::Group(GroupResource::Present {
+            name: "docker".into(),
+            gid: None,
+            system: true,
+            append_users: Some(strings(&["me"])),
+        }))
+        .render(&Resource::Group(GroupResource::Absent {
+            name: "docker".into(),
+        }))
+        .section("state")
+        .render(&ResourceState::Group(GroupState::Absent))
+        .render(&ResourceState::Group(GroupState::Present {
+            gid: 998,
+            members: strings(&["me", "ci"]),
+        }))
+        .section("change")
+        .render(&ResourceChange::Group(GroupChange::Create {
+            name: "docker".into(),
+            gid: None,
+            system: true,
+            append_users: strings(&["me"]),
+        }))
+        .render(&ResourceChange::Group(GroupChange::Modify {
+            name: "docker".into(),
+            gid: Some(998),
+            append_users: Vec::new(),
+        }))
+        .render(&ResourceChange::Group(GroupChange::Delete {
+            name: "docker".into(),
+        }))
+        .assert_matches(snapshot_path("group"));
+}
+
+#[test]
+fn apk() {
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::Apk(ApkParams::Package {
+            package: "neovim".into(),
+        }))
+        .render(&ResourceParams::Apk(ApkParams::Packages {
+            packages: strings(&["neovim", "ripgrep"]),
+        }))
+        .section("resource")
+        .render(&Resource::Apk(ApkResource {
+            package: "neovim".into(),
+        }))
+        .section("state")
+        .render(&ResourceState::Apk(ApkState::NotInstalled))
+        .render(&ResourceState::Apk(ApkState::Installed))
+        .section("change")
+        .render(&ResourceChange::Apk(ApkChange::Install {
+            package: "neovim".into(),
+        }))
+        .assert_matches(snapshot_path("apk"));
+}
+
+#[test]
+fn nix() {
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::Nix(NixParams {
+            packages: strings(&["ripgrep", "fd"]),
+            flake: "nixpkgs".into(),
+            present: true,
+        }))
+        .render(&ResourceParams::Nix(NixParams {
+            packages: strings(&["hello"]),
+            flake: "nixpkgs".into(),
+            present: false,
+        }))
+        .section("resource")
+        .render(&Resource::Nix(NixResource::Present {
+            flake: "nixpkgs".into(),
+            attribute: "ripgrep".into(),
+        }))
+        .render(&Resource::Nix(NixResource::Absent {
+            attribute: "hello".into(),
+        }))
+        .section("state")
+        .render(&ResourceState::Nix(NixState::NotInstalled))
+        .render(&ResourceState::Nix(NixState::Installed {
+            element: "hello".into(),
+        }))
+        .section("change")
+        .render(&ResourceChange::Nix(NixChange::Install {
+            installable: "nixpkgs#ripgrep".into(),
+        }))
+        .render(&ResourceChange::Nix(NixChange::Remove {
+            element: "hello".into(),
+        }))
+        .assert_matches(snapshot_path("nix"));
+}
+
+#[test]
+fn dnf() {
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::Dnf(DnfParams {
+            packages: strings(&["neovim", "ripgrep"]),
+            present: true,
+        }))
+        .render(&ResourceParams::Dnf(DnfParams {
+            packages: strings(&["nano"]),
+            present: false,
+        }))
+        .section("resource")
+        .render(&Resource::Dnf(DnfResource::Present {
+            package: "neovim".into(),
+        }))
+        .render(&Resource::Dnf(DnfResource::Absent {
+            package: "nano".into(),
+        }))
+        .section("state")
+        .render(&ResourceState::Dnf(DnfState::NotInstalled))
+        .render(&ResourceState::Dnf(DnfState::Installed))
+        .section("change")
+        .render(&ResourceChange::Dnf(DnfChange::Install {
+            package: "neovim".into(),
+        }))
+        .render(&ResourceChange::Dnf(DnfChange::Remove {
+            package: "nano".into(),
+        }))
+        .assert_matches(snapshot_path("dnf"));
+}
+
+#[test]
+fn pacman() {
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::Pacman(PacmanParams::Package {
+            package: "neovim".into(),
+        }))
+        .render(&ResourceParams::Pacman(PacmanParams::Packages {
+            packages: strings(&["neovim", "ripgrep"]),
+        }))
+        .section("resource")
+        .render(&Resource::Pacman(PacmanResource {
+            package: "neovim".into(),
+        }))
+        .section("state")
+        .render(&ResourceState::Pacman(PacmanState::NotInstalled))
+        .render(&ResourceState::Pacman(PacmanState::Installed))
+        .section("change")
+        .render(&ResourceChange::Pacman(PacmanChange::Install {
+            package: "neovim".into(),
+        }))
+        .assert_matches(snapshot_path("pacman"));
+}
+
+#[test]
+fn aur() {
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::Aur(AurParams {
+            packages: strings(&["paru-bin", "zoom"]),
+            user: "mikey".into(),
+            helper: "paru".into(),
+        }))
+        .section("resource")
+        .render(&Resource::Aur(AurResource {
+            package: "zoom".into(),
+            user: "mikey".into(),
+            helper: "paru".into(),
+        }))
+        .section("state")
+        .render(&ResourceState::Aur(AurState::NotInstalled))
+        .render(&ResourceState::Aur(AurState::Installed))
+        .section("change")
+        .render(&ResourceChange::Aur(AurChange::Install {
+            package: "zoom".into(),
+            user: "mikey".into(),
+            helper: "paru".into(),
+        }))
+        .assert_matches(snapshot_path("aur"));
+}
+
+#[test]
+fn podman() {
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::Podman(PodmanParams::Present {
+            name: "web".into(),
+            image: "nginx:1.27".into(),
+            command: None,
+            env: None,
+            ports: Some(strings(&["8080:80"])),
+            volumes: None,
+            restart_policy: None,
+            running: None,
+        }))
+        .render(&ResourceParams::Podman(PodmanParams::Absent {
+            name: "web".into(),
+        }))
+        .section("resource")
+        .render(&Resource::Podman(PodmanResource::Present {
+            name: "web".into(),
+            image: "nginx:1.27".into(),
+            command: None,
+            env: Vec::new(),
+            ports: strings(&["8080:80"]),
+            volumes: Vec::new(),
+            restart_policy: None,
+            running: true,
+        }))
+        .render(&Resource::Podman(PodmanResource::Absent {
+            name: "web".into(),
+        }))
+        .section("state")
+        .render(&ResourceState::Podman(PodmanState::Absent))
+        .render(&ResourceState::Podman(PodmanState::Present {
+            image: "docker.io/library/nginx:1.27".into(),
+            running: false,
+            config_hash: None,
+        }))
+        .section("change")
+        .render(&ResourceChange::Podman(PodmanChange::Create {
+            name: "web".into(),
+            image: "nginx:1.27".into(),
+            command: None,
+            env: Vec::new(),
+            ports: strings(&["8080:80"]),
+            volumes: Vec::new(),
+            restart_policy: None,
+            start: true,
+        }))
+        .render(&ResourceChange::Podman(PodmanChange::Start {
+            name: "web".into(),
+        }))
+        .render(&ResourceChange::Podman(PodmanChange::Stop {
+            name: "web".into(),
+        }))
+        .render(&ResourceChange::Podman(PodmanChange::Recreate {
+            name: "web".into(),
+            image: "nginx:1.27".into(),
+            command: None,
+            env: Vec::new(),
+            ports: strings(&["8080:80"]),
+            volumes: Vec::new(),
+            restart_policy: None,
+            start: true,
+        }))
+        .render(&ResourceChange::Podman(PodmanChange::Remove {
+            name: "web".into(),
+        }))
+        .assert_matches(snapshot_path("podman"));
+}
+
+#[test]
+fn pip() {
+    let venv = || FilePath::new("/home/me/.venvs/tools");
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::Pip(PipParams::Package {
+            package: "black".into(),
+            version: Some("24.1.0".into()),
+            venv: None,
+        }))
+        .render(&ResourceParams::Pip(PipParams::Packages {
+            packages: strings(&["requests", "httpx==0.27.0"]),
+            venv: Some(venv()),
+        }))
+        .section("resource")
+        .render(&Resource::Pip(PipResource {
+            target: PipTarget::Pipx,
+            package: "black".into(),
+            version: Some("24.1.0".into()),
+        }))
+        .render(&Resource::Pip(PipResource {
+            target: PipTarget::Venv { path: venv() },
+            package: "requests".into(),
+            version: None,
+        }))
+        .section("state")
+        .render(&ResourceState::Pip(PipState::NoVenv))
+        .render(&ResourceState::Pip(PipState::NotInstalled))
+        .render(&ResourceState::Pip(PipState::Installed {
+            version: "23.1.0".into(),
+        }))
+        .section("change")
+        .render(&ResourceChange::Pip(PipChange::Install {
+            target: PipTarget::Pipx,
+            requirement: "black==24.1.0".into(),
+            installed: Some("23.1.0".into()),
+            create_venv: false,
+        }))
+        .render(&ResourceChange::Pip(PipChange::Install {
+            target: PipTarget::Venv { path: venv() },
+            requirement: "requests".into(),
+            installed: None,
+            create_venv: true,
+        }))
+        .assert_matches(snapshot_path("pip"));
+}
+
+#[test]
+fn rustup() {
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::Rustup(RustupParams {
+            toolchain: "stable".into(),
+            components: strings(&["clippy", "rustfmt"]),
+            targets: strings(&["wasm32-unknown-unknown"]),
+        }))
+        .render(&ResourceParams::Rustup(RustupParams {
+            toolchain: "nightly-2024-06-01".into(),
+            components: vec![],
+            targets: vec![],
+        }))
+        .section("resource")
+        .render(&Resource::Rustup(RustupResource::Toolchain {
+            toolchain: "stable".into(),
+        }))
+        .render(&Resource::Rustup(RustupResource::Component {
+            toolchain: "stable".into(),
+            component: "clippy".into(),
+        }))
+        .render(&Resource::Rustup(RustupResource::Target {
+            toolchain: "stable".into(),
+            target: "wasm32-unknown-unknown".into(),
+        }))
+        .section("state")
+        .render(&ResourceState::Rustup(RustupState::NoToolchain))
+        .render(&ResourceState::Rustup(RustupState::Missing))
+        .render(&ResourceState::Rustup(RustupState::Installed))
+        .section("change")
+        .render(&ResourceChange::Rustup(RustupChange::InstallToolchain {
+            toolchain: "stable".into(),
+        }))
+        .render(&ResourceChange::Rustup(RustupChange::AddComponent {
+            toolchain: "stable".into(),
+            component: "clippy".into(),
+            install_toolchain: true,
+        }))
+        .render(&ResourceChange::Rustup(RustupChange::AddTarget {
+            toolchain: "stable".into(),
+            target: "wasm32-unknown-unknown".into(),
+            install_toolchain: false,
+        }))
+        .assert_matches(snapshot_path("rustup"));
+}
+
+#[test]
+fn brew() {
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::Brew(BrewParams::Package {
+            package: "ripgrep".into(),
+            kind: BrewKind::Formula,
+        }))
+        .render(&ResourceParams::Brew(BrewParams::Packages {
+            packages: strings(&["git", "neovim"]),
+            kind: BrewKind::Formula,
+        }))
+        .render(&ResourceParams::Brew(BrewParams::Packages {
+            packages: strings(&["firefox"]),
+            kind: BrewKind::Cask,
+        }))
+        .section("resource")
+        .render(&Resource::Brew(BrewResource {
+            package: "ripgrep".into(),
+            kind: BrewKind::Formula,
+        }))
+        .render(&Resource::Brew(BrewResource {
+            package: "firefox".into(),
+            kind: BrewKind::Cask,
+        }))
+        .section("state")
+        .render(&ResourceState::Brew(BrewState::NotInstalled))
+        .render(&ResourceState::Brew(BrewState::Installed))
+        .section("change")
+        .render(&ResourceChange::Brew(BrewChange::Install {
+            package: "firefox".into(),
+            kind: BrewKind::Cask,
+        }))
+        .assert_matches(snapshot_path("brew"));
+}
+
+#[test]
+fn launchd() {
+    let plist = || FilePath::new("/Library/LaunchDaemons/com.example.agent.plist");
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::Launchd(LaunchdParams {
+            plist: plist(),
+            label: None,
+            domain: None,
+            enabled: None,
+            active: Some(true),
+        }))
+        .section("resource")
+        .render(&Resource::Launchd(LaunchdResource {
+            domain: "system".into(),
+            label: "com.example.agent".into(),
+            plist: plist(),
+            enabled: true,
+            active: true,
+        }))
+        .section("state")
+        .render(&ResourceState::Launchd(LaunchdState {
+            enabled: false,
+            active: false,
+        }))
+        .section("change")
+        .render(&ResourceChange::Launchd(LaunchdChange {
+            domain: "system".into(),
+            label: "com.example.agent".into(),
+            plist: plist(),
+            enable: Some(true),
+            active: Some(true),
+        }))
+        .render(&ResourceChange::Launchd(LaunchdChange {
+            domain: "gui/501".into(),
+            label: "com.example.agent".into(),
+            plist: plist(),
+            enable: None,
+            active: Some(false),
+        }))
+        .assert_matches(snapshot_path("launchd"));
+}
+
+#[test]
+fn firewall() {
+    let ssh = || FirewallRule {
+        action: FirewallAction::Limit,
+        port: Some("22".into()),
+        protocol: Some(FirewallProtocol::Tcp),
+        from: None,
+    };
+    let postgres = || FirewallRule {
+        action: FirewallAction::Allow,
+        port: Some("5432".into()),
+        protocol: Some(FirewallProtocol::Tcp),
+        from: Some("10.0.0.0/8".into()),
+    };
+    let blocked = || FirewallRule {
+        action: FirewallAction::Deny,
+        port: None,
+        protocol: None,
+        from: Some("203.0.113.4".into()),
+    };
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::Firewall(FirewallParams::Rule(
+            FirewallRuleParams {
+                rule: ssh(),
+                present: true,
+            },
+        )))
+        .render(&ResourceParams::Firewall(FirewallParams::Rules {
+            rules: vec![
+                FirewallRuleParams {
+                    rule: postgres(),
+                    present: true,
+                },
+                FirewallRuleParams {
+                    rule: blocked(),
+                    present: false,
+                },
+            ],
+        }))
+        .section("resource")
+        .render(&Resource::Firewall(FirewallResource::Present(postgres())))
+        .render(&Resource::Firewall(FirewallResource::Absent(blocked())))
+        .section("state")
+        .render(&ResourceState::Firewall(FirewallState::Present))
+        .render(&ResourceState::Firewall(FirewallState::Absent))
+        .section("change")
+        .render(&ResourceChange::Firewall(FirewallChange::Add(ssh())))
+        .render(&ResourceChange::Firewall(FirewallChange::Delete(blocked())))
+        .assert_matches(snapshot_path("firewall"));
+}
+
+#[test]
+fn networkd() {
+    let content = || "[NetDev]\nName=br0\nKind=bridge\n".to_string();
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::Networkd(NetworkdParams {
+            name: "10-br0".into(),
+            network: Some("[Match]\nName=br0\n\n[Network]\nAddress=10.0.0.1/24\n".into()),
+            netdev: Some(content()),
+        }))
+        .section("resource")
+        .render(&Resource::Networkd(NetworkdResource {
+            name: "10-br0.netdev".into(),
+            path: FilePath::new("/etc/systemd/network/10-br0.netdev"),
+            content: content(),
+        }))
+        .section("state")
+        .render(&ResourceState::Networkd(NetworkdState::Matches))
+        .render(&ResourceState::Networkd(NetworkdState::Differs))
+        .section("change")
+        .render(&ResourceChange::Networkd(NetworkdChange::Write {
+            name: "10-br0.netdev".into(),
+            path: FilePath::new("/etc/systemd/network/10-br0.netdev"),
+            content: content(),
+        }))
+        .assert_matches(snapshot_path("networkd"));
+}
+
+#[test]
+fn time() {
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::Time(TimeParams {
+            timezone: Some("Europe/Berlin".into()),
+            ntp: Some(true),
+        }))
+        .render(&ResourceParams::Time(TimeParams {
+            timezone: None,
+            ntp: Some(false),
+        }))
+        .section("resource")
+        .render(&Resource::Time(TimeResource::Timezone(
+            "Europe/Berlin".into(),
+        )))
+        .render(&Resource::Time(TimeResource::Ntp(true)))
+        .section("state")
+        .render(&ResourceState::Time(TimeState::Timezone(Some(
+            "UTC".into(),
+        ))))
+        .render(&ResourceState::Time(TimeState::Timezone(None)))
+        .render(&ResourceState::Time(TimeState::Ntp(false)))
+        .section("change")
+        .render(&ResourceChange::Time(TimeChange::SetTimezone(
+            "Europe/Berlin".into(),
+        )))
+        .render(&ResourceChange::Time(TimeChange::SetNtp(true)))
+        .assert_matches(snapshot_path("time"));
+}
+
+#[test]
+fn podman_image() {
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::PodmanImage(PodmanImageParams::Image {
+            image: "nginx:1.27".into(),
+        }))
+        .render(&ResourceParams::PodmanImage(PodmanImageParams::Images {
+            images: strings(&["nginx:1.27", "redis@sha256:0123abc"]),
+        }))
+        .section("resource")
+        .render(&Resource::PodmanImage(PodmanImageResource {
+            image: "nginx:1.27".into(),
+        }))
+        .section("state")
+        .render(&ResourceState::PodmanImage(PodmanImageState::Absent))
+        .render(&ResourceState::PodmanImage(PodmanImageState::Present))
+        .section("change")
+        .render(&ResourceChange::PodmanImage(PodmanImageChange::Pull {
+            image: "nginx:1.27".into(),
+        }))
+        .assert_matches(snapshot_path("podman_image"));
+}
+
+#[test]
+fn secret() {
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::Secret(SecretParams {
+            name: "github-token".into(),
+            path: FilePath::new("/home/me/.config/gh/token"),
+            mode: None,
+            user: None,
+            group: None,
+            restarts: None,
+        }))
+        .assert_matches(snapshot_path("secret"));
+}
+
+#[test]
+fn systemd() {
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::Systemd(SystemdParams {
+            name: "nginx.service".into(),
+            enabled: Some(true),
+            active: None,
+        }))
+        .section("resource")
+        .render(&Resource::Systemd(SystemdResource {
+            name: "nginx.service".into(),
+            enabled: true,
+            active: true,
+        }))
+        .section("state")
+        .render(&ResourceState::Systemd(SystemdState {
+            enabled: false,
+            active: true,
+        }))
+        .section("change")
+        .render(&ResourceChange::Systemd(SystemdChange {
+            name: "nginx.service".into(),
+            enable: Some(true),
+            active: Some(true),
+        }))
+        .render(&ResourceChange::Systemd(SystemdChange {
+            name: "nginx.service".into(),
+            enable: Some(false),
+            active: None,
+        }))
+        .render(&ResourceChange::Systemd(SystemdChange {
+            name: "nginx.service".into(),
+            enable: None,
+            active: Some(false),
+        }))
+        .assert_matches(snapshot_path("systemd"));
+}
+
+#[test]
+fn systemd_unit() {
+    let name = || "myapp.service".to_string();
+    let path = || FilePath::new("/etc/systemd/system/myapp.service");
+    let content = || "[Service]\nExecStart=/usr/bin/myapp\n".to_string();
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::SystemdUnit(SystemdUnitParams {
+            name: name(),
+            content: content(),
+            enabled: None,
+            active: Some(true),
+        }))
+        .section("resource")
+        .render(&Resource::SystemdUnit(SystemdUnitResource::UnitFile {
+            name: name(),
+            path: path(),
+            content: content(),
+            restart: true,
+        }))
+        .render(&Resource::SystemdUnit(SystemdUnitResource::Unit(
+            SystemdResource {
+                name: name(),
+                enabled: true,
+                active: true,
+            },
+        )))
+        .section("state")
+        .render(&ResourceState::SystemdUnit(
+            SystemdUnitState::UnitFileMatches,
+        ))
+        .render(&ResourceState::SystemdUnit(
+            SystemdUnitState::UnitFileDiffers,
+        ))
+        .render(&ResourceState::SystemdUnit(SystemdUnitState::Unit(
+            SystemdState {
+                enabled: false,
+                active: false,
+            },
+        )))
+        .section("change")
+        .render(&ResourceChange::SystemdUnit(
+            SystemdUnitChange::WriteUnitFile {
+                name: name(),
+                path: path(),
+                content: content(),
+                restart: true,
+            },
+        ))
+        .render(&ResourceChange::SystemdUnit(
+            SystemdUnitChange::WriteUnitFile {
+                name: name(),
+                path: path(),
+                content: content(),
+                restart: false,
+            },
+        ))
+        .render(&ResourceChange::SystemdUnit(SystemdUnitChange::Unit(
+            SystemdChange {
+                name: name(),
+                enable: Some(true),
+                active: Some(true),
+            },
+        )))
+        .assert_matches(snapshot_path("systemd_unit"));
+}
+
+#[test]
+fn user() {
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::User(UserParams::Present {
+            name: "me".into(),
+            uid: Some(1000),
+            group: None,
+            append_groups: Some(strings(&["docker"])),
+            comment: None,
+            home: None,
+            shell: Some("/bin/zsh".into()),
+            system: None,
+            create_home: None,
+        }))
+        .render(&ResourceParams::User(UserParams::Absent {
+            name: "me".into(),
+            remove_home: None,
+        }))
+        .section("resource")
+        .render(&Resource::User(UserResource::Present {
+            name: "me".into(),
+            uid: Some(1000),
+            group: None,
+            append_groups: Some(strings(&["docker"])),
+            comment: None,
+            home: None,
+            shell: Some("/bin/zsh".into()),
+            system: false,
+            create_home: true,
+        }))
+        .render(&Resource::User(UserResource::Absent {
+            name: "me".into(),
+            remove_home: false,
+        }))
+        .section("state")
+        .render(&ResourceState::User(UserState::Absent))
+        .render(&ResourceState::User(UserState::Present {
+            uid: 1000,
+            primary_group: "me".into(),
+            extra_groups: strings(&["docker"]),
+            comment: String::new(),
+            home: "/home/me".into(),
+            shell: "/bin/zsh".into(),
+        }))
+        .section("change")
+        .render(&ResourceChange::User(UserChange::Create {
+            name: "me".into(),
+            uid: Some(1000),
+            primary_group: None,
+            append_groups: strings(&["docker"]),
+            comment: None,
+            home: None,
+            shell: Some("/bin/zsh".into()),
+            system: false,
+            create_home: true,
+        }))
+        .render(&ResourceChange::User(UserChange::Modify {
+            name: "me".into(),
+            uid: None,
+            primary_group: None,
+            append_groups: Some(strings(&["docker"])),
+            comment: None,
+            home: None,
+            shell: None,
+        }))
+        .render(&ResourceChange::User(UserChange::Delete {
+            name: "me".into(),
+            remove_home: true,
+        }))
+        .assert_matches(snapshot_path("user"));
+}
+
+#[test]
+fn wireguard() {
+    let content = || {
+        "[Interface]\nAddress = 10.0.0.1/24\nListenPort = 51820\nPostUp = wg set %i private-key /etc/wireguard/wg0.key\n".to_string()
+    };
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::Wireguard(WireguardParams {
+            interface: "wg0".into(),
+            private_key_path: "/etc/wireguard/wg0.key".into(),
+            address: vec!["10.0.0.1/24".into()],
+            listen_port: Some(51820),
+            peers: vec![WireguardPeerParams {
+                public_key: "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=".into(),
+                preshared_key: Some("/UwcSPg38hW/D9Y3tcS1FOV0K1wuURMbS0sesJEP5ak=".into()),
+                endpoint: Some("vpn.example.com:51820".into()),
+                allowed_ips: vec!["10.0.0.2/32".into()],
+                persistent_keepalive: Some(25),
+            }],
+        }))
+        .section("resource")
+        .render(&Resource::Wireguard(WireguardResource {
+            interface: "wg0".into(),
+            path: FilePath::new("/etc/wireguard/wg0.conf"),
+            content: content(),
+        }))
+        .section("state")
+        .render(&ResourceState::Wireguard(WireguardState::Matches))
+        .render(&ResourceState::Wireguard(WireguardState::Differs))
+        .section("change")
+        .render(&ResourceChange::Wireguard(WireguardChange::Write {
+            interface: "wg0".into(),
+            path: FilePath::new("/etc/wireguard/wg0.conf"),
+            content: content(),
+        }))
+        .assert_matches(snapshot_path("wireguard"));
+}
+
+#[test]
+fn tls_cert() {
+    let resource = || TlsCertResource {
+        cert_path: FilePath::new("/etc/nginx/tls/example.com.crt"),
+        key_path: FilePath::new("/etc/nginx/tls/example.com.key"),
+        names: strings(&["example.com", "www.example.com"]),
+        renew_days: 30,
+        issuer: TlsCertIssuer::Acme {
+            email: "admin@example.com".into(),
+            webroot: Some("/var/www/html".into()),
+        },
+    };
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::TlsCert(TlsCertParams {
+            cert_path: "/etc/ssl/lusid/home.lan.crt".into(),
+            key_path: "/etc/ssl/lusid/home.lan.key".into(),
+            names: strings(&["home.lan", "192.168.1.10"]),
+            renew_days: 30,
+            issuer: TlsCertIssuer::SelfSigned { days: 365 },
+        }))
+        .section("resource")
+        .render(&Resource::TlsCert(resource()))
+        .section("state")
+        .render(&ResourceState::TlsCert(TlsCertState::Missing))
+        .render(&ResourceState::TlsCert(TlsCertState::Present {
+            names: strings(&["example.com", "www.example.com"]),
+            not_after: "Oct 16 12:00:00 2027 GMT".into(),
+            expiring: true,
+        }))
+        .section("change")
+        .render(&ResourceChange::TlsCert(TlsCertChange::Issue(resource())))
+        .render(&ResourceChange::TlsCert(TlsCertChange::Renew {
+            resource: resource(),
+            not_after: "Oct 16 12:00:00 2027 GMT".into(),
+        }))
+        .assert_matches(snapshot_path("tls_cert"));
+}
+
+#[test]
+fn git_config() {
+    let scope = || GitConfigScope::Global {
+        user: Some("me".into()),
+    };
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::GitConfig(GitConfigParams {
+            scope: scope(),
+            settings: vec![
+                GitConfigSetting {
+                    key: "user.name".into(),
+                    values: strings(&["Mikey"]),
+                },
+                GitConfigSetting {
+                    key: "credential.helper".into(),
+                    values: strings(&["", "cache --timeout=3600"]),
+                },
+                GitConfigSetting {
+                    key: "alias.co".into(),
+                    values: vec![],
+                },
+            ],
+        }))
+        .render(&ResourceParams::GitConfig(GitConfigParams {
+            scope: GitConfigScope::System,
+            settings: vec![GitConfigSetting {
+                key: "init.defaultBranch".into(),
+                values: strings(&["main"]),
+            }],
+        }))
+        .section("resource")
+        .render(&Resource::GitConfig(GitConfigResource {
+            scope: scope(),
+            key: "user.name".into(),
+            values: strings(&["Mikey"]),
+        }))
+        .section("state")
+        .render(&ResourceState::GitConfig(GitConfigState::Unset))
+        .render(&ResourceState::GitConfig(GitConfigState::Set(strings(&[
+            "Mikey",
+        ]))))
+        .section("change")
+        .render(&ResourceChange::GitConfig(GitConfigChange::Set {
+            scope: scope(),
+            key: "user.name".into(),
+            values: strings(&["Mikey"]),
+        }))
+        .render(&ResourceChange::GitConfig(GitConfigChange::Unset {
+            scope: GitConfigScope::Global { user: None },
+            key: "alias.co".into(),
+        }))
+        .assert_matches(snapshot_path("git_config"));
+}
+
+#[test]
+fn gpg_key() {
+    let resource = || GpgKeyResource {
+        fingerprint: "9DC858229FC7DD38854AE2D88D81803C0EBFCD88".into(),
+        source: GpgKeySource::Url {
+            url: "https://download.docker.com/linux/debian/gpg".into(),
+        },
+        keyring: GpgKeyring::File {
+            path: FilePath::new("/etc/apt/keyrings/docker.gpg"),
+        },
+    };
+    Snapshot::new()
+        .section("params")
+        .render(&ResourceParams::GpgKey(GpgKeyParams {
+            fingerprint: "9DC858229FC7DD38854AE2D88D81803C0EBFCD88".into(),
+            source: GpgKeySource::Url {
+                url: "https://download.docker.com/linux/debian/gpg".into(),
+            },
+            keyring: GpgKeyParamsKeyring::Apt {
+                name: "docker".into(),
+            },
+        }))
+        .render(&ResourceParams::GpgKey(GpgKeyParams {
+            fingerprint: "FAFC2B9B27A58C139883BB57EFDBFB83254EFF84".into(),
+            source: GpgKeySource::Keyserver {
+                keyserver: "hkps://keys.openpgp.org".into(),
+            },
+            keyring: GpgKeyParamsKeyring::User {
+                user: Some("mikey".into()),
+            },
+        }))
+        .section("resource")
+        .render(&Resource::GpgKey(resource()))
+        .section("state")
+        .render(&ResourceState::GpgKey(GpgKeyState::Missing))
+        .render(&ResourceState::GpgKey(GpgKeyState::Present))
+        .render(&ResourceState::GpgKey(GpgKeyState::Other(strings(&[
+            "0E5776264A9A4BACEEB0EB8AB4A10636C06FECB8",
+        ]))))
+        .section("change")
+        .render(&ResourceChange::GpgKey(GpgKeyChange::Import(resource())))
+        .assert_matches(snapshot_path("gpg_key"));
+}
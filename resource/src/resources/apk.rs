@@ -0,0 +1,263 @@
+//! `@core/apk`: Alpine packages, installed with `apk add` (see
+//! [`lusid_operation::operations::apk`]).
+//!
+//! Each package is its own atom, probed together with one `apk info -e`, and
+//! an epoch's installs merge into one `apk add`.
+
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use async_trait::async_trait;
+use lusid_causality::{CausalityMeta, CausalityTree};
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_operation::{Operation, operations::apk::ApkOperation};
+use lusid_params::{ParseError, ParseParams, StructFields};
+use lusid_view::impl_display_render;
+use rimu::{Spanned, Value};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApkParams {
+    Package { package: String },
+    Packages { packages: Vec<String> },
+}
+
+impl ParseParams for ApkParams {
+    fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
+        let mut fields = StructFields::new(value)?;
+        let out = if fields.has("packages") {
+            ApkParams::Packages {
+                packages: fields.required_string_list("packages")?,
+            }
+        } else {
+            ApkParams::Package {
+                package: fields.required_string("package")?,
+            }
+        };
+        fields.finish()?;
+        Ok(out)
+    }
+}
+
+impl Display for ApkParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApkParams::Package { package } => write!(f, "Apk(package = {package})"),
+            ApkParams::Packages { packages } => {
+                write!(f, "Apk(packages = [{}])", packages.join(", "))
+            }
+        }
+    }
+}
+
+impl_display_render!(ApkParams);
+
+#[derive(Debug, Clone)]
+pub struct ApkResource {
+    pub package: String,
+}
+
+impl Display for ApkResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self { package } = self;
+        write!(f, "Apk({package})")
+    }
+}
+
+impl_display_render!(ApkResource);
+
+#[derive(Debug, Clone)]
+pub enum ApkState {
+    NotInstalled,
+    Installed,
+}
+
+impl Display for ApkState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApkState::NotInstalled => write!(f, "Apk::NotInstalled"),
+            ApkState::Installed => write!(f, "Apk::Installed"),
+        }
+    }
+}
+
+impl_display_render!(ApkState);
+
+#[derive(Error, Debug)]
+pub enum ApkStateError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+
+    #[error("apk info failed: {stderr}")]
+    Query { stderr: String },
+}
+
+#[derive(Debug, Clone)]
+pub enum ApkChange {
+    Install { package: String },
+}
+
+impl Display for ApkChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApkChange::Install { package } => write!(f, "Apk::Install({package})"),
+        }
+    }
+}
+
+impl_display_render!(ApkChange);
+
+/// Which of the packages `apk info -e` was asked about are installed, from
+/// its output.
+///
+/// It lists each one installed, under the name it was asked for, and says
+/// nothing of the rest but exits with how many there were. Anything on
+/// stderr is apk's own failure.
+fn parse_installed(
+    succeeded: bool,
+    stdout: &str,
+    stderr: &str,
+) -> Result<HashSet<String>, ApkStateError> {
+    if !succeeded && !stderr.trim().is_empty() {
+        return Err(ApkStateError::Query {
+            stderr: stderr.trim().to_owned(),
+        });
+    }
+    Ok(stdout
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+#[derive(Debug, Clone)]
+pub struct Apk;
+
+#[async_trait]
+impl ResourceType for Apk {
+    const ID: &'static str = "apk";
+    const DESCRIPTION: &'static str = "Install Alpine packages with apk.";
+    const PLATFORMS: &'static [&'static str] = &["alpine"];
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[
+        ParamsDoc {
+            description: "One package.",
+            params: &[ParamDoc::required(
+                "package",
+                ParamDocType::String,
+                "Name of the package to install.",
+            )],
+        },
+        ParamsDoc {
+            description: "Many packages.",
+            params: &[ParamDoc::required(
+                "packages",
+                ParamDocType::StringList,
+                "Names of the packages to install.",
+            )],
+        },
+    ];
+
+    type Params = ApkParams;
+    type Resource = ApkResource;
+
+    fn resources(params: Self::Params) -> Vec<CausalityTree<Self::Resource>> {
+        let packages = match params {
+            ApkParams::Package { package } => vec![package],
+            ApkParams::Packages { packages } => packages,
+        };
+        packages
+            .into_iter()
+            .map(|package| CausalityTree::leaf(CausalityMeta::default(), ApkResource { package }))
+            .collect()
+    }
+
+    type State = ApkState;
+    type StateError = ApkStateError;
+    async fn state(
+        ctx: &mut Context,
+        resource: &Self::Resource,
+    ) -> Result<Self::State, Self::StateError> {
+        let mut states = Self::states_bulk(ctx, &[resource]).await?;
+        Ok(states.remove(0))
+    }
+
+    async fn states_bulk(
+        _ctx: &mut Context,
+        resources: &[&Self::Resource],
+    ) -> Result<Vec<Self::State>, Self::StateError> {
+        // With no packages, `apk info -e` would say nothing and succeed, but
+        // there's no need to run it.
+        if resources.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let outcome = Command::new("apk")
+            .arg("info")
+            .arg("-e")
+            .arg("--")
+            .args(resources.iter().map(|resource| &resource.package))
+            .outcome()
+            .await?;
+        let installed = parse_installed(
+            outcome.status.success(),
+            &String::from_utf8_lossy(&outcome.stdout),
+            &String::from_utf8_lossy(&outcome.stderr),
+        )?;
+
+        Ok(resources
+            .iter()
+            .map(|resource| {
+                if installed.contains(&resource.package) {
+                    ApkState::Installed
+                } else {
+                    ApkState::NotInstalled
+                }
+            })
+            .collect())
+    }
+
+    type Change = ApkChange;
+    fn change(resource: &Self::Resource, state: &Self::State) -> Option<Self::Change> {
+        match state {
+            ApkState::Installed => None,
+            ApkState::NotInstalled => Some(ApkChange::Install {
+                package: resource.package.clone(),
+            }),
+        }
+    }
+
+    fn operations(change: Self::Change) -> Vec<CausalityTree<Operation>> {
+        match change {
+            ApkChange::Install { package } => vec![CausalityTree::leaf(
+                CausalityMeta::default(),
+                Operation::Apk(ApkOperation::Install {
+                    packages: vec![package],
+                }),
+            )],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn installed_packages_are_the_ones_apk_info_lists() {
+        let installed = parse_installed(false, "curl\n", "").unwrap();
+        assert_eq!(installed, HashSet::from(["curl".to_owned()]));
+
+        let error = parse_installed(false, "", "ERROR: Unable to lock database\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "apk info failed: ERROR: Unable to lock database"
+        );
+    }
+}
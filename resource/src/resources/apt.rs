@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fmt::Display;
 
 use async_trait::async_trait;
@@ -8,11 +9,14 @@ use lusid_operation::{Operation, operations::apt::AptOperation};
 use lusid_params::{ParseError, ParseParams, StructFields};
 use lusid_view::impl_display_render;
 use rimu::{Spanned, Value};
+use serde::Serialize;
 use thiserror::Error;
 
 use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum AptParams {
     Package { package: String },
     Packages { packages: Vec<String> },
@@ -88,6 +92,68 @@ pub enum AptStateError {
 
     #[error("failed to parse status: {status}")]
     ParseStatus { status: String },
+
+    #[error("dpkg-query failed: {stderr}")]
+    Query { stderr: String },
+}
+
+/// Whether each `${binary:Package}` listed by `dpkg-query` is installed.
+/// That's the bare name for a native package, and `name:arch` for one of
+/// another architecture or that's co-installable across them.
+fn parse_installed(stdout: &str) -> Result<HashMap<&str, bool>, AptStateError> {
+    let mut installed = HashMap::new();
+    for line in stdout.lines() {
+        let Some((package, status)) = line.split_once('\t') else {
+            continue;
+        };
+        installed.insert(
+            package,
+            matches!(parse_status(status)?, AptState::Installed),
+        );
+    }
+    Ok(installed)
+}
+
+/// Whether `package`, as named in a plan, is installed.
+///
+/// Note(cc): a name with an `:arch` only matches that architecture, so
+/// `foo:i386` isn't installed just because `foo` is. A bare name also
+/// matches any `name:arch` line, as `dpkg-query -W libc6` lists `libc6:amd64`
+/// for a co-installable package.
+fn is_installed(installed: &HashMap<&str, bool>, package: &str) -> bool {
+    if installed.get(package) == Some(&true) {
+        return true;
+    }
+    if package.contains(':') {
+        return false;
+    }
+    installed.iter().any(|(listed, is_installed)| {
+        *is_installed
+            && listed
+                .split_once(':')
+                .is_some_and(|(name, _arch)| name == package)
+    })
+}
+
+/// An [`AptState`] from a `dpkg-query` `${Status}`: want, error flag and
+/// status, e.g. `install ok installed`.
+fn parse_status(status: &str) -> Result<AptState, AptStateError> {
+    let status_parts: Vec<_> = status.split(" ").collect();
+    let Some(current) = status_parts.get(2) else {
+        return Err(AptStateError::ParseStatus {
+            status: status.to_string(),
+        });
+    };
+    match *current {
+        "not-installed" => Ok(AptState::NotInstalled),
+        "unpacked" => Ok(AptState::NotInstalled),
+        "half-installed" => Ok(AptState::NotInstalled),
+        "installed" => Ok(AptState::Installed),
+        "config-files" => Ok(AptState::NotInstalled),
+        _ => Err(AptStateError::ParseStatus {
+            status: status.to_string(),
+        }),
+    }
 }
 
 // TODO(cc): add an `Uninstall` variant. Today a package can be declared but not
@@ -113,6 +179,26 @@ pub struct Apt;
 #[async_trait]
 impl ResourceType for Apt {
     const ID: &'static str = "apt";
+    const DESCRIPTION: &'static str = "Install Debian packages with apt-get.";
+    const PLATFORMS: &'static [&'static str] = &["debian", "ubuntu"];
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[
+        ParamsDoc {
+            description: "One package.",
+            params: &[ParamDoc::required(
+                "package",
+                ParamDocType::String,
+                "Name of the package to install.",
+            )],
+        },
+        ParamsDoc {
+            description: "Many packages.",
+            params: &[ParamDoc::required(
+                "packages",
+                ParamDocType::StringList,
+                "Names of the packages to install.",
+            )],
+        },
+    ];
 
     type Params = AptParams;
     type Resource = AptResource;
@@ -143,22 +229,7 @@ impl ResourceType for Apt {
             .handle(
                 |stdout| {
                     let stdout = String::from_utf8_lossy(stdout);
-                    let status_parts: Vec<_> = stdout.trim_matches('\'').split(" ").collect();
-                    let Some(status) = status_parts.get(2) else {
-                        return Err(AptStateError::ParseStatus {
-                            status: stdout.to_string(),
-                        });
-                    };
-                    match *status {
-                        "not-installed" => Ok(AptState::NotInstalled),
-                        "unpacked" => Ok(AptState::NotInstalled),
-                        "half-installed" => Ok(AptState::NotInstalled),
-                        "installed" => Ok(AptState::Installed),
-                        "config-files" => Ok(AptState::NotInstalled),
-                        _ => Err(AptStateError::ParseStatus {
-                            status: stdout.to_string(),
-                        }),
-                    }
+                    parse_status(stdout.trim_matches('\''))
                 },
                 |stderr| {
                     let stderr = String::from_utf8_lossy(stderr);
@@ -172,6 +243,43 @@ impl ResourceType for Apt {
             .await?
     }
 
+    async fn states_bulk(
+        _ctx: &mut Context,
+        resources: &[&Self::Resource],
+    ) -> Result<Vec<Self::State>, Self::StateError> {
+        // With no packages, `dpkg-query -W` would list every one installed.
+        if resources.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // One line per package found. Exits 1 if any weren't, still listing
+        // the rest; those are reported on stderr, and aren't installed.
+        let outcome = Command::new("dpkg-query")
+            .arg("-W")
+            .arg("--showformat=${binary:Package}\t${Status}\n")
+            .args(resources.iter().map(|resource| &resource.package))
+            .outcome()
+            .await?;
+        if !matches!(outcome.status.code(), Some(0 | 1)) {
+            return Err(AptStateError::Query {
+                stderr: String::from_utf8_lossy(&outcome.stderr).trim().to_owned(),
+            });
+        }
+
+        let stdout = String::from_utf8_lossy(&outcome.stdout);
+        let installed = parse_installed(&stdout)?;
+        Ok(resources
+            .iter()
+            .map(|resource| {
+                if is_installed(&installed, &resource.package) {
+                    AptState::Installed
+                } else {
+                    AptState::NotInstalled
+                }
+            })
+            .collect())
+    }
+
     type Change = AptChange;
     fn change(resource: &Self::Resource, state: &Self::State) -> Option<Self::Change> {
         match state {
@@ -201,3 +309,24 @@ impl ResourceType for Apt {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INSTALLED: &str = "install ok installed";
+    const REMOVED: &str = "deinstall ok config-files";
+
+    #[test]
+    fn keys_state_on_the_architecture() {
+        let stdout = format!("foo\t{INSTALLED}\nlibc6:amd64\t{INSTALLED}\nlibc6:i386\t{REMOVED}\n");
+        let installed = parse_installed(&stdout).unwrap();
+
+        assert!(is_installed(&installed, "foo"));
+        assert!(!is_installed(&installed, "foo:i386"));
+        assert!(is_installed(&installed, "libc6"));
+        assert!(is_installed(&installed, "libc6:amd64"));
+        assert!(!is_installed(&installed, "libc6:i386"));
+        assert!(!is_installed(&installed, "bar"));
+    }
+}
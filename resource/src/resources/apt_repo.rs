@@ -12,11 +12,13 @@ use lusid_operation::{
 use lusid_params::{ParseError, ParseParams, StructFields};
 use lusid_view::impl_display_render;
 use rimu::{Spanned, Value};
+use serde::Serialize;
 use thiserror::Error;
 
 use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
 
-const KEYRINGS_DIR: &str = "/etc/apt/keyrings";
+pub(crate) const KEYRINGS_DIR: &str = "/etc/apt/keyrings";
 const SOURCES_LIST_DIR: &str = "/etc/apt/sources.list.d";
 
 // TODO(cc): accept `String | List<String>` for `uris` / `suites` / `components`.
@@ -28,7 +30,7 @@ const SOURCES_LIST_DIR: &str = "/etc/apt/sources.list.d";
 // (`^[a-z0-9][a-z0-9._-]*$`). `name` is interpolated into `/etc/apt/keyrings/`
 // and `/etc/apt/sources.list.d/`, so a path-traversing value would let a plan
 // author write outside those directories.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct AptRepoParams {
     /// Filesystem-safe stem reused as the basename of the sources file
     /// (`<name>.sources`) and keyring (`<name>.asc`).
@@ -42,6 +44,10 @@ pub struct AptRepoParams {
 
     pub key_url: String,
 
+    /// Expected SHA-256 of the key. Pins it, so a key already in the host's
+    /// artifact cache is installed without downloading it again.
+    pub key_sha256: Option<String>,
+
     pub types: Option<Vec<String>>,
 
     pub architectures: Option<Vec<String>>,
@@ -57,6 +63,7 @@ impl ParseParams for AptRepoParams {
         let suites = fields.required_string_list("suites")?;
         let components = fields.required_string_list("components")?;
         let key_url = fields.required_string("key_url")?;
+        let key_sha256 = fields.optional_string("key_sha256")?;
         let types = fields.optional_string_list("types")?;
         let architectures = fields.optional_string_list("architectures")?;
         let enabled = fields.optional_bool("enabled")?;
@@ -67,6 +74,7 @@ impl ParseParams for AptRepoParams {
             suites,
             components,
             key_url,
+            key_sha256,
             types,
             architectures,
             enabled,
@@ -96,6 +104,7 @@ pub struct AptRepoResource {
     pub sources_path: FilePath,
     pub sources_content: String,
     pub key_url: String,
+    pub key_sha256: Option<String>,
     pub key_path: FilePath,
 }
 
@@ -150,7 +159,8 @@ pub enum AptRepoChange {
     Install {
         name: String,
         ensure_dir: bool,
-        key: Option<(String, FilePath)>,
+        /// The key's url, expected sha256 and path.
+        key: Option<(String, Option<String>, FilePath)>,
         sources: Option<(FilePath, String)>,
     },
 }
@@ -181,6 +191,51 @@ pub struct AptRepo;
 #[async_trait]
 impl ResourceType for AptRepo {
     const ID: &'static str = "apt-repo";
+    const DESCRIPTION: &'static str =
+        "Add an apt repository, as a deb822 sources file with its signing key.";
+    const PLATFORMS: &'static [&'static str] = &["debian", "ubuntu"];
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[ParamsDoc {
+        description: "A repository.",
+        params: &[
+            ParamDoc::required(
+                "name",
+                ParamDocType::String,
+                "Name of the sources and keyring files.",
+            ),
+            ParamDoc::required("uris", ParamDocType::StringList, "Repository URIs."),
+            ParamDoc::required(
+                "suites",
+                ParamDocType::StringList,
+                "Suites, e.g. `bookworm`.",
+            ),
+            ParamDoc::required(
+                "components",
+                ParamDocType::StringList,
+                "Components, e.g. `main`.",
+            ),
+            ParamDoc::required(
+                "key_url",
+                ParamDocType::String,
+                "URL of the repository's signing key.",
+            ),
+            ParamDoc::optional(
+                "key_sha256",
+                ParamDocType::String,
+                "Expected SHA-256 of the signing key. Lets a key in the host's artifact cache be reused.",
+            ),
+            ParamDoc::optional("types", ParamDocType::StringList, "Defaults to `deb`."),
+            ParamDoc::optional(
+                "architectures",
+                ParamDocType::StringList,
+                "Architectures to fetch, defaults to all.",
+            ),
+            ParamDoc::optional(
+                "enabled",
+                ParamDocType::Boolean,
+                "Whether apt uses the repository, defaults to true.",
+            ),
+        ],
+    }];
 
     type Params = AptRepoParams;
     type Resource = AptRepoResource;
@@ -192,6 +247,7 @@ impl ResourceType for AptRepo {
             suites,
             components,
             key_url,
+            key_sha256,
             types,
             architectures,
             enabled,
@@ -218,6 +274,7 @@ impl ResourceType for AptRepo {
                 sources_path,
                 sources_content,
                 key_url,
+                key_sha256,
                 key_path,
             },
         )]
@@ -263,7 +320,11 @@ impl ResourceType for AptRepo {
         let key = if key_present {
             None
         } else {
-            Some((resource.key_url.clone(), resource.key_path.clone()))
+            Some((
+                resource.key_url.clone(),
+                resource.key_sha256.clone(),
+                resource.key_path.clone(),
+            ))
         };
         let sources = if sources_matches {
             None
@@ -303,7 +364,7 @@ impl ResourceType for AptRepo {
                 }
 
                 let key_emitted = key.is_some();
-                if let Some((url, path)) = key {
+                if let Some((url, sha256, path)) = key {
                     let meta = CausalityMeta {
                         id: Some("key".into()),
                         requires: if ensure_dir {
@@ -318,6 +379,7 @@ impl ResourceType for AptRepo {
                         Operation::AptRepo(AptRepoOperation::DownloadKey {
                             name: name.clone(),
                             url,
+                            sha256,
                             path,
                         }),
                     ));
@@ -516,6 +578,7 @@ Enabled: no
             sources_path: sources_path(),
             sources_content: content.to_string(),
             key_url: "https://download.docker.com/linux/debian/gpg".into(),
+            key_sha256: None,
             key_path: key_path(),
         }
     }
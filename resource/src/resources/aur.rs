@@ -0,0 +1,272 @@
+//! `@core/aur`: Arch User Repository packages, built and installed with an
+//! AUR helper like paru or yay (see [`lusid_operation::operations::aur`]).
+//!
+//! makepkg refuses to run as root, so the helper runs as `user`, which needs
+//! passwordless sudo for the helper's own `pacman -U`. Installed AUR packages
+//! are in pacman's database like any other, so they're probed as
+//! [`Pacman`] probes its packages.
+
+use std::fmt::Display;
+
+use async_trait::async_trait;
+use lusid_causality::{CausalityMeta, CausalityTree};
+use lusid_ctx::Context;
+use lusid_operation::{Operation, operations::aur::AurOperation};
+use lusid_params::{ParseError, ParseParams, StructFields};
+use lusid_view::impl_display_render;
+use rimu::{Spanned, Value};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
+use crate::resources::pacman::{Pacman, PacmanResource, PacmanState, PacmanStateError};
+
+const DEFAULT_HELPER: &str = "paru";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AurParams {
+    pub packages: Vec<String>,
+    pub user: String,
+    pub helper: String,
+}
+
+impl ParseParams for AurParams {
+    fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
+        let mut fields = StructFields::new(value)?;
+        let packages = if fields.has("packages") {
+            fields.required_string_list("packages")?
+        } else {
+            vec![fields.required_string("package")?]
+        };
+        let user = fields.required_string("user")?;
+        let helper = fields
+            .optional_string("helper")?
+            .unwrap_or_else(|| DEFAULT_HELPER.to_owned());
+        fields.finish()?;
+        Ok(AurParams {
+            packages,
+            user,
+            helper,
+        })
+    }
+}
+
+impl Display for AurParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            packages,
+            user,
+            helper,
+        } = self;
+        write!(
+            f,
+            "Aur(packages = [{}], user = {user}, helper = {helper})",
+            packages.join(", ")
+        )
+    }
+}
+
+impl_display_render!(AurParams);
+
+#[derive(Debug, Clone)]
+pub struct AurResource {
+    pub package: String,
+    pub user: String,
+    pub helper: String,
+}
+
+impl Display for AurResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            package,
+            user,
+            helper,
+        } = self;
+        write!(f, "Aur({package}, user = {user}, helper = {helper})")
+    }
+}
+
+impl_display_render!(AurResource);
+
+#[derive(Debug, Clone)]
+pub enum AurState {
+    NotInstalled,
+    Installed,
+}
+
+impl Display for AurState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AurState::NotInstalled => write!(f, "Aur::NotInstalled"),
+            AurState::Installed => write!(f, "Aur::Installed"),
+        }
+    }
+}
+
+impl_display_render!(AurState);
+
+#[derive(Error, Debug)]
+pub enum AurStateError {
+    #[error(transparent)]
+    Pacman(#[from] PacmanStateError),
+}
+
+#[derive(Debug, Clone)]
+pub enum AurChange {
+    Install {
+        package: String,
+        user: String,
+        helper: String,
+    },
+}
+
+impl Display for AurChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AurChange::Install {
+                package,
+                user,
+                helper,
+            } => write!(
+                f,
+                "Aur::Install({package}, user = {user}, helper = {helper})"
+            ),
+        }
+    }
+}
+
+impl_display_render!(AurChange);
+
+#[derive(Debug, Clone)]
+pub struct Aur;
+
+#[async_trait]
+impl ResourceType for Aur {
+    const ID: &'static str = "aur";
+    const DESCRIPTION: &'static str =
+        "Build and install Arch User Repository packages with an AUR helper.";
+    const PLATFORMS: &'static [&'static str] = &["arch"];
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[
+        ParamsDoc {
+            description: "One package.",
+            params: &[
+                ParamDoc::required(
+                    "package",
+                    ParamDocType::String,
+                    "Name of the AUR package to install.",
+                ),
+                USER_DOC,
+                HELPER_DOC,
+            ],
+        },
+        ParamsDoc {
+            description: "Many packages.",
+            params: &[
+                ParamDoc::required(
+                    "packages",
+                    ParamDocType::StringList,
+                    "Names of the AUR packages to install.",
+                ),
+                USER_DOC,
+                HELPER_DOC,
+            ],
+        },
+    ];
+
+    type Params = AurParams;
+    type Resource = AurResource;
+
+    fn resources(params: Self::Params) -> Vec<CausalityTree<Self::Resource>> {
+        let AurParams {
+            packages,
+            user,
+            helper,
+        } = params;
+        packages
+            .into_iter()
+            .map(|package| {
+                CausalityTree::leaf(
+                    CausalityMeta::default(),
+                    AurResource {
+                        package,
+                        user: user.clone(),
+                        helper: helper.clone(),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    type State = AurState;
+    type StateError = AurStateError;
+    async fn state(
+        ctx: &mut Context,
+        resource: &Self::Resource,
+    ) -> Result<Self::State, Self::StateError> {
+        let mut states = Self::states_bulk(ctx, &[resource]).await?;
+        Ok(states.remove(0))
+    }
+
+    async fn states_bulk(
+        ctx: &mut Context,
+        resources: &[&Self::Resource],
+    ) -> Result<Vec<Self::State>, Self::StateError> {
+        let packages: Vec<PacmanResource> = resources
+            .iter()
+            .map(|resource| PacmanResource {
+                package: resource.package.clone(),
+            })
+            .collect();
+        let packages: Vec<&PacmanResource> = packages.iter().collect();
+        let states = Pacman::states_bulk(ctx, &packages).await?;
+        Ok(states
+            .into_iter()
+            .map(|state| match state {
+                PacmanState::NotInstalled => AurState::NotInstalled,
+                PacmanState::Installed => AurState::Installed,
+            })
+            .collect())
+    }
+
+    type Change = AurChange;
+    fn change(resource: &Self::Resource, state: &Self::State) -> Option<Self::Change> {
+        match state {
+            AurState::Installed => None,
+            AurState::NotInstalled => Some(AurChange::Install {
+                package: resource.package.clone(),
+                user: resource.user.clone(),
+                helper: resource.helper.clone(),
+            }),
+        }
+    }
+
+    fn operations(change: Self::Change) -> Vec<CausalityTree<Operation>> {
+        match change {
+            AurChange::Install {
+                package,
+                user,
+                helper,
+            } => vec![CausalityTree::leaf(
+                CausalityMeta::default(),
+                Operation::Aur(AurOperation::Install {
+                    helper,
+                    user,
+                    packages: vec![package],
+                }),
+            )],
+        }
+    }
+}
+
+const USER_DOC: ParamDoc = ParamDoc::required(
+    "user",
+    ParamDocType::String,
+    "User to build the packages as, with passwordless sudo for pacman. Not root.",
+);
+
+const HELPER_DOC: ParamDoc = ParamDoc::optional(
+    "helper",
+    ParamDocType::String,
+    "AUR helper to build and install with, taking pacman's `-S` flags, like `paru` or `yay`. Default: paru.",
+);
@@ -0,0 +1,342 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use async_trait::async_trait;
+use lusid_causality::{CausalityMeta, CausalityTree};
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_operation::{
+    Operation,
+    operations::brew::{self, BrewOperation},
+};
+use lusid_params::{ParseError, ParseParams, StructFields};
+use lusid_view::impl_display_render;
+use rimu::{Spanned, Value};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
+
+/// Whether a package is a formula (command-line software) or a cask (a macOS
+/// app).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BrewKind {
+    Formula,
+    Cask,
+}
+
+impl Display for BrewKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BrewKind::Formula => write!(f, "formula"),
+            BrewKind::Cask => write!(f, "cask"),
+        }
+    }
+}
+
+impl BrewKind {
+    /// `brew list`'s flag for this kind.
+    fn flag(&self) -> &'static str {
+        match self {
+            BrewKind::Formula => "--formula",
+            BrewKind::Cask => "--cask",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BrewParams {
+    Package {
+        package: String,
+        kind: BrewKind,
+    },
+    Packages {
+        packages: Vec<String>,
+        kind: BrewKind,
+    },
+}
+
+impl ParseParams for BrewParams {
+    fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
+        let mut fields = StructFields::new(value)?;
+        let kind = match fields.optional_bool("cask")? {
+            Some(true) => BrewKind::Cask,
+            Some(false) | None => BrewKind::Formula,
+        };
+        let out = if fields.has("packages") {
+            BrewParams::Packages {
+                packages: fields.required_string_list("packages")?,
+                kind,
+            }
+        } else {
+            BrewParams::Package {
+                package: fields.required_string("package")?,
+                kind,
+            }
+        };
+        fields.finish()?;
+        Ok(out)
+    }
+}
+
+impl BrewParams {
+    pub fn kind(&self) -> BrewKind {
+        match self {
+            BrewParams::Package { kind, .. } | BrewParams::Packages { kind, .. } => *kind,
+        }
+    }
+}
+
+impl Display for BrewParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BrewParams::Package { package, kind } => write!(f, "Brew({kind} = {package})"),
+            BrewParams::Packages { packages, kind } => {
+                let kinds = match kind {
+                    BrewKind::Formula => "formulae",
+                    BrewKind::Cask => "casks",
+                };
+                write!(f, "Brew({kinds} = [{}])", packages.join(", "))
+            }
+        }
+    }
+}
+
+impl_display_render!(BrewParams);
+
+#[derive(Debug, Clone)]
+pub struct BrewResource {
+    pub package: String,
+    pub kind: BrewKind,
+}
+
+impl Display for BrewResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self { package, kind } = self;
+        write!(f, "Brew({kind} {package})")
+    }
+}
+
+impl_display_render!(BrewResource);
+
+#[derive(Debug, Clone)]
+pub enum BrewState {
+    NotInstalled,
+    Installed,
+}
+
+impl Display for BrewState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BrewState::NotInstalled => write!(f, "Brew::NotInstalled"),
+            BrewState::Installed => write!(f, "Brew::Installed"),
+        }
+    }
+}
+
+impl_display_render!(BrewState);
+
+#[derive(Error, Debug)]
+pub enum BrewStateError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+
+    #[error("brew list {flag} failed: {stderr}")]
+    List { flag: &'static str, stderr: String },
+}
+
+// TODO(cc): add an `Uninstall` variant, as for apt and pacman.
+#[derive(Debug, Clone)]
+pub enum BrewChange {
+    Install { package: String, kind: BrewKind },
+}
+
+impl Display for BrewChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BrewChange::Install { package, kind } => write!(f, "Brew::Install({kind} {package})"),
+        }
+    }
+}
+
+impl_display_render!(BrewChange);
+
+#[derive(Debug, Clone)]
+pub struct Brew;
+
+#[async_trait]
+impl ResourceType for Brew {
+    const ID: &'static str = "brew";
+    const DESCRIPTION: &'static str =
+        "Install Homebrew packages: formulae on macOS and Linux, casks on macOS.";
+    // Casks are refused off macOS when planning, see `lusid_plan::core`.
+    const PLATFORMS: &'static [&'static str] = &["linux", "macos"];
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[
+        ParamsDoc {
+            description: "One package.",
+            params: &[
+                ParamDoc::required(
+                    "package",
+                    ParamDocType::String,
+                    "Name of the formula or cask to install.",
+                ),
+                ParamDoc::optional(
+                    "cask",
+                    ParamDocType::Boolean,
+                    "Whether it's a cask rather than a formula. Casks are macOS only. Defaults to false.",
+                ),
+            ],
+        },
+        ParamsDoc {
+            description: "Many packages.",
+            params: &[
+                ParamDoc::required(
+                    "packages",
+                    ParamDocType::StringList,
+                    "Names of the formulae or casks to install.",
+                ),
+                ParamDoc::optional(
+                    "cask",
+                    ParamDocType::Boolean,
+                    "Whether they're casks rather than formulae. Casks are macOS only. Defaults to false.",
+                ),
+            ],
+        },
+    ];
+
+    type Params = BrewParams;
+    type Resource = BrewResource;
+
+    fn resources(params: Self::Params) -> Vec<CausalityTree<Self::Resource>> {
+        match params {
+            BrewParams::Package { package, kind } => vec![CausalityTree::leaf(
+                CausalityMeta::default(),
+                BrewResource { package, kind },
+            )],
+            BrewParams::Packages { packages, kind } => packages
+                .into_iter()
+                .map(|package| {
+                    CausalityTree::leaf(CausalityMeta::default(), BrewResource { package, kind })
+                })
+                .collect(),
+        }
+    }
+
+    type State = BrewState;
+    type StateError = BrewStateError;
+
+    async fn state(
+        ctx: &mut Context,
+        resource: &Self::Resource,
+    ) -> Result<Self::State, Self::StateError> {
+        let mut states = Self::states_bulk(ctx, &[resource]).await?;
+        Ok(states.remove(0))
+    }
+
+    async fn states_bulk(
+        _ctx: &mut Context,
+        resources: &[&Self::Resource],
+    ) -> Result<Vec<Self::State>, Self::StateError> {
+        // Only ask about the kinds asked for: `brew list --cask` is slow.
+        let mut formulae = HashSet::new();
+        let mut casks = HashSet::new();
+        if resources.iter().any(|r| r.kind == BrewKind::Formula) {
+            formulae = list(BrewKind::Formula).await?;
+        }
+        if resources.iter().any(|r| r.kind == BrewKind::Cask) {
+            casks = list(BrewKind::Cask).await?;
+        }
+
+        Ok(resources
+            .iter()
+            .map(|resource| {
+                let installed = match resource.kind {
+                    BrewKind::Formula => &formulae,
+                    BrewKind::Cask => &casks,
+                };
+                if installed.contains(short_name(&resource.package)) {
+                    BrewState::Installed
+                } else {
+                    BrewState::NotInstalled
+                }
+            })
+            .collect())
+    }
+
+    type Change = BrewChange;
+
+    fn change(resource: &Self::Resource, state: &Self::State) -> Option<Self::Change> {
+        match state {
+            BrewState::Installed => None,
+            BrewState::NotInstalled => Some(BrewChange::Install {
+                package: resource.package.clone(),
+                kind: resource.kind,
+            }),
+        }
+    }
+
+    fn operations(change: Self::Change) -> Vec<CausalityTree<Operation>> {
+        let BrewChange::Install { package, kind } = change;
+        let operation = match kind {
+            BrewKind::Formula => BrewOperation::InstallFormulae {
+                formulae: vec![package],
+            },
+            BrewKind::Cask => BrewOperation::InstallCasks {
+                casks: vec![package],
+            },
+        };
+        vec![CausalityTree::leaf(
+            CausalityMeta::default(),
+            Operation::Brew(operation),
+        )]
+    }
+}
+
+/// The installed packages of `kind`, by short name.
+async fn list(kind: BrewKind) -> Result<HashSet<String>, BrewStateError> {
+    let outcome = Command::new(brew::program())
+        .args(["list", kind.flag(), "-1"])
+        .outcome()
+        .await?;
+    if !outcome.status.success() {
+        return Err(BrewStateError::List {
+            flag: kind.flag(),
+            stderr: String::from_utf8_lossy(&outcome.stderr).trim().to_owned(),
+        });
+    }
+    Ok(parse_list(&String::from_utf8_lossy(&outcome.stdout)))
+}
+
+fn parse_list(stdout: &str) -> HashSet<String> {
+    stdout
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// `brew list` names packages without their tap, so `homebrew/cask-fonts/
+/// font-fira-code` is listed as `font-fira-code`.
+// Note(cc): an alias (`python` for `python@3.12`) is listed under the name
+// it resolves to, so it reads as not installed and is installed again each
+// apply, which `brew install` does nothing for but warn.
+fn short_name(package: &str) -> &str {
+    package.rsplit('/').next().unwrap_or(package)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn installed_packages_are_matched_by_short_name() {
+        let installed = parse_list("git\nripgrep\n\nfont-fira-code\n");
+        assert!(installed.contains(short_name("ripgrep")));
+        assert!(installed.contains(short_name("homebrew/cask-fonts/font-fira-code")));
+        assert!(!installed.contains(short_name("neovim")));
+    }
+}
@@ -1,34 +1,90 @@
-use std::{fmt::Display, str::FromStr};
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
 
 use async_trait::async_trait;
 use lusid_causality::{CausalityMeta, CausalityTree};
 use lusid_cmd::{Command as RunCommand, CommandError as RunCommandError};
 use lusid_ctx::Context;
+use lusid_fs::{self as fs, FsError};
 use lusid_operation::{
     Operation,
-    operations::command::{CommandExecutor, CommandOperation},
+    operations::{
+        command::{CommandExecutor, CommandOperation},
+        file::FilePath,
+    },
 };
 use lusid_params::{ParseError, ParseParams, StructFields};
 use lusid_view::impl_display_render;
 use rimu::{Spanned, Value};
+use serde::Serialize;
 use thiserror::Error;
 
 use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
+use crate::resources::file::parse_file_path;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum CommandParams {
     Install {
         is_installed: Option<String>,
         install: String,
         uninstall: Option<String>,
+        guards: CommandGuards,
     },
     Uninstall {
         is_installed: Option<String>,
         install: Option<String>,
         uninstall: String,
+        guards: CommandGuards,
     },
 }
 
+/// Checks that decide whether a command is due, so applying a plan again
+/// doesn't run it again. `creates` joins `is_installed` in saying whether
+/// it's installed, while `only_if` and `unless` can skip it either way.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CommandGuards {
+    /// A path the install command creates: installed once it exists.
+    pub creates: Option<FilePath>,
+    /// Shell command that skips the command when it succeeds.
+    pub unless: Option<String>,
+    /// Shell command that skips the command unless it succeeds.
+    pub only_if: Option<String>,
+}
+
+impl CommandGuards {
+    fn parse(fields: &mut StructFields) -> Result<Self, Spanned<ParseError>> {
+        Ok(CommandGuards {
+            creates: fields.optional("creates", parse_file_path)?,
+            unless: fields.optional_string("unless")?,
+            only_if: fields.optional_string("only_if")?,
+        })
+    }
+}
+
+impl Display for CommandGuards {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            creates,
+            unless,
+            only_if,
+        } = self;
+        if let Some(creates) = creates {
+            write!(f, ", creates = {creates}")?;
+        }
+        if let Some(unless) = unless {
+            write!(f, ", unless = {unless:?}")?;
+        }
+        if let Some(only_if) = only_if {
+            write!(f, ", only_if = {only_if:?}")?;
+        }
+        Ok(())
+    }
+}
+
 impl ParseParams for CommandParams {
     fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
         let mut fields = StructFields::new(value)?;
@@ -38,11 +94,13 @@ impl ParseParams for CommandParams {
                 is_installed: fields.optional_string("is_installed")?,
                 install: fields.required_string("install")?,
                 uninstall: fields.optional_string("uninstall")?,
+                guards: CommandGuards::parse(&mut fields)?,
             },
             "uninstall" => CommandParams::Uninstall {
                 is_installed: fields.optional_string("is_installed")?,
                 install: fields.optional_string("install")?,
                 uninstall: fields.required_string("uninstall")?,
+                guards: CommandGuards::parse(&mut fields)?,
             },
             _ => unreachable!(),
         };
@@ -58,24 +116,26 @@ impl Display for CommandParams {
                 is_installed,
                 install,
                 uninstall,
+                guards,
             } => {
                 write!(
                     f,
                     "Command::Install(is_installed = {:?}, install = {}, uninstall = \
-                     {:?})",
-                    is_installed, install, uninstall
+                     {:?}{})",
+                    is_installed, install, uninstall, guards
                 )
             }
             CommandParams::Uninstall {
                 is_installed,
                 install,
                 uninstall,
+                guards,
             } => {
                 write!(
                     f,
                     "Command::Uninstall(is_installed = {:?}, install = {:?}, uninstall = \
-                     {})",
-                    is_installed, install, uninstall
+                     {}{})",
+                    is_installed, install, uninstall, guards
                 )
             }
         }
@@ -96,6 +156,7 @@ pub struct CommandResource {
     pub is_installed: Option<String>,
     pub install: Option<String>,
     pub uninstall: Option<String>,
+    pub guards: CommandGuards,
 }
 
 impl Display for CommandResource {
@@ -105,6 +166,7 @@ impl Display for CommandResource {
             is_installed,
             install,
             uninstall,
+            guards,
         } = self;
 
         let status = match status {
@@ -115,8 +177,8 @@ impl Display for CommandResource {
         write!(
             f,
             "Command::{status}(is_installed = {:?}, install = {:?}, uninstall \
-             = {:?})",
-            is_installed, install, uninstall
+             = {:?}{})",
+            is_installed, install, uninstall, guards
         )
     }
 }
@@ -128,6 +190,10 @@ pub enum CommandState {
     Installed,
     NotInstalled,
     Unknown,
+    /// An `only_if` or `unless` guard says not to run the command.
+    Skipped {
+        guard: String,
+    },
 }
 
 impl Display for CommandState {
@@ -136,6 +202,7 @@ impl Display for CommandState {
             CommandState::NotInstalled => write!(f, "Command::NotInstalled"),
             CommandState::Installed => write!(f, "Command::Installed"),
             CommandState::Unknown => write!(f, "Command::Unknown"),
+            CommandState::Skipped { guard } => write!(f, "Command::Skipped({guard})"),
         }
     }
 }
@@ -147,6 +214,9 @@ pub enum CommandStateError {
     #[error(transparent)]
     Command(#[from] RunCommandError),
 
+    #[error(transparent)]
+    Fs(#[from] FsError),
+
     #[error("failed to parse command: {0}")]
     ParseCommand(#[source] <RunCommand as FromStr>::Err),
 }
@@ -168,12 +238,72 @@ impl Display for CommandChange {
 
 impl_display_render!(CommandChange);
 
+const CREATES_DOC: ParamDoc = ParamDoc::optional(
+    "creates",
+    ParamDocType::TargetPath,
+    "Path the install creates: installed once it exists.",
+);
+const UNLESS_DOC: ParamDoc = ParamDoc::optional(
+    "unless",
+    ParamDocType::String,
+    "Shell command that skips this one when it succeeds.",
+);
+const ONLY_IF_DOC: ParamDoc = ParamDoc::optional(
+    "only_if",
+    ParamDocType::String,
+    "Shell command that skips this one unless it succeeds.",
+);
+
 #[derive(Debug, Clone)]
 pub struct Command;
 
 #[async_trait]
 impl ResourceType for Command {
     const ID: &'static str = "command";
+    const DESCRIPTION: &'static str = "Run shell commands to install or uninstall something.";
+    const PLATFORMS: &'static [&'static str] = &["linux", "macos"];
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[
+        ParamsDoc {
+            description: "Installed.",
+            params: &[
+                ParamDoc::required("status", ParamDocType::Literal("install"), "Install."),
+                ParamDoc::required("install", ParamDocType::String, "Command that installs."),
+                ParamDoc::optional(
+                    "is_installed",
+                    ParamDocType::String,
+                    "Command that exits successfully when already installed.",
+                ),
+                ParamDoc::optional(
+                    "uninstall",
+                    ParamDocType::String,
+                    "Command that uninstalls.",
+                ),
+                CREATES_DOC,
+                UNLESS_DOC,
+                ONLY_IF_DOC,
+            ],
+        },
+        ParamsDoc {
+            description: "Uninstalled.",
+            params: &[
+                ParamDoc::required("status", ParamDocType::Literal("uninstall"), "Uninstall."),
+                ParamDoc::required(
+                    "uninstall",
+                    ParamDocType::String,
+                    "Command that uninstalls.",
+                ),
+                ParamDoc::optional(
+                    "is_installed",
+                    ParamDocType::String,
+                    "Command that exits successfully when installed.",
+                ),
+                ParamDoc::optional("install", ParamDocType::String, "Command that installs."),
+                CREATES_DOC,
+                UNLESS_DOC,
+                ONLY_IF_DOC,
+            ],
+        },
+    ];
 
     type Params = CommandParams;
     type Resource = CommandResource;
@@ -184,21 +314,25 @@ impl ResourceType for Command {
                 is_installed,
                 install,
                 uninstall,
+                guards,
             } => CommandResource {
                 status: CommandStatus::Install,
                 is_installed,
                 install: Some(install),
                 uninstall,
+                guards,
             },
             CommandParams::Uninstall {
                 is_installed,
                 install,
                 uninstall,
+                guards,
             } => CommandResource {
                 status: CommandStatus::Uninstall,
                 is_installed,
                 install,
                 uninstall: Some(uninstall),
+                guards,
             },
         };
 
@@ -212,24 +346,62 @@ impl ResourceType for Command {
         _ctx: &mut Context,
         resource: &Self::Resource,
     ) -> Result<Self::State, Self::StateError> {
-        let Some(ref is_installed) = resource.is_installed else {
-            return Ok(CommandState::Unknown);
-        };
+        let CommandGuards {
+            creates,
+            unless,
+            only_if,
+        } = &resource.guards;
+
+        if let Some(only_if) = only_if {
+            let passed = shell_succeeds(only_if).await?;
+            if !passed {
+                return Ok(CommandState::Skipped {
+                    guard: format!("only_if = {only_if:?} failed"),
+                });
+            }
+        }
+        if let Some(unless) = unless {
+            let passed = shell_succeeds(unless).await?;
+            if passed {
+                return Ok(CommandState::Skipped {
+                    guard: format!("unless = {unless:?} succeeded"),
+                });
+            }
+        }
 
-        if is_installed.trim().is_empty() {
-            return Ok(CommandState::Unknown);
-        };
+        let is_installed = resource
+            .is_installed
+            .as_ref()
+            .filter(|is_installed| !is_installed.trim().is_empty());
 
-        let mut cmd =
-            RunCommand::from_str(is_installed).map_err(CommandStateError::ParseCommand)?;
-        let output = cmd.output().await?;
-        let status = output.status.await?;
-        let state = if status.success() {
-            CommandState::Installed
-        } else {
-            CommandState::NotInstalled
-        };
-        Ok(state)
+        if creates.is_none() && is_installed.is_none() {
+            if only_if.is_none() && unless.is_none() {
+                return Ok(CommandState::Unknown);
+            }
+            // A passing `only_if` or `unless`, on its own, says the command is
+            // due.
+            return Ok(match resource.status {
+                CommandStatus::Install => CommandState::NotInstalled,
+                CommandStatus::Uninstall => CommandState::Installed,
+            });
+        }
+
+        // Installed when every check given says so.
+        if let Some(creates) = creates {
+            let created = fs::path_exists(creates.as_path()).await?;
+            if !created {
+                return Ok(CommandState::NotInstalled);
+            }
+        }
+        if let Some(is_installed) = is_installed {
+            let mut cmd =
+                RunCommand::from_str(is_installed).map_err(CommandStateError::ParseCommand)?;
+            let output = cmd.output().await?;
+            if !output.status.await?.success() {
+                return Ok(CommandState::NotInstalled);
+            }
+        }
+        Ok(CommandState::Installed)
     }
 
     type Change = CommandChange;
@@ -247,6 +419,7 @@ impl ResourceType for Command {
                 .clone()
                 .map(|command| CommandChange::Uninstall { command }),
             (_, CommandState::Unknown) => None,
+            (_, CommandState::Skipped { .. }) => None,
         }
     }
 
@@ -264,3 +437,103 @@ impl ResourceType for Command {
         }
     }
 }
+
+/// Whether the shell command `command` exits successfully.
+async fn shell_succeeds(command: &str) -> Result<bool, CommandStateError> {
+    let output = RunCommand::new_sh(command).output().await?;
+    Ok(output.status.await?.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn command(
+        status: CommandStatus,
+        is_installed: Option<&str>,
+        guards: CommandGuards,
+    ) -> CommandResource {
+        CommandResource {
+            status,
+            is_installed: is_installed.map(Into::into),
+            install: Some("touch /tmp/installed".into()),
+            uninstall: Some("rm /tmp/installed".into()),
+            guards,
+        }
+    }
+
+    async fn state(resource: &CommandResource) -> CommandState {
+        let dir = tempdir().unwrap();
+        let mut ctx = lusid_ctx::Context::create(dir.path()).unwrap();
+        Command::state(&mut ctx, resource).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn only_if_is_checked_before_unless() {
+        let guards = CommandGuards {
+            only_if: Some("false".into()),
+            unless: Some("true".into()),
+            ..CommandGuards::default()
+        };
+        let state = state(&command(CommandStatus::Install, None, guards)).await;
+        let CommandState::Skipped { guard } = state else {
+            panic!("expected Skipped, got {state:?}");
+        };
+        assert_eq!(guard, "only_if = \"false\" failed");
+
+        let guards = CommandGuards {
+            only_if: Some("true".into()),
+            unless: Some("true".into()),
+            ..CommandGuards::default()
+        };
+        let state = state(&command(CommandStatus::Install, None, guards)).await;
+        let CommandState::Skipped { guard } = state else {
+            panic!("expected Skipped, got {state:?}");
+        };
+        assert_eq!(guard, "unless = \"true\" succeeded");
+    }
+
+    #[tokio::test]
+    async fn passing_guards_on_their_own_make_the_command_due() {
+        let guards = CommandGuards {
+            unless: Some("false".into()),
+            ..CommandGuards::default()
+        };
+        let install = command(CommandStatus::Install, None, guards.clone());
+        assert!(matches!(state(&install).await, CommandState::NotInstalled));
+        let uninstall = command(CommandStatus::Uninstall, None, guards);
+        assert!(matches!(state(&uninstall).await, CommandState::Installed));
+
+        let unguarded = command(CommandStatus::Install, None, CommandGuards::default());
+        assert!(matches!(state(&unguarded).await, CommandState::Unknown));
+    }
+
+    #[tokio::test]
+    async fn creates_and_is_installed_must_both_say_installed() {
+        let dir = tempdir().unwrap();
+        let created = dir.path().join("created");
+        let guards = CommandGuards {
+            creates: Some(FilePath::new(created.to_string_lossy().into_owned())),
+            ..CommandGuards::default()
+        };
+
+        let missing = command(CommandStatus::Install, Some("true"), guards.clone());
+        assert!(matches!(state(&missing).await, CommandState::NotInstalled));
+
+        tokio::fs::write(&created, b"").await.unwrap();
+        let installed = command(CommandStatus::Install, Some("true"), guards.clone());
+        assert!(matches!(state(&installed).await, CommandState::Installed));
+        let not_installed = command(CommandStatus::Install, Some("false"), guards.clone());
+        assert!(matches!(
+            state(&not_installed).await,
+            CommandState::NotInstalled
+        ));
+        let creates_only = command(CommandStatus::Install, None, guards);
+        assert!(matches!(
+            state(&creates_only).await,
+            CommandState::Installed
+        ));
+    }
+}
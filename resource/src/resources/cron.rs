@@ -0,0 +1,369 @@
+//! `@core/cron`: one named crontab entry.
+//!
+//! Each entry is found in `crontab -l` by the marker comment naming it (see
+//! [`lusid_operation::operations::cron`]), and set or removed on its own, so
+//! other entries and hand-written jobs in the same crontab are untouched.
+//!
+//! Note(cc): for a systemd timer, declare the `.timer` and `.service` units
+//! with `@core/systemd-unit` and enable the timer; that already tracks the
+//! unit files and reloads systemd when they change.
+
+use std::fmt::Display;
+
+use async_trait::async_trait;
+use lusid_causality::{CausalityMeta, CausalityTree};
+use lusid_ctx::Context;
+use lusid_operation::{
+    Operation,
+    operations::cron::{CronApplyError, CronOperation, find_entry, read_crontab},
+};
+use lusid_params::{ParseError, ParseParams, StructFields};
+use lusid_view::impl_display_render;
+use rimu::{Spanned, Value};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
+
+// TODO(cc): reject a `name`, `schedule` or `command` with a newline at
+// param-time. An entry is a marker line plus one job line, so a newline would
+// split the job and the next run would see it as changed.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CronParams {
+    Present {
+        name: String,
+        /// Five cron fields (`0 3 * * *`) or a nickname (`@daily`).
+        schedule: String,
+        command: String,
+        /// Whose crontab; the apply user's when `None`.
+        user: Option<String>,
+    },
+    Absent {
+        name: String,
+        user: Option<String>,
+    },
+}
+
+impl ParseParams for CronParams {
+    fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
+        let mut fields = StructFields::new(value)?;
+        let state = fields.take_discriminator("state", &["present", "absent"])?;
+        let out = match state {
+            "present" => CronParams::Present {
+                name: fields.required_string("name")?,
+                schedule: fields.required_string("schedule")?,
+                command: fields.required_string("command")?,
+                user: fields.optional_string("user")?,
+            },
+            "absent" => CronParams::Absent {
+                name: fields.required_string("name")?,
+                user: fields.optional_string("user")?,
+            },
+            _ => unreachable!(),
+        };
+        fields.finish()?;
+        Ok(out)
+    }
+}
+
+impl Display for CronParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CronParams::Present {
+                name,
+                schedule,
+                command,
+                user,
+            } => write!(
+                f,
+                "Cron::Present(name = {name}, schedule = {schedule}, command = {command}{})",
+                User(user)
+            ),
+            CronParams::Absent { name, user } => {
+                write!(f, "Cron::Absent(name = {name}{})", User(user))
+            }
+        }
+    }
+}
+
+impl_display_render!(CronParams);
+
+#[derive(Debug, Clone)]
+pub enum CronResource {
+    Present {
+        name: String,
+        /// The entry's job line: schedule, then command.
+        job: String,
+        user: Option<String>,
+    },
+    Absent {
+        name: String,
+        user: Option<String>,
+    },
+}
+
+impl Display for CronResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CronResource::Present { name, job, user } => {
+                write!(f, "Cron::Present(name = {name}, job = {job}{})", User(user))
+            }
+            CronResource::Absent { name, user } => {
+                write!(f, "Cron::Absent(name = {name}{})", User(user))
+            }
+        }
+    }
+}
+
+impl_display_render!(CronResource);
+
+#[derive(Debug, Clone)]
+pub enum CronState {
+    Absent,
+    Present { job: String },
+}
+
+impl Display for CronState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CronState::Absent => write!(f, "Cron::Absent"),
+            CronState::Present { job } => write!(f, "Cron::Present(job = {job})"),
+        }
+    }
+}
+
+impl_display_render!(CronState);
+
+#[derive(Error, Debug)]
+pub enum CronStateError {
+    #[error(transparent)]
+    Crontab(#[from] CronApplyError),
+}
+
+#[derive(Debug, Clone)]
+pub enum CronChange {
+    /// Add the entry, or replace its job line. `before` is the job line it
+    /// replaces, if any.
+    Set {
+        name: String,
+        job: String,
+        user: Option<String>,
+        before: Option<String>,
+    },
+    Remove {
+        name: String,
+        user: Option<String>,
+    },
+}
+
+impl Display for CronChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CronChange::Set {
+                name,
+                job,
+                user,
+                before,
+            } => {
+                write!(f, "Cron::Set(name = {name}, job = {job}{}", User(user))?;
+                if let Some(before) = before {
+                    write!(f, ", was {before}")?;
+                }
+                write!(f, ")")
+            }
+            CronChange::Remove { name, user } => {
+                write!(f, "Cron::Remove(name = {name}{})", User(user))
+            }
+        }
+    }
+}
+
+impl_display_render!(CronChange);
+
+/// Formats an entry's user as a trailing `, user = <user>` argument, or
+/// nothing for the apply user.
+struct User<'a>(&'a Option<String>);
+
+impl Display for User<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(user) => write!(f, ", user = {user}"),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cron;
+
+#[async_trait]
+impl ResourceType for Cron {
+    const ID: &'static str = "cron";
+    const DESCRIPTION: &'static str = "Manage a named entry in a user's crontab.";
+    const PLATFORMS: &'static [&'static str] = &["linux", "macos"];
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[
+        ParamsDoc {
+            description: "Present.",
+            params: &[
+                ParamDoc::required("state", ParamDocType::Literal("present"), "Present."),
+                ParamDoc::required(
+                    "name",
+                    ParamDocType::String,
+                    "Entry name, marking the job in the crontab.",
+                ),
+                ParamDoc::required(
+                    "schedule",
+                    ParamDocType::String,
+                    "Five cron fields, e.g. `0 3 * * *`, or a nickname, e.g. `@daily`.",
+                ),
+                ParamDoc::required("command", ParamDocType::String, "Command to run."),
+                ParamDoc::optional(
+                    "user",
+                    ParamDocType::String,
+                    "Whose crontab, defaults to the apply user's.",
+                ),
+            ],
+        },
+        ParamsDoc {
+            description: "Removed.",
+            params: &[
+                ParamDoc::required("state", ParamDocType::Literal("absent"), "Absent."),
+                ParamDoc::required("name", ParamDocType::String, "Entry name."),
+                ParamDoc::optional(
+                    "user",
+                    ParamDocType::String,
+                    "Whose crontab, defaults to the apply user's.",
+                ),
+            ],
+        },
+    ];
+
+    type Params = CronParams;
+    type Resource = CronResource;
+
+    fn resources(params: Self::Params) -> Vec<CausalityTree<Self::Resource>> {
+        let resource = match params {
+            CronParams::Present {
+                name,
+                schedule,
+                command,
+                user,
+            } => CronResource::Present {
+                name,
+                job: format!("{} {}", schedule.trim(), command.trim()),
+                user,
+            },
+            CronParams::Absent { name, user } => CronResource::Absent { name, user },
+        };
+        vec![CausalityTree::leaf(CausalityMeta::default(), resource)]
+    }
+
+    type State = CronState;
+    type StateError = CronStateError;
+
+    async fn state(
+        _ctx: &mut Context,
+        resource: &Self::Resource,
+    ) -> Result<Self::State, Self::StateError> {
+        let (name, user) = match resource {
+            CronResource::Present { name, user, .. } | CronResource::Absent { name, user } => {
+                (name, user)
+            }
+        };
+        let crontab = read_crontab(user.as_deref()).await?;
+        Ok(match find_entry(&crontab, name) {
+            Some(job) => CronState::Present {
+                job: job.to_owned(),
+            },
+            None => CronState::Absent,
+        })
+    }
+
+    type Change = CronChange;
+
+    fn change(resource: &Self::Resource, state: &Self::State) -> Option<Self::Change> {
+        match (resource, state) {
+            (CronResource::Present { job, .. }, CronState::Present { job: current })
+                if job == current =>
+            {
+                None
+            }
+            (CronResource::Present { name, job, user }, state) => Some(CronChange::Set {
+                name: name.clone(),
+                job: job.clone(),
+                user: user.clone(),
+                before: match state {
+                    CronState::Present { job } => Some(job.clone()),
+                    CronState::Absent => None,
+                },
+            }),
+            (CronResource::Absent { .. }, CronState::Absent) => None,
+            (CronResource::Absent { name, user }, CronState::Present { .. }) => {
+                Some(CronChange::Remove {
+                    name: name.clone(),
+                    user: user.clone(),
+                })
+            }
+        }
+    }
+
+    fn operations(change: Self::Change) -> Vec<CausalityTree<Operation>> {
+        let operation = match change {
+            CronChange::Set {
+                name, job, user, ..
+            } => CronOperation::Set { user, name, job },
+            CronChange::Remove { name, user } => CronOperation::Remove { user, name },
+        };
+        vec![CausalityTree::leaf(
+            CausalityMeta::default(),
+            Operation::Cron(operation),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn present() -> CronResource {
+        let params = CronParams::Present {
+            name: "backup".into(),
+            schedule: "0 3 * * *".into(),
+            command: "/usr/local/bin/backup".into(),
+            user: None,
+        };
+        match Cron::resources(params).pop() {
+            Some(CausalityTree::Leaf { node, .. }) => node,
+            _ => panic!("expected leaf"),
+        }
+    }
+
+    #[test]
+    fn matching_job_is_unchanged() {
+        let state = CronState::Present {
+            job: "0 3 * * * /usr/local/bin/backup".into(),
+        };
+        assert!(Cron::change(&present(), &state).is_none());
+    }
+
+    #[test]
+    fn changed_job_is_set_in_place() {
+        let state = CronState::Present {
+            job: "0 4 * * * /usr/local/bin/backup".into(),
+        };
+        let change = Cron::change(&present(), &state).expect("change");
+        assert_eq!(
+            change.to_string(),
+            "Cron::Set(name = backup, job = 0 3 * * * /usr/local/bin/backup, was 0 4 * * * /usr/local/bin/backup)"
+        );
+        let absent = CronResource::Absent {
+            name: "backup".into(),
+            user: Some("me".into()),
+        };
+        let change = Cron::change(&absent, &state).expect("change");
+        assert_eq!(change.to_string(), "Cron::Remove(name = backup, user = me)");
+        assert!(Cron::change(&absent, &CronState::Absent).is_none());
+    }
+}
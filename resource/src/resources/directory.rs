@@ -14,23 +14,30 @@ use lusid_operation::{
 use lusid_params::{ParseError, ParseParams, StructFields};
 use lusid_view::impl_display_render;
 use rimu::{Span, Spanned, Value};
+use serde::Serialize;
 use thiserror::Error;
 
 use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
+use crate::resources::file::{
+    GROUP_DOC, MODE_DOC, PATH_DOC, SOURCE_DOC, USER_DOC, parse_file_path,
+};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum DirectoryParams {
     /// Recursive copy of the directory tree at `source` into `path`. Edits
     /// to `source` only propagate on the next apply. The state probe is
     /// intentionally weak (existence-as-directory at `path` ⇒ `Sourced`);
     /// content drift in `source` after first apply is not detected — declare
     /// `state: "absent"` and re-apply to force a refresh.
-    /// Note(cc): a content-aware recursive diff is a follow-up; see Salt's
-    /// `file.recurse`.
+    /// For a per-file diff, see `@core/file`'s
+    /// [`FileParams::Synced`](super::file::FileParams::Synced).
     Sourced {
         source: FilePath,
         /// Span of the `source` value in the plan source. Carried so
         /// host-path validation errors can point at the offending line.
+        #[serde(skip)]
         source_span: Span,
         path: FilePath,
         mode: Option<FileMode>,
@@ -49,6 +56,7 @@ pub enum DirectoryParams {
         source: FilePath,
         /// Span of the `source` value in the plan source. See
         /// [`DirectoryParams::Sourced::source_span`] for rationale.
+        #[serde(skip)]
         source_span: Span,
         path: FilePath,
     },
@@ -76,7 +84,7 @@ impl ParseParams for DirectoryParams {
                 DirectoryParams::Sourced {
                     source: FilePath::new(source_path.to_string_lossy().into_owned()),
                     source_span,
-                    path: FilePath::new(fields.required_target_path("path")?),
+                    path: fields.required("path", parse_file_path)?,
                     mode: fields.optional_u32("mode")?.map(FileMode::new),
                     user: fields.optional_string("user")?.map(FileUser::new),
                     group: fields.optional_string("group")?.map(FileGroup::new),
@@ -88,17 +96,17 @@ impl ParseParams for DirectoryParams {
                 DirectoryParams::Linked {
                     source: FilePath::new(source_path.to_string_lossy().into_owned()),
                     source_span,
-                    path: FilePath::new(fields.required_target_path("path")?),
+                    path: fields.required("path", parse_file_path)?,
                 }
             }
             "present" => DirectoryParams::Present {
-                path: FilePath::new(fields.required_target_path("path")?),
+                path: fields.required("path", parse_file_path)?,
                 mode: fields.optional_u32("mode")?.map(FileMode::new),
                 user: fields.optional_string("user")?.map(FileUser::new),
                 group: fields.optional_string("group")?.map(FileGroup::new),
             },
             "absent" => DirectoryParams::Absent {
-                path: FilePath::new(fields.required_target_path("path")?),
+                path: fields.required("path", parse_file_path)?,
             },
             _ => unreachable!(),
         };
@@ -275,6 +283,47 @@ pub struct Directory;
 #[async_trait]
 impl ResourceType for Directory {
     const ID: &'static str = "directory";
+    const DESCRIPTION: &'static str =
+        "Manage a directory: its contents, or a symlink, mode and owner.";
+    const PLATFORMS: &'static [&'static str] = &["linux", "macos"];
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[
+        ParamsDoc {
+            description: "Copied from a directory next to the plan.",
+            params: &[
+                ParamDoc::required("state", ParamDocType::Literal("sourced"), "Sourced."),
+                SOURCE_DOC,
+                PATH_DOC,
+                MODE_DOC,
+                USER_DOC,
+                GROUP_DOC,
+            ],
+        },
+        ParamsDoc {
+            description: "A symlink to a directory next to the plan.",
+            params: &[
+                ParamDoc::required("state", ParamDocType::Literal("linked"), "Linked."),
+                SOURCE_DOC,
+                PATH_DOC,
+            ],
+        },
+        ParamsDoc {
+            description: "Present, with whatever contents it has.",
+            params: &[
+                ParamDoc::required("state", ParamDocType::Literal("present"), "Present."),
+                PATH_DOC,
+                MODE_DOC,
+                USER_DOC,
+                GROUP_DOC,
+            ],
+        },
+        ParamsDoc {
+            description: "Removed.",
+            params: &[
+                ParamDoc::required("state", ParamDocType::Literal("absent"), "Absent."),
+                PATH_DOC,
+            ],
+        },
+    ];
 
     type Params = DirectoryParams;
     type Resource = DirectoryResource;
@@ -417,7 +466,6 @@ impl ResourceType for Directory {
                     DirectoryState::UserIncorrect
                 } else {
                     let actual_user = fs::get_owner_user(path.as_path()).await?;
-                    let actual_user = actual_user.map(|u| u.name.to_string());
                     if actual_user.as_deref() == Some(user.as_str()) {
                         DirectoryState::UserCorrect
                     } else {
@@ -431,7 +479,6 @@ impl ResourceType for Directory {
                     DirectoryState::GroupIncorrect
                 } else {
                     let actual_group = fs::get_owner_group(path.as_path()).await?;
-                    let actual_group = actual_group.map(|g| g.name.to_string());
                     if actual_group.as_deref() == Some(group.as_str()) {
                         DirectoryState::GroupCorrect
                     } else {
@@ -0,0 +1,318 @@
+//! `@core/dnf`: Fedora and RHEL packages, installed or removed with dnf
+//! (see [`lusid_operation::operations::dnf`]).
+//!
+//! Each package is its own atom, probed together with one `rpm -q`, and an
+//! epoch's installs and removals each merge into one `dnf` command.
+
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use async_trait::async_trait;
+use lusid_causality::{CausalityMeta, CausalityTree};
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_operation::{Operation, operations::dnf::DnfOperation};
+use lusid_params::{ParseError, ParseParams, StructFields};
+use lusid_view::impl_display_render;
+use rimu::{Spanned, Value};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DnfParams {
+    pub packages: Vec<String>,
+    pub present: bool,
+}
+
+impl ParseParams for DnfParams {
+    fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
+        let mut fields = StructFields::new(value)?;
+        // As for apt, `packages` wins over `package` when present.
+        let packages = if fields.has("packages") {
+            fields.required_string_list("packages")?
+        } else {
+            vec![fields.required_string("package")?]
+        };
+        let present = if fields.has("state") {
+            fields.take_discriminator("state", &["present", "absent"])? == "present"
+        } else {
+            true
+        };
+        fields.finish()?;
+        Ok(DnfParams { packages, present })
+    }
+}
+
+impl Display for DnfParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self { packages, present } = self;
+        let packages = packages.join(", ");
+        if *present {
+            write!(f, "Dnf(packages = [{packages}])")
+        } else {
+            write!(f, "Dnf::Absent(packages = [{packages}])")
+        }
+    }
+}
+
+impl_display_render!(DnfParams);
+
+#[derive(Debug, Clone)]
+pub enum DnfResource {
+    Present { package: String },
+    Absent { package: String },
+}
+
+impl DnfResource {
+    pub fn package(&self) -> &str {
+        match self {
+            DnfResource::Present { package } | DnfResource::Absent { package } => package,
+        }
+    }
+}
+
+impl Display for DnfResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DnfResource::Present { package } => write!(f, "Dnf({package})"),
+            DnfResource::Absent { package } => write!(f, "Dnf::Absent({package})"),
+        }
+    }
+}
+
+impl_display_render!(DnfResource);
+
+#[derive(Debug, Clone)]
+pub enum DnfState {
+    NotInstalled,
+    Installed,
+}
+
+impl Display for DnfState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DnfState::NotInstalled => write!(f, "Dnf::NotInstalled"),
+            DnfState::Installed => write!(f, "Dnf::Installed"),
+        }
+    }
+}
+
+impl_display_render!(DnfState);
+
+#[derive(Error, Debug)]
+pub enum DnfStateError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+
+    #[error("rpm -q failed: {output}")]
+    Query { output: String },
+}
+
+#[derive(Debug, Clone)]
+pub enum DnfChange {
+    Install { package: String },
+    Remove { package: String },
+}
+
+impl Display for DnfChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DnfChange::Install { package } => write!(f, "Dnf::Install({package})"),
+            DnfChange::Remove { package } => write!(f, "Dnf::Remove({package})"),
+        }
+    }
+}
+
+impl_display_render!(DnfChange);
+
+/// Which of `packages` aren't installed, from `rpm -q`'s output for them.
+///
+/// rpm lists each package it has, and says `package <name> is not installed`
+/// on stdout for each it hasn't, exiting with how many that was.
+fn parse_missing<'a>(
+    packages: impl IntoIterator<Item = &'a str>,
+    succeeded: bool,
+    stdout: &str,
+    stderr: &str,
+) -> Result<HashSet<&'a str>, DnfStateError> {
+    let not_installed: HashSet<&str> = stdout
+        .lines()
+        .filter_map(|line| {
+            line.strip_prefix("package ")
+                .and_then(|rest| rest.strip_suffix(" is not installed"))
+        })
+        .collect();
+    // A failure without any missing package, or with anything on stderr, is
+    // rpm's own.
+    if !succeeded && (not_installed.is_empty() || !stderr.trim().is_empty()) {
+        return Err(DnfStateError::Query {
+            output: format!("{}\n{}", stdout.trim(), stderr.trim())
+                .trim()
+                .to_owned(),
+        });
+    }
+    Ok(packages
+        .into_iter()
+        .filter(|package| not_installed.contains(package))
+        .collect())
+}
+
+#[derive(Debug, Clone)]
+pub struct Dnf;
+
+#[async_trait]
+impl ResourceType for Dnf {
+    const ID: &'static str = "dnf";
+    const DESCRIPTION: &'static str = "Install or remove Fedora and RHEL packages with dnf.";
+    const PLATFORMS: &'static [&'static str] = &["fedora", "rhel"];
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[
+        ParamsDoc {
+            description: "One package.",
+            params: &[
+                ParamDoc::required(
+                    "package",
+                    ParamDocType::String,
+                    "Name of the package to install or remove.",
+                ),
+                STATE_DOC,
+            ],
+        },
+        ParamsDoc {
+            description: "Many packages.",
+            params: &[
+                ParamDoc::required(
+                    "packages",
+                    ParamDocType::StringList,
+                    "Names of the packages to install or remove.",
+                ),
+                STATE_DOC,
+            ],
+        },
+    ];
+
+    type Params = DnfParams;
+    type Resource = DnfResource;
+
+    fn resources(params: Self::Params) -> Vec<CausalityTree<Self::Resource>> {
+        let DnfParams { packages, present } = params;
+        packages
+            .into_iter()
+            .map(|package| {
+                let resource = if present {
+                    DnfResource::Present { package }
+                } else {
+                    DnfResource::Absent { package }
+                };
+                CausalityTree::leaf(CausalityMeta::default(), resource)
+            })
+            .collect()
+    }
+
+    type State = DnfState;
+    type StateError = DnfStateError;
+    async fn state(
+        ctx: &mut Context,
+        resource: &Self::Resource,
+    ) -> Result<Self::State, Self::StateError> {
+        let mut states = Self::states_bulk(ctx, &[resource]).await?;
+        Ok(states.remove(0))
+    }
+
+    async fn states_bulk(
+        _ctx: &mut Context,
+        resources: &[&Self::Resource],
+    ) -> Result<Vec<Self::State>, Self::StateError> {
+        // With no packages, `rpm -q` would fail asking for some.
+        if resources.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let outcome = Command::new("rpm")
+            .arg("-q")
+            .arg("--")
+            .args(resources.iter().map(|resource| resource.package()))
+            .outcome()
+            .await?;
+        let missing = parse_missing(
+            resources.iter().map(|resource| resource.package()),
+            outcome.status.success(),
+            &String::from_utf8_lossy(&outcome.stdout),
+            &String::from_utf8_lossy(&outcome.stderr),
+        )?;
+
+        Ok(resources
+            .iter()
+            .map(|resource| {
+                if missing.contains(resource.package()) {
+                    DnfState::NotInstalled
+                } else {
+                    DnfState::Installed
+                }
+            })
+            .collect())
+    }
+
+    type Change = DnfChange;
+    fn change(resource: &Self::Resource, state: &Self::State) -> Option<Self::Change> {
+        match (resource, state) {
+            (DnfResource::Present { package }, DnfState::NotInstalled) => {
+                Some(DnfChange::Install {
+                    package: package.clone(),
+                })
+            }
+            (DnfResource::Absent { package }, DnfState::Installed) => Some(DnfChange::Remove {
+                package: package.clone(),
+            }),
+            (DnfResource::Present { .. }, DnfState::Installed)
+            | (DnfResource::Absent { .. }, DnfState::NotInstalled) => None,
+        }
+    }
+
+    fn operations(change: Self::Change) -> Vec<CausalityTree<Operation>> {
+        let operation = match change {
+            DnfChange::Install { package } => DnfOperation::Install {
+                packages: vec![package],
+            },
+            DnfChange::Remove { package } => DnfOperation::Remove {
+                packages: vec![package],
+            },
+        };
+        vec![CausalityTree::leaf(
+            CausalityMeta::default(),
+            Operation::Dnf(operation),
+        )]
+    }
+}
+
+const STATE_DOC: ParamDoc = ParamDoc::optional(
+    "state",
+    ParamDocType::OneOf(&[
+        ParamDocType::Literal("present"),
+        ParamDocType::Literal("absent"),
+    ]),
+    "Whether the packages should be installed. Default: present.",
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_packages_are_the_ones_rpm_says_are_not_installed() {
+        let stdout = "curl-8.6.0-7.fc40.x86_64\npackage nginx is not installed\n";
+        let missing = parse_missing(["curl", "nginx"], false, stdout, "").unwrap();
+        assert_eq!(missing, HashSet::from(["nginx"]));
+
+        let missing = parse_missing(["curl"], true, "curl-8.6.0-7.fc40.x86_64\n", "").unwrap();
+        assert!(missing.is_empty());
+    }
+
+    #[test]
+    fn other_rpm_failures_are_errors() {
+        let error = parse_missing(["curl"], false, "", "error: rpmdb open failed\n").unwrap_err();
+        assert_eq!(error.to_string(), "rpm -q failed: error: rpmdb open failed");
+    }
+}
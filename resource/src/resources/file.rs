@@ -1,4 +1,9 @@
-use std::fmt::{self, Display};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt::{self, Display, Write as _};
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
 
 use async_trait::async_trait;
 use lusid_causality::{CausalityMeta, CausalityTree};
@@ -6,17 +11,26 @@ use lusid_ctx::Context;
 use lusid_fs::{self as fs, FsError};
 use lusid_operation::{
     Operation,
-    operations::file::{FileGroup, FileMode, FileOperation, FilePath, FileSource, FileUser},
+    operations::{
+        directory::DirectoryOperation,
+        file::{FileGroup, FileMode, FileOperation, FilePath, FileSource, FileUser},
+        service::ServiceOperation,
+    },
 };
-use lusid_params::{ParseError, ParseParams, StructFields};
+use lusid_params::{ParseError, ParseParams, StructFields, parse_target_path};
 use lusid_view::impl_display_render;
-use rimu::{Span, Spanned, Value};
+use rimu::{SourceId, Span, Spanned, Value};
 use secrecy::ExposeSecret;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 use thiserror::Error;
+use tokio::io::AsyncReadExt;
 
 use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum FileParams {
     /// Byte-copy from `source` (a host-path) into `path` (a target-path),
     /// atomically. Edits to `source` only propagate on the next apply. Use
@@ -27,11 +41,32 @@ pub enum FileParams {
         source: FilePath,
         /// Span of the `source` value in the plan source. Carried so
         /// host-path validation errors can point at the offending line.
+        #[serde(skip)]
         source_span: Span,
         path: FilePath,
         mode: Option<FileMode>,
         user: Option<FileUser>,
         group: Option<FileGroup>,
+        /// Systemd unit to restart once the file's contents change, e.g. an
+        /// nginx config restarting `nginx.service`. Only content changes
+        /// count: a mode or owner fix alone doesn't restart anything.
+        restarts: Option<String>,
+        /// Create `path`'s missing parent directories first, rather than
+        /// failing with ENOENT. See [`FileParents`].
+        parents: Option<FileParents>,
+    },
+
+    /// Write `contents`, given inline in the plan, to `path`. For small files
+    /// (env files, systemd drop-ins) where a separate source file is more
+    /// ceremony than content; Rimu string interpolation fills in params.
+    Contents {
+        contents: String,
+        path: FilePath,
+        mode: Option<FileMode>,
+        user: Option<FileUser>,
+        group: Option<FileGroup>,
+        restarts: Option<String>,
+        parents: Option<FileParents>,
     },
 
     /// Materialise `path` as a symlink to `source` (a host-path on the
@@ -53,8 +88,33 @@ pub enum FileParams {
         source: FilePath,
         /// Span of the `source` value in the plan source. See
         /// [`FileParams::Sourced::source_span`] for rationale.
+        #[serde(skip)]
+        source_span: Span,
+        path: FilePath,
+        restarts: Option<String>,
+        parents: Option<FileParents>,
+    },
+
+    /// Mirror the host directory tree at `source` into `path`, one
+    /// [`FileResource::Sourced`] per file, so a change lists exactly the files
+    /// it writes, with their digests. Directories missing from `path` are
+    /// created; files keep their host modes. With `delete`, whatever else is
+    /// under `path` is removed.
+    ///
+    /// The tree is walked at plan load, as a template is rendered, so files
+    /// added to `source` are picked up on the next apply.
+    Synced {
+        source: FilePath,
+        /// Span of the `source` value in the plan source. See
+        /// [`FileParams::Sourced::source_span`] for rationale.
+        #[serde(skip)]
         source_span: Span,
         path: FilePath,
+        /// What's under `source`, each directory before what's in it.
+        entries: Vec<FileSyncEntry>,
+        delete: bool,
+        restarts: Option<String>,
+        parents: Option<FileParents>,
     },
 
     Present {
@@ -62,17 +122,65 @@ pub enum FileParams {
         mode: Option<FileMode>,
         user: Option<FileUser>,
         group: Option<FileGroup>,
+        restarts: Option<String>,
+        parents: Option<FileParents>,
     },
     Absent {
         path: FilePath,
+        restarts: Option<String>,
     },
 }
 
+/// A directory or file under a synced tree's `source`, by its `/`-separated
+/// path relative to it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileSyncEntry {
+    Directory(String),
+    File(String),
+}
+
+impl FileSyncEntry {
+    pub fn relative(&self) -> &str {
+        match self {
+            FileSyncEntry::Directory(relative) | FileSyncEntry::File(relative) => relative,
+        }
+    }
+}
+
+/// `parents: true`, or `parents: { mode, user, group }` to also set the
+/// permissions of the directories it creates. Parent directories that already
+/// exist are left as they are: `parents` never chmods `/etc`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FileParents {
+    pub mode: Option<FileMode>,
+    pub user: Option<FileUser>,
+    pub group: Option<FileGroup>,
+}
+
+fn parse_parents(value: Spanned<Value>) -> Result<Option<FileParents>, Spanned<ParseError>> {
+    if let Value::Boolean(parents) = value.inner() {
+        return Ok(parents.then(FileParents::default));
+    }
+    let mut fields = StructFields::new(value)?;
+    let parents = FileParents {
+        mode: fields.optional_u32("mode")?.map(FileMode::new),
+        user: fields.optional_string("user")?.map(FileUser::new),
+        group: fields.optional_string("group")?.map(FileGroup::new),
+    };
+    fields.finish()?;
+    Ok(Some(parents))
+}
+
 impl ParseParams for FileParams {
     fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
         let mut fields = StructFields::new(value)?;
-        let state =
-            fields.take_discriminator("state", &["sourced", "linked", "present", "absent"])?;
+        let state = fields.take_discriminator(
+            "state",
+            &[
+                "sourced", "contents", "template", "linked", "synced", "present", "absent",
+            ],
+        )?;
         let out = match state {
             "sourced" => {
                 // `source` is a `host-path`; the parser resolves a relative
@@ -85,10 +193,37 @@ impl ParseParams for FileParams {
                 FileParams::Sourced {
                     source: FilePath::new(source_path.to_string_lossy().into_owned()),
                     source_span,
-                    path: FilePath::new(fields.required_target_path("path")?),
+                    path: fields.required("path", parse_file_path)?,
+                    mode: fields.optional_u32("mode")?.map(FileMode::new),
+                    user: fields.optional_string("user")?.map(FileUser::new),
+                    group: fields.optional_string("group")?.map(FileGroup::new),
+                    restarts: fields.optional_string("restarts")?,
+                    parents: fields.optional("parents", parse_parents)?.flatten(),
+                }
+            }
+            "contents" => FileParams::Contents {
+                contents: fields.required_string("contents")?,
+                path: fields.required("path", parse_file_path)?,
+                mode: fields.optional_u32("mode")?.map(FileMode::new),
+                user: fields.optional_string("user")?.map(FileUser::new),
+                group: fields.optional_string("group")?.map(FileGroup::new),
+                restarts: fields.optional_string("restarts")?,
+                parents: fields.optional("parents", parse_parents)?.flatten(),
+            },
+            // Rendered here, at plan load, so it lowers to `Contents`: the
+            // template's `vars` are Rimu values, which don't outlive the plan.
+            "template" => {
+                let (source_path, source_span) =
+                    fields.required_host_path_spanned("source")?.take();
+                let vars = fields.optional("vars", Ok)?;
+                FileParams::Contents {
+                    contents: render_template(&source_path, source_span, vars)?,
+                    path: fields.required("path", parse_file_path)?,
                     mode: fields.optional_u32("mode")?.map(FileMode::new),
                     user: fields.optional_string("user")?.map(FileUser::new),
                     group: fields.optional_string("group")?.map(FileGroup::new),
+                    restarts: fields.optional_string("restarts")?,
+                    parents: fields.optional("parents", parse_parents)?.flatten(),
                 }
             }
             "linked" => {
@@ -100,17 +235,38 @@ impl ParseParams for FileParams {
                 FileParams::Linked {
                     source: FilePath::new(source_path.to_string_lossy().into_owned()),
                     source_span,
-                    path: FilePath::new(fields.required_target_path("path")?),
+                    path: fields.required("path", parse_file_path)?,
+                    restarts: fields.optional_string("restarts")?,
+                    parents: fields.optional("parents", parse_parents)?.flatten(),
+                }
+            }
+            // Walked here, at plan load, like a template is rendered, so the
+            // change tree has one atom per file.
+            "synced" => {
+                let (source_path, source_span) =
+                    fields.required_host_path_spanned("source")?.take();
+                let entries = walk_synced_source(&source_path, &source_span)?;
+                FileParams::Synced {
+                    source: FilePath::new(source_path.to_string_lossy().into_owned()),
+                    source_span,
+                    path: fields.required("path", parse_file_path)?,
+                    entries,
+                    delete: fields.optional_bool("delete")?.unwrap_or(false),
+                    restarts: fields.optional_string("restarts")?,
+                    parents: fields.optional("parents", parse_parents)?.flatten(),
                 }
             }
             "present" => FileParams::Present {
-                path: FilePath::new(fields.required_target_path("path")?),
+                path: fields.required("path", parse_file_path)?,
                 mode: fields.optional_u32("mode")?.map(FileMode::new),
                 user: fields.optional_string("user")?.map(FileUser::new),
                 group: fields.optional_string("group")?.map(FileGroup::new),
+                restarts: fields.optional_string("restarts")?,
+                parents: fields.optional("parents", parse_parents)?.flatten(),
             },
             "absent" => FileParams::Absent {
-                path: FilePath::new(fields.required_target_path("path")?),
+                path: fields.required("path", parse_file_path)?,
+                restarts: fields.optional_string("restarts")?,
             },
             _ => unreachable!(),
         };
@@ -119,17 +275,159 @@ impl ParseParams for FileParams {
     }
 }
 
+/// Render the Rimu template at `source`: a function of `vars` returning the
+/// file's contents as a string, e.g.
+/// `(vars) => "server_name " + vars.hostname + ";\n"`. `vars` is `null` when
+/// the plan gives none.
+fn render_template(
+    source: &Path,
+    source_span: Span,
+    vars: Option<Spanned<Value>>,
+) -> Result<String, Spanned<ParseError>> {
+    let failed = |reason: String| {
+        Spanned::new(
+            ParseError::Template {
+                path: source.display().to_string(),
+                reason,
+            },
+            source_span.clone(),
+        )
+    };
+    let code = std::fs::read_to_string(source).map_err(|reason| failed(reason.to_string()))?;
+    let source_id = SourceId::from(source.to_string_lossy().into_owned());
+    let (ast, errors) = rimu::parse(&code, source_id.clone());
+    if !errors.is_empty() {
+        return Err(failed(format!("{errors:?}")));
+    }
+    let Some(ast) = ast else {
+        return Err(failed("no code found".into()));
+    };
+    let env = Rc::new(RefCell::new(rimu::Environment::new()));
+    let template = rimu::evaluate(&ast, env).map_err(|reason| failed(reason.to_string()))?;
+    let (template, template_span) = template.take();
+    let Value::Function(template) = template else {
+        return Err(failed("expected a function of `vars`".into()));
+    };
+    let vars = vars.unwrap_or_else(|| Spanned::new(Value::Null, Span::new(source_id, 0, 0)));
+    let contents = rimu::call(template_span, template, &[vars])
+        .map_err(|reason| failed(reason.to_string()))?;
+    match contents.into_inner() {
+        Value::String(contents) => Ok(contents),
+        other => Err(failed(format!(
+            "expected it to return a string, got {other:?}"
+        ))),
+    }
+}
+
+/// Walk the host directory at `source` for a synced tree, in name order.
+/// Symlinks to files are followed, and their targets' contents synced; a
+/// symlink to a directory fails the walk rather than risk a link cycle.
+fn walk_synced_source(
+    source: &Path,
+    source_span: &Span,
+) -> Result<Vec<FileSyncEntry>, Spanned<ParseError>> {
+    fn walk(dir: &Path, relative: &str, entries: &mut Vec<FileSyncEntry>) -> Result<(), String> {
+        let failed = |path: &Path, error: std::io::Error| format!("{}: {error}", path.display());
+        let mut names = Vec::new();
+        for entry in std::fs::read_dir(dir).map_err(|error| failed(dir, error))? {
+            let entry = entry.map_err(|error| failed(dir, error))?;
+            let name = entry
+                .file_name()
+                .into_string()
+                .map_err(|name| format!("{}: {name:?} isn't UTF-8", dir.display()))?;
+            names.push(name);
+        }
+        names.sort();
+        for name in names {
+            let path = dir.join(&name);
+            let child = if relative.is_empty() {
+                name
+            } else {
+                format!("{relative}/{name}")
+            };
+            let metadata =
+                std::fs::symlink_metadata(&path).map_err(|error| failed(&path, error))?;
+            if metadata.is_dir() {
+                entries.push(FileSyncEntry::Directory(child.clone()));
+                walk(&path, &child, entries)?;
+            } else if std::fs::metadata(&path)
+                .map_err(|error| failed(&path, error))?
+                .is_file()
+            {
+                entries.push(FileSyncEntry::File(child));
+            } else {
+                return Err(format!(
+                    "{}: not a file, nor a directory that isn't a symlink",
+                    path.display()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    let mut entries = Vec::new();
+    walk(source, "", &mut entries).map_err(|reason| {
+        Spanned::new(
+            ParseError::HostDirectory {
+                path: source.display().to_string(),
+                reason,
+            },
+            source_span.clone(),
+        )
+    })?;
+    Ok(entries)
+}
+
+/// Parse a target-path field into a validated, normalized [`FilePath`]. Used
+/// by every resource with target-path params, so `..` segments and embedded
+/// NULs fail at plan load with a span rather than at apply time.
+pub(crate) fn parse_file_path(value: Spanned<Value>) -> Result<FilePath, Spanned<ParseError>> {
+    let span = value.span();
+    let path = parse_target_path(value)?;
+    FilePath::parse(path.clone()).map_err(|error| {
+        Spanned::new(
+            ParseError::InvalidTargetPath {
+                value: path,
+                reason: error.to_string(),
+            },
+            span,
+        )
+    })
+}
+
 impl Display for FileParams {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             FileParams::Sourced { source, path, .. } => {
                 write!(f, "File::Sourced(source = {source}, path = {path})")
             }
+            FileParams::Contents { contents, path, .. } => write!(
+                f,
+                "File::Contents(path = {path}, contents = {} bytes)",
+                contents.len()
+            ),
             FileParams::Linked { source, path, .. } => {
                 write!(f, "File::Linked(source = {source}, path = {path})")
             }
+            FileParams::Synced {
+                source,
+                path,
+                entries,
+                delete,
+                ..
+            } => {
+                write!(
+                    f,
+                    "File::Synced(source = {source}, path = {path}, {} entries",
+                    entries.len()
+                )?;
+                if *delete {
+                    write!(f, ", delete")?;
+                }
+                write!(f, ")")
+            }
             FileParams::Present { path, .. } => write!(f, "File::Present(path = {path})"),
-            FileParams::Absent { path } => write!(f, "File::Absent(path = {path})"),
+            FileParams::Absent { path, .. } => write!(f, "File::Absent(path = {path})"),
         }
     }
 }
@@ -141,10 +439,17 @@ pub enum FileResource {
     Sourced {
         source: FilePath,
         path: FilePath,
+        restarts: Option<String>,
+    },
+    Contents {
+        contents: String,
+        path: FilePath,
+        restarts: Option<String>,
     },
     Linked {
         source: FilePath,
         path: FilePath,
+        restarts: Option<String>,
     },
     /// Contents sourced from a decrypted secret by name; resolved against
     /// [`Context::secrets`] at state/apply time so plaintext never travels
@@ -152,12 +457,15 @@ pub enum FileResource {
     Secret {
         name: String,
         path: FilePath,
+        restarts: Option<String>,
     },
     Present {
         path: FilePath,
+        restarts: Option<String>,
     },
     Absent {
         path: FilePath,
+        restarts: Option<String>,
     },
     Mode {
         path: FilePath,
@@ -171,45 +479,128 @@ pub enum FileResource {
         path: FilePath,
         group: FileGroup,
     },
+    /// A missing ancestor of a file declared with `parents`, or a directory
+    /// of a synced tree.
+    Parent {
+        path: FilePath,
+        parents: FileParents,
+    },
+    /// What's under a synced tree's `path` that its `source` doesn't have.
+    /// `keep` is the source's entries, relative to `path`.
+    Extraneous {
+        path: FilePath,
+        keep: Vec<String>,
+        restarts: Option<String>,
+    },
 }
 
 impl Display for FileResource {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            FileResource::Sourced { source, path } => {
-                write!(f, "FileSourced({source} -> {path})")
+            FileResource::Sourced {
+                source,
+                path,
+                restarts,
+            } => {
+                write!(f, "FileSourced({source} -> {path}{})", Restarts(restarts))
+            }
+            FileResource::Contents {
+                contents,
+                path,
+                restarts,
+            } => write!(
+                f,
+                "FileContents({} bytes -> {path}{})",
+                contents.len(),
+                Restarts(restarts)
+            ),
+            FileResource::Linked {
+                source,
+                path,
+                restarts,
+            } => {
+                write!(f, "FileLinked({source} -> {path}{})", Restarts(restarts))
+            }
+            FileResource::Secret {
+                name,
+                path,
+                restarts,
+            } => {
+                write!(
+                    f,
+                    "FileSecret(secret = {name} -> {path}{})",
+                    Restarts(restarts)
+                )
             }
-            FileResource::Linked { source, path } => {
-                write!(f, "FileLinked({source} -> {path})")
+            FileResource::Present { path, restarts } => {
+                write!(f, "FilePresent({path}{})", Restarts(restarts))
             }
-            FileResource::Secret { name, path } => {
-                write!(f, "FileSecret(secret = {name} -> {path})")
+            FileResource::Absent { path, restarts } => {
+                write!(f, "FileAbsent({path}{})", Restarts(restarts))
             }
-            FileResource::Present { path } => write!(f, "FilePresent({path})"),
-            FileResource::Absent { path } => write!(f, "FileAbsent({path})"),
             FileResource::Mode { path, mode } => write!(f, "FileMode({path}, mode = {mode})"),
             FileResource::User { path, user } => write!(f, "FileUser({path}, user = {user})"),
             FileResource::Group { path, group } => write!(f, "FileGroup({path}, group = {group})"),
+            FileResource::Parent { path, .. } => write!(f, "FileParent({path})"),
+            FileResource::Extraneous {
+                path,
+                keep,
+                restarts,
+            } => write!(
+                f,
+                "FileExtraneous({path}, keep = {} entries{})",
+                keep.len(),
+                Restarts(restarts)
+            ),
         }
     }
 }
 
 impl_display_render!(FileResource);
 
+/// Formats a `restarts` unit as a trailing `, restarts = <unit>` argument,
+/// or nothing when there isn't one.
+struct Restarts<'a>(&'a Option<String>);
+
+impl Display for Restarts<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(unit) => write!(f, ", restarts = {unit}"),
+            None => Ok(()),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum FileState {
     Sourced,
-    NotSourced,
+    /// `current` and `desired` describe the contents on either side, for the
+    /// rendered change. Both are `None` for secrets.
+    NotSourced {
+        current: Option<FileDigest>,
+        desired: Option<FileDigest>,
+    },
     Linked,
     NotLinked,
     Present,
     Absent,
     ModeCorrect,
-    ModeIncorrect,
+    ModeIncorrect {
+        current: Option<FileMode>,
+    },
     UserCorrect,
-    UserIncorrect,
+    UserIncorrect {
+        current: Option<String>,
+    },
     GroupCorrect,
-    GroupIncorrect,
+    GroupIncorrect {
+        current: Option<String>,
+    },
+    NoExtraneous,
+    Extraneous {
+        files: Vec<FilePath>,
+        directories: Vec<FilePath>,
+    },
 }
 
 impl Display for FileState {
@@ -217,17 +608,19 @@ impl Display for FileState {
         use FileState::*;
         let text = match self {
             Sourced => "Sourced",
-            NotSourced => "NotSourced",
+            NotSourced { .. } => "NotSourced",
             Linked => "Linked",
             NotLinked => "NotLinked",
             Present => "Present",
             Absent => "Absent",
             ModeCorrect => "ModeCorrect",
-            ModeIncorrect => "ModeIncorrect",
+            ModeIncorrect { .. } => "ModeIncorrect",
             UserCorrect => "UserCorrect",
-            UserIncorrect => "UserIncorrect",
+            UserIncorrect { .. } => "UserIncorrect",
             GroupCorrect => "GroupCorrect",
-            GroupIncorrect => "GroupIncorrect",
+            GroupIncorrect { .. } => "GroupIncorrect",
+            NoExtraneous => "NoExtraneous",
+            Extraneous { .. } => "Extraneous",
         };
         write!(f, "{text}")
     }
@@ -235,6 +628,85 @@ impl Display for FileState {
 
 impl_display_render!(FileState);
 
+/// Size and SHA-256 of file contents, so a reviewer can sanity-check a write
+/// without the full diff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDigest {
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl FileDigest {
+    pub fn of(bytes: &[u8]) -> Self {
+        Self::from_hasher(Sha256::new_with_prefix(bytes), bytes.len() as u64)
+    }
+
+    /// Digest of the file at `path`, read a chunk at a time.
+    pub async fn of_file(path: &Path) -> Result<Self, FsError> {
+        let mut file = fs::open_file(path).await?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0; 64 * 1024];
+        let mut size = 0;
+        loop {
+            let read = file
+                .read(&mut buffer)
+                .await
+                .map_err(|source| FsError::ReadFile {
+                    path: path.to_path_buf(),
+                    source,
+                })?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            size += read as u64;
+        }
+        Ok(Self::from_hasher(hasher, size))
+    }
+
+    fn from_hasher(hasher: Sha256, size: u64) -> Self {
+        let mut sha256 = String::with_capacity(64);
+        for byte in hasher.finalize() {
+            let _ = write!(sha256, "{byte:02x}");
+        }
+        Self { size, sha256 }
+    }
+}
+
+impl Display for FileDigest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} B sha256:{}", self.size, &self.sha256[..12])
+    }
+}
+
+/// Formats a metadata change's current value as a trailing `, was <value>`
+/// argument, or nothing when it isn't known.
+struct Was<'a, T>(&'a Option<T>);
+
+impl<T: Display> Display for Was<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(value) => write!(f, ", was {value}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Formats a write's before/after contents as a trailing
+/// `, <before> -> <after>` argument, or nothing when neither is known.
+struct Digests<'a>(&'a Option<FileDigest>, &'a Option<FileDigest>);
+
+impl Display for Digests<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.0, self.1) {
+            (None, None) => Ok(()),
+            (Some(before), Some(after)) => write!(f, ", {before} -> {after}"),
+            (None, Some(after)) => write!(f, ", new -> {after}"),
+            (Some(before), None) => write!(f, ", {before} -> ?"),
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum FileStateError {
     #[error(transparent)]
@@ -254,67 +726,257 @@ pub enum FileChange {
     Write {
         path: FilePath,
         source: FileSource,
+        restarts: Option<String>,
+        /// Contents before and after the write, when known. See
+        /// [`FileState::NotSourced`].
+        before: Option<FileDigest>,
+        after: Option<FileDigest>,
     },
     CreateSymlink {
         source: FilePath,
         path: FilePath,
+        restarts: Option<String>,
     },
     Remove {
         path: FilePath,
+        restarts: Option<String>,
     },
     ChangeMode {
         path: FilePath,
         mode: FileMode,
+        before: Option<FileMode>,
     },
+    /// Sets exactly one of `user` or `group`; `before` is its current name.
     ChangeOwner {
         path: FilePath,
         user: Option<FileUser>,
         group: Option<FileGroup>,
+        before: Option<String>,
+    },
+    CreateParent {
+        path: FilePath,
+        parents: FileParents,
+    },
+    /// Remove what's under a synced tree's `path` that its `source` doesn't
+    /// have: `directories` whole.
+    RemoveExtraneous {
+        path: FilePath,
+        files: Vec<FilePath>,
+        directories: Vec<FilePath>,
+        restarts: Option<String>,
     },
 }
 
 impl Display for FileChange {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            FileChange::Write { path, source } => match source {
-                FileSource::Contents(contents) => write!(
-                    f,
-                    "File::Write(path = {}, source = Contents({} bytes))",
-                    path,
-                    contents.len()
-                ),
-                FileSource::Path(source_path) => write!(
+            FileChange::Write {
+                path,
+                source,
+                restarts,
+                before,
+                after,
+            } => {
+                let source = match source {
+                    FileSource::Contents(contents) => format!("Contents({} bytes)", contents.len()),
+                    FileSource::Path(source_path) => format!("Path({source_path})"),
+                    FileSource::Secret(name) => format!("Secret({name})"),
+                };
+                write!(
                     f,
-                    "File::Write(path = {}, source = Path({}))",
-                    path, source_path
-                ),
-                FileSource::Secret(name) => {
-                    write!(f, "File::Write(path = {}, source = Secret({}))", path, name)
-                }
-            },
-            FileChange::CreateSymlink { source, path } => {
-                write!(f, "File::CreateSymlink(source = {source}, path = {path})")
+                    "File::Write(path = {path}, source = {source}{}{})",
+                    Restarts(restarts),
+                    Digests(before, after)
+                )
             }
-            FileChange::Remove { path } => write!(f, "File::Remove(path = {path})"),
-            FileChange::ChangeMode { path, mode } => {
-                write!(f, "File::ChangeMode(path = {path}, mode = {mode})")
+            FileChange::CreateSymlink {
+                source,
+                path,
+                restarts,
+            } => write!(
+                f,
+                "File::CreateSymlink(source = {source}, path = {path}{})",
+                Restarts(restarts)
+            ),
+            FileChange::Remove { path, restarts } => {
+                write!(f, "File::Remove(path = {path}{})", Restarts(restarts))
             }
-            FileChange::ChangeOwner { path, user, group } => write!(
+            FileChange::ChangeMode { path, mode, before } => write!(
                 f,
-                "File::ChangeOwner(path = {path}, user = {user:?}, group = {group:?})"
+                "File::ChangeMode(path = {path}, mode = {mode}{})",
+                Was(before)
             ),
+            FileChange::ChangeOwner {
+                path,
+                user,
+                group,
+                before,
+            } => write!(
+                f,
+                "File::ChangeOwner(path = {path}, user = {user:?}, group = {group:?}{})",
+                Was(before)
+            ),
+            FileChange::CreateParent { path, .. } => {
+                write!(f, "File::CreateParent(path = {path})")
+            }
+            FileChange::RemoveExtraneous {
+                path,
+                files,
+                directories,
+                restarts,
+            } => {
+                let remove: Vec<String> = files
+                    .iter()
+                    .map(ToString::to_string)
+                    .chain(directories.iter().map(|directory| format!("{directory}/")))
+                    .collect();
+                write!(
+                    f,
+                    "File::RemoveExtraneous(path = {path}, remove = [{}]{})",
+                    remove.join(", "),
+                    Restarts(restarts)
+                )
+            }
         }
     }
 }
 
 impl_display_render!(FileChange);
 
+// Params shared by the file-like resources' docs.
+pub(crate) const SOURCE_DOC: ParamDoc = ParamDoc::required(
+    "source",
+    ParamDocType::HostPath,
+    "Path to copy or link from, relative to the plan.",
+);
+pub(crate) const PATH_DOC: ParamDoc =
+    ParamDoc::required("path", ParamDocType::TargetPath, "Path on the target.");
+pub(crate) const MODE_DOC: ParamDoc = ParamDoc::optional(
+    "mode",
+    ParamDocType::Number,
+    "Permissions as a decimal number, e.g. `420` for 0o644.",
+);
+pub(crate) const USER_DOC: ParamDoc = ParamDoc::optional("user", ParamDocType::String, "Owner.");
+pub(crate) const GROUP_DOC: ParamDoc =
+    ParamDoc::optional("group", ParamDocType::String, "Owning group.");
+pub(crate) const RESTARTS_DOC: ParamDoc = ParamDoc::optional(
+    "restarts",
+    ParamDocType::String,
+    "Systemd unit to restart when the contents change.",
+);
+const PARENTS_DOC: ParamDoc = ParamDoc::optional(
+    "parents",
+    ParamDocType::OneOf(&[ParamDocType::Boolean, ParamDocType::Object]),
+    "Create missing parent directories: `true`, or `{ mode, user, group }` for them.",
+);
+
 #[derive(Debug, Clone)]
 pub struct File;
 
 #[async_trait]
 impl ResourceType for File {
     const ID: &'static str = "file";
+    const DESCRIPTION: &'static str = "Manage a file: its contents, or a symlink, mode and owner.";
+    const PLATFORMS: &'static [&'static str] = &["linux", "macos"];
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[
+        ParamsDoc {
+            description: "Copied from a file next to the plan.",
+            params: &[
+                ParamDoc::required("state", ParamDocType::Literal("sourced"), "Sourced."),
+                SOURCE_DOC,
+                PATH_DOC,
+                MODE_DOC,
+                USER_DOC,
+                GROUP_DOC,
+                RESTARTS_DOC,
+                PARENTS_DOC,
+            ],
+        },
+        ParamsDoc {
+            description: "Written with contents given inline.",
+            params: &[
+                ParamDoc::required("state", ParamDocType::Literal("contents"), "Contents."),
+                ParamDoc::required("contents", ParamDocType::String, "Contents of the file."),
+                PATH_DOC,
+                MODE_DOC,
+                USER_DOC,
+                GROUP_DOC,
+                RESTARTS_DOC,
+                PARENTS_DOC,
+            ],
+        },
+        ParamsDoc {
+            description: "Written with contents rendered from a Rimu template next to the plan.",
+            params: &[
+                ParamDoc::required("state", ParamDocType::Literal("template"), "Template."),
+                ParamDoc::required(
+                    "source",
+                    ParamDocType::HostPath,
+                    "Path to the template, relative to the plan: a Rimu function of `vars` returning the contents.",
+                ),
+                ParamDoc::optional(
+                    "vars",
+                    ParamDocType::Any,
+                    "Passed to the template, e.g. the plan's `params`.",
+                ),
+                PATH_DOC,
+                MODE_DOC,
+                USER_DOC,
+                GROUP_DOC,
+                RESTARTS_DOC,
+                PARENTS_DOC,
+            ],
+        },
+        ParamsDoc {
+            description: "A symlink to a file next to the plan.",
+            params: &[
+                ParamDoc::required("state", ParamDocType::Literal("linked"), "Linked."),
+                SOURCE_DOC,
+                PATH_DOC,
+                RESTARTS_DOC,
+                PARENTS_DOC,
+            ],
+        },
+        ParamsDoc {
+            description: "A directory tree copied file by file from a directory next to the plan.",
+            params: &[
+                ParamDoc::required("state", ParamDocType::Literal("synced"), "Synced."),
+                ParamDoc::required(
+                    "source",
+                    ParamDocType::HostPath,
+                    "Directory to copy from, relative to the plan.",
+                ),
+                ParamDoc::required("path", ParamDocType::TargetPath, "Directory on the target."),
+                ParamDoc::optional(
+                    "delete",
+                    ParamDocType::Boolean,
+                    "Remove whatever under `path` isn't in `source`. Defaults to `false`.",
+                ),
+                RESTARTS_DOC,
+                PARENTS_DOC,
+            ],
+        },
+        ParamsDoc {
+            description: "Present, with whatever contents it has.",
+            params: &[
+                ParamDoc::required("state", ParamDocType::Literal("present"), "Present."),
+                PATH_DOC,
+                MODE_DOC,
+                USER_DOC,
+                GROUP_DOC,
+                RESTARTS_DOC,
+                PARENTS_DOC,
+            ],
+        },
+        ParamsDoc {
+            description: "Removed.",
+            params: &[
+                ParamDoc::required("state", ParamDocType::Literal("absent"), "Absent."),
+                PATH_DOC,
+                RESTARTS_DOC,
+            ],
+        },
+    ];
 
     type Params = FileParams;
     type Resource = FileResource;
@@ -359,6 +1021,46 @@ impl ResourceType for File {
             nodes
         }
 
+        // One atom per ancestor of `path` below `/`, outermost first, each
+        // requiring the one above it. Returns the atoms, and what the file's
+        // own atom should require: the innermost parent.
+        fn parent_atoms(
+            path: &FilePath,
+            parents: Option<FileParents>,
+        ) -> (Vec<CausalityTree<FileResource>>, Vec<String>) {
+            let Some(parents) = parents else {
+                return (Vec::new(), Vec::new());
+            };
+            let mut ancestors = Vec::new();
+            let mut next = path.parent();
+            while let Some(ancestor) = next {
+                next = ancestor.parent();
+                if next.is_some() {
+                    ancestors.push(ancestor);
+                }
+            }
+            ancestors.reverse();
+
+            let mut nodes = Vec::with_capacity(ancestors.len());
+            let mut requires = Vec::new();
+            for (index, ancestor) in ancestors.into_iter().enumerate() {
+                let id = format!("parent-{index}");
+                nodes.push(CausalityTree::leaf(
+                    CausalityMeta {
+                        id: Some(id.clone()),
+                        requires: std::mem::take(&mut requires),
+                        required_by: vec![],
+                    },
+                    FileResource::Parent {
+                        path: ancestor,
+                        parents: parents.clone(),
+                    },
+                ));
+                requires = vec![id];
+            }
+            (nodes, requires)
+        }
+
         match params {
             FileParams::Sourced {
                 source,
@@ -367,14 +1069,48 @@ impl ResourceType for File {
                 mode,
                 user,
                 group,
+                restarts,
+                parents,
             } => {
-                let mut nodes = vec![CausalityTree::leaf(
-                    CausalityMeta::id("file".into()),
+                let (mut nodes, requires) = parent_atoms(&path, parents);
+                nodes.push(CausalityTree::leaf(
+                    CausalityMeta {
+                        id: Some("file".into()),
+                        requires,
+                        required_by: vec![],
+                    },
                     FileResource::Sourced {
                         source,
                         path: path.clone(),
+                        restarts,
                     },
-                )];
+                ));
+                nodes.extend(permission_atoms(&path, mode, user, group));
+                nodes
+            }
+
+            FileParams::Contents {
+                contents,
+                path,
+                mode,
+                user,
+                group,
+                restarts,
+                parents,
+            } => {
+                let (mut nodes, requires) = parent_atoms(&path, parents);
+                nodes.push(CausalityTree::leaf(
+                    CausalityMeta {
+                        id: Some("file".into()),
+                        requires,
+                        required_by: vec![],
+                    },
+                    FileResource::Contents {
+                        contents,
+                        path: path.clone(),
+                        restarts,
+                    },
+                ));
                 nodes.extend(permission_atoms(&path, mode, user, group));
                 nodes
             }
@@ -383,28 +1119,118 @@ impl ResourceType for File {
                 source,
                 source_span: _,
                 path,
-            } => vec![CausalityTree::leaf(
-                CausalityMeta::default(),
-                FileResource::Linked { source, path },
-            )],
+                restarts,
+                parents,
+            } => {
+                let (mut nodes, requires) = parent_atoms(&path, parents);
+                nodes.push(CausalityTree::leaf(
+                    CausalityMeta::requires(requires),
+                    FileResource::Linked {
+                        source,
+                        path,
+                        restarts,
+                    },
+                ));
+                nodes
+            }
 
             FileParams::Present {
                 path,
                 mode,
                 user,
                 group,
+                restarts,
+                parents,
             } => {
-                let mut nodes = vec![CausalityTree::leaf(
-                    CausalityMeta::id("file".into()),
-                    FileResource::Present { path: path.clone() },
-                )];
+                let (mut nodes, requires) = parent_atoms(&path, parents);
+                nodes.push(CausalityTree::leaf(
+                    CausalityMeta {
+                        id: Some("file".into()),
+                        requires,
+                        required_by: vec![],
+                    },
+                    FileResource::Present {
+                        path: path.clone(),
+                        restarts,
+                    },
+                ));
                 nodes.extend(permission_atoms(&path, mode, user, group));
                 nodes
             }
 
-            FileParams::Absent { path } => vec![CausalityTree::leaf(
+            // The root directory is `dir`, each directory below it
+            // `dir:<relative path>`, and each file requires its directory.
+            FileParams::Synced {
+                source,
+                source_span: _,
+                path,
+                entries,
+                delete,
+                restarts,
+                parents,
+            } => {
+                fn directory_id(relative: &str) -> String {
+                    match relative.rsplit_once('/') {
+                        Some((parent, _)) => format!("dir:{parent}"),
+                        None => "dir".into(),
+                    }
+                }
+                let (mut nodes, requires) = parent_atoms(&path, parents);
+                nodes.push(CausalityTree::leaf(
+                    CausalityMeta {
+                        id: Some("dir".into()),
+                        requires,
+                        required_by: vec![],
+                    },
+                    FileResource::Parent {
+                        path: path.clone(),
+                        parents: FileParents::default(),
+                    },
+                ));
+                for entry in &entries {
+                    let relative = entry.relative();
+                    let requires = vec![directory_id(relative)];
+                    nodes.push(match entry {
+                        FileSyncEntry::Directory(_) => CausalityTree::leaf(
+                            CausalityMeta {
+                                id: Some(format!("dir:{relative}")),
+                                requires,
+                                required_by: vec![],
+                            },
+                            FileResource::Parent {
+                                path: synced_path(&path, relative),
+                                parents: FileParents::default(),
+                            },
+                        ),
+                        FileSyncEntry::File(_) => CausalityTree::leaf(
+                            CausalityMeta::requires(requires),
+                            FileResource::Sourced {
+                                source: synced_path(&source, relative),
+                                path: synced_path(&path, relative),
+                                restarts: restarts.clone(),
+                            },
+                        ),
+                    });
+                }
+                if delete {
+                    nodes.push(CausalityTree::leaf(
+                        CausalityMeta::default(),
+                        FileResource::Extraneous {
+                            path,
+                            keep: entries
+                                .iter()
+                                .map(|entry| entry.relative().to_owned())
+                                .collect(),
+                            restarts,
+                        },
+                    ));
+                }
+                nodes
+            }
+
+            FileParams::Absent { path, restarts } => vec![CausalityTree::leaf(
                 CausalityMeta::default(),
-                FileResource::Absent { path },
+                FileResource::Absent { path, restarts },
             )],
         }
     }
@@ -417,25 +1243,20 @@ impl ResourceType for File {
         resource: &Self::Resource,
     ) -> Result<Self::State, Self::StateError> {
         let state = match resource {
-            FileResource::Sourced { source, path } => {
-                if !fs::path_exists(path.as_path()).await? {
-                    FileState::NotSourced
-                } else {
-                    let source_contents = fs::read_file_to_bytes(source.as_path()).await?;
-                    let path_contents = fs::read_file_to_bytes(path.as_path()).await?;
-                    if source_contents == path_contents {
-                        FileState::Sourced
-                    } else {
-                        FileState::NotSourced
-                    }
-                }
+            FileResource::Sourced { source, path, .. } => probe_sourced(source, path).await?,
+
+            FileResource::Contents { contents, path, .. } => {
+                probe_contents(path, contents.as_bytes(), true).await?
             }
 
-            FileResource::Linked { source, path } => probe_linked_state(source, path).await?,
+            FileResource::Linked { source, path, .. } => probe_linked_state(source, path).await?,
 
-            FileResource::Secret { name, path } => {
+            FileResource::Secret { name, path, .. } => {
                 if !fs::path_exists(path.as_path()).await? {
-                    FileState::NotSourced
+                    FileState::NotSourced {
+                        current: None,
+                        desired: None,
+                    }
                 } else {
                     // Compare the file's current contents against the
                     // decrypted secret plaintext. A missing secret here
@@ -445,16 +1266,15 @@ impl ResourceType for File {
                         .secrets()
                         .get(name)
                         .ok_or_else(|| FileStateError::MissingSecret { name: name.clone() })?;
-                    let path_contents = fs::read_file_to_bytes(path.as_path()).await?;
-                    if path_contents.as_slice() == secret.expose_secret().as_bytes() {
-                        FileState::Sourced
-                    } else {
-                        FileState::NotSourced
-                    }
+                    // Never digest the plaintext: a checksum of a short
+                    // secret is as good as the secret.
+                    probe_contents(path, secret.expose_secret().as_bytes(), false).await?
                 }
             }
 
-            FileResource::Present { path } | FileResource::Absent { path } => {
+            FileResource::Present { path, .. }
+            | FileResource::Absent { path, .. }
+            | FileResource::Parent { path, .. } => {
                 if fs::path_exists(path.as_path()).await? {
                     FileState::Present
                 } else {
@@ -462,44 +1282,57 @@ impl ResourceType for File {
                 }
             }
 
+            FileResource::Extraneous { path, keep, .. } => {
+                let (files, directories) = find_extraneous(path, keep).await?;
+                if files.is_empty() && directories.is_empty() {
+                    FileState::NoExtraneous
+                } else {
+                    FileState::Extraneous { files, directories }
+                }
+            }
+
             FileResource::Mode { path, mode } => {
                 if !fs::path_exists(path.as_path()).await? {
-                    FileState::ModeIncorrect
+                    FileState::ModeIncorrect { current: None }
                 } else {
                     let actual_mode = fs::get_mode(path.as_path()).await?;
                     let actual_mode = actual_mode & 0o7777;
                     if actual_mode == mode.as_u32() {
                         FileState::ModeCorrect
                     } else {
-                        FileState::ModeIncorrect
+                        FileState::ModeIncorrect {
+                            current: Some(FileMode::new(actual_mode)),
+                        }
                     }
                 }
             }
 
             FileResource::User { path, user } => {
                 if !fs::path_exists(path.as_path()).await? {
-                    FileState::UserIncorrect
+                    FileState::UserIncorrect { current: None }
                 } else {
                     let actual_user = fs::get_owner_user(path.as_path()).await?;
-                    let actual_user = actual_user.map(|u| u.name.to_string());
                     if actual_user.as_deref() == Some(user.as_str()) {
                         FileState::UserCorrect
                     } else {
-                        FileState::UserIncorrect
+                        FileState::UserIncorrect {
+                            current: actual_user,
+                        }
                     }
                 }
             }
 
             FileResource::Group { path, group } => {
                 if !fs::path_exists(path.as_path()).await? {
-                    FileState::GroupIncorrect
+                    FileState::GroupIncorrect { current: None }
                 } else {
                     let actual_group = fs::get_owner_group(path.as_path()).await?;
-                    let actual_group = actual_group.map(|g| g.name.to_string());
                     if actual_group.as_deref() == Some(group.as_str()) {
                         FileState::GroupCorrect
                     } else {
-                        FileState::GroupIncorrect
+                        FileState::GroupIncorrect {
+                            current: actual_group,
+                        }
                     }
                 }
             }
@@ -512,75 +1345,146 @@ impl ResourceType for File {
 
     fn change(resource: &Self::Resource, state: &Self::State) -> Option<Self::Change> {
         match (resource, state) {
-            (FileResource::Sourced { source, path }, FileState::NotSourced) => {
-                Some(FileChange::Write {
-                    path: path.clone(),
-                    source: FileSource::Path(source.clone()),
-                })
-            }
+            (
+                FileResource::Sourced {
+                    source,
+                    path,
+                    restarts,
+                },
+                FileState::NotSourced { current, desired },
+            ) => Some(FileChange::Write {
+                path: path.clone(),
+                source: FileSource::Path(source.clone()),
+                restarts: restarts.clone(),
+                before: current.clone(),
+                after: desired.clone(),
+            }),
 
             (FileResource::Sourced { .. }, FileState::Sourced) => None,
 
-            (FileResource::Linked { source, path }, FileState::NotLinked) => {
-                Some(FileChange::CreateSymlink {
-                    source: source.clone(),
-                    path: path.clone(),
-                })
-            }
+            (
+                FileResource::Contents {
+                    contents,
+                    path,
+                    restarts,
+                },
+                FileState::NotSourced { current, desired },
+            ) => Some(FileChange::Write {
+                path: path.clone(),
+                source: FileSource::Contents(contents.as_bytes().into()),
+                restarts: restarts.clone(),
+                before: current.clone(),
+                after: desired.clone(),
+            }),
+
+            (FileResource::Contents { .. }, FileState::Sourced) => None,
+
+            (
+                FileResource::Linked {
+                    source,
+                    path,
+                    restarts,
+                },
+                FileState::NotLinked,
+            ) => Some(FileChange::CreateSymlink {
+                source: source.clone(),
+                path: path.clone(),
+                restarts: restarts.clone(),
+            }),
 
             (FileResource::Linked { .. }, FileState::Linked) => None,
 
-            (FileResource::Secret { name, path }, FileState::NotSourced) => {
+            (
+                FileResource::Secret {
+                    name,
+                    path,
+                    restarts,
+                },
+                FileState::NotSourced { current, desired },
+            ) => Some(FileChange::Write {
+                path: path.clone(),
+                source: FileSource::Secret(name.clone()),
+                restarts: restarts.clone(),
+                before: current.clone(),
+                after: desired.clone(),
+            }),
+
+            (FileResource::Secret { .. }, FileState::Sourced) => None,
+
+            (FileResource::Present { path, restarts }, FileState::Absent) => {
                 Some(FileChange::Write {
                     path: path.clone(),
-                    source: FileSource::Secret(name.clone()),
+                    source: FileSource::Contents(Arc::from([])),
+                    restarts: restarts.clone(),
+                    before: None,
+                    after: Some(FileDigest::of(&[])),
                 })
             }
 
-            (FileResource::Secret { .. }, FileState::Sourced) => None,
-
-            (FileResource::Present { path }, FileState::Absent) => Some(FileChange::Write {
-                path: path.clone(),
-                source: FileSource::Contents(Vec::new()),
-            }),
-
             (FileResource::Present { .. }, FileState::Present) => None,
 
-            (FileResource::Absent { path }, FileState::Present) => {
-                Some(FileChange::Remove { path: path.clone() })
+            (FileResource::Absent { path, restarts }, FileState::Present) => {
+                Some(FileChange::Remove {
+                    path: path.clone(),
+                    restarts: restarts.clone(),
+                })
             }
 
             (FileResource::Absent { .. }, FileState::Absent) => None,
 
-            (FileResource::Mode { path, mode }, FileState::ModeIncorrect) => {
+            (FileResource::Mode { path, mode }, FileState::ModeIncorrect { current }) => {
                 Some(FileChange::ChangeMode {
                     path: path.clone(),
                     mode: *mode,
+                    before: *current,
                 })
             }
 
             (FileResource::Mode { .. }, FileState::ModeCorrect) => None,
 
-            (FileResource::User { path, user }, FileState::UserIncorrect) => {
+            (FileResource::User { path, user }, FileState::UserIncorrect { current }) => {
                 Some(FileChange::ChangeOwner {
                     path: path.clone(),
                     user: Some(user.clone()),
                     group: None,
+                    before: current.clone(),
                 })
             }
 
             (FileResource::User { .. }, FileState::UserCorrect) => None,
 
-            (FileResource::Group { path, group }, FileState::GroupIncorrect) => {
+            (FileResource::Group { path, group }, FileState::GroupIncorrect { current }) => {
                 Some(FileChange::ChangeOwner {
                     path: path.clone(),
                     user: None,
                     group: Some(group.clone()),
+                    before: current.clone(),
                 })
             }
 
             (FileResource::Group { .. }, FileState::GroupCorrect) => None,
 
+            (FileResource::Parent { path, parents }, FileState::Absent) => {
+                Some(FileChange::CreateParent {
+                    path: path.clone(),
+                    parents: parents.clone(),
+                })
+            }
+
+            (FileResource::Parent { .. }, FileState::Present) => None,
+
+            (
+                FileResource::Extraneous { path, restarts, .. },
+                FileState::Extraneous { files, directories },
+            ) => Some(FileChange::RemoveExtraneous {
+                path: path.clone(),
+                files: files.clone(),
+                directories: directories.clone(),
+                restarts: restarts.clone(),
+            }),
+
+            (FileResource::Extraneous { .. }, FileState::NoExtraneous) => None,
+
             _ => {
                 // TODO (mw): Return an error. Which means changing the trait's change method.
                 // Or, alternatively, we have separate resources for each case, so there's no
@@ -591,24 +1495,232 @@ impl ResourceType for File {
     }
 
     fn operations(change: Self::Change) -> Vec<CausalityTree<Operation>> {
-        let op = match change {
-            FileChange::Write { path, source } => {
-                Operation::File(FileOperation::Write { path, source })
-            }
-            FileChange::CreateSymlink { source, path } => {
-                Operation::File(FileOperation::CreateSymlink { source, path })
+        let (op, restarts) = match change {
+            FileChange::Write {
+                path,
+                source,
+                restarts,
+                ..
+            } => (
+                Operation::File(FileOperation::Write { path, source }),
+                restarts,
+            ),
+            FileChange::CreateSymlink {
+                source,
+                path,
+                restarts,
+            } => (
+                Operation::File(FileOperation::CreateSymlink { source, path }),
+                restarts,
+            ),
+            FileChange::Remove { path, restarts } => {
+                (Operation::File(FileOperation::Remove { path }), restarts)
             }
-            FileChange::Remove { path } => Operation::File(FileOperation::Remove { path }),
-            FileChange::ChangeMode { path, mode } => {
-                Operation::File(FileOperation::ChangeMode { path, mode })
+            FileChange::ChangeMode { path, mode, .. } => (
+                Operation::File(FileOperation::ChangeMode { path, mode }),
+                None,
+            ),
+            FileChange::ChangeOwner {
+                path, user, group, ..
+            } => (
+                Operation::File(FileOperation::ChangeOwner { path, user, group }),
+                None,
+            ),
+            FileChange::CreateParent { path, parents } => {
+                return create_parent_operations(path, parents);
             }
-            FileChange::ChangeOwner { path, user, group } => {
-                Operation::File(FileOperation::ChangeOwner { path, user, group })
+            FileChange::RemoveExtraneous {
+                files,
+                directories,
+                restarts,
+                ..
+            } => {
+                return remove_extraneous_operations(files, directories, restarts);
             }
         };
 
-        vec![CausalityTree::leaf(CausalityMeta::default(), op)]
+        let Some(name) = restarts else {
+            return vec![CausalityTree::leaf(CausalityMeta::default(), op)];
+        };
+        // The restart only exists because this change does, so it lands in an
+        // epoch after the write and never on an apply where the file was
+        // already up to date.
+        //
+        // Note(cc): the restart is ordered after the content operation only.
+        // A mode/owner fix on the same file is a sibling change we can't see
+        // from here, so it may share the restart's epoch.
+        vec![
+            CausalityTree::leaf(CausalityMeta::id("file".into()), op),
+            CausalityTree::leaf(
+                CausalityMeta::requires(vec!["file".into()]),
+                Operation::Service(ServiceOperation::Restart { unit: name }),
+            ),
+        ]
+    }
+}
+
+/// Create a missing parent directory, then give it the `parents` permissions.
+/// Only reached when the directory didn't exist, so existing directories keep
+/// theirs.
+fn create_parent_operations(path: FilePath, parents: FileParents) -> Vec<CausalityTree<Operation>> {
+    let FileParents { mode, user, group } = parents;
+    let mut operations = vec![CausalityTree::leaf(
+        CausalityMeta::id("create".into()),
+        Operation::Directory(DirectoryOperation::Create { path: path.clone() }),
+    )];
+    if let Some(mode) = mode {
+        operations.push(CausalityTree::leaf(
+            CausalityMeta::requires(vec!["create".into()]),
+            Operation::Directory(DirectoryOperation::ChangeMode {
+                path: path.clone(),
+                mode,
+            }),
+        ));
+    }
+    if user.is_some() || group.is_some() {
+        operations.push(CausalityTree::leaf(
+            CausalityMeta::requires(vec!["create".into()]),
+            Operation::Directory(DirectoryOperation::ChangeOwner { path, user, group }),
+        ));
+    }
+    operations
+}
+
+/// Remove each of a synced tree's extraneous files and directories, then
+/// restart `restarts` once they're gone.
+fn remove_extraneous_operations(
+    files: Vec<FilePath>,
+    directories: Vec<FilePath>,
+    restarts: Option<String>,
+) -> Vec<CausalityTree<Operation>> {
+    let removes: Vec<_> = files
+        .into_iter()
+        .map(|path| Operation::File(FileOperation::Remove { path }))
+        .chain(
+            directories
+                .into_iter()
+                .map(|path| Operation::Directory(DirectoryOperation::Remove { path })),
+        )
+        .map(|op| CausalityTree::leaf(CausalityMeta::default(), op))
+        .collect();
+    let Some(name) = restarts else {
+        return removes;
+    };
+    vec![
+        CausalityTree::branch(CausalityMeta::id("remove".into()), removes),
+        CausalityTree::leaf(
+            CausalityMeta::requires(vec!["remove".into()]),
+            Operation::Service(ServiceOperation::Restart { unit: name }),
+        ),
+    ]
+}
+
+/// `relative`, a synced tree's entry, under `root`.
+fn synced_path(root: &FilePath, relative: &str) -> FilePath {
+    root.join(relative)
+        .expect("a directory entry's name has no NUL")
+}
+
+/// The files and directories under the synced tree at `root` that aren't in
+/// `keep`, relative to it. A directory that isn't is removed whole, so isn't
+/// walked into; nor is a symlink, whatever it points at. Nothing is
+/// extraneous under a `root` that doesn't exist yet.
+///
+/// Note(cc): an entry that's a file on one side and a directory on the other
+/// is kept, so the sync's write into it fails rather than it being replaced.
+async fn find_extraneous(
+    root: &FilePath,
+    keep: &[String],
+) -> Result<(Vec<FilePath>, Vec<FilePath>), FsError> {
+    let keep: HashSet<&str> = keep.iter().map(String::as_str).collect();
+    let mut files = Vec::new();
+    let mut directories = Vec::new();
+    if !fs::path_exists(root.as_path()).await? {
+        return Ok((files, directories));
+    }
+    let mut pending = vec![String::new()];
+    while let Some(relative) = pending.pop() {
+        for child in fs::read_dir(root.as_path().join(&relative)).await? {
+            // Note(cc): a name that isn't UTF-8 can't be a `FilePath`, so is
+            // left alone.
+            let Some(name) = child.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let child_relative = if relative.is_empty() {
+                name.to_owned()
+            } else {
+                format!("{relative}/{name}")
+            };
+            let metadata = tokio::fs::symlink_metadata(&child)
+                .await
+                .map_err(|source| FsError::Metadata {
+                    path: child.clone(),
+                    source,
+                })?;
+            if keep.contains(child_relative.as_str()) {
+                if metadata.is_dir() {
+                    pending.push(child_relative);
+                }
+                continue;
+            }
+            let path = synced_path(root, &child_relative);
+            if metadata.is_dir() {
+                directories.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    directories.sort();
+    Ok((files, directories))
+}
+
+/// Compare the file at `path` against `desired`. With `digest`, a mismatch
+/// carries a [`FileDigest`] of each side for the rendered change.
+async fn probe_contents(
+    path: &FilePath,
+    desired: &[u8],
+    digest: bool,
+) -> Result<FileState, FileStateError> {
+    let exists = fs::path_exists(path.as_path()).await?;
+    if exists && fs::file_equals(path.as_path(), desired).await? {
+        return Ok(FileState::Sourced);
+    }
+    if !digest {
+        return Ok(FileState::NotSourced {
+            current: None,
+            desired: None,
+        });
+    }
+    let current = if exists {
+        Some(FileDigest::of_file(path.as_path()).await?)
+    } else {
+        None
+    };
+    Ok(FileState::NotSourced {
+        current,
+        desired: Some(FileDigest::of(desired)),
+    })
+}
+
+/// Compare the file at `path` against the host file at `source`, as
+/// [`probe_contents`] does, without reading either into memory whole: a
+/// sourced file can be a multi-hundred-MB image or archive.
+async fn probe_sourced(source: &FilePath, path: &FilePath) -> Result<FileState, FileStateError> {
+    let exists = fs::path_exists(path.as_path()).await?;
+    if exists && fs::files_equal(source.as_path(), path.as_path()).await? {
+        return Ok(FileState::Sourced);
     }
+    let current = if exists {
+        Some(FileDigest::of_file(path.as_path()).await?)
+    } else {
+        None
+    };
+    Ok(FileState::NotSourced {
+        current,
+        desired: Some(FileDigest::of_file(source.as_path()).await?),
+    })
 }
 
 /// Probe `path` for whether it's a symlink with the desired `source` target.
@@ -655,6 +1767,7 @@ mod tests {
         let resource = FileResource::Sourced {
             source: file_path(&source),
             path: file_path(&target),
+            restarts: None,
         };
         let mut ctx = lusid_ctx::Context::create(dir.path()).unwrap();
         let state = File::state(&mut ctx, &resource).await.unwrap();
@@ -672,10 +1785,15 @@ mod tests {
         let resource = FileResource::Sourced {
             source: file_path(&source),
             path: file_path(&target),
+            restarts: None,
         };
         let mut ctx = lusid_ctx::Context::create(dir.path()).unwrap();
         let state = File::state(&mut ctx, &resource).await.unwrap();
-        assert!(matches!(state, FileState::NotSourced));
+        let FileState::NotSourced { current, desired } = state else {
+            panic!("expected NotSourced, got {state:?}");
+        };
+        assert_eq!(current, Some(FileDigest::of(b"old")));
+        assert_eq!(desired, Some(FileDigest::of(b"new")));
     }
 
     #[tokio::test]
@@ -688,10 +1806,36 @@ mod tests {
         let resource = FileResource::Sourced {
             source: file_path(&source),
             path: file_path(&target),
+            restarts: None,
+        };
+        let mut ctx = lusid_ctx::Context::create(dir.path()).unwrap();
+        let state = File::state(&mut ctx, &resource).await.unwrap();
+        assert!(matches!(state, FileState::NotSourced { .. }));
+    }
+
+    // --- Contents state probe -------------------------------------------
+
+    #[tokio::test]
+    async fn contents_probe_compares_bytes() {
+        let dir = tempdir().unwrap();
+        let target = dir.path().join("app.env");
+        let resource = FileResource::Contents {
+            contents: "PORT=8080\n".into(),
+            path: file_path(&target),
+            restarts: None,
         };
         let mut ctx = lusid_ctx::Context::create(dir.path()).unwrap();
+
+        let state = File::state(&mut ctx, &resource).await.unwrap();
+        assert!(matches!(state, FileState::NotSourced { .. }));
+
+        tokio::fs::write(&target, b"PORT=80\n").await.unwrap();
+        let state = File::state(&mut ctx, &resource).await.unwrap();
+        assert!(matches!(state, FileState::NotSourced { .. }));
+
+        tokio::fs::write(&target, b"PORT=8080\n").await.unwrap();
         let state = File::state(&mut ctx, &resource).await.unwrap();
-        assert!(matches!(state, FileState::NotSourced));
+        assert!(matches!(state, FileState::Sourced));
     }
 
     // --- Linked state probe (lexical-symlink-target) --------------------
@@ -760,12 +1904,18 @@ mod tests {
         let resource = FileResource::Sourced {
             source: FilePath::new("/host/src.txt"),
             path: FilePath::new("/target/dest.txt"),
+            restarts: None,
+        };
+        let state = FileState::NotSourced {
+            current: None,
+            desired: None,
         };
-        let change = File::change(&resource, &FileState::NotSourced).expect("some change");
+        let change = File::change(&resource, &state).expect("some change");
         match change {
             FileChange::Write {
                 path,
                 source: FileSource::Path(s),
+                ..
             } => {
                 assert_eq!(path.as_path(), std::path::Path::new("/target/dest.txt"));
                 assert_eq!(s.as_path(), std::path::Path::new("/host/src.txt"));
@@ -779,14 +1929,200 @@ mod tests {
         let resource = FileResource::Linked {
             source: FilePath::new("/host/src.txt"),
             path: FilePath::new("/target/dest.txt"),
+            restarts: None,
         };
         let change = File::change(&resource, &FileState::NotLinked).expect("some change");
         match change {
-            FileChange::CreateSymlink { source, path } => {
+            FileChange::CreateSymlink { source, path, .. } => {
                 assert_eq!(source.as_path(), std::path::Path::new("/host/src.txt"));
                 assert_eq!(path.as_path(), std::path::Path::new("/target/dest.txt"));
             }
             other => panic!("expected CreateSymlink, got {other:?}"),
         }
     }
+
+    // --- Restarts ---------------------------------------------------------
+
+    #[test]
+    fn content_change_with_restarts_emits_restart_after_write() {
+        let change = FileChange::Write {
+            path: FilePath::new("/etc/nginx/nginx.conf"),
+            source: FileSource::Contents(b"events {}\n".as_slice().into()),
+            restarts: Some("nginx.service".into()),
+            before: None,
+            after: None,
+        };
+        let ops = File::operations(change);
+        assert_eq!(ops.len(), 2);
+        let CausalityTree::Leaf { meta, node } = &ops[1] else {
+            panic!("expected leaf");
+        };
+        assert_eq!(meta.requires, vec!["file".to_string()]);
+        assert!(matches!(
+            node,
+            Operation::Service(ServiceOperation::Restart { unit }) if unit == "nginx.service"
+        ));
+    }
+
+    #[test]
+    fn content_change_without_restarts_emits_only_the_write() {
+        let change = FileChange::Write {
+            path: FilePath::new("/etc/nginx/nginx.conf"),
+            source: FileSource::Contents(b"events {}\n".as_slice().into()),
+            restarts: None,
+            before: None,
+            after: None,
+        };
+        let ops = File::operations(change);
+        assert_eq!(ops.len(), 1);
+        let CausalityTree::Leaf { node, .. } = &ops[0] else {
+            panic!("expected leaf");
+        };
+        assert!(matches!(
+            node,
+            Operation::File(FileOperation::Write { path, .. })
+                if path.to_string() == "/etc/nginx/nginx.conf"
+        ));
+    }
+
+    // --- Parents ----------------------------------------------------------
+
+    #[test]
+    fn parents_emit_an_atom_per_ancestor_before_the_file() {
+        let params = FileParams::Present {
+            path: FilePath::new("/etc/nginx/conf.d/site.conf"),
+            mode: None,
+            user: None,
+            group: None,
+            restarts: None,
+            parents: Some(FileParents::default()),
+        };
+        let atoms = File::resources(params);
+        let parents: Vec<String> = atoms
+            .iter()
+            .filter_map(|atom| match atom {
+                CausalityTree::Leaf {
+                    node: FileResource::Parent { path, .. },
+                    ..
+                } => Some(path.to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(parents, vec!["/etc", "/etc/nginx", "/etc/nginx/conf.d"]);
+        let CausalityTree::Leaf { meta, .. } = &atoms[3] else {
+            panic!("expected leaf");
+        };
+        assert_eq!(meta.id.as_deref(), Some("file"));
+        assert_eq!(meta.requires, vec!["parent-2".to_string()]);
+    }
+
+    // --- Synced -----------------------------------------------------------
+
+    #[test]
+    fn synced_walk_lists_directories_before_their_contents() {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("lua/plugins")).unwrap();
+        std::fs::write(dir.path().join("init.lua"), b"x").unwrap();
+        std::fs::write(dir.path().join("lua/plugins/lsp.lua"), b"x").unwrap();
+        std::fs::write(dir.path().join("lua/options.lua"), b"x").unwrap();
+
+        let span = Span::new(SourceId::empty(), 0, 0);
+        let entries = walk_synced_source(dir.path(), &span).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                FileSyncEntry::File("init.lua".into()),
+                FileSyncEntry::Directory("lua".into()),
+                FileSyncEntry::File("lua/options.lua".into()),
+                FileSyncEntry::Directory("lua/plugins".into()),
+                FileSyncEntry::File("lua/plugins/lsp.lua".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn synced_files_require_their_directory() {
+        let params = FileParams::Synced {
+            source: FilePath::new("/home/me/dotfiles/nvim"),
+            source_span: Span::new(SourceId::empty(), 0, 0),
+            path: FilePath::new("/home/me/.config/nvim"),
+            entries: vec![
+                FileSyncEntry::Directory("lua".into()),
+                FileSyncEntry::File("lua/options.lua".into()),
+            ],
+            delete: true,
+            restarts: None,
+            parents: None,
+        };
+        let atoms: Vec<(Option<String>, Vec<String>, String)> = File::resources(params)
+            .into_iter()
+            .map(|atom| match atom {
+                CausalityTree::Leaf { meta, node } => (meta.id, meta.requires, node.to_string()),
+                CausalityTree::Branch { .. } => panic!("expected leaf"),
+            })
+            .collect();
+        assert_eq!(
+            atoms,
+            vec![
+                (Some("dir".into()), vec![], "FileParent(/home/me/.config/nvim)".into()),
+                (
+                    Some("dir:lua".into()),
+                    vec!["dir".into()],
+                    "FileParent(/home/me/.config/nvim/lua)".into()
+                ),
+                (
+                    None,
+                    vec!["dir:lua".into()],
+                    "FileSourced(/home/me/dotfiles/nvim/lua/options.lua -> /home/me/.config/nvim/lua/options.lua)".into()
+                ),
+                (
+                    None,
+                    vec![],
+                    "FileExtraneous(/home/me/.config/nvim, keep = 2 entries)".into()
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn extraneous_skips_kept_entries_and_removes_directories_whole() {
+        let dir = tempdir().unwrap();
+        tokio::fs::create_dir_all(dir.path().join("lua/old"))
+            .await
+            .unwrap();
+        tokio::fs::create_dir_all(dir.path().join("after/ftplugin"))
+            .await
+            .unwrap();
+        tokio::fs::write(dir.path().join("init.lua"), b"x")
+            .await
+            .unwrap();
+        tokio::fs::write(dir.path().join("init.vim"), b"x")
+            .await
+            .unwrap();
+        tokio::fs::write(dir.path().join("lua/options.lua"), b"x")
+            .await
+            .unwrap();
+        tokio::fs::write(dir.path().join("lua/old/keys.lua"), b"x")
+            .await
+            .unwrap();
+        tokio::fs::write(dir.path().join("after/ftplugin/rust.lua"), b"x")
+            .await
+            .unwrap();
+
+        let root = file_path(dir.path());
+        let keep = vec!["init.lua".into(), "lua".into(), "lua/options.lua".into()];
+        let (files, directories) = find_extraneous(&root, &keep).await.unwrap();
+        assert_eq!(files, vec![file_path(&dir.path().join("init.vim"))]);
+        assert_eq!(
+            directories,
+            vec![
+                file_path(&dir.path().join("after")),
+                file_path(&dir.path().join("lua/old")),
+            ]
+        );
+
+        let missing = file_path(&dir.path().join("missing"));
+        let (files, directories) = find_extraneous(&missing, &keep).await.unwrap();
+        assert!(files.is_empty() && directories.is_empty());
+    }
 }
@@ -0,0 +1,444 @@
+//! `@core/firewall`: incoming firewall rules, managed with ufw (see
+//! [`lusid_operation::operations::firewall`]).
+//!
+//! A `rules` list expands to one atom per rule, each requiring the one
+//! before, so a list's rules are added in the order it gives them: ufw
+//! applies the first rule that matches. Order rules across plan items with
+//! `requires`, as for anything else.
+//!
+//! Note(cc): this only adds and deletes rules. It doesn't enable ufw, or set
+//! its default policies, as doing either over SSH without an allow rule for
+//! it already in place cuts the session off. Run `ufw --force enable` with
+//! `@core/command`, requiring the rules.
+
+use std::fmt::Display;
+
+use async_trait::async_trait;
+use lusid_causality::{CausalityMeta, CausalityTree};
+use lusid_ctx::Context;
+use lusid_operation::{
+    Operation,
+    operations::firewall::{
+        FirewallAction, FirewallApplyError, FirewallOperation, FirewallProtocol, FirewallRule,
+        read_rules,
+    },
+};
+use lusid_params::{ParseError, ParseParams, StructFields, parse_list};
+use lusid_view::impl_display_render;
+use rimu::{Spanned, Value};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
+
+/// One rule, and whether it should be there.
+#[derive(Debug, Clone, Serialize)]
+pub struct FirewallRuleParams {
+    pub rule: FirewallRule,
+    pub present: bool,
+}
+
+impl ParseParams for FirewallRuleParams {
+    fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
+        let mut fields = StructFields::new(value)?;
+        let out = parse_rule_fields(&mut fields)?;
+        fields.finish()?;
+        Ok(out)
+    }
+}
+
+impl Display for FirewallRuleParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self { rule, present } = self;
+        if *present {
+            write!(f, "{rule}")
+        } else {
+            write!(f, "absent {rule}")
+        }
+    }
+}
+
+// TODO(cc): reject a port range without a `protocol` at param-time. ufw
+// refuses to add one, so today it fails at apply time.
+fn parse_rule_fields(fields: &mut StructFields) -> Result<FirewallRuleParams, Spanned<ParseError>> {
+    let action = match fields.take_discriminator("action", &["allow", "deny", "reject", "limit"])? {
+        "allow" => FirewallAction::Allow,
+        "deny" => FirewallAction::Deny,
+        "reject" => FirewallAction::Reject,
+        "limit" => FirewallAction::Limit,
+        _ => unreachable!(),
+    };
+    let from = fields.optional_string("from")?;
+    // A rule matches on a port, a source, or both.
+    let port = if from.is_some() {
+        fields.optional("port", parse_port)?
+    } else {
+        Some(fields.required("port", parse_port)?)
+    };
+    let protocol = if fields.has("protocol") {
+        match fields.take_discriminator("protocol", &["tcp", "udp"])? {
+            "tcp" => Some(FirewallProtocol::Tcp),
+            "udp" => Some(FirewallProtocol::Udp),
+            _ => unreachable!(),
+        }
+    } else {
+        None
+    };
+    let present = if fields.has("state") {
+        fields.take_discriminator("state", &["present", "absent"])? == "present"
+    } else {
+        true
+    };
+    Ok(FirewallRuleParams {
+        rule: FirewallRule {
+            action,
+            port,
+            protocol,
+            from,
+        },
+        present,
+    })
+}
+
+/// A port number, or a `"first:last"` range.
+fn parse_port(value: Spanned<Value>) -> Result<String, Spanned<ParseError>> {
+    let (value, span) = value.take();
+    let port = match &value {
+        Value::Number(number) => number
+            .to_u32()
+            .filter(|port| u16::try_from(*port).is_ok())
+            .map(|port| port.to_string()),
+        Value::String(port) => {
+            let valid = match port.split_once(':') {
+                Some((first, last)) => first.parse::<u16>().is_ok() && last.parse::<u16>().is_ok(),
+                None => port.parse::<u16>().is_ok(),
+            };
+            valid.then(|| port.clone())
+        }
+        _ => None,
+    };
+    port.ok_or_else(|| {
+        Spanned::new(
+            ParseError::TypeMismatch {
+                expected: "port number or \"first:last\" port range",
+                got: Box::new(value),
+            },
+            span,
+        )
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FirewallParams {
+    Rule(FirewallRuleParams),
+    Rules { rules: Vec<FirewallRuleParams> },
+}
+
+impl ParseParams for FirewallParams {
+    fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
+        let mut fields = StructFields::new(value)?;
+        let out = if fields.has("rules") {
+            FirewallParams::Rules {
+                rules: fields.required("rules", |value| {
+                    parse_list(value, FirewallRuleParams::parse_params)
+                })?,
+            }
+        } else {
+            FirewallParams::Rule(parse_rule_fields(&mut fields)?)
+        };
+        fields.finish()?;
+        Ok(out)
+    }
+}
+
+impl Display for FirewallParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FirewallParams::Rule(rule) => write!(f, "Firewall({rule})"),
+            FirewallParams::Rules { rules } => {
+                let rules: Vec<String> = rules.iter().map(ToString::to_string).collect();
+                write!(f, "Firewall(rules = [{}])", rules.join(", "))
+            }
+        }
+    }
+}
+
+impl_display_render!(FirewallParams);
+
+#[derive(Debug, Clone)]
+pub enum FirewallResource {
+    Present(FirewallRule),
+    Absent(FirewallRule),
+}
+
+impl FirewallResource {
+    fn rule(&self) -> &FirewallRule {
+        match self {
+            FirewallResource::Present(rule) | FirewallResource::Absent(rule) => rule,
+        }
+    }
+}
+
+impl Display for FirewallResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FirewallResource::Present(rule) => write!(f, "Firewall::Present({rule})"),
+            FirewallResource::Absent(rule) => write!(f, "Firewall::Absent({rule})"),
+        }
+    }
+}
+
+impl_display_render!(FirewallResource);
+
+#[derive(Debug, Clone)]
+pub enum FirewallState {
+    Present,
+    Absent,
+}
+
+impl Display for FirewallState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FirewallState::Present => write!(f, "Firewall::Present"),
+            FirewallState::Absent => write!(f, "Firewall::Absent"),
+        }
+    }
+}
+
+impl_display_render!(FirewallState);
+
+#[derive(Error, Debug)]
+pub enum FirewallStateError {
+    #[error(transparent)]
+    Read(#[from] FirewallApplyError),
+}
+
+#[derive(Debug, Clone)]
+pub enum FirewallChange {
+    Add(FirewallRule),
+    Delete(FirewallRule),
+}
+
+impl Display for FirewallChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FirewallChange::Add(rule) => write!(f, "Firewall::Add({rule})"),
+            FirewallChange::Delete(rule) => write!(f, "Firewall::Delete({rule})"),
+        }
+    }
+}
+
+impl_display_render!(FirewallChange);
+
+#[derive(Debug, Clone)]
+pub struct Firewall;
+
+#[async_trait]
+impl ResourceType for Firewall {
+    const ID: &'static str = "firewall";
+    const DESCRIPTION: &'static str =
+        "Add or delete incoming firewall rules with ufw, in the order they're listed.";
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[
+        ParamsDoc {
+            description: "One rule.",
+            params: RULE_PARAMS,
+        },
+        ParamsDoc {
+            description: "Rules, added in order.",
+            params: &[ParamDoc::required(
+                "rules",
+                ParamDocType::ObjectList,
+                "Rules, each with the params of one rule: `action`, and `port`, `protocol`, `from` and `state` as needed.",
+            )],
+        },
+    ];
+
+    type Params = FirewallParams;
+    type Resource = FirewallResource;
+
+    fn resources(params: Self::Params) -> Vec<CausalityTree<Self::Resource>> {
+        fn resource(params: FirewallRuleParams) -> FirewallResource {
+            let FirewallRuleParams { rule, present } = params;
+            if present {
+                FirewallResource::Present(rule)
+            } else {
+                FirewallResource::Absent(rule)
+            }
+        }
+
+        match params {
+            FirewallParams::Rule(params) => {
+                vec![CausalityTree::leaf(
+                    CausalityMeta::default(),
+                    resource(params),
+                )]
+            }
+            FirewallParams::Rules { rules } => rules
+                .into_iter()
+                .enumerate()
+                .map(|(index, params)| {
+                    let meta = CausalityMeta {
+                        id: Some(format!("rule-{index}")),
+                        requires: match index {
+                            0 => vec![],
+                            _ => vec![format!("rule-{}", index - 1)],
+                        },
+                        required_by: vec![],
+                    };
+                    CausalityTree::leaf(meta, resource(params))
+                })
+                .collect(),
+        }
+    }
+
+    type State = FirewallState;
+    type StateError = FirewallStateError;
+
+    async fn state(
+        ctx: &mut Context,
+        resource: &Self::Resource,
+    ) -> Result<Self::State, Self::StateError> {
+        let mut states = Self::states_bulk(ctx, &[resource]).await?;
+        Ok(states.remove(0))
+    }
+
+    async fn states_bulk(
+        _ctx: &mut Context,
+        resources: &[&Self::Resource],
+    ) -> Result<Vec<Self::State>, Self::StateError> {
+        let current = read_rules().await?;
+        Ok(resources
+            .iter()
+            .map(|resource| {
+                if current.contains(resource.rule()) {
+                    FirewallState::Present
+                } else {
+                    FirewallState::Absent
+                }
+            })
+            .collect())
+    }
+
+    type Change = FirewallChange;
+
+    fn change(resource: &Self::Resource, state: &Self::State) -> Option<Self::Change> {
+        match (resource, state) {
+            (FirewallResource::Present(rule), FirewallState::Absent) => {
+                Some(FirewallChange::Add(rule.clone()))
+            }
+            (FirewallResource::Absent(rule), FirewallState::Present) => {
+                Some(FirewallChange::Delete(rule.clone()))
+            }
+            (FirewallResource::Present(_), FirewallState::Present)
+            | (FirewallResource::Absent(_), FirewallState::Absent) => None,
+        }
+    }
+
+    fn operations(change: Self::Change) -> Vec<CausalityTree<Operation>> {
+        let operation = match change {
+            FirewallChange::Add(rule) => FirewallOperation::Add { rule },
+            FirewallChange::Delete(rule) => FirewallOperation::Delete { rule },
+        };
+        vec![CausalityTree::leaf(
+            CausalityMeta::default(),
+            Operation::Firewall(operation),
+        )]
+    }
+}
+
+const RULE_PARAMS: &[ParamDoc] = &[
+    ParamDoc::required(
+        "action",
+        ParamDocType::OneOf(&[
+            ParamDocType::Literal("allow"),
+            ParamDocType::Literal("deny"),
+            ParamDocType::Literal("reject"),
+            ParamDocType::Literal("limit"),
+        ]),
+        "What to do with matching traffic. `reject` tells the sender; `limit` allows all but addresses that connect 6 times in 30 seconds.",
+    ),
+    ParamDoc::optional(
+        "port",
+        ParamDocType::OneOf(&[ParamDocType::Number, ParamDocType::String]),
+        "Port, or `\"first:last\"` range, the traffic is to. Required without `from`.",
+    ),
+    ParamDoc::optional(
+        "protocol",
+        ParamDocType::OneOf(&[ParamDocType::Literal("tcp"), ParamDocType::Literal("udp")]),
+        "Protocol to match, defaults to both. Required with a port range.",
+    ),
+    ParamDoc::optional(
+        "from",
+        ParamDocType::String,
+        "Source address or CIDR block to match, defaults to anywhere.",
+    ),
+    ParamDoc::optional(
+        "state",
+        ParamDocType::OneOf(&[
+            ParamDocType::Literal("present"),
+            ParamDocType::Literal("absent"),
+        ]),
+        "Whether the rule should exist, defaults to `present`.",
+    ),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(port: &str) -> FirewallRuleParams {
+        FirewallRuleParams {
+            rule: FirewallRule {
+                action: FirewallAction::Allow,
+                port: Some(port.into()),
+                protocol: Some(FirewallProtocol::Tcp),
+                from: None,
+            },
+            present: true,
+        }
+    }
+
+    #[test]
+    fn rules_are_chained_in_order() {
+        let resources = Firewall::resources(FirewallParams::Rules {
+            rules: vec![rule("22"), rule("80"), rule("443")],
+        });
+        let metas: Vec<(Option<String>, Vec<String>)> = resources
+            .iter()
+            .map(|tree| match tree {
+                CausalityTree::Leaf { meta, .. } => (meta.id.clone(), meta.requires.clone()),
+                _ => panic!("expected leaf"),
+            })
+            .collect();
+        assert_eq!(
+            metas,
+            vec![
+                (Some("rule-0".into()), vec![]),
+                (Some("rule-1".into()), vec!["rule-0".into()]),
+                (Some("rule-2".into()), vec!["rule-1".into()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn changes_follow_presence() {
+        let present = FirewallResource::Present(rule("22").rule);
+        let absent = FirewallResource::Absent(rule("22").rule);
+        assert!(Firewall::change(&present, &FirewallState::Present).is_none());
+        assert!(Firewall::change(&absent, &FirewallState::Absent).is_none());
+        assert_eq!(
+            Firewall::change(&present, &FirewallState::Absent)
+                .expect("change")
+                .to_string(),
+            "Firewall::Add(allow 22/tcp)"
+        );
+        assert_eq!(
+            Firewall::change(&absent, &FirewallState::Present)
+                .expect("change")
+                .to_string(),
+            "Firewall::Delete(allow 22/tcp)"
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::{fmt::Display, path::PathBuf};
+use std::{ffi::OsStr, fmt::Display, path::PathBuf};
 
 use async_trait::async_trait;
 use lusid_causality::{CausalityMeta, CausalityTree};
@@ -7,32 +7,44 @@ use lusid_ctx::Context;
 use lusid_fs::{self as fs, FsError};
 use lusid_operation::{
     Operation,
-    operations::{file::FilePath, git::GitOperation},
+    operations::{
+        file::FilePath,
+        git::{GitOperation, GitOwner},
+    },
 };
 use lusid_params::{ParseError, ParseParams, StructFields};
 use lusid_view::impl_display_render;
 use rimu::{Spanned, Value};
+use serde::Serialize;
 use thiserror::Error;
 
 use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
+use crate::resources::file::parse_file_path;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct GitParams {
     pub repo: String,
     pub path: FilePath,
     pub version: Option<String>,
     pub update: Option<bool>,
     pub force: Option<bool>,
+    pub user: Option<String>,
+    pub group: Option<String>,
+    pub depth: Option<u32>,
 }
 
 impl ParseParams for GitParams {
     fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
         let mut fields = StructFields::new(value)?;
         let repo = fields.required_string("repo")?;
-        let path = FilePath::new(fields.required_target_path("path")?);
+        let path = fields.required("path", parse_file_path)?;
         let version = fields.optional_string("version")?;
         let update = fields.optional_bool("update")?;
         let force = fields.optional_bool("force")?;
+        let user = fields.optional_string("user")?;
+        let group = fields.optional_string("group")?;
+        let depth = fields.optional_u32("depth")?;
         fields.finish()?;
         Ok(GitParams {
             repo,
@@ -40,6 +52,9 @@ impl ParseParams for GitParams {
             version,
             update,
             force,
+            user,
+            group,
+            depth,
         })
     }
 }
@@ -48,8 +63,15 @@ impl Display for GitParams {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Git(repo = {}, path = {}, version = {:?}, update = {:?}, force = {:?})",
-            self.repo, self.path, self.version, self.update, self.force
+            "Git(repo = {}, path = {}, version = {:?}, update = {:?}, force = {:?}, user = {:?}, group = {:?}, depth = {:?})",
+            self.repo,
+            self.path,
+            self.version,
+            self.update,
+            self.force,
+            self.user,
+            self.group,
+            self.depth
         )
     }
 }
@@ -63,20 +85,61 @@ pub struct GitResource {
     pub version: Option<String>,
     pub update: bool,
     pub force: bool,
+    /// Who owns the clone; every git command, including state probes, runs as them.
+    pub owner: GitOwner,
+    /// `git clone --depth`. Only applies to the initial clone.
+    pub depth: Option<u32>,
 }
 
 impl Display for GitResource {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Git(repo = {}, path = {}, version = {:?}, update = {}, force = {})",
-            self.repo, self.path, self.version, self.update, self.force
+            "Git(repo = {}, path = {}, version = {:?}, update = {}, force = {}, user = {:?}, group = {:?}, depth = {:?})",
+            self.repo,
+            self.path,
+            self.version,
+            self.update,
+            self.force,
+            self.owner.user,
+            self.owner.group,
+            self.depth
         )
     }
 }
 
 impl_display_render!(GitResource);
 
+/// What the resource's `version` names, resolved against the clone's local
+/// refs at state time. With no `version` and a detached HEAD, the pin is
+/// origin's default branch (`refs/remotes/origin/HEAD`).
+///
+/// Note(cc): resolved before any fetch, so a branch's `commit` is as of the
+/// last fetch, and a ref that only exists upstream is [`GitPin::Unknown`]
+/// until the checkout's fetch brings it in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GitPin {
+    /// A branch on origin, at `commit`.
+    Branch { name: String, commit: String },
+    /// A tag, peeled to the commit it points at.
+    Tag { name: String, commit: String },
+    /// A commit (full or abbreviated hash) present in the clone.
+    Commit { commit: String },
+    /// Not a known ref or commit locally.
+    Unknown { version: String },
+}
+
+impl Display for GitPin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GitPin::Branch { name, commit } => write!(f, "Branch({name} @ {commit})"),
+            GitPin::Tag { name, commit } => write!(f, "Tag({name} @ {commit})"),
+            GitPin::Commit { commit } => write!(f, "Commit({commit})"),
+            GitPin::Unknown { version } => write!(f, "Unknown({version})"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum GitState {
     Absent,
@@ -84,6 +147,7 @@ pub enum GitState {
         head: Option<String>,
         branch: Option<String>,
         is_dirty: bool,
+        pin: Option<GitPin>,
     },
 }
 
@@ -95,12 +159,17 @@ impl Display for GitState {
                 head,
                 branch,
                 is_dirty,
+                pin,
             } => {
                 write!(
                     f,
-                    "Git::Present(head = {:?}, branch = {:?}, is_dirty = {is_dirty})",
+                    "Git::Present(head = {:?}, branch = {:?}, is_dirty = {is_dirty}",
                     head, branch
-                )
+                )?;
+                if let Some(pin) = pin {
+                    write!(f, ", pin = {pin}")?;
+                }
+                write!(f, ")")
             }
         }
     }
@@ -137,39 +206,69 @@ pub enum GitChange {
     Clone {
         repo: String,
         path: FilePath,
+        depth: Option<u32>,
+        owner: GitOwner,
     },
     Checkout {
         path: FilePath,
         version: String,
         force: bool,
         fetch: bool,
+        owner: GitOwner,
     },
     Pull {
         path: FilePath,
+        owner: GitOwner,
     },
 }
 
 impl Display for GitChange {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            GitChange::Clone { repo, path } => {
-                write!(f, "Git::Clone(repo = {}, path = {})", repo, path)
+            GitChange::Clone {
+                repo,
+                path,
+                depth,
+                owner,
+            } => {
+                write!(f, "Git::Clone(repo = {}, path = {}", repo, path)?;
+                if let Some(depth) = depth {
+                    write!(f, ", depth = {depth}")?;
+                }
+                write_owner(f, owner)?;
+                write!(f, ")")
             }
             GitChange::Checkout {
                 path,
                 version,
                 force,
                 fetch,
-            } => write!(
-                f,
-                "Git::Checkout(path = {}, version = {}, force = {}, fetch = {})",
-                path, version, force, fetch
-            ),
-            GitChange::Pull { path } => write!(f, "Git::Pull(path = {})", path),
+                owner,
+            } => {
+                write!(
+                    f,
+                    "Git::Checkout(path = {}, version = {}, force = {}, fetch = {}",
+                    path, version, force, fetch
+                )?;
+                write_owner(f, owner)?;
+                write!(f, ")")
+            }
+            GitChange::Pull { path, owner } => {
+                write!(f, "Git::Pull(path = {}", path)?;
+                write_owner(f, owner)?;
+                write!(f, ")")
+            }
         }
     }
 }
 
+fn write_owner(f: &mut std::fmt::Formatter<'_>, owner: &GitOwner) -> std::fmt::Result {
+    if owner.is_set() {
+        write!(f, ", owner = {owner}")?;
+    }
+    Ok(())
+}
+
 impl_display_render!(GitChange);
 
 #[derive(Debug, Clone)]
@@ -178,6 +277,41 @@ pub struct Git;
 #[async_trait]
 impl ResourceType for Git {
     const ID: &'static str = "git";
+    const DESCRIPTION: &'static str = "Clone a git repository, and keep it at a version.";
+    const PLATFORMS: &'static [&'static str] = &["linux", "macos"];
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[ParamsDoc {
+        description: "A checkout.",
+        params: &[
+            ParamDoc::required("repo", ParamDocType::String, "URL to clone from."),
+            ParamDoc::required("path", ParamDocType::TargetPath, "Where to clone to."),
+            ParamDoc::optional(
+                "version",
+                ParamDocType::String,
+                "Branch, tag or commit to check out, defaults to the remote's HEAD.",
+            ),
+            ParamDoc::optional(
+                "update",
+                ParamDocType::Boolean,
+                "Fetch and move to the latest of `version`, defaults to true.",
+            ),
+            ParamDoc::optional(
+                "force",
+                ParamDocType::Boolean,
+                "Discard local changes in the way, defaults to false.",
+            ),
+            ParamDoc::optional("user", ParamDocType::String, "Owner of the checkout."),
+            ParamDoc::optional(
+                "group",
+                ParamDocType::String,
+                "Owning group of the checkout.",
+            ),
+            ParamDoc::optional(
+                "depth",
+                ParamDocType::Number,
+                "Shallow clone to this depth.",
+            ),
+        ],
+    }];
 
     type Params = GitParams;
     type Resource = GitResource;
@@ -191,6 +325,11 @@ impl ResourceType for Git {
                 version: params.version,
                 update: params.update.unwrap_or(true),
                 force: params.force.unwrap_or(false),
+                owner: GitOwner {
+                    user: params.user,
+                    group: params.group,
+                },
+                depth: params.depth,
             },
         )]
     }
@@ -251,10 +390,17 @@ impl ResourceType for Git {
             .ok()
             .map(|s| String::from_utf8_lossy(&s).trim().to_string());
 
+        let pin = match resource.version.as_deref() {
+            Some(version) => Some(resolve_pin(resource, version).await),
+            None if branch.is_none() => default_branch_pin(resource).await,
+            None => None,
+        };
+
         Ok(GitState::Present {
             head,
             branch,
             is_dirty,
+            pin,
         })
     }
 
@@ -265,32 +411,55 @@ impl ResourceType for Git {
             GitState::Absent => Some(GitChange::Clone {
                 repo: resource.repo.clone(),
                 path: resource.path.clone(),
+                depth: resource.depth,
+                owner: resource.owner.clone(),
             }),
             GitState::Present {
                 head,
                 branch,
                 is_dirty,
-            } => change_for_present(resource, head.as_deref(), branch.as_deref(), *is_dirty),
+                pin,
+            } => change_for_present(
+                resource,
+                head.as_deref(),
+                branch.as_deref(),
+                *is_dirty,
+                pin.as_ref(),
+            ),
         }
     }
 
     fn operations(change: Self::Change) -> Vec<CausalityTree<Operation>> {
         match change {
-            GitChange::Clone { repo, path } => vec![CausalityTree::leaf(
+            GitChange::Clone {
+                repo,
+                path,
+                depth,
+                owner,
+            } => vec![CausalityTree::leaf(
                 CausalityMeta::default(),
-                Operation::Git(GitOperation::Clone { repo, path }),
+                Operation::Git(GitOperation::Clone {
+                    repo,
+                    path,
+                    depth,
+                    owner,
+                }),
             )],
             GitChange::Checkout {
                 path,
                 version,
                 force,
                 fetch,
+                owner,
             } => {
                 if fetch {
                     vec![
                         CausalityTree::leaf(
                             CausalityMeta::id("fetch".into()),
-                            Operation::Git(GitOperation::Fetch { path: path.clone() }),
+                            Operation::Git(GitOperation::Fetch {
+                                path: path.clone(),
+                                owner: owner.clone(),
+                            }),
                         ),
                         CausalityTree::leaf(
                             CausalityMeta::requires(vec!["fetch".into()]),
@@ -298,6 +467,7 @@ impl ResourceType for Git {
                                 path,
                                 version,
                                 force,
+                                owner,
                             }),
                         ),
                     ]
@@ -308,13 +478,14 @@ impl ResourceType for Git {
                             path,
                             version,
                             force,
+                            owner,
                         }),
                     )]
                 }
             }
-            GitChange::Pull { path } => vec![CausalityTree::leaf(
+            GitChange::Pull { path, owner } => vec![CausalityTree::leaf(
                 CausalityMeta::default(),
-                Operation::Git(GitOperation::Pull { path }),
+                Operation::Git(GitOperation::Pull { path, owner }),
             )],
         }
     }
@@ -325,33 +496,97 @@ fn change_for_present(
     head: Option<&str>,
     branch: Option<&str>,
     is_dirty: bool,
+    pin: Option<&GitPin>,
 ) -> Option<GitChange> {
-    if let Some(version) = resource.version.as_deref() {
-        let matches = branch == Some(version) || head == Some(version);
-        if matches {
-            if !is_dirty && resource.update && branch == Some(version) {
-                return Some(GitChange::Pull {
-                    path: resource.path.clone(),
-                });
-            }
-            return None;
-        }
-
-        return Some(GitChange::Checkout {
+    let checkout = |version: &str, fetch: bool| {
+        Some(GitChange::Checkout {
             path: resource.path.clone(),
             version: version.to_string(),
             force: resource.force,
-            fetch: resource.update,
-        });
+            fetch,
+            owner: resource.owner.clone(),
+        })
+    };
+    let pull = || {
+        (!is_dirty && resource.update).then(|| GitChange::Pull {
+            path: resource.path.clone(),
+            owner: resource.owner.clone(),
+        })
+    };
+
+    match pin {
+        // Only a branch moves, so only a branch is pulled.
+        Some(GitPin::Branch { name, .. }) if branch == Some(name.as_str()) => pull(),
+        Some(GitPin::Branch { name, .. }) => checkout(name, resource.update),
+        // Tags and commits are fixed: once HEAD is there, there's nothing to do.
+        Some(GitPin::Tag { commit, .. } | GitPin::Commit { commit })
+            if head == Some(commit.as_str()) =>
+        {
+            None
+        }
+        Some(GitPin::Tag { name, .. }) => checkout(name, false),
+        Some(GitPin::Commit { commit }) => checkout(commit, false),
+        // The checkout can't succeed without fetching, whatever `update` says.
+        Some(GitPin::Unknown { version }) => checkout(version, true),
+        None if branch.is_some() => pull(),
+        None => None,
     }
+}
 
-    if !is_dirty && resource.update && branch.is_some() {
-        return Some(GitChange::Pull {
-            path: resource.path.clone(),
-        });
+/// Resolve `version` to a [`GitPin`]: a branch on origin first, then a tag,
+/// then a commit.
+async fn resolve_pin(resource: &GitResource, version: &str) -> GitPin {
+    if let Some(commit) = rev_parse(resource, &format!("refs/remotes/origin/{version}")).await {
+        return GitPin::Branch {
+            name: version.to_string(),
+            commit,
+        };
+    }
+    if let Some(commit) = rev_parse(resource, &format!("refs/tags/{version}^{{commit}}")).await {
+        return GitPin::Tag {
+            name: version.to_string(),
+            commit,
+        };
+    }
+    let is_hash = !version.is_empty() && version.chars().all(|c| c.is_ascii_hexdigit());
+    let commit = if is_hash {
+        rev_parse(resource, &format!("{version}^{{commit}}")).await
+    } else {
+        None
+    };
+    match commit {
+        Some(commit) => GitPin::Commit { commit },
+        None => GitPin::Unknown {
+            version: version.to_string(),
+        },
     }
+}
+
+/// Origin's default branch as a [`GitPin::Branch`], if the clone knows it.
+async fn default_branch_pin(resource: &GitResource) -> Option<GitPin> {
+    let output = git_run(
+        resource,
+        [
+            "symbolic-ref",
+            "--quiet",
+            "--short",
+            "refs/remotes/origin/HEAD",
+        ],
+    )
+    .await
+    .ok()?;
+    let remote_branch = String::from_utf8_lossy(&output).trim().to_string();
+    let name = remote_branch.strip_prefix("origin/")?.to_string();
+    let commit = rev_parse(resource, &format!("refs/remotes/origin/{name}")).await?;
+    Some(GitPin::Branch { name, commit })
+}
 
-    None
+/// The full commit hash `rev` resolves to, or `None` if it doesn't.
+async fn rev_parse(resource: &GitResource, rev: &str) -> Option<String> {
+    let output = git_run(resource, ["rev-parse", "--verify", "--quiet", rev])
+        .await
+        .ok()?;
+    Some(String::from_utf8_lossy(&output).trim().to_string())
 }
 
 fn resolve_git_dir(base: &std::path::Path, git_dir: &str) -> PathBuf {
@@ -363,11 +598,136 @@ fn resolve_git_dir(base: &std::path::Path, git_dir: &str) -> PathBuf {
     }
 }
 
-async fn git_run(
+async fn git_run<S: AsRef<OsStr>>(
     resource: &GitResource,
-    args: impl IntoIterator<Item = &'static str>,
+    args: impl IntoIterator<Item = S>,
 ) -> Result<Vec<u8>, CommandError> {
     let mut cmd = Command::new("git");
     cmd.arg("-C").arg(resource.path.as_path()).args(args);
-    cmd.run().await
+    // Probe as the owner too: git refuses to read a repo owned by someone
+    // else (`safe.directory`), which would surface as a bogus state error.
+    resource.owner.wrap(cmd).run().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resource(version: Option<&str>) -> GitResource {
+        GitResource {
+            repo: "https://github.com/ahdinosaur/lusid".into(),
+            path: FilePath::new("/home/me/src/lusid"),
+            version: version.map(Into::into),
+            update: true,
+            force: false,
+            owner: GitOwner::default(),
+            depth: None,
+        }
+    }
+
+    const HEAD: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    #[test]
+    fn tag_at_head_is_up_to_date() {
+        let pin = GitPin::Tag {
+            name: "v1.0.0".into(),
+            commit: HEAD.into(),
+        };
+        let change = change_for_present(
+            &resource(Some("v1.0.0")),
+            Some(HEAD),
+            None,
+            false,
+            Some(&pin),
+        );
+        assert!(change.is_none());
+    }
+
+    #[test]
+    fn tag_elsewhere_checks_out_without_fetch() {
+        let pin = GitPin::Tag {
+            name: "v1.0.0".into(),
+            commit: "fedcba".into(),
+        };
+        let change = change_for_present(
+            &resource(Some("v1.0.0")),
+            Some(HEAD),
+            Some("main"),
+            false,
+            Some(&pin),
+        );
+        assert!(matches!(
+            change,
+            Some(GitChange::Checkout { ref version, fetch: false, .. }) if version == "v1.0.0"
+        ));
+    }
+
+    #[test]
+    fn branch_is_pulled_once_checked_out() {
+        let pin = GitPin::Branch {
+            name: "main".into(),
+            commit: HEAD.into(),
+        };
+        let change = change_for_present(
+            &resource(Some("main")),
+            Some(HEAD),
+            Some("main"),
+            false,
+            Some(&pin),
+        );
+        assert!(matches!(change, Some(GitChange::Pull { .. })));
+
+        // From a detached tag checkout to a branch pin.
+        let change =
+            change_for_present(&resource(Some("main")), Some(HEAD), None, false, Some(&pin));
+        assert!(matches!(
+            change,
+            Some(GitChange::Checkout { fetch: true, .. })
+        ));
+    }
+
+    #[test]
+    fn unknown_version_always_fetches() {
+        let mut resource = resource(Some("release-2"));
+        resource.update = false;
+        let pin = GitPin::Unknown {
+            version: "release-2".into(),
+        };
+        let change = change_for_present(&resource, Some(HEAD), Some("main"), false, Some(&pin));
+        assert!(matches!(
+            change,
+            Some(GitChange::Checkout { fetch: true, .. })
+        ));
+    }
+
+    #[test]
+    fn changes_run_as_owner() {
+        let mut resource = resource(Some("main"));
+        resource.owner = GitOwner {
+            user: Some("me".into()),
+            group: None,
+        };
+        let pin = GitPin::Branch {
+            name: "main".into(),
+            commit: HEAD.into(),
+        };
+        let change = change_for_present(&resource, Some(HEAD), Some("main"), false, Some(&pin));
+        let Some(GitChange::Pull { owner, .. }) = change else {
+            panic!("expected a pull, got {change:?}");
+        };
+        assert_eq!(owner.user.as_deref(), Some("me"));
+    }
+
+    #[test]
+    fn detached_without_version_checks_out_default_branch() {
+        let pin = GitPin::Branch {
+            name: "main".into(),
+            commit: HEAD.into(),
+        };
+        let change = change_for_present(&resource(None), Some(HEAD), None, false, Some(&pin));
+        assert!(matches!(
+            change,
+            Some(GitChange::Checkout { ref version, .. }) if version == "main"
+        ));
+    }
 }
@@ -0,0 +1,420 @@
+//! `@core/git-config`: keys in a user's `~/.gitconfig` or the machine's
+//! `/etc/gitconfig`, set one by one with `git config`, so a plan can own
+//! `user.name` or an alias without owning the whole file.
+//!
+//! Each key is its own atom, probed with `git config --get-all`. A key can
+//! have many values, like `credential.helper`, which are kept in order; an
+//! empty list or `null` unsets the key. Keys the plan doesn't name are left
+//! as they are.
+
+use std::fmt::Display;
+
+use async_trait::async_trait;
+use lusid_causality::{CausalityMeta, CausalityTree};
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_operation::{
+    Operation,
+    operations::git::{GitConfigScope, GitOperation},
+};
+use lusid_params::{ParseError, ParseParams, StructFields, parse_list, parse_string};
+use lusid_view::impl_display_render;
+use rimu::{Spanned, Value};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
+
+/// One key and the values it should have, none to unset it.
+#[derive(Debug, Clone, Serialize)]
+pub struct GitConfigSetting {
+    pub key: String,
+    pub values: Vec<String>,
+}
+
+impl Display for GitConfigSetting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self { key, values } = self;
+        write!(f, "{key} = [{}]", values.join(", "))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GitConfigParams {
+    pub scope: GitConfigScope,
+    pub settings: Vec<GitConfigSetting>,
+}
+
+impl ParseParams for GitConfigParams {
+    fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
+        let mut fields = StructFields::new(value)?;
+        let scope = match fields.optional("scope", parse_scope)? {
+            Some("system") => GitConfigScope::System,
+            _ => GitConfigScope::Global {
+                user: fields.optional_string("user")?,
+            },
+        };
+        let settings = fields.required("settings", parse_settings)?;
+        fields.finish()?;
+        Ok(GitConfigParams { scope, settings })
+    }
+}
+
+fn parse_scope(value: Spanned<Value>) -> Result<&'static str, Spanned<ParseError>> {
+    let span = value.span();
+    let scope = parse_string(value)?;
+    match scope.as_str() {
+        "global" => Ok("global"),
+        "system" => Ok("system"),
+        _ => Err(Spanned::new(
+            ParseError::UnknownDiscriminator {
+                key: "scope",
+                got: Box::new(Value::String(scope)),
+                expected: vec!["global", "system"],
+            },
+            span,
+        )),
+    }
+}
+
+fn parse_settings(value: Spanned<Value>) -> Result<Vec<GitConfigSetting>, Spanned<ParseError>> {
+    let (value, span) = value.take();
+    let Value::Object(object) = value else {
+        return Err(Spanned::new(
+            ParseError::TypeMismatch {
+                expected: "object of git config keys",
+                got: Box::new(value),
+            },
+            span,
+        ));
+    };
+    object
+        .into_iter()
+        .map(|(key, value)| {
+            let field = |error: Spanned<ParseError>| {
+                let span = error.span();
+                Spanned::new(
+                    ParseError::Field {
+                        key: key.clone(),
+                        error: Box::new(error),
+                    },
+                    span,
+                )
+            };
+            if !is_valid_key(&key) {
+                return Err(field(Spanned::new(
+                    ParseError::TypeMismatch {
+                        expected: "git config key, like `user.name` or `url.<base>.insteadOf`",
+                        got: Box::new(Value::String(key.clone())),
+                    },
+                    value.span(),
+                )));
+            }
+            let values = parse_values(value).map_err(field)?;
+            Ok(GitConfigSetting { key, values })
+        })
+        .collect()
+}
+
+// Git stores every value as a string; booleans and numbers are written as
+// git would show them.
+fn parse_values(value: Spanned<Value>) -> Result<Vec<String>, Spanned<ParseError>> {
+    match value.inner() {
+        Value::Null => Ok(Vec::new()),
+        Value::List(_) => parse_list(value, parse_value),
+        _ => parse_value(value).map(|value| vec![value]),
+    }
+}
+
+fn parse_value(value: Spanned<Value>) -> Result<String, Spanned<ParseError>> {
+    let (value, span) = value.take();
+    match value {
+        Value::String(value) => Ok(value),
+        Value::Boolean(value) => Ok(value.to_string()),
+        Value::Number(value) => Ok(value.to_string()),
+        other => Err(Spanned::new(
+            ParseError::TypeMismatch {
+                expected: "string, boolean or number",
+                got: Box::new(other),
+            },
+            span,
+        )),
+    }
+}
+
+/// Whether `key` is `section.name` or `section.subsection.name`, as `git
+/// config` takes them: the section and name of letters, digits and `-`, the
+/// name starting with a letter, and the subsection anything on one line.
+fn is_valid_key(key: &str) -> bool {
+    let (Some((section, _)), Some((rest, name))) = (key.split_once('.'), key.rsplit_once('.'))
+    else {
+        return false;
+    };
+    let word = |word: &str| word.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    !section.is_empty()
+        && word(section)
+        && name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && word(name)
+        && !rest.contains('\n')
+}
+
+impl Display for GitConfigParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self { scope, settings } = self;
+        let settings: Vec<String> = settings.iter().map(ToString::to_string).collect();
+        write!(
+            f,
+            "GitConfig({scope}, settings = [{}])",
+            settings.join(", ")
+        )
+    }
+}
+
+impl_display_render!(GitConfigParams);
+
+#[derive(Debug, Clone)]
+pub struct GitConfigResource {
+    pub scope: GitConfigScope,
+    pub key: String,
+    pub values: Vec<String>,
+}
+
+impl Display for GitConfigResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self { scope, key, values } = self;
+        write!(f, "GitConfig({scope}, {key} = [{}])", values.join(", "))
+    }
+}
+
+impl_display_render!(GitConfigResource);
+
+#[derive(Debug, Clone)]
+pub enum GitConfigState {
+    Unset,
+    Set(Vec<String>),
+}
+
+impl Display for GitConfigState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GitConfigState::Unset => write!(f, "GitConfig::Unset"),
+            GitConfigState::Set(values) => write!(f, "GitConfig::Set([{}])", values.join(", ")),
+        }
+    }
+}
+
+impl_display_render!(GitConfigState);
+
+#[derive(Error, Debug)]
+pub enum GitConfigStateError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+
+    #[error("failed to read git config {key}: {stderr}")]
+    Read { key: String, stderr: String },
+}
+
+#[derive(Debug, Clone)]
+pub enum GitConfigChange {
+    Set {
+        scope: GitConfigScope,
+        key: String,
+        values: Vec<String>,
+    },
+    Unset {
+        scope: GitConfigScope,
+        key: String,
+    },
+}
+
+impl Display for GitConfigChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GitConfigChange::Set { scope, key, values } => write!(
+                f,
+                "GitConfig::Set({scope}, {key} = [{}])",
+                values.join(", ")
+            ),
+            GitConfigChange::Unset { scope, key } => {
+                write!(f, "GitConfig::Unset({scope}, {key})")
+            }
+        }
+    }
+}
+
+impl_display_render!(GitConfigChange);
+
+#[derive(Debug, Clone)]
+pub struct GitConfig;
+
+#[async_trait]
+impl ResourceType for GitConfig {
+    const ID: &'static str = "git-config";
+    const DESCRIPTION: &'static str =
+        "Set keys in a user's global or the system's git config, leaving the rest of it alone.";
+    const PLATFORMS: &'static [&'static str] = &["linux", "macos"];
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[ParamsDoc {
+        description: "Some keys.",
+        params: &[
+            ParamDoc::required(
+                "settings",
+                ParamDocType::Object,
+                "Keys and their values, e.g. `{ \"user.name\": \"Mikey\", \"alias.co\": \"checkout\" }`. A list sets many values, like `credential.helper`'s; an empty list or `null` unsets the key.",
+            ),
+            ParamDoc::optional(
+                "scope",
+                ParamDocType::OneOf(&[
+                    ParamDocType::Literal("global"),
+                    ParamDocType::Literal("system"),
+                ]),
+                "`global` for a user's `~/.gitconfig`, or `system` for `/etc/gitconfig`. Default: global.",
+            ),
+            ParamDoc::optional(
+                "user",
+                ParamDocType::String,
+                "Whose global config to set, through sudo. Default: the apply user.",
+            ),
+        ],
+    }];
+
+    type Params = GitConfigParams;
+    type Resource = GitConfigResource;
+
+    fn resources(params: Self::Params) -> Vec<CausalityTree<Self::Resource>> {
+        let GitConfigParams { scope, settings } = params;
+        settings
+            .into_iter()
+            .map(|GitConfigSetting { key, values }| {
+                CausalityTree::leaf(
+                    CausalityMeta::default(),
+                    GitConfigResource {
+                        scope: scope.clone(),
+                        key,
+                        values,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    type State = GitConfigState;
+    type StateError = GitConfigStateError;
+
+    // `-z` ends each value with a NUL rather than a newline, so values with
+    // newlines in them come back whole. `--get-all` exits 1 for a key that
+    // isn't set, and a missing config file is one with nothing set.
+    async fn state(
+        _ctx: &mut Context,
+        resource: &Self::Resource,
+    ) -> Result<Self::State, Self::StateError> {
+        let GitConfigResource { scope, key, .. } = resource;
+        let mut cmd = Command::new("git");
+        cmd.args(["config", scope.flag(), "-z", "--get-all"])
+            .arg(key);
+        let outcome = scope.wrap(cmd).outcome().await?;
+        if outcome.status.code() == Some(1) {
+            return Ok(GitConfigState::Unset);
+        }
+        if !outcome.status.success() {
+            return Err(GitConfigStateError::Read {
+                key: key.clone(),
+                stderr: String::from_utf8_lossy(&outcome.stderr).trim().to_owned(),
+            });
+        }
+        Ok(GitConfigState::Set(parse_values_output(&outcome.stdout)))
+    }
+
+    type Change = GitConfigChange;
+
+    fn change(resource: &Self::Resource, state: &Self::State) -> Option<Self::Change> {
+        let GitConfigResource { scope, key, values } = resource;
+        match state {
+            GitConfigState::Unset if values.is_empty() => None,
+            GitConfigState::Set(current) if current == values => None,
+            _ if values.is_empty() => Some(GitConfigChange::Unset {
+                scope: scope.clone(),
+                key: key.clone(),
+            }),
+            _ => Some(GitConfigChange::Set {
+                scope: scope.clone(),
+                key: key.clone(),
+                values: values.clone(),
+            }),
+        }
+    }
+
+    fn operations(change: Self::Change) -> Vec<CausalityTree<Operation>> {
+        let operation = match change {
+            GitConfigChange::Set { scope, key, values } => {
+                GitOperation::SetConfig { scope, key, values }
+            }
+            GitConfigChange::Unset { scope, key } => GitOperation::UnsetConfig { scope, key },
+        };
+        vec![CausalityTree::leaf(
+            CausalityMeta::default(),
+            Operation::Git(operation),
+        )]
+    }
+}
+
+/// The values `git config -z --get-all` printed, each ended by a NUL.
+fn parse_values_output(stdout: &[u8]) -> Vec<String> {
+    let Some(stdout) = stdout.strip_suffix(b"\0") else {
+        return Vec::new();
+    };
+    stdout
+        .split(|byte| *byte == 0)
+        .map(|value| String::from_utf8_lossy(value).into_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_keys_and_values() {
+        assert!(is_valid_key("user.name"));
+        assert!(is_valid_key("url.git@github.com:.insteadOf"));
+        assert!(is_valid_key("credential.https://example.com.helper"));
+        assert!(!is_valid_key("name"));
+        assert!(!is_valid_key("user.1name"));
+        assert!(!is_valid_key(".name"));
+        assert!(!is_valid_key("user name.x"));
+
+        assert_eq!(
+            parse_values_output(b"\0cache --timeout=3600\0"),
+            ["", "cache --timeout=3600"]
+        );
+        assert_eq!(
+            parse_values_output(b"line one\nline two\0"),
+            ["line one\nline two"]
+        );
+        assert!(parse_values_output(b"").is_empty());
+    }
+
+    #[test]
+    fn only_differing_values_change() {
+        let resource = |values: &[&str]| GitConfigResource {
+            scope: GitConfigScope::Global { user: None },
+            key: "credential.helper".into(),
+            values: values.iter().map(ToString::to_string).collect(),
+        };
+        let set =
+            |values: &[&str]| GitConfigState::Set(values.iter().map(ToString::to_string).collect());
+        let change =
+            |resource, state| GitConfig::change(&resource, &state).map(|change| change.to_string());
+
+        assert_eq!(change(resource(&["", "store"]), set(&["", "store"])), None);
+        assert_eq!(change(resource(&[]), GitConfigState::Unset), None);
+        assert_eq!(
+            change(resource(&["", "store"]), set(&["store", ""])).as_deref(),
+            Some("GitConfig::Set(global, credential.helper = [, store])")
+        );
+        assert_eq!(
+            change(resource(&[]), set(&["store"])).as_deref(),
+            Some("GitConfig::Unset(global, credential.helper)")
+        );
+    }
+}
@@ -0,0 +1,503 @@
+//! `@core/gpg-key`: a GPG public key, imported by its fingerprint into a
+//! user's keyring or into an apt keyring under `/etc/apt/keyrings/`.
+//!
+//! The fingerprint is the key's identity: a key is fetched from `url` or a
+//! `keyserver`, and imported only if it is the key with that fingerprint.
+//! One that isn't fails the apply rather than landing in the keyring. An apt
+//! keyring is this key's alone, so one holding other keys is replaced.
+
+use std::fmt::Display;
+
+use async_trait::async_trait;
+use lusid_causality::{CausalityMeta, CausalityTree};
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_fs::{self as fs, FsError};
+use lusid_operation::{
+    Operation,
+    operations::{
+        apt_repo::AptRepoOperation,
+        file::FilePath,
+        gpg::{GpgKeySource, GpgKeyring, GpgOperation},
+    },
+};
+use lusid_params::{ParseError, ParseParams, StructFields, parse_string};
+use lusid_view::impl_display_render;
+use rimu::{Spanned, Value};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
+use crate::resources::apt_repo::KEYRINGS_DIR;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GpgKeyParams {
+    pub fingerprint: String,
+    pub source: GpgKeySource,
+    pub keyring: GpgKeyParamsKeyring,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GpgKeyParamsKeyring {
+    User {
+        user: Option<String>,
+    },
+    /// `/etc/apt/keyrings/<name>.gpg`.
+    Apt {
+        name: String,
+    },
+}
+
+impl ParseParams for GpgKeyParams {
+    fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
+        let mut fields = StructFields::new(value)?;
+        let fingerprint = fields.required("fingerprint", parse_fingerprint)?;
+        // With both, `url` is left over for `finish` to reject.
+        let source = if fields.has("keyserver") {
+            GpgKeySource::Keyserver {
+                keyserver: fields.required_string("keyserver")?,
+            }
+        } else {
+            GpgKeySource::Url {
+                url: fields.required_string("url")?,
+            }
+        };
+        let keyring = match fields.optional("keyring", parse_keyring)? {
+            Some("apt") => GpgKeyParamsKeyring::Apt {
+                name: fields.required("name", parse_name)?,
+            },
+            _ => GpgKeyParamsKeyring::User {
+                user: fields.optional_string("user")?,
+            },
+        };
+        fields.finish()?;
+        Ok(GpgKeyParams {
+            fingerprint,
+            source,
+            keyring,
+        })
+    }
+}
+
+fn parse_keyring(value: Spanned<Value>) -> Result<&'static str, Spanned<ParseError>> {
+    let span = value.span();
+    let keyring = parse_string(value)?;
+    match keyring.as_str() {
+        "user" => Ok("user"),
+        "apt" => Ok("apt"),
+        _ => Err(Spanned::new(
+            ParseError::UnknownDiscriminator {
+                key: "keyring",
+                got: Box::new(Value::String(keyring)),
+                expected: vec!["user", "apt"],
+            },
+            span,
+        )),
+    }
+}
+
+/// A fingerprint as gpg prints it, upper-case without spaces, from one
+/// written either case, with spaces between its groups or a `0x` before it.
+fn parse_fingerprint(value: Spanned<Value>) -> Result<String, Spanned<ParseError>> {
+    let span = value.span();
+    let written = parse_string(value)?;
+    let Some(fingerprint) = normalize_fingerprint(&written) else {
+        return Err(Spanned::new(
+            ParseError::TypeMismatch {
+                expected: "key fingerprint of 40 or 64 hex digits",
+                got: Box::new(Value::String(written)),
+            },
+            span,
+        ));
+    };
+    Ok(fingerprint)
+}
+
+fn normalize_fingerprint(written: &str) -> Option<String> {
+    let trimmed = written.trim();
+    let trimmed = trimmed
+        .strip_prefix("0x")
+        .or_else(|| trimmed.strip_prefix("0X"))
+        .unwrap_or(trimmed);
+    let fingerprint: String = trimmed
+        .chars()
+        .filter(|c| *c != ' ')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    let is_valid =
+        matches!(fingerprint.len(), 40 | 64) && fingerprint.chars().all(|c| c.is_ascii_hexdigit());
+    is_valid.then_some(fingerprint)
+}
+
+// `name` is a file name under `/etc/apt/keyrings/`, so it can't leave it.
+fn parse_name(value: Spanned<Value>) -> Result<String, Spanned<ParseError>> {
+    let span = value.span();
+    let name = parse_string(value)?;
+    let is_valid = name.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "._-".contains(c));
+    if !is_valid {
+        return Err(Spanned::new(
+            ParseError::TypeMismatch {
+                expected: "keyring name of lower-case letters, digits, `.`, `_` and `-`",
+                got: Box::new(Value::String(name)),
+            },
+            span,
+        ));
+    }
+    Ok(name)
+}
+
+impl Display for GpgKeyParamsKeyring {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GpgKeyParamsKeyring::User { user: None } => write!(f, "keyring = user"),
+            GpgKeyParamsKeyring::User { user: Some(user) } => {
+                write!(f, "keyring = user, user = {user}")
+            }
+            GpgKeyParamsKeyring::Apt { name } => write!(f, "keyring = apt, name = {name}"),
+        }
+    }
+}
+
+impl Display for GpgKeyParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            fingerprint,
+            source,
+            keyring,
+        } = self;
+        write!(
+            f,
+            "GpgKey(fingerprint = {fingerprint}, {source}, {keyring})"
+        )
+    }
+}
+
+impl_display_render!(GpgKeyParams);
+
+#[derive(Debug, Clone)]
+pub struct GpgKeyResource {
+    pub fingerprint: String,
+    pub source: GpgKeySource,
+    pub keyring: GpgKeyring,
+}
+
+impl Display for GpgKeyResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            fingerprint,
+            source,
+            keyring,
+        } = self;
+        write!(f, "GpgKey({fingerprint} in {keyring}, {source})")
+    }
+}
+
+impl_display_render!(GpgKeyResource);
+
+#[derive(Debug, Clone)]
+pub enum GpgKeyState {
+    Missing,
+    Present,
+    /// A keyring file holding other keys, by fingerprint, and not this one.
+    Other(Vec<String>),
+}
+
+impl Display for GpgKeyState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GpgKeyState::Missing => write!(f, "GpgKey::Missing"),
+            GpgKeyState::Present => write!(f, "GpgKey::Present"),
+            GpgKeyState::Other(fingerprints) => {
+                write!(f, "GpgKey::Other([{}])", fingerprints.join(", "))
+            }
+        }
+    }
+}
+
+impl_display_render!(GpgKeyState);
+
+#[derive(Error, Debug)]
+pub enum GpgKeyStateError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+
+    #[error(transparent)]
+    Fs(#[from] FsError),
+
+    #[error("failed to read keyring {path}: {stderr}")]
+    Read { path: FilePath, stderr: String },
+}
+
+#[derive(Debug, Clone)]
+pub enum GpgKeyChange {
+    Import(GpgKeyResource),
+}
+
+impl Display for GpgKeyChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GpgKeyChange::Import(GpgKeyResource {
+                fingerprint,
+                source,
+                keyring,
+            }) => write!(f, "GpgKey::Import({fingerprint} into {keyring}, {source})"),
+        }
+    }
+}
+
+impl_display_render!(GpgKeyChange);
+
+const FINGERPRINT_DOC: ParamDoc = ParamDoc::required(
+    "fingerprint",
+    ParamDocType::String,
+    "The key's full fingerprint, e.g. `9DC8 5822 9FC7 DD38 854A  E2D8 8D81 803C 0EBF CD88`. A key fetched with any other is an error.",
+);
+const URL_DOC: ParamDoc = ParamDoc::optional(
+    "url",
+    ParamDocType::String,
+    "URL of the key file, armored or not. One of `url` or `keyserver` is required.",
+);
+const KEYSERVER_DOC: ParamDoc = ParamDoc::optional(
+    "keyserver",
+    ParamDocType::String,
+    "Keyserver to receive the key from by its fingerprint, e.g. `hkps://keys.openpgp.org`.",
+);
+
+#[derive(Debug, Clone)]
+pub struct GpgKey;
+
+#[async_trait]
+impl ResourceType for GpgKey {
+    const ID: &'static str = "gpg-key";
+    const DESCRIPTION: &'static str =
+        "Import a GPG public key by its fingerprint into a user's or an apt keyring.";
+    const PLATFORMS: &'static [&'static str] = &["linux"];
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[
+        ParamsDoc {
+            description: "Into a user's keyring.",
+            params: &[
+                FINGERPRINT_DOC,
+                URL_DOC,
+                KEYSERVER_DOC,
+                ParamDoc::optional(
+                    "keyring",
+                    ParamDocType::Literal("user"),
+                    "A user's own keyring, in their GnuPG home. Default.",
+                ),
+                ParamDoc::optional(
+                    "user",
+                    ParamDocType::String,
+                    "Whose keyring, through sudo. Default: the apply user.",
+                ),
+            ],
+        },
+        ParamsDoc {
+            description: "Into an apt keyring.",
+            params: &[
+                FINGERPRINT_DOC,
+                URL_DOC,
+                KEYSERVER_DOC,
+                ParamDoc::required(
+                    "keyring",
+                    ParamDocType::Literal("apt"),
+                    "A keyring of its own for apt, to name in a source's `Signed-By`.",
+                ),
+                ParamDoc::required(
+                    "name",
+                    ParamDocType::String,
+                    "Names the keyring `/etc/apt/keyrings/<name>.gpg`.",
+                ),
+            ],
+        },
+    ];
+
+    type Params = GpgKeyParams;
+    type Resource = GpgKeyResource;
+
+    fn resources(params: Self::Params) -> Vec<CausalityTree<Self::Resource>> {
+        let GpgKeyParams {
+            fingerprint,
+            source,
+            keyring,
+        } = params;
+        let keyring = match keyring {
+            GpgKeyParamsKeyring::User { user } => GpgKeyring::User { user },
+            GpgKeyParamsKeyring::Apt { name } => GpgKeyring::File {
+                path: FilePath::new(format!("{KEYRINGS_DIR}/{name}.gpg")),
+            },
+        };
+        vec![CausalityTree::leaf(
+            CausalityMeta::default(),
+            GpgKeyResource {
+                fingerprint,
+                source,
+                keyring,
+            },
+        )]
+    }
+
+    type State = GpgKeyState;
+    type StateError = GpgKeyStateError;
+
+    // A user's keyring is asked for the key; `--list-keys` fails for one it
+    // doesn't have. A keyring file is read whole with `--show-keys`, which
+    // imports nothing.
+    async fn state(
+        _ctx: &mut Context,
+        resource: &Self::Resource,
+    ) -> Result<Self::State, Self::StateError> {
+        let GpgKeyResource {
+            fingerprint,
+            keyring,
+            ..
+        } = resource;
+        match keyring {
+            GpgKeyring::User { .. } => {
+                let mut cmd = Command::new("gpg");
+                cmd.args(["--batch", "--with-colons", "--list-keys"])
+                    .arg(fingerprint);
+                let outcome = keyring.wrap(cmd).outcome().await?;
+                let has_key = outcome.status.success()
+                    && primary_fingerprints(&String::from_utf8_lossy(&outcome.stdout))
+                        .contains(fingerprint);
+                Ok(if has_key {
+                    GpgKeyState::Present
+                } else {
+                    GpgKeyState::Missing
+                })
+            }
+            GpgKeyring::File { path } => {
+                if !fs::path_exists(path.as_path()).await? {
+                    return Ok(GpgKeyState::Missing);
+                }
+                let mut cmd = Command::new("gpg");
+                cmd.args(["--batch", "--with-colons", "--show-keys"])
+                    .arg(path.as_path());
+                let outcome = cmd.outcome().await?;
+                if !outcome.status.success() {
+                    return Err(GpgKeyStateError::Read {
+                        path: path.clone(),
+                        stderr: String::from_utf8_lossy(&outcome.stderr).trim().to_owned(),
+                    });
+                }
+                let fingerprints = primary_fingerprints(&String::from_utf8_lossy(&outcome.stdout));
+                Ok(if fingerprints == [fingerprint.as_str()] {
+                    GpgKeyState::Present
+                } else {
+                    GpgKeyState::Other(fingerprints)
+                })
+            }
+        }
+    }
+
+    type Change = GpgKeyChange;
+
+    fn change(resource: &Self::Resource, state: &Self::State) -> Option<Self::Change> {
+        match state {
+            GpgKeyState::Present => None,
+            GpgKeyState::Missing | GpgKeyState::Other(_) => {
+                Some(GpgKeyChange::Import(resource.clone()))
+            }
+        }
+    }
+
+    fn operations(change: Self::Change) -> Vec<CausalityTree<Operation>> {
+        let GpgKeyChange::Import(GpgKeyResource {
+            fingerprint,
+            source,
+            keyring,
+        }) = change;
+        let import = |meta| {
+            CausalityTree::leaf(
+                meta,
+                Operation::Gpg(GpgOperation::ImportKey {
+                    fingerprint,
+                    source,
+                    keyring: keyring.clone(),
+                }),
+            )
+        };
+        match &keyring {
+            GpgKeyring::User { .. } => vec![import(CausalityMeta::default())],
+            GpgKeyring::File { .. } => vec![
+                CausalityTree::leaf(
+                    CausalityMeta::id("keyrings-dir".into()),
+                    Operation::AptRepo(AptRepoOperation::EnsureKeyringsDir {
+                        path: FilePath::new(KEYRINGS_DIR),
+                    }),
+                ),
+                import(CausalityMeta::requires(vec!["keyrings-dir".into()])),
+            ],
+        }
+    }
+}
+
+/// The primary keys' fingerprints in gpg's `--with-colons` listing: each
+/// `pub` record's first `fpr` record, before those of its subkeys.
+fn primary_fingerprints(listing: &str) -> Vec<String> {
+    let mut fingerprints = Vec::new();
+    let mut in_primary = false;
+    for line in listing.lines() {
+        let mut fields = line.split(':');
+        match fields.next() {
+            Some("pub") => in_primary = true,
+            Some("fpr") if in_primary => {
+                in_primary = false;
+                if let Some(fingerprint) = fields.nth(8) {
+                    fingerprints.push(fingerprint.to_owned());
+                }
+            }
+            _ => {}
+        }
+    }
+    fingerprints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_fingerprints() {
+        assert_eq!(
+            normalize_fingerprint("9dc8 5822 9fc7 dd38 854a  e2d8 8d81 803c 0ebf cd88").as_deref(),
+            Some("9DC858229FC7DD38854AE2D88D81803C0EBFCD88")
+        );
+        assert_eq!(
+            normalize_fingerprint("0x9DC858229FC7DD38854AE2D88D81803C0EBFCD88").as_deref(),
+            Some("9DC858229FC7DD38854AE2D88D81803C0EBFCD88")
+        );
+        // A long key id isn't a fingerprint.
+        assert_eq!(normalize_fingerprint("8D81803C0EBFCD88"), None);
+        assert_eq!(
+            normalize_fingerprint("9DC858229FC7DD38854AE2D88D81803C0EBFCDXX"),
+            None
+        );
+    }
+
+    #[test]
+    fn reads_primary_fingerprints_but_not_subkeys() {
+        let listing = "\
+tru::1:1792164556:0:3:1:5
+pub:-:255:22:EFDBFB83254EFF84:1792164548:::-:::scSC:::::ed25519:::0:
+fpr:::::::::FAFC2B9B27A58C139883BB57EFDBFB83254EFF84:
+uid:-::::1792164548::03DC22E9F897D3AE136C29CF64B7901137DAC1A2::Test <t@example.com>::::::::::0:
+sub:-:255:18:5B2D0E5C0C3E2F11:1792164548::::::e:::::cv25519::
+fpr:::::::::3C1F7F0E9A2B4C5D6E7F80915B2D0E5C0C3E2F11:
+pub:-:255:22:B4A10636C06FECB8:1792164548:::-:::scSC:::::ed25519:::0:
+fpr:::::::::0E5776264A9A4BACEEB0EB8AB4A10636C06FECB8:
+";
+        assert_eq!(
+            primary_fingerprints(listing),
+            [
+                "FAFC2B9B27A58C139883BB57EFDBFB83254EFF84",
+                "0E5776264A9A4BACEEB0EB8AB4A10636C06FECB8",
+            ]
+        );
+    }
+}
@@ -8,16 +8,19 @@ use lusid_operation::{Operation, operations::group::GroupOperation};
 use lusid_params::{ParseError, ParseParams, StructFields};
 use lusid_view::impl_display_render;
 use rimu::{Spanned, Value};
+use serde::Serialize;
 use thiserror::Error;
 
 use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
 
 /// Plan-level parameters for the `@core/group` resource.
 ///
 /// Tagged by `state: "present" | "absent"`. Mirrors the shape used by Salt
 /// (`group.present`) and Ansible (`ansible.builtin.group`), with an additional
 /// `append_users` field to declaratively guarantee supplementary group membership.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum GroupParams {
     Present {
         name: String,
@@ -169,6 +172,30 @@ pub struct Group;
 #[async_trait]
 impl ResourceType for Group {
     const ID: &'static str = "group";
+    const DESCRIPTION: &'static str = "Manage a Unix group.";
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[
+        ParamsDoc {
+            description: "Present.",
+            params: &[
+                ParamDoc::required("state", ParamDocType::Literal("present"), "Present."),
+                ParamDoc::required("name", ParamDocType::String, "Group name."),
+                ParamDoc::optional("gid", ParamDocType::Number, "Group id."),
+                ParamDoc::optional("system", ParamDocType::Boolean, "Create as a system group."),
+                ParamDoc::optional(
+                    "append_users",
+                    ParamDocType::StringList,
+                    "Users to add as members, keeping any others.",
+                ),
+            ],
+        },
+        ParamsDoc {
+            description: "Removed.",
+            params: &[
+                ParamDoc::required("state", ParamDocType::Literal("absent"), "Absent."),
+                ParamDoc::required("name", ParamDocType::String, "Group name."),
+            ],
+        },
+    ];
 
     type Params = GroupParams;
     type Resource = GroupResource;
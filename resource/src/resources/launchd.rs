@@ -0,0 +1,380 @@
+use std::fmt::Display;
+use std::path::Path;
+
+use async_trait::async_trait;
+use lusid_causality::{CausalityMeta, CausalityTree};
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_operation::{
+    Operation,
+    operations::{
+        file::FilePath,
+        launchd::{LaunchdOperation, SYSTEM_DOMAIN},
+    },
+};
+use lusid_params::{ParseError, ParseParams, StructFields};
+use lusid_view::impl_display_render;
+use rimu::{Spanned, Value};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
+use crate::resources::file::parse_file_path;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LaunchdParams {
+    pub plist: FilePath,
+    pub label: Option<String>,
+    pub domain: Option<String>,
+    pub enabled: Option<bool>,
+    pub active: Option<bool>,
+}
+
+impl ParseParams for LaunchdParams {
+    fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
+        let mut fields = StructFields::new(value)?;
+        let plist = fields.required("plist", parse_file_path)?;
+        let label = fields.optional_string("label")?;
+        let domain = fields.optional_string("domain")?;
+        let enabled = fields.optional_bool("enabled")?;
+        let active = fields.optional_bool("active")?;
+        fields.finish()?;
+        Ok(LaunchdParams {
+            plist,
+            label,
+            domain,
+            enabled,
+            active,
+        })
+    }
+}
+
+impl Display for LaunchdParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            plist,
+            label,
+            domain,
+            enabled,
+            active,
+        } = self;
+        write!(
+            f,
+            "Launchd(plist = {plist}, label = {label:?}, domain = {domain:?}, enabled = {enabled:?}, active = {active:?})"
+        )
+    }
+}
+
+impl_display_render!(LaunchdParams);
+
+#[derive(Debug, Clone)]
+pub struct LaunchdResource {
+    pub domain: String,
+    pub label: String,
+    pub plist: FilePath,
+    pub enabled: bool,
+    pub active: bool,
+}
+
+impl Display for LaunchdResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            domain,
+            label,
+            plist: _,
+            enabled,
+            active,
+        } = self;
+        write!(
+            f,
+            "Launchd({domain}/{label}, enabled = {enabled}, active = {active})"
+        )
+    }
+}
+
+impl_display_render!(LaunchdResource);
+
+#[derive(Debug, Clone)]
+pub struct LaunchdState {
+    pub enabled: bool,
+    pub active: bool,
+}
+
+impl Display for LaunchdState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self { enabled, active } = self;
+        write!(f, "Launchd(enabled = {enabled}, active = {active})")
+    }
+}
+
+impl_display_render!(LaunchdState);
+
+#[derive(Error, Debug)]
+pub enum LaunchdStateError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+
+    #[error("launchctl print {target} failed: {stderr}")]
+    Print { target: String, stderr: String },
+
+    #[error("unknown launchctl print-disabled value for {label}: {value}")]
+    UnknownDisabled { label: String, value: String },
+}
+
+/// Desired-state delta for a launchd service, as for
+/// [`SystemdChange`](crate::resources::systemd::SystemdChange): each field is
+/// `Some(desired)` if it needs to change.
+#[derive(Debug, Clone)]
+pub struct LaunchdChange {
+    pub domain: String,
+    pub label: String,
+    pub plist: FilePath,
+    pub enable: Option<bool>,
+    pub active: Option<bool>,
+}
+
+impl Display for LaunchdChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            domain,
+            label,
+            plist: _,
+            enable,
+            active,
+        } = self;
+        let mut verbs: Vec<&'static str> = Vec::new();
+        if let Some(enable) = enable {
+            verbs.push(if *enable { "enable" } else { "disable" });
+        }
+        if let Some(active) = active {
+            verbs.push(if *active { "load" } else { "unload" });
+        }
+        write!(f, "Launchd::{}({domain}/{label})", verbs.join("+"))
+    }
+}
+
+impl_display_render!(LaunchdChange);
+
+#[derive(Debug, Clone)]
+pub struct Launchd;
+
+#[async_trait]
+impl ResourceType for Launchd {
+    const ID: &'static str = "launchd";
+    const DESCRIPTION: &'static str =
+        "Enable and load, or disable and unload, a macOS launchd service.";
+    const PLATFORMS: &'static [&'static str] = &["macos"];
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[ParamsDoc {
+        description: "A service.",
+        params: &[
+            ParamDoc::required(
+                "plist",
+                ParamDocType::TargetPath,
+                "Path of the service's property list, e.g. written by `@core/file`.",
+            ),
+            ParamDoc::optional(
+                "label",
+                ParamDocType::String,
+                "The service's `Label`. Defaults to the plist's file name without `.plist`.",
+            ),
+            ParamDoc::optional(
+                "domain",
+                ParamDocType::String,
+                "launchd domain: `system` for a daemon, or `gui/<uid>` for a user's agent. Defaults to `system`.",
+            ),
+            ParamDoc::optional(
+                "enabled",
+                ParamDocType::Boolean,
+                "Whether launchd may load it, at boot or login.",
+            ),
+            ParamDoc::optional("active", ParamDocType::Boolean, "Whether it's loaded now."),
+        ],
+    }];
+
+    type Params = LaunchdParams;
+    type Resource = LaunchdResource;
+
+    fn resources(params: Self::Params) -> Vec<CausalityTree<Self::Resource>> {
+        let LaunchdParams {
+            plist,
+            label,
+            domain,
+            enabled,
+            active,
+        } = params;
+        let label = label.unwrap_or_else(|| label_of(plist.as_path()));
+        vec![CausalityTree::leaf(
+            CausalityMeta::default(),
+            LaunchdResource {
+                domain: domain.unwrap_or_else(|| SYSTEM_DOMAIN.to_owned()),
+                label,
+                plist,
+                enabled: enabled.unwrap_or(true),
+                active: active.unwrap_or(true),
+            },
+        )]
+    }
+
+    type State = LaunchdState;
+    type StateError = LaunchdStateError;
+
+    async fn state(
+        _ctx: &mut Context,
+        resource: &Self::Resource,
+    ) -> Result<Self::State, Self::StateError> {
+        let LaunchdResource { domain, label, .. } = resource;
+
+        // A service that isn't loaded exits non-zero with "Could not find
+        // service", the same "nothing here yet" as systemd's `not-found`.
+        let target = format!("{domain}/{label}");
+        let outcome = Command::new("launchctl")
+            .arg("print")
+            .arg(&target)
+            .outcome()
+            .await?;
+        let active = if outcome.status.success() {
+            true
+        } else {
+            let stderr = String::from_utf8_lossy(&outcome.stderr);
+            let stdout = String::from_utf8_lossy(&outcome.stdout);
+            if !(stderr.contains("Could not find service")
+                || stdout.contains("Could not find service"))
+            {
+                return Err(LaunchdStateError::Print {
+                    target,
+                    stderr: stderr.trim().to_owned(),
+                });
+            }
+            false
+        };
+
+        let disabled = Command::new("launchctl")
+            .arg("print-disabled")
+            .arg(domain)
+            .run()
+            .await?;
+        let enabled = parse_enabled(&String::from_utf8_lossy(&disabled), label)?;
+
+        Ok(LaunchdState { enabled, active })
+    }
+
+    type Change = LaunchdChange;
+
+    fn change(resource: &Self::Resource, state: &Self::State) -> Option<Self::Change> {
+        let enable = (resource.enabled != state.enabled).then_some(resource.enabled);
+        let active = (resource.active != state.active).then_some(resource.active);
+        if enable.is_none() && active.is_none() {
+            return None;
+        }
+        Some(LaunchdChange {
+            domain: resource.domain.clone(),
+            label: resource.label.clone(),
+            plist: resource.plist.clone(),
+            enable,
+            active,
+        })
+    }
+
+    fn operations(change: Self::Change) -> Vec<CausalityTree<Operation>> {
+        let LaunchdChange {
+            domain,
+            label,
+            plist,
+            enable,
+            active,
+        } = change;
+        let mut ops: Vec<CausalityTree<Operation>> = Vec::new();
+        if let Some(enable) = enable {
+            let op = if enable {
+                LaunchdOperation::Enable {
+                    domain: domain.clone(),
+                    label: label.clone(),
+                }
+            } else {
+                LaunchdOperation::Disable {
+                    domain: domain.clone(),
+                    label: label.clone(),
+                }
+            };
+            ops.push(CausalityTree::leaf(
+                CausalityMeta::id("enable".into()),
+                Operation::Launchd(op),
+            ));
+        }
+        if let Some(active) = active {
+            let op = if active {
+                LaunchdOperation::Bootstrap { domain, plist }
+            } else {
+                LaunchdOperation::Bootout { domain, label }
+            };
+            // launchd refuses to bootstrap a disabled service.
+            let meta = if ops.is_empty() {
+                CausalityMeta::default()
+            } else {
+                CausalityMeta::requires(vec!["enable".into()])
+            };
+            ops.push(CausalityTree::leaf(meta, Operation::Launchd(op)));
+        }
+        ops
+    }
+}
+
+/// The label launchd conventionally gives a plist: its file name, less
+/// `.plist`.
+fn label_of(plist: &Path) -> String {
+    plist
+        .file_stem()
+        .unwrap_or(plist.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Whether `launchctl print-disabled` output leaves `label` enabled. Lines
+/// read `"com.example.agent" => disabled`, or `=> true` before macOS 13. A
+/// service it doesn't list is enabled.
+fn parse_enabled(output: &str, label: &str) -> Result<bool, LaunchdStateError> {
+    let quoted = format!("\"{label}\"");
+    for line in output.lines() {
+        let Some((name, value)) = line.trim().split_once("=>") else {
+            continue;
+        };
+        if name.trim() != quoted {
+            continue;
+        }
+        return match value.trim() {
+            "disabled" | "true" => Ok(false),
+            "enabled" | "false" => Ok(true),
+            other => Err(LaunchdStateError::UnknownDisabled {
+                label: label.to_owned(),
+                value: other.to_owned(),
+            }),
+        };
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn label_defaults_to_the_plist_name() {
+        assert_eq!(
+            label_of(Path::new("/Library/LaunchDaemons/com.example.agent.plist")),
+            "com.example.agent"
+        );
+    }
+
+    #[test]
+    fn reads_print_disabled() {
+        let output = "disabled services = {\n\
+                      \t\"com.example.agent\" => disabled\n\
+                      \t\"com.example.other\" => enabled\n\
+                      }\n";
+        assert!(!parse_enabled(output, "com.example.agent").unwrap());
+        assert!(parse_enabled(output, "com.example.other").unwrap());
+        assert!(parse_enabled(output, "com.example.missing").unwrap());
+        assert!(!parse_enabled("\t\"com.example.agent\" => true\n", "com.example.agent").unwrap());
+    }
+}
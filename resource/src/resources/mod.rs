@@ -1,12 +1,30 @@
+pub mod apk;
 pub mod apt;
 pub mod apt_repo;
+pub mod aur;
+pub mod brew;
 pub mod command;
+pub mod cron;
 pub mod directory;
+pub mod dnf;
 pub mod file;
+pub mod firewall;
 pub mod git;
+pub mod git_config;
+pub mod gpg_key;
 pub mod group;
+pub mod launchd;
+pub mod networkd;
+pub mod nix;
 pub mod pacman;
+pub mod pip;
 pub mod podman;
+pub mod podman_image;
+pub mod rustup;
 pub mod secret;
 pub mod systemd;
+pub mod systemd_unit;
+pub mod time;
+pub mod tls_cert;
 pub mod user;
+pub mod wireguard;
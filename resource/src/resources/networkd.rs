@@ -0,0 +1,349 @@
+//! `@core/networkd`: a systemd-networkd `.network` file, `.netdev` file, or
+//! both, for static addresses, bridges, VLANs and the like (see
+//! [`lusid_operation::operations::networkd`]).
+//!
+//! Each file given is its own atom, written to
+//! `/etc/systemd/network/<name>.network` or `<name>.netdev`. When one
+//! changes, its operations write it and then run `networkctl reload`, once
+//! per epoch however many files changed.
+//
+// Note(cc): `networkctl reload` creates the netdev of a new `.netdev` file,
+// but leaves an existing netdev as it is when its file changes; that takes
+// deleting the netdev (`networkctl delete`) or a reboot, which is left to
+// the plan's author. A changed `.network` file does reconfigure the links it
+// matches.
+
+use std::fmt::Display;
+
+use async_trait::async_trait;
+use lusid_causality::{CausalityMeta, CausalityTree};
+use lusid_ctx::Context;
+use lusid_fs::{self as fs, FsError};
+use lusid_operation::{
+    Operation,
+    operations::{file::FilePath, networkd::NetworkdOperation},
+};
+use lusid_params::{ParseError, ParseParams, StructFields, parse_string};
+use lusid_view::impl_display_render;
+use rimu::{Spanned, Value};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
+
+const NETWORK_DIR: &str = "/etc/systemd/network";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NetworkdParams {
+    /// The files' name, without extension, e.g. `10-br0`.
+    pub name: String,
+    /// Contents of `<name>.network`.
+    pub network: Option<String>,
+    /// Contents of `<name>.netdev`.
+    pub netdev: Option<String>,
+}
+
+impl ParseParams for NetworkdParams {
+    fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
+        let mut fields = StructFields::new(value)?;
+        let name = fields.required("name", parse_name)?;
+        let network = fields.optional_string("network")?;
+        let netdev = fields.optional_string("netdev")?;
+        fields.finish()?;
+        Ok(NetworkdParams {
+            name,
+            network,
+            netdev,
+        })
+    }
+}
+
+// The name is joined onto `/etc/systemd/network/`, so it has to stay a file
+// name.
+fn parse_name(value: Spanned<Value>) -> Result<String, Spanned<ParseError>> {
+    let span = value.span();
+    let name = parse_string(value)?;
+    let reason = if name.is_empty() {
+        Some("the name is empty")
+    } else if name.contains(['/', '\0']) {
+        Some("the name must be a file name, without `/`")
+    } else if name.starts_with('.') {
+        Some("the name can't start with `.`")
+    } else {
+        None
+    };
+    match reason {
+        Some(reason) => Err(Spanned::new(
+            ParseError::InvalidTargetPath {
+                value: format!("{NETWORK_DIR}/{name}"),
+                reason: reason.to_owned(),
+            },
+            span,
+        )),
+        None => Ok(name),
+    }
+}
+
+impl Display for NetworkdParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            name,
+            network,
+            netdev,
+        } = self;
+        let bytes = |content: &Option<String>| match content {
+            Some(content) => format!("{} bytes", content.len()),
+            None => "none".to_owned(),
+        };
+        write!(
+            f,
+            "Networkd(name = {name}, network = {}, netdev = {})",
+            bytes(network),
+            bytes(netdev)
+        )
+    }
+}
+
+impl_display_render!(NetworkdParams);
+
+/// One config file. `name` is the file's name, e.g. `10-br0.netdev`.
+#[derive(Debug, Clone)]
+pub struct NetworkdResource {
+    pub name: String,
+    pub path: FilePath,
+    pub content: String,
+}
+
+impl Display for NetworkdResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            name,
+            path,
+            content,
+        } = self;
+        write!(
+            f,
+            "Networkd(name = {name}, path = {path}, content = {} bytes)",
+            content.len()
+        )
+    }
+}
+
+impl_display_render!(NetworkdResource);
+
+#[derive(Debug, Clone)]
+pub enum NetworkdState {
+    Matches,
+    Differs,
+}
+
+impl Display for NetworkdState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetworkdState::Matches => write!(f, "Networkd::Matches"),
+            NetworkdState::Differs => write!(f, "Networkd::Differs"),
+        }
+    }
+}
+
+impl_display_render!(NetworkdState);
+
+#[derive(Error, Debug)]
+pub enum NetworkdStateError {
+    #[error(transparent)]
+    Fs(#[from] FsError),
+}
+
+#[derive(Debug, Clone)]
+pub enum NetworkdChange {
+    Write {
+        name: String,
+        path: FilePath,
+        content: String,
+    },
+}
+
+impl Display for NetworkdChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetworkdChange::Write { name, path, .. } => {
+                write!(f, "Networkd::write+reload({name} -> {path})")
+            }
+        }
+    }
+}
+
+impl_display_render!(NetworkdChange);
+
+#[derive(Debug, Clone)]
+pub struct Networkd;
+
+#[async_trait]
+impl ResourceType for Networkd {
+    const ID: &'static str = "networkd";
+    const DESCRIPTION: &'static str = "Write systemd-networkd `.network` and `.netdev` files, reloading networkd when they change.";
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[ParamsDoc {
+        description: "A network's config files. Whichever of `network` and `netdev` isn't given is left as it is.",
+        params: &[
+            ParamDoc::required(
+                "name",
+                ParamDocType::String,
+                "The files' name, without extension, e.g. `10-br0`. networkd reads files in the order of their names.",
+            ),
+            ParamDoc::optional(
+                "network",
+                ParamDocType::String,
+                "Contents of `/etc/systemd/network/<name>.network`: which links it matches, and their addresses, routes and DNS.",
+            ),
+            ParamDoc::optional(
+                "netdev",
+                ParamDocType::String,
+                "Contents of `/etc/systemd/network/<name>.netdev`: a virtual device to create, like a bridge or VLAN.",
+            ),
+        ],
+    }];
+
+    type Params = NetworkdParams;
+    type Resource = NetworkdResource;
+
+    fn resources(params: Self::Params) -> Vec<CausalityTree<Self::Resource>> {
+        let NetworkdParams {
+            name,
+            network,
+            netdev,
+        } = params;
+        [("netdev", netdev), ("network", network)]
+            .into_iter()
+            .filter_map(|(extension, content)| {
+                let file_name = format!("{name}.{extension}");
+                content.map(|content| NetworkdResource {
+                    path: FilePath::new(format!("{NETWORK_DIR}/{file_name}")),
+                    name: file_name,
+                    content,
+                })
+            })
+            .map(|resource| CausalityTree::leaf(CausalityMeta::default(), resource))
+            .collect()
+    }
+
+    type State = NetworkdState;
+    type StateError = NetworkdStateError;
+
+    async fn state(
+        _ctx: &mut Context,
+        resource: &Self::Resource,
+    ) -> Result<Self::State, Self::StateError> {
+        let NetworkdResource { path, content, .. } = resource;
+        let matches = fs::path_exists(path.as_path()).await?
+            && fs::read_file_to_string(path.as_path()).await? == *content;
+        Ok(if matches {
+            NetworkdState::Matches
+        } else {
+            NetworkdState::Differs
+        })
+    }
+
+    type Change = NetworkdChange;
+
+    fn change(resource: &Self::Resource, state: &Self::State) -> Option<Self::Change> {
+        match state {
+            NetworkdState::Matches => None,
+            NetworkdState::Differs => {
+                let NetworkdResource {
+                    name,
+                    path,
+                    content,
+                } = resource.clone();
+                Some(NetworkdChange::Write {
+                    name,
+                    path,
+                    content,
+                })
+            }
+        }
+    }
+
+    fn operations(change: Self::Change) -> Vec<CausalityTree<Operation>> {
+        match change {
+            NetworkdChange::Write {
+                name,
+                path,
+                content,
+            } => vec![
+                CausalityTree::leaf(
+                    CausalityMeta::id("write".into()),
+                    Operation::Networkd(NetworkdOperation::WriteConfig {
+                        name,
+                        path,
+                        content,
+                    }),
+                ),
+                CausalityTree::leaf(
+                    CausalityMeta::requires(vec!["write".into()]),
+                    Operation::Networkd(NetworkdOperation::Reload),
+                ),
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_each_file_given() {
+        let resources = Networkd::resources(NetworkdParams {
+            name: "10-br0".into(),
+            network: Some("[Match]\nName=br0\n".into()),
+            netdev: Some("[NetDev]\nName=br0\nKind=bridge\n".into()),
+        });
+        let paths: Vec<String> = resources
+            .iter()
+            .map(|tree| match tree {
+                CausalityTree::Leaf { node, .. } => node.path.to_string(),
+                _ => panic!("expected leaf"),
+            })
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "/etc/systemd/network/10-br0.netdev",
+                "/etc/systemd/network/10-br0.network",
+            ]
+        );
+
+        let resources = Networkd::resources(NetworkdParams {
+            name: "20-wired".into(),
+            network: Some("[Match]\nName=en*\n".into()),
+            netdev: None,
+        });
+        assert_eq!(resources.len(), 1);
+    }
+
+    #[test]
+    fn changed_file_is_written_then_reloaded() {
+        let resource = NetworkdResource {
+            name: "20-wired.network".into(),
+            path: FilePath::new("/etc/systemd/network/20-wired.network"),
+            content: "[Match]\nName=en*\n".into(),
+        };
+        assert!(Networkd::change(&resource, &NetworkdState::Matches).is_none());
+        let change = Networkd::change(&resource, &NetworkdState::Differs).expect("change");
+        let operations: Vec<String> = Networkd::operations(change)
+            .iter()
+            .map(|tree| match tree {
+                CausalityTree::Leaf { node, .. } => node.to_string(),
+                _ => panic!("expected leaf"),
+            })
+            .collect();
+        assert_eq!(
+            operations,
+            [
+                "Networkd::WriteConfig(name = 20-wired.network, path = /etc/systemd/network/20-wired.network, 17 bytes)",
+                "Networkd::Reload",
+            ]
+        );
+    }
+}
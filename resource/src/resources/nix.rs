@@ -0,0 +1,397 @@
+//! `@core/nix`: packages in the apply user's Nix profile, installed or
+//! removed with `nix profile` (see [`lusid_operation::operations::nix`]).
+//!
+//! Each package is a flake attribute, like `ripgrep` from `nixpkgs`, and its
+//! own atom. One `nix profile list --json` probes them all, and an epoch's
+//! installs and removals each merge into one `nix profile` command.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use async_trait::async_trait;
+use lusid_causality::{CausalityMeta, CausalityTree};
+use lusid_cmd::CommandError;
+use lusid_ctx::Context;
+use lusid_operation::{
+    Operation,
+    operations::nix::{NixOperation, nix},
+};
+use lusid_params::{ParseError, ParseParams, StructFields};
+use lusid_view::impl_display_render;
+use rimu::{Spanned, Value};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
+
+const DEFAULT_FLAKE: &str = "nixpkgs";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NixParams {
+    pub packages: Vec<String>,
+    pub flake: String,
+    pub present: bool,
+}
+
+impl ParseParams for NixParams {
+    fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
+        let mut fields = StructFields::new(value)?;
+        let packages = if fields.has("packages") {
+            fields.required_string_list("packages")?
+        } else {
+            vec![fields.required_string("package")?]
+        };
+        let flake = fields
+            .optional_string("flake")?
+            .unwrap_or_else(|| DEFAULT_FLAKE.to_owned());
+        let present = if fields.has("state") {
+            fields.take_discriminator("state", &["present", "absent"])? == "present"
+        } else {
+            true
+        };
+        fields.finish()?;
+        Ok(NixParams {
+            packages,
+            flake,
+            present,
+        })
+    }
+}
+
+impl Display for NixParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            packages,
+            flake,
+            present,
+        } = self;
+        let packages = packages.join(", ");
+        if *present {
+            write!(f, "Nix(flake = {flake}, packages = [{packages}])")
+        } else {
+            write!(f, "Nix::Absent(packages = [{packages}])")
+        }
+    }
+}
+
+impl_display_render!(NixParams);
+
+#[derive(Debug, Clone)]
+pub enum NixResource {
+    Present { flake: String, attribute: String },
+    Absent { attribute: String },
+}
+
+impl NixResource {
+    pub fn attribute(&self) -> &str {
+        match self {
+            NixResource::Present { attribute, .. } | NixResource::Absent { attribute } => attribute,
+        }
+    }
+}
+
+impl Display for NixResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NixResource::Present { flake, attribute } => write!(f, "Nix({flake}#{attribute})"),
+            NixResource::Absent { attribute } => write!(f, "Nix::Absent({attribute})"),
+        }
+    }
+}
+
+impl_display_render!(NixResource);
+
+#[derive(Debug, Clone)]
+pub enum NixState {
+    NotInstalled,
+    /// In the profile, as the element `nix profile remove` knows it by.
+    Installed {
+        element: String,
+    },
+}
+
+impl Display for NixState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NixState::NotInstalled => write!(f, "Nix::NotInstalled"),
+            NixState::Installed { element } => write!(f, "Nix::Installed({element})"),
+        }
+    }
+}
+
+impl_display_render!(NixState);
+
+#[derive(Error, Debug)]
+pub enum NixStateError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+
+    #[error("nix profile list failed: {stderr}")]
+    List { stderr: String },
+
+    #[error("failed to parse nix profile list output: {0}")]
+    ParseList(#[source] serde_json::Error),
+}
+
+#[derive(Debug, Clone)]
+pub enum NixChange {
+    Install { installable: String },
+    Remove { element: String },
+}
+
+impl Display for NixChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NixChange::Install { installable } => write!(f, "Nix::Install({installable})"),
+            NixChange::Remove { element } => write!(f, "Nix::Remove({element})"),
+        }
+    }
+}
+
+impl_display_render!(NixChange);
+
+/// The `nix profile list --json` fields we read.
+#[derive(Debug, Deserialize)]
+struct ProfileList {
+    elements: ProfileElements,
+}
+
+/// Since Nix 2.20, elements are keyed by name; before, they were a list,
+/// and `nix profile remove` took an element's attribute path instead.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ProfileElements {
+    Named(HashMap<String, ProfileElement>),
+    Listed(Vec<ProfileElement>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProfileElement {
+    /// Like `legacyPackages.x86_64-linux.ripgrep`. Missing for store paths
+    /// installed directly.
+    #[serde(default)]
+    attr_path: Option<String>,
+}
+
+/// The profile's elements installed from a flake, as pairs of the name to
+/// remove each by and its attribute path.
+fn parse_list(stdout: &[u8]) -> Result<Vec<(String, String)>, NixStateError> {
+    let list: ProfileList = serde_json::from_slice(stdout).map_err(NixStateError::ParseList)?;
+    Ok(match list.elements {
+        ProfileElements::Named(elements) => elements
+            .into_iter()
+            .filter_map(|(name, element)| Some((name, element.attr_path?)))
+            .collect(),
+        ProfileElements::Listed(elements) => elements
+            .into_iter()
+            .filter_map(|element| {
+                let attr_path = element.attr_path?;
+                Some((attr_path.clone(), attr_path))
+            })
+            .collect(),
+    })
+}
+
+/// Whether `attr_path` is `attribute` of some flake, under whichever of its
+/// per-system outputs: `legacyPackages.x86_64-linux.ripgrep` is `ripgrep`.
+// Note(cc): the flake an element came from isn't compared, so `ripgrep` from
+// one flake counts as installed from any other.
+fn is_attribute(attr_path: &str, attribute: &str) -> bool {
+    attr_path == attribute
+        || attr_path
+            .strip_suffix(attribute)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+#[derive(Debug, Clone)]
+pub struct Nix;
+
+#[async_trait]
+impl ResourceType for Nix {
+    const ID: &'static str = "nix";
+    const DESCRIPTION: &'static str = "Install or remove packages in the apply user's Nix profile.";
+    const PLATFORMS: &'static [&'static str] = &["linux", "macos"];
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[
+        ParamsDoc {
+            description: "One package.",
+            params: &[
+                ParamDoc::required(
+                    "package",
+                    ParamDocType::String,
+                    "Attribute of the package in the flake, like `ripgrep`.",
+                ),
+                FLAKE_DOC,
+                STATE_DOC,
+            ],
+        },
+        ParamsDoc {
+            description: "Many packages.",
+            params: &[
+                ParamDoc::required(
+                    "packages",
+                    ParamDocType::StringList,
+                    "Attributes of the packages in the flake.",
+                ),
+                FLAKE_DOC,
+                STATE_DOC,
+            ],
+        },
+    ];
+
+    type Params = NixParams;
+    type Resource = NixResource;
+
+    fn resources(params: Self::Params) -> Vec<CausalityTree<Self::Resource>> {
+        let NixParams {
+            packages,
+            flake,
+            present,
+        } = params;
+        packages
+            .into_iter()
+            .map(|attribute| {
+                let resource = if present {
+                    NixResource::Present {
+                        flake: flake.clone(),
+                        attribute,
+                    }
+                } else {
+                    NixResource::Absent { attribute }
+                };
+                CausalityTree::leaf(CausalityMeta::default(), resource)
+            })
+            .collect()
+    }
+
+    type State = NixState;
+    type StateError = NixStateError;
+    async fn state(
+        ctx: &mut Context,
+        resource: &Self::Resource,
+    ) -> Result<Self::State, Self::StateError> {
+        let mut states = Self::states_bulk(ctx, &[resource]).await?;
+        Ok(states.remove(0))
+    }
+
+    async fn states_bulk(
+        _ctx: &mut Context,
+        resources: &[&Self::Resource],
+    ) -> Result<Vec<Self::State>, Self::StateError> {
+        if resources.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let outcome = nix().args(["profile", "list", "--json"]).outcome().await?;
+        if !outcome.status.success() {
+            return Err(NixStateError::List {
+                stderr: String::from_utf8_lossy(&outcome.stderr).trim().to_owned(),
+            });
+        }
+        let elements = parse_list(&outcome.stdout)?;
+
+        Ok(resources
+            .iter()
+            .map(|resource| {
+                let installed = elements
+                    .iter()
+                    .find(|(_, attr_path)| is_attribute(attr_path, resource.attribute()));
+                match installed {
+                    Some((element, _)) => NixState::Installed {
+                        element: element.clone(),
+                    },
+                    None => NixState::NotInstalled,
+                }
+            })
+            .collect())
+    }
+
+    type Change = NixChange;
+    fn change(resource: &Self::Resource, state: &Self::State) -> Option<Self::Change> {
+        match (resource, state) {
+            (NixResource::Present { flake, attribute }, NixState::NotInstalled) => {
+                Some(NixChange::Install {
+                    installable: format!("{flake}#{attribute}"),
+                })
+            }
+            (NixResource::Absent { .. }, NixState::Installed { element }) => {
+                Some(NixChange::Remove {
+                    element: element.clone(),
+                })
+            }
+            (NixResource::Present { .. }, NixState::Installed { .. })
+            | (NixResource::Absent { .. }, NixState::NotInstalled) => None,
+        }
+    }
+
+    fn operations(change: Self::Change) -> Vec<CausalityTree<Operation>> {
+        let operation = match change {
+            NixChange::Install { installable } => NixOperation::Install {
+                installables: vec![installable],
+            },
+            NixChange::Remove { element } => NixOperation::Remove {
+                elements: vec![element],
+            },
+        };
+        vec![CausalityTree::leaf(
+            CausalityMeta::default(),
+            Operation::Nix(operation),
+        )]
+    }
+}
+
+const FLAKE_DOC: ParamDoc = ParamDoc::optional(
+    "flake",
+    ParamDocType::String,
+    "Flake to install the packages from. Default: nixpkgs.",
+);
+
+const STATE_DOC: ParamDoc = ParamDoc::optional(
+    "state",
+    ParamDocType::OneOf(&[
+        ParamDocType::Literal("present"),
+        ParamDocType::Literal("absent"),
+    ]),
+    "Whether the packages should be in the profile. Default: present.",
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elements_are_read_from_either_list_format() {
+        let named = br#"{"elements":{"ripgrep":{"active":true,"attrPath":"legacyPackages.x86_64-linux.ripgrep","originalUrl":"flake:nixpkgs","storePaths":[]}},"version":3}"#;
+        assert_eq!(
+            parse_list(named).unwrap(),
+            [(
+                "ripgrep".to_owned(),
+                "legacyPackages.x86_64-linux.ripgrep".to_owned()
+            )]
+        );
+
+        let listed = br#"{"elements":[{"active":true,"attrPath":"legacyPackages.aarch64-darwin.fd","originalUrl":"flake:nixpkgs","storePaths":[]},{"active":true,"storePaths":["/nix/store/abc-hello"]}],"version":2}"#;
+        assert_eq!(
+            parse_list(listed).unwrap(),
+            [(
+                "legacyPackages.aarch64-darwin.fd".to_owned(),
+                "legacyPackages.aarch64-darwin.fd".to_owned()
+            )]
+        );
+    }
+
+    #[test]
+    fn attributes_match_under_any_system() {
+        assert!(is_attribute(
+            "legacyPackages.x86_64-linux.ripgrep",
+            "ripgrep"
+        ));
+        assert!(is_attribute(
+            "legacyPackages.x86_64-linux.python3Packages.black",
+            "python3Packages.black"
+        ));
+        assert!(!is_attribute("legacyPackages.x86_64-linux.fd", "d"));
+        assert!(!is_attribute("legacyPackages.x86_64-linux.fd", "ripgrep"));
+    }
+}
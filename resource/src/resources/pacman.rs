@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::fmt::Display;
 
 use async_trait::async_trait;
@@ -8,11 +9,14 @@ use lusid_operation::{Operation, operations::pacman::PacmanOperation};
 use lusid_params::{ParseError, ParseParams, StructFields};
 use lusid_view::impl_display_render;
 use rimu::{Spanned, Value};
+use serde::Serialize;
 use thiserror::Error;
 
 use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum PacmanParams {
     Package { package: String },
     Packages { packages: Vec<String> },
@@ -86,6 +90,9 @@ pub enum PacmanStateError {
 
     #[error("failed to determine package status: {output}")]
     ParseStatus { output: String },
+
+    #[error("pacman -Q failed: {stderr}")]
+    Query { stderr: String },
 }
 
 // TODO(cc): add an `Uninstall` variant — mirror image of the apt resource. A declared
@@ -111,6 +118,26 @@ pub struct Pacman;
 #[async_trait]
 impl ResourceType for Pacman {
     const ID: &'static str = "pacman";
+    const DESCRIPTION: &'static str = "Install Arch Linux packages with pacman.";
+    const PLATFORMS: &'static [&'static str] = &["arch"];
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[
+        ParamsDoc {
+            description: "One package.",
+            params: &[ParamDoc::required(
+                "package",
+                ParamDocType::String,
+                "Name of the package to install.",
+            )],
+        },
+        ParamsDoc {
+            description: "Many packages.",
+            params: &[ParamDoc::required(
+                "packages",
+                ParamDocType::StringList,
+                "Names of the packages to install.",
+            )],
+        },
+    ];
 
     type Params = PacmanParams;
     type Resource = PacmanResource;
@@ -161,6 +188,57 @@ impl ResourceType for Pacman {
             .await?
     }
 
+    async fn states_bulk(
+        _ctx: &mut Context,
+        resources: &[&Self::Resource],
+    ) -> Result<Vec<Self::State>, Self::StateError> {
+        // With no packages, `pacman -Q` would list every one installed.
+        if resources.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Found packages are listed under their own names, which for a
+        // provided name (`sh`) isn't the one asked for, so go by what's
+        // missing: each gets an error line, and the exit status is 1.
+        let outcome = Command::new("pacman")
+            .arg("-Q")
+            .args(resources.iter().map(|resource| &resource.package))
+            .outcome()
+            .await?;
+        let stderr = String::from_utf8_lossy(&outcome.stderr);
+        let mut missing: HashSet<&str> = HashSet::new();
+        if !outcome.status.success() {
+            // Any other error, or a failure without any, is pacman's own.
+            for line in stderr.lines().filter(|line| !line.trim().is_empty()) {
+                let Some(package) = line
+                    .strip_prefix("error: package '")
+                    .and_then(|rest| rest.strip_suffix("' was not found"))
+                else {
+                    return Err(PacmanStateError::Query {
+                        stderr: stderr.trim().to_owned(),
+                    });
+                };
+                missing.insert(package);
+            }
+            if missing.is_empty() {
+                return Err(PacmanStateError::Query {
+                    stderr: stderr.trim().to_owned(),
+                });
+            }
+        }
+
+        Ok(resources
+            .iter()
+            .map(|resource| {
+                if missing.contains(resource.package.as_str()) {
+                    PacmanState::NotInstalled
+                } else {
+                    PacmanState::Installed
+                }
+            })
+            .collect())
+    }
+
     type Change = PacmanChange;
     fn change(resource: &Self::Resource, state: &Self::State) -> Option<Self::Change> {
         match state {
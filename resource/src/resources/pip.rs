@@ -0,0 +1,625 @@
+//! `@core/pip`: Python packages, installed with pipx or into a virtualenv.
+//!
+//! Without `venv`, each package is a pipx app in a virtualenv of its own, for
+//! tools like `black` or `httpie`. With `venv`, packages go into that
+//! virtualenv, which is created with `python3 -m venv` if it's missing. pipx
+//! (or `python3`, for a venv) must already be installed, e.g. by `@core/apt`.
+//!
+//! A package pinned as `name==version` is reinstalled when another version is
+//! installed; an unpinned one is only installed when it's missing. Installed
+//! versions are read with `pipx list --json` or the venv's `pip list`, once
+//! per target for all of its packages.
+//!
+//! Note(cc): versions are compared as strings, so pin them as pip reports
+//! them: `2.0` would be reinstalled over an installed `2.0.0` on every apply.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Display;
+
+use async_trait::async_trait;
+use lusid_causality::{CausalityMeta, CausalityTree};
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_fs::{self as fs, FsError};
+use lusid_operation::{
+    Operation,
+    operations::{
+        file::FilePath,
+        pip::{PipOperation, PipTarget},
+    },
+};
+use lusid_params::{ParseError, ParseParams, StructFields, parse_list, parse_string};
+use lusid_view::impl_display_render;
+use rimu::{Spanned, Value};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
+use crate::resources::file::parse_file_path;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PipParams {
+    Package {
+        package: String,
+        version: Option<String>,
+        venv: Option<FilePath>,
+    },
+    Packages {
+        /// Each `name` or `name==version`.
+        packages: Vec<String>,
+        venv: Option<FilePath>,
+    },
+}
+
+impl ParseParams for PipParams {
+    fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
+        let mut fields = StructFields::new(value)?;
+        // Untagged dispatch, as for `@core/apt`: `packages` wins when present.
+        let out = if fields.has("packages") {
+            PipParams::Packages {
+                packages: fields
+                    .required("packages", |value| parse_list(value, parse_requirement))?,
+                venv: fields.optional("venv", parse_file_path)?,
+            }
+        } else {
+            PipParams::Package {
+                package: fields.required("package", parse_package)?,
+                version: fields.optional("version", parse_version)?,
+                venv: fields.optional("venv", parse_file_path)?,
+            }
+        };
+        fields.finish()?;
+        Ok(out)
+    }
+}
+
+/// Parse a `name` or `name==version` requirement, so a range like `>=2` or a
+/// URL fails at plan load rather than never matching what's installed.
+fn parse_requirement(value: Spanned<Value>) -> Result<String, Spanned<ParseError>> {
+    parse_checked(value, |requirement| {
+        split_requirement(requirement).map(|_| ())
+    })
+}
+
+/// Parse the single-package form's `package`, which is pinned with `version`
+/// rather than `==`.
+fn parse_package(value: Spanned<Value>) -> Result<String, Spanned<ParseError>> {
+    parse_checked(value, |package| match split_requirement(package)? {
+        (_, None) => Ok(()),
+        (_, Some(_)) => Err("pin the version with `version`, not `==`"),
+    })
+}
+
+fn parse_version(value: Spanned<Value>) -> Result<String, Spanned<ParseError>> {
+    parse_checked(value, |version| {
+        if is_version(version) {
+            Ok(())
+        } else {
+            Err(VERSION_REASON)
+        }
+    })
+}
+
+fn parse_checked(
+    value: Spanned<Value>,
+    check: impl FnOnce(&str) -> Result<(), &'static str>,
+) -> Result<String, Spanned<ParseError>> {
+    let span = value.span();
+    let string = parse_string(value)?;
+    match check(&string) {
+        Ok(()) => Ok(string),
+        Err(reason) => Err(Spanned::new(
+            ParseError::InvalidRequirement {
+                value: string,
+                reason: reason.to_owned(),
+            },
+            span,
+        )),
+    }
+}
+
+const VERSION_REASON: &str = "expected a single version, e.g. 24.1.0";
+
+fn is_version(version: &str) -> bool {
+    !version.is_empty() && !version.contains(|c: char| c.is_whitespace() || c == '=')
+}
+
+/// Split a requirement into its name and pinned version.
+fn split_requirement(requirement: &str) -> Result<(&str, Option<&str>), &'static str> {
+    let (name, version) = match requirement.split_once("==") {
+        Some((name, version)) => (name, Some(version)),
+        None => (requirement, None),
+    };
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.');
+    if name.is_empty()
+        || !name.chars().all(is_name_char)
+        || !name.starts_with(|c: char| c.is_ascii_alphanumeric())
+    {
+        return Err("expected a package name, optionally pinned as name==version");
+    }
+    if !version.is_none_or(is_version) {
+        return Err(VERSION_REASON);
+    }
+    Ok((name, version))
+}
+
+impl Display for PipParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PipParams::Package {
+                package,
+                version,
+                venv,
+            } => {
+                write!(f, "Pip(package = {package}")?;
+                if let Some(version) = version {
+                    write!(f, ", version = {version}")?;
+                }
+                write!(f, "{})", Venv(venv))
+            }
+            PipParams::Packages { packages, venv } => {
+                write!(f, "Pip(packages = [{}]{})", packages.join(", "), Venv(venv))
+            }
+        }
+    }
+}
+
+impl_display_render!(PipParams);
+
+/// Formats a trailing `, venv = <path>` argument, or nothing for pipx.
+struct Venv<'a>(&'a Option<FilePath>);
+
+impl Display for Venv<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(path) => write!(f, ", venv = {path}"),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PipResource {
+    pub target: PipTarget,
+    pub package: String,
+    /// Pinned version; any installed version will do when `None`.
+    pub version: Option<String>,
+}
+
+impl PipResource {
+    /// The package as pip or pipx takes it: `name` or `name==version`.
+    pub fn requirement(&self) -> String {
+        match &self.version {
+            Some(version) => format!("{}=={version}", self.package),
+            None => self.package.clone(),
+        }
+    }
+}
+
+impl Display for PipResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Pip({}, target = {})", self.requirement(), self.target)
+    }
+}
+
+impl_display_render!(PipResource);
+
+#[derive(Debug, Clone)]
+pub enum PipState {
+    /// The virtualenv to install into doesn't exist yet.
+    NoVenv,
+    NotInstalled,
+    Installed {
+        version: String,
+    },
+}
+
+impl Display for PipState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PipState::NoVenv => write!(f, "Pip::NoVenv"),
+            PipState::NotInstalled => write!(f, "Pip::NotInstalled"),
+            PipState::Installed { version } => write!(f, "Pip::Installed(version = {version})"),
+        }
+    }
+}
+
+impl_display_render!(PipState);
+
+#[derive(Error, Debug)]
+pub enum PipStateError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+
+    #[error(transparent)]
+    Fs(#[from] FsError),
+
+    #[error("{command} failed: {stderr}")]
+    List {
+        command: &'static str,
+        stderr: String,
+    },
+
+    #[error("failed to parse {command} output: {source}")]
+    ParseList {
+        command: &'static str,
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+// TODO(cc): add an `absent` state (`pipx uninstall`, `pip uninstall -y`).
+#[derive(Debug, Clone)]
+pub enum PipChange {
+    /// Install `requirement` into `target`, creating the virtualenv first if
+    /// `create_venv`. `installed` is the version it replaces, if any.
+    Install {
+        target: PipTarget,
+        requirement: String,
+        installed: Option<String>,
+        create_venv: bool,
+    },
+}
+
+impl Display for PipChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PipChange::Install {
+                target,
+                requirement,
+                installed,
+                create_venv,
+            } => {
+                write!(f, "Pip::Install({requirement}, target = {target}")?;
+                if let Some(installed) = installed {
+                    write!(f, ", was {installed}")?;
+                }
+                if *create_venv {
+                    write!(f, ", creating venv")?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl_display_render!(PipChange);
+
+/// The `pipx list --json` fields we read.
+#[derive(Debug, Deserialize)]
+struct PipxList {
+    venvs: HashMap<String, PipxVenv>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PipxVenv {
+    metadata: PipxMetadata,
+}
+
+#[derive(Debug, Deserialize)]
+struct PipxMetadata {
+    main_package: PipxPackage,
+}
+
+#[derive(Debug, Deserialize)]
+struct PipxPackage {
+    package: String,
+    package_version: String,
+}
+
+/// An entry of `pip list --format=json`.
+#[derive(Debug, Deserialize)]
+struct PipListEntry {
+    name: String,
+    version: String,
+}
+
+/// A package name as PyPI compares them (PEP 503): case-insensitive, with
+/// runs of `-`, `_` and `.` all equal.
+fn normalize_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut separator = false;
+    for c in name.chars() {
+        if matches!(c, '-' | '_' | '.') {
+            separator = true;
+            continue;
+        }
+        if separator && !out.is_empty() {
+            out.push('-');
+        }
+        separator = false;
+        out.push(c.to_ascii_lowercase());
+    }
+    out
+}
+
+/// The versions installed into `target`, by normalized name, or `None` when
+/// it's a virtualenv that doesn't exist.
+async fn installed_versions(
+    target: &PipTarget,
+) -> Result<Option<HashMap<String, String>>, PipStateError> {
+    match target {
+        PipTarget::Pipx => {
+            let command = "pipx list";
+            let stdout = list(Command::new("pipx").args(["list", "--json"]), command).await?;
+            let pipx: PipxList = serde_json::from_slice(&stdout)
+                .map_err(|source| PipStateError::ParseList { command, source })?;
+            Ok(Some(
+                pipx.venvs
+                    .into_values()
+                    .map(|venv| {
+                        let PipxPackage {
+                            package,
+                            package_version,
+                        } = venv.metadata.main_package;
+                        (normalize_name(&package), package_version)
+                    })
+                    .collect(),
+            ))
+        }
+        PipTarget::Venv { path } => {
+            let pip = PipTarget::venv_pip(path);
+            if !fs::path_exists(&pip).await? {
+                return Ok(None);
+            }
+            let command = "pip list";
+            let stdout = list(
+                Command::new(pip).args(["list", "--format=json", "--disable-pip-version-check"]),
+                command,
+            )
+            .await?;
+            let entries: Vec<PipListEntry> = serde_json::from_slice(&stdout)
+                .map_err(|source| PipStateError::ParseList { command, source })?;
+            Ok(Some(
+                entries
+                    .into_iter()
+                    .map(|entry| (normalize_name(&entry.name), entry.version))
+                    .collect(),
+            ))
+        }
+    }
+}
+
+async fn list(cmd: &mut Command, command: &'static str) -> Result<Vec<u8>, PipStateError> {
+    let outcome = cmd.outcome().await?;
+    if !outcome.status.success() {
+        return Err(PipStateError::List {
+            command,
+            stderr: String::from_utf8_lossy(&outcome.stderr).trim().to_owned(),
+        });
+    }
+    Ok(outcome.stdout)
+}
+
+fn state_in(resource: &PipResource, installed: Option<&HashMap<String, String>>) -> PipState {
+    let Some(installed) = installed else {
+        return PipState::NoVenv;
+    };
+    match installed.get(&normalize_name(&resource.package)) {
+        Some(version) => PipState::Installed {
+            version: version.clone(),
+        },
+        None => PipState::NotInstalled,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Pip;
+
+#[async_trait]
+impl ResourceType for Pip {
+    const ID: &'static str = "pip";
+    const DESCRIPTION: &'static str = "Install Python packages with pipx or into a virtualenv.";
+    const PLATFORMS: &'static [&'static str] = &["linux", "macos"];
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[
+        ParamsDoc {
+            description: "One package.",
+            params: &[
+                ParamDoc::required("package", ParamDocType::String, "Package name."),
+                ParamDoc::optional(
+                    "version",
+                    ParamDocType::String,
+                    "Exact version to pin, e.g. `24.1.0`.",
+                ),
+                VENV_DOC,
+            ],
+        },
+        ParamsDoc {
+            description: "Many packages.",
+            params: &[
+                ParamDoc::required(
+                    "packages",
+                    ParamDocType::StringList,
+                    "Packages, each `name` or pinned as `name==version`.",
+                ),
+                VENV_DOC,
+            ],
+        },
+    ];
+
+    type Params = PipParams;
+    type Resource = PipResource;
+
+    fn resources(params: Self::Params) -> Vec<CausalityTree<Self::Resource>> {
+        let target = |venv: Option<FilePath>| match venv {
+            Some(path) => PipTarget::Venv { path },
+            None => PipTarget::Pipx,
+        };
+        let resources = match params {
+            PipParams::Package {
+                package,
+                version,
+                venv,
+            } => vec![PipResource {
+                target: target(venv),
+                package,
+                version,
+            }],
+            PipParams::Packages { packages, venv } => packages
+                .iter()
+                .map(|requirement| {
+                    // Checked by `parse_requirement`.
+                    let (package, version) = split_requirement(requirement)
+                        .expect("requirement was validated at parse time");
+                    PipResource {
+                        target: target(venv.clone()),
+                        package: package.to_owned(),
+                        version: version.map(str::to_owned),
+                    }
+                })
+                .collect(),
+        };
+        resources
+            .into_iter()
+            .map(|resource| CausalityTree::leaf(CausalityMeta::default(), resource))
+            .collect()
+    }
+
+    type State = PipState;
+    type StateError = PipStateError;
+
+    async fn state(
+        _ctx: &mut Context,
+        resource: &Self::Resource,
+    ) -> Result<Self::State, Self::StateError> {
+        let installed = installed_versions(&resource.target).await?;
+        Ok(state_in(resource, installed.as_ref()))
+    }
+
+    async fn states_bulk(
+        _ctx: &mut Context,
+        resources: &[&Self::Resource],
+    ) -> Result<Vec<Self::State>, Self::StateError> {
+        let targets: BTreeSet<&PipTarget> =
+            resources.iter().map(|resource| &resource.target).collect();
+        let mut installed: BTreeMap<&PipTarget, Option<HashMap<String, String>>> = BTreeMap::new();
+        for target in targets {
+            installed.insert(target, installed_versions(target).await?);
+        }
+        Ok(resources
+            .iter()
+            .map(|resource| state_in(resource, installed[&resource.target].as_ref()))
+            .collect())
+    }
+
+    type Change = PipChange;
+
+    fn change(resource: &Self::Resource, state: &Self::State) -> Option<Self::Change> {
+        let install = |installed: Option<String>, create_venv: bool| PipChange::Install {
+            target: resource.target.clone(),
+            requirement: resource.requirement(),
+            installed,
+            create_venv,
+        };
+        match state {
+            PipState::NoVenv => Some(install(None, true)),
+            PipState::NotInstalled => Some(install(None, false)),
+            PipState::Installed { version } => match &resource.version {
+                Some(pinned) if pinned != version => Some(install(Some(version.clone()), false)),
+                _ => None,
+            },
+        }
+    }
+
+    fn operations(change: Self::Change) -> Vec<CausalityTree<Operation>> {
+        match change {
+            PipChange::Install {
+                target,
+                requirement,
+                create_venv,
+                ..
+            } => {
+                let install = Operation::Pip(PipOperation::Install {
+                    target: target.clone(),
+                    requirements: vec![requirement],
+                });
+                match target {
+                    PipTarget::Venv { path } if create_venv => vec![
+                        CausalityTree::leaf(
+                            CausalityMeta::id("venv".into()),
+                            Operation::Pip(PipOperation::CreateVenv { path }),
+                        ),
+                        CausalityTree::leaf(CausalityMeta::requires(vec!["venv".into()]), install),
+                    ],
+                    _ => vec![CausalityTree::leaf(CausalityMeta::default(), install)],
+                }
+            }
+        }
+    }
+}
+
+const VENV_DOC: ParamDoc = ParamDoc::optional(
+    "venv",
+    ParamDocType::TargetPath,
+    "Virtualenv to install into, created if missing; installs with pipx when omitted.",
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resource(version: Option<&str>) -> PipResource {
+        PipResource {
+            target: PipTarget::Pipx,
+            package: "black".into(),
+            version: version.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn requirements_are_a_name_and_an_optional_pin() {
+        assert_eq!(split_requirement("black"), Ok(("black", None)));
+        assert_eq!(
+            split_requirement("zope.interface==6.1"),
+            Ok(("zope.interface", Some("6.1")))
+        );
+        assert!(split_requirement("black>=24").is_err());
+        assert!(split_requirement("black==").is_err());
+        assert!(split_requirement("--index-url").is_err());
+        assert_eq!(normalize_name("Zope_Interface"), "zope-interface");
+        assert_eq!(normalize_name("a.-_b"), "a-b");
+    }
+
+    #[test]
+    fn only_a_pin_reinstalls_an_installed_package() {
+        let installed = PipState::Installed {
+            version: "23.1.0".into(),
+        };
+        assert!(Pip::change(&resource(None), &installed).is_none());
+        assert!(Pip::change(&resource(Some("23.1.0")), &installed).is_none());
+
+        let change = Pip::change(&resource(Some("24.1.0")), &installed).expect("change");
+        assert_eq!(
+            change.to_string(),
+            "Pip::Install(black==24.1.0, target = pipx, was 23.1.0)"
+        );
+    }
+
+    #[test]
+    fn missing_venv_is_created_first() {
+        let resource = PipResource {
+            target: PipTarget::Venv {
+                path: FilePath::new("/opt/tools"),
+            },
+            package: "requests".into(),
+            version: None,
+        };
+        let change = Pip::change(&resource, &PipState::NoVenv).expect("change");
+        let operations: Vec<String> = Pip::operations(change)
+            .into_iter()
+            .map(|tree| match tree {
+                CausalityTree::Leaf { node, .. } => node.to_string(),
+                _ => panic!("expected leaf"),
+            })
+            .collect();
+        assert_eq!(
+            operations,
+            [
+                "Pip::CreateVenv(path = /opt/tools)",
+                "Pip::Install(target = venv /opt/tools, requirements = [requests])",
+            ]
+        );
+    }
+}
@@ -18,6 +18,7 @@ use sha2::{Digest, Sha256};
 use thiserror::Error;
 
 use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
 
 /// Plan-level parameters for the `@core/podman` resource.
 ///
@@ -29,7 +30,8 @@ use crate::ResourceType;
 /// An upstream change to a floating tag (e.g. `nginx:latest` republished)
 /// will not trigger a recreate — pin with `@sha256:...` for digest-level
 /// control.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum PodmanParams {
     Present {
         name: String,
@@ -249,6 +251,50 @@ pub struct Podman;
 #[async_trait]
 impl ResourceType for Podman {
     const ID: &'static str = "podman";
+    const DESCRIPTION: &'static str = "Run a long-running podman container.";
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[
+        ParamsDoc {
+            description: "Present.",
+            params: &[
+                ParamDoc::required("state", ParamDocType::Literal("present"), "Present."),
+                ParamDoc::required("name", ParamDocType::String, "Container name."),
+                ParamDoc::required("image", ParamDocType::String, "Image to run."),
+                ParamDoc::optional(
+                    "command",
+                    ParamDocType::StringList,
+                    "Command to run, instead of the image's.",
+                ),
+                ParamDoc::optional("env", ParamDocType::StringList, "`KEY=value` variables."),
+                ParamDoc::optional(
+                    "ports",
+                    ParamDocType::StringList,
+                    "Published ports, as for `podman run -p`.",
+                ),
+                ParamDoc::optional(
+                    "volumes",
+                    ParamDocType::StringList,
+                    "Mounted volumes, as for `podman run -v`.",
+                ),
+                ParamDoc::optional(
+                    "restart_policy",
+                    ParamDocType::String,
+                    "As for `podman run --restart`.",
+                ),
+                ParamDoc::optional(
+                    "running",
+                    ParamDocType::Boolean,
+                    "Whether it's running, defaults to true.",
+                ),
+            ],
+        },
+        ParamsDoc {
+            description: "Removed.",
+            params: &[
+                ParamDoc::required("state", ParamDocType::Literal("absent"), "Absent."),
+                ParamDoc::required("name", ParamDocType::String, "Container name."),
+            ],
+        },
+    ];
 
     type Params = PodmanParams;
     type Resource = PodmanResource;
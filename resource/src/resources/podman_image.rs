@@ -0,0 +1,260 @@
+//! `@core/podman-image`: container images present in local storage.
+//!
+//! Separate from `@core/podman`, which pulls an image it's missing as part of
+//! creating the container. Declaring images here pulls them ahead of time,
+//! and each missing image is a [`PodmanOperation::Pull`], which the
+//! operation layer's merge folds with the epoch's other pulls into one
+//! `podman pull`.
+//!
+//! An image is present when `podman image exists` finds it, so a floating tag
+//! like `nginx:latest` is pulled once and not refreshed after. Pin a digest
+//! (`nginx@sha256:...`) to be exact.
+
+use std::fmt::Display;
+
+use async_trait::async_trait;
+use lusid_causality::{CausalityMeta, CausalityTree};
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_operation::{Operation, operations::podman::PodmanOperation};
+use lusid_params::{ParseError, ParseParams, StructFields};
+use lusid_view::impl_display_render;
+use rimu::{Spanned, Value};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PodmanImageParams {
+    Image { image: String },
+    Images { images: Vec<String> },
+}
+
+impl ParseParams for PodmanImageParams {
+    fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
+        let mut fields = StructFields::new(value)?;
+        // Untagged dispatch, as for `@core/apt`: `images` wins when present.
+        let out = if fields.has("images") {
+            PodmanImageParams::Images {
+                images: fields.required_string_list("images")?,
+            }
+        } else {
+            PodmanImageParams::Image {
+                image: fields.required_string("image")?,
+            }
+        };
+        fields.finish()?;
+        Ok(out)
+    }
+}
+
+impl Display for PodmanImageParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PodmanImageParams::Image { image } => write!(f, "PodmanImage(image = {image})"),
+            PodmanImageParams::Images { images } => {
+                write!(f, "PodmanImage(images = [{}])", images.join(", "))
+            }
+        }
+    }
+}
+
+impl_display_render!(PodmanImageParams);
+
+#[derive(Debug, Clone)]
+pub struct PodmanImageResource {
+    /// `name:tag` or `name@digest`, as given to `podman pull`.
+    pub image: String,
+}
+
+impl Display for PodmanImageResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self { image } = self;
+        write!(f, "PodmanImage({image})")
+    }
+}
+
+impl_display_render!(PodmanImageResource);
+
+#[derive(Debug, Clone)]
+pub enum PodmanImageState {
+    Absent,
+    Present,
+}
+
+impl Display for PodmanImageState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PodmanImageState::Absent => write!(f, "PodmanImage::Absent"),
+            PodmanImageState::Present => write!(f, "PodmanImage::Present"),
+        }
+    }
+}
+
+impl_display_render!(PodmanImageState);
+
+#[derive(Error, Debug)]
+pub enum PodmanImageStateError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+
+    #[error("podman image exists failed: {stderr}")]
+    Exists { stderr: String },
+}
+
+// TODO(cc): add an `absent` state removing the image with `podman rmi`, once
+// there's a way to tell it isn't used by a declared container.
+#[derive(Debug, Clone)]
+pub enum PodmanImageChange {
+    Pull { image: String },
+}
+
+impl Display for PodmanImageChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PodmanImageChange::Pull { image } => write!(f, "PodmanImage::Pull({image})"),
+        }
+    }
+}
+
+impl_display_render!(PodmanImageChange);
+
+#[derive(Debug, Clone)]
+pub struct PodmanImage;
+
+#[async_trait]
+impl ResourceType for PodmanImage {
+    const ID: &'static str = "podman-image";
+    const DESCRIPTION: &'static str = "Pull podman images into local storage.";
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[
+        ParamsDoc {
+            description: "One image.",
+            params: &[ParamDoc::required(
+                "image",
+                ParamDocType::String,
+                "Image to pull, as `name:tag` or `name@digest`.",
+            )],
+        },
+        ParamsDoc {
+            description: "Many images.",
+            params: &[ParamDoc::required(
+                "images",
+                ParamDocType::StringList,
+                "Images to pull, each as `name:tag` or `name@digest`.",
+            )],
+        },
+    ];
+
+    type Params = PodmanImageParams;
+    type Resource = PodmanImageResource;
+
+    fn resources(params: Self::Params) -> Vec<CausalityTree<Self::Resource>> {
+        let images = match params {
+            PodmanImageParams::Image { image } => vec![image],
+            PodmanImageParams::Images { images } => images,
+        };
+        images
+            .into_iter()
+            .map(|image| {
+                CausalityTree::leaf(CausalityMeta::default(), PodmanImageResource { image })
+            })
+            .collect()
+    }
+
+    type State = PodmanImageState;
+    type StateError = PodmanImageStateError;
+
+    async fn state(
+        _ctx: &mut Context,
+        resource: &Self::Resource,
+    ) -> Result<Self::State, Self::StateError> {
+        // `podman image exists` exits 0 when the image is stored locally, 1
+        // when it isn't, and 125 when podman itself fails.
+        let outcome = Command::new("podman")
+            .args(["image", "exists", "--", &resource.image])
+            .outcome()
+            .await?;
+        match outcome.status.code() {
+            Some(0) => Ok(PodmanImageState::Present),
+            Some(1) => Ok(PodmanImageState::Absent),
+            _ => Err(PodmanImageStateError::Exists {
+                stderr: String::from_utf8_lossy(&outcome.stderr).trim().to_owned(),
+            }),
+        }
+    }
+
+    type Change = PodmanImageChange;
+
+    fn change(resource: &Self::Resource, state: &Self::State) -> Option<Self::Change> {
+        match state {
+            PodmanImageState::Present => None,
+            PodmanImageState::Absent => Some(PodmanImageChange::Pull {
+                image: resource.image.clone(),
+            }),
+        }
+    }
+
+    fn operations(change: Self::Change) -> Vec<CausalityTree<Operation>> {
+        match change {
+            PodmanImageChange::Pull { image } => vec![CausalityTree::leaf(
+                CausalityMeta::default(),
+                Operation::Podman(PodmanOperation::Pull {
+                    images: vec![image],
+                }),
+            )],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_image_is_its_own_resource() {
+        let params = PodmanImageParams::Images {
+            images: vec![
+                "docker.io/library/nginx:1.27".into(),
+                "redis@sha256:abc".into(),
+            ],
+        };
+        let resources: Vec<String> = PodmanImage::resources(params)
+            .into_iter()
+            .map(|tree| match tree {
+                CausalityTree::Leaf { node, .. } => node.to_string(),
+                _ => panic!("expected leaf"),
+            })
+            .collect();
+        assert_eq!(
+            resources,
+            [
+                "PodmanImage(docker.io/library/nginx:1.27)",
+                "PodmanImage(redis@sha256:abc)",
+            ]
+        );
+    }
+
+    #[test]
+    fn only_absent_images_are_pulled() {
+        let resource = PodmanImageResource {
+            image: "docker.io/library/nginx:1.27".into(),
+        };
+        assert!(PodmanImage::change(&resource, &PodmanImageState::Present).is_none());
+
+        let change = PodmanImage::change(&resource, &PodmanImageState::Absent).expect("change");
+        let operations: Vec<String> = PodmanImage::operations(change)
+            .into_iter()
+            .map(|tree| match tree {
+                CausalityTree::Leaf { node, .. } => node.to_string(),
+                _ => panic!("expected leaf"),
+            })
+            .collect();
+        assert_eq!(
+            operations,
+            ["Podman::Pull(images = [docker.io/library/nginx:1.27])"]
+        );
+    }
+}
@@ -0,0 +1,523 @@
+//! `@core/rustup`: a Rust toolchain, with its components and targets.
+//!
+//! Each toolchain, component and target is its own resource. A missing
+//! toolchain is installed with rustup's minimal profile, so list the
+//! components you want (`clippy`, `rustfmt`, ...) rather than relying on the
+//! default profile's. rustup itself must already be installed for the apply
+//! user, and is run as them.
+//!
+//! Installed toolchains, components and targets are read once per apply: the
+//! host triple from `rustup show`, the toolchains from `rustup toolchain
+//! list`, and each toolchain's components and targets from `rustup component
+//! list --installed` and `rustup target list --installed`.
+//!
+//! Note(cc): `rustup show` also lists installed toolchains, but only the
+//! active toolchain's targets and none of its components, and its layout
+//! changed in rustup 1.28, so only its `Default host:` line is read.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use async_trait::async_trait;
+use lusid_causality::{CausalityMeta, CausalityTree};
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_operation::{Operation, operations::rustup::RustupOperation};
+use lusid_params::{ParseError, ParseParams, StructFields};
+use lusid_view::impl_display_render;
+use rimu::{Spanned, Value};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RustupParams {
+    pub toolchain: String,
+    pub components: Vec<String>,
+    pub targets: Vec<String>,
+}
+
+impl ParseParams for RustupParams {
+    fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
+        let mut fields = StructFields::new(value)?;
+        let out = RustupParams {
+            toolchain: fields.required_string("toolchain")?,
+            components: fields
+                .optional_string_list("components")?
+                .unwrap_or_default(),
+            targets: fields.optional_string_list("targets")?.unwrap_or_default(),
+        };
+        fields.finish()?;
+        Ok(out)
+    }
+}
+
+impl Display for RustupParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            toolchain,
+            components,
+            targets,
+        } = self;
+        write!(f, "Rustup(toolchain = {toolchain}")?;
+        if !components.is_empty() {
+            write!(f, ", components = [{}]", components.join(", "))?;
+        }
+        if !targets.is_empty() {
+            write!(f, ", targets = [{}]", targets.join(", "))?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl_display_render!(RustupParams);
+
+#[derive(Debug, Clone)]
+pub enum RustupResource {
+    Toolchain {
+        toolchain: String,
+    },
+    Component {
+        toolchain: String,
+        component: String,
+    },
+    Target {
+        toolchain: String,
+        target: String,
+    },
+}
+
+impl RustupResource {
+    pub fn toolchain(&self) -> &str {
+        match self {
+            RustupResource::Toolchain { toolchain }
+            | RustupResource::Component { toolchain, .. }
+            | RustupResource::Target { toolchain, .. } => toolchain,
+        }
+    }
+}
+
+impl Display for RustupResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RustupResource::Toolchain { toolchain } => write!(f, "RustupToolchain({toolchain})"),
+            RustupResource::Component {
+                toolchain,
+                component,
+            } => write!(f, "RustupComponent({component}, toolchain = {toolchain})"),
+            RustupResource::Target { toolchain, target } => {
+                write!(f, "RustupTarget({target}, toolchain = {toolchain})")
+            }
+        }
+    }
+}
+
+impl_display_render!(RustupResource);
+
+#[derive(Debug, Clone)]
+pub enum RustupState {
+    /// The toolchain a component or target belongs to isn't installed.
+    NoToolchain,
+    Missing,
+    Installed,
+}
+
+impl Display for RustupState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RustupState::NoToolchain => write!(f, "Rustup::NoToolchain"),
+            RustupState::Missing => write!(f, "Rustup::Missing"),
+            RustupState::Installed => write!(f, "Rustup::Installed"),
+        }
+    }
+}
+
+impl_display_render!(RustupState);
+
+#[derive(Error, Debug)]
+pub enum RustupStateError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+
+    #[error("rustup {command} failed: {stderr}")]
+    List { command: String, stderr: String },
+
+    #[error("rustup show printed no default host")]
+    NoHost,
+}
+
+// TODO(cc): add an `absent` state (`rustup toolchain uninstall`, `rustup
+// component remove`, `rustup target remove`).
+#[derive(Debug, Clone)]
+pub enum RustupChange {
+    InstallToolchain {
+        toolchain: String,
+    },
+    /// Add `component` to `toolchain`, installing the toolchain with it if
+    /// `install_toolchain`.
+    AddComponent {
+        toolchain: String,
+        component: String,
+        install_toolchain: bool,
+    },
+    /// Add `target` to `toolchain`, installing the toolchain with it if
+    /// `install_toolchain`.
+    AddTarget {
+        toolchain: String,
+        target: String,
+        install_toolchain: bool,
+    },
+}
+
+impl Display for RustupChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (name, item, toolchain, install_toolchain) = match self {
+            RustupChange::InstallToolchain { toolchain } => {
+                return write!(f, "Rustup::InstallToolchain({toolchain})");
+            }
+            RustupChange::AddComponent {
+                toolchain,
+                component,
+                install_toolchain,
+            } => ("AddComponent", component, toolchain, install_toolchain),
+            RustupChange::AddTarget {
+                toolchain,
+                target,
+                install_toolchain,
+            } => ("AddTarget", target, toolchain, install_toolchain),
+        };
+        write!(f, "Rustup::{name}({item}, toolchain = {toolchain}")?;
+        if *install_toolchain {
+            write!(f, ", installing toolchain")?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl_display_render!(RustupChange);
+
+/// What `rustup` reports as installed, read as it's needed.
+struct Installed {
+    host: String,
+    toolchains: Vec<String>,
+    components: BTreeMap<String, Vec<String>>,
+    targets: BTreeMap<String, Vec<String>>,
+}
+
+impl Installed {
+    async fn read() -> Result<Self, RustupStateError> {
+        let show = rustup(&["show"]).await?;
+        let host = show
+            .lines()
+            .find_map(|line| line.strip_prefix("Default host:"))
+            .map(|host| host.trim().to_owned())
+            .ok_or(RustupStateError::NoHost)?;
+        let toolchains = names(&rustup(&["toolchain", "list"]).await?);
+        Ok(Self {
+            host,
+            toolchains,
+            components: BTreeMap::new(),
+            targets: BTreeMap::new(),
+        })
+    }
+
+    /// The installed toolchain `toolchain` names, in rustup's full form.
+    fn toolchain(&self, toolchain: &str) -> Option<String> {
+        self.toolchains
+            .iter()
+            .find(|installed| is_named(installed, toolchain, &self.host))
+            .cloned()
+    }
+
+    async fn state(&mut self, resource: &RustupResource) -> Result<RustupState, RustupStateError> {
+        let Some(toolchain) = self.toolchain(resource.toolchain()) else {
+            return Ok(match resource {
+                RustupResource::Toolchain { .. } => RustupState::Missing,
+                _ => RustupState::NoToolchain,
+            });
+        };
+        let (installed, name) = match resource {
+            RustupResource::Toolchain { .. } => return Ok(RustupState::Installed),
+            RustupResource::Component { component, .. } => (
+                installed_in(&mut self.components, "component", &toolchain).await?,
+                component,
+            ),
+            RustupResource::Target { target, .. } => (
+                installed_in(&mut self.targets, "target", &toolchain).await?,
+                target,
+            ),
+        };
+        Ok(
+            if installed
+                .iter()
+                .any(|installed| is_named(installed, name, &self.host))
+            {
+                RustupState::Installed
+            } else {
+                RustupState::Missing
+            },
+        )
+    }
+}
+
+/// The `kind`s (`component` or `target`) installed in `toolchain`, listed
+/// once and kept in `cache`.
+async fn installed_in<'a>(
+    cache: &'a mut BTreeMap<String, Vec<String>>,
+    kind: &str,
+    toolchain: &str,
+) -> Result<&'a [String], RustupStateError> {
+    if !cache.contains_key(toolchain) {
+        let list = rustup(&[kind, "list", "--installed", "--toolchain", toolchain]).await?;
+        cache.insert(toolchain.to_owned(), names(&list));
+    }
+    Ok(&cache[toolchain])
+}
+
+/// Whether rustup's `installed` name is `name`, which rustup suffixes with
+/// the host triple for toolchains and host-specific components: `stable` is
+/// installed as `stable-x86_64-unknown-linux-gnu`.
+fn is_named(installed: &str, name: &str, host: &str) -> bool {
+    installed == name
+        || installed
+            .strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix('-'))
+            == Some(host)
+}
+
+/// The first word of each line, e.g. `stable-x86_64-unknown-linux-gnu` of
+/// `stable-x86_64-unknown-linux-gnu (active, default)`.
+fn names(list: &str) -> Vec<String> {
+    list.lines()
+        .filter(|line| *line != "no installed toolchains")
+        .filter_map(|line| line.split_whitespace().next())
+        .map(str::to_owned)
+        .collect()
+}
+
+async fn rustup(args: &[&str]) -> Result<String, RustupStateError> {
+    let outcome = Command::new("rustup").args(args).outcome().await?;
+    if !outcome.status.success() {
+        return Err(RustupStateError::List {
+            command: args.join(" "),
+            stderr: String::from_utf8_lossy(&outcome.stderr).trim().to_owned(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&outcome.stdout).into_owned())
+}
+
+#[derive(Debug, Clone)]
+pub struct Rustup;
+
+#[async_trait]
+impl ResourceType for Rustup {
+    const ID: &'static str = "rustup";
+    const DESCRIPTION: &'static str =
+        "Install Rust toolchains, with their components and targets, with rustup.";
+    const PLATFORMS: &'static [&'static str] = &["linux", "macos"];
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[ParamsDoc {
+        description: "A toolchain.",
+        params: &[
+            ParamDoc::required(
+                "toolchain",
+                ParamDocType::String,
+                "Toolchain, e.g. `stable`, `nightly-2024-06-01` or `1.80.0`.",
+            ),
+            ParamDoc::optional(
+                "components",
+                ParamDocType::StringList,
+                "Components to add, e.g. `clippy`, `rustfmt` or `rust-src`.",
+            ),
+            ParamDoc::optional(
+                "targets",
+                ParamDocType::StringList,
+                "Targets to add, e.g. `wasm32-unknown-unknown`.",
+            ),
+        ],
+    }];
+
+    type Params = RustupParams;
+    type Resource = RustupResource;
+
+    fn resources(params: Self::Params) -> Vec<CausalityTree<Self::Resource>> {
+        let RustupParams {
+            toolchain,
+            components,
+            targets,
+        } = params;
+        let components = components
+            .into_iter()
+            .map(|component| RustupResource::Component {
+                toolchain: toolchain.clone(),
+                component,
+            });
+        let targets = targets.into_iter().map(|target| RustupResource::Target {
+            toolchain: toolchain.clone(),
+            target,
+        });
+        std::iter::once(RustupResource::Toolchain {
+            toolchain: toolchain.clone(),
+        })
+        .chain(components)
+        .chain(targets)
+        .map(|resource| CausalityTree::leaf(CausalityMeta::default(), resource))
+        .collect()
+    }
+
+    type State = RustupState;
+    type StateError = RustupStateError;
+
+    async fn state(
+        _ctx: &mut Context,
+        resource: &Self::Resource,
+    ) -> Result<Self::State, Self::StateError> {
+        Installed::read().await?.state(resource).await
+    }
+
+    async fn states_bulk(
+        _ctx: &mut Context,
+        resources: &[&Self::Resource],
+    ) -> Result<Vec<Self::State>, Self::StateError> {
+        let mut installed = Installed::read().await?;
+        let mut states = Vec::with_capacity(resources.len());
+        for resource in resources {
+            states.push(installed.state(resource).await?);
+        }
+        Ok(states)
+    }
+
+    type Change = RustupChange;
+
+    fn change(resource: &Self::Resource, state: &Self::State) -> Option<Self::Change> {
+        let install_toolchain = match state {
+            RustupState::Installed => return None,
+            RustupState::Missing => false,
+            RustupState::NoToolchain => true,
+        };
+        let toolchain = resource.toolchain().to_owned();
+        Some(match resource {
+            RustupResource::Toolchain { .. } => RustupChange::InstallToolchain { toolchain },
+            RustupResource::Component { component, .. } => RustupChange::AddComponent {
+                toolchain,
+                component: component.clone(),
+                install_toolchain,
+            },
+            RustupResource::Target { target, .. } => RustupChange::AddTarget {
+                toolchain,
+                target: target.clone(),
+                install_toolchain,
+            },
+        })
+    }
+
+    // A component or target of a missing toolchain is installed along with
+    // it: the operation layer's merge folds it into the toolchain's install.
+    fn operations(change: Self::Change) -> Vec<CausalityTree<Operation>> {
+        let operation = match change {
+            RustupChange::InstallToolchain { toolchain } => RustupOperation::Install {
+                toolchain,
+                components: vec![],
+                targets: vec![],
+            },
+            RustupChange::AddComponent {
+                toolchain,
+                component,
+                install_toolchain: true,
+            } => RustupOperation::Install {
+                toolchain,
+                components: vec![component],
+                targets: vec![],
+            },
+            RustupChange::AddComponent {
+                toolchain,
+                component,
+                install_toolchain: false,
+            } => RustupOperation::AddComponents {
+                toolchain,
+                components: vec![component],
+            },
+            RustupChange::AddTarget {
+                toolchain,
+                target,
+                install_toolchain: true,
+            } => RustupOperation::Install {
+                toolchain,
+                components: vec![],
+                targets: vec![target],
+            },
+            RustupChange::AddTarget {
+                toolchain,
+                target,
+                install_toolchain: false,
+            } => RustupOperation::AddTargets {
+                toolchain,
+                targets: vec![target],
+            },
+        };
+        vec![CausalityTree::leaf(
+            CausalityMeta::default(),
+            Operation::Rustup(operation),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_match_with_or_without_the_host() {
+        let host = "x86_64-unknown-linux-gnu";
+        assert!(is_named("stable-x86_64-unknown-linux-gnu", "stable", host));
+        assert!(is_named("rust-src", "rust-src", host));
+        assert!(is_named(
+            "wasm32-unknown-unknown",
+            "wasm32-unknown-unknown",
+            host
+        ));
+        assert!(!is_named(
+            "nightly-2024-06-01-x86_64-unknown-linux-gnu",
+            "nightly",
+            host
+        ));
+        assert_eq!(
+            names(
+                "stable-x86_64-unknown-linux-gnu (active, default)\nnightly-x86_64-unknown-linux-gnu\n"
+            ),
+            [
+                "stable-x86_64-unknown-linux-gnu",
+                "nightly-x86_64-unknown-linux-gnu"
+            ]
+        );
+        assert!(names("no installed toolchains\n").is_empty());
+    }
+
+    #[test]
+    fn missing_toolchain_is_installed_with_its_components() {
+        let component = RustupResource::Component {
+            toolchain: "stable".into(),
+            component: "clippy".into(),
+        };
+        assert!(Rustup::change(&component, &RustupState::Installed).is_none());
+
+        let operations: Vec<String> = [RustupState::NoToolchain, RustupState::Missing]
+            .iter()
+            .flat_map(|state| {
+                Rustup::operations(Rustup::change(&component, state).expect("change"))
+            })
+            .map(|tree| match tree {
+                CausalityTree::Leaf { node, .. } => node.to_string(),
+                _ => panic!("expected leaf"),
+            })
+            .collect();
+        assert_eq!(
+            operations,
+            [
+                "Rustup::Install(toolchain = stable, components = [clippy], targets = [])",
+                "Rustup::AddComponents(toolchain = stable, components = [clippy])",
+            ]
+        );
+    }
+}
@@ -33,32 +33,41 @@ use lusid_operation::{
 use lusid_params::{ParseError, ParseParams, StructFields};
 use lusid_view::impl_display_render;
 use rimu::{Spanned, Value};
+use serde::Serialize;
 
 use crate::ResourceType;
-use crate::resources::file::{File, FileChange, FileResource, FileState, FileStateError};
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
+use crate::resources::file::{
+    File, FileChange, FileResource, FileState, FileStateError, GROUP_DOC, PATH_DOC, RESTARTS_DOC,
+    USER_DOC, parse_file_path,
+};
 
 /// Default mode applied when the plan omits `mode`. `0o600` = read/write
 /// for the owner only. Overridable by the plan (e.g. a secret that is
 /// deliberately group-readable for a multi-user service).
 pub const DEFAULT_MODE: u32 = 0o600;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct SecretParams {
     pub name: String,
     pub path: FilePath,
     pub mode: Option<FileMode>,
     pub user: Option<FileUser>,
     pub group: Option<FileGroup>,
+    /// Systemd unit to restart once the secret's contents change. See
+    /// [`FileParams::Sourced::restarts`](super::file::FileParams::Sourced::restarts).
+    pub restarts: Option<String>,
 }
 
 impl ParseParams for SecretParams {
     fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
         let mut fields = StructFields::new(value)?;
         let name = fields.required_string("name")?;
-        let path = FilePath::new(fields.required_target_path("path")?);
+        let path = fields.required("path", parse_file_path)?;
         let mode = fields.optional_u32("mode")?.map(FileMode::new);
         let user = fields.optional_string("user")?.map(FileUser::new);
         let group = fields.optional_string("group")?.map(FileGroup::new);
+        let restarts = fields.optional_string("restarts")?;
         fields.finish()?;
         Ok(SecretParams {
             name,
@@ -66,6 +75,7 @@ impl ParseParams for SecretParams {
             mode,
             user,
             group,
+            restarts,
         })
     }
 }
@@ -84,6 +94,23 @@ pub struct Secret;
 #[async_trait]
 impl ResourceType for Secret {
     const ID: &'static str = "secret";
+    const DESCRIPTION: &'static str = "Write a decrypted secret to a file, private by default.";
+    const PLATFORMS: &'static [&'static str] = &["linux", "macos"];
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[ParamsDoc {
+        description: "A secret file.",
+        params: &[
+            ParamDoc::required("name", ParamDocType::String, "Name of the secret."),
+            PATH_DOC,
+            ParamDoc::optional(
+                "mode",
+                ParamDocType::Number,
+                "Permissions as a decimal number, defaults to `384` (0o600).",
+            ),
+            USER_DOC,
+            GROUP_DOC,
+            RESTARTS_DOC,
+        ],
+    }];
 
     type Params = SecretParams;
     type Resource = FileResource;
@@ -95,6 +122,7 @@ impl ResourceType for Secret {
             mode,
             user,
             group,
+            restarts,
         } = params;
         let mode = mode.unwrap_or_else(|| FileMode::new(DEFAULT_MODE));
 
@@ -104,6 +132,7 @@ impl ResourceType for Secret {
                 FileResource::Secret {
                     name,
                     path: path.clone(),
+                    restarts,
                 },
             ),
             // Always emit a Mode atom: the default mode is a guarantee of this
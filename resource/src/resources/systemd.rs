@@ -4,15 +4,17 @@ use async_trait::async_trait;
 use lusid_causality::{CausalityMeta, CausalityTree};
 use lusid_cmd::{Command, CommandError};
 use lusid_ctx::Context;
-use lusid_operation::{Operation, operations::systemd::SystemdOperation};
+use lusid_operation::{Operation, operations::service::ServiceOperation};
 use lusid_params::{ParseError, ParseParams, StructFields};
 use lusid_view::impl_display_render;
 use rimu::{Spanned, Value};
+use serde::Serialize;
 use thiserror::Error;
 
 use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct SystemdParams {
     pub name: String,
     pub enabled: Option<bool>,
@@ -139,6 +141,23 @@ pub struct Systemd;
 #[async_trait]
 impl ResourceType for Systemd {
     const ID: &'static str = "systemd";
+    const DESCRIPTION: &'static str = "Enable and start, or disable and stop, a systemd unit.";
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[ParamsDoc {
+        description: "A unit.",
+        params: &[
+            ParamDoc::required(
+                "name",
+                ParamDocType::String,
+                "Unit name, e.g. `nginx.service`.",
+            ),
+            ParamDoc::optional(
+                "enabled",
+                ParamDocType::Boolean,
+                "Whether it starts at boot.",
+            ),
+            ParamDoc::optional("active", ParamDocType::Boolean, "Whether it's running now."),
+        ],
+    }];
 
     type Params = SystemdParams;
     type Resource = SystemdResource;
@@ -242,24 +261,24 @@ impl ResourceType for Systemd {
         let mut ops: Vec<CausalityTree<Operation>> = Vec::new();
         if let Some(enable) = enable {
             let op = if enable {
-                SystemdOperation::Enable { name: name.clone() }
+                ServiceOperation::Enable { unit: name.clone() }
             } else {
-                SystemdOperation::Disable { name: name.clone() }
+                ServiceOperation::Disable { unit: name.clone() }
             };
             ops.push(CausalityTree::leaf(
                 CausalityMeta::default(),
-                Operation::Systemd(op),
+                Operation::Service(op),
             ));
         }
         if let Some(active) = active {
             let op = if active {
-                SystemdOperation::Start { name }
+                ServiceOperation::Start { unit: name }
             } else {
-                SystemdOperation::Stop { name }
+                ServiceOperation::Stop { unit: name }
             };
             ops.push(CausalityTree::leaf(
                 CausalityMeta::default(),
-                Operation::Systemd(op),
+                Operation::Service(op),
             ));
         }
         ops
@@ -0,0 +1,401 @@
+//! `@core/systemd-unit`: a unit file the plan writes itself, plus the unit's
+//! enabled / active state as in `@core/systemd`.
+//!
+//! Expands to two atoms: the unit file, then the unit (requiring the file).
+//! When the file changes, its operations write it, run `systemctl
+//! daemon-reload` so systemd sees the new file, and restart the unit if it
+//! should be running; the unit's own enable / start operations come after.
+
+use std::fmt::Display;
+
+use async_trait::async_trait;
+use lusid_causality::{CausalityMeta, CausalityTree};
+use lusid_ctx::Context;
+use lusid_fs::{self as fs, FsError};
+use lusid_operation::{
+    Operation,
+    operations::{file::FilePath, service::ServiceOperation, systemd::SystemdOperation},
+};
+use lusid_params::{ParseError, ParseParams, StructFields};
+use lusid_view::impl_display_render;
+use rimu::{Spanned, Value};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
+use crate::resources::systemd::{
+    Systemd, SystemdChange, SystemdResource, SystemdState, SystemdStateError,
+};
+
+const UNIT_DIR: &str = "/etc/systemd/system";
+
+// TODO(cc): validate `name` as a unit name (`<prefix>.<type>`, no `/`) at
+// param-time. It's joined onto `/etc/systemd/system/`, so today a `../` in it
+// would write the file elsewhere.
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemdUnitParams {
+    /// Unit name, also the file's name, e.g. `myapp.service`.
+    pub name: String,
+    pub content: String,
+    pub enabled: Option<bool>,
+    pub active: Option<bool>,
+}
+
+impl ParseParams for SystemdUnitParams {
+    fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
+        let mut fields = StructFields::new(value)?;
+        let name = fields.required_string("name")?;
+        let content = fields.required_string("content")?;
+        let enabled = fields.optional_bool("enabled")?;
+        let active = fields.optional_bool("active")?;
+        fields.finish()?;
+        Ok(SystemdUnitParams {
+            name,
+            content,
+            enabled,
+            active,
+        })
+    }
+}
+
+impl Display for SystemdUnitParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            name,
+            content,
+            enabled,
+            active,
+        } = self;
+        write!(
+            f,
+            "SystemdUnit(name = {name}, content = {} bytes, enabled = {enabled:?}, active = {active:?})",
+            content.len()
+        )
+    }
+}
+
+impl_display_render!(SystemdUnitParams);
+
+#[derive(Debug, Clone)]
+pub enum SystemdUnitResource {
+    /// The unit file. `restart` is whether the unit should be restarted once
+    /// it changes, i.e. whether the unit should be running.
+    UnitFile {
+        name: String,
+        path: FilePath,
+        content: String,
+        restart: bool,
+    },
+    Unit(SystemdResource),
+}
+
+impl Display for SystemdUnitResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SystemdUnitResource::UnitFile {
+                name,
+                path,
+                content,
+                restart,
+            } => write!(
+                f,
+                "SystemdUnitFile(name = {name}, path = {path}, content = {} bytes, restart = {restart})",
+                content.len()
+            ),
+            SystemdUnitResource::Unit(unit) => unit.fmt(f),
+        }
+    }
+}
+
+impl_display_render!(SystemdUnitResource);
+
+#[derive(Debug, Clone)]
+pub enum SystemdUnitState {
+    UnitFileMatches,
+    UnitFileDiffers,
+    Unit(SystemdState),
+}
+
+impl Display for SystemdUnitState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SystemdUnitState::UnitFileMatches => write!(f, "SystemdUnitFile::Matches"),
+            SystemdUnitState::UnitFileDiffers => write!(f, "SystemdUnitFile::Differs"),
+            SystemdUnitState::Unit(state) => state.fmt(f),
+        }
+    }
+}
+
+impl_display_render!(SystemdUnitState);
+
+#[derive(Error, Debug)]
+pub enum SystemdUnitStateError {
+    #[error(transparent)]
+    Fs(#[from] FsError),
+
+    #[error(transparent)]
+    Systemd(#[from] SystemdStateError),
+}
+
+#[derive(Debug, Clone)]
+pub enum SystemdUnitChange {
+    WriteUnitFile {
+        name: String,
+        path: FilePath,
+        content: String,
+        restart: bool,
+    },
+    Unit(SystemdChange),
+}
+
+impl Display for SystemdUnitChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SystemdUnitChange::WriteUnitFile {
+                name,
+                path,
+                restart,
+                ..
+            } => {
+                let verbs = if *restart {
+                    "write+reload+restart"
+                } else {
+                    "write+reload"
+                };
+                write!(f, "SystemdUnitFile::{verbs}({name} -> {path})")
+            }
+            SystemdUnitChange::Unit(change) => change.fmt(f),
+        }
+    }
+}
+
+impl_display_render!(SystemdUnitChange);
+
+#[derive(Debug, Clone)]
+pub struct SystemdUnit;
+
+#[async_trait]
+impl ResourceType for SystemdUnit {
+    const ID: &'static str = "systemd-unit";
+    const DESCRIPTION: &'static str = "Write a systemd unit file, reloading systemd when it changes, and enable and start, or disable and stop, the unit.";
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[ParamsDoc {
+        description: "A unit and its file.",
+        params: &[
+            ParamDoc::required(
+                "name",
+                ParamDocType::String,
+                "Unit name, e.g. `myapp.service`, written to `/etc/systemd/system/<name>`.",
+            ),
+            ParamDoc::required(
+                "content",
+                ParamDocType::String,
+                "Contents of the unit file.",
+            ),
+            ParamDoc::optional(
+                "enabled",
+                ParamDocType::Boolean,
+                "Whether it starts at boot, defaults to true.",
+            ),
+            ParamDoc::optional(
+                "active",
+                ParamDocType::Boolean,
+                "Whether it's running now, defaults to true. A running unit is restarted when its file changes.",
+            ),
+        ],
+    }];
+
+    type Params = SystemdUnitParams;
+    type Resource = SystemdUnitResource;
+
+    fn resources(params: Self::Params) -> Vec<CausalityTree<Self::Resource>> {
+        let SystemdUnitParams {
+            name,
+            content,
+            enabled,
+            active,
+        } = params;
+        let active = active.unwrap_or(true);
+        let path = FilePath::new(format!("{UNIT_DIR}/{name}"));
+
+        vec![
+            CausalityTree::leaf(
+                CausalityMeta::id("unit-file".into()),
+                SystemdUnitResource::UnitFile {
+                    name: name.clone(),
+                    path,
+                    content,
+                    restart: active,
+                },
+            ),
+            CausalityTree::leaf(
+                CausalityMeta::requires(vec!["unit-file".into()]),
+                SystemdUnitResource::Unit(SystemdResource {
+                    name,
+                    enabled: enabled.unwrap_or(true),
+                    active,
+                }),
+            ),
+        ]
+    }
+
+    type State = SystemdUnitState;
+    type StateError = SystemdUnitStateError;
+
+    async fn state(
+        ctx: &mut Context,
+        resource: &Self::Resource,
+    ) -> Result<Self::State, Self::StateError> {
+        match resource {
+            SystemdUnitResource::UnitFile { path, content, .. } => {
+                let matches = fs::path_exists(path.as_path()).await?
+                    && fs::read_file_to_string(path.as_path()).await? == *content;
+                Ok(if matches {
+                    SystemdUnitState::UnitFileMatches
+                } else {
+                    SystemdUnitState::UnitFileDiffers
+                })
+            }
+            SystemdUnitResource::Unit(unit) => {
+                Ok(SystemdUnitState::Unit(Systemd::state(ctx, unit).await?))
+            }
+        }
+    }
+
+    type Change = SystemdUnitChange;
+
+    fn change(resource: &Self::Resource, state: &Self::State) -> Option<Self::Change> {
+        match (resource, state) {
+            (SystemdUnitResource::UnitFile { .. }, SystemdUnitState::UnitFileMatches) => None,
+            (
+                SystemdUnitResource::UnitFile {
+                    name,
+                    path,
+                    content,
+                    restart,
+                },
+                SystemdUnitState::UnitFileDiffers,
+            ) => Some(SystemdUnitChange::WriteUnitFile {
+                name: name.clone(),
+                path: path.clone(),
+                content: content.clone(),
+                restart: *restart,
+            }),
+            (SystemdUnitResource::Unit(unit), SystemdUnitState::Unit(state)) => {
+                Systemd::change(unit, state).map(SystemdUnitChange::Unit)
+            }
+            _ => panic!("Unexpected case in change method for SystemdUnit resource."),
+        }
+    }
+
+    fn operations(change: Self::Change) -> Vec<CausalityTree<Operation>> {
+        match change {
+            SystemdUnitChange::WriteUnitFile {
+                name,
+                path,
+                content,
+                restart,
+            } => {
+                let mut ops = vec![
+                    CausalityTree::leaf(
+                        CausalityMeta::id("write".into()),
+                        Operation::Systemd(SystemdOperation::WriteUnit {
+                            name: name.clone(),
+                            path,
+                            content,
+                        }),
+                    ),
+                    CausalityTree::leaf(
+                        CausalityMeta {
+                            id: Some("daemon-reload".into()),
+                            requires: vec!["write".into()],
+                            required_by: vec![],
+                        },
+                        Operation::Systemd(SystemdOperation::DaemonReload),
+                    ),
+                ];
+                if restart {
+                    ops.push(CausalityTree::leaf(
+                        CausalityMeta::requires(vec!["daemon-reload".into()]),
+                        Operation::Service(ServiceOperation::Restart { unit: name }),
+                    ));
+                }
+                ops
+            }
+            SystemdUnitChange::Unit(change) => Systemd::operations(change),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(active: Option<bool>) -> SystemdUnitParams {
+        SystemdUnitParams {
+            name: "myapp.service".into(),
+            content: "[Service]\nExecStart=/usr/bin/myapp\n".into(),
+            enabled: None,
+            active,
+        }
+    }
+
+    fn unit_file(active: Option<bool>) -> SystemdUnitResource {
+        SystemdUnit::resources(params(active))
+            .into_iter()
+            .find_map(|tree| match tree {
+                CausalityTree::Leaf { node, .. } => {
+                    matches!(node, SystemdUnitResource::UnitFile { .. }).then_some(node)
+                }
+                _ => None,
+            })
+            .expect("unit file atom")
+    }
+
+    fn operations(tree: &[CausalityTree<Operation>]) -> Vec<String> {
+        tree.iter()
+            .map(|tree| match tree {
+                CausalityTree::Leaf { node, .. } => node.to_string(),
+                _ => panic!("expected leaf"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unit_file_is_written_under_etc_systemd() {
+        let SystemdUnitResource::UnitFile { path, .. } = unit_file(None) else {
+            unreachable!()
+        };
+        assert_eq!(path, FilePath::new("/etc/systemd/system/myapp.service"));
+    }
+
+    #[test]
+    fn changed_unit_file_reloads_then_restarts() {
+        let resource = unit_file(None);
+        let change =
+            SystemdUnit::change(&resource, &SystemdUnitState::UnitFileDiffers).expect("change");
+        assert_eq!(
+            operations(&SystemdUnit::operations(change)),
+            [
+                "Systemd::WriteUnit(name = myapp.service, path = /etc/systemd/system/myapp.service, 35 bytes)",
+                "Systemd::DaemonReload",
+                "Service::Restart(myapp.service)",
+            ]
+        );
+    }
+
+    #[test]
+    fn inactive_unit_is_not_restarted() {
+        let resource = unit_file(Some(false));
+        let change =
+            SystemdUnit::change(&resource, &SystemdUnitState::UnitFileDiffers).expect("change");
+        assert_eq!(
+            operations(&SystemdUnit::operations(change)),
+            [
+                "Systemd::WriteUnit(name = myapp.service, path = /etc/systemd/system/myapp.service, 35 bytes)",
+                "Systemd::DaemonReload",
+            ]
+        );
+        assert!(SystemdUnit::change(&resource, &SystemdUnitState::UnitFileMatches).is_none());
+    }
+}
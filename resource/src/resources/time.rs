@@ -0,0 +1,255 @@
+//! `@core/time`: the system clock's timezone and network time sync (see
+//! [`lusid_operation::operations::time`]).
+//!
+//! Each of `timezone` and `ntp` given is its own atom, and one that isn't is
+//! left as it is. The timezone is read from where the `/etc/localtime`
+//! symlink points, as `timedatectl` itself does, so checking it costs no
+//! process; NTP is read with `timedatectl show` only when `ntp` is given.
+
+use std::fmt::Display;
+use std::path::Path;
+
+use async_trait::async_trait;
+use lusid_causality::{CausalityMeta, CausalityTree};
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_fs::{self as fs, FsError, SymlinkTarget};
+use lusid_operation::{Operation, operations::time::TimeOperation};
+use lusid_params::{ParseError, ParseParams, StructFields};
+use lusid_view::impl_display_render;
+use rimu::{Spanned, Value};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
+
+const LOCALTIME: &str = "/etc/localtime";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimeParams {
+    pub timezone: Option<String>,
+    pub ntp: Option<bool>,
+}
+
+impl ParseParams for TimeParams {
+    fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
+        let mut fields = StructFields::new(value)?;
+        let timezone = fields.optional_string("timezone")?;
+        let ntp = fields.optional_bool("ntp")?;
+        fields.finish()?;
+        Ok(TimeParams { timezone, ntp })
+    }
+}
+
+impl Display for TimeParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self { timezone, ntp } = self;
+        write!(f, "Time(timezone = {timezone:?}, ntp = {ntp:?})")
+    }
+}
+
+impl_display_render!(TimeParams);
+
+#[derive(Debug, Clone)]
+pub enum TimeResource {
+    Timezone(String),
+    Ntp(bool),
+}
+
+impl Display for TimeResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimeResource::Timezone(timezone) => write!(f, "Time::Timezone({timezone})"),
+            TimeResource::Ntp(enabled) => write!(f, "Time::Ntp({enabled})"),
+        }
+    }
+}
+
+impl_display_render!(TimeResource);
+
+#[derive(Debug, Clone)]
+pub enum TimeState {
+    /// The current timezone, or `None` if `/etc/localtime` isn't a link into
+    /// the zoneinfo database, as when it's a copied file.
+    Timezone(Option<String>),
+    Ntp(bool),
+}
+
+impl Display for TimeState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimeState::Timezone(Some(timezone)) => write!(f, "Time::Timezone({timezone})"),
+            TimeState::Timezone(None) => write!(f, "Time::Timezone(unknown)"),
+            TimeState::Ntp(enabled) => write!(f, "Time::Ntp({enabled})"),
+        }
+    }
+}
+
+impl_display_render!(TimeState);
+
+#[derive(Error, Debug)]
+pub enum TimeStateError {
+    #[error(transparent)]
+    Fs(#[from] FsError),
+
+    #[error(transparent)]
+    Command(#[from] CommandError),
+
+    #[error("unexpected timedatectl NTP value: {value}")]
+    UnknownNtp { value: String },
+}
+
+#[derive(Debug, Clone)]
+pub enum TimeChange {
+    SetTimezone(String),
+    SetNtp(bool),
+}
+
+impl Display for TimeChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimeChange::SetTimezone(timezone) => write!(f, "Time::SetTimezone({timezone})"),
+            TimeChange::SetNtp(enabled) => write!(f, "Time::SetNtp({enabled})"),
+        }
+    }
+}
+
+impl_display_render!(TimeChange);
+
+#[derive(Debug, Clone)]
+pub struct Time;
+
+#[async_trait]
+impl ResourceType for Time {
+    const ID: &'static str = "time";
+    const DESCRIPTION: &'static str = "Set the timezone, and turn network time sync on or off.";
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[ParamsDoc {
+        description: "The clock. Whatever isn't given is left as it is.",
+        params: &[
+            ParamDoc::optional(
+                "timezone",
+                ParamDocType::String,
+                "IANA timezone, e.g. `Europe/Berlin` or `UTC`.",
+            ),
+            ParamDoc::optional(
+                "ntp",
+                ParamDocType::Boolean,
+                "Whether the clock is kept in sync over the network, by systemd-timesyncd or another NTP service.",
+            ),
+        ],
+    }];
+
+    type Params = TimeParams;
+    type Resource = TimeResource;
+
+    fn resources(params: Self::Params) -> Vec<CausalityTree<Self::Resource>> {
+        let TimeParams { timezone, ntp } = params;
+        timezone
+            .map(TimeResource::Timezone)
+            .into_iter()
+            .chain(ntp.map(TimeResource::Ntp))
+            .map(|resource| CausalityTree::leaf(CausalityMeta::default(), resource))
+            .collect()
+    }
+
+    type State = TimeState;
+    type StateError = TimeStateError;
+
+    async fn state(
+        _ctx: &mut Context,
+        resource: &Self::Resource,
+    ) -> Result<Self::State, Self::StateError> {
+        match resource {
+            TimeResource::Timezone(_) => {
+                let timezone = match fs::probe_symlink(LOCALTIME).await? {
+                    SymlinkTarget::Symlink(target) => parse_localtime_target(&target),
+                    // With no `/etc/localtime`, glibc and systemd use UTC.
+                    SymlinkTarget::Missing => Some("UTC".to_owned()),
+                    SymlinkTarget::NotASymlink => None,
+                };
+                Ok(TimeState::Timezone(timezone))
+            }
+            TimeResource::Ntp(_) => {
+                let output = Command::new("timedatectl")
+                    .args(["show", "--property=NTP", "--value"])
+                    .run()
+                    .await?;
+                match String::from_utf8_lossy(&output).trim() {
+                    "yes" => Ok(TimeState::Ntp(true)),
+                    "no" => Ok(TimeState::Ntp(false)),
+                    value => Err(TimeStateError::UnknownNtp {
+                        value: value.to_owned(),
+                    }),
+                }
+            }
+        }
+    }
+
+    type Change = TimeChange;
+
+    fn change(resource: &Self::Resource, state: &Self::State) -> Option<Self::Change> {
+        match (resource, state) {
+            (TimeResource::Timezone(timezone), TimeState::Timezone(current)) => {
+                let changed = current.as_ref() != Some(timezone);
+                changed.then(|| TimeChange::SetTimezone(timezone.clone()))
+            }
+            (TimeResource::Ntp(enabled), TimeState::Ntp(current)) => {
+                (current != enabled).then_some(TimeChange::SetNtp(*enabled))
+            }
+            (TimeResource::Timezone(_), TimeState::Ntp(_))
+            | (TimeResource::Ntp(_), TimeState::Timezone(_)) => {
+                unreachable!("state is read for its own resource")
+            }
+        }
+    }
+
+    fn operations(change: Self::Change) -> Vec<CausalityTree<Operation>> {
+        let operation = match change {
+            TimeChange::SetTimezone(timezone) => TimeOperation::SetTimezone { timezone },
+            TimeChange::SetNtp(enabled) => TimeOperation::SetNtp { enabled },
+        };
+        vec![CausalityTree::leaf(
+            CausalityMeta::default(),
+            Operation::Time(operation),
+        )]
+    }
+}
+
+/// The timezone an `/etc/localtime` link target names: what follows
+/// `zoneinfo/`, e.g. `Europe/Berlin` in `../usr/share/zoneinfo/Europe/Berlin`.
+fn parse_localtime_target(target: &Path) -> Option<String> {
+    let target = target.to_str()?;
+    let (_, timezone) = target.rsplit_once("zoneinfo/")?;
+    // Some distros link through `zoneinfo/posix/` or `zoneinfo/right/`,
+    // which name the same zones.
+    let timezone = timezone
+        .strip_prefix("posix/")
+        .or_else(|| timezone.strip_prefix("right/"))
+        .unwrap_or(timezone);
+    (!timezone.is_empty()).then(|| timezone.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_localtime_target() {
+        let parse = |target: &str| parse_localtime_target(Path::new(target));
+        assert_eq!(
+            parse("/usr/share/zoneinfo/Europe/Berlin").as_deref(),
+            Some("Europe/Berlin")
+        );
+        assert_eq!(
+            parse("../usr/share/zoneinfo/America/Argentina/Buenos_Aires").as_deref(),
+            Some("America/Argentina/Buenos_Aires")
+        );
+        assert_eq!(parse("/usr/share/zoneinfo/UTC").as_deref(), Some("UTC"));
+        assert_eq!(
+            parse("/usr/share/zoneinfo/posix/Pacific/Auckland").as_deref(),
+            Some("Pacific/Auckland")
+        );
+        assert_eq!(parse("/etc/timezone-copy"), None);
+    }
+}
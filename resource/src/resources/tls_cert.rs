@@ -0,0 +1,601 @@
+//! `@core/tls-cert`: a TLS certificate and its private key at given paths,
+//! self-signed or issued by an ACME CA like Let's Encrypt (see
+//! [`lusid_operation::operations::tls_cert`]).
+//!
+//! The pair is probed through sudo, as keys are root's alone. A missing pair,
+//! or a certificate whose subject alternative names aren't `names`, is
+//! issued; one that expires within `renew_days` is renewed, so applying on a
+//! schedule keeps it fresh.
+//
+// Note(cc): this doesn't reload whatever serves the certificate. Have a
+// `@core/systemd` item for the server require this, or reload it from a
+// `@core/command`.
+
+use std::{collections::BTreeSet, fmt::Display, net::IpAddr};
+
+use async_trait::async_trait;
+use lusid_causality::{CausalityMeta, CausalityTree};
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_operation::{
+    Operation,
+    operations::{file::FilePath, tls_cert::TlsCertOperation},
+};
+use lusid_params::{ParseError, ParseParams, StructFields, parse_list, parse_string};
+use lusid_view::impl_display_render;
+use rimu::{Spanned, Value};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
+
+const DEFAULT_RENEW_DAYS: u32 = 30;
+const DEFAULT_DAYS: u32 = 365;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Who signs the certificate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TlsCertIssuer {
+    /// Its own key, for `days`.
+    SelfSigned { days: u32 },
+    /// An ACME CA through certbot, which registers with `email`.
+    Acme {
+        email: String,
+        webroot: Option<String>,
+    },
+}
+
+impl Display for TlsCertIssuer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TlsCertIssuer::SelfSigned { days } => write!(f, "self-signed for {days} days"),
+            TlsCertIssuer::Acme { email, webroot } => {
+                write!(f, "acme as {email}")?;
+                if let Some(webroot) = webroot {
+                    write!(f, " via {webroot}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TlsCertParams {
+    pub cert_path: String,
+    pub key_path: String,
+    pub names: Vec<String>,
+    pub renew_days: u32,
+    pub issuer: TlsCertIssuer,
+}
+
+impl ParseParams for TlsCertParams {
+    fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
+        let mut fields = StructFields::new(value)?;
+        let issuer = fields.take_discriminator("issuer", &["self-signed", "acme"])?;
+        let cert_path = fields.required_target_path("cert_path")?;
+        let key_path = fields.required_target_path("key_path")?;
+        let names = fields.required("names", parse_names)?;
+        let renew_days = fields
+            .optional_u32("renew_days")?
+            .unwrap_or(DEFAULT_RENEW_DAYS);
+        let issuer = match issuer {
+            "self-signed" => TlsCertIssuer::SelfSigned {
+                days: fields.optional_u32("days")?.unwrap_or(DEFAULT_DAYS),
+            },
+            "acme" => TlsCertIssuer::Acme {
+                email: fields.required_string("email")?,
+                webroot: fields.optional_target_path("webroot")?,
+            },
+            _ => unreachable!(),
+        };
+        fields.finish()?;
+        Ok(TlsCertParams {
+            cert_path,
+            key_path,
+            names,
+            renew_days,
+            issuer,
+        })
+    }
+}
+
+// The first name is the certificate's common name, in an openssl `-subj`,
+// and the names are joined with commas into its subjectAltName, so neither
+// `/` nor `,` can be in one.
+fn parse_names(value: Spanned<Value>) -> Result<Vec<String>, Spanned<ParseError>> {
+    let span = value.span();
+    let names = parse_list(value, parse_name)?;
+    if names.is_empty() {
+        return Err(Spanned::new(
+            ParseError::TypeMismatch {
+                expected: "at least one name",
+                got: Box::new(Value::List(Vec::new())),
+            },
+            span,
+        ));
+    }
+    Ok(names)
+}
+
+fn parse_name(value: Spanned<Value>) -> Result<String, Spanned<ParseError>> {
+    let span = value.span();
+    let name = parse_string(value)?;
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-._*:".contains(c));
+    if valid {
+        Ok(name)
+    } else {
+        Err(Spanned::new(
+            ParseError::TypeMismatch {
+                expected: "host name or IP address",
+                got: Box::new(Value::String(name)),
+            },
+            span,
+        ))
+    }
+}
+
+impl Display for TlsCertParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            cert_path,
+            key_path,
+            names,
+            renew_days,
+            issuer,
+        } = self;
+        write!(
+            f,
+            "TlsCert(cert = {cert_path}, key = {key_path}, names = [{}], renew_days = {renew_days}, {issuer})",
+            names.join(", ")
+        )
+    }
+}
+
+impl_display_render!(TlsCertParams);
+
+#[derive(Debug, Clone)]
+pub struct TlsCertResource {
+    pub cert_path: FilePath,
+    pub key_path: FilePath,
+    pub names: Vec<String>,
+    pub renew_days: u32,
+    pub issuer: TlsCertIssuer,
+}
+
+impl Display for TlsCertResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            cert_path,
+            key_path,
+            names,
+            renew_days,
+            issuer,
+        } = self;
+        write!(
+            f,
+            "TlsCert(cert = {cert_path}, key = {key_path}, names = [{}], renew_days = {renew_days}, {issuer})",
+            names.join(", ")
+        )
+    }
+}
+
+impl_display_render!(TlsCertResource);
+
+#[derive(Debug, Clone)]
+pub enum TlsCertState {
+    /// No key, or no certificate openssl can read.
+    Missing,
+    Present {
+        /// The certificate's subject alternative names.
+        names: Vec<String>,
+        /// When it expires, as openssl shows it.
+        not_after: String,
+        /// Whether it expires within the resource's `renew_days`.
+        expiring: bool,
+    },
+}
+
+impl Display for TlsCertState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TlsCertState::Missing => write!(f, "TlsCert::Missing"),
+            TlsCertState::Present {
+                names,
+                not_after,
+                expiring,
+            } => {
+                write!(
+                    f,
+                    "TlsCert::Present(names = [{}], not_after = {not_after}",
+                    names.join(", ")
+                )?;
+                if *expiring {
+                    write!(f, ", expiring")?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl_display_render!(TlsCertState);
+
+#[derive(Error, Debug)]
+pub enum TlsCertStateError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+}
+
+#[derive(Debug, Clone)]
+pub enum TlsCertChange {
+    /// No pair, or a certificate for other names.
+    Issue(TlsCertResource),
+    /// A certificate that expires within `renew_days`, at `not_after`.
+    Renew {
+        resource: TlsCertResource,
+        not_after: String,
+    },
+}
+
+impl Display for TlsCertChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TlsCertChange::Issue(resource) => write!(
+                f,
+                "TlsCert::Issue({} for [{}], {})",
+                resource.cert_path,
+                resource.names.join(", "),
+                resource.issuer
+            ),
+            TlsCertChange::Renew {
+                resource,
+                not_after,
+            } => write!(
+                f,
+                "TlsCert::Renew({}, expiring {not_after})",
+                resource.cert_path
+            ),
+        }
+    }
+}
+
+impl_display_render!(TlsCertChange);
+
+#[derive(Debug, Clone)]
+pub struct TlsCert;
+
+#[async_trait]
+impl ResourceType for TlsCert {
+    const ID: &'static str = "tls-cert";
+    const DESCRIPTION: &'static str = "Keep a TLS certificate and its private key at given paths, self-signed or from an ACME CA, renewing it before it expires.";
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[
+        ParamsDoc {
+            description: "Self-signed.",
+            params: &[
+                ParamDoc::required(
+                    "issuer",
+                    ParamDocType::Literal("self-signed"),
+                    "Sign the certificate with its own key.",
+                ),
+                CERT_PATH_DOC,
+                KEY_PATH_DOC,
+                ParamDoc::required(
+                    "names",
+                    ParamDocType::StringList,
+                    "Host names and IP addresses the certificate is for. The first is also its common name.",
+                ),
+                RENEW_DAYS_DOC,
+                ParamDoc::optional(
+                    "days",
+                    ParamDocType::Number,
+                    "How many days the certificate is valid for. Default: 365.",
+                ),
+            ],
+        },
+        ParamsDoc {
+            description: "From an ACME CA, through certbot.",
+            params: &[
+                ParamDoc::required(
+                    "issuer",
+                    ParamDocType::Literal("acme"),
+                    "Have certbot obtain the certificate, e.g. from Let's Encrypt.",
+                ),
+                CERT_PATH_DOC,
+                KEY_PATH_DOC,
+                ParamDoc::required(
+                    "names",
+                    ParamDocType::StringList,
+                    "Domains the certificate is for, each pointing at this machine. The first names certbot's copy, under `/etc/letsencrypt/live/`.",
+                ),
+                RENEW_DAYS_DOC,
+                ParamDoc::required(
+                    "email",
+                    ParamDocType::String,
+                    "Email to register the ACME account with, for expiry notices.",
+                ),
+                ParamDoc::optional(
+                    "webroot",
+                    ParamDocType::TargetPath,
+                    "Directory a web server on port 80 serves, to answer the challenge from. When not given, certbot listens on port 80 itself.",
+                ),
+            ],
+        },
+    ];
+
+    type Params = TlsCertParams;
+    type Resource = TlsCertResource;
+
+    fn resources(params: Self::Params) -> Vec<CausalityTree<Self::Resource>> {
+        let TlsCertParams {
+            cert_path,
+            key_path,
+            names,
+            renew_days,
+            issuer,
+        } = params;
+        vec![CausalityTree::leaf(
+            CausalityMeta::default(),
+            TlsCertResource {
+                cert_path: FilePath::new(cert_path),
+                key_path: FilePath::new(key_path),
+                names,
+                renew_days,
+                issuer,
+            },
+        )]
+    }
+
+    type State = TlsCertState;
+    type StateError = TlsCertStateError;
+
+    async fn state(
+        _ctx: &mut Context,
+        resource: &Self::Resource,
+    ) -> Result<Self::State, Self::StateError> {
+        let TlsCertResource {
+            cert_path,
+            key_path,
+            renew_days,
+            ..
+        } = resource;
+
+        let mut cmd = Command::new("test");
+        cmd.arg("-s").arg(key_path.as_path());
+        if !cmd.sudo().outcome().await?.status.success() {
+            return Ok(TlsCertState::Missing);
+        }
+
+        let mut cmd = Command::new("openssl");
+        cmd.args([
+            "x509",
+            "-noout",
+            "-enddate",
+            "-ext",
+            "subjectAltName",
+            "-in",
+        ])
+        .arg(cert_path.as_path());
+        let outcome = cmd.sudo().outcome().await?;
+        if !outcome.status.success() {
+            return Ok(TlsCertState::Missing);
+        }
+        let shown = String::from_utf8_lossy(&outcome.stdout);
+
+        // `-checkend` exits unsuccessfully when the certificate expires
+        // within that many seconds.
+        let seconds = u64::from(*renew_days) * SECONDS_PER_DAY;
+        let mut cmd = Command::new("openssl");
+        cmd.args(["x509", "-noout", "-checkend"])
+            .arg(seconds.to_string())
+            .arg("-in")
+            .arg(cert_path.as_path());
+        let expiring = !cmd.sudo().outcome().await?.status.success();
+
+        Ok(TlsCertState::Present {
+            names: parse_subject_alt_names(&shown),
+            not_after: parse_not_after(&shown).unwrap_or_default(),
+            expiring,
+        })
+    }
+
+    type Change = TlsCertChange;
+
+    fn change(resource: &Self::Resource, state: &Self::State) -> Option<Self::Change> {
+        match state {
+            TlsCertState::Missing => Some(TlsCertChange::Issue(resource.clone())),
+            TlsCertState::Present { names, .. } if !same_names(names, &resource.names) => {
+                Some(TlsCertChange::Issue(resource.clone()))
+            }
+            TlsCertState::Present {
+                not_after,
+                expiring: true,
+                ..
+            } => Some(TlsCertChange::Renew {
+                resource: resource.clone(),
+                not_after: not_after.clone(),
+            }),
+            TlsCertState::Present { .. } => None,
+        }
+    }
+
+    fn operations(change: Self::Change) -> Vec<CausalityTree<Operation>> {
+        let (resource, renew) = match change {
+            TlsCertChange::Issue(resource) => (resource, false),
+            TlsCertChange::Renew { resource, .. } => (resource, true),
+        };
+        let TlsCertResource {
+            cert_path,
+            key_path,
+            names,
+            issuer,
+            ..
+        } = resource;
+        let operation = match issuer {
+            TlsCertIssuer::SelfSigned { days } => TlsCertOperation::SelfSigned {
+                cert_path,
+                key_path,
+                names,
+                days,
+            },
+            TlsCertIssuer::Acme { email, webroot } => TlsCertOperation::Acme {
+                cert_path,
+                key_path,
+                domains: names,
+                email,
+                webroot,
+                renew,
+            },
+        };
+        vec![CausalityTree::leaf(
+            CausalityMeta::default(),
+            Operation::TlsCert(operation),
+        )]
+    }
+}
+
+const CERT_PATH_DOC: ParamDoc = ParamDoc::required(
+    "cert_path",
+    ParamDocType::TargetPath,
+    "Where the certificate, with any chain after it, is written in PEM, mode 0644.",
+);
+
+const KEY_PATH_DOC: ParamDoc = ParamDoc::required(
+    "key_path",
+    ParamDocType::TargetPath,
+    "Where the private key is written in PEM, mode 0600.",
+);
+
+const RENEW_DAYS_DOC: ParamDoc = ParamDoc::optional(
+    "renew_days",
+    ParamDocType::Number,
+    "Renew the certificate once it expires within this many days. Default: 30.",
+);
+
+/// The `notAfter=` date from `openssl x509 -enddate`.
+fn parse_not_after(shown: &str) -> Option<String> {
+    shown
+        .lines()
+        .find_map(|line| line.strip_prefix("notAfter="))
+        .map(|date| date.trim().to_owned())
+}
+
+/// The names on the line after `openssl x509 -ext subjectAltName`'s header,
+/// like `DNS:example.com, IP Address:10.0.0.1`. Entries of other types are
+/// skipped.
+fn parse_subject_alt_names(shown: &str) -> Vec<String> {
+    let mut lines = shown.lines();
+    if lines
+        .by_ref()
+        .find(|line| line.starts_with("X509v3 Subject Alternative Name"))
+        .is_none()
+    {
+        return Vec::new();
+    }
+    let Some(entries) = lines.next() else {
+        return Vec::new();
+    };
+    entries
+        .split(',')
+        .filter_map(|entry| {
+            let entry = entry.trim();
+            entry
+                .strip_prefix("DNS:")
+                .or_else(|| entry.strip_prefix("IP Address:"))
+                .map(str::to_owned)
+        })
+        .collect()
+}
+
+/// Whether the certificate's names are the wanted ones, in any order. DNS
+/// names are compared without case, and IP addresses by value, as openssl
+/// shows IPv6 addresses in full.
+fn same_names(have: &[String], want: &[String]) -> bool {
+    fn normalize(names: &[String]) -> BTreeSet<String> {
+        names
+            .iter()
+            .map(|name| match name.parse::<IpAddr>() {
+                Ok(ip) => ip.to_string(),
+                Err(_) => name.to_ascii_lowercase(),
+            })
+            .collect()
+    }
+    normalize(have) == normalize(want)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(ToString::to_string).collect()
+    }
+
+    fn resource(issuer: TlsCertIssuer) -> TlsCertResource {
+        TlsCertResource {
+            cert_path: FilePath::new("/etc/ssl/lusid/example.com.crt"),
+            key_path: FilePath::new("/etc/ssl/lusid/example.com.key"),
+            names: strings(&["example.com", "::1"]),
+            renew_days: 30,
+            issuer,
+        }
+    }
+
+    #[test]
+    fn reads_openssl_output() {
+        let shown = "notAfter=Oct 16 12:00:00 2027 GMT\n\
+                     X509v3 Subject Alternative Name: \n    \
+                     DNS:Example.com, IP Address:0:0:0:0:0:0:0:1, email:admin@example.com\n";
+        assert_eq!(
+            parse_not_after(shown).as_deref(),
+            Some("Oct 16 12:00:00 2027 GMT")
+        );
+        let names = parse_subject_alt_names(shown);
+        assert_eq!(names, strings(&["Example.com", "0:0:0:0:0:0:0:1"]));
+        assert!(same_names(&names, &strings(&["::1", "example.com"])));
+        assert!(!same_names(&names, &strings(&["example.com"])));
+        assert!(parse_subject_alt_names("notAfter=Oct 16 12:00:00 2027 GMT\n").is_empty());
+    }
+
+    #[test]
+    fn other_names_are_reissued_before_expiry_is_renewed() {
+        let resource = resource(TlsCertIssuer::Acme {
+            email: "admin@example.com".into(),
+            webroot: None,
+        });
+        let present = |names: &[&str], expiring| TlsCertState::Present {
+            names: strings(names),
+            not_after: "Oct 16 12:00:00 2027 GMT".into(),
+            expiring,
+        };
+
+        assert!(TlsCert::change(&resource, &present(&["example.com", "::1"], false)).is_none());
+        assert!(matches!(
+            TlsCert::change(&resource, &TlsCertState::Missing),
+            Some(TlsCertChange::Issue(_))
+        ));
+        assert!(matches!(
+            TlsCert::change(&resource, &present(&["example.com"], true)),
+            Some(TlsCertChange::Issue(_))
+        ));
+        let change =
+            TlsCert::change(&resource, &present(&["example.com", "::1"], true)).expect("change");
+        assert_eq!(
+            change.to_string(),
+            "TlsCert::Renew(/etc/ssl/lusid/example.com.crt, expiring Oct 16 12:00:00 2027 GMT)"
+        );
+        let CausalityTree::Leaf { node, .. } = &TlsCert::operations(change)[0] else {
+            panic!("expected leaf");
+        };
+        assert!(matches!(
+            node,
+            Operation::TlsCert(TlsCertOperation::Acme { renew: true, .. })
+        ));
+    }
+}
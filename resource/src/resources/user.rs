@@ -11,9 +11,12 @@ use lusid_operation::{
 use lusid_params::{ParseError, ParseParams, StructFields};
 use lusid_view::impl_display_render;
 use rimu::{Spanned, Value};
+use serde::Serialize;
 use thiserror::Error;
 
 use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
+use crate::resources::file::parse_file_path;
 
 /// Plan-level parameters for the `@core/user` resource.
 ///
@@ -22,7 +25,8 @@ use crate::ResourceType;
 // TODO(cc): add password (hashed), lock/unlock (`usermod -L`/`-U`), and account
 // expiry (`chage` / `usermod --expiredate`) support. Salt and Ansible both expose
 // these.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum UserParams {
     Present {
         name: String,
@@ -57,7 +61,7 @@ impl ParseParams for UserParams {
                 group: fields.optional_string("group")?,
                 append_groups: fields.optional_string_list("append_groups")?,
                 comment: fields.optional_string("comment")?,
-                home: fields.optional_target_path("home")?.map(FilePath::new),
+                home: fields.optional("home", parse_file_path)?,
                 shell: fields.optional_string("shell")?,
                 system: fields.optional_bool("system")?,
                 create_home: fields.optional_bool("create_home")?,
@@ -240,6 +244,44 @@ pub struct User;
 #[async_trait]
 impl ResourceType for User {
     const ID: &'static str = "user";
+    const DESCRIPTION: &'static str = "Manage a Unix user.";
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[
+        ParamsDoc {
+            description: "Present.",
+            params: &[
+                ParamDoc::required("state", ParamDocType::Literal("present"), "Present."),
+                ParamDoc::required("name", ParamDocType::String, "User name."),
+                ParamDoc::optional("uid", ParamDocType::Number, "User id."),
+                ParamDoc::optional("group", ParamDocType::String, "Primary group."),
+                ParamDoc::optional(
+                    "append_groups",
+                    ParamDocType::StringList,
+                    "Groups to add the user to, keeping any others.",
+                ),
+                ParamDoc::optional("comment", ParamDocType::String, "Full name or comment."),
+                ParamDoc::optional("home", ParamDocType::TargetPath, "Home directory."),
+                ParamDoc::optional("shell", ParamDocType::String, "Login shell."),
+                ParamDoc::optional("system", ParamDocType::Boolean, "Create as a system user."),
+                ParamDoc::optional(
+                    "create_home",
+                    ParamDocType::Boolean,
+                    "Create the home directory.",
+                ),
+            ],
+        },
+        ParamsDoc {
+            description: "Removed.",
+            params: &[
+                ParamDoc::required("state", ParamDocType::Literal("absent"), "Absent."),
+                ParamDoc::required("name", ParamDocType::String, "User name."),
+                ParamDoc::optional(
+                    "remove_home",
+                    ParamDocType::Boolean,
+                    "Also remove the home directory.",
+                ),
+            ],
+        },
+    ];
 
     type Params = UserParams;
     type Resource = UserResource;
@@ -0,0 +1,663 @@
+//! `@core/wireguard`: a WireGuard interface, configured for wg-quick (see
+//! [`lusid_operation::operations::wireguard`]).
+//!
+//! The interface's config is rendered to `/etc/wireguard/<interface>.conf`.
+//! Its private key stays in the file at `private_key_path`, which wg-quick
+//! reads on `PostUp`, so the config only holds peers' preshared keys. When
+//! the config changes, its operations write it and then restart
+//! `wg-quick@<interface>.service`; an unchanged config leaves the interface
+//! up as it is.
+//!
+//! Key material is redacted wherever the resource is shown: a public key as
+//! its first characters, enough to tell peers apart, and a preshared key
+//! not at all.
+//
+// Note(cc): this doesn't write the private key, or enable the interface at
+// boot. Write the key with `@core/file` and a secret, mode 0600, and enable
+// `wg-quick@<interface>` with `@core/systemd`, both required by this.
+
+use std::fmt::Display;
+
+use async_trait::async_trait;
+use lusid_causality::{CausalityMeta, CausalityTree};
+use lusid_cmd::{Command, CommandError};
+use lusid_ctx::Context;
+use lusid_operation::{
+    Operation,
+    operations::{file::FilePath, service::ServiceOperation, wireguard::WireguardOperation},
+};
+use lusid_params::{
+    ParseError, ParseParams, StructFields, parse_list, parse_string, parse_target_path,
+};
+use lusid_view::impl_display_render;
+use rimu::{Spanned, Value};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use thiserror::Error;
+
+use crate::ResourceType;
+use crate::docs::{ParamDoc, ParamDocType, ParamsDoc};
+
+const CONFIG_DIR: &str = "/etc/wireguard";
+
+/// How many characters of a public key are shown.
+const KEY_PREFIX_LEN: usize = 8;
+
+#[derive(Clone)]
+pub struct WireguardPeerParams {
+    pub public_key: String,
+    pub preshared_key: Option<String>,
+    pub endpoint: Option<String>,
+    pub allowed_ips: Vec<String>,
+    pub persistent_keepalive: Option<u32>,
+}
+
+impl ParseParams for WireguardPeerParams {
+    fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
+        let mut fields = StructFields::new(value)?;
+        let public_key = fields.required("public_key", parse_public_key)?;
+        let preshared_key = fields.optional_string("preshared_key")?;
+        let endpoint = fields.optional_string("endpoint")?;
+        let allowed_ips = fields.required_string_list("allowed_ips")?;
+        let persistent_keepalive = fields.optional_u32("persistent_keepalive")?;
+        fields.finish()?;
+        Ok(WireguardPeerParams {
+            public_key,
+            preshared_key,
+            endpoint,
+            allowed_ips,
+            persistent_keepalive,
+        })
+    }
+}
+
+impl std::fmt::Debug for WireguardPeerParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            public_key,
+            preshared_key,
+            endpoint,
+            allowed_ips,
+            persistent_keepalive,
+        } = self;
+        f.debug_struct("WireguardPeerParams")
+            .field("public_key", &redact_public_key(public_key))
+            .field(
+                "preshared_key",
+                &preshared_key.as_ref().map(|_| format_args!("<redacted>")),
+            )
+            .field("endpoint", endpoint)
+            .field("allowed_ips", allowed_ips)
+            .field("persistent_keepalive", persistent_keepalive)
+            .finish()
+    }
+}
+
+/// Keys as in its `Debug`: the public key's first characters, and never the
+/// preshared key.
+impl Serialize for WireguardPeerParams {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Self {
+            public_key,
+            preshared_key,
+            endpoint,
+            allowed_ips,
+            persistent_keepalive,
+        } = self;
+        let mut peer = serializer.serialize_struct("WireguardPeerParams", 5)?;
+        peer.serialize_field("public_key", &redact_public_key(public_key))?;
+        peer.serialize_field(
+            "preshared_key",
+            &preshared_key.as_ref().map(|_| "<redacted>"),
+        )?;
+        peer.serialize_field("endpoint", endpoint)?;
+        peer.serialize_field("allowed_ips", allowed_ips)?;
+        peer.serialize_field("persistent_keepalive", persistent_keepalive)?;
+        peer.end()
+    }
+}
+
+impl Display for WireguardPeerParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            public_key,
+            preshared_key,
+            endpoint,
+            allowed_ips,
+            persistent_keepalive,
+        } = self;
+        write!(f, "Peer(public_key = {}", redact_public_key(public_key))?;
+        if preshared_key.is_some() {
+            write!(f, ", preshared_key = <redacted>")?;
+        }
+        if let Some(endpoint) = endpoint {
+            write!(f, ", endpoint = {endpoint}")?;
+        }
+        write!(f, ", allowed_ips = [{}]", allowed_ips.join(", "))?;
+        if let Some(persistent_keepalive) = persistent_keepalive {
+            write!(f, ", persistent_keepalive = {persistent_keepalive}")?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WireguardParams {
+    /// The interface's name, e.g. `wg0`.
+    pub interface: String,
+    pub private_key_path: String,
+    pub address: Vec<String>,
+    pub listen_port: Option<u16>,
+    pub peers: Vec<WireguardPeerParams>,
+}
+
+impl ParseParams for WireguardParams {
+    fn parse_params(value: Spanned<Value>) -> Result<Self, Spanned<ParseError>> {
+        let mut fields = StructFields::new(value)?;
+        let interface = fields.required("interface", parse_interface)?;
+        let private_key_path = fields.required("private_key_path", parse_private_key_path)?;
+        let address = fields.optional_string_list("address")?.unwrap_or_default();
+        let listen_port = fields.optional("listen_port", parse_listen_port)?;
+        let peers = fields
+            .optional("peers", |value| {
+                parse_list(value, WireguardPeerParams::parse_params)
+            })?
+            .unwrap_or_default();
+        fields.finish()?;
+        Ok(WireguardParams {
+            interface,
+            private_key_path,
+            address,
+            listen_port,
+            peers,
+        })
+    }
+}
+
+// The name is joined onto `/etc/wireguard/`, and wg-quick only takes names
+// that Linux would as an interface's.
+fn parse_interface(value: Spanned<Value>) -> Result<String, Spanned<ParseError>> {
+    let span = value.span();
+    let interface = parse_string(value)?;
+    let valid = (1..=15).contains(&interface.len())
+        && interface
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_=+.-".contains(c));
+    if valid {
+        Ok(interface)
+    } else {
+        Err(Spanned::new(
+            ParseError::TypeMismatch {
+                expected: "interface name of 1 to 15 letters, digits or `_=+.-`",
+                got: Box::new(Value::String(interface)),
+            },
+            span,
+        ))
+    }
+}
+
+// wg-quick runs `PostUp` through bash, so the path can't be split into words.
+fn parse_private_key_path(value: Spanned<Value>) -> Result<String, Spanned<ParseError>> {
+    let span = value.span();
+    let path = parse_target_path(value)?;
+    if path.contains(|c: char| c.is_whitespace() || "'\"\\$`;&|".contains(c)) {
+        return Err(Spanned::new(
+            ParseError::InvalidTargetPath {
+                value: path,
+                reason: "wg-quick reads it through a shell, so it can't hold whitespace or shell characters".to_owned(),
+            },
+            span,
+        ));
+    }
+    Ok(path)
+}
+
+fn parse_listen_port(value: Spanned<Value>) -> Result<u16, Spanned<ParseError>> {
+    let (value, span) = value.take();
+    let port = match &value {
+        Value::Number(number) => number.to_u32().and_then(|port| u16::try_from(port).ok()),
+        _ => None,
+    };
+    port.ok_or_else(|| {
+        Spanned::new(
+            ParseError::TypeMismatch {
+                expected: "port number",
+                got: Box::new(value),
+            },
+            span,
+        )
+    })
+}
+
+// A key is 32 bytes in base64: 43 characters and one `=` of padding. Public
+// keys are checked here rather than by wg-quick at apply time; preshared
+// keys aren't, as the error would show the key.
+fn parse_public_key(value: Spanned<Value>) -> Result<String, Spanned<ParseError>> {
+    let span = value.span();
+    let key = parse_string(value)?;
+    let valid = key.len() == 44
+        && key.ends_with('=')
+        && key
+            .bytes()
+            .take(43)
+            .all(|b| b.is_ascii_alphanumeric() || b == b'+' || b == b'/');
+    if valid {
+        Ok(key)
+    } else {
+        Err(Spanned::new(
+            ParseError::TypeMismatch {
+                expected: "base64 WireGuard public key",
+                got: Box::new(Value::String(key)),
+            },
+            span,
+        ))
+    }
+}
+
+/// The first characters of `key`, which are enough to tell it apart.
+fn redact_public_key(key: &str) -> String {
+    match key.get(..KEY_PREFIX_LEN) {
+        Some(prefix) => format!("{prefix}…"),
+        None => key.to_owned(),
+    }
+}
+
+impl Display for WireguardParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            interface,
+            private_key_path,
+            address,
+            listen_port,
+            peers,
+        } = self;
+        write!(
+            f,
+            "Wireguard(interface = {interface}, private_key_path = {private_key_path}"
+        )?;
+        if !address.is_empty() {
+            write!(f, ", address = [{}]", address.join(", "))?;
+        }
+        if let Some(listen_port) = listen_port {
+            write!(f, ", listen_port = {listen_port}")?;
+        }
+        let peers: Vec<String> = peers.iter().map(ToString::to_string).collect();
+        write!(f, ", peers = [{}])", peers.join(", "))
+    }
+}
+
+impl_display_render!(WireguardParams);
+
+/// The interface's wg-quick config.
+pub fn render_config(params: &WireguardParams) -> String {
+    let WireguardParams {
+        private_key_path,
+        address,
+        listen_port,
+        peers,
+        ..
+    } = params;
+    let mut config = String::from("[Interface]\n");
+    if !address.is_empty() {
+        config.push_str(&format!("Address = {}\n", address.join(", ")));
+    }
+    if let Some(listen_port) = listen_port {
+        config.push_str(&format!("ListenPort = {listen_port}\n"));
+    }
+    config.push_str(&format!(
+        "PostUp = wg set %i private-key {private_key_path}\n"
+    ));
+    for peer in peers {
+        let WireguardPeerParams {
+            public_key,
+            preshared_key,
+            endpoint,
+            allowed_ips,
+            persistent_keepalive,
+        } = peer;
+        config.push_str(&format!("\n[Peer]\nPublicKey = {public_key}\n"));
+        if let Some(preshared_key) = preshared_key {
+            config.push_str(&format!("PresharedKey = {preshared_key}\n"));
+        }
+        if let Some(endpoint) = endpoint {
+            config.push_str(&format!("Endpoint = {endpoint}\n"));
+        }
+        config.push_str(&format!("AllowedIPs = {}\n", allowed_ips.join(", ")));
+        if let Some(persistent_keepalive) = persistent_keepalive {
+            config.push_str(&format!("PersistentKeepalive = {persistent_keepalive}\n"));
+        }
+    }
+    config
+}
+
+/// The rendered config of `interface`.
+#[derive(Clone)]
+pub struct WireguardResource {
+    pub interface: String,
+    pub path: FilePath,
+    pub content: String,
+}
+
+// The config holds preshared keys, so Debug leaves it out like Display.
+impl std::fmt::Debug for WireguardResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            interface, path, ..
+        } = self;
+        f.debug_struct("WireguardResource")
+            .field("interface", interface)
+            .field("path", path)
+            .field("content", &format_args!("<redacted>"))
+            .finish()
+    }
+}
+
+impl Display for WireguardResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            interface,
+            path,
+            content,
+        } = self;
+        write!(
+            f,
+            "Wireguard(interface = {interface}, path = {path}, content = {} bytes)",
+            content.len()
+        )
+    }
+}
+
+impl_display_render!(WireguardResource);
+
+#[derive(Debug, Clone)]
+pub enum WireguardState {
+    Matches,
+    Differs,
+}
+
+impl Display for WireguardState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WireguardState::Matches => write!(f, "Wireguard::Matches"),
+            WireguardState::Differs => write!(f, "Wireguard::Differs"),
+        }
+    }
+}
+
+impl_display_render!(WireguardState);
+
+#[derive(Error, Debug)]
+pub enum WireguardStateError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+}
+
+#[derive(Clone)]
+pub enum WireguardChange {
+    Write {
+        interface: String,
+        path: FilePath,
+        content: String,
+    },
+}
+
+impl std::fmt::Debug for WireguardChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WireguardChange::Write {
+                interface, path, ..
+            } => f
+                .debug_struct("Write")
+                .field("interface", interface)
+                .field("path", path)
+                .field("content", &format_args!("<redacted>"))
+                .finish(),
+        }
+    }
+}
+
+impl Display for WireguardChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WireguardChange::Write {
+                interface, path, ..
+            } => {
+                write!(f, "Wireguard::write+restart({interface} -> {path})")
+            }
+        }
+    }
+}
+
+impl_display_render!(WireguardChange);
+
+#[derive(Debug, Clone)]
+pub struct Wireguard;
+
+#[async_trait]
+impl ResourceType for Wireguard {
+    const ID: &'static str = "wireguard";
+    const DESCRIPTION: &'static str =
+        "Configure a WireGuard interface for wg-quick, restarting it when its config changes.";
+    const PARAMS_DOCS: &'static [ParamsDoc] = &[ParamsDoc {
+        description: "An interface and its peers.",
+        params: &[
+            ParamDoc::required(
+                "interface",
+                ParamDocType::String,
+                "The interface's name, e.g. `wg0`. Its config is `/etc/wireguard/<interface>.conf`.",
+            ),
+            ParamDoc::required(
+                "private_key_path",
+                ParamDocType::TargetPath,
+                "The file holding the interface's private key, read when it comes up. It isn't written into the config.",
+            ),
+            ParamDoc::optional(
+                "address",
+                ParamDocType::StringList,
+                "The interface's addresses, e.g. `10.0.0.1/24`.",
+            ),
+            ParamDoc::optional(
+                "listen_port",
+                ParamDocType::Number,
+                "The UDP port to listen on. A random port when not given.",
+            ),
+            ParamDoc::optional(
+                "peers",
+                ParamDocType::ObjectList,
+                "Peers, each with a `public_key` and `allowed_ips`, and optionally a `preshared_key`, `endpoint` and `persistent_keepalive` in seconds.",
+            ),
+        ],
+    }];
+
+    type Params = WireguardParams;
+    type Resource = WireguardResource;
+
+    fn resources(params: Self::Params) -> Vec<CausalityTree<Self::Resource>> {
+        let content = render_config(&params);
+        let WireguardParams { interface, .. } = params;
+        vec![CausalityTree::leaf(
+            CausalityMeta::default(),
+            WireguardResource {
+                path: FilePath::new(format!("{CONFIG_DIR}/{interface}.conf")),
+                interface,
+                content,
+            },
+        )]
+    }
+
+    type State = WireguardState;
+    type StateError = WireguardStateError;
+
+    // `/etc/wireguard` is root's alone, so the config is read through sudo. A
+    // config that can't be read, because it's missing, is one to write.
+    async fn state(
+        _ctx: &mut Context,
+        resource: &Self::Resource,
+    ) -> Result<Self::State, Self::StateError> {
+        let WireguardResource { path, content, .. } = resource;
+        let mut cmd = Command::new("cat");
+        cmd.arg(path.as_path());
+        let outcome = cmd.sudo().outcome().await?;
+        let matches = outcome.status.success() && outcome.stdout == content.as_bytes();
+        Ok(if matches {
+            WireguardState::Matches
+        } else {
+            WireguardState::Differs
+        })
+    }
+
+    type Change = WireguardChange;
+
+    fn change(resource: &Self::Resource, state: &Self::State) -> Option<Self::Change> {
+        match state {
+            WireguardState::Matches => None,
+            WireguardState::Differs => {
+                let WireguardResource {
+                    interface,
+                    path,
+                    content,
+                } = resource.clone();
+                Some(WireguardChange::Write {
+                    interface,
+                    path,
+                    content,
+                })
+            }
+        }
+    }
+
+    fn operations(change: Self::Change) -> Vec<CausalityTree<Operation>> {
+        match change {
+            WireguardChange::Write {
+                interface,
+                path,
+                content,
+            } => {
+                let unit = format!("wg-quick@{interface}.service");
+                vec![
+                    CausalityTree::leaf(
+                        CausalityMeta::id("write".into()),
+                        Operation::Wireguard(WireguardOperation::WriteConfig {
+                            interface,
+                            path,
+                            content,
+                        }),
+                    ),
+                    CausalityTree::leaf(
+                        CausalityMeta::requires(vec!["write".into()]),
+                        Operation::Service(ServiceOperation::Restart { unit }),
+                    ),
+                ]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUBLIC_KEY: &str = "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=";
+    const PRESHARED_KEY: &str = "/UwcSPg38hW/D9Y3tcS1FOV0K1wuURMbS0sesJEP5ak=";
+
+    fn params() -> WireguardParams {
+        WireguardParams {
+            interface: "wg0".into(),
+            private_key_path: "/etc/wireguard/wg0.key".into(),
+            address: vec!["10.0.0.1/24".into(), "fd00::1/64".into()],
+            listen_port: Some(51820),
+            peers: vec![WireguardPeerParams {
+                public_key: PUBLIC_KEY.into(),
+                preshared_key: Some(PRESHARED_KEY.into()),
+                endpoint: Some("vpn.example.com:51820".into()),
+                allowed_ips: vec!["10.0.0.2/32".into()],
+                persistent_keepalive: Some(25),
+            }],
+        }
+    }
+
+    #[test]
+    fn config_reads_the_private_key_from_its_file() {
+        assert_eq!(
+            render_config(&params()),
+            format!(
+                "[Interface]\n\
+                 Address = 10.0.0.1/24, fd00::1/64\n\
+                 ListenPort = 51820\n\
+                 PostUp = wg set %i private-key /etc/wireguard/wg0.key\n\
+                 \n\
+                 [Peer]\n\
+                 PublicKey = {PUBLIC_KEY}\n\
+                 PresharedKey = {PRESHARED_KEY}\n\
+                 Endpoint = vpn.example.com:51820\n\
+                 AllowedIPs = 10.0.0.2/32\n\
+                 PersistentKeepalive = 25\n"
+            )
+        );
+    }
+
+    #[test]
+    fn keys_are_redacted() {
+        let params = params();
+        let shown = params.to_string();
+        assert!(shown.contains("public_key = xTIBA5rb…"));
+        assert!(!shown.contains(PUBLIC_KEY));
+        assert!(!shown.contains(PRESHARED_KEY));
+
+        let resources = Wireguard::resources(params);
+        let CausalityTree::Leaf { node, .. } = &resources[0] else {
+            panic!("expected leaf");
+        };
+        assert_eq!(node.path.to_string(), "/etc/wireguard/wg0.conf");
+        assert!(!node.to_string().contains(PRESHARED_KEY));
+    }
+
+    #[test]
+    fn debug_and_serialize_redact_keys() {
+        let params = params();
+        let resource = WireguardResource {
+            interface: "wg0".into(),
+            path: FilePath::new("/etc/wireguard/wg0.conf"),
+            content: render_config(&params),
+        };
+        let change = Wireguard::change(&resource, &WireguardState::Differs).expect("change");
+        for shown in [
+            format!("{params:?}"),
+            format!("{:?}", params.peers[0]),
+            format!("{resource:?}"),
+            format!("{change:?}"),
+            serde_json::to_string(&params).unwrap(),
+        ] {
+            assert!(shown.contains("<redacted>"), "{shown}");
+            assert!(!shown.contains(PUBLIC_KEY), "{shown}");
+            assert!(!shown.contains(PRESHARED_KEY), "{shown}");
+        }
+    }
+
+    #[test]
+    fn changed_config_is_written_then_restarted() {
+        let resource = WireguardResource {
+            interface: "wg0".into(),
+            path: FilePath::new("/etc/wireguard/wg0.conf"),
+            content: render_config(&params()),
+        };
+        assert!(Wireguard::change(&resource, &WireguardState::Matches).is_none());
+        let change = Wireguard::change(&resource, &WireguardState::Differs).expect("change");
+        let operations: Vec<String> = Wireguard::operations(change)
+            .iter()
+            .map(|tree| match tree {
+                CausalityTree::Leaf { node, .. } => node.to_string(),
+                _ => panic!("expected leaf"),
+            })
+            .collect();
+        assert_eq!(
+            operations,
+            [
+                format!(
+                    "Wireguard::WriteConfig(interface = wg0, path = /etc/wireguard/wg0.conf, {} bytes)",
+                    resource.content.len()
+                ),
+                "Service::Restart(wg-quick@wg0.service)".to_owned(),
+            ]
+        );
+    }
+}
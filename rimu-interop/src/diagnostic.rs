@@ -0,0 +1,152 @@
+//! rustc-style rendering of an error message with the plan source excerpt its
+//! [`Span`] points at:
+//!
+//! ```text
+//! error: Failed to parse field "package": Expected string, got value Number(1)
+//!  --> /home/me/plans/base.lusid:4:14
+//!   |
+//! 4 |     package: 1,
+//!   |              ^
+//! ```
+//!
+//! Spans whose source can't be read (CLI `--params`, synthesised values, URL
+//! plans) fall back to the bare `error: <message>` line. Warnings render the
+//! same way, headed `warning:`.
+
+use std::fmt::Display;
+
+use rimu::Span;
+
+/// Render `message` with the excerpt at `span`, reading the span's source from
+/// disk. See [`render_excerpt`] for the format.
+pub fn render_diagnostic(message: &dyn Display, span: Option<&Span>) -> String {
+    render_with_header(format!("error: {message}"), span)
+}
+
+/// [`render_diagnostic`] for something that isn't fatal, headed `warning:`.
+pub fn render_warning(message: &dyn Display, span: Option<&Span>) -> String {
+    render_with_header(format!("warning: {message}"), span)
+}
+
+fn render_with_header(header: String, span: Option<&Span>) -> String {
+    let Some(span) = span else {
+        return header;
+    };
+    let source_id = span.source();
+    let name = source_id.as_str();
+    if name.is_empty() {
+        return header;
+    }
+    match std::fs::read_to_string(name) {
+        Ok(code) => format!(
+            "{header}\n{}",
+            render_excerpt(name, &code, span.start(), span.end())
+        ),
+        Err(_) => header,
+    }
+}
+
+/// Render the lines of `code` covered by the `start..end` range, with a
+/// `-->` location header and carets under the covered columns.
+///
+/// Note(cc): rimu's lexer runs over `chars()`, so span offsets are char
+/// indices rather than byte indices; we count in chars to match. Multi-line
+/// spans mark from the start column to the end of each line, except the last.
+pub fn render_excerpt(name: &str, code: &str, start: usize, end: usize) -> String {
+    let end = end.max(start + 1);
+
+    // (line number, first char offset, line text) for each line the span touches.
+    let mut lines: Vec<(usize, usize, &str)> = Vec::new();
+    let mut offset = 0;
+    for (index, line) in code.split('\n').enumerate() {
+        let len = line.chars().count();
+        let line_end = offset + len;
+        if line_end >= start && offset < end {
+            lines.push((index + 1, offset, line.strip_suffix('\r').unwrap_or(line)));
+        }
+        if offset >= end {
+            break;
+        }
+        offset = line_end + 1;
+    }
+
+    let Some(&(first_number, first_offset, _)) = lines.first() else {
+        return format!(" --> {name}");
+    };
+    let column = start - first_offset + 1;
+    let gutter = lines
+        .last()
+        .map(|(number, _, _)| number.to_string().len())
+        .unwrap_or(1);
+    let pad = " ".repeat(gutter);
+
+    let mut out = format!("{pad}--> {name}:{first_number}:{column}\n{pad} |");
+    for (number, line_offset, text) in lines {
+        let len = text.chars().count();
+        let from = start.saturating_sub(line_offset).min(len);
+        let to = (end - line_offset).min(len);
+        // Zero-width marks (an empty line or an end-of-line span) still get one caret.
+        let carets = to.saturating_sub(from).max(1);
+        out.push_str(&format!(
+            "\n{number:>gutter$} | {text}\n{pad} | {}{}",
+            " ".repeat(from),
+            "^".repeat(carets)
+        ));
+    }
+    out
+}
+
+/// The 1-based line of `code` that the char offset `offset` falls on, for
+/// pointing at a span without an excerpt (e.g. `plans/web.lusid:42`).
+pub fn line_number(code: &str, offset: usize) -> usize {
+    code.chars().take(offset).filter(|c| *c == '\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_line_span() {
+        let code = "{\n  module: \"@core/fil\",\n}\n";
+        let start = code.find('"').unwrap();
+        let end = start + "\"@core/fil\"".len();
+        assert_eq!(
+            render_excerpt("plan.lusid", code, start, end),
+            " --> plan.lusid:2:11\n  |\n2 |   module: \"@core/fil\",\n  |           ^^^^^^^^^^^"
+        );
+    }
+
+    #[test]
+    fn multi_line_span() {
+        let code = "a: [\n  1,\n]\n";
+        assert_eq!(
+            render_excerpt("plan.lusid", code, 3, 11),
+            " --> plan.lusid:1:4\n  |\n1 | a: [\n  |    ^\n2 |   1,\n  | ^^^^\n3 | ]\n  | ^"
+        );
+    }
+
+    #[test]
+    fn counts_chars_not_bytes() {
+        let code = "name: \"café\", x: 1";
+        let start = code.chars().position(|c| c == 'x').unwrap();
+        assert_eq!(
+            render_excerpt("plan.lusid", code, start, start + 1),
+            " --> plan.lusid:1:15\n  |\n1 | name: \"café\", x: 1\n  |               ^"
+        );
+    }
+
+    #[test]
+    fn line_numbers_count_chars() {
+        let code = "café: 1\nb: 2\n\nd: 4";
+        assert_eq!(line_number(code, 0), 1);
+        assert_eq!(
+            line_number(code, code.chars().position(|c| c == 'b').unwrap()),
+            2
+        );
+        assert_eq!(
+            line_number(code, code.chars().position(|c| c == 'd').unwrap()),
+            4
+        );
+    }
+}
@@ -5,9 +5,13 @@
 //! - [`to_rimu`]: serialize any `Serialize` type into a [`rimu::Spanned<Value>`]
 //!   carrying a synthetic span (used for exposing Rust structs like `System` to
 //!   plan scripts).
+//! - [`render_diagnostic`]: render an error message with the plan source excerpt its
+//!   span points at, rustc-style ([`render_warning`] for warnings).
 
+mod diagnostic;
 mod from_rimu;
 mod to_rimu;
 
+pub use crate::diagnostic::*;
 pub use crate::from_rimu::*;
 pub use crate::to_rimu::*;
@@ -97,6 +97,19 @@ pub enum LoadError {
     GuestModeWithoutIdentity,
 }
 
+impl LoadError {
+    /// Stable, machine-readable code for this failure.
+    pub fn code(&self) -> &'static str {
+        match self {
+            LoadError::Identity(_) => "secrets.identity",
+            LoadError::Recipients(_) => "secrets.recipients",
+            LoadError::DecryptDir(_) | LoadError::DecryptAll(_) => "secrets.decrypt",
+            LoadError::NoAliasForIdentity => "secrets.no-alias-for-identity",
+            LoadError::GuestModeWithoutIdentity => "secrets.guest-without-identity",
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::Path;
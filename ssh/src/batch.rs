@@ -0,0 +1,100 @@
+//! Run many short commands in one remote exec.
+//!
+//! Opening a channel is cheap next to opening a connection, but each command
+//! still costs a round trip to open its channel, exec, and wait for the exit
+//! status. Over a high-latency link that dominates probes like `uname -m`, so
+//! [`Ssh::output_batch`](crate::Ssh::output_batch) sends a small `sh` helper
+//! that runs each command in turn and frames its output, and splits the
+//! frames back apart here.
+//!
+//! Each frame is a header line `<exit code> <stdout bytes> <stderr bytes>`,
+//! then exactly that much stdout and stderr, so commands may print anything.
+//! Commands run with `sh -c` and no stdin, one after another: a command that
+//! blocks holds up the rest of the batch.
+
+use thiserror::Error;
+
+use crate::command::SshOutput;
+
+#[derive(Error, Debug)]
+pub enum SshBatchError {
+    #[error("batch helper failed (exit code {exit_code:?}): {stderr}")]
+    Helper {
+        exit_code: Option<u32>,
+        stderr: String,
+    },
+
+    #[error("malformed batch output for command {index}: {reason}")]
+    Malformed { index: usize, reason: &'static str },
+}
+
+/// Split the helper's `output` into the outputs of its `count` commands.
+pub(super) fn batch_outputs(
+    output: SshOutput,
+    count: usize,
+) -> Result<Vec<SshOutput>, SshBatchError> {
+    if !output.success() {
+        return Err(SshBatchError::Helper {
+            exit_code: output.exit_code,
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        });
+    }
+    parse_frames(&output.stdout, count)
+}
+
+/// The helper, as a command to exec.
+pub(super) fn helper_script(commands: &[String]) -> String {
+    let quoted: Vec<String> = commands
+        .iter()
+        .map(|command| shell_words::quote(command).into_owned())
+        .collect();
+    format!(
+        "t=$(mktemp -d) || exit 125\n\
+         trap 'rm -rf \"$t\"' EXIT\n\
+         for c in {}; do\n\
+         sh -c \"$c\" >\"$t/o\" 2>\"$t/e\" </dev/null\n\
+         s=$?\n\
+         printf '%s %s %s\\n' \"$s\" \"$(wc -c <\"$t/o\")\" \"$(wc -c <\"$t/e\")\"\n\
+         cat \"$t/o\" \"$t/e\"\n\
+         done\n",
+        quoted.join(" ")
+    )
+}
+
+fn parse_frames(mut stdout: &[u8], count: usize) -> Result<Vec<SshOutput>, SshBatchError> {
+    let mut outputs = Vec::with_capacity(count);
+    for index in 0..count {
+        let malformed = |reason| SshBatchError::Malformed { index, reason };
+
+        let newline = stdout
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .ok_or_else(|| malformed("missing header"))?;
+        let header =
+            std::str::from_utf8(&stdout[..newline]).map_err(|_| malformed("bad header"))?;
+        let numbers: Vec<u64> = header
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map_err(|_| malformed("bad header"))?;
+        let [exit_code, stdout_len, stderr_len] = numbers[..] else {
+            return Err(malformed("bad header"));
+        };
+        stdout = &stdout[newline + 1..];
+
+        let (stdout_len, stderr_len) = (stdout_len as usize, stderr_len as usize);
+        if stdout.len() < stdout_len + stderr_len {
+            return Err(malformed("truncated output"));
+        }
+        let (command_stdout, rest) = stdout.split_at(stdout_len);
+        let (command_stderr, rest) = rest.split_at(stderr_len);
+        stdout = rest;
+
+        outputs.push(SshOutput {
+            exit_code: Some(exit_code as u32),
+            stdout: command_stdout.to_vec(),
+            stderr: command_stderr.to_vec(),
+        });
+    }
+    Ok(outputs)
+}
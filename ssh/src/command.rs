@@ -1,10 +1,10 @@
 use async_promise::Promise;
 use thiserror::Error;
-use tokio::io::AsyncWrite;
+use tokio::io::{AsyncReadExt, AsyncWrite};
 use tracing::info;
 
 use crate::SshError;
-use crate::session::{AsyncChannel, AsyncSession, NoCheckHandler};
+use crate::session::{AsyncChannel, AsyncSession, PinnedKeyHandler};
 use crate::stream::ReadStream;
 
 /// Command execution specific errors.
@@ -22,6 +22,13 @@ pub enum SshCommandError {
 
     #[error("SSH protocol error: {0}")]
     Russh(#[from] russh::Error),
+
+    #[error("failed to read output of remote command `{command}`: {source}")]
+    ReadOutput {
+        command: String,
+        #[source]
+        source: std::io::Error,
+    },
 }
 
 pub struct SshChannelHandle {
@@ -83,13 +90,53 @@ pub struct SshCommandHandle {
     pub command: String,
 }
 
+/// Everything a finished remote command produced, for short commands whose
+/// output is parsed rather than streamed.
+#[derive(Debug, Clone)]
+pub struct SshOutput {
+    pub exit_code: Option<u32>,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl SshOutput {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+impl SshCommandHandle {
+    /// Read stdout and stderr to the end (concurrently, so neither can fill up
+    /// and stall the other), then wait for the exit code.
+    pub async fn output(mut self) -> Result<SshOutput, SshError> {
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let (stdout_result, stderr_result) = tokio::join!(
+            self.stdout.read_to_end(&mut stdout),
+            self.stderr.read_to_end(&mut stderr),
+        );
+        stdout_result
+            .and(stderr_result)
+            .map_err(|source| SshCommandError::ReadOutput {
+                command: self.command.clone(),
+                source,
+            })?;
+        let exit_code = self.channel.wait().await?;
+        Ok(SshOutput {
+            exit_code,
+            stdout,
+            stderr,
+        })
+    }
+}
+
 /// Execute a remote command and return a streaming handle.
 ///
 /// - stdout/stderr streams are created before exec to avoid missing data.
 /// - exec requests a reply, so success_failure() will resolve.
 #[tracing::instrument(skip(session))]
 pub(super) async fn ssh_command(
-    session: &AsyncSession<NoCheckHandler>,
+    session: &AsyncSession<PinnedKeyHandler>,
     command: &str,
 ) -> Result<SshCommandHandle, SshCommandError> {
     let channel = session
@@ -2,12 +2,13 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use russh::client::Config;
-use russh::keys::PrivateKey;
+use russh::keys::ssh_key::HashAlg;
+use russh::keys::{PrivateKey, PublicKey};
 use thiserror::Error;
 use tokio::net::ToSocketAddrs;
 use tokio::time::{Instant, sleep};
 
-use crate::session::{AsyncSession, NoCheckHandler};
+use crate::session::{AsyncSession, PinnedKeyHandler};
 
 #[derive(Debug, Clone)]
 pub struct SshConnectOptions<Addrs>
@@ -15,6 +16,9 @@ where
     Addrs: ToSocketAddrs + Clone + Send,
 {
     pub private_key: PrivateKey,
+    /// The server's expected host key. Connections to a server presenting
+    /// any other key fail with [`SshConnectError::HostKeyMismatch`].
+    pub host_key: PublicKey,
     pub addrs: Addrs,
     pub username: String,
     pub config: Arc<Config>,
@@ -26,6 +30,9 @@ pub enum SshConnectError {
     #[error("timed out connecting to SSH server")]
     Timeout,
 
+    #[error("SSH server's host key doesn't match the pinned key ({expected})")]
+    HostKeyMismatch { expected: String },
+
     #[error("SSH protocol error: {0}")]
     Russh(#[from] russh::Error),
 }
@@ -34,16 +41,17 @@ pub enum SshConnectError {
 ///
 /// - Retries transient IO errors until timeout is exceeded.
 /// - Authenticates via public key.
-/// - Host key verification is disabled (NoCheckHandler).
+/// - Verifies the server's host key against `host_key` (PinnedKeyHandler).
 #[tracing::instrument(skip(options))]
 pub(super) async fn connect_with_retry<Addrs>(
     options: SshConnectOptions<Addrs>,
-) -> Result<AsyncSession<NoCheckHandler>, SshConnectError>
+) -> Result<AsyncSession<PinnedKeyHandler>, SshConnectError>
 where
     Addrs: ToSocketAddrs + Clone + Send,
 {
     let SshConnectOptions {
         private_key,
+        host_key,
         addrs,
         username,
         config,
@@ -53,8 +61,11 @@ where
     let start = Instant::now();
     tracing::info!("Connecting to SSH");
 
+    let expected = host_key.fingerprint(HashAlg::Sha256).to_string();
+
     let mut session = loop {
-        match AsyncSession::connect(config.clone(), addrs.clone(), NoCheckHandler).await {
+        let handler = PinnedKeyHandler::new(host_key.clone());
+        match AsyncSession::connect(config.clone(), addrs.clone(), handler).await {
             Ok(session) => {
                 tracing::trace!("SSH transport established");
                 break session;
@@ -78,6 +89,10 @@ where
                     "SSH transport not ready; will retry"
                 );
             }
+            Err(russh::Error::UnknownKey) => {
+                tracing::warn!(expected = %expected, "SSH host key mismatch");
+                return Err(SshConnectError::HostKeyMismatch { expected });
+            }
             Err(error) => {
                 tracing::warn!(err = %error, "Non-retryable SSH error");
                 return Err(SshConnectError::Russh(error));
@@ -106,6 +106,21 @@ impl SshKeypair {
             .to_string())
     }
 
+    /// Load a keypair from an OpenSSH private key file, like
+    /// `~/.ssh/id_ed25519`, deriving its public key. The key must not be
+    /// passphrase-protected.
+    // Note(cc): no agent support, so a passphrase-protected key can't be
+    // used; `russh` can talk to an agent, but `Ssh::connect` only takes a key.
+    #[tracing::instrument]
+    pub async fn load_private(path: &Path) -> Result<Self, SshKeypairError> {
+        let private_key_string = fs::read_file_to_string(path).await?;
+        let private_key = PrivateKey::from_openssh(&private_key_string)?;
+        Ok(Self {
+            public_key: private_key.public_key().clone(),
+            private_key,
+        })
+    }
+
     /// Load a keypair from the directory.
     #[tracing::instrument(skip_all)]
     pub async fn load(directory: &Path) -> Result<Self, SshKeypairError> {
@@ -130,3 +145,9 @@ impl SshKeypair {
         })
     }
 }
+
+/// Parse a single-line OpenSSH public key (`ssh-ed25519 AAAA...`), like a
+/// server's host key from its `/etc/ssh/ssh_host_ed25519_key.pub`.
+pub fn parse_public_key(key: &str) -> Result<PublicKey, SshKeypairError> {
+    Ok(PublicKey::from_openssh(key.trim())?)
+}
@@ -2,17 +2,29 @@
 //!
 //! Built on [`russh`]. Provides:
 //!
-//! - [`Ssh::connect`] — connect with retry + public key auth.
+//! - [`Ssh::connect`] — connect with retry + public key auth, verifying the
+//!   server against a pinned host key.
 //! - [`Ssh::command`] — run a remote command and tail stdout/stderr as
 //!   [`tokio::io::AsyncRead`] streams.
+//! - [`Ssh::output`] — run a short remote command and collect its output.
+//! - [`Ssh::output_batch`] — run many short commands in one remote exec.
 //! - [`Ssh::sync`] — SFTP a local file / directory / bytes onto the remote.
+//! - [`Ssh::fetch`] — SFTP a remote file / directory back down.
 //! - [`Ssh::terminal`] — forward the current TTY to an interactive remote shell.
-//! - [`SshKeypair`] — create / load an ed25519 keypair on disk.
+//! - [`SshKeypair`] — create / load an ed25519 keypair on disk, or load a
+//!   user's own private key.
+//! - [`parse_public_key`] — read a server's host key, to pin a connection to.
 //!
-//! Note(cc): host key verification is disabled (`NoCheckHandler`) because lusid
-//! currently SSHs only into VMs it has just booted. If/when lusid grows into
-//! arbitrary remote machines, this must be revisited.
+//! An [`Ssh`] is one connection: each command, transfer or terminal opens its
+//! own channel on it, multiplexed by SSH, so keep one around rather than
+//! reconnecting per command.
+//!
+//! Note(cc): host keys are pinned rather than looked up in `known_hosts`:
+//! lusid generates the host keys of the VMs it boots (see `lusid_vm`), and a
+//! remote machine's host key is configured alongside its address. There's no
+//! trust-on-first-use or `known_hosts` support.
 
+mod batch;
 mod command;
 mod connect;
 mod keypair;
@@ -21,19 +33,21 @@ mod stream;
 mod sync;
 mod terminal;
 
-pub use crate::command::{SshCommandError, SshCommandHandle};
+pub use crate::batch::SshBatchError;
+pub use crate::command::{SshCommandError, SshCommandHandle, SshOutput};
 pub use crate::connect::{SshConnectError, SshConnectOptions};
-pub use crate::keypair::{SshKeypair, SshKeypairError};
+pub use crate::keypair::{SshKeypair, SshKeypairError, parse_public_key};
 pub use crate::sync::{SshSyncError, SshVolume};
 pub use crate::terminal::SshTerminalError;
 
+use std::path::Path;
 use thiserror::Error;
 use tokio::net::ToSocketAddrs;
 
 use crate::connect::connect_with_retry;
-use crate::session::{AsyncSession, NoCheckHandler};
+use crate::session::{AsyncSession, PinnedKeyHandler};
 
-type Session = AsyncSession<NoCheckHandler>;
+type Session = AsyncSession<PinnedKeyHandler>;
 
 #[derive(Error, Debug)]
 pub enum SshError {
@@ -43,6 +57,9 @@ pub enum SshError {
     #[error(transparent)]
     Command(#[from] SshCommandError),
 
+    #[error(transparent)]
+    Batch(#[from] SshBatchError),
+
     #[error(transparent)]
     Terminal(#[from] SshTerminalError),
 
@@ -83,6 +100,23 @@ impl Ssh {
             .map_err(SshError::Command)
     }
 
+    /// Execute a remote command to completion and collect its output.
+    #[tracing::instrument(skip(self))]
+    pub async fn output(&mut self, command: &str) -> Result<SshOutput, SshError> {
+        self.command(command).await?.output().await
+    }
+
+    /// Execute short remote commands in one round trip, collecting each one's
+    /// output in order. See [`batch`] for how they run.
+    #[tracing::instrument(skip(self))]
+    pub async fn output_batch(&mut self, commands: &[String]) -> Result<Vec<SshOutput>, SshError> {
+        if commands.is_empty() {
+            return Ok(Vec::new());
+        }
+        let output = self.output(&batch::helper_script(commands)).await?;
+        Ok(batch::batch_outputs(output, commands.len())?)
+    }
+
     /// Synchronize a volume (directory, file, or raw bytes) via SFTP.
     #[tracing::instrument(skip(self))]
     pub async fn sync(&mut self, volume: SshVolume) -> Result<(), SshError> {
@@ -91,6 +125,14 @@ impl Ssh {
             .map_err(SshError::Sync)
     }
 
+    /// Download a remote file, or a directory recursively, via SFTP.
+    #[tracing::instrument(skip(self))]
+    pub async fn fetch(&mut self, remote: &str, local: &Path) -> Result<(), SshError> {
+        sync::ssh_fetch(&self.session, remote, local)
+            .await
+            .map_err(SshError::Sync)
+    }
+
     /// Synchronize a volume (directory, file, or raw bytes) via SFTP.
     #[tracing::instrument(skip(self))]
     pub async fn terminal(&mut self) -> Result<Option<u32>, SshError> {
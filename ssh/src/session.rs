@@ -24,19 +24,28 @@ use tracing::Instrument;
 
 use crate::stream::ReadStream;
 
-/// A handler that does NOT check the server's public key.
-///
-/// Only use in controlled environments with public key authentication.
-pub struct NoCheckHandler;
+/// A handler that only accepts the server's public key if it matches the
+/// pinned host key. A mismatch fails the connection with
+/// [`SshError::UnknownKey`].
+pub struct PinnedKeyHandler {
+    host_key: ssh_key::PublicKey,
+}
+
+impl PinnedKeyHandler {
+    pub fn new(host_key: ssh_key::PublicKey) -> Self {
+        Self { host_key }
+    }
+}
 
-impl Handler for NoCheckHandler {
+impl Handler for PinnedKeyHandler {
     type Error = SshError;
 
     async fn check_server_key(
         &mut self,
-        _server_public_key: &ssh_key::PublicKey,
+        server_public_key: &ssh_key::PublicKey,
     ) -> Result<bool, Self::Error> {
-        Ok(true)
+        // Compare key material only; the comment isn't sent over the wire.
+        Ok(server_public_key.key_data() == self.host_key.key_data())
     }
 }
 
@@ -66,10 +75,8 @@ impl<H: 'static + Handler> AsyncSession<H> {
     }
 }
 
-impl AsyncSession<NoCheckHandler> {
-    /// Connect and authenticate with the given user and key_path via public key.
-    ///
-    /// Uses NoCheckHandler (skips host key verification).
+impl AsyncSession<PinnedKeyHandler> {
+    /// Authenticate with the given user and private key.
     pub async fn auth_publickey(
         &mut self,
         username: impl AsRef<str>,
@@ -16,7 +16,7 @@ use tracing::{debug, info, instrument, trace, warn};
 
 use lusid_fs::{self as fs, FsError};
 
-use crate::session::{AsyncSession, NoCheckHandler};
+use crate::session::{AsyncSession, PinnedKeyHandler};
 
 #[derive(Clone, PartialEq, Eq)]
 pub enum SshVolume {
@@ -80,7 +80,7 @@ pub enum SshSyncError {
 
 #[instrument(skip(session))]
 pub(super) async fn ssh_sync(
-    session: &AsyncSession<NoCheckHandler>,
+    session: &AsyncSession<PinnedKeyHandler>,
     volume: SshVolume,
 ) -> Result<(), SshSyncError> {
     info!("Starting SSH volume sync");
@@ -90,6 +90,24 @@ pub(super) async fn ssh_sync(
     Ok(())
 }
 
+/// Download a remote file, or a directory recursively, to `local`.
+#[instrument(skip(session))]
+pub(super) async fn ssh_fetch(
+    session: &AsyncSession<PinnedKeyHandler>,
+    remote: &str,
+    local: &Path,
+) -> Result<(), SshSyncError> {
+    info!("Starting SSH fetch");
+    let mut sftp = open_sftp(session).await?;
+    if sftp.metadata(remote).await?.is_dir() {
+        sftp_download_dir(&mut sftp, remote, local).await?;
+    } else {
+        sftp_download_file(&mut sftp, remote, local).await?;
+    }
+    info!("Fetch completed");
+    Ok(())
+}
+
 #[instrument(skip_all)]
 async fn open_sftp<H: Handler + 'static>(
     session: &AsyncSession<H>,
@@ -248,6 +266,71 @@ async fn sftp_upload_file_bytes(
     Ok(())
 }
 
+#[instrument(skip(sftp))]
+async fn sftp_download_dir(
+    sftp: &mut SftpSession,
+    remote_root: &str,
+    local_root: &Path,
+) -> Result<(), SshSyncError> {
+    let mut stack: Vec<(String, PathBuf)> = vec![(remote_root.to_owned(), local_root.to_owned())];
+    while let Some((remote_dir, local_dir)) = stack.pop() {
+        trace!(local = %local_dir.display(), "Ensuring local directory exists");
+        fs::create_dir(&local_dir).await?;
+
+        for entry in sftp.read_dir(&remote_dir).await? {
+            let name = entry.file_name();
+            if name == "." || name == ".." {
+                continue;
+            }
+            let remote_path = format!("{}/{}", remote_dir.trim_end_matches('/'), name);
+            let local_path = local_dir.join(&name);
+
+            let file_type = entry.file_type();
+            if file_type.is_symlink() {
+                warn!(path = %remote_path, "Skipping symlink");
+            } else if file_type.is_dir() {
+                stack.push((remote_path, local_path));
+            } else if file_type.is_file() {
+                sftp_download_file(sftp, &remote_path, &local_path).await?;
+            } else {
+                warn!(path = %remote_path, "Skipping special/unsupported file type");
+            }
+        }
+    }
+
+    debug!("Directory download completed");
+    Ok(())
+}
+
+#[instrument(skip(sftp))]
+async fn sftp_download_file(
+    sftp: &mut SftpSession,
+    remote: &str,
+    local: &Path,
+) -> Result<(), SshSyncError> {
+    #[allow(clippy::collapsible_if)]
+    if let Some(parent) = local.parent() {
+        if !parent.as_os_str().is_empty() {
+            trace!(parent = %parent.display(), "Ensuring local parent directory exists");
+            fs::create_dir(parent).await?;
+        }
+    }
+
+    let mut remote_file = sftp.open(remote).await?;
+    trace!("Opened remote file for reading");
+    let mut local_file = fs::create_file(local).await?;
+    tokio::io::copy(&mut remote_file, &mut local_file).await?;
+    local_file.flush().await?;
+    remote_file.shutdown().await?;
+
+    if let Some(permissions) = sftp.metadata(remote).await?.permissions {
+        fs::change_mode(local, permissions & 0o7777).await?;
+    }
+
+    debug!("File download completed");
+    Ok(())
+}
+
 #[instrument(skip(sftp))]
 async fn sftp_mkdirs(sftp: &mut SftpSession, remote_dir: &str) -> Result<(), SshSyncError> {
     let remote_dir = remote_dir.trim();
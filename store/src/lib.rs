@@ -4,10 +4,18 @@
 //! `LocalFile` (read straight off disk), but the shape is deliberately extensible:
 //! future backends could cover HTTP URLs, git blobs, or content-hashed blobs living
 //! in the XDG cache directory.
+//!
+//! Many lusid processes on a host share one cache dir, so whatever writes to it
+//! does so under a [`StoreLock`] (see [`lock`]).
 //
 // TODO(cc): the only backend today is a thin wrapper around `tokio::fs::read`. When
 // adding remote backends, wire up the `cache_dir` argument that `SubStore::new`
-// already receives (currently ignored by `LocalFileStore`).
+// already receives (currently ignored by `LocalFileStore`), and write entries
+// under a `StoreLock`, as `lusid_http::ArtifactCache` does.
+
+pub mod lock;
+
+pub use crate::lock::StoreLock;
 
 use async_trait::async_trait;
 use displaydoc::Display;
@@ -45,6 +53,8 @@ pub enum StoreItemId {
 pub enum StoreError {
     /// Local file store failed
     LocalFile(#[from] io::Error),
+    /// Failed to lock {path:?}: {source}
+    Lock { path: PathBuf, source: io::Error },
 }
 
 impl Store {
@@ -0,0 +1,70 @@
+//! Locks for lusid processes sharing one cache dir.
+//!
+//! A group apply runs a `lusid-apply` per machine, and every one of them on a
+//! host shares its cache. Whatever writes an entry there takes the entry's
+//! [`StoreLock`] first, so a second process waits for the first to finish the
+//! entry rather than writing over it, then finds it done.
+//!
+//! The lock is an advisory `flock(2)` on a `.lock` file next to the entry.
+//! The OS releases it when its holder exits, crashed or not, so a lock is
+//! never left stale. The lock file itself stays: removing it would race with
+//! a process about to lock it.
+
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use tracing::debug;
+
+use crate::StoreError;
+
+/// An exclusive lock on a cache entry, held until dropped.
+#[derive(Debug)]
+pub struct StoreLock {
+    path: PathBuf,
+    _file: File,
+}
+
+impl StoreLock {
+    /// Lock `path`, creating it and its parent dir if need be, and waiting
+    /// for whichever process holds it.
+    pub async fn acquire(path: impl Into<PathBuf>) -> Result<Self, StoreError> {
+        let path = path.into();
+        let lock_path = path.clone();
+        tokio::task::spawn_blocking(move || Self::acquire_blocking(lock_path))
+            .await
+            .map_err(|error| StoreError::Lock {
+                path,
+                source: io::Error::other(error),
+            })?
+    }
+
+    fn acquire_blocking(path: PathBuf) -> Result<Self, StoreError> {
+        let lock_error = |source| StoreError::Lock {
+            path: path.clone(),
+            source,
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(lock_error)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .map_err(lock_error)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                debug!(path = %path.display(), "waiting for another lusid process's lock");
+                file.lock().map_err(lock_error)?;
+            }
+            Err(TryLockError::Error(source)) => return Err(lock_error(source)),
+        }
+        Ok(Self { path, _file: file })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
@@ -5,8 +5,9 @@
 //! `rimu-interop` and exposed so plans can branch on hostname, OS distro, user,
 //! and so on.
 //!
-//! Detection is best-effort and `non_exhaustive` — new fields and OS variants
-//! can be added without a breaking change to the Rust API.
+//! Detection is best-effort. [`Os`] and [`Linux`] are exhaustive on purpose:
+//! a new OS variant is a compile error wherever lusid picks something per OS,
+//! like a package manager, rather than a silent default.
 
 mod arch;
 mod hardware;
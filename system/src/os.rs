@@ -1,10 +1,15 @@
 //! OS detection. On Linux we parse `/etc/os-release` via the `etc-os-release` crate
-//! and map the `ID` to a known distro variant (Ubuntu / Debian / Arch for now).
+//! and map the `ID` to a known distro variant (Ubuntu / Debian / Arch / Fedora /
+//! RHEL / Alpine for now, with RHEL's rebuilds counting as RHEL). On macOS we ask
+//! `sw_vers` for the product version.
 //!
 //! The serde shape uses nested internal tags: the outer `type: "linux"` discriminates
 //! [`Os`], and the inner `linux: "ubuntu"` discriminates [`Linux`]. Version fields
 //! are named after the distro (`ubuntu: "22.04"`, `debian: 12`) so the plan-facing
-//! YAML reads naturally.
+//! YAML reads naturally. macOS is `{ type: "macos", macos: "14.5" }`.
+//!
+//! [`Os::platforms`] names the platforms a machine counts as, which resources
+//! declare they run on (see `ResourceType::PLATFORMS` in `lusid-resource`).
 
 use etc_os_release::{Error as OsReleaseError, OsRelease};
 use serde::{Deserialize, Serialize, de};
@@ -18,16 +23,20 @@ use tokio::task::block_in_place;
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 #[serde(tag = "type")]
-#[non_exhaustive]
 pub enum Os {
     #[serde(rename = "linux")]
     Linux(Linux),
+    #[serde(rename = "macos")]
+    MacOs(MacOs),
 }
 
 #[derive(Error, Debug)]
 pub enum GetOsError {
     #[error("failed to get OS on Linux: {0}")]
     Linux(#[from] GetLinuxError),
+
+    #[error("failed to get OS on macOS: {0}")]
+    MacOs(#[from] GetMacOsError),
 }
 
 impl Os {
@@ -35,11 +44,32 @@ impl Os {
     pub async fn get() -> Result<Self, GetOsError> {
         Ok(Os::Linux(Linux::get().await?))
     }
+
+    #[cfg(target_os = "macos")]
+    pub async fn get() -> Result<Self, GetOsError> {
+        Ok(Os::MacOs(MacOs::get().await?))
+    }
+
+    /// The platforms this OS counts as: `linux` and the distro's id
+    /// (`ubuntu`, `debian`, `arch`, `fedora`, `rhel`, `alpine`) on Linux,
+    /// `macos` on macOS.
+    pub fn platforms(&self) -> Vec<&'static str> {
+        match self {
+            Os::Linux(linux) => vec!["linux", linux.id()],
+            Os::MacOs(_) => vec!["macos"],
+        }
+    }
+
+    /// Whether this OS counts as any of `platforms`.
+    pub fn is_any_of(&self, platforms: &[&str]) -> bool {
+        self.platforms()
+            .iter()
+            .any(|platform| platforms.contains(platform))
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 #[serde(tag = "linux")]
-#[non_exhaustive]
 pub enum Linux {
     #[serde(rename = "ubuntu")]
     Ubuntu {
@@ -54,6 +84,23 @@ pub enum Linux {
     },
     #[serde(rename = "arch")]
     Arch, // no version
+    #[serde(rename = "fedora")]
+    Fedora {
+        #[serde(rename = "fedora")]
+        version: u16,
+    },
+    /// Red Hat Enterprise Linux, or a rebuild of it: CentOS Stream, Rocky
+    /// Linux or AlmaLinux.
+    #[serde(rename = "rhel")]
+    Rhel {
+        #[serde(rename = "rhel")]
+        version: String,
+    },
+    #[serde(rename = "alpine")]
+    Alpine {
+        #[serde(rename = "alpine")]
+        version: String,
+    },
 }
 
 #[derive(Error, Debug)]
@@ -74,6 +121,13 @@ pub enum GetLinuxError {
         error: ParseIntError,
     },
 
+    #[error("invalid fedora version {version_id}: {error}")]
+    InvalidFedoraVersion {
+        version_id: String,
+        #[source]
+        error: ParseIntError,
+    },
+
     #[error("unknown linux distribution")]
     UnknownLinux { id: String },
 }
@@ -113,6 +167,34 @@ impl Linux {
                 Linux::Debian { version }
             }
             "arch" => Linux::Arch,
+            "fedora" => {
+                let Some(version_id) = version_id else {
+                    return Err(GetLinuxError::MissingVersionField);
+                };
+                let version = u16::from_str(version_id).map_err(|error| {
+                    GetLinuxError::InvalidFedoraVersion {
+                        version_id: version_id.to_owned(),
+                        error,
+                    }
+                })?;
+                Linux::Fedora { version }
+            }
+            "rhel" | "centos" | "rocky" | "almalinux" => {
+                let Some(version_id) = version_id else {
+                    return Err(GetLinuxError::MissingVersionField);
+                };
+                Linux::Rhel {
+                    version: version_id.to_owned(),
+                }
+            }
+            "alpine" => {
+                let Some(version_id) = version_id else {
+                    return Err(GetLinuxError::MissingVersionField);
+                };
+                Linux::Alpine {
+                    version: version_id.to_owned(),
+                }
+            }
             id => {
                 return Err(GetLinuxError::UnknownLinux { id: id.to_owned() });
             }
@@ -121,12 +203,68 @@ impl Linux {
     }
 }
 
+impl Linux {
+    /// The distro's `/etc/os-release` `ID`.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Linux::Ubuntu { .. } => "ubuntu",
+            Linux::Debian { .. } => "debian",
+            Linux::Arch => "arch",
+            Linux::Fedora { .. } => "fedora",
+            Linux::Rhel { .. } => "rhel",
+            Linux::Alpine { .. } => "alpine",
+        }
+    }
+}
+
+/// macOS, by product version (`14.5`, `15.0`, ...).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct MacOs {
+    #[serde(rename = "macos")]
+    pub version: String,
+}
+
+#[derive(Error, Debug)]
+pub enum GetMacOsError {
+    #[error("failed to run sw_vers: {0}")]
+    SwVers(#[source] std::io::Error),
+
+    #[error("sw_vers failed: {stderr}")]
+    SwVersFailed { stderr: String },
+
+    #[error("sw_vers printed no product version")]
+    MissingVersion,
+}
+
+impl MacOs {
+    pub async fn get() -> Result<Self, GetMacOsError> {
+        let output = tokio::process::Command::new("sw_vers")
+            .arg("-productVersion")
+            .output()
+            .await
+            .map_err(GetMacOsError::SwVers)?;
+        if !output.status.success() {
+            return Err(GetMacOsError::SwVersFailed {
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+            });
+        }
+        let version = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+        if version.is_empty() {
+            return Err(GetMacOsError::MissingVersion);
+        }
+        Ok(MacOs { version })
+    }
+}
+
 impl Display for Linux {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             Linux::Ubuntu { version } => write!(f, "ubuntu-{}", version),
             Linux::Debian { version } => write!(f, "debian-{}", version),
             Linux::Arch => write!(f, "arch"),
+            Linux::Fedora { version } => write!(f, "fedora-{}", version),
+            Linux::Rhel { version } => write!(f, "rhel-{}", version),
+            Linux::Alpine { version } => write!(f, "alpine-{}", version),
         }
     }
 }
@@ -135,6 +273,7 @@ impl Display for Os {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             Os::Linux(l) => write!(f, "linux-{}", l),
+            Os::MacOs(MacOs { version }) => write!(f, "macos-{}", version),
         }
     }
 }
@@ -218,4 +357,54 @@ mod tests {
         let os: Os = from_str(j).unwrap();
         assert_eq!(os.to_string(), "linux-arch");
     }
+
+    #[test]
+    fn fedora_and_rhel() {
+        let j = r#"{
+            "type": "linux",
+            "linux": "fedora",
+            "fedora": 40
+        }"#;
+        let os: Os = from_str(j).unwrap();
+        assert_eq!(os.to_string(), "linux-fedora-40");
+        assert_eq!(os.platforms(), vec!["linux", "fedora"]);
+
+        let os = Os::Linux(Linux::Rhel {
+            version: "9.4".into(),
+        });
+        assert_eq!(os.to_string(), "linux-rhel-9.4");
+        assert!(os.is_any_of(&["fedora", "rhel"]));
+    }
+
+    #[test]
+    fn alpine() {
+        let j = r#"{
+            "type": "linux",
+            "linux": "alpine",
+            "alpine": "3.20.1"
+        }"#;
+        let os: Os = from_str(j).unwrap();
+        assert_eq!(os.to_string(), "linux-alpine-3.20.1");
+        assert_eq!(os.platforms(), vec!["linux", "alpine"]);
+    }
+
+    #[test]
+    fn macos_version() {
+        let j = r#"{
+            "type": "macos",
+            "macos": "14.5"
+        }"#;
+        let os: Os = from_str(j).unwrap();
+        assert_eq!(os.to_string(), "macos-14.5");
+        assert_eq!(os.platforms(), vec!["macos"]);
+        assert!(!os.is_any_of(&["linux"]));
+    }
+
+    #[test]
+    fn linux_platforms() {
+        let os = Os::Linux(Linux::Debian { version: 12 });
+        assert_eq!(os.platforms(), vec!["linux", "debian"]);
+        assert!(os.is_any_of(&["debian", "ubuntu"]));
+        assert!(!os.is_any_of(&["arch", "macos"]));
+    }
 }
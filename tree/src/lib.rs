@@ -12,6 +12,8 @@
 //!
 //! The async `map` family on `FlatTree` accept `write_start`/`write_update` callbacks,
 //! which is how the streaming TUI protocol gets progress updates during tree transformations.
+//! `write_update` borrows each new node (or subtree) before it's stored, so rendering an
+//! update costs no clone of the node itself.
 //!
 //! # FlatTree invariants
 //!
@@ -27,10 +29,18 @@
 //! - `Tree → FlatTree`: root lands at index 0.
 //! - `FlatTree → Tree`: lenient — missing children are skipped; if the root itself is
 //!   missing, returns an empty `Branch` with `Meta::default()`.
+//!
+//! # Testing
+//!
+//! With the `proptest` feature, [`strategy`] provides random `Tree` / `FlatTree`
+//! strategies (including arenas with holes) and checkers for the invariants above.
 
 use std::future::Future;
 use thiserror::Error;
 
+#[cfg(feature = "proptest")]
+pub mod strategy;
+
 /// Recursive nested tree. Either a `Branch` with children or a `Leaf` with a value,
 /// each carrying a `Meta` payload.
 #[derive(Debug, Clone)]
@@ -263,7 +273,7 @@ where
     where
         NextNode: Clone,
         MapFn: Fn(Node) -> NextNode + Copy,
-        WriteUpdateFn: Fn(usize, NextNode) -> WriteUpdateFut,
+        WriteUpdateFn: Fn(usize, &NextNode) -> WriteUpdateFut,
         WriteUpdateFut: Future<Output = Result<(), Error>>,
     {
         let mut next_nodes = vec![None; self.nodes.len()];
@@ -275,11 +285,11 @@ where
                 }
                 Some(FlatTreeNode::Leaf { meta, node }) => {
                     let next_node = map(node);
+                    write_update(index, &next_node).await?;
                     next_nodes[index] = Some(FlatTreeNode::Leaf {
                         meta,
-                        node: next_node.clone(),
+                        node: next_node,
                     });
-                    write_update(index, next_node).await?;
                 }
             }
         }
@@ -297,7 +307,7 @@ where
     where
         NextNode: Clone,
         MapFn: Fn(Node) -> Option<NextNode> + Copy,
-        WriteUpdateFn: Fn(usize, Option<NextNode>) -> WriteUpdateFut,
+        WriteUpdateFn: Fn(usize, Option<&NextNode>) -> WriteUpdateFut,
         WriteUpdateFut: Future<Output = Result<(), Error>>,
     {
         let mut next_nodes = vec![None; self.nodes.len()];
@@ -309,10 +319,8 @@ where
                 }
                 Some(FlatTreeNode::Leaf { meta, node }) => {
                     let next_node = map(node);
-                    next_nodes[index] = next_node
-                        .clone()
-                        .map(|node| FlatTreeNode::Leaf { meta, node });
-                    write_update(index, next_node).await?;
+                    write_update(index, next_node.as_ref()).await?;
+                    next_nodes[index] = next_node.map(|node| FlatTreeNode::Leaf { meta, node });
                 }
             }
         }
@@ -353,7 +361,7 @@ where
         NextNode: Clone,
         MapFn: Fn(Node, Meta) -> Tree<NextNode, Meta> + Copy,
         WriteFut: Future<Output = Result<(), Error>>,
-        WriteUpdateFn: Fn(usize, Tree<NextNode, Meta>) -> WriteFut,
+        WriteUpdateFn: Fn(usize, &Tree<NextNode, Meta>) -> WriteFut,
     {
         let mut next_nodes = vec![None; self.nodes.len()];
         for (index, node) in self.nodes.into_iter().enumerate() {
@@ -364,8 +372,8 @@ where
                 }
                 Some(FlatTreeNode::Leaf { meta, node }) => {
                     let next_tree = map(node, meta);
-                    replace_tree_nodes(&mut next_nodes, Some(next_tree.clone()), index);
-                    write_update(index, next_tree).await?;
+                    write_update(index, &next_tree).await?;
+                    replace_tree_nodes(&mut next_nodes, Some(next_tree), index);
                 }
             }
         }
@@ -397,7 +405,7 @@ where
         Fut: Future<Output = Result<NextNode, Error>>,
         WriteStartFn: FnMut(usize) -> WriteStartFut,
         WriteStartFut: Future<Output = Result<(), Error>>,
-        WriteUpdateFn: FnMut(usize, NextNode) -> WriteUpdateFut,
+        WriteUpdateFn: FnMut(usize, &NextNode) -> WriteUpdateFut,
         WriteUpdateFut: Future<Output = Result<(), Error>>,
     {
         let mut next_nodes = vec![None; self.nodes.len()];
@@ -410,17 +418,84 @@ where
                 Some(FlatTreeNode::Leaf { meta, node }) => {
                     write_start(index).await?;
                     let next_node = map(node).await?;
+                    write_update(index, &next_node).await?;
                     next_nodes[index] = Some(FlatTreeNode::Leaf {
                         meta,
-                        node: next_node.clone(),
+                        node: next_node,
                     });
-                    write_update(index, next_node).await?;
                 }
             }
         }
         Ok(FlatTree { nodes: next_nodes })
     }
 
+    /// Async, fallible transform of every leaf in one call, for leaves that
+    /// are cheaper to map together than one by one.
+    ///
+    /// `map` gets the leaves in index order and must return one node per
+    /// leaf, in the same order. `write_start` fires for every leaf before
+    /// `map` runs; `write_update` for each after it resolves.
+    ///
+    /// # Panics
+    ///
+    /// If `map` returns a different number of nodes than it was given.
+    pub async fn map_batch_result_async<
+        NextNode,
+        Error,
+        MapFn,
+        Fut,
+        WriteStartFn,
+        WriteStartFut,
+        WriteUpdateFn,
+        WriteUpdateFut,
+    >(
+        self,
+        map: MapFn,
+        mut write_start: WriteStartFn,
+        mut write_update: WriteUpdateFn,
+    ) -> Result<FlatTree<NextNode, Meta>, Error>
+    where
+        NextNode: Clone,
+        MapFn: FnOnce(Vec<Node>) -> Fut,
+        Fut: Future<Output = Result<Vec<NextNode>, Error>>,
+        WriteStartFn: FnMut(usize) -> WriteStartFut,
+        WriteStartFut: Future<Output = Result<(), Error>>,
+        WriteUpdateFn: FnMut(usize, &NextNode) -> WriteUpdateFut,
+        WriteUpdateFut: Future<Output = Result<(), Error>>,
+    {
+        let mut next_nodes = vec![None; self.nodes.len()];
+        let mut leaves = Vec::new();
+        let mut nodes = Vec::new();
+        for (index, node) in self.nodes.into_iter().enumerate() {
+            match node {
+                None => {}
+                Some(FlatTreeNode::Branch { meta, children }) => {
+                    next_nodes[index] = Some(FlatTreeNode::Branch { meta, children })
+                }
+                Some(FlatTreeNode::Leaf { meta, node }) => {
+                    write_start(index).await?;
+                    leaves.push((index, meta));
+                    nodes.push(node);
+                }
+            }
+        }
+
+        let mapped = map(nodes).await?;
+        assert_eq!(
+            mapped.len(),
+            leaves.len(),
+            "map_batch_result_async: map must return one node per leaf"
+        );
+        for ((index, meta), next_node) in leaves.into_iter().zip(mapped) {
+            write_update(index, &next_node).await?;
+            next_nodes[index] = Some(FlatTreeNode::Leaf {
+                meta,
+                node: next_node,
+            });
+        }
+        Ok(FlatTree { nodes: next_nodes })
+    }
+
     /// Async, fallible subtree-expansion variant of [`map_tree`](Self::map_tree).
     /// Each leaf can asynchronously produce a full subtree.
     pub async fn map_tree_result_async<
@@ -443,7 +518,7 @@ where
         Fut: Future<Output = Result<Tree<NextNode, Meta>, Error>>,
         WriteFut: Future<Output = Result<(), Error>>,
         WriteStartFn: Fn(usize) -> WriteFut,
-        WriteUpdateFn: Fn(usize, Tree<NextNode, Meta>) -> WriteFut,
+        WriteUpdateFn: Fn(usize, &Tree<NextNode, Meta>) -> WriteFut,
     {
         let mut next_nodes = vec![None; self.nodes.len()];
         for (index, node) in self.nodes.into_iter().enumerate() {
@@ -455,8 +530,8 @@ where
                 Some(FlatTreeNode::Leaf { meta, node }) => {
                     write_start(index).await?;
                     let next_tree = map(node, meta).await?;
-                    replace_tree_nodes(&mut next_nodes, Some(next_tree.clone()), index);
-                    write_update(index, next_tree).await?;
+                    write_update(index, &next_tree).await?;
+                    replace_tree_nodes(&mut next_nodes, Some(next_tree), index);
                 }
             }
         }
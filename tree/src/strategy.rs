@@ -0,0 +1,240 @@
+//! [`proptest`] strategies for [`Tree`] / [`FlatTree`] plus invariant
+//! checkers, for fuzzing this crate and anything built on it.
+//!
+//! Enabled by the `proptest` feature. Downstream crates pull it in as a
+//! dev-dependency feature and combine the strategies with their own node /
+//! meta strategies:
+//!
+//! ```ignore
+//! proptest! {
+//!     #[test]
+//!     fn round_trips(flat in arb_flat_tree(any::<u8>(), any::<u8>())) {
+//!         check_lenient_round_trip(&flat)?;
+//!     }
+//! }
+//! ```
+//!
+//! [`arb_flat_tree`] deliberately generates arenas with holes (tombstoned
+//! slots, including the root), since those are exactly the states lenient
+//! reconstruction must tolerate.
+
+use std::fmt::Debug;
+
+use proptest::{collection::vec, prelude::*, sample::Index};
+
+use crate::{FlatTree, FlatTreeNode, Tree};
+
+/// Random nested trees, up to 4 levels deep with up to 6 children per branch.
+/// The root may be a leaf.
+pub fn arb_tree<Node, Meta>(
+    node: impl Strategy<Value = Node> + Clone + 'static,
+    meta: impl Strategy<Value = Meta> + Clone + 'static,
+) -> impl Strategy<Value = Tree<Node, Meta>>
+where
+    Node: Debug + Clone + 'static,
+    Meta: Debug + Clone + 'static,
+{
+    let leaf = (meta.clone(), node).prop_map(|(meta, node)| Tree::Leaf { meta, node });
+    leaf.prop_recursive(4, 64, 6, move |inner| {
+        (meta.clone(), vec(inner, 0..6))
+            .prop_map(|(meta, children)| Tree::Branch { meta, children })
+    })
+}
+
+/// Random flat trees built from [`arb_tree`], then damaged by tombstoning up
+/// to 3 random slots (possibly the root).
+///
+/// Note(cc): this doesn't generate out-of-bounds child indices, even though
+/// lenient reconstruction tolerates them: a later `replace_tree` appends new
+/// nodes at the end of the arena, so a dangling index can come to alias an
+/// unrelated node. The crate never produces such indices itself (tombstoned
+/// slots are never reused), so they'd only report that known hazard.
+pub fn arb_flat_tree<Node, Meta>(
+    node: impl Strategy<Value = Node> + Clone + 'static,
+    meta: impl Strategy<Value = Meta> + Clone + 'static,
+) -> impl Strategy<Value = FlatTree<Node, Meta>>
+where
+    Node: Debug + Clone + 'static,
+    Meta: Debug + Clone + 'static,
+{
+    (arb_tree(node, meta), vec(any::<Index>(), 0..4)).prop_map(|(tree, holes)| {
+        let mut flat = FlatTree::from(tree);
+        let len = flat.nodes.len();
+        for hole in holes {
+            flat.nodes[hole.index(len)] = None;
+        }
+        flat
+    })
+}
+
+/// `Tree → FlatTree → Tree` is the identity on any nested tree.
+pub fn check_tree_round_trip<Node, Meta>(tree: &Tree<Node, Meta>) -> Result<(), TestCaseError>
+where
+    Node: Debug + Clone + PartialEq,
+    Meta: Debug + Clone + PartialEq + Default,
+{
+    let round_tripped = Tree::from(FlatTree::from(tree.clone()));
+    prop_assert_eq!(shape(&round_tripped), shape(tree));
+    Ok(())
+}
+
+/// Lenient `FlatTree → Tree` is stable: converting the result back to a flat
+/// tree and rebuilding yields the same tree, and rebuilding never invents
+/// leaves that weren't in the arena.
+pub fn check_lenient_round_trip<Node, Meta>(
+    flat: &FlatTree<Node, Meta>,
+) -> Result<(), TestCaseError>
+where
+    Node: Debug + Clone + PartialEq,
+    Meta: Debug + Clone + PartialEq + Default,
+{
+    let once = Tree::from(flat.clone());
+    let twice = Tree::from(FlatTree::from(once.clone()));
+    prop_assert_eq!(shape(&twice), shape(&once));
+
+    let rebuilt_leaves = shape(&once)
+        .iter()
+        .filter(|(_, node, _)| node.is_some())
+        .count();
+    prop_assert!(rebuilt_leaves <= flat.leaves().count());
+    Ok(())
+}
+
+/// Replacing a reachable subtree (picked by `target` among reachable nodes)
+/// and then rebuilding puts exactly `replacement` at that node's position,
+/// leaving its ancestors' paths intact.
+pub fn check_replace_then_build<Node, Meta>(
+    flat: &FlatTree<Node, Meta>,
+    target: Index,
+    replacement: Tree<Node, Meta>,
+) -> Result<(), TestCaseError>
+where
+    Node: Debug + Clone + PartialEq,
+    Meta: Debug + Clone + PartialEq + Default,
+{
+    let reachable = flat.depth_first_search();
+    if reachable.is_empty() {
+        return Ok(());
+    }
+    let index = reachable[target.index(reachable.len())];
+    let path = path_to(flat, index);
+    prop_assert!(path.is_some(), "reachable node {} has no path", index);
+    let path = path.unwrap_or_default();
+
+    let mut replaced = flat.clone();
+    replaced.replace_tree(Some(replacement.clone()), index);
+    let rebuilt = Tree::from(replaced);
+
+    let subtree = follow(&rebuilt, &path);
+    prop_assert!(subtree.is_some(), "path {:?} missing after replace", path);
+    if let Some(subtree) = subtree {
+        prop_assert_eq!(shape(subtree), shape(&replacement));
+    }
+    Ok(())
+}
+
+/// Pre-order `(depth, leaf node, meta)` listing, for structural comparison
+/// without requiring `PartialEq` on `Tree` itself.
+fn shape<Node, Meta>(tree: &Tree<Node, Meta>) -> Vec<(usize, Option<&Node>, &Meta)> {
+    fn walk<'a, Node, Meta>(
+        tree: &'a Tree<Node, Meta>,
+        depth: usize,
+        out: &mut Vec<(usize, Option<&'a Node>, &'a Meta)>,
+    ) {
+        match tree {
+            Tree::Branch { meta, children } => {
+                out.push((depth, None, meta));
+                for child in children {
+                    walk(child, depth + 1, out);
+                }
+            }
+            Tree::Leaf { meta, node } => out.push((depth, Some(node), meta)),
+        }
+    }
+
+    let mut out = Vec::new();
+    walk(tree, 0, &mut out);
+    out
+}
+
+/// Child positions from the root to `index`, counting only children that
+/// lenient reconstruction keeps (in bounds and not tombstoned).
+fn path_to<Node, Meta>(flat: &FlatTree<Node, Meta>, index: usize) -> Option<Vec<usize>>
+where
+    Node: Clone,
+    Meta: Clone,
+{
+    fn search<Node, Meta>(
+        nodes: &[Option<FlatTreeNode<Node, Meta>>],
+        current: usize,
+        target: usize,
+        path: &mut Vec<usize>,
+    ) -> bool {
+        if current == target {
+            return true;
+        }
+        let Some(Some(FlatTreeNode::Branch { children, .. })) = nodes.get(current) else {
+            return false;
+        };
+        let present = children
+            .iter()
+            .copied()
+            .filter(|&child| matches!(nodes.get(child), Some(Some(_))));
+        for (position, child) in present.enumerate() {
+            path.push(position);
+            if search(nodes, child, target, path) {
+                return true;
+            }
+            path.pop();
+        }
+        false
+    }
+
+    let mut path = Vec::new();
+    search(
+        &flat.nodes,
+        FlatTree::<Node, Meta>::root_index(),
+        index,
+        &mut path,
+    )
+    .then_some(path)
+}
+
+fn follow<'a, Node, Meta>(
+    tree: &'a Tree<Node, Meta>,
+    path: &[usize],
+) -> Option<&'a Tree<Node, Meta>> {
+    match path.split_first() {
+        None => Some(tree),
+        Some((&position, rest)) => match tree {
+            Tree::Branch { children, .. } => follow(children.get(position)?, rest),
+            Tree::Leaf { .. } => None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn tree_round_trip(tree in arb_tree(any::<u8>(), any::<u8>())) {
+            check_tree_round_trip(&tree)?;
+        }
+
+        #[test]
+        fn lenient_round_trip(flat in arb_flat_tree(any::<u8>(), any::<u8>())) {
+            check_lenient_round_trip(&flat)?;
+        }
+
+        #[test]
+        fn replace_then_build(
+            flat in arb_flat_tree(any::<u8>(), any::<u8>()),
+            target in any::<Index>(),
+            replacement in arb_tree(any::<u8>(), any::<u8>()),
+        ) {
+            check_replace_then_build(&flat, target, replacement)?;
+        }
+    }
+}
@@ -17,15 +17,23 @@
 //! and render them. Styling metadata ([`TextStyle`], [`Color`], [`Alignment`])
 //! travels with the view.
 //!
-//! Note(cc): the current TUI ([`lusid/src/tui.rs`]) uses ratatui's own styling
-//! and only consumes the text content of views (via `Display`). The style
-//! fields here are intentional overhead for a future renderer that honours
-//! them, and for non-TUI consumers.
+//! With the `testing` feature, `Snapshot` checks rendered text against
+//! golden files so `Display` / `Render` regressions fail a test.
+//!
+//! Note(cc): the current TUI ([`lusid/src/tui.rs`]) honours span and line
+//! styles in its tree rows (e.g. the colour of a change's severity), but
+//! renders paragraphs and operation output as plain text and ignores
+//! [`Alignment`]. The rest is for a fuller renderer, and for non-TUI
+//! consumers.
 
 mod render;
+#[cfg(feature = "testing")]
+mod snapshot;
 mod tree;
 mod view;
 
 pub use crate::render::*;
+#[cfg(feature = "testing")]
+pub use crate::snapshot::*;
 pub use crate::tree::*;
 pub use crate::view::*;
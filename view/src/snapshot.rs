@@ -0,0 +1,133 @@
+//! Golden-file snapshots of rendered views.
+//!
+//! `Display` / [`Render`] impls are what the TUI shows for every resource,
+//! state, change and operation, and nothing else checks them. A [`Snapshot`]
+//! collects the rendered text of a set of values and compares it against a
+//! checked-in file, so a formatting change fails a test and shows up as a
+//! reviewable diff.
+//!
+//! Snapshot files are plain text: one rendered value per line, grouped under
+//! `# title` headings. To accept a change, re-run the tests with
+//! `LUSID_UPDATE_SNAPSHOTS=1` and commit the rewritten files.
+//!
+//! Enabled by the `testing` feature.
+
+use std::{fmt::Write as _, fs, path::Path};
+
+use crate::Render;
+
+/// Set to any non-empty value to rewrite snapshot files instead of comparing.
+pub const UPDATE_SNAPSHOTS_ENV: &str = "LUSID_UPDATE_SNAPSHOTS";
+
+#[derive(Debug, Default, Clone)]
+pub struct Snapshot {
+    text: String,
+}
+
+impl Snapshot {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a `# title` group. Groups are separated by a blank line.
+    pub fn section(&mut self, title: &str) -> &mut Self {
+        if !self.text.is_empty() {
+            self.text.push('\n');
+        }
+        let _ = writeln!(self.text, "# {title}");
+        self
+    }
+
+    /// Append the rendered text of `value`.
+    pub fn render(&mut self, value: &impl Render) -> &mut Self {
+        let _ = writeln!(self.text, "{}", value.render());
+        self
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Compare against the snapshot file at `path`, or rewrite it when
+    /// [`UPDATE_SNAPSHOTS_ENV`] is set.
+    ///
+    /// Panics with a line diff on mismatch, or if the file is missing.
+    #[track_caller]
+    pub fn assert_matches(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+
+        if std::env::var_os(UPDATE_SNAPSHOTS_ENV).is_some_and(|value| !value.is_empty()) {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).expect("create snapshot directory");
+            }
+            fs::write(path, &self.text).expect("write snapshot");
+            return;
+        }
+
+        let expected = match fs::read_to_string(path) {
+            Ok(expected) => expected,
+            Err(err) => panic!(
+                "failed to read snapshot {}: {err}\n\
+                 re-run with {UPDATE_SNAPSHOTS_ENV}=1 to create it",
+                path.display()
+            ),
+        };
+
+        if expected != self.text {
+            panic!(
+                "snapshot {} does not match rendered output:\n{}\n\
+                 re-run with {UPDATE_SNAPSHOTS_ENV}=1 to accept the new output",
+                path.display(),
+                line_diff(&expected, &self.text)
+            );
+        }
+    }
+}
+
+/// Minimal `-expected` / `+actual` listing of the lines that differ, by
+/// position. Snapshots are small and append-mostly, so this is enough to spot
+/// the change without pulling in a diff crate.
+fn line_diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    let mut out = String::new();
+    for index in 0..expected.len().max(actual.len()) {
+        let (old, new) = (expected.get(index), actual.get(index));
+        if old == new {
+            continue;
+        }
+        let _ = writeln!(out, "@@ line {} @@", index + 1);
+        if let Some(old) = old {
+            let _ = writeln!(out, "-{old}");
+        }
+        if let Some(new) = new {
+            let _ = writeln!(out, "+{new}");
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections_and_lines() {
+        let mut snapshot = Snapshot::new();
+        snapshot
+            .section("first")
+            .render(&"a")
+            .render(&"b")
+            .section("second")
+            .render(&"c");
+        assert_eq!(snapshot.as_str(), "# first\na\nb\n\n# second\nc\n");
+    }
+
+    #[test]
+    fn diff_lists_changed_and_extra_lines() {
+        assert_eq!(
+            line_diff("a\nb\n", "a\nc\nd\n"),
+            "@@ line 2 @@\n-b\n+c\n@@ line 3 @@\n+d\n"
+        );
+    }
+}
@@ -16,7 +16,17 @@ pub enum ViewTree {
 
 impl Display for ViewTree {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        TermTree::<View>::from(self.clone()).fmt(f)
+        term_tree(self).fmt(f)
+    }
+}
+
+/// Borrow `tree` as a [`TermTree`], so printing it clones no views.
+fn term_tree(tree: &ViewTree) -> TermTree<&View> {
+    match tree {
+        ViewTree::Branch { view, children } => {
+            TermTree::new(view).with_leaves(children.iter().map(term_tree))
+        }
+        ViewTree::Leaf { view } => TermTree::new(view),
     }
 }
 
@@ -1,20 +1,24 @@
 use serde::{Deserialize, Serialize};
-use std::fmt::Display;
+use std::{fmt::Display, sync::Arc};
 
 use crate::TextStyle;
 
 /// A run of text with uniform styling. The atomic unit of the view system:
 /// [`Line`](crate::Line)s are `Vec<Span>`, so a line can mix colours and
 /// weights without nesting.
+///
+/// `content` is shared, so cloning a view (as the TUI does when it keeps a
+/// completed view, or templates one phase's tree for the next) copies no
+/// text. It serializes as a plain string.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Span {
-    pub content: String,
+    pub content: Arc<str>,
     pub style: TextStyle,
 }
 
 impl Span {
     /// Create a new Span with given content and default style.
-    pub fn new<T: Into<String>>(content: T) -> Self {
+    pub fn new<T: Into<Arc<str>>>(content: T) -> Self {
         Self {
             content: content.into(),
             ..Default::default()
@@ -22,7 +26,7 @@ impl Span {
     }
 
     /// Create a new Span with given content and style.
-    pub fn new_styled<T: Into<String>>(content: T, style: TextStyle) -> Self {
+    pub fn new_styled<T: Into<Arc<str>>>(content: T, style: TextStyle) -> Self {
         Self {
             content: content.into(),
             style,
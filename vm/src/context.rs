@@ -2,7 +2,7 @@ use lusid_ctx::{Context as BaseContext, ContextError as BaseContextError};
 use thiserror::Error;
 
 use crate::paths::{ExecutablePaths, ExecutablePathsError, Paths};
-use lusid_http::{HttpClient, HttpError};
+use lusid_http::{ArtifactCache, HttpClient, HttpError};
 
 #[derive(Error, Debug)]
 pub enum ContextError {
@@ -17,11 +17,13 @@ pub enum ContextError {
 }
 
 /// VM-crate-internal context: the pieces of the base [`BaseContext`] that the
-/// VM pipeline touches (HTTP for image downloads, filesystem paths for
-/// images/instances, resolved executable paths for qemu/virt-get-kernel/etc.).
+/// VM pipeline touches (HTTP and the artifact cache for image downloads,
+/// filesystem paths for images/instances, resolved executable paths for
+/// qemu/virt-get-kernel/etc.).
 #[derive(Debug, Clone)]
 pub struct Context {
     http_client: HttpClient,
+    artifacts: ArtifactCache,
     paths: Paths,
     executables: ExecutablePaths,
 }
@@ -29,10 +31,12 @@ pub struct Context {
 impl Context {
     pub fn create(base: &mut BaseContext) -> Result<Self, ContextError> {
         let http_client = base.http_client().clone();
+        let artifacts = base.artifact_cache().clone();
         let paths = Paths::new(base.paths().clone());
         let executables = ExecutablePaths::new()?;
         Ok(Self {
             http_client,
+            artifacts,
             paths,
             executables,
         })
@@ -42,6 +46,10 @@ impl Context {
         &mut self.http_client
     }
 
+    pub fn artifact_cache(&self) -> &ArtifactCache {
+        &self.artifacts
+    }
+
     pub fn paths(&self) -> &Paths {
         &self.paths
     }
@@ -1,8 +1,7 @@
 use lusid_fs::{self as fs, FsError};
-use sha2::Digest;
+use lusid_http::Checksum;
 use std::path::Path;
 use thiserror::Error;
-use tokio::io::AsyncReadExt;
 
 use crate::image::index::{VmImageHashRef, VmImageIndex};
 
@@ -19,13 +18,6 @@ pub enum VmImageHashError {
 
     #[error("malformed sha512sums line {line_index}: '{line}'")]
     MalformedLine { line_index: usize, line: String },
-
-    #[error("sha512 mismatch for '{name}': expected {expected}, actual {actual}")]
-    HashMismatch {
-        name: String,
-        expected: String,
-        actual: String,
-    },
 }
 
 #[derive(Debug, Clone)]
@@ -41,29 +33,15 @@ impl<'a> VmImageHash<'a> {
             VmImageHashRef::Sha256Sums { url: _ } => VmImageHash::Sha256Sums { path },
         }
     }
-    pub async fn validate(
-        &self,
-        image_index: &VmImageIndex,
-        image_path: &Path,
-    ) -> Result<(), VmImageHashError> {
-        match self {
-            VmImageHash::Sha512Sums { path: hash_path } => {
-                self.validate_hash::<Sha512>(image_index, image_path, hash_path)
-                    .await
-            }
-            VmImageHash::Sha256Sums { path: hash_path } => {
-                self.validate_hash::<Sha256>(image_index, image_path, hash_path)
-                    .await
-            }
-        }
-    }
 
-    pub async fn validate_hash<H: HashType>(
-        &self,
-        image_index: &VmImageIndex,
-        image_path: &Path,
-        hash_path: &Path,
-    ) -> Result<(), VmImageHashError> {
+    /// The digest the sums file lists for the image, as hex. The image
+    /// itself is checked against it as it's fetched into the artifact cache
+    /// (see [`VmImageHash::checksum`]).
+    pub async fn expected(&self, image_index: &VmImageIndex) -> Result<String, VmImageHashError> {
+        let (hash_path, hex_length) = match self {
+            VmImageHash::Sha512Sums { path } => (path, 128),
+            VmImageHash::Sha256Sums { path } => (path, 64),
+        };
         let sums = fs::read_file_to_string(hash_path).await?;
 
         // Resolve the target name we need to look up in sums
@@ -74,77 +52,18 @@ impl<'a> VmImageHash<'a> {
             }
         })?;
 
-        let expected = lookup_sum(&sums, image_name, H::hex_length())?;
-
-        let actual = H::file_to_hex(image_path).await?;
-
-        // Compare (case-insensitive to be safe)
-        if expected.eq_ignore_ascii_case(&actual) {
-            Ok(())
-        } else {
-            Err(VmImageHashError::HashMismatch {
-                name: image_name.to_string(),
-                expected,
-                actual,
-            })
-        }
+        lookup_sum(&sums, image_name, hex_length)
     }
-}
-
-pub trait HashType {
-    fn hex_length() -> usize;
 
-    async fn file_to_hex<P: AsRef<Path>>(path: P) -> Result<String, FsError>;
-}
-
-pub trait Sha2HashType {
-    type Digest: Digest;
-}
-
-impl<H: Sha2HashType> HashType for H {
-    fn hex_length() -> usize {
-        H::Digest::output_size() * 2
-    }
-
-    async fn file_to_hex<P: AsRef<Path>>(path: P) -> Result<String, FsError> {
-        let p = path.as_ref();
-        let mut file = fs::open_file(p).await?;
-        let mut hasher = H::Digest::new();
-        let mut buf = [0u8; 8192];
-
-        loop {
-            let n = file
-                .read(&mut buf)
-                .await
-                .map_err(|source| FsError::ReadFile {
-                    path: p.to_path_buf(),
-                    source,
-                })?;
-            if n == 0 {
-                break;
-            }
-            hasher.update(&buf[..n]);
-        }
-
-        let digest = hasher.finalize();
-        let mut hex = String::with_capacity(digest.len() * 2);
-        for b in digest {
-            hex.push_str(&format!("{:02x}", b));
+    /// The [`Checksum`] an `expected` digest pins the image to.
+    pub fn checksum<'h>(&self, expected: &'h str) -> Checksum<'h> {
+        match self {
+            VmImageHash::Sha512Sums { path: _ } => Checksum::Sha512(expected),
+            VmImageHash::Sha256Sums { path: _ } => Checksum::Sha256(expected),
         }
-        Ok(hex)
     }
 }
 
-pub struct Sha256;
-impl Sha2HashType for Sha256 {
-    type Digest = sha2::Sha256;
-}
-
-pub struct Sha512;
-impl Sha2HashType for Sha512 {
-    type Digest = sha2::Sha512;
-}
-
 /// Parse the contents of a SHASUMS file and return the hash that
 /// corresponds to `image_name`.
 ///
@@ -13,12 +13,6 @@ pub struct VmImageIndex {
 }
 
 impl VmImageIndex {
-    pub fn to_image_file_name(&self) -> String {
-        let arch = &self.arch;
-        let os = &self.os;
-        let ext = self.image.to_extension();
-        format!("{arch}_{os}.{ext}")
-    }
     pub fn to_hash_file_name(&self) -> String {
         let arch = &self.arch;
         let os = &self.os;
@@ -40,11 +34,6 @@ impl VmImageRef {
             VmImageRef::Qcow2 { url } => url,
         }
     }
-    fn to_extension(&self) -> &str {
-        match self {
-            VmImageRef::Qcow2 { url: _ } => "qcow2",
-        }
-    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -3,8 +3,10 @@
 //! The catalogue is a static `images.toml` (compiled in via `include_str!`)
 //! mapping `(arch, os)` pairs to a download URL and a checksum URL (SHA-256
 //! or SHA-512 SUMS file). [`get_image`] picks the row matching the requested
-//! [`Machine`], downloads both files into `cache_dir/vm/images/` if absent,
-//! verifies the hash, and returns a [`VmImage`] pointing at the cached file.
+//! [`Machine`], downloads the checksum file into `cache_dir/vm/images/` if
+//! absent, then fetches the image into the host's
+//! [artifact cache](lusid_http::ArtifactCache), pinned to the digest the
+//! checksum file lists, and returns a [`VmImage`] pointing at the cached file.
 
 use lusid_fs::{self as fs, FsError};
 use lusid_http::HttpError;
@@ -24,7 +26,6 @@ use crate::{
         hash::{VmImageHash, VmImageHashError},
         index::{VmImageIndex, VmImagesList},
     },
-    paths::Paths,
 };
 
 #[derive(Error, Debug)]
@@ -62,8 +63,7 @@ impl VmImage {
     // deliberate — the `_ => unimplemented!()` arm would fire if `images.toml`
     // ever lists a non-Linux `os:` value, which isn't a supported state. If
     // FreeBSD/etc. guests are ever added, this needs a real error path.
-    pub fn new(paths: &Paths, image_index: VmImageIndex) -> Self {
-        let image_path = paths.image_file(&image_index.to_image_file_name());
+    pub fn new(image_path: PathBuf, image_index: VmImageIndex) -> Self {
         let VmImageIndex {
             arch,
             os,
@@ -102,11 +102,11 @@ pub async fn get_image(ctx: &mut Context, machine: &Machine) -> Result<VmImage,
 
     info!("fetching...");
 
-    fetch_image(ctx, &image_index).await?;
+    let image_path = fetch_image(ctx, &image_index).await?;
 
     info!("fetched.");
 
-    let image = get_image_from_index(ctx, image_index);
+    let image = VmImage::new(image_path, image_index);
 
     Ok(image)
 }
@@ -121,15 +121,14 @@ async fn find_image_index_for_machine(
     Ok(image_index)
 }
 
-async fn fetch_image(ctx: &mut Context, image_index: &VmImageIndex) -> Result<(), VmImageError> {
-    let image_path = ctx.paths().image_file(&image_index.to_image_file_name());
-
+// Fetch the image into the artifact cache, pinned to the digest its sums
+// file lists, returning its path there.
+async fn fetch_image(
+    ctx: &mut Context,
+    image_index: &VmImageIndex,
+) -> Result<PathBuf, VmImageError> {
     fs::setup_directory_access(ctx.paths().images_dir()).await?;
 
-    ctx.http_client()
-        .download_file(image_index.image.to_url(), &image_path)
-        .await?;
-
     let hash_path = ctx.paths().image_file(&image_index.to_hash_file_name());
 
     ctx.http_client()
@@ -137,11 +136,12 @@ async fn fetch_image(ctx: &mut Context, image_index: &VmImageIndex) -> Result<()
         .await?;
 
     let hash = VmImageHash::new(&image_index.hash, &hash_path);
-    hash.validate(image_index, &image_path).await?;
+    let expected = hash.expected(image_index).await?;
 
-    Ok(())
-}
+    let image_path = ctx
+        .artifact_cache()
+        .fetch(image_index.image.to_url(), Some(hash.checksum(&expected)))
+        .await?;
 
-fn get_image_from_index(ctx: &mut Context, image_index: VmImageIndex) -> VmImage {
-    VmImage::new(ctx.paths(), image_index)
+    Ok(image_path)
 }
@@ -1,26 +1,27 @@
 mod paths;
+mod ready;
 mod setup;
 mod start;
 
 use self::paths::*;
+use self::ready::*;
 use self::setup::*;
 use self::start::*;
 
 use lusid_ctx::Context as BaseContext;
 use lusid_fs::{self as fs, FsError};
-use lusid_machine::Machine;
+use lusid_machine::{Machine, MachineVmDisk, MachineVmUsbDevice};
 use lusid_ssh::{SshKeypair, SshKeypairError};
 use lusid_system::{Arch, CpuCount, DiskSize, Linux, MemorySize};
 use nix::{
     sys::signal::{Signal, kill},
     unistd::Pid,
 };
+use russh::keys::PublicKey;
 use serde::{Deserialize, Serialize};
 use std::num::ParseIntError;
-use std::time::Duration;
 use std::{fmt::Display, net::Ipv4Addr, path::PathBuf, str::FromStr};
 use thiserror::Error;
-use tokio::time::sleep;
 
 use crate::{
     context::{Context, ContextError},
@@ -49,6 +50,23 @@ pub enum VmError {
     #[error("failed to load ssh keypair")]
     LoadSshKeypair(#[source] SshKeypairError),
 
+    #[error(
+        "instance has no pinned ssh host key (set up before host keys were pinned); remove {} to recreate it",
+        dir.display()
+    )]
+    NoSshHostKey { dir: PathBuf },
+
+    #[error("failed to parse pinned ssh host key")]
+    ParseSshHostKey(#[source] russh::keys::ssh_key::Error),
+
+    #[error("VM {instance_id} didn't become ready: {reason}\n\nrecent console output:\n{console}")]
+    NotReady {
+        instance_id: String,
+        #[source]
+        reason: VmReadyError,
+        console: String,
+    },
+
     #[error("failed to check whether instance dir exists")]
     DirExists(#[source] fs::FsError),
 
@@ -76,7 +94,7 @@ pub enum VmError {
 
 /// A configured (and usually running) VM instance. Serialized to
 /// `<instance_dir>/state.json` so that re-running with the same `instance_id`
-/// picks up the same overlay, keypair, and forwarded SSH port.
+/// picks up the same overlay, keypair, host key, and forwarded SSH port.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Vm {
     pub id: String,
@@ -92,6 +110,12 @@ pub struct Vm {
     /// setup time via [`get_free_tcp_port`](crate::utils::get_free_tcp_port)
     /// and persisted so reruns stay reachable at the same address.
     pub ssh_port: u16,
+    /// The guest's SSH host key (OpenSSH public key line), generated at setup
+    /// time and seeded into the guest by cloud-init. Connections verify the
+    /// guest against it. `None` only for instances set up before host keys
+    /// were pinned.
+    #[serde(default)]
+    pub ssh_host_key: Option<String>,
     pub memory_size: Option<MemorySize>,
     pub cpu_count: Option<CpuCount>,
     /// Virtual size of the overlay qcow2, applied at first-create time by
@@ -100,6 +124,17 @@ pub struct Vm {
     pub ports: Vec<VmPort>,
     pub graphics: Option<bool>,
     pub kvm: Option<bool>,
+    /// Extra devices and raw args from
+    /// [`MachineVmOptions`](lusid_machine::MachineVmOptions), validated and
+    /// merged into the QEMU command line at start.
+    #[serde(default)]
+    pub disks: Vec<MachineVmDisk>,
+    #[serde(default)]
+    pub usb: Vec<MachineVmUsbDevice>,
+    #[serde(default)]
+    pub bridge: Option<String>,
+    #[serde(default)]
+    pub qemu_args: Vec<String>,
 }
 
 impl Vm {
@@ -107,7 +142,9 @@ impl Vm {
     ///
     /// Idempotent: if `<instance_dir>/state.json` already exists it is loaded
     /// instead of rebuilt, and qemu is only spawned if no `qemu.pid` is
-    /// present. Blocks until `127.0.0.1:<ssh_port>` answers.
+    /// present. After spawning, blocks until the guest has finished booting
+    /// (cloud-init done, see `ready.rs`), failing with
+    /// [`VmError::NotReady`] after [`READY_TIMEOUT`].
     pub async fn run(ctx: &mut BaseContext, options: VmOptions<'_>) -> Result<Vm, VmError> {
         let mut ctx = Context::create(ctx)?;
 
@@ -132,14 +169,7 @@ impl Vm {
 
         if !instance.is_qemu_running().await? {
             instance.start(&mut ctx).await?;
-
-            loop {
-                if instance.is_ssh_open() {
-                    break;
-                }
-
-                sleep(Duration::from_millis(100)).await;
-            }
+            wait_ready(&instance).await?;
         }
 
         Ok(instance)
@@ -228,6 +258,18 @@ impl Vm {
             .await
             .map_err(VmError::LoadSshKeypair)
     }
+
+    /// The guest's pinned SSH host key, to pass as
+    /// [`SshConnectOptions::host_key`](lusid_ssh::SshConnectOptions::host_key).
+    pub fn ssh_host_key(&self) -> Result<PublicKey, VmError> {
+        let host_key = self
+            .ssh_host_key
+            .as_deref()
+            .ok_or_else(|| VmError::NoSshHostKey {
+                dir: self.dir.clone(),
+            })?;
+        PublicKey::from_openssh(host_key).map_err(VmError::ParseSshHostKey)
+    }
 }
 
 /// A host→guest TCP forward translated into a QEMU `hostfwd` rule. An omitted
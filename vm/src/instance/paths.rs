@@ -13,7 +13,9 @@
 //!   initrd.img              — initrd (optional; not every image ships one)
 //!   cloud-init-{meta,user}-data, cloud-init.iso — seed ISO for first boot
 //!   id_ed25519[.pub]        — SSH keypair (written by lusid_ssh::SshKeypair)
+//!   host-key/id_ed25519[.pub] — the guest's SSH host key, seeded by cloud-init
 //!   qemu.pid                — pid of the daemonized qemu process
+//!   console.log             — guest serial console, truncated on each start
 //!   qmp.sock                — QMP control socket (currently unused by lusid)
 //! ```
 //!
@@ -84,10 +86,18 @@ impl<'a> VmPaths<'a> {
         self.instance_dir.join("cloud-init.iso")
     }
 
+    pub fn ssh_host_key_dir(&self) -> PathBuf {
+        self.instance_dir.join("host-key")
+    }
+
     pub fn qemu_pid_path(&self) -> PathBuf {
         self.instance_dir.join("qemu.pid")
     }
 
+    pub fn console_log_path(&self) -> PathBuf {
+        self.instance_dir.join("console.log")
+    }
+
     pub fn qemu_qmp_socket_path(&self) -> PathBuf {
         self.instance_dir.join("qmp.sock")
     }
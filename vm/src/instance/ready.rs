@@ -0,0 +1,110 @@
+//! Wait for a freshly started [`Vm`] to finish booting.
+//!
+//! An open SSH port only means sshd is listening: cloud-init may still be
+//! installing packages or writing config, and an apply racing it can fail in
+//! confusing ways. So once the port answers, SSH in (verifying the pinned
+//! host key) and block on `cloud-init status --wait`, all within
+//! [`READY_TIMEOUT`].
+//!
+//! The guest's serial console is logged to `<instance_dir>/console.log` (see
+//! [`instance_start`](super::start)), so when the VM doesn't become ready the
+//! error carries its tail — usually where a failed boot explains itself.
+
+use lusid_fs as fs;
+use lusid_ssh::{Ssh, SshConnectOptions, SshError};
+use russh::keys::{PrivateKey, PublicKey};
+use std::{net::Ipv4Addr, sync::Arc, time::Duration};
+use thiserror::Error;
+use tokio::time::{sleep, timeout};
+
+use crate::instance::{Vm, VmError};
+
+/// How long a started VM gets to boot and finish cloud-init. Generous because
+/// the first boot installs packages, and without KVM everything is slow.
+pub const READY_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// How many trailing console lines to include in a [`VmError::NotReady`].
+const CONSOLE_TAIL_LINES: usize = 40;
+
+#[derive(Error, Debug)]
+pub enum VmReadyError {
+    #[error("timed out after {}s", .0.as_secs())]
+    Timeout(Duration),
+
+    #[error("failed to check cloud-init over ssh: {0}")]
+    Ssh(#[from] SshError),
+
+    #[error("cloud-init failed: {status}")]
+    CloudInit { status: String },
+}
+
+pub(super) async fn wait_ready(instance: &Vm) -> Result<(), VmError> {
+    let private_key = instance.ssh_keypair().await?.private_key;
+    let host_key = instance.ssh_host_key()?;
+
+    let reason = match timeout(READY_TIMEOUT, probe(instance, private_key, host_key)).await {
+        Ok(Ok(())) => {
+            tracing::info!(instance_id = %instance.id, "VM ready");
+            return Ok(());
+        }
+        Ok(Err(reason)) => reason,
+        Err(_) => VmReadyError::Timeout(READY_TIMEOUT),
+    };
+
+    Err(VmError::NotReady {
+        instance_id: instance.id.clone(),
+        console: console_tail(instance).await,
+        reason,
+    })
+}
+
+async fn probe(
+    instance: &Vm,
+    private_key: PrivateKey,
+    host_key: PublicKey,
+) -> Result<(), VmReadyError> {
+    while !instance.is_ssh_open() {
+        sleep(Duration::from_millis(100)).await;
+    }
+
+    let mut ssh = Ssh::connect(SshConnectOptions {
+        private_key,
+        host_key,
+        addrs: (Ipv4Addr::LOCALHOST, instance.ssh_port),
+        username: instance.user.clone(),
+        config: Arc::new(Default::default()),
+        timeout: READY_TIMEOUT,
+    })
+    .await?;
+    let output = ssh.output("cloud-init status --wait").await?;
+    let _ = ssh.disconnect().await;
+
+    let status = status_text(&output.stdout, &output.stderr);
+    match output.exit_code {
+        Some(0) => Ok(()),
+        // Exit code 2: finished, but with recoverable errors ("degraded").
+        Some(2) => {
+            tracing::warn!(%status, "cloud-init finished with recoverable errors");
+            Ok(())
+        }
+        _ => Err(VmReadyError::CloudInit { status }),
+    }
+}
+
+/// `cloud-init status` prints a line of dots while waiting; drop them.
+fn status_text(stdout: &[u8], stderr: &[u8]) -> String {
+    let stdout = String::from_utf8_lossy(stdout);
+    let stderr = String::from_utf8_lossy(stderr);
+    let text = format!("{}\n{}", stdout.trim_start_matches('.'), stderr);
+    text.trim().to_owned()
+}
+
+async fn console_tail(instance: &Vm) -> String {
+    let Ok(bytes) = fs::read_file_to_bytes(instance.paths().console_log_path()).await else {
+        return "(no console output)".to_owned();
+    };
+    let console = String::from_utf8_lossy(&bytes);
+    let lines: Vec<&str> = console.lines().collect();
+    let start = lines.len().saturating_sub(CONSOLE_TAIL_LINES);
+    lines[start..].join("\n")
+}
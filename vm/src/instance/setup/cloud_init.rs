@@ -1,5 +1,6 @@
 use lusid_cmd::{Command, CommandError};
 use lusid_fs::{self as fs, FsError};
+use lusid_ssh::{SshKeypair, SshKeypairError};
 use lusid_system::Hostname;
 use russh::keys::PublicKey;
 use serde::{Deserialize, Serialize};
@@ -18,6 +19,9 @@ pub enum CloudInitError {
     #[error(transparent)]
     SshKey(#[from] russh::keys::ssh_key::Error),
 
+    #[error(transparent)]
+    SshKeypair(#[from] SshKeypairError),
+
     #[error(transparent)]
     Command(#[from] CommandError),
 }
@@ -32,15 +36,27 @@ struct CloudInitMetaData {
 struct CloudInitUserData {
     hostname: String,
     ssh_authorized_keys: Vec<String>,
+    /// Host keys to install instead of generating them on first boot, so the
+    /// host can pin the guest's key before it has ever seen it.
+    ssh_keys: CloudInitSshKeys,
+    /// Don't generate any other host key types alongside ours.
+    ssh_genkeytypes: Vec<String>,
     packages: Vec<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CloudInitSshKeys {
+    ed25519_private: String,
+    ed25519_public: String,
+}
+
 pub(super) async fn setup_cloud_init(
     executables: &ExecutablePaths,
     paths: &VmPaths<'_>,
     instance_id: &str,
     hostname: &Hostname,
     ssh_public_key: &PublicKey,
+    ssh_host_keypair: &SshKeypair,
 ) -> Result<(), CloudInitError> {
     let meta_data_path = paths.cloud_init_meta_data_path();
     let user_data_path = paths.cloud_init_user_data_path();
@@ -62,6 +78,11 @@ pub(super) async fn setup_cloud_init(
         let user_data = CloudInitUserData {
             hostname: hostname.to_string(),
             ssh_authorized_keys: vec![ssh_public_key.to_openssh()?],
+            ssh_keys: CloudInitSshKeys {
+                ed25519_private: ssh_host_keypair.private_openssh()?,
+                ed25519_public: ssh_host_keypair.public_openssh()?,
+            },
+            ssh_genkeytypes: vec![],
             packages: vec!["openssh".to_owned()],
         };
         fs::write_file(
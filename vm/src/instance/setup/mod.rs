@@ -1,6 +1,6 @@
 //! One-time per-instance setup: download the guest image, build the overlay,
-//! extract the kernel, make UEFI vars writeable, mint SSH keys, seed
-//! cloud-init. Each sub-step is idempotent (skips work if the output file
+//! extract the kernel, make UEFI vars writeable, mint SSH keys (ours and
+//! the guest's host key), seed cloud-init. Each sub-step is idempotent (skips work if the output file
 //! already exists), so partial runs can be resumed by re-invoking.
 
 mod cloud_init;
@@ -82,6 +82,10 @@ pub async fn setup_instance(
         cpu_count,
         disk_size,
         graphics,
+        disks,
+        usb,
+        bridge,
+        qemu_args,
     } = machine.vm.clone().unwrap_or_default();
 
     let VmImage {
@@ -105,6 +109,9 @@ pub async fn setup_instance(
         setup_kernel(executables, &instance_paths, &source_image_path).await?;
 
     let ssh_keypair = SshKeypair::load_or_create(&instance_dir).await?;
+    // Minted here rather than on first boot, so it can be pinned in the
+    // instance state before the guest has ever run.
+    let ssh_host_keypair = SshKeypair::load_or_create(&instance_paths.ssh_host_key_dir()).await?;
     let ssh_port = get_free_tcp_port().ok_or(VmSetupError::NoOpenPortsAvailable)?;
 
     setup_cloud_init(
@@ -113,6 +120,7 @@ pub async fn setup_instance(
         instance_id,
         &machine.hostname,
         &ssh_keypair.public_key,
+        &ssh_host_keypair,
     )
     .await?;
 
@@ -125,6 +133,7 @@ pub async fn setup_instance(
         user,
         has_initrd,
         ssh_port,
+        ssh_host_key: Some(ssh_host_keypair.public_openssh()?),
         memory_size,
         cpu_count,
         disk_size,
@@ -134,5 +143,9 @@ pub async fn setup_instance(
         // on hosts without `/dev/kvm` access. `instance_start` currently
         // defaults this to `true`, which will fail on such hosts.
         kvm: None,
+        disks,
+        usb,
+        bridge,
+        qemu_args,
     })
 }
@@ -5,9 +5,15 @@
 //! Defaults when the [`Vm`]'s optional fields are `None`: 8 GiB memory, 2
 //! CPUs, graphics on, KVM on. The SSH forward is always prepended to the
 //! caller-supplied `ports` so it survives any reordering.
+//!
+//! The machine's extra devices (disks, USB passthrough, a bridged NIC) come
+//! after lusid's own, and its raw `qemu_args` last of all. They're checked
+//! first by [`validate_devices`], so a typo fails here with a clear error
+//! rather than as a qemu that died daemonizing.
 
 use lusid_system::{CpuCount, MemorySize};
 use std::net::Ipv4Addr;
+use std::path::PathBuf;
 use thiserror::Error;
 
 use crate::{
@@ -20,6 +26,80 @@ use crate::{
 pub enum VmStartError {
     #[error(transparent)]
     Qemu(#[from] QemuError),
+
+    #[error("vm disk not found: {}", path.display())]
+    DiskNotFound { path: PathBuf },
+
+    #[error("invalid vm bridge name {bridge:?}: {reason}")]
+    InvalidBridge {
+        bridge: String,
+        reason: &'static str,
+    },
+
+    #[error("qemu_args can't contain {arg}: {reason}")]
+    ReservedQemuArg { arg: String, reason: &'static str },
+}
+
+/// QEMU options lusid sets itself, which `qemu_args` would conflict with.
+const RESERVED_QEMU_ARGS: &[(&str, &str)] = &[
+    ("-daemonize", "lusid runs qemu daemonized"),
+    ("-pidfile", "lusid tracks qemu by its pid file"),
+    ("-qmp", "lusid sets up the QMP socket"),
+    ("-kernel", "lusid boots the image's kernel"),
+    ("-initrd", "lusid boots the image's initrd"),
+    ("-append", "lusid sets the kernel command line"),
+    ("-nographic", "use `graphics = false` instead"),
+    ("-m", "use `memory_size` instead"),
+    ("-smp", "use `cpu_count` instead"),
+];
+
+/// Check the machine's extra devices and raw args before handing them to
+/// qemu.
+fn validate_devices(instance: &Vm) -> Result<(), VmStartError> {
+    for disk in &instance.disks {
+        if !disk.path.is_file() {
+            return Err(VmStartError::DiskNotFound {
+                path: disk.path.clone(),
+            });
+        }
+    }
+
+    if let Some(bridge) = &instance.bridge {
+        // Linux interface names: at most 15 bytes (IFNAMSIZ - 1).
+        let reason = if bridge.is_empty() || bridge.len() > 15 {
+            Some("must be 1 to 15 characters")
+        } else if !bridge
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        {
+            Some("may only contain letters, digits, '-', '_' and '.'")
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            return Err(VmStartError::InvalidBridge {
+                bridge: bridge.clone(),
+                reason,
+            });
+        }
+    }
+
+    for arg in &instance.qemu_args {
+        // QEMU accepts `--opt` as well as `-opt`.
+        let option = arg.strip_prefix('-').filter(|rest| rest.starts_with('-'));
+        let option = option.unwrap_or(arg.as_str());
+        if let Some(&(_, reason)) = RESERVED_QEMU_ARGS
+            .iter()
+            .find(|(reserved, _)| *reserved == option)
+        {
+            return Err(VmStartError::ReservedQemuArg {
+                arg: arg.clone(),
+                reason,
+            });
+        }
+    }
+
+    Ok(())
 }
 
 pub(super) async fn instance_start(
@@ -35,15 +115,22 @@ pub(super) async fn instance_start(
         user: _,
         has_initrd,
         ssh_port,
+        ssh_host_key: _,
         memory_size,
         cpu_count,
         disk_size: _,
         ports,
         graphics,
         kvm,
+        disks,
+        usb,
+        bridge,
+        qemu_args,
     } = instance;
     let paths = instance.paths();
 
+    validate_devices(instance)?;
+
     let other_ports = ports.clone();
     let mut ports = vec![VmPort {
         host_ip: Some(Ipv4Addr::LOCALHOST),
@@ -69,9 +156,18 @@ pub(super) async fn instance_start(
         .memory(memory_size_in_gb)
         .plash_drives(paths.ovmf_code_system_path(), &paths.ovmf_vars_path());
 
+    // The serial console goes last so it's `/dev/console`, which is where
+    // cloud-init reports; it's logged to `console.log` for readiness errors.
+    let serial_console = match arch {
+        lusid_system::Arch::X86_64 => "ttyS0",
+        lusid_system::Arch::Aarch64 => "ttyAMA0",
+    };
     qemu.kernel(
         &paths.kernel_path(),
-        Some(&format!("rw root={}", kernel_root)),
+        Some(&format!(
+            "rw root={} console=tty0 console={}",
+            kernel_root, serial_console
+        )),
     );
     if *has_initrd {
         qemu.initrd(&paths.initrd_path());
@@ -80,6 +176,7 @@ pub(super) async fn instance_start(
     qemu.qmp_socket(&paths.qemu_qmp_socket_path())
         .kvm(kvm)
         .pid_file(paths.qemu_pid_path())
+        .serial_log(&paths.console_log_path())
         .graphics(graphics)
         .ports(&ports);
 
@@ -87,6 +184,22 @@ pub(super) async fn instance_start(
     qemu.virtio_drive("overlay-disk", "qcow2", &paths.overlay_image_path())
         .virtio_drive("cloud-init", "raw", &paths.cloud_init_image_path());
 
+    // The machine's extra devices, then its raw args.
+    for (index, disk) in disks.iter().enumerate() {
+        let node_name = format!("extra-disk-{index}");
+        let format = disk.format().as_str();
+        if disk.readonly {
+            qemu.readonly_virtio_drive(&node_name, format, &disk.path);
+        } else {
+            qemu.virtio_drive(&node_name, format, &disk.path);
+        }
+    }
+    qemu.usb_host_devices(usb);
+    if let Some(bridge) = bridge {
+        qemu.bridge(bridge);
+    }
+    qemu.args(qemu_args);
+
     tracing::debug!(cmd = ?qemu, "spawning QEMU");
 
     let _child = qemu.spawn().await?;
@@ -3,10 +3,10 @@ use std::path::{Path, PathBuf};
 use thiserror::Error;
 use which::which_global;
 
-/// Base paths specialised for this crate. Cacheable downloads (guest images +
-/// sums) live under `cache_dir/vm/images`; per-VM mutable state (overlays,
-/// OVMF vars, kernel, cloud-init, keypair, pid) lives under
-/// `data_dir/vm/instances/<id>`.
+/// Base paths specialised for this crate. Guest image sums live under
+/// `cache_dir/vm/images`, and the images themselves in the host's artifact
+/// cache; per-VM mutable state (overlays, OVMF vars, kernel, cloud-init,
+/// keypair, pid) lives under `data_dir/vm/instances/<id>`.
 #[derive(Debug, Clone)]
 pub struct Paths {
     base: BasePaths,
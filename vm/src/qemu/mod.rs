@@ -3,6 +3,8 @@ use std::{ffi::OsStr, net::Ipv4Addr, path::Path};
 use thiserror::Error;
 use tokio::process::{Child, Command};
 
+use lusid_machine::MachineVmUsbDevice;
+
 use crate::instance::VmPort;
 
 #[derive(Error, Debug)]
@@ -93,10 +95,25 @@ impl Qemu {
 
     /// Add a virtio drive with explicit node name, format and file path.
     pub fn virtio_drive(&mut self, node_name: &str, format: &str, file: &Path) -> &mut Self {
-        let file = file.display();
+        self.drive(node_name, format, file, "")
+    }
+
+    /// Add a read-only virtio drive.
+    pub fn readonly_virtio_drive(
+        &mut self,
+        node_name: &str,
+        format: &str,
+        file: &Path,
+    ) -> &mut Self {
+        self.drive(node_name, format, file, ",readonly=on")
+    }
+
+    fn drive(&mut self, node_name: &str, format: &str, file: &Path, extra: &str) -> &mut Self {
+        // Commas separate `-drive` options, so one in the path is doubled.
+        let file = file.display().to_string().replace(',', ",,");
         self.command.args([
             "-drive",
-            &format!("if=virtio,node-name={node_name},format={format},file={file}"),
+            &format!("if=virtio,node-name={node_name},format={format},file={file}{extra}"),
         ]);
 
         self
@@ -145,6 +162,47 @@ impl Qemu {
         self
     }
 
+    /// Log the first serial port to a file, truncating it.
+    pub fn serial_log(&mut self, path: &Path) -> &mut Self {
+        let path = path.display();
+        self.command.args(["-serial", &format!("file:{path}")]);
+        self
+    }
+
+    /// Pass host USB devices through, on an xHCI controller.
+    pub fn usb_host_devices(&mut self, devices: &[MachineVmUsbDevice]) -> &mut Self {
+        if devices.is_empty() {
+            return self;
+        }
+        self.command.args(["-device", "qemu-xhci,id=xhci"]);
+        for MachineVmUsbDevice {
+            vendor_id,
+            product_id,
+        } in devices
+        {
+            self.command.args([
+                "-device",
+                &format!(
+                    "usb-host,bus=xhci.0,vendorid={vendor_id:#06x},productid={product_id:#06x}"
+                ),
+            ]);
+        }
+        self
+    }
+
+    /// Add a virtio NIC on a host bridge, via `qemu-bridge-helper`.
+    pub fn bridge(&mut self, bridge: &str) -> &mut Self {
+        self.command
+            .args(["-nic", &format!("bridge,br={bridge},model=virtio")]);
+        self
+    }
+
+    /// Append raw arguments.
+    pub fn args(&mut self, args: &[String]) -> &mut Self {
+        self.command.args(args);
+        self
+    }
+
     pub fn pid_file<P: AsRef<Path>>(&mut self, path: P) -> &mut Self {
         self.command.arg("-pidfile").arg(path.as_ref());
         self